@@ -56,6 +56,30 @@ enum Command {
         /// Show test output even for passing tests
         #[arg(long)]
         verbose: bool,
+        /// Re-run every test, discarding cached results
+        #[arg(long)]
+        force: bool,
+        /// Number of generated inputs each property is run on
+        #[arg(long, default_value = "100")]
+        cases: usize,
+        /// Seed for generating property inputs
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Report format: text, junit, tap, json, or events (JSON lines while tests run)
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Write the report to this file instead of standard output
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Record which code the tests evaluate, and write lcov, JSON and HTML reports to coverage/
+        #[arg(long)]
+        coverage: bool,
+        /// Number of tests to run at the same time
+        #[arg(short = 'j', long, default_value = "1")]
+        jobs: usize,
+        /// Rewrite snapshots that no longer match with what the tests produce
+        #[arg(long)]
+        update_snapshots: bool,
     },
 
     /// Run a benchmark
@@ -73,6 +97,44 @@ enum Command {
         wasm: bool,
     },
 
+    /// Build a definition and only what it depends on
+    Build {
+        /// Name of the entry definition
+        #[arg(required_unless_present = "workspace")]
+        entry: Option<String>,
+        /// Compilation target
+        #[arg(long, default_value = "wasm")]
+        target: String,
+        /// VBin codebase to build from
+        #[arg(long, default_value = "codebase.vibes")]
+        codebase: PathBuf,
+        /// Output file (defaults to <entry>.wasm)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Emit a component exporting the entry instead of a core module
+        #[arg(long)]
+        component: bool,
+        /// Build the `main` of every member of the workspace containing the
+        /// current directory, into the output directory (default target/wasm)
+        #[arg(long, conflicts_with_all = ["entry", "component"])]
+        workspace: bool,
+    },
+
+    /// Run programs on every backend and report where their results differ
+    Conformance {
+        /// Files or directories of .vibe programs (defaults to examples, vibe/lib and vibe/tests)
+        paths: Vec<PathBuf>,
+        /// Number of random well-typed programs to generate
+        #[arg(long, default_value = "200")]
+        programs: usize,
+        /// Seed for generating programs
+        #[arg(long, default_value = "0")]
+        seed: u64,
+        /// List the files that were skipped and why
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
     /// Generate WebAssembly Component from XS module
     Component {
         #[command(subcommand)]
@@ -84,6 +146,12 @@ enum Command {
         #[command(subcommand)]
         command: cli::CodebaseCommand,
     },
+
+    /// Manage package dependencies
+    Package {
+        #[command(subcommand)]
+        command: vibe_cli::package_commands::PackageCommand,
+    },
 }
 
 fn main() -> Result<()> {
@@ -108,10 +176,13 @@ fn main() -> Result<()> {
                 Command::Parse { file } => cli::Command::Parse { file },
                 Command::Check { path, verbose } => cli::Command::Check { path, verbose },
                 Command::Exec { file } => cli::Command::Run { file },
-                Command::Test { path, all, verbose } => cli::Command::Test { path, all, verbose },
+                Command::Test { path, all, verbose, force, cases, seed, format, output, coverage, jobs, update_snapshots } => cli::Command::Test { path, all, verbose, force, cases, seed, format, output, coverage, jobs, update_snapshots },
                 Command::Bench { file, iterations, incremental, wasm } => cli::Command::Bench { file, iterations, incremental, wasm },
+                Command::Build { entry, target, codebase, output, component, workspace } => cli::Command::Build { entry, target, codebase, output, component, workspace },
+                Command::Conformance { paths, programs, seed, verbose } => cli::Command::Conformance { paths, programs, seed, verbose },
                 Command::Component { command } => cli::Command::Component { command },
                 Command::Codebase { command } => cli::Command::Codebase { command },
+                Command::Package { command } => cli::Command::Package { command },
                 _ => unreachable!(),
             };
 
@@ -6,11 +6,13 @@ use colored::*;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::test_runner::TestSuite;
-use vibe_compiler::type_check;
-use vibe_language::parser::parse;
+use crate::test_runner::{load_test_cache, TestSuite};
+use vibe_compiler::{type_check, type_check_all, TypeChecker, TypeDiagnostic};
+use vibe_language::parser::experimental::StructuredParseError;
+use vibe_language::parser::{parse, parse_with_recovery};
 use vibe_language::pretty_print::pretty_print;
 use vibe_language::{Type, Value};
+use vibe_codebase::package::modules::PackageModules;
 use vibe_codebase::vbin::VBinStorage;
 use vibe_codebase::{Codebase, Hash};
 
@@ -29,7 +31,7 @@ pub enum Command {
         /// The XS file to parse
         file: PathBuf,
     },
-    /// Type check a file or directory
+    /// Type check a file, a directory, or every changed member of a workspace
     Check {
         /// The XS file or directory to type check
         path: PathBuf,
@@ -42,7 +44,7 @@ pub enum Command {
         /// The XS file to run
         file: PathBuf,
     },
-    /// Run tests in a file or directory
+    /// Run tests in a file, a directory, or every member of a workspace
     Test {
         /// The XS file or directory containing tests (defaults to current directory)
         #[arg(default_value = ".")]
@@ -53,6 +55,30 @@ pub enum Command {
         /// Show test output even for passing tests
         #[arg(long)]
         verbose: bool,
+        /// Re-run every test, discarding cached results
+        #[arg(long)]
+        force: bool,
+        /// Number of generated inputs each property is run on
+        #[arg(long, default_value = "100")]
+        cases: usize,
+        /// Seed for generating property inputs
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Report format: text, junit, tap, json, or events (JSON lines while tests run)
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Write the report to this file instead of standard output
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Record which code the tests evaluate, and write lcov, JSON and HTML reports to coverage/
+        #[arg(long)]
+        coverage: bool,
+        /// Number of tests to run at the same time
+        #[arg(short = 'j', long, default_value = "1")]
+        jobs: usize,
+        /// Rewrite snapshots that no longer match with what the tests produce
+        #[arg(long)]
+        update_snapshots: bool,
     },
     /// Run a benchmark
     Bench {
@@ -68,6 +94,42 @@ pub enum Command {
         #[arg(long)]
         wasm: bool,
     },
+    /// Build a definition and only what it depends on
+    Build {
+        /// Name of the entry definition
+        #[arg(required_unless_present = "workspace")]
+        entry: Option<String>,
+        /// Compilation target
+        #[arg(long, default_value = "wasm")]
+        target: String,
+        /// VBin codebase to build from
+        #[arg(long, default_value = "codebase.vibes")]
+        codebase: PathBuf,
+        /// Output file (defaults to <entry>.wasm)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Emit a component exporting the entry instead of a core module
+        #[arg(long)]
+        component: bool,
+        /// Build the `main` of every member of the workspace containing the
+        /// current directory, into the output directory (default target/wasm)
+        #[arg(long, conflicts_with_all = ["entry", "component"])]
+        workspace: bool,
+    },
+    /// Run programs on every backend and report where their results differ
+    Conformance {
+        /// Files or directories of .vibe programs (defaults to examples, vibe/lib and vibe/tests)
+        paths: Vec<PathBuf>,
+        /// Number of random well-typed programs to generate
+        #[arg(long, default_value = "200")]
+        programs: usize,
+        /// Seed for generating programs
+        #[arg(long, default_value = "0")]
+        seed: u64,
+        /// List the files that were skipped and why
+        #[arg(short, long)]
+        verbose: bool,
+    },
     /// Generate WebAssembly Component from XS module
     Component {
         #[command(subcommand)]
@@ -93,6 +155,12 @@ pub enum Command {
         #[arg(long)]
         debug: bool,
     },
+    /// Start Debug Adapter Protocol server
+    DebugAdapter {
+        /// Port to listen on (default: stdio)
+        #[arg(long)]
+        port: Option<u16>,
+    },
     /// Start Model Context Protocol server
     Mcp {
         /// Port to listen on (default: 3000)
@@ -132,7 +200,23 @@ pub enum ComponentCommand {
     Run {
         /// WASM component file
         input: PathBuf,
-        /// Arguments to pass to the component
+        /// Exported function to call instead of `wasi:cli/run` or `run`
+        #[arg(long)]
+        invoke: Option<String>,
+        /// Preopen a directory read-only
+        #[arg(long = "allow-read", value_name = "DIR")]
+        allow_read: Vec<PathBuf>,
+        /// Preopen a directory read-write
+        #[arg(long = "allow-write", value_name = "DIR")]
+        allow_write: Vec<PathBuf>,
+        /// Pass an environment variable through to the component
+        #[arg(long = "allow-env", value_name = "VAR")]
+        allow_env: Vec<String>,
+        /// Pass the whole environment through to the component
+        #[arg(long)]
+        allow_all_env: bool,
+        /// Arguments to pass to the component, after `--`
+        #[arg(last = true)]
         args: Vec<String>,
     },
 }
@@ -263,13 +347,18 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
             let source = fs::read_to_string(&file)
                 .with_context(|| format!("Failed to read file: {}", file.display()))?;
 
-            match parse(&source) {
-                Ok(expr) => {
+            let parsed = parse_with_recovery(&source);
+            if parsed.has_errors() {
+                eprintln!("{}: {}", "Parse error".red(), format_parse_errors(&parsed.errors));
+                std::process::exit(1);
+            }
+            match parsed.into_expr() {
+                Some(expr) => {
                     println!("{}", "Parse successful!".green());
                     println!("{expr:#?}");
                 }
-                Err(e) => {
-                    eprintln!("{}: {}", "Parse error".red(), e);
+                None => {
+                    eprintln!("{}: No expressions found", "Parse error".red());
                     std::process::exit(1);
                 }
             }
@@ -278,6 +367,21 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
         Command::Check { path, verbose } => {
             use walkdir::WalkDir;
 
+            if let Some(workspace) = crate::workspace_commands::workspace_at(&path)? {
+                println!("{} {} members", "Checking workspace:".cyan().bold(), workspace.members.len());
+                let report = crate::workspace_commands::check_workspace(&workspace, verbose)?;
+                println!(
+                    "Checked {} members, {} up to date, {} failed",
+                    report.checked.len(),
+                    report.up_to_date.len(),
+                    report.failed.len()
+                );
+                if !report.failed.is_empty() {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+
             let mut checked_files = 0;
             let mut errors = 0;
 
@@ -371,18 +475,25 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
             // Check file extension
             let extension = file.extension().and_then(|s| s.to_str()).unwrap_or("");
             
+            // Packages locked for the file's project are available as modules
+            let packages = crate::package_commands::project_modules(&file)?;
+
             // Parse and type check to get effects
             match parse(&source) {
                 Ok(expr) => {
                     // Type check
-                    match type_check(&expr) {
+                    let mut type_env = packages.as_ref().map(PackageModules::type_env).unwrap_or_default();
+                    match TypeChecker::new().check_spanned(&expr, &mut type_env).map_err(|d| d.to_xs_error()) {
                         Ok(_ty) => {
                             // Run without permission checks
                             use vibe_runtime::Interpreter;
                             let mut interpreter = Interpreter::new();
 
-                            // Create environment with builtins
-                            let env = Interpreter::create_initial_env();
+                            // Create environment with builtins and package exports
+                            let env = match &packages {
+                                Some(packages) => packages.environment(&Interpreter::create_initial_env()),
+                                None => Interpreter::create_initial_env(),
+                            };
                             
                             // For .vibe files, look for and execute main function
                             // For .vsh files (or no extension), execute top-level expression
@@ -453,10 +564,96 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
             path,
             all: _all,
             verbose,
+            force,
+            cases,
+            seed,
+            format,
+            output,
+            coverage,
+            jobs,
+            update_snapshots,
         } => {
+            use crate::test_runner::report::{JsonEventWriter, ReportFormat};
             use walkdir::WalkDir;
-            
-            let mut suite = TestSuite::new(verbose);
+
+            let format: ReportFormat = format.parse()?;
+            if format == ReportFormat::Text && output.is_some() {
+                anyhow::bail!("--output needs a report format other than text");
+            }
+            let defaults = vibe_runtime::property::PropertyConfig::default();
+            let properties = vibe_runtime::property::PropertyConfig {
+                cases,
+                seed: seed.unwrap_or(defaults.seed),
+                ..defaults
+            };
+
+            let workspace = crate::workspace_commands::workspace_at(&path)?;
+            // Results are cached next to the tests
+            let cache_dir = match &workspace {
+                Some(workspace) => workspace.root.clone(),
+                None if path.is_file() => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+                None => path.clone(),
+            };
+            // A report on standard output replaces the text
+            let text_output = format == ReportFormat::Text || output.is_some();
+            let mut suite = TestSuite::new(verbose)
+                .with_cache(load_test_cache(&cache_dir, force))
+                .with_property_config(properties)
+                .with_text_output(text_output)
+                .with_jobs(jobs)
+                .with_snapshot_updates(update_snapshots);
+            if coverage {
+                suite = suite.with_coverage();
+            }
+            if format == ReportFormat::Events {
+                let writer: Box<dyn std::io::Write + Send> = match &output {
+                    Some(file) => Box::new(fs::File::create(file).with_context(|| {
+                        format!("Failed to create {}", file.display())
+                    })?),
+                    None => Box::new(std::io::stdout()),
+                };
+                suite = suite.with_listener(Box::new(JsonEventWriter::new(writer)));
+            }
+            let write_report = |summary: &crate::test_runner::TestSummary| -> Result<()> {
+                let Some(report) = format.render(summary) else {
+                    return Ok(());
+                };
+                match &output {
+                    Some(file) => fs::write(file, report)
+                        .with_context(|| format!("Failed to write {}", file.display())),
+                    None => {
+                        print!("{report}");
+                        Ok(())
+                    }
+                }
+            };
+
+            // Coverage is written next to the test cache
+            let write_coverage = |suite: &TestSuite| -> Result<()> {
+                let Some(coverage) = suite.coverage() else {
+                    return Ok(());
+                };
+                let report = coverage.report()?;
+                let dir = cache_dir.join("coverage");
+                report.write(&dir)?;
+                if text_output {
+                    println!();
+                    print!("{}", report.render());
+                    println!("Coverage report written to {}", dir.display());
+                }
+                Ok(())
+            };
+
+            if let Some(workspace) = workspace {
+                let summary = crate::workspace_commands::test_workspace(&workspace, &mut suite)?;
+                write_report(&summary)?;
+                write_coverage(&suite)?;
+                if summary.failed > 0 {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+
             let mut total_files = 0;
             let mut error_files = 0;
             
@@ -489,7 +686,7 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
                             Ok(tests_found) => {
                                 if tests_found > 0 {
                                     total_files += 1;
-                                    if verbose {
+                                    if verbose && text_output {
                                         println!("Found {} tests in {}", tests_found, path.display());
                                     }
                                 }
@@ -506,27 +703,14 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
                 std::process::exit(1);
             }
             
-            // Run all collected tests
-            if suite.total_tests() == 0 {
-                // Print the summary even when no tests are found
-                use colored::Colorize;
-                println!("{}", "Running tests...".bright_blue().bold());
-                println!();
-                println!("{}", "Test Summary".bright_blue().bold());
-                println!("{}", "============".bright_blue());
-                println!("Total: 0 | Passed: {} | Failed: {} | Cached: {}",
-                    "0".to_string().green(),
-                    "0".to_string().red(),
-                    "0".to_string().cyan()
-                );
-                println!("Duration: 0.00s");
-                println!();
-                println!("{}", "All tests passed!".green().bold());
-                return Ok(());
+            // Run all collected tests; the summary is printed even when no
+            // tests are found
+            if suite.total_tests() > 0 && text_output {
+                println!("\nRunning {} tests from {} files...", suite.total_tests(), total_files);
             }
-            
-            println!("\nRunning {} tests from {} files...", suite.total_tests(), total_files);
             let summary = suite.run_all();
+            write_report(&summary)?;
+            write_coverage(&suite)?;
             
             if error_files > 0 {
                 eprintln!("\n{} {} files had errors", "Warning:".yellow(), error_files);
@@ -575,6 +759,37 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
             }
         }
 
+        Command::Build {
+            entry,
+            target,
+            codebase,
+            output,
+            component,
+            workspace,
+        } => {
+            if workspace {
+                let workspace = vibe_codebase::package::workspace::Workspace::find(&std::env::current_dir()?)?
+                    .ok_or_else(|| anyhow::anyhow!("Not inside a workspace"))?;
+                let output = output.unwrap_or_else(|| workspace.root.join("target").join("wasm"));
+                let built = crate::workspace_commands::build_workspace(&workspace, &output)?;
+                println!("{} built {} members", "Success:".green(), built.len());
+            } else {
+                let entry = entry.expect("clap requires an entry without --workspace");
+                crate::wasm_build::run_build(&entry, &target, &codebase, output, component)?;
+            }
+        }
+
+        Command::Conformance {
+            paths,
+            programs,
+            seed,
+            verbose,
+        } => {
+            if !crate::conformance::run_conformance(paths, programs, seed, verbose)? {
+                std::process::exit(1);
+            }
+        }
+
         Command::Component { command } => {
             crate::component_commands::handle_component_command(command)?;
         }
@@ -595,6 +810,10 @@ pub fn run_cli_with_args(args: Args) -> Result<()> {
             handle_lsp_command(port, debug)?;
         }
 
+        Command::DebugAdapter { port } => {
+            crate::dap::run_debug_adapter(port)?;
+        }
+
         Command::Mcp { port, debug } => {
             handle_mcp_command(port, debug)?;
         }
@@ -639,13 +858,69 @@ pub fn format_value(val: &Value) -> String {
     }
 }
 
-fn check_file(path: &Path, verbose: bool) -> Result<Type> {
+/// Render every syntax error as `line:column: message`, one per line
+fn format_parse_errors(errors: &[StructuredParseError]) -> String {
+    let plural = if errors.len() == 1 { "" } else { "s" };
+    let mut output = format!("{} syntax error{}", errors.len(), plural);
+    for error in errors {
+        output.push_str(&format!(
+            "\n  {}:{}: {}",
+            error.location.line, error.location.column, error.message
+        ));
+    }
+    output
+}
+
+/// 1-based line and column of a character offset in `source`
+pub(crate) fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for ch in source.chars().take(offset) {
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// Render every type error as `line:column: message` followed by its notes
+fn format_type_errors(source: &str, errors: &[TypeDiagnostic]) -> String {
+    let plural = if errors.len() == 1 { "" } else { "s" };
+    let mut output = format!("{} type error{}", errors.len(), plural);
+    for error in errors {
+        let (line, column) = line_column(source, error.span.start);
+        output.push_str(&format!("\n  {}:{}: {}", line, column, error.message));
+        for (span, note) in error.notes() {
+            let (line, column) = line_column(source, span.start);
+            output.push_str(&format!("\n    {}:{}: note: {}", line, column, note));
+        }
+    }
+    output
+}
+
+pub(crate) fn check_file(path: &Path, verbose: bool) -> Result<Type> {
     let source = fs::read_to_string(path)
         .with_context(|| format!("Failed to read file: {}", path.display()))?;
 
-    let expr = parse(&source).map_err(|e| anyhow::anyhow!("Parse error: {}", e))?;
-
-    let ty = type_check(&expr).map_err(|e| anyhow::anyhow!("Type error: {}", e))?;
+    let parsed = parse_with_recovery(&source);
+    if parsed.has_errors() {
+        anyhow::bail!("Parse error: {}", format_parse_errors(&parsed.errors));
+    }
+    let expr = parsed
+        .into_expr()
+        .ok_or_else(|| anyhow::anyhow!("Parse error: No expressions found"))?;
+
+    let ty = match crate::package_commands::project_modules(path)? {
+        Some(packages) => {
+            let (ty, errors) = TypeChecker::new().check_all(&expr, &mut packages.type_env());
+            if errors.is_empty() { Ok(ty) } else { Err(errors) }
+        }
+        None => type_check_all(&expr),
+    }
+    .map_err(|errors| anyhow::anyhow!("Type error: {}", format_type_errors(&source, &errors)))?;
 
     if verbose {
         println!("  Type: {}", format_type(&ty));
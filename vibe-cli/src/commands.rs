@@ -15,6 +15,7 @@ pub enum Command {
     Edit(String),        // edit <name|hash> - 定義を編集
     Update,              // update - 変更をコミット
     Undo,                // undo - 最後の変更を取り消し
+    Redo,                // redo - 取り消した変更をやり直し
 
     // 検索・参照
     Find(String),         // find <pattern> - パターンで検索
@@ -38,9 +39,13 @@ pub enum Command {
     History(Option<usize>), // history [n] - 評価履歴
     Log(Option<usize>),     // log [n] - コミット履歴
 
+    // モジュール出力
+    Export(String, Vec<String>), // export <file> [names...] - 定義と依存をモジュールに書き出す
+
     // デバッグ
-    Debug(String), // debug <expr> - デバッグ情報付きで評価
-    Trace(String), // trace <expr> - トレース付きで評価
+    Debug(String),         // debug <expr> - デバッグ情報付きで評価
+    Trace(String),         // trace <expr> - トレース付きで評価
+    Break(Option<String>), // break [name] - ブレークポイントの切り替え/一覧
 
     // LSP相当機能
     References(String), // references <name> - 参照を検索
@@ -68,8 +73,8 @@ impl Command {
 
         // Check for pipeline syntax
         if input.contains('|') {
-            let commands: Vec<String> = input
-                .split('|')
+            let commands: Vec<String> = split_pipeline(input)
+                .into_iter()
                 .map(|s| s.trim().to_string())
                 .filter(|s| !s.is_empty())
                 .collect();
@@ -126,6 +131,7 @@ impl Command {
 
             "update" => Ok(Command::Update),
             "undo" => Ok(Command::Undo),
+            "redo" => Ok(Command::Redo),
 
             "find" => {
                 if args.is_empty() {
@@ -197,6 +203,14 @@ impl Command {
                 Ok(Command::Log(limit))
             }
 
+            "export" | "promote" => {
+                if args.is_empty() {
+                    anyhow::bail!("export requires a file name")
+                }
+                let names = args[1..].iter().map(|s| s.to_string()).collect();
+                Ok(Command::Export(args[0].to_string(), names))
+            }
+
             "debug" => {
                 if args.is_empty() {
                     anyhow::bail!("debug requires an expression")
@@ -211,6 +225,8 @@ impl Command {
                 Ok(Command::Trace(args.join(" ")))
             }
 
+            "break" | "b" => Ok(Command::Break(args.first().map(|s| s.to_string()))),
+
             "references" | "refs" => {
                 if args.is_empty() {
                     anyhow::bail!("references requires a name")
@@ -273,6 +289,7 @@ impl fmt::Display for Command {
             Command::Edit(name) => write!(f, "edit {name}"),
             Command::Update => write!(f, "update"),
             Command::Undo => write!(f, "undo"),
+            Command::Redo => write!(f, "redo"),
             Command::Find(pattern) => write!(f, "find {pattern}"),
             Command::Search(query) => write!(f, "search {query}"),
             Command::Ls(None) => write!(f, "ls"),
@@ -289,8 +306,12 @@ impl fmt::Display for Command {
             Command::History(Some(n)) => write!(f, "history {n}"),
             Command::Log(None) => write!(f, "log"),
             Command::Log(Some(n)) => write!(f, "log {n}"),
+            Command::Export(path, names) if names.is_empty() => write!(f, "export {path}"),
+            Command::Export(path, names) => write!(f, "export {} {}", path, names.join(" ")),
             Command::Debug(expr) => write!(f, "debug {expr}"),
             Command::Trace(expr) => write!(f, "trace {expr}"),
+            Command::Break(None) => write!(f, "break"),
+            Command::Break(Some(name)) => write!(f, "break {name}"),
             Command::References(name) => write!(f, "references {name}"),
             Command::Definition(name) => write!(f, "definition {name}"),
             Command::Hover(expr) => write!(f, "hover {expr}"),
@@ -304,6 +325,34 @@ impl fmt::Display for Command {
     }
 }
 
+/// Split a pipeline at `|`, leaving `|>`, `||` and bars inside blocks or
+/// strings alone
+fn split_pipeline(input: &str) -> Vec<&str> {
+    let mut stages = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut start = 0;
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => in_string = !in_string,
+            '{' | '(' | '[' if !in_string => depth += 1,
+            '}' | ')' | ']' if !in_string => depth = depth.saturating_sub(1),
+            '|' if !in_string && depth == 0 => {
+                if let Some((_, '>' | '|')) = chars.peek() {
+                    chars.next();
+                } else {
+                    stages.push(&input[start..i]);
+                    start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    stages.push(&input[start..]);
+    stages
+}
+
 pub fn print_ucm_help() {
     println!("{}", "XS Shell - UCM-style Commands".bold().cyan());
     println!();
@@ -325,9 +374,10 @@ pub fn print_ucm_help() {
     println!("{}", "Definition Management:".bold());
     println!("  add [definition]     Add a definition to the codebase");
     println!("  view <name|hash>     View a definition");
-    println!("  edit <name|hash>     Edit a definition");
-    println!("  update               Commit changes to codebase");
+    println!("  edit <name|hash>     Open a definition in scratch.vibe ($EDITOR)");
+    println!("  update               Type check scratch.vibe and commit its definitions");
     println!("  undo                 Undo the last change");
+    println!("  redo                 Redo the last undone change");
     println!();
 
     println!("{}", "Search and Navigation:".bold());
@@ -340,10 +390,16 @@ pub fn print_ucm_help() {
 
     println!("{}", "Pipeline Processing:".bold());
     println!("  cmd | filter field value   Filter by field value");
+    println!("  cmd | where field > value  Compare a field by its type");
+    println!("  cmd | where {{fn r -> ..}}   Keep rows the function is true for");
+    println!("  cmd | each {{fn r -> ..}}    Apply a function to every row");
+    println!("  cmd | reduce [--fold v] {{fn acc r -> ..}}  Fold the rows");
     println!("  cmd | select fields...     Select specific fields");
     println!("  cmd | sort field [desc]    Sort by field");
     println!("  cmd | take n               Take first n items");
     println!("  cmd | group by field       Group by field");
+    println!("  from json|csv|toml [file]  Read data from a file or piped text");
+    println!("  cmd | to json|csv|toml     Render the data as text");
     println!("  cmd | count                Count items");
     println!();
 
@@ -367,9 +423,15 @@ pub fn print_ucm_help() {
     println!("  log [n]              Show commit history");
     println!();
 
+    println!("{}", "Modules:".bold());
+    println!("  export <file> [names]  Write definitions and their dependencies to a module");
+    println!("                         (defaults to this session's definitions; alias: promote)");
+    println!();
+
     println!("{}", "Debug:".bold());
-    println!("  debug <expr>         Evaluate with debug info");
-    println!("  trace <expr>         Evaluate with execution trace");
+    println!("  debug <expr>         Step through an evaluation");
+    println!("  trace <expr>         Evaluate and print the call tree");
+    println!("  break [name]         Toggle a breakpoint on a definition, or list them");
     println!();
 
     println!("{}", "LSP-like Features:".bold());
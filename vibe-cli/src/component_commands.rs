@@ -2,14 +2,16 @@
 
 use anyhow::{Context, Result};
 use colored::*;
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use vibe_compiler::wasm::wit_generator::WitGenerator;
 use vibe_compiler::{type_check, TypeChecker, TypeEnv};
 use vibe_language::parser::parse;
-use vibe_language::{Expr, Span, Type};
+use vibe_language::{Expr, Span, Type, TypeDefinition};
 
-use crate::cli::ComponentCommand;
+use crate::cli::{format_value, ComponentCommand};
+use crate::component_runtime::{self, Entry, Permissions, RunOutcome};
 
 /// Handle component-related commands
 pub fn handle_component_command(cmd: ComponentCommand) -> Result<()> {
@@ -21,7 +23,23 @@ pub fn handle_component_command(cmd: ComponentCommand) -> Result<()> {
             optimize,
         } => build_component(&input, output, wit, optimize),
         ComponentCommand::GenerateWit { input, output } => generate_wit(&input, output),
-        ComponentCommand::Run { input, args } => run_component(&input, args),
+        ComponentCommand::Run {
+            input,
+            invoke,
+            allow_read,
+            allow_write,
+            allow_env,
+            allow_all_env,
+            args,
+        } => {
+            let permissions = Permissions {
+                allow_read,
+                allow_write,
+                allow_env,
+                allow_all_env,
+            };
+            run_component(&input, invoke, args, &permissions)
+        }
     }
 }
 
@@ -38,7 +56,7 @@ fn generate_wit(file: &PathBuf, output: Option<PathBuf>) -> Result<()> {
     let (_module_name, exports) = extract_module_info(&expr)?;
 
     // Type check the module and extract export types
-    let export_types = extract_export_types(&expr)?;
+    let module_types = extract_export_types(&expr)?;
 
     // Generate package name from file name
     let package_name = if let Some(stem) = file.file_stem() {
@@ -48,15 +66,12 @@ fn generate_wit(file: &PathBuf, output: Option<PathBuf>) -> Result<()> {
     };
 
     // Create WIT generator
-    let mut generator = WitGenerator::new(package_name, "0.1.0".to_string());
-
-    // Add exports
-    for ((name, _), typ) in exports.iter().zip(export_types.iter()) {
-        generator.add_export(name.clone(), typ.clone());
-    }
+    let generator = wit_generator(package_name, "0.1.0", &exports, module_types);
 
     // Generate WIT content
-    let wit_content = generator.generate();
+    let wit_content = generator
+        .generate()
+        .with_context(|| "Failed to map exports to WIT")?;
 
     // Write output
     if let Some(output_path) = output {
@@ -92,7 +107,7 @@ fn _build_component_old(file: &PathBuf, output: &PathBuf, version: &str) -> Resu
 
     // Generate WIT for the module
     // Instead of re-type-checking exports, extract types from the already type-checked module
-    let export_types = extract_export_types(&expr)?;
+    let module_types = extract_export_types(&expr)?;
 
     let package_name = if let Some(stem) = file.file_stem() {
         format!("xs:{}", stem.to_string_lossy())
@@ -103,11 +118,9 @@ fn _build_component_old(file: &PathBuf, output: &PathBuf, version: &str) -> Resu
     // Re-extract module information to ensure correct order
     let (_module_name, exports) = extract_module_info(&expr)?;
 
-    let mut wit_generator = WitGenerator::new(package_name.clone(), version.to_string());
-    for ((name, _), typ) in exports.iter().zip(export_types.iter()) {
-        wit_generator.add_export(name.clone(), typ.clone());
-    }
-    let wit_content = wit_generator.generate();
+    let wit_content = wit_generator(package_name, version, &exports, module_types)
+        .generate()
+        .with_context(|| "Failed to map exports to WIT")?;
 
     // Build the component using the new builder
     use vibe_compiler::wasm::component_builder;
@@ -207,13 +220,42 @@ fn find_definition_in_body_list(name: &str, body: &[Expr]) -> Option<Expr> {
     None
 }
 
+/// Types of a module's exports and what is needed to describe them in WIT
+struct ModuleTypes {
+    exports: Vec<Type>,
+    definitions: Vec<TypeDefinition>,
+    /// Argument types generic exports are applied to within the module
+    instantiations: Vec<(String, Vec<Type>)>,
+}
+
+/// Create a WIT generator for a module's exports
+fn wit_generator(
+    package_name: String,
+    version: &str,
+    exports: &[(String, Expr)],
+    module_types: ModuleTypes,
+) -> WitGenerator {
+    let mut generator = WitGenerator::new(package_name, version.to_string());
+    for definition in module_types.definitions {
+        generator.add_type_definition(definition.name.clone(), definition);
+    }
+    for ((name, _), typ) in exports.iter().zip(module_types.exports) {
+        generator.add_export(name.clone(), typ);
+    }
+    for (name, arg_types) in module_types.instantiations {
+        generator.add_instantiation(name, arg_types);
+    }
+    generator
+}
+
 /// Extract export types from a type-checked module
-fn extract_export_types(expr: &Expr) -> Result<Vec<Type>> {
+fn extract_export_types(expr: &Expr) -> Result<ModuleTypes> {
     match expr {
         Expr::Module { exports, body, .. } => {
             // Type check the module body to get the types
             let mut checker = TypeChecker::new();
             let mut type_env = TypeEnv::default();
+            let mut definitions = Vec::new();
 
             // Type check the body expressions
             for body_expr in body {
@@ -270,8 +312,11 @@ fn extract_export_types(expr: &Expr) -> Result<Vec<Type>> {
                         let scheme = vibe_compiler::TypeScheme::mono(func_type);
                         type_env.add_binding(name.0.clone(), scheme);
                     }
-                    Expr::TypeDef { .. } => {
-                        // Skip type definitions for now
+                    Expr::TypeDef { definition, .. } => {
+                        checker
+                            .check(body_expr, &mut type_env)
+                            .map_err(|e| anyhow::anyhow!("Type error: {:?}", e))?;
+                        definitions.push(definition.clone());
                     }
                     _ => {
                         // Skip other expressions
@@ -296,12 +341,99 @@ fn extract_export_types(expr: &Expr) -> Result<Vec<Type>> {
                 }
             }
 
-            Ok(export_types)
+            // Generic exports are monomorphised at the argument types they
+            // are applied to in the module
+            let generic: HashSet<&str> = exports
+                .iter()
+                .zip(&export_types)
+                .filter(|(_, typ)| !typ.free_vars().is_empty())
+                .map(|(name, _)| name.0.as_str())
+                .collect();
+            let mut applications = Vec::new();
+            for body_expr in body {
+                collect_applications(body_expr, &generic, &mut applications);
+            }
+            let instantiations = applications
+                .into_iter()
+                .filter_map(|(name, args)| {
+                    let arg_types = args
+                        .iter()
+                        .map(|arg| checker.check(arg, &mut type_env).ok())
+                        .collect::<Option<Vec<_>>>()?;
+                    arg_types
+                        .iter()
+                        .all(|typ| typ.free_vars().is_empty())
+                        .then_some((name, arg_types))
+                })
+                .collect();
+
+            Ok(ModuleTypes {
+                exports: export_types,
+                definitions,
+                instantiations,
+            })
         }
         _ => Err(anyhow::anyhow!("Not a module expression")),
     }
 }
 
+/// Collect applications of the named functions, flattening curried calls
+fn collect_applications(expr: &Expr, names: &HashSet<&str>, out: &mut Vec<(String, Vec<Expr>)>) {
+    match expr {
+        Expr::Apply { func, args, .. } => {
+            let mut all_args: Vec<Expr> = args.clone();
+            let mut head = func.as_ref();
+            while let Expr::Apply { func, args, .. } = head {
+                all_args.splice(0..0, args.iter().cloned());
+                head = func;
+            }
+            if let Expr::Ident(name, _) = head {
+                if names.contains(name.0.as_str()) {
+                    out.push((name.0.clone(), all_args));
+                }
+            }
+            collect_applications(func, names, out);
+            for arg in args {
+                collect_applications(arg, names, out);
+            }
+        }
+        Expr::Let { value, .. } | Expr::LetRec { value, .. } => {
+            collect_applications(value, names, out)
+        }
+        Expr::LetIn { value, body, .. } | Expr::LetRecIn { value, body, .. } => {
+            collect_applications(value, names, out);
+            collect_applications(body, names, out);
+        }
+        Expr::Rec { body, .. } | Expr::Lambda { body, .. } | Expr::FunctionDef { body, .. } => {
+            collect_applications(body, names, out)
+        }
+        Expr::If {
+            cond,
+            then_expr,
+            else_expr,
+            ..
+        } => {
+            for expr in [cond, then_expr, else_expr] {
+                collect_applications(expr, names, out);
+            }
+        }
+        Expr::Match { expr, cases, .. } => {
+            collect_applications(expr, names, out);
+            for (_, body) in cases {
+                collect_applications(body, names, out);
+            }
+        }
+        Expr::Block { exprs, .. }
+        | Expr::List(exprs, _)
+        | Expr::Constructor { args: exprs, .. } => {
+            for expr in exprs {
+                collect_applications(expr, names, out);
+            }
+        }
+        _ => {}
+    }
+}
+
 /// Type check exports and return their types (legacy function kept for reference)
 fn _type_check_exports(
     checker: &mut TypeChecker,
@@ -374,16 +506,33 @@ fn build_component(
     Ok(())
 }
 
-/// Run WebAssembly component
-fn run_component(input: &PathBuf, args: Vec<String>) -> Result<()> {
-    println!("{}: Component running not yet implemented", "Note".yellow());
-    println!("Would run {} with args: {:?}", input.display(), args);
-    Ok(())
+/// Run a WebAssembly component under WASI preview 2
+fn run_component(
+    input: &Path,
+    invoke: Option<String>,
+    args: Vec<String>,
+    permissions: &Permissions,
+) -> Result<()> {
+    let entry = match invoke {
+        Some(name) => Entry::Function(name),
+        None => Entry::Default,
+    };
+
+    match component_runtime::run_component(input, &entry, &args, permissions)? {
+        RunOutcome::Exited(0) => Ok(()),
+        RunOutcome::Exited(code) => std::process::exit(code),
+        RunOutcome::Returned(Some(value)) => {
+            println!("{}", format_value(&value));
+            Ok(())
+        }
+        RunOutcome::Returned(None) => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use vibe_language::parser::recovery::split_top_level;
     use vibe_language::{Ident, Span};
 
     #[test]
@@ -419,4 +568,34 @@ mod tests {
         assert_eq!(exports.len(), 1);
         assert_eq!(exports[0].0, "add");
     }
+
+    #[test]
+    fn test_wit_for_module_with_types_and_generic_export() {
+        // The parser has no module form, so only the wrapper is built by hand
+        let source = "type Color = | Red | Green\nlet identity = fn x -> x\nidentity \"hi\"";
+        let body = split_top_level(source)
+            .into_iter()
+            .map(|span| parse(&source[span.start..span.end]).unwrap())
+            .collect();
+        let module = Expr::Module {
+            name: Ident("Util".to_string()),
+            exports: vec![Ident("identity".to_string())],
+            body,
+            span: Span::new(0, 0),
+        };
+
+        let (_, exports) = extract_module_info(&module).unwrap();
+        let module_types = extract_export_types(&module).unwrap();
+        let wit = wit_generator("xs:util".to_string(), "0.1.0", &exports, module_types)
+            .generate()
+            .unwrap();
+        assert!(
+            wit.contains("  enum color {\n    red,\n    green,\n  }"),
+            "{wit}"
+        );
+        assert!(
+            wit.contains("identity-string: func(arg1: string) -> string;"),
+            "{wit}"
+        );
+    }
 }
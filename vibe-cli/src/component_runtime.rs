@@ -0,0 +1,992 @@
+//! Running WebAssembly components under WASI preview 2
+//!
+//! A component is either a WASI command, exporting `wasi:cli/run`, or a
+//! library exporting plain functions. Commands receive the CLI arguments as
+//! WASI args; for functions each argument is parsed according to the WIT type
+//! of the matching parameter and the result is mapped back to a Vibe value.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+use vibe_language::{Ident, Value};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Func, Instance, Linker, ResourceTable, Type, Val};
+use wasmtime::{Config, Engine, Store};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtx, WasiCtxBuilder, WasiView};
+
+/// Host resources a component may access. Everything is denied by default.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    /// Directories preopened read-only
+    pub allow_read: Vec<PathBuf>,
+    /// Directories preopened read-write
+    pub allow_write: Vec<PathBuf>,
+    /// Environment variables passed through from the host
+    pub allow_env: Vec<String>,
+    /// Pass through the whole host environment
+    pub allow_all_env: bool,
+}
+
+/// What to call in the component
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry {
+    /// `wasi:cli/run` if exported, otherwise a function named `run`
+    Default,
+    /// An exported function
+    Function(String),
+}
+
+/// Outcome of running a component
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+    /// A WASI command finished with an exit code
+    Exited(i32),
+    /// An exported function returned; `None` when it has no result
+    Returned(Option<Box<Value>>),
+}
+
+struct HostState {
+    ctx: WasiCtx,
+    table: ResourceTable,
+}
+
+impl WasiView for HostState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.ctx
+    }
+}
+
+/// Instantiate the component at `path` and invoke `entry` with `args`
+pub fn run_component(
+    path: &Path,
+    entry: &Entry,
+    args: &[String],
+    permissions: &Permissions,
+) -> Result<RunOutcome> {
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config)?;
+
+    let component = Component::from_file(&engine, path)
+        .with_context(|| format!("Failed to load component: {}", path.display()))?;
+    run_loaded(
+        &engine,
+        &component,
+        &program_name(path),
+        entry,
+        args,
+        permissions,
+    )
+}
+
+fn program_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "component".to_string())
+}
+
+fn run_loaded(
+    engine: &Engine,
+    component: &Component,
+    program: &str,
+    entry: &Entry,
+    args: &[String],
+    permissions: &Permissions,
+) -> Result<RunOutcome> {
+    let mut linker = Linker::<HostState>::new(engine);
+    wasmtime_wasi::add_to_linker_sync(&mut linker)?;
+
+    let is_command = exports_cli_run(engine, component);
+    let mut builder = WasiCtxBuilder::new();
+    builder.inherit_stdio().arg(program);
+    if is_command && *entry == Entry::Default {
+        builder.args(args);
+    }
+    apply_permissions(&mut builder, permissions)?;
+
+    let mut store = Store::new(
+        engine,
+        HostState {
+            ctx: builder.build(),
+            table: ResourceTable::new(),
+        },
+    );
+
+    let name = match entry {
+        Entry::Default if is_command => return run_command(&mut store, component, &linker),
+        Entry::Default => "run",
+        Entry::Function(name) => name.as_str(),
+    };
+
+    let instance = linker.instantiate(&mut store, component)?;
+    let func = find_func(&mut store, &instance, engine, component, name).ok_or_else(|| {
+        let available = exported_functions(engine, component);
+        anyhow!(
+            "Component has no exported function `{}` (exports: {})",
+            name,
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            }
+        )
+    })?;
+
+    let params = func.params(&store);
+    if params.len() != args.len() {
+        bail!(
+            "`{}` expects {} argument(s) but {} were given",
+            name,
+            params.len(),
+            args.len()
+        );
+    }
+    let params = params
+        .iter()
+        .zip(args)
+        .enumerate()
+        .map(|(i, (ty, arg))| {
+            parse_arg(arg, ty).with_context(|| format!("Invalid argument {} `{}`", i + 1, arg))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut results = vec![Val::Bool(false); func.results(&store).len()];
+    func.call(&mut store, &params, &mut results)?;
+    func.post_return(&mut store)?;
+
+    let value = match results.len() {
+        0 => None,
+        1 => Some(val_to_value(&results[0])?),
+        _ => Some(tuple_value(vals_to_values(&results)?)),
+    };
+    Ok(RunOutcome::Returned(value.map(Box::new)))
+}
+
+fn run_command(
+    store: &mut Store<HostState>,
+    component: &Component,
+    linker: &Linker<HostState>,
+) -> Result<RunOutcome> {
+    let command =
+        wasmtime_wasi::bindings::sync::Command::instantiate(&mut *store, component, linker)?;
+    match command.wasi_cli_run().call_run(&mut *store) {
+        Ok(Ok(())) => Ok(RunOutcome::Exited(0)),
+        Ok(Err(())) => Ok(RunOutcome::Exited(1)),
+        // `exit` inside the component surfaces as a trap carrying the code
+        Err(e) => match e.downcast_ref::<I32Exit>() {
+            Some(exit) => Ok(RunOutcome::Exited(exit.0)),
+            None => Err(e),
+        },
+    }
+}
+
+fn apply_permissions(builder: &mut WasiCtxBuilder, permissions: &Permissions) -> Result<()> {
+    let read_only = permissions.allow_read.iter().map(|dir| (dir, false));
+    let read_write = permissions.allow_write.iter().map(|dir| (dir, true));
+    for (dir, writable) in read_only.chain(read_write) {
+        let (dir_perms, file_perms) = if writable {
+            (DirPerms::all(), FilePerms::all())
+        } else {
+            (DirPerms::READ, FilePerms::READ)
+        };
+        // The guest sees the directory under the path it was granted as
+        builder
+            .preopened_dir(dir, dir.to_string_lossy(), dir_perms, file_perms)
+            .with_context(|| format!("Cannot preopen directory: {}", dir.display()))?;
+    }
+
+    if permissions.allow_all_env {
+        builder.inherit_env();
+    } else {
+        for name in &permissions.allow_env {
+            if let Ok(value) = std::env::var(name) {
+                builder.env(name, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn exports_cli_run(engine: &Engine, component: &Component) -> bool {
+    component
+        .component_type()
+        .exports(engine)
+        .any(|(name, item)| {
+            name.starts_with("wasi:cli/run@") && matches!(item, ComponentItem::ComponentInstance(_))
+        })
+}
+
+/// Look up a function exported at the top level or from an exported
+/// interface such as the `exports` interface of components built by `vibe`
+fn find_func(
+    store: &mut Store<HostState>,
+    instance: &Instance,
+    engine: &Engine,
+    component: &Component,
+    name: &str,
+) -> Option<Func> {
+    if let Some(func) = instance.get_func(&mut *store, name) {
+        return Some(func);
+    }
+    component
+        .component_type()
+        .exports(engine)
+        .filter(|(_, item)| matches!(item, ComponentItem::ComponentInstance(_)))
+        .find_map(|(interface, _)| {
+            let interface = instance.get_export(&mut *store, None, interface)?;
+            let export = instance.get_export(&mut *store, Some(&interface), name)?;
+            instance.get_func(&mut *store, export)
+        })
+}
+
+fn exported_functions(engine: &Engine, component: &Component) -> Vec<String> {
+    let mut names = Vec::new();
+    for (name, item) in component.component_type().exports(engine) {
+        match item {
+            ComponentItem::ComponentFunc(_) => names.push(name.to_string()),
+            ComponentItem::ComponentInstance(interface) => names.extend(
+                interface
+                    .exports(engine)
+                    .filter(|(_, item)| matches!(item, ComponentItem::ComponentFunc(_)))
+                    .map(|(name, _)| name.to_string()),
+            ),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Parse a command-line argument as a value of WIT type `ty`.
+///
+/// Scalars are written as usual (`42`, `-1.5`, `true`); a top-level string
+/// needs no quotes. Compound values use a literal syntax: lists `[1, 2]`,
+/// tuples `(1, "a")`, records `{name: "x", age: 3}`, flags `{read, write}`,
+/// options `none` / `some(1)` (or just the payload), results `ok(1)` /
+/// `err("e")`, and variant or enum cases by name, e.g. `circle(2.0)`.
+pub fn parse_arg(text: &str, ty: &Type) -> Result<Val> {
+    if let Type::String = ty {
+        if !text.trim_start().starts_with('"') {
+            return Ok(Val::String(text.to_string()));
+        }
+    }
+
+    let mut parser = ArgParser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value(ty)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        bail!("unexpected `{}`", parser.rest());
+    }
+    Ok(value)
+}
+
+struct ArgParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ArgParser {
+    fn value(&mut self, ty: &Type) -> Result<Val> {
+        self.skip_whitespace();
+        Ok(match ty {
+            Type::Bool => match self.word().as_str() {
+                "true" => Val::Bool(true),
+                "false" => Val::Bool(false),
+                other => bail!("expected `true` or `false`, found `{}`", other),
+            },
+            Type::S8 => Val::S8(self.integer()?),
+            Type::U8 => Val::U8(self.integer()?),
+            Type::S16 => Val::S16(self.integer()?),
+            Type::U16 => Val::U16(self.integer()?),
+            Type::S32 => Val::S32(self.integer()?),
+            Type::U32 => Val::U32(self.integer()?),
+            Type::S64 => Val::S64(self.integer()?),
+            Type::U64 => Val::U64(self.integer()?),
+            Type::Float32 => Val::Float32(self.float()? as f32),
+            Type::Float64 => Val::Float64(self.float()?),
+            Type::Char => {
+                let quoted = self.eat('\'');
+                let c = self.next().ok_or_else(|| anyhow!("expected a character"))?;
+                if quoted {
+                    self.expect('\'')?;
+                }
+                Val::Char(c)
+            }
+            Type::String => Val::String(self.string()?),
+            Type::List(list) => {
+                let element = list.ty();
+                let mut items = Vec::new();
+                self.sequence('[', ']', |p| {
+                    items.push(p.value(&element)?);
+                    Ok(())
+                })?;
+                Val::List(items)
+            }
+            Type::Tuple(tuple) => {
+                let types: Vec<Type> = tuple.types().collect();
+                let mut items = Vec::new();
+                self.sequence('(', ')', |p| {
+                    let ty = types
+                        .get(items.len())
+                        .ok_or_else(|| anyhow!("too many tuple elements"))?;
+                    items.push(p.value(ty)?);
+                    Ok(())
+                })?;
+                if items.len() != types.len() {
+                    bail!(
+                        "expected {} tuple elements, found {}",
+                        types.len(),
+                        items.len()
+                    );
+                }
+                Val::Tuple(items)
+            }
+            Type::Record(record) => {
+                let fields: Vec<(String, Type)> = record
+                    .fields()
+                    .map(|field| (field.name.to_string(), field.ty))
+                    .collect();
+                let mut values: Vec<Option<Val>> = vec![None; fields.len()];
+                self.sequence('{', '}', |p| {
+                    let name = p.word();
+                    let index = fields
+                        .iter()
+                        .position(|(field, _)| *field == name)
+                        .ok_or_else(|| anyhow!("unknown record field `{}`", name))?;
+                    p.skip_whitespace();
+                    p.expect(':')?;
+                    values[index] = Some(p.value(&fields[index].1)?);
+                    Ok(())
+                })?;
+                let values = fields
+                    .iter()
+                    .zip(values)
+                    .map(|((name, _), value)| {
+                        value
+                            .map(|value| (name.clone(), value))
+                            .ok_or_else(|| anyhow!("missing record field `{}`", name))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Val::Record(values)
+            }
+            Type::Flags(flags) => {
+                let known: Vec<String> = flags.names().map(str::to_string).collect();
+                let mut set = Vec::new();
+                self.sequence('{', '}', |p| {
+                    let name = p.word();
+                    if !known.contains(&name) {
+                        bail!("unknown flag `{}`", name);
+                    }
+                    set.push(name);
+                    Ok(())
+                })?;
+                Val::Flags(set)
+            }
+            Type::Enum(cases) => {
+                let name = self.word();
+                if !cases.names().any(|case| case == name) {
+                    bail!("unknown enum case `{}`", name);
+                }
+                Val::Enum(name)
+            }
+            Type::Variant(variant) => {
+                let name = self.word();
+                let case = variant
+                    .cases()
+                    .find(|case| case.name == name)
+                    .ok_or_else(|| anyhow!("unknown variant case `{}`", name))?;
+                let payload = match case.ty {
+                    Some(ty) => Some(Box::new(self.payload(&ty)?)),
+                    None => None,
+                };
+                Val::Variant(name, payload)
+            }
+            Type::Option(option) => {
+                let start = self.pos;
+                match self.word().as_str() {
+                    "none" => Val::Option(None),
+                    "some" => Val::Option(Some(Box::new(self.payload(&option.ty())?))),
+                    _ => {
+                        self.pos = start;
+                        Val::Option(Some(Box::new(self.value(&option.ty())?)))
+                    }
+                }
+            }
+            Type::Result(result) => {
+                let word = self.word();
+                let ty = match word.as_str() {
+                    "ok" => result.ok(),
+                    "err" => result.err(),
+                    other => bail!("expected `ok` or `err`, found `{}`", other),
+                };
+                let payload = match ty {
+                    Some(ty) => Some(Box::new(self.payload(&ty)?)),
+                    None => None,
+                };
+                if word == "ok" {
+                    Val::Result(Ok(payload))
+                } else {
+                    Val::Result(Err(payload))
+                }
+            }
+            Type::Own(_) | Type::Borrow(_) => {
+                bail!("resources cannot be passed on the command line")
+            }
+        })
+    }
+
+    /// A parenthesised case payload: `(value)`
+    fn payload(&mut self, ty: &Type) -> Result<Val> {
+        self.skip_whitespace();
+        self.expect('(')?;
+        let value = self.value(ty)?;
+        self.skip_whitespace();
+        self.expect(')')?;
+        Ok(value)
+    }
+
+    /// Comma-separated items between `open` and `close`
+    fn sequence(
+        &mut self,
+        open: char,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        self.expect(open)?;
+        self.skip_whitespace();
+        if self.eat(close) {
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.skip_whitespace();
+            if self.eat(close) {
+                return Ok(());
+            }
+            self.expect(',')?;
+            self.skip_whitespace();
+        }
+    }
+
+    fn integer<T>(&mut self) -> Result<T>
+    where
+        T: std::str::FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let text = self.number_text();
+        text.replace('_', "")
+            .parse()
+            .with_context(|| format!("`{}` is not a valid integer of this width", text))
+    }
+
+    fn float(&mut self) -> Result<f64> {
+        let text = self.number_text();
+        text.parse()
+            .with_context(|| format!("`{}` is not a valid number", text))
+    }
+
+    fn number_text(&mut self) -> String {
+        self.skip_whitespace();
+        self.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.' | '_'))
+    }
+
+    /// An identifier, including WIT's kebab-case names
+    fn word(&mut self) -> String {
+        self.skip_whitespace();
+        self.take_while(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some(c) => text.push(c),
+                    None => bail!("unterminated string"),
+                },
+                Some(c) => text.push(c),
+                None => bail!("unterminated string"),
+            }
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|&c| pred(c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.chars.get(self.pos) == Some(&expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.eat(expected) {
+            Ok(())
+        } else if self.pos < self.chars.len() {
+            bail!("expected `{}`, found `{}`", expected, self.rest())
+        } else {
+            bail!("expected `{}` at end of input", expected)
+        }
+    }
+
+    fn rest(&self) -> String {
+        self.chars[self.pos..].iter().collect()
+    }
+}
+
+/// Convert a component value to the Vibe value it corresponds to.
+///
+/// Variant and enum cases become constructors named in PascalCase, options
+/// and results become `Some`/`None` and `Ok`/`Err`, and tuples become a
+/// `Tuple` constructor.
+pub fn val_to_value(val: &Val) -> Result<Value> {
+    Ok(match val {
+        Val::Bool(b) => Value::Bool(*b),
+        Val::S8(n) => Value::Int(*n as i64),
+        Val::U8(n) => Value::Int(*n as i64),
+        Val::S16(n) => Value::Int(*n as i64),
+        Val::U16(n) => Value::Int(*n as i64),
+        Val::S32(n) => Value::Int(*n as i64),
+        Val::U32(n) => Value::Int(*n as i64),
+        Val::S64(n) => Value::Int(*n),
+        Val::U64(n) => Value::Int(
+            i64::try_from(*n).map_err(|_| anyhow!("u64 value {} does not fit in an Int", n))?,
+        ),
+        Val::Float32(f) => Value::Float(*f as f64),
+        Val::Float64(f) => Value::Float(*f),
+        Val::Char(c) => Value::String(c.to_string()),
+        Val::String(s) => Value::String(s.clone()),
+        Val::List(items) => Value::List(vals_to_values(items)?),
+        Val::Record(fields) => Value::Record {
+            fields: fields
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), val_to_value(value)?)))
+                .collect::<Result<_>>()?,
+        },
+        Val::Tuple(items) => tuple_value(vals_to_values(items)?),
+        Val::Variant(case, payload) => {
+            constructor(&pascal_case(case), vals_to_values(payload.as_deref())?)
+        }
+        Val::Enum(case) => constructor(&pascal_case(case), vec![]),
+        Val::Option(None) => constructor("None", vec![]),
+        Val::Option(Some(value)) => constructor("Some", vec![val_to_value(value)?]),
+        Val::Result(Ok(payload)) => constructor("Ok", vals_to_values(payload.as_deref())?),
+        Val::Result(Err(payload)) => constructor("Err", vals_to_values(payload.as_deref())?),
+        Val::Flags(names) => Value::List(names.iter().cloned().map(Value::String).collect()),
+        Val::Resource(_) => constructor("Resource", vec![]),
+    })
+}
+
+fn vals_to_values<'a>(vals: impl IntoIterator<Item = &'a Val>) -> Result<Vec<Value>> {
+    vals.into_iter().map(val_to_value).collect()
+}
+
+fn constructor(name: &str, values: Vec<Value>) -> Value {
+    Value::Constructor {
+        name: Ident(name.to_string()),
+        values,
+    }
+}
+
+fn tuple_value(values: Vec<Value>) -> Value {
+    constructor("Tuple", values)
+}
+
+/// `not-found` -> `NotFound`
+fn pascal_case(name: &str) -> String {
+    name.split(['-', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A component exporting `add: func(a: s32, b: s32) -> s32` and
+    /// `describe: func(p: point) -> option<string>` built from text
+    const LIBRARY: &str = r#"
+        (component
+          (core module $m
+            (memory (export "memory") 1)
+            (func (export "add") (param i32 i32) (result i32)
+              local.get 0
+              local.get 1
+              i32.add)
+            (func (export "describe") (param i32 i32) (result i32)
+              ;; Some("hi") when x > y, otherwise None; result area at 16
+              (if (i32.gt_s (local.get 0) (local.get 1))
+                (then
+                  (i32.store8 (i32.const 16) (i32.const 1))
+                  (i32.store (i32.const 20) (i32.const 100))
+                  (i32.store (i32.const 24) (i32.const 2)))
+                (else
+                  (i32.store8 (i32.const 16) (i32.const 0))))
+              i32.const 16)
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+              i32.const 200)
+            (data (i32.const 100) "hi"))
+          (core instance $i (instantiate $m))
+          (type $point (record (field "x" s32) (field "y" s32)))
+          (export $p "point" (type $point))
+          (func (export "add") (param "a" s32) (param "b" s32) (result s32)
+            (canon lift (core func $i "add")))
+          (func (export "describe") (param "p" $p) (result (option string))
+            (canon lift (core func $i "describe")
+              (memory $i "memory") (realloc (func $i "realloc"))))
+        )
+    "#;
+
+    fn run(entry: &str, args: &[&str]) -> Result<RunOutcome> {
+        run_bytes(LIBRARY.as_bytes(), entry, args)
+    }
+
+    fn run_bytes(bytes: &[u8], entry: &str, args: &[&str]) -> Result<RunOutcome> {
+        run_with(
+            bytes,
+            &Entry::Function(entry.to_string()),
+            args,
+            &Permissions::default(),
+        )
+    }
+
+    fn run_with(
+        bytes: &[u8],
+        entry: &Entry,
+        args: &[&str],
+        permissions: &Permissions,
+    ) -> Result<RunOutcome> {
+        let mut config = Config::new();
+        config.wasm_component_model(true);
+        let engine = Engine::new(&config)?;
+        let component = Component::new(&engine, bytes)?;
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        run_loaded(&engine, &component, "test.wasm", entry, &args, permissions)
+    }
+
+    /// A WASI command whose `run` returns `ok` or `err` depending on `status`
+    fn command(status: i32) -> String {
+        format!(
+            r#"
+            (component
+              (core module $m
+                (func (export "run") (result i32)
+                  i32.const {status}))
+              (core instance $i (instantiate $m))
+              (func $run (result (result)) (canon lift (core func $i "run")))
+              (instance $cli (export "run" (func $run)))
+              (export "wasi:cli/run@0.2.2" (instance $cli)))
+            "#
+        )
+    }
+
+    /// Core module providing the memory and bump allocator that lowered
+    /// imports returning lists write into
+    const MEMORY: &str = r#"
+        (core module $mem
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr
+              (i32.and
+                (i32.add (global.get $next) (i32.sub (local.get 2) (i32.const 1)))
+                (i32.sub (i32.const 0) (local.get 2))))
+            (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+            local.get $ptr))
+        (core instance $mem (instantiate $mem))
+    "#;
+
+    /// Core module exporting `count`, which calls the lowered import `get`
+    /// and returns the length of the list it produced
+    const COUNT: &str = r#"
+        (core module $main
+          (import "host" "get" (func $get (param i32)))
+          (import "mem" "memory" (memory 1))
+          (func (export "count") (result i32)
+            (call $get (i32.const 0))
+            (i32.load (i32.const 4))))
+        (core instance $main (instantiate $main
+          (with "host" (instance (export "get" (func $get))))
+          (with "mem" (instance $mem))))
+        (func (export "count") (result u32) (canon lift (core func $main "count")))
+    "#;
+
+    /// A component exporting `count: func() -> u32`, the number of
+    /// environment variables it can see
+    fn environment_counter() -> String {
+        format!(
+            r#"
+            (component
+              (import "wasi:cli/environment@0.2.2" (instance $env
+                (export "get-environment" (func (result (list (tuple string string)))))))
+              {MEMORY}
+              (core func $get (canon lower (func $env "get-environment")
+                (memory $mem "memory") (realloc (func $mem "realloc"))))
+              {COUNT})
+            "#
+        )
+    }
+
+    /// A component exporting `count: func() -> u32`, the number of
+    /// directories preopened for it
+    fn preopen_counter() -> String {
+        format!(
+            r#"
+            (component $c
+              (import "wasi:filesystem/types@0.2.2" (instance $types
+                (export "descriptor" (type (sub resource)))))
+              (alias export $types "descriptor" (type $descriptor))
+              (import "wasi:filesystem/preopens@0.2.2" (instance $preopens
+                (alias outer $c $descriptor (type $d))
+                (export "get-directories"
+                  (func (result (list (tuple (own $d) string)))))))
+              {MEMORY}
+              (core func $get (canon lower (func $preopens "get-directories")
+                (memory $mem "memory") (realloc (func $mem "realloc"))))
+              {COUNT})
+            "#
+        )
+    }
+
+    fn count(bytes: &str, permissions: &Permissions) -> Value {
+        let entry = Entry::Function("count".to_string());
+        match run_with(bytes.as_bytes(), &entry, &[], permissions).unwrap() {
+            RunOutcome::Returned(Some(value)) => *value,
+            other => panic!("expected a count, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invoke_export_with_converted_args() {
+        let outcome = run("add", &["40", "2"]).unwrap();
+        assert_eq!(
+            outcome,
+            RunOutcome::Returned(Some(Box::new(Value::Int(42))))
+        );
+    }
+
+    #[test]
+    fn test_record_argument_and_option_result() {
+        let outcome = run("describe", &["{x: 3, y: 1}"]).unwrap();
+        assert_eq!(
+            outcome,
+            RunOutcome::Returned(Some(Box::new(constructor(
+                "Some",
+                vec![Value::String("hi".to_string())]
+            ))))
+        );
+
+        let outcome = run("describe", &["{ y: 5, x: 1 }"]).unwrap();
+        assert_eq!(
+            outcome,
+            RunOutcome::Returned(Some(Box::new(constructor("None", vec![]))))
+        );
+    }
+
+    #[test]
+    fn test_argument_errors() {
+        let error = run("add", &["1"]).unwrap_err();
+        assert!(error.to_string().contains("expects 2 argument(s)"));
+
+        let error = run("add", &["1", "x"]).unwrap_err();
+        assert!(format!("{error:#}").contains("not a valid integer"));
+
+        let error = run("describe", &["{x: 1}"]).unwrap_err();
+        assert!(format!("{error:#}").contains("missing record field `y`"));
+
+        let error = run("missing", &[]).unwrap_err();
+        assert!(error.to_string().contains("exports: add, describe"));
+    }
+
+    #[test]
+    fn test_wasi_command_entry() {
+        let outcome = run_with(
+            command(0).as_bytes(),
+            &Entry::Default,
+            &["a", "b"],
+            &Permissions::default(),
+        )
+        .unwrap();
+        assert_eq!(outcome, RunOutcome::Exited(0));
+
+        let outcome = run_with(
+            command(1).as_bytes(),
+            &Entry::Default,
+            &[],
+            &Permissions::default(),
+        )
+        .unwrap();
+        assert_eq!(outcome, RunOutcome::Exited(1));
+    }
+
+    #[test]
+    fn test_allow_env() {
+        let component = environment_counter();
+        assert_eq!(count(&component, &Permissions::default()), Value::Int(0));
+
+        let unset = Permissions {
+            allow_env: vec!["VIBE_TEST_UNSET_VARIABLE".to_string()],
+            ..Permissions::default()
+        };
+        assert_eq!(count(&component, &unset), Value::Int(0));
+
+        let granted = Permissions {
+            allow_env: vec!["PATH".to_string()],
+            ..Permissions::default()
+        };
+        assert_eq!(count(&component, &granted), Value::Int(1));
+    }
+
+    #[test]
+    fn test_allow_read() {
+        let component = preopen_counter();
+        assert_eq!(count(&component, &Permissions::default()), Value::Int(0));
+
+        let dir = tempfile::tempdir().unwrap();
+        let granted = Permissions {
+            allow_read: vec![dir.path().to_path_buf()],
+            ..Permissions::default()
+        };
+        assert_eq!(count(&component, &granted), Value::Int(1));
+
+        let missing = Permissions {
+            allow_read: vec![dir.path().join("missing")],
+            ..Permissions::default()
+        };
+        let error = run_with(
+            component.as_bytes(),
+            &Entry::Function("count".to_string()),
+            &[],
+            &missing,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("Cannot preopen directory"));
+    }
+
+    #[test]
+    fn test_u64_out_of_range() {
+        assert_eq!(val_to_value(&Val::U64(7)).unwrap(), Value::Int(7));
+        let error = val_to_value(&Val::U64(u64::MAX)).unwrap_err();
+        assert!(error.to_string().contains("does not fit in an Int"));
+    }
+
+    #[test]
+    fn test_pascal_case() {
+        assert_eq!(pascal_case("not-found"), "NotFound");
+        assert_eq!(pascal_case("red"), "Red");
+    }
+
+    #[test]
+    fn test_invoke_component_built_from_wit() {
+        use vibe_compiler::wasm::component::ComponentBuilder;
+        use vibe_compiler::wasm::{WasmFunction, WasmInstr, WasmModule, WasmType};
+
+        let wit = r#"package xs:shapes@0.1.0;
+
+interface exports {
+  record point {
+    x: s64,
+    y: s64,
+  }
+
+  variant shape {
+    circle(f64),
+    square(s64),
+  }
+
+  norm: func(arg1: point) -> s64;
+  unit-circle: func() -> shape;
+}
+
+world shapes {
+  export exports;
+}
+"#;
+        let function = |name: &str, params, results, body| WasmFunction {
+            name: name.to_string(),
+            params,
+            results,
+            locals: vec![],
+            body,
+        };
+        let module = WasmModule {
+            functions: vec![
+                function(
+                    "norm",
+                    vec![WasmType::I64, WasmType::I64],
+                    vec![WasmType::I64],
+                    vec![
+                        WasmInstr::LocalGet(0),
+                        WasmInstr::LocalGet(1),
+                        WasmInstr::I64Add,
+                    ],
+                ),
+                function(
+                    "unit_circle",
+                    vec![],
+                    vec![WasmType::I32, WasmType::I64],
+                    vec![
+                        WasmInstr::I32Const(0),
+                        WasmInstr::I64Const(1.5f64.to_bits() as i64),
+                    ],
+                ),
+            ],
+            ..WasmModule::default()
+        };
+        let mut builder = ComponentBuilder::new("shapes".to_string(), "0.1.0".to_string())
+            .with_wit_source(wit.to_string());
+        builder.add_module("main".to_string(), module);
+        let bytes = builder.build().unwrap();
+
+        let outcome = run_bytes(&bytes, "norm", &["{x: 40, y: 2}"]).unwrap();
+        assert_eq!(
+            outcome,
+            RunOutcome::Returned(Some(Box::new(Value::Int(42))))
+        );
+
+        let outcome = run_bytes(&bytes, "unit-circle", &[]).unwrap();
+        assert_eq!(
+            outcome,
+            RunOutcome::Returned(Some(Box::new(constructor(
+                "Circle",
+                vec![Value::Float(1.5)]
+            ))))
+        );
+    }
+}
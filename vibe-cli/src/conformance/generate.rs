@@ -0,0 +1,247 @@
+//! Random well-typed programs
+//!
+//! A program is generated from the type it should have, choosing only
+//! constructs that produce that type, so every program passes the type
+//! checker by construction. Programs stay within what every backend is meant
+//! to run: Int and Bool values, arithmetic and comparisons, `if`,
+//! `let ... in` and applied lambdas.
+//!
+//! Integers are kept well away from overflow, which each backend handles in
+//! its own way: every Int expression carries a bound on its magnitude, and
+//! an operation that could exceed the limit is not generated.
+
+use vibe_language::{Expr, Ident, Literal, Span};
+use vibe_runtime::property::Rng;
+
+/// Largest magnitude a generated Int expression may have
+const LIMIT: i64 = 1 << 40;
+
+/// Types of generated expressions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Bool,
+}
+
+/// A variable in scope, with the bound on its magnitude if it is an Int
+#[derive(Debug, Clone)]
+struct Var {
+    name: String,
+    ty: Ty,
+    bound: i64,
+}
+
+/// Generates programs from a seed; the same seed gives the same programs
+pub struct ProgramGenerator {
+    rng: Rng,
+    max_depth: usize,
+    next_var: usize,
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            max_depth: 4,
+            next_var: 0,
+        }
+    }
+
+    /// Nest expressions at most `depth` deep
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// A program of type Int or Bool
+    pub fn program(&mut self) -> Expr {
+        self.next_var = 0;
+        let ty = if self.rng.below(2) == 0 {
+            Ty::Bool
+        } else {
+            Ty::Int
+        };
+        self.expr(ty, self.max_depth, &mut Vec::new()).0
+    }
+
+    /// An expression of type `ty` and the bound on its magnitude
+    fn expr(&mut self, ty: Ty, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        if depth == 0 {
+            return self.leaf(ty, scope);
+        }
+        match (ty, self.rng.below(11)) {
+            (_, 0..=1) => self.leaf(ty, scope),
+            (_, 2) => self.conditional(ty, depth, scope),
+            (_, 3) => self.let_in(ty, depth, scope),
+            (_, 4) => self.applied_lambda(ty, depth, scope),
+            (Ty::Int, 5..=8) => self.arithmetic(depth, scope),
+            (Ty::Int, _) => self.division(depth, scope),
+            (Ty::Bool, _) => self.comparison(depth, scope),
+        }
+    }
+
+    /// A literal, or a variable in scope
+    fn leaf(&mut self, ty: Ty, scope: &[Var]) -> (Expr, i64) {
+        let vars: Vec<&Var> = scope.iter().filter(|var| var.ty == ty).collect();
+        if !vars.is_empty() && self.rng.below(1) == 0 {
+            let var = vars[self.rng.below(vars.len() - 1)];
+            return (ident(&var.name), var.bound);
+        }
+        match ty {
+            Ty::Int => {
+                let n = self.rng.between(10);
+                (literal(Literal::Int(n)), n.abs())
+            }
+            Ty::Bool => (literal(Literal::Bool(self.rng.below(1) == 0)), 1),
+        }
+    }
+
+    fn conditional(&mut self, ty: Ty, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        let (cond, _) = self.expr(Ty::Bool, depth - 1, scope);
+        let (then_expr, then_bound) = self.expr(ty, depth - 1, scope);
+        let (else_expr, else_bound) = self.expr(ty, depth - 1, scope);
+        let expr = Expr::If {
+            cond: Box::new(cond),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+            span: span(),
+        };
+        (expr, then_bound.max(else_bound))
+    }
+
+    fn let_in(&mut self, ty: Ty, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        let var = self.fresh_var();
+        let (value, bound) = self.expr(var.ty, depth - 1, scope);
+        let name = var.name.clone();
+        scope.push(Var { bound, ..var });
+        let (body, bound) = self.expr(ty, depth - 1, scope);
+        scope.pop();
+        let expr = Expr::LetIn {
+            name: Ident(name),
+            type_ann: None,
+            value: Box::new(value),
+            body: Box::new(body),
+            span: span(),
+        };
+        (expr, bound)
+    }
+
+    /// `(fn x -> body) arg`
+    fn applied_lambda(&mut self, ty: Ty, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        let var = self.fresh_var();
+        let (arg, bound) = self.expr(var.ty, depth - 1, scope);
+        let name = var.name.clone();
+        scope.push(Var { bound, ..var });
+        let (body, bound) = self.expr(ty, depth - 1, scope);
+        scope.pop();
+        let lambda = Expr::Lambda {
+            params: vec![(Ident(name), None)],
+            body: Box::new(body),
+            span: span(),
+        };
+        (apply(lambda, vec![arg]), bound)
+    }
+
+    fn arithmetic(&mut self, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        let (left, left_bound) = self.expr(Ty::Int, depth - 1, scope);
+        let (right, right_bound) = self.expr(Ty::Int, depth - 1, scope);
+        let (op, bound) = match self.rng.below(2) {
+            0 => ("+", left_bound.saturating_add(right_bound)),
+            1 => ("-", left_bound.saturating_add(right_bound)),
+            _ => ("*", left_bound.saturating_mul(right_bound)),
+        };
+        if bound > LIMIT {
+            return (left, left_bound);
+        }
+        (call(op, vec![left, right]), bound)
+    }
+
+    /// Division or remainder by a literal that is not zero
+    fn division(&mut self, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        let (left, bound) = self.expr(Ty::Int, depth - 1, scope);
+        let divisor = (self.rng.below(4) + 1) as i64 * if self.rng.below(1) == 0 { 1 } else { -1 };
+        let divisor_expr = literal(Literal::Int(divisor));
+        if self.rng.below(1) == 0 {
+            (call("/", vec![left, divisor_expr]), bound)
+        } else {
+            let bound = bound.min(divisor.abs() - 1);
+            (call("%", vec![left, divisor_expr]), bound)
+        }
+    }
+
+    fn comparison(&mut self, depth: usize, scope: &mut Vec<Var>) -> (Expr, i64) {
+        let op = ["<", ">", "<=", ">=", "=="][self.rng.below(4)];
+        let (left, _) = self.expr(Ty::Int, depth - 1, scope);
+        let (right, _) = self.expr(Ty::Int, depth - 1, scope);
+        (call(op, vec![left, right]), 1)
+    }
+
+    /// A new variable of a random type; its bound is set once its value is
+    /// generated
+    fn fresh_var(&mut self) -> Var {
+        self.next_var += 1;
+        Var {
+            name: format!("x{}", self.next_var),
+            ty: if self.rng.below(2) == 0 {
+                Ty::Bool
+            } else {
+                Ty::Int
+            },
+            bound: 0,
+        }
+    }
+}
+
+fn span() -> Span {
+    Span::new(0, 0)
+}
+
+fn literal(literal: Literal) -> Expr {
+    Expr::Literal(literal, span())
+}
+
+fn ident(name: &str) -> Expr {
+    Expr::Ident(Ident(name.to_string()), span())
+}
+
+fn apply(func: Expr, args: Vec<Expr>) -> Expr {
+    Expr::Apply {
+        func: Box::new(func),
+        args,
+        span: span(),
+    }
+}
+
+fn call(func: &str, args: Vec<Expr>) -> Expr {
+    apply(ident(func), args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_runtime::Interpreter;
+
+    #[test]
+    fn test_generated_programs_type_check_and_run() {
+        let mut generator = ProgramGenerator::new(7);
+        for _ in 0..300 {
+            let program = generator.program();
+            let ty = vibe_compiler::type_check(&program).unwrap_or_else(|e| {
+                panic!(
+                    "{e}: {}",
+                    vibe_language::pretty_print::pretty_print(&program)
+                )
+            });
+            assert!(
+                matches!(ty, vibe_language::Type::Int | vibe_language::Type::Bool),
+                "{ty}"
+            );
+            Interpreter::new()
+                .eval(&program, &Interpreter::create_initial_env())
+                .unwrap();
+        }
+
+        let first: Vec<Expr> = (0..5).map(|_| ProgramGenerator::new(3).program()).collect();
+        assert!(first.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+}
@@ -0,0 +1,775 @@
+//! Differential conformance testing of the execution backends
+//!
+//! Every program is run on the AST `Interpreter`, which is the reference,
+//! and on each other backend that supports it. A backend that produces a
+//! different value, or fails where the reference succeeds (or the other way
+//! round), diverges. A diverging program is shrunk to the smallest program
+//! that still type checks and still diverges before it is reported.
+//!
+//! Programs come from source files and from [`ProgramGenerator`].
+
+pub mod generate;
+
+use anyhow::Result;
+use colored::*;
+use std::collections::HashMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use vibe_compiler::type_check;
+use vibe_compiler::wasm::program::{compile_program, encode_module, ProgramDefinition};
+use vibe_language::ir::TypedIrExpr;
+use vibe_language::parser::parse;
+use vibe_language::pretty_print::pretty_print;
+use vibe_language::{Expr, Literal, Type, Value};
+use vibe_runtime::{Backend as _, Interpreter, InterpreterBackend};
+use wasmtime::{Engine, Instance, Module, Store};
+
+use crate::cli::format_value;
+pub use generate::ProgramGenerator;
+
+/// Most shrinking steps tried on one diverging program
+const MAX_SHRINK_STEPS: usize = 500;
+
+/// What running a program on a backend produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observed {
+    Value(String),
+    Error(String),
+    /// The backend does not handle this program, so it is not compared
+    Unsupported(String),
+}
+
+impl Observed {
+    /// Whether two results agree; errors agree with any other error, since
+    /// each backend words its errors differently
+    fn agrees_with(&self, other: &Observed) -> bool {
+        match (self, other) {
+            (Observed::Value(a), Observed::Value(b)) => a == b,
+            (Observed::Error(_), Observed::Error(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Observed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Observed::Value(value) => write!(f, "{value}"),
+            Observed::Error(message) => write!(f, "error: {message}"),
+            Observed::Unsupported(reason) => write!(f, "unsupported: {reason}"),
+        }
+    }
+}
+
+/// A way of running well-typed programs
+pub trait Backend {
+    fn name(&self) -> &'static str;
+
+    /// Run `program`, whose type is `ty`
+    fn run(&self, program: &Expr, ty: &Type) -> Observed;
+}
+
+/// The tree-walking AST interpreter, against which the others are compared
+pub struct AstInterpreter;
+
+impl Backend for AstInterpreter {
+    fn name(&self) -> &'static str {
+        "interpreter"
+    }
+
+    fn run(&self, program: &Expr, _ty: &Type) -> Observed {
+        let result =
+            catch_panic(|| Interpreter::new().eval(program, &Interpreter::create_initial_env()));
+        match result {
+            Ok(Ok(value)) => Observed::Value(format_value(&value)),
+            Ok(Err(e)) => Observed::Error(e.to_string()),
+            Err(message) => Observed::Error(message),
+        }
+    }
+}
+
+/// `InterpreterBackend` over typed IR lowered from the program
+pub struct TypedIr;
+
+impl Backend for TypedIr {
+    fn name(&self) -> &'static str {
+        "typed-ir"
+    }
+
+    fn run(&self, program: &Expr, _ty: &Type) -> Observed {
+        let ir = match lower(program, &mut HashMap::new()) {
+            Ok(ir) => ir,
+            Err(reason) => return Observed::Unsupported(reason),
+        };
+        let result = catch_panic(|| {
+            let mut backend = InterpreterBackend::new();
+            let compiled = backend.compile(&ir)?;
+            backend.execute(&compiled)
+        });
+        match result {
+            Ok(Ok(value)) => Observed::Value(format_value(&value)),
+            Ok(Err(e)) => Observed::Error(e.to_string()),
+            Err(message) => Observed::Error(message),
+        }
+    }
+}
+
+/// Builtins `InterpreterBackend` implements, by the name programs use, with
+/// the name it knows them by and their result type
+fn ir_builtin(name: &str) -> Option<(&'static str, Type)> {
+    Some(match name {
+        "+" => ("+", Type::Int),
+        "-" => ("-", Type::Int),
+        "*" => ("*", Type::Int),
+        "/" => ("/", Type::Int),
+        "%" => ("%", Type::Int),
+        "<" => ("<", Type::Bool),
+        ">" => (">", Type::Bool),
+        "<=" => ("<=", Type::Bool),
+        ">=" => (">=", Type::Bool),
+        "=" | "==" => ("=", Type::Bool),
+        _ => return None,
+    })
+}
+
+/// Lower `expr` to typed IR, with the types of the variables in scope
+fn lower(expr: &Expr, scope: &mut HashMap<String, Type>) -> Result<TypedIrExpr, String> {
+    match expr {
+        Expr::Literal(literal, _) => {
+            let ty = match literal {
+                Literal::Int(_) => Type::Int,
+                Literal::Float(_) => Type::Float,
+                Literal::Bool(_) => Type::Bool,
+                Literal::String(_) => Type::String,
+            };
+            Ok(TypedIrExpr::Literal {
+                value: literal.clone(),
+                ty,
+            })
+        }
+        Expr::Ident(name, _) => match scope.get(&name.0) {
+            Some(ty) => Ok(TypedIrExpr::Var {
+                name: name.0.clone(),
+                ty: ty.clone(),
+            }),
+            None => Err(format!("reference to `{}`", name.0)),
+        },
+        Expr::LetIn {
+            name, value, body, ..
+        } => {
+            let value = lower(value, scope)?;
+            let shadowed = scope.insert(name.0.clone(), value.get_type().clone());
+            let body = lower(body, scope);
+            match shadowed {
+                Some(ty) => scope.insert(name.0.clone(), ty),
+                None => scope.remove(&name.0),
+            };
+            let body = body?;
+            Ok(TypedIrExpr::Let {
+                name: name.0.clone(),
+                ty: body.get_type().clone(),
+                value: Box::new(value),
+                body: Box::new(body),
+            })
+        }
+        Expr::If {
+            cond,
+            then_expr,
+            else_expr,
+            ..
+        } => {
+            let then_expr = lower(then_expr, scope)?;
+            Ok(TypedIrExpr::If {
+                cond: Box::new(lower(cond, scope)?),
+                ty: then_expr.get_type().clone(),
+                then_expr: Box::new(then_expr),
+                else_expr: Box::new(lower(else_expr, scope)?),
+            })
+        }
+        Expr::Apply { func, args, .. } => {
+            let Expr::Ident(name, _) = func.as_ref() else {
+                return Err("closures".to_string());
+            };
+            let Some((builtin, ty)) = ir_builtin(&name.0) else {
+                return Err(format!("call of `{}`", name.0));
+            };
+            if scope.contains_key(&name.0) || args.len() != 2 {
+                return Err(format!("call of `{}`", name.0));
+            }
+            let args = args
+                .iter()
+                .map(|arg| lower(arg, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            let arg_types = args.iter().map(|arg| arg.get_type().clone());
+            let func_ty = arg_types.rfold(ty.clone(), |result, arg| {
+                Type::Function(Box::new(arg), Box::new(result))
+            });
+            Ok(TypedIrExpr::Apply {
+                func: Box::new(TypedIrExpr::Var {
+                    name: builtin.to_string(),
+                    ty: func_ty,
+                }),
+                args,
+                ty,
+            })
+        }
+        Expr::Lambda { .. } => Err("closures".to_string()),
+        _ => Err(format!("{} expressions", expr_kind(expr))),
+    }
+}
+
+/// The WASM code generator, run with wasmtime; only programs of type Int or
+/// Bool, whose values a core module can return, are compared
+#[derive(Default)]
+pub struct Wasm {
+    engine: Engine,
+}
+
+impl Backend for Wasm {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn run(&self, program: &Expr, ty: &Type) -> Observed {
+        if !matches!(ty, Type::Int | Type::Bool) {
+            return Observed::Unsupported(format!("result of type {ty}"));
+        }
+        let definition = ProgramDefinition {
+            name: "main".to_string(),
+            expr: program.clone(),
+        };
+        let compiled = match catch_panic(|| compile_program(&[definition], "main")) {
+            Ok(Ok(compiled)) => compiled,
+            Ok(Err(e)) => return Observed::Unsupported(e.to_string()),
+            Err(message) => return Observed::Error(message),
+        };
+        let result = encode_module(&compiled)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                let module = Module::new(&self.engine, &bytes).map_err(|e| e.to_string())?;
+                let mut store = Store::new(&self.engine, ());
+                let instance =
+                    Instance::new(&mut store, &module, &[]).map_err(|e| e.to_string())?;
+                let main = instance
+                    .get_typed_func::<(), i64>(&mut store, "main")
+                    .map_err(|e| e.to_string())?;
+                main.call(&mut store, ()).map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(n) if *ty == Type::Bool => Observed::Value(format_value(&Value::Bool(n != 0))),
+            Ok(n) => Observed::Value(format_value(&Value::Int(n))),
+            Err(message) => Observed::Error(message),
+        }
+    }
+}
+
+/// Run `f`, turning a panic into its message
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        format!("panicked: {message}")
+    })
+}
+
+fn expr_kind(expr: &Expr) -> String {
+    let debug = format!("{expr:?}");
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or("unknown")
+        .to_lowercase()
+}
+
+/// A well-typed program to run
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// File the program was read from, or `generated #n`
+    pub name: String,
+    pub expr: Expr,
+    pub ty: Type,
+}
+
+impl Program {
+    /// `None` if `expr` does not type check
+    pub fn new(name: impl Into<String>, expr: Expr) -> Option<Self> {
+        let ty = type_check(&expr).ok()?;
+        Some(Self {
+            name: name.into(),
+            expr,
+            ty,
+        })
+    }
+}
+
+/// A file that was not run, and why
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Parse and type check every `.vibe` file under `paths`
+pub fn load_programs(paths: &[PathBuf]) -> (Vec<Program>, Vec<Skipped>) {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_files(path, &mut files);
+    }
+    files.sort();
+
+    let mut programs = Vec::new();
+    let mut skipped = Vec::new();
+    for path in files {
+        let name = path.display().to_string();
+        let loaded = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| parse(&source).map_err(|e| format!("parse error: {e}")))
+            .and_then(|expr| match type_check(&expr) {
+                Ok(ty) => Ok(Program { name, expr, ty }),
+                Err(e) => Err(format!("type error: {e}")),
+            });
+        match loaded {
+            Ok(program) => programs.push(program),
+            Err(reason) => skipped.push(Skipped { path, reason }),
+        }
+    }
+    (programs, skipped)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            collect_files(&entry.path(), files);
+        }
+    } else if path.extension().and_then(|s| s.to_str()) == Some("vibe") {
+        files.push(path.to_path_buf());
+    }
+}
+
+/// Programs that behave differently on different backends
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub name: String,
+    /// The program as found
+    pub original: Expr,
+    /// The smallest program found that still diverges
+    pub minimized: Expr,
+    /// What each backend produced for the minimized program
+    pub results: Vec<(&'static str, Observed)>,
+}
+
+/// Outcome of a conformance run
+#[derive(Debug, Default)]
+pub struct Report {
+    pub programs: usize,
+    /// Programs each backend ran, by backend
+    pub runs: Vec<(&'static str, usize)>,
+    pub skipped: Vec<Skipped>,
+    pub divergences: Vec<Divergence>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Runs programs on a reference backend and compares the others with it
+pub struct Harness {
+    reference: Box<dyn Backend>,
+    backends: Vec<Box<dyn Backend>>,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new(Box::new(AstInterpreter))
+            .with_backend(Box::new(TypedIr))
+            .with_backend(Box::new(Wasm::default()))
+    }
+}
+
+impl Harness {
+    pub fn new(reference: Box<dyn Backend>) -> Self {
+        Self {
+            reference,
+            backends: Vec::new(),
+        }
+    }
+
+    pub fn with_backend(mut self, backend: Box<dyn Backend>) -> Self {
+        self.backends.push(backend);
+        self
+    }
+
+    /// What every backend produces for `expr`
+    fn results(&self, expr: &Expr, ty: &Type) -> Vec<(&'static str, Observed)> {
+        std::iter::once(&self.reference)
+            .chain(&self.backends)
+            .map(|backend| (backend.name(), backend.run(expr, ty)))
+            .collect()
+    }
+
+    fn diverges(results: &[(&'static str, Observed)]) -> bool {
+        let (reference, others) = results.split_first().expect("reference result");
+        others.iter().any(|(_, observed)| {
+            !matches!(observed, Observed::Unsupported(_)) && !observed.agrees_with(&reference.1)
+        })
+    }
+
+    /// Run `program` and, if the backends disagree, shrink it
+    pub fn check(&self, program: &Program) -> (Vec<(&'static str, Observed)>, Option<Divergence>) {
+        let results = self.results(&program.expr, &program.ty);
+        if !Self::diverges(&results) {
+            return (results, None);
+        }
+        let minimized = self.minimize(&program.expr, &program.ty);
+        let divergence = Divergence {
+            name: program.name.clone(),
+            original: program.expr.clone(),
+            results: self.results(&minimized, &program.ty),
+            minimized,
+        };
+        (results, Some(divergence))
+    }
+
+    /// Shrink a diverging program of type `ty` greedily: take the first
+    /// smaller program that still has type `ty` and still diverges, until
+    /// there is none
+    pub fn minimize(&self, expr: &Expr, ty: &Type) -> Expr {
+        let mut current = expr.clone();
+        let mut steps = 0;
+        'shrink: while steps < MAX_SHRINK_STEPS {
+            let size = measure(&current);
+            for candidate in shrinks(&current) {
+                steps += 1;
+                if measure(&candidate) >= size {
+                    continue;
+                }
+                if type_check(&candidate).ok().as_ref() != Some(ty) {
+                    continue;
+                }
+                if Self::diverges(&self.results(&candidate, ty)) {
+                    current = candidate;
+                    continue 'shrink;
+                }
+                if steps >= MAX_SHRINK_STEPS {
+                    break 'shrink;
+                }
+            }
+            break;
+        }
+        current
+    }
+
+    /// Run `programs`, then `generated` programs from `seed`
+    pub fn run(&self, programs: &[Program], generated: usize, seed: u64) -> Report {
+        let mut report = Report {
+            runs: std::iter::once(&self.reference)
+                .chain(&self.backends)
+                .map(|backend| (backend.name(), 0))
+                .collect(),
+            ..Report::default()
+        };
+        let mut generator = ProgramGenerator::new(seed);
+        let generated = (0..generated)
+            .filter_map(|n| Program::new(format!("generated #{}", n + 1), generator.program()));
+        for program in programs.iter().cloned().chain(generated) {
+            report.programs += 1;
+            let (results, divergence) = self.check(&program);
+            for ((_, count), (_, observed)) in report.runs.iter_mut().zip(&results) {
+                if !matches!(observed, Observed::Unsupported(_)) {
+                    *count += 1;
+                }
+            }
+            report.divergences.extend(divergence);
+        }
+        report
+    }
+}
+
+/// Size of a program: its node count, then the magnitude of its integers
+fn measure(expr: &Expr) -> (usize, u64) {
+    let mut nodes = 0;
+    let mut magnitude = 0u64;
+    visit(expr, &mut |expr| {
+        nodes += 1;
+        if let Expr::Literal(Literal::Int(n), _) = expr {
+            magnitude = magnitude.saturating_add(n.unsigned_abs());
+        }
+    });
+    (nodes, magnitude)
+}
+
+fn visit(expr: &Expr, f: &mut impl FnMut(&Expr)) {
+    f(expr);
+    for child in children(expr) {
+        visit(child, f);
+    }
+}
+
+/// The subexpressions shrinking looks into
+fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::If {
+            cond,
+            then_expr,
+            else_expr,
+            ..
+        } => vec![cond, then_expr, else_expr],
+        Expr::LetIn { value, body, .. } => vec![value, body],
+        Expr::Lambda { body, .. } => vec![body],
+        Expr::Apply { func, args, .. } => std::iter::once(func.as_ref()).chain(args).collect(),
+        Expr::Block { exprs, .. } => exprs.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `expr` with its children replaced by `children`, in the order
+/// [`children`] lists them
+fn with_children(expr: &Expr, mut children: Vec<Expr>) -> Expr {
+    let mut next = || Box::new(children.remove(0));
+    match expr {
+        Expr::If { span, .. } => Expr::If {
+            cond: next(),
+            then_expr: next(),
+            else_expr: next(),
+            span: span.clone(),
+        },
+        Expr::LetIn {
+            name,
+            type_ann,
+            span,
+            ..
+        } => Expr::LetIn {
+            name: name.clone(),
+            type_ann: type_ann.clone(),
+            value: next(),
+            body: next(),
+            span: span.clone(),
+        },
+        Expr::Lambda { params, span, .. } => Expr::Lambda {
+            params: params.clone(),
+            body: next(),
+            span: span.clone(),
+        },
+        Expr::Apply { span, .. } => {
+            let func = next();
+            Expr::Apply {
+                func,
+                args: children,
+                span: span.clone(),
+            }
+        }
+        Expr::Block { span, .. } => Expr::Block {
+            exprs: children,
+            span: span.clone(),
+        },
+        _ => expr.clone(),
+    }
+}
+
+/// Programs one step smaller than `expr`, the most drastic first
+fn shrinks(expr: &Expr) -> Vec<Expr> {
+    let mut candidates: Vec<Expr> = children(expr).into_iter().cloned().collect();
+    match expr {
+        Expr::Literal(Literal::Int(n), span) if *n != 0 => {
+            for smaller in [0, n / 2] {
+                candidates.push(Expr::Literal(Literal::Int(smaller), span.clone()));
+            }
+        }
+        Expr::Block { exprs, .. } => {
+            for i in 0..exprs.len() {
+                let mut rest = exprs.clone();
+                rest.remove(i);
+                candidates.push(with_children(expr, rest));
+            }
+        }
+        _ => {}
+    }
+
+    let own: Vec<Expr> = children(expr).into_iter().cloned().collect();
+    for (i, child) in own.iter().enumerate() {
+        for smaller in shrinks(child) {
+            let mut replaced = own.clone();
+            replaced[i] = smaller;
+            candidates.push(with_children(expr, replaced));
+        }
+    }
+    candidates
+}
+
+/// Directories conformance runs on when given none
+pub fn default_paths() -> Vec<PathBuf> {
+    ["examples", "vibe/lib", "vibe/tests"]
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+/// Format `report` for the terminal
+pub fn format_report(report: &Report) -> String {
+    let mut out = String::new();
+    for divergence in &report.divergences {
+        out.push_str(&format!(
+            "{} {}\n",
+            "Divergence:".red().bold(),
+            divergence.name
+        ));
+        out.push_str(&format!(
+            "  minimized program:\n    {}\n",
+            pretty_print(&divergence.minimized).replace('\n', "\n    ")
+        ));
+        for (backend, observed) in &divergence.results {
+            out.push_str(&format!("  {backend:<12} {observed}\n"));
+        }
+        out.push('\n');
+    }
+    let runs: Vec<String> = report
+        .runs
+        .iter()
+        .map(|(backend, count)| format!("{backend} {count}"))
+        .collect();
+    out.push_str(&format!(
+        "{} programs ({} files skipped), runs: {}\n",
+        report.programs,
+        report.skipped.len(),
+        runs.join(", ")
+    ));
+    if report.passed() {
+        out.push_str(&format!("{}\n", "All backends agree".green()));
+    } else {
+        out.push_str(&format!(
+            "{}\n",
+            format!("{} programs diverge", report.divergences.len()).red()
+        ));
+    }
+    out
+}
+
+/// `vibe conformance`: returns whether every backend agreed
+pub fn run_conformance(
+    paths: Vec<PathBuf>,
+    generated: usize,
+    seed: u64,
+    verbose: bool,
+) -> Result<bool> {
+    let paths = if paths.is_empty() {
+        default_paths()
+    } else {
+        paths
+    };
+    let (programs, skipped) = load_programs(&paths);
+    if verbose {
+        for skip in &skipped {
+            eprintln!("Skipped {}: {}", skip.path.display(), skip.reason);
+        }
+    }
+    let mut report = Harness::default().run(&programs, generated, seed);
+    report.skipped = skipped;
+    print!("{}", format_report(&report));
+    Ok(report.passed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::{Ident, Span};
+
+    /// The interpreter with `-` evaluated as `+`
+    struct SwapsMinus;
+
+    impl Backend for SwapsMinus {
+        fn name(&self) -> &'static str {
+            "swaps-minus"
+        }
+
+        fn run(&self, program: &Expr, ty: &Type) -> Observed {
+            fn swap(expr: &Expr) -> Expr {
+                match expr {
+                    Expr::Ident(name, span) if name.0 == "-" => {
+                        Expr::Ident(Ident("+".to_string()), span.clone())
+                    }
+                    _ => with_children(expr, children(expr).into_iter().map(swap).collect()),
+                }
+            }
+            AstInterpreter.run(&swap(program), ty)
+        }
+    }
+
+    #[test]
+    fn test_divergence_is_found_and_minimized() {
+        let harness = Harness::new(Box::new(AstInterpreter)).with_backend(Box::new(SwapsMinus));
+        let mut generator = ProgramGenerator::new(11).with_max_depth(5);
+        let divergence = (0..200)
+            .filter_map(|_| Program::new("generated", generator.program()))
+            .find_map(|program| harness.check(&program).1)
+            .expect("a program that subtracts");
+
+        assert!(measure(&divergence.minimized) < measure(&divergence.original));
+        // `0 - 1`, or `1 - 0`: nothing smaller tells `-` from `+`
+        assert_eq!(
+            measure(&divergence.minimized),
+            (4, 1),
+            "{:?}",
+            divergence.minimized
+        );
+        let Expr::Apply { func, args, .. } = &divergence.minimized else {
+            panic!("{:?}", divergence.minimized);
+        };
+        assert_eq!(**func, Expr::Ident(Ident("-".to_string()), Span::new(0, 0)));
+        assert!(args.iter().all(|arg| matches!(arg, Expr::Literal(..))));
+        assert_ne!(divergence.results[0].1, divergence.results[1].1);
+    }
+
+    #[test]
+    fn test_lowering_to_typed_ir() {
+        let span = Span::new(0, 0);
+        let var = |name: &str| Expr::Ident(Ident(name.to_string()), span.clone());
+        let int = |n: i64| Expr::Literal(Literal::Int(n), span.clone());
+        let call = |func: &str, args: Vec<Expr>| Expr::Apply {
+            func: Box::new(var(func)),
+            args,
+            span: span.clone(),
+        };
+
+        // let x = 3 in if x < 4 then x * 2 else 0
+        let program = Expr::LetIn {
+            name: Ident("x".to_string()),
+            type_ann: None,
+            value: Box::new(int(3)),
+            body: Box::new(Expr::If {
+                cond: Box::new(call("<", vec![var("x"), int(4)])),
+                then_expr: Box::new(call("*", vec![var("x"), int(2)])),
+                else_expr: Box::new(int(0)),
+                span: span.clone(),
+            }),
+            span: span.clone(),
+        };
+        assert_eq!(
+            TypedIr.run(&program, &Type::Int),
+            Observed::Value("6".to_string())
+        );
+
+        let lambda = Expr::Apply {
+            func: Box::new(Expr::Lambda {
+                params: vec![(Ident("y".to_string()), None)],
+                body: Box::new(var("y")),
+                span: span.clone(),
+            }),
+            args: vec![int(1)],
+            span: span.clone(),
+        };
+        assert!(matches!(
+            TypedIr.run(&lambda, &Type::Int),
+            Observed::Unsupported(_)
+        ));
+    }
+}
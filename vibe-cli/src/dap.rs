@@ -0,0 +1,433 @@
+//! Debug Adapter Protocol server for the step debugger
+//!
+//! Editors talk to the adapter over stdio or TCP with `Content-Length` framed
+//! JSON messages. The program runs on its own thread; while it is paused the
+//! adapter answers stack, scope and variable requests from the paused call
+//! stack until the editor asks to continue or step.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value as Json};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use vibe_compiler::type_check;
+use vibe_language::parser::parse;
+use vibe_language::{Expr, Ident, Span};
+use vibe_runtime::debugger::{
+    Breakpoints, DebugCommand, DebugFrontend, Debugger, Frame, StopEvent, StopReason,
+};
+use vibe_runtime::Interpreter;
+
+const THREAD_ID: i64 = 1;
+
+/// Read one framed message. Returns `None` at end of input.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+
+    let length = length.context("Message without Content-Length header")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Write one framed message
+pub fn write_message<W: Write + ?Sized>(writer: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Outgoing side of the connection, shared with the program thread
+struct Connection {
+    writer: Mutex<Box<dyn Write + Send>>,
+    seq: AtomicI64,
+}
+
+impl Connection {
+    fn send(&self, mut message: Json) {
+        message["seq"] = json!(self.seq.fetch_add(1, Ordering::SeqCst) + 1);
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // A closed connection leaves nobody to report the error to
+        let _ = write_message(&mut **writer, &message);
+    }
+
+    fn event(&self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn respond(&self, request: &Json, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    fn fail(&self, request: &Json, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }));
+    }
+}
+
+/// Call stack of the paused program, if it is paused
+type PausedStack = Arc<Mutex<Option<StopEvent>>>;
+
+/// Reports pauses as `stopped` events and waits for the editor's next step
+struct DapFrontend {
+    connection: Arc<Connection>,
+    paused: PausedStack,
+    commands: Receiver<DebugCommand>,
+}
+
+impl DebugFrontend for DapFrontend {
+    fn pause(&mut self, stop: &StopEvent) -> DebugCommand {
+        let (reason, description) = match &stop.reason {
+            StopReason::Breakpoint(name) => {
+                ("function breakpoint", format!("Breakpoint on {name}"))
+            }
+            StopReason::Step => ("step", "Step".to_string()),
+            StopReason::Entry => ("entry", "Entry".to_string()),
+        };
+        *self.paused.lock().unwrap_or_else(|e| e.into_inner()) = Some(stop.clone());
+        self.connection.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        );
+
+        // Once the editor has disconnected, run to the end
+        let command = self.commands.recv().unwrap_or(DebugCommand::Continue);
+        *self.paused.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        command
+    }
+
+    fn output(&mut self, text: &str) -> bool {
+        self.connection.event(
+            "output",
+            json!({ "category": "stdout", "output": format!("{text}\n") }),
+        );
+        true
+    }
+}
+
+/// A program loaded by `launch`
+struct Program {
+    path: PathBuf,
+    source: String,
+    expr: Expr,
+    stop_on_entry: bool,
+}
+
+impl Program {
+    fn load(path: &Path, stop_on_entry: bool) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let expr = parse(&source).map_err(|e| anyhow::anyhow!("Parse error: {}", e))?;
+        type_check(&expr).map_err(|e| anyhow::anyhow!("Type error: {}", e))?;
+
+        // .vibe files run their main function, like `vibe run`
+        let expr = if path.extension().and_then(|e| e.to_str()) == Some("vibe") {
+            Expr::Block {
+                exprs: vec![
+                    expr,
+                    Expr::Apply {
+                        func: Box::new(Expr::Ident(Ident("main".to_string()), Span::new(0, 0))),
+                        args: vec![],
+                        span: Span::new(0, 0),
+                    },
+                ],
+                span: Span::new(0, 0),
+            }
+        } else {
+            expr
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            source,
+            expr,
+            stop_on_entry,
+        })
+    }
+
+    /// 1-based line and column of a source offset
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.source[..offset.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        (line, column)
+    }
+}
+
+/// Serve one debugging session until the editor disconnects or the input ends
+pub fn serve<R: BufRead>(mut reader: R, writer: impl Write + Send + 'static) -> Result<()> {
+    let connection = Arc::new(Connection {
+        writer: Mutex::new(Box::new(writer)),
+        seq: AtomicI64::new(0),
+    });
+    let paused = PausedStack::default();
+    let breakpoints = Breakpoints::default();
+    let (commands, command_receiver) = mpsc::channel();
+    let mut command_receiver = Some(command_receiver);
+    let mut program: Option<Arc<Program>> = None;
+
+    while let Some(request) = read_message(&mut reader)? {
+        let args = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                connection.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsFunctionBreakpoints": true,
+                    }),
+                );
+                connection.event("initialized", json!({}));
+            }
+
+            "launch" => {
+                let Some(path) = args["program"].as_str() else {
+                    connection.fail(&request, "launch requires a program");
+                    continue;
+                };
+                let stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                match Program::load(Path::new(path), stop_on_entry) {
+                    Ok(loaded) => {
+                        program = Some(Arc::new(loaded));
+                        connection.respond(&request, json!({}));
+                    }
+                    Err(e) => connection.fail(&request, &e.to_string()),
+                }
+            }
+
+            "setFunctionBreakpoints" => {
+                let names: Vec<String> = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|b| b["name"].as_str().map(str::to_string))
+                    .collect();
+                let verified: Vec<_> = names.iter().map(|_| json!({ "verified": true })).collect();
+                *breakpoints.lock().unwrap_or_else(|e| e.into_inner()) =
+                    names.into_iter().collect();
+                connection.respond(&request, json!({ "breakpoints": verified }));
+            }
+
+            "setBreakpoints" => {
+                let unverified: Vec<_> = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|_| {
+                        json!({
+                            "verified": false,
+                            "message": "Only function breakpoints are supported",
+                        })
+                    })
+                    .collect();
+                connection.respond(&request, json!({ "breakpoints": unverified }));
+            }
+
+            "setExceptionBreakpoints" => connection.respond(&request, json!({})),
+
+            "configurationDone" => {
+                connection.respond(&request, json!({}));
+                if let (Some(program), Some(receiver)) = (&program, command_receiver.take()) {
+                    let frontend = DapFrontend {
+                        connection: connection.clone(),
+                        paused: paused.clone(),
+                        commands: receiver,
+                    };
+                    spawn_program(program.clone(), frontend, breakpoints.clone());
+                }
+            }
+
+            "threads" => connection.respond(
+                &request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            ),
+
+            "stackTrace" => {
+                let paused = paused.lock().unwrap_or_else(|e| e.into_inner());
+                let (Some(stop), Some(program)) = (paused.as_ref(), &program) else {
+                    connection.fail(&request, "The program is not paused");
+                    continue;
+                };
+                let frames: Vec<_> = stop
+                    .frames
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(id, frame)| {
+                        let (line, column) = program.position(frame.span.start);
+                        json!({
+                            "id": id,
+                            "name": frame.to_string(),
+                            "line": line,
+                            "column": column,
+                            "source": { "path": program.path },
+                        })
+                    })
+                    .collect();
+                connection.respond(
+                    &request,
+                    json!({ "stackFrames": frames, "totalFrames": frames.len() }),
+                );
+            }
+
+            "scopes" => {
+                let frame_id = args["frameId"].as_u64().unwrap_or_default();
+                connection.respond(
+                    &request,
+                    json!({
+                        "scopes": [{
+                            "name": "Locals",
+                            "variablesReference": frame_id + 1,
+                            "expensive": false,
+                        }]
+                    }),
+                );
+            }
+
+            "variables" => {
+                let reference = args["variablesReference"].as_u64().unwrap_or_default();
+                let paused = paused.lock().unwrap_or_else(|e| e.into_inner());
+                let frame = paused
+                    .as_ref()
+                    .and_then(|stop| stop.frames.get((reference as usize).checked_sub(1)?));
+                let variables: Vec<_> = frame
+                    .map(Frame::bindings)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| {
+                        json!({
+                            "name": name,
+                            "value": value.to_string(),
+                            "variablesReference": 0,
+                        })
+                    })
+                    .collect();
+                connection.respond(&request, json!({ "variables": variables }));
+            }
+
+            "evaluate" => {
+                let expression = args["expression"].as_str().unwrap_or_default().trim();
+                let paused = paused.lock().unwrap_or_else(|e| e.into_inner());
+                let value = paused.as_ref().and_then(|stop| {
+                    let frame = match args["frameId"].as_u64() {
+                        Some(id) => stop.frames.get(id as usize),
+                        None => stop.frames.last(),
+                    };
+                    frame?.lookup(expression)
+                });
+                match value {
+                    Some(value) => connection.respond(
+                        &request,
+                        json!({ "result": value.to_string(), "variablesReference": 0 }),
+                    ),
+                    None => connection.fail(&request, &format!("{expression} is not bound here")),
+                }
+            }
+
+            command @ ("continue" | "next" | "stepIn" | "stepOut") => {
+                let step = match command {
+                    "continue" => DebugCommand::Continue,
+                    "next" => DebugCommand::StepOver,
+                    "stepIn" => DebugCommand::StepIn,
+                    _ => DebugCommand::StepOut,
+                };
+                // Respond first: once resumed, the program may send events
+                connection.respond(&request, json!({ "allThreadsContinued": true }));
+                let _ = commands.send(step);
+            }
+
+            "disconnect" | "terminate" => {
+                breakpoints
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clear();
+                connection.respond(&request, json!({}));
+                break;
+            }
+
+            other => connection.fail(&request, &format!("Unsupported request: {other}")),
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_program(program: Arc<Program>, frontend: DapFrontend, breakpoints: Breakpoints) {
+    let connection = frontend.connection.clone();
+    let mut debugger = Debugger::new(Box::new(frontend)).with_breakpoint_handle(breakpoints);
+    if program.stop_on_entry {
+        debugger = debugger.stop_on_entry();
+    }
+
+    thread::spawn(move || {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_observer(Some(Arc::new(Mutex::new(debugger))));
+        let env = Interpreter::create_initial_env();
+        let exit_code = match interpreter.eval(&program.expr, &env) {
+            Ok(value) => {
+                connection.event(
+                    "output",
+                    json!({ "category": "console", "output": format!("=> {value}\n") }),
+                );
+                0
+            }
+            Err(e) => {
+                connection.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("Error: {e}\n") }),
+                );
+                1
+            }
+        };
+        connection.event("exited", json!({ "exitCode": exit_code }));
+        connection.event("terminated", json!({}));
+    });
+}
+
+/// Run the adapter on stdio, or on a TCP port for a single connection
+pub fn run_debug_adapter(port: Option<u16>) -> Result<()> {
+    match port {
+        Some(port) => {
+            let addr = format!("127.0.0.1:{port}");
+            let listener = std::net::TcpListener::bind(&addr)?;
+            eprintln!("Debug adapter listening on {addr}");
+            let (stream, _) = listener.accept()?;
+            let writer = stream.try_clone()?;
+            serve(io::BufReader::new(stream), writer)
+        }
+        None => serve(io::BufReader::new(io::stdin()), io::stdout()),
+    }
+}
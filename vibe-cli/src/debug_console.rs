@@ -0,0 +1,140 @@
+//! Console frontend for the step debugger used by the shell's `debug` command
+
+use std::io::{self, BufRead, BufReader, Stdin, Stdout, Write};
+use vibe_runtime::debugger::{DebugCommand, DebugFrontend, Frame, StopEvent, StopReason};
+
+const HELP: &str = "\
+  c, continue      Run to the next breakpoint
+  s, step          Step into the next application
+  n, next          Step over the current application
+  o, out           Run until the current function returns
+  bt, backtrace    Show the call stack
+  l, locals        Show the bindings of the current frame
+  p, print <name>  Show the value of a binding";
+
+/// Reads debugger commands line by line. End of input continues the program.
+pub struct ConsoleFrontend<R, W> {
+    input: R,
+    output: W,
+}
+
+impl ConsoleFrontend<BufReader<Stdin>, Stdout> {
+    pub fn stdio() -> Self {
+        Self::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> ConsoleFrontend<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
+    /// Run one console command. Returns how to continue, or `None` to keep
+    /// reading commands.
+    fn handle_line(&mut self, line: &str, stop: &StopEvent) -> io::Result<Option<DebugCommand>> {
+        let frame = stop.frames.last();
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("c" | "continue"), _) => return Ok(Some(DebugCommand::Continue)),
+            (Some("s" | "step"), _) => return Ok(Some(DebugCommand::StepIn)),
+            (Some("n" | "next"), _) => return Ok(Some(DebugCommand::StepOver)),
+            (Some("o" | "out"), _) => return Ok(Some(DebugCommand::StepOut)),
+            (Some("bt" | "backtrace"), _) => {
+                for (i, frame) in stop.frames.iter().rev().enumerate() {
+                    writeln!(self.output, "  #{i} {frame}")?;
+                }
+            }
+            (Some("l" | "locals"), _) => {
+                for (name, value) in frame.map(Frame::bindings).unwrap_or_default() {
+                    writeln!(self.output, "  {name} = {value}")?;
+                }
+            }
+            (Some("p" | "print"), Some(name)) => match frame.and_then(|f| f.lookup(name)) {
+                Some(value) => writeln!(self.output, "  {name} = {value}")?,
+                None => writeln!(self.output, "  {name} is not bound here")?,
+            },
+            (Some("h" | "help"), _) => writeln!(self.output, "{HELP}")?,
+            (Some(other), _) => {
+                writeln!(self.output, "Unknown command `{other}`, type `help`")?;
+            }
+            (None, _) => {}
+        }
+        Ok(None)
+    }
+
+    fn prompt(&mut self, stop: &StopEvent) -> io::Result<DebugCommand> {
+        let call = stop.frames.last().map(Frame::to_string).unwrap_or_default();
+        match &stop.reason {
+            StopReason::Breakpoint(name) => {
+                writeln!(self.output, "Breakpoint on {name}: {call}")?;
+            }
+            StopReason::Entry | StopReason::Step => writeln!(self.output, "Stopped at {call}")?,
+        }
+
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(DebugCommand::Continue);
+            }
+            if let Some(command) = self.handle_line(line.trim(), stop)? {
+                return Ok(command);
+            }
+        }
+    }
+}
+
+impl<R: BufRead + Send, W: Write + Send> DebugFrontend for ConsoleFrontend<R, W> {
+    fn pause(&mut self, stop: &StopEvent) -> DebugCommand {
+        self.prompt(stop).unwrap_or(DebugCommand::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::{Environment, Ident, Span, Value};
+
+    fn stop() -> StopEvent {
+        let frame = |function: &str, arg: i64| Frame {
+            function: function.to_string(),
+            params: vec!["x".to_string()],
+            args: vec![Value::Int(arg)],
+            span: Span::new(0, 0),
+            env: Environment::new().extend(Ident("base".to_string()), Value::Int(10)),
+        };
+        StopEvent {
+            reason: StopReason::Breakpoint("double".to_string()),
+            frames: vec![frame("quad", 3), frame("double", 3)],
+        }
+    }
+
+    #[test]
+    fn test_console_commands() {
+        let input = "bt\nlocals\np base\np y\nwat\nn\n";
+        let mut console = ConsoleFrontend::new(input.as_bytes(), Vec::new());
+        assert_eq!(console.pause(&stop()), DebugCommand::StepOver);
+
+        let output = String::from_utf8(console.into_output()).unwrap();
+        assert!(
+            output.starts_with("Breakpoint on double: double 3\n"),
+            "{output}"
+        );
+        assert!(output.contains("  #0 double 3\n  #1 quad 3\n"), "{output}");
+        assert!(output.contains("  x = 3\n  base = 10\n"), "{output}");
+        assert!(output.contains("  base = 10\n"), "{output}");
+        assert!(output.contains("  y is not bound here"), "{output}");
+        assert!(output.contains("Unknown command `wat`"), "{output}");
+    }
+
+    #[test]
+    fn test_end_of_input_continues() {
+        let mut console = ConsoleFrontend::new("".as_bytes(), Vec::new());
+        assert_eq!(console.pause(&stop()), DebugCommand::Continue);
+    }
+}
@@ -8,19 +8,28 @@ use colored::Colorize;
 // CLI modules
 pub mod cli;
 pub mod component_commands;
+pub mod component_runtime;
 pub mod package_commands;
+pub mod wasm_build;
+pub mod workspace_commands;
 
 // Shell modules
 pub mod api;
 pub mod commands;
+pub mod debug_console;
 pub mod hole_completion;
+pub mod module_export;
 pub mod multi_store;
+pub mod namespace_history;
 pub mod search_patterns;
 pub mod shell;
 
 // MCP (Model Context Protocol) module
 pub mod mcp;
 
+// DAP (Debug Adapter Protocol) module
+pub mod dap;
+
 // LSP (Language Server Protocol) module
 pub mod lsp;
 
@@ -28,6 +37,7 @@ pub mod lsp;
 pub mod test_runner;
 pub mod incremental_bench;
 pub mod wasm_bench;
+pub mod conformance;
 
 // Re-export important types
 pub use cli::{run_cli, Args, Command};
@@ -1,4 +1,5 @@
 use dashmap::DashMap;
+use std::sync::{Arc, Mutex};
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
@@ -6,11 +7,15 @@ use tracing::{debug, error, info};
 
 use super::source_map::SourceMap;
 use super::text_document::TextDocuments;
+use vibe_codebase::database::XsDatabaseImpl;
+use vibe_codebase::{CodebaseQueries, ModuleDiagnostic, ModuleId, SyntaxDiagnostic};
 
 pub struct XSLanguageServer {
     client: Client,
     documents: TextDocuments,
     source_maps: DashMap<Url, SourceMap>,
+    /// Incremental database; each open document is a module keyed by its URI
+    database: Mutex<XsDatabaseImpl>,
 }
 
 impl XSLanguageServer {
@@ -19,6 +24,7 @@ impl XSLanguageServer {
             client,
             documents: TextDocuments::new(),
             source_maps: DashMap::new(),
+            database: Mutex::new(XsDatabaseImpl::new()),
         }
     }
 
@@ -42,88 +48,98 @@ impl XSLanguageServer {
 
         debug!("Analyzing document: {}", uri);
 
-        // Parse the document
-        match vibe_language::parser::parse(&content) {
-            Ok(expr) => {
-                // Generate source map
-                let source_map = SourceMap::from_ast(&expr, &content);
-                self.source_maps.insert(uri.clone(), source_map);
-
-                // Type check
-                match vibe_compiler::type_check(&expr) {
-                    Ok(_type) => {
-                        // Clear diagnostics on successful type check
-                        self.client
-                            .publish_diagnostics(uri.clone(), vec![], None)
-                            .await;
-                    }
-                    Err(e) => {
-                        // Convert type errors to diagnostics
-                        let diagnostic = self.type_error_to_diagnostic(e, uri);
-                        self.client
-                            .publish_diagnostics(uri.clone(), vec![diagnostic], None)
-                            .await;
-                    }
+        // Only the top-level definitions that changed are re-parsed and
+        // re-checked, along with the definitions that depend on them
+        let (expr, module_diagnostics) = {
+            let mut db = self.database.lock().unwrap();
+            let module = ModuleId(uri.to_string());
+            db.set_module_text(module.clone(), Arc::new(content.clone()));
+            (
+                db.module_expr(module.clone()),
+                db.module_diagnostics(module),
+            )
+        };
+
+        let source_map = SourceMap::from_ast(&expr, &content);
+
+        // Type errors are only meaningful once the document parses
+        let has_syntax_errors = module_diagnostics
+            .iter()
+            .any(|d| matches!(d, ModuleDiagnostic::Syntax(_)));
+        let diagnostics = module_diagnostics
+            .iter()
+            .filter_map(|diagnostic| match diagnostic {
+                ModuleDiagnostic::Syntax(error) => {
+                    Some(self.parse_error_to_diagnostic(error, &content, &source_map))
                 }
-            }
-            Err(e) => {
-                // Convert parse errors to diagnostics
-                let diagnostic = self.parse_error_to_diagnostic(e, uri);
-                self.client
-                    .publish_diagnostics(uri.clone(), vec![diagnostic], None)
-                    .await;
-            }
-        }
+                ModuleDiagnostic::Type(error) if !has_syntax_errors => {
+                    Some(self.type_error_to_diagnostic(error, uri, &source_map))
+                }
+                ModuleDiagnostic::Type(_) => None,
+            })
+            .collect();
+
+        self.source_maps.insert(uri.clone(), source_map);
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics, None)
+            .await;
 
         Ok(())
     }
 
-    fn parse_error_to_diagnostic(&self, error: vibe_language::XsError, _uri: &Url) -> Diagnostic {
-        let (position, message) = match error {
-            vibe_language::XsError::ParseError(pos, msg) => (pos, msg),
-            _ => (0, format!("{}", error)),
-        };
-
-        let range = Range {
-            start: Position {
-                line: 0, // TODO: Convert position to line/column
-                character: position as u32,
-            },
-            end: Position {
-                line: 0,
-                character: (position + 1) as u32,
-            },
-        };
+    fn parse_error_to_diagnostic(
+        &self,
+        error: &SyntaxDiagnostic,
+        content: &str,
+        source_map: &SourceMap,
+    ) -> Diagnostic {
+        let span =
+            vibe_language::Span::new(error.span.start, error.span.end.max(error.span.start + 1));
+
+        // Errors at end of input have no character to point at
+        let range = source_map.span_to_range(&span).unwrap_or_else(|| {
+            let position = end_position(content);
+            Range {
+                start: position,
+                end: position,
+            }
+        });
 
         Diagnostic {
             range,
             severity: Some(DiagnosticSeverity::ERROR),
-            code: None,
+            code: Some(NumberOrString::String(error.code.clone())),
             code_description: None,
             source: Some("xs".to_string()),
-            message,
+            message: error.message.clone(),
             related_information: None,
             tags: None,
             data: None,
         }
     }
 
-    fn type_error_to_diagnostic(&self, error: vibe_language::XsError, _uri: &Url) -> Diagnostic {
-        let (span, message) = match error {
-            vibe_language::XsError::TypeError(span, msg) => (span, msg),
-            _ => (vibe_language::Span::new(0, 0), format!("{}", error)),
-        };
-
-        let range = Range {
-            start: Position {
-                line: 0, // TODO: Convert span to line/column using source map
-                character: span.start as u32,
-            },
-            end: Position {
-                line: 0,
-                character: span.end as u32,
-            },
-        };
+    fn type_error_to_diagnostic(
+        &self,
+        error: &vibe_compiler::TypeDiagnostic,
+        uri: &Url,
+        source_map: &SourceMap,
+    ) -> Diagnostic {
+        let range = source_map.span_to_range(&error.span).unwrap_or_default();
+
+        // Point at where the expected and found types came from
+        let related_information: Vec<_> = error
+            .notes()
+            .into_iter()
+            .filter_map(|(span, message)| {
+                Some(DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: source_map.span_to_range(&span)?,
+                    },
+                    message,
+                })
+            })
+            .collect();
 
         Diagnostic {
             range,
@@ -131,14 +147,28 @@ impl XSLanguageServer {
             code: None,
             code_description: None,
             source: Some("xs".to_string()),
-            message,
-            related_information: None,
+            message: error.to_string(),
+            related_information: if related_information.is_empty() {
+                None
+            } else {
+                Some(related_information)
+            },
             tags: None,
             data: None,
         }
     }
 }
 
+/// Position just past the last character of `content`
+fn end_position(content: &str) -> Position {
+    let line = content.matches('\n').count();
+    let last_line = content.rsplit('\n').next().unwrap_or("");
+    Position {
+        line: line as u32,
+        character: last_line.encode_utf16().count() as u32,
+    }
+}
+
 #[tower_lsp::async_trait]
 impl LanguageServer for XSLanguageServer {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
@@ -180,9 +210,12 @@ impl LanguageServer for XSLanguageServer {
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri;
 
+        // Changes are applied in order; a change without a range replaces the whole text
         for change in params.content_changes {
-            // In full sync mode, we expect the full text
-            self.documents.update(uri.clone(), change.text);
+            match change.range {
+                Some(range) => self.documents.apply_change(&uri, range, &change.text),
+                None => self.documents.update(uri.clone(), change.text),
+            }
         }
 
         if let Err(e) = self.analyze_document(&uri).await {
@@ -195,6 +228,10 @@ impl LanguageServer for XSLanguageServer {
         debug!("Document closed: {}", uri);
         self.documents.close(&uri);
         self.source_maps.remove(&uri);
+        self.database
+            .lock()
+            .unwrap()
+            .set_module_text(ModuleId(uri.to_string()), Arc::new(String::new()));
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -1,6 +1,6 @@
 use dashmap::DashMap;
 use ropey::Rope;
-use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::{Position, Range, Url};
 
 /// Manages text documents in memory
 pub struct TextDocuments {
@@ -24,6 +24,16 @@ impl TextDocuments {
         self.documents.insert(uri, rope);
     }
 
+    /// Replace the text in `range` with `text`, as sent by incremental sync
+    pub fn apply_change(&self, uri: &Url, range: Range, text: &str) {
+        if let Some(mut rope) = self.documents.get_mut(uri) {
+            let start = position_to_char(&rope, range.start);
+            let end = position_to_char(&rope, range.end).max(start);
+            rope.remove(start..end);
+            rope.insert(start, text);
+        }
+    }
+
     pub fn close(&self, uri: &Url) {
         self.documents.remove(uri);
     }
@@ -36,3 +46,61 @@ impl TextDocuments {
         self.documents.get(uri)
     }
 }
+
+/// Character index of an LSP position, whose column counts UTF-16 code units.
+/// Positions past the end of a line or the document are clamped.
+fn position_to_char(rope: &Rope, position: Position) -> usize {
+    let line = position.line as usize;
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+
+    let line_start = rope.line_to_char(line);
+    let line_end = if line + 1 < rope.len_lines() {
+        // Stop before the line break
+        rope.line_to_char(line + 1) - 1
+    } else {
+        rope.len_chars()
+    };
+    let start_cu = rope.char_to_utf16_cu(line_start);
+    let end_cu = rope.char_to_utf16_cu(line_end);
+    let target = (start_cu + position.character as usize).min(end_cu);
+    rope.utf16_cu_to_char(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range {
+            start: Position::new(start.0, start.1),
+            end: Position::new(end.0, end.1),
+        }
+    }
+
+    #[test]
+    fn test_apply_incremental_changes() {
+        let documents = TextDocuments::new();
+        let uri = Url::parse("file:///main.vibe").unwrap();
+        documents.open(uri.clone(), "let x = 1\nlet y = x".to_string());
+
+        documents.apply_change(&uri, range((0, 8), (0, 9)), "42");
+        documents.apply_change(&uri, range((1, 4), (1, 5)), "z");
+        documents.apply_change(&uri, range((1, 9), (1, 9)), "\nz");
+        assert_eq!(documents.get(&uri).unwrap(), "let x = 42\nlet z = x\nz");
+    }
+
+    #[test]
+    fn test_columns_are_utf16_and_clamped() {
+        let documents = TextDocuments::new();
+        let uri = Url::parse("file:///main.vibe").unwrap();
+        documents.open(uri.clone(), "\"😀\" a\nb".to_string());
+
+        // The emoji is two UTF-16 code units
+        documents.apply_change(&uri, range((0, 5), (0, 6)), "c");
+        // Columns past the end of a line stop before the line break
+        documents.apply_change(&uri, range((0, 50), (0, 50)), "!");
+        assert_eq!(documents.get(&uri).unwrap(), "\"😀\" c!\nb");
+    }
+}
@@ -150,8 +150,22 @@ async fn execute_parse(args: Value) -> Result<Vec<ToolResult>, String> {
     let args: ParseArgs =
         serde_json::from_value(args).map_err(|e| format!("Invalid arguments: {e}"))?;
 
-    match vibe_language::parser::parse(&args.code) {
-        Ok(expr) => {
+    let parsed = vibe_language::parser::parse_with_recovery(&args.code);
+    if parsed.has_errors() {
+        let diagnostics = parsed
+            .errors
+            .iter()
+            .map(|e| format!("{}:{}: {}", e.location.line, e.location.column, e.message))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(format!(
+            "Parse error ({} syntax errors):\n{diagnostics}",
+            parsed.errors.len()
+        ));
+    }
+
+    match parsed.into_expr() {
+        Some(expr) => {
             let ast_json = serde_json::to_string_pretty(&expr)
                 .map_err(|e| format!("Failed to serialize AST: {e}"))?;
 
@@ -159,7 +173,7 @@ async fn execute_parse(args: Value) -> Result<Vec<ToolResult>, String> {
                 text: format!("Successfully parsed XS code:\n```json\n{ast_json}\n```"),
             }])
         }
-        Err(e) => Err(format!("Parse error: {e}")),
+        None => Err("Parse error: No expressions found".to_string()),
     }
 }
 
@@ -0,0 +1,233 @@
+//! Turn shell definitions into a self-contained module (`export` / `promote`)
+//!
+//! The definitions an export list needs are found with
+//! [`Codebase::get_all_dependencies`], ordered so that each one follows the
+//! definitions it uses, and printed back as source with
+//! [`print_source`]. Definitions the exports do not need are reported, not
+//! emitted.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use vibe_codebase::{Codebase, Hash};
+use vibe_language::lib_modules::get_module_functions;
+use vibe_language::source_print::print_source;
+use vibe_language::{Expr, Type};
+
+use crate::namespace_history::{dependency_order, SourceDefinition};
+
+/// Library modules imports are resolved against, most specific first
+const LIBRARY_MODULES: [&[&str]; 4] = [
+    &["lib", "String"],
+    &["lib", "List"],
+    &["lib", "Int"],
+    &["lib"],
+];
+
+/// A shell definition with its parsed expression and inferred type
+pub struct ExportCandidate {
+    pub definition: SourceDefinition,
+    pub expr: Expr,
+    pub ty: Type,
+}
+
+/// The result of exporting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedModule {
+    /// Module source, ready to be written to a `.vibe` file
+    pub source: String,
+    pub exports: Vec<String>,
+    /// Emitted definitions in dependency order
+    pub definitions: Vec<String>,
+    /// `import` lines for library functions the definitions use
+    pub imports: Vec<String>,
+    /// Definitions left out because no export needs them
+    pub unused: Vec<String>,
+}
+
+/// Build a module exporting `exports` and everything they depend on.
+/// `in_scope` is the shell's type environment, used to find the library
+/// functions the definitions were written against.
+pub fn export_module(
+    candidates: Vec<ExportCandidate>,
+    exports: &[String],
+    in_scope: &HashMap<String, Type>,
+) -> Result<ExportedModule> {
+    if exports.is_empty() {
+        anyhow::bail!("Nothing to export");
+    }
+
+    // Add terms dependencies first so that references resolve to them
+    let sources: Vec<_> = candidates.iter().map(|c| c.definition.clone()).collect();
+    let mut by_name: HashMap<String, ExportCandidate> = candidates
+        .into_iter()
+        .map(|c| (c.definition.name.clone(), c))
+        .collect();
+    let mut codebase = Codebase::new();
+    let mut names: HashMap<Hash, String> = HashMap::new();
+    let mut all_hashes = Vec::new();
+    for definition in dependency_order(&sources) {
+        let candidate = &by_name[&definition.name];
+        let hash = codebase
+            .add_term(
+                Some(definition.name.clone()),
+                candidate.expr.clone(),
+                candidate.ty.clone(),
+            )
+            .with_context(|| format!("Failed to add {}", definition.name))?;
+        names.insert(hash.clone(), definition.name.clone());
+        all_hashes.push(hash);
+    }
+
+    let mut needed = HashSet::new();
+    for name in exports {
+        let term = codebase
+            .get_term_by_name(name)
+            .ok_or_else(|| anyhow::anyhow!("{name} is not defined"))?;
+        needed.insert(term.hash.clone());
+        needed.extend(codebase.get_all_dependencies(&term.hash)?);
+    }
+    let needed: Vec<Hash> = all_hashes
+        .iter()
+        .filter(|h| needed.contains(*h))
+        .cloned()
+        .collect();
+    let ordered = codebase
+        .topological_order(&needed)
+        .context("Definitions depend on each other in a cycle")?;
+
+    let emitted: Vec<ExportCandidate> = ordered
+        .iter()
+        .map(|hash| by_name.remove(&names[hash]).unwrap())
+        .collect();
+    let emitted_names: HashSet<&str> = emitted.iter().map(|c| c.definition.name.as_str()).collect();
+    let unused = sources
+        .iter()
+        .filter(|d| !emitted_names.contains(d.name.as_str()))
+        .map(|d| d.name.clone())
+        .collect();
+    let imports = library_imports(&emitted, &emitted_names, in_scope);
+
+    let mut source = format!("export {{ {} }}\n", exports.join(", "));
+    if !imports.is_empty() {
+        source.push('\n');
+        for import in &imports {
+            source.push_str(import);
+            source.push('\n');
+        }
+    }
+    for candidate in &emitted {
+        source.push('\n');
+        source.push_str(&print_source(&candidate.expr));
+        source.push('\n');
+    }
+
+    Ok(ExportedModule {
+        source,
+        exports: exports.to_vec(),
+        definitions: emitted.iter().map(|c| c.definition.name.clone()).collect(),
+        imports,
+        unused,
+    })
+}
+
+/// `import` lines for the library functions the definitions refer to
+fn library_imports(
+    definitions: &[ExportCandidate],
+    defined: &HashSet<&str>,
+    in_scope: &HashMap<String, Type>,
+) -> Vec<String> {
+    let referenced: BTreeSet<String> = definitions
+        .iter()
+        .flat_map(|c| c.definition.referenced_names())
+        .filter(|name| !defined.contains(name.as_str()) && in_scope.contains_key(name))
+        .collect();
+
+    let modules: Vec<(String, HashMap<String, Type>)> = LIBRARY_MODULES
+        .iter()
+        .filter_map(|path| {
+            let path: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+            get_module_functions(&path).map(|functions| (path.join("/"), functions))
+        })
+        .collect();
+
+    let mut imports: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for name in &referenced {
+        if let Some((path, _)) = modules.iter().find(|(_, f)| f.contains_key(name)) {
+            imports.entry(path).or_default().push(name);
+        }
+    }
+    imports
+        .into_iter()
+        .map(|(path, names)| format!("import {} {{ {} }}", path, names.join(", ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::parser::parse;
+
+    fn candidate(name: &str, source: &str) -> ExportCandidate {
+        ExportCandidate {
+            definition: SourceDefinition {
+                namespace: "scratch".to_string(),
+                name: name.to_string(),
+                source: source.to_string(),
+                hash: String::new(),
+            },
+            expr: parse(source).unwrap(),
+            ty: Type::Int,
+        }
+    }
+
+    fn candidates() -> Vec<ExportCandidate> {
+        vec![
+            candidate("total", "let total = base"),
+            candidate("base", "let base = 10"),
+            candidate("scratch", "let scratch = 1"),
+            candidate("report", "let report = total"),
+        ]
+    }
+
+    #[test]
+    fn test_exports_dependency_closure_in_order() {
+        let module = export_module(candidates(), &["report".to_string()], &HashMap::new()).unwrap();
+        assert_eq!(module.definitions, vec!["base", "total", "report"]);
+        assert_eq!(module.unused, vec!["scratch"]);
+        assert_eq!(
+            module.source,
+            "export { report }\n\nlet base = 10\n\nlet total = base\n\nlet report = total\n"
+        );
+    }
+
+    #[test]
+    fn test_imports_library_functions() {
+        let mut in_scope = HashMap::new();
+        in_scope.insert("concat".to_string(), Type::String);
+        in_scope.insert("length".to_string(), Type::Int);
+        let module = export_module(
+            vec![candidate("greet", "let greet = concat")],
+            &["greet".to_string()],
+            &in_scope,
+        )
+        .unwrap();
+        assert_eq!(module.imports, vec!["import lib/String { concat }"]);
+        assert!(module.source.contains("\nimport lib/String { concat }\n"));
+        let expr = parse(&module.source).unwrap();
+        let ty = vibe_compiler::type_check(&expr).unwrap();
+        assert_eq!(ty.to_string(), "String -> String -> String");
+        // The import binds the library function when the module runs
+        let mut interpreter = vibe_runtime::Interpreter::new();
+        let env = vibe_runtime::Interpreter::create_initial_env();
+        assert!(interpreter.eval(&expr, &env).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_export() {
+        let error = export_module(candidates(), &["missing".to_string()], &HashMap::new())
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "missing is not defined");
+        assert!(export_module(candidates(), &[], &HashMap::new()).is_err());
+    }
+}
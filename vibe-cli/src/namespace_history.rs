@@ -0,0 +1,550 @@
+//! Persisted history of shell namespace states
+//!
+//! Every change to the shell's definitions (`add`, `update`, `merge`) records
+//! the full set of definitions as a new state. `undo` and `redo` move a cursor
+//! through these states, and the history is written to disk after each move so
+//! it survives across sessions. Each branch has its own history; forking a
+//! branch copies it, and merging looks for the last state two histories share.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use vibe_language::parser::lexer::{Lexer, Token};
+
+/// A named definition together with the source it was written as
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceDefinition {
+    pub namespace: String,
+    pub name: String,
+    pub source: String,
+    /// Content hash the name was bound to when the state was recorded
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl SourceDefinition {
+    /// The name qualified with its namespace, e.g. `Math.double`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.name)
+    }
+
+    /// Names the source refers to, with `A.b` kept as one qualified name.
+    /// Over-approximates by taking every symbol, which at worst treats a
+    /// shadowed name as a dependency.
+    pub fn referenced_names(&self) -> HashSet<String> {
+        let mut lexer = Lexer::new(&self.source);
+        let mut tokens = Vec::new();
+        loop {
+            match lexer.next_token() {
+                Ok(Some((token, _))) => tokens.push(token),
+                Ok(None) => break,
+                Err(_) => lexer.skip_char(),
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            if let Token::Symbol(name) = &tokens[i] {
+                match (tokens.get(i + 1), tokens.get(i + 2)) {
+                    (Some(Token::Dot), Some(Token::Symbol(member))) => {
+                        names.insert(format!("{name}.{member}"));
+                        names.insert(name.clone());
+                        i += 3;
+                        continue;
+                    }
+                    _ => {
+                        names.insert(name.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        names.remove(&self.name);
+        names.remove(&self.qualified_name());
+        names
+    }
+
+    /// Qualified names of the definitions this one refers to. A bare name
+    /// resolves to the definition in the same namespace if there is one, and
+    /// otherwise to every definition with that name.
+    pub fn dependencies(&self, definitions: &[SourceDefinition]) -> HashSet<String> {
+        let mut dependencies = HashSet::new();
+        for name in self.referenced_names() {
+            let qualified: Vec<_> = definitions
+                .iter()
+                .filter(|d| d.qualified_name() == name)
+                .collect();
+            let local: Vec<_> = definitions
+                .iter()
+                .filter(|d| d.namespace == self.namespace && d.name == name)
+                .collect();
+            let found = if !qualified.is_empty() {
+                qualified
+            } else if !local.is_empty() {
+                local
+            } else {
+                definitions.iter().filter(|d| d.name == name).collect()
+            };
+            dependencies.extend(
+                found
+                    .into_iter()
+                    .filter(|d| *d != self)
+                    .map(SourceDefinition::qualified_name),
+            );
+        }
+        dependencies
+    }
+
+    /// Whether two definitions bind the same content
+    fn same_content(&self, other: &SourceDefinition) -> bool {
+        if self.hash.is_empty() || other.hash.is_empty() {
+            self.source == other.source
+        } else {
+            self.hash == other.hash
+        }
+    }
+}
+
+/// The definitions of the shell at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceState {
+    /// What produced this state, e.g. "add x" or "update f, g"
+    pub description: String,
+    pub definitions: Vec<SourceDefinition>,
+}
+
+impl NamespaceState {
+    /// Hash identifying the set of name bindings, independent of order
+    pub fn hash(&self) -> String {
+        let mut bindings: Vec<_> = self
+            .definitions
+            .iter()
+            .map(|d| format!("{}.{}={}", d.namespace, d.name, d.hash))
+            .collect();
+        bindings.sort();
+        vibe_codebase::Hash::new(bindings.join("\n").as_bytes()).to_hex()
+    }
+}
+
+/// Linear undo history of namespace states
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamespaceHistory {
+    states: Vec<NamespaceState>,
+    cursor: usize,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Default for NamespaceHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NamespaceHistory {
+    /// An in-memory history starting from an empty namespace
+    pub fn new() -> Self {
+        Self {
+            states: vec![NamespaceState {
+                description: "initial state".to_string(),
+                definitions: Vec::new(),
+            }],
+            cursor: 0,
+            path: None,
+        }
+    }
+
+    /// Load the history stored at `path`, or start a new one that will be
+    /// saved there
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let mut history = if path.exists() {
+            let data = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let history: NamespaceHistory = serde_json::from_str(&data)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            if history.states.is_empty() || history.cursor >= history.states.len() {
+                anyhow::bail!("Corrupt namespace history in {}", path.display());
+            }
+            history
+        } else {
+            Self::new()
+        };
+        history.path = Some(path);
+        Ok(history)
+    }
+
+    pub fn current(&self) -> &NamespaceState {
+        &self.states[self.cursor]
+    }
+
+    /// States up to the current one, newest first
+    pub fn log(&self) -> impl Iterator<Item = &NamespaceState> {
+        self.states[..=self.cursor].iter().rev()
+    }
+
+    /// The most recent state that both histories reached
+    pub fn common_ancestor(&self, other: &NamespaceHistory) -> &NamespaceState {
+        let shared = self.states[..=self.cursor]
+            .iter()
+            .zip(&other.states[..=other.cursor])
+            .take_while(|(a, b)| a == b)
+            .count();
+        &self.states[shared.saturating_sub(1)]
+    }
+
+    /// Copy this history to be saved at `path`, without any redo states
+    pub fn fork(&self, path: PathBuf) -> Result<Self> {
+        let history = Self {
+            states: self.states[..=self.cursor].to_vec(),
+            cursor: self.cursor,
+            path: Some(path),
+        };
+        history.save()?;
+        Ok(history)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.cursor > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor + 1 < self.states.len()
+    }
+
+    /// Record a new state, discarding anything that could have been redone
+    pub fn record(&mut self, state: NamespaceState) -> Result<()> {
+        self.states.truncate(self.cursor + 1);
+        self.states.push(state);
+        self.cursor += 1;
+        self.save()
+    }
+
+    /// Step back one state, returning the description of the undone change
+    pub fn undo(&mut self) -> Result<Option<String>> {
+        if !self.can_undo() {
+            return Ok(None);
+        }
+        let undone = self.states[self.cursor].description.clone();
+        self.cursor -= 1;
+        self.save()?;
+        Ok(Some(undone))
+    }
+
+    /// Step forward one state, returning the description of the redone change
+    pub fn redo(&mut self) -> Result<Option<String>> {
+        if !self.can_redo() {
+            return Ok(None);
+        }
+        self.cursor += 1;
+        self.save()?;
+        Ok(Some(self.states[self.cursor].description.clone()))
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so a crash never leaves a truncated history
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Location of a branch's history file under a shell's storage directory
+pub fn history_path(storage_path: &Path, branch: &str) -> PathBuf {
+    storage_path
+        .join(".vibe")
+        .join("branches")
+        .join(format!("{branch}.json"))
+}
+
+/// File recording which branch the shell was last on
+pub fn head_path(storage_path: &Path) -> PathBuf {
+    storage_path.join(".vibe").join("HEAD")
+}
+
+/// A name bound to different content on both sides of a merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub name: String,
+    /// Hash on the current branch, `None` if the name was removed there
+    pub ours: Option<String>,
+    /// Hash on the merged branch, `None` if the name was removed there
+    pub theirs: Option<String>,
+}
+
+/// Three-way merge of definitions by name. A name changed on only one side
+/// takes that side's binding; a name changed differently on both is a conflict.
+pub fn merge_definitions(
+    base: &[SourceDefinition],
+    ours: &[SourceDefinition],
+    theirs: &[SourceDefinition],
+) -> Result<Vec<SourceDefinition>, Vec<MergeConflict>> {
+    fn find<'a>(
+        defs: &'a [SourceDefinition],
+        key: &SourceDefinition,
+    ) -> Option<&'a SourceDefinition> {
+        defs.iter()
+            .find(|d| d.namespace == key.namespace && d.name == key.name)
+    }
+    fn same(a: Option<&SourceDefinition>, b: Option<&SourceDefinition>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.same_content(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    // Keep our order, then whatever only the other side or the base has
+    let mut seen = HashSet::new();
+    let keys: Vec<&SourceDefinition> = ours
+        .iter()
+        .chain(theirs)
+        .chain(base)
+        .filter(|d| seen.insert((d.namespace.as_str(), d.name.as_str())))
+        .collect();
+
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+    for key in keys {
+        let (b, o, t) = (find(base, key), find(ours, key), find(theirs, key));
+        let chosen = if same(o, t) || same(b, t) {
+            o
+        } else if same(b, o) {
+            t
+        } else {
+            conflicts.push(MergeConflict {
+                name: key.name.clone(),
+                ours: o.map(|d| d.hash.clone()),
+                theirs: t.map(|d| d.hash.clone()),
+            });
+            continue;
+        };
+        merged.extend(chosen.cloned());
+    }
+
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(conflicts)
+    }
+}
+
+/// Order definitions so each comes after the definitions it refers to,
+/// keeping the original order wherever dependencies allow
+pub fn dependency_order(definitions: &[SourceDefinition]) -> Vec<SourceDefinition> {
+    let mut pending: Vec<(&SourceDefinition, HashSet<String>)> = definitions
+        .iter()
+        .map(|d| (d, d.dependencies(definitions)))
+        .collect();
+
+    let mut ordered = Vec::with_capacity(definitions.len());
+    while !pending.is_empty() {
+        // A cycle can only come from shadowed names, so fall back to source order
+        let next = pending
+            .iter()
+            .position(|(_, deps)| deps.is_empty())
+            .unwrap_or(0);
+        let (definition, _) = pending.remove(next);
+        for (_, deps) in pending.iter_mut() {
+            deps.remove(&definition.qualified_name());
+        }
+        ordered.push(definition.clone());
+    }
+    ordered
+}
+
+/// Qualified names of the definitions that transitively refer to any of the
+/// qualified names in `changed`
+pub fn dependents_of(definitions: &[SourceDefinition], changed: &HashSet<String>) -> Vec<String> {
+    let dependencies: Vec<_> = definitions
+        .iter()
+        .map(|d| (d.qualified_name(), d.dependencies(definitions)))
+        .collect();
+    let mut affected = changed.clone();
+    let mut dependents = Vec::new();
+    loop {
+        let mut grew = false;
+        for (name, deps) in &dependencies {
+            if affected.contains(name) {
+                continue;
+            }
+            if deps.iter().any(|dep| affected.contains(dep)) {
+                affected.insert(name.clone());
+                dependents.push(name.clone());
+                grew = true;
+            }
+        }
+        if !grew {
+            return dependents;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn def(name: &str, source: &str) -> SourceDefinition {
+        SourceDefinition {
+            namespace: "scratch".to_string(),
+            name: name.to_string(),
+            source: source.to_string(),
+            hash: String::new(),
+        }
+    }
+
+    fn state(description: &str, definitions: Vec<SourceDefinition>) -> NamespaceState {
+        NamespaceState {
+            description: description.to_string(),
+            definitions,
+        }
+    }
+
+    #[test]
+    fn test_undo_redo_persists() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = history_path(temp_dir.path(), "main");
+
+        let mut history = NamespaceHistory::load(path.clone()).unwrap();
+        assert!(!history.can_undo());
+        history
+            .record(state("add x", vec![def("x", "let x = 1")]))
+            .unwrap();
+        history
+            .record(state("update x", vec![def("x", "let x = 2")]))
+            .unwrap();
+
+        assert_eq!(history.undo().unwrap().as_deref(), Some("update x"));
+        assert_eq!(history.current().definitions[0].source, "let x = 1");
+
+        let mut reloaded = NamespaceHistory::load(path).unwrap();
+        assert_eq!(reloaded.current().description, "add x");
+        assert_eq!(reloaded.redo().unwrap().as_deref(), Some("update x"));
+        assert_eq!(reloaded.redo().unwrap(), None);
+    }
+
+    #[test]
+    fn test_record_discards_redo_states() {
+        let mut history = NamespaceHistory::new();
+        history.record(state("add x", vec![])).unwrap();
+        history.undo().unwrap();
+        history.record(state("add y", vec![])).unwrap();
+        assert!(!history.can_redo());
+        assert_eq!(history.undo().unwrap().as_deref(), Some("add y"));
+        assert_eq!(history.undo().unwrap(), None);
+    }
+
+    #[test]
+    fn test_dependency_order_and_dependents() {
+        let definitions = vec![
+            def("total", "let total = double base"),
+            def("double", "let double = fn x -> x * 2"),
+            def("base", "let base = 21"),
+            def("other", "let other = 0"),
+        ];
+
+        let order: Vec<_> = dependency_order(&definitions)
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(order, vec!["double", "base", "total", "other"]);
+
+        let changed = HashSet::from(["scratch.base".to_string()]);
+        assert_eq!(dependents_of(&definitions, &changed), vec!["scratch.total"]);
+    }
+
+    #[test]
+    fn test_dependencies_respect_namespaces() {
+        let in_ns = |namespace: &str, name: &str, source: &str| SourceDefinition {
+            namespace: namespace.to_string(),
+            ..def(name, source)
+        };
+        let definitions = vec![
+            in_ns("Math", "base", "let base = 1"),
+            in_ns("Other", "base", "let base = 2"),
+            in_ns("Math", "total", "let total = base + 1"),
+            in_ns("Other", "uses", "let uses = Math.total"),
+        ];
+
+        assert_eq!(
+            definitions[2].dependencies(&definitions),
+            HashSet::from(["Math.base".to_string()])
+        );
+        assert_eq!(
+            definitions[3].dependencies(&definitions),
+            HashSet::from(["Math.total".to_string()])
+        );
+
+        let changed = HashSet::from(["Other.base".to_string()]);
+        assert!(dependents_of(&definitions, &changed).is_empty());
+        let changed = HashSet::from(["Math.base".to_string()]);
+        assert_eq!(
+            dependents_of(&definitions, &changed),
+            vec!["Math.total", "Other.uses"]
+        );
+    }
+
+    #[test]
+    fn test_fork_shares_ancestor() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut main = NamespaceHistory::load(history_path(temp_dir.path(), "main")).unwrap();
+        main.record(state("add x", vec![def("x", "let x = 1")]))
+            .unwrap();
+
+        let mut feature = main.fork(history_path(temp_dir.path(), "feature")).unwrap();
+        feature
+            .record(state(
+                "add y",
+                vec![def("x", "let x = 1"), def("y", "let y = 2")],
+            ))
+            .unwrap();
+        main.record(state(
+            "add z",
+            vec![def("x", "let x = 1"), def("z", "let z = 3")],
+        ))
+        .unwrap();
+
+        assert_eq!(main.common_ancestor(&feature).description, "add x");
+        let log: Vec<_> = feature.log().map(|s| s.description.as_str()).collect();
+        assert_eq!(log, vec!["add y", "add x", "initial state"]);
+    }
+
+    #[test]
+    fn test_merge_definitions() {
+        let base = vec![def("x", "let x = 1"), def("y", "let y = 1")];
+        let ours = vec![def("x", "let x = 2"), def("y", "let y = 1")];
+        let theirs = vec![
+            def("x", "let x = 1"),
+            def("y", "let y = 1"),
+            def("z", "let z = 3"),
+        ];
+        let merged = merge_definitions(&base, &ours, &theirs).unwrap();
+        let sources: Vec<_> = merged.iter().map(|d| d.source.as_str()).collect();
+        assert_eq!(sources, vec!["let x = 2", "let y = 1", "let z = 3"]);
+
+        let mut theirs = theirs;
+        theirs[0] = SourceDefinition {
+            hash: "bbbb".to_string(),
+            ..def("x", "let x = 3")
+        };
+        let conflicts = merge_definitions(&base, &ours, &theirs).unwrap_err();
+        assert_eq!(
+            conflicts,
+            vec![MergeConflict {
+                name: "x".to_string(),
+                ours: Some(String::new()),
+                theirs: Some("bbbb".to_string()),
+            }]
+        );
+    }
+}
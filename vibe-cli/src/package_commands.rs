@@ -1,12 +1,16 @@
 //! Package management commands for Vibe CLI
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Subcommand;
 use colored::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use vibe_codebase::package::{
     cache::PackageCache,
+    install::{InstallOutcome, Installer, Update},
+    lockfile::Lockfile,
     manifest::PackageManifest,
+    modules::PackageModules,
+    workspace::Workspace,
     hash::calculate_package_hash,
     registry::LocalRegistry,
     resolver::PackageRegistry,
@@ -21,14 +25,34 @@ pub enum PackageCommand {
         name: String,
     },
     
+    /// Add a dependency to package.vibe and install it
+    Add {
+        /// Package to add (name, name@range or name#hash)
+        package: String,
+        
+        /// Only use packages already in the cache
+        #[arg(long)]
+        offline: bool,
+    },
+    
     /// Install dependencies
     Install {
-        /// Package to install (name or name#hash)
+        /// Package to install (name, name@range or name#hash)
         package: Option<String>,
         
         /// Save to dependencies
         #[arg(long)]
         save: bool,
+        
+        /// Only use the lockfile and packages already in the cache
+        #[arg(long)]
+        offline: bool,
+    },
+    
+    /// Remove a dependency from package.vibe
+    Remove {
+        /// Package name
+        package: String,
     },
     
     /// Publish package to registry
@@ -57,20 +81,95 @@ pub enum PackageCommand {
     Clear,
     
     /// Update package dependencies
-    Update,
+    Update {
+        /// Packages to update (defaults to all)
+        packages: Vec<String>,
+        
+        /// Only use packages already in the cache
+        #[arg(long)]
+        offline: bool,
+    },
+}
+
+/// Where package commands find the project, the cache and the registry
+#[derive(Debug, Clone)]
+pub struct PackageContext {
+    pub project_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub registry_dir: PathBuf,
+}
+
+impl PackageContext {
+    /// The current directory with the default cache and local registry
+    pub fn from_env() -> Result<Self> {
+        Ok(PackageContext {
+            project_dir: std::env::current_dir()?,
+            cache_dir: PackageCache::default_cache_dir()?,
+            registry_dir: default_registry_dir(),
+        })
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.project_dir.join("package.vibe")
+    }
+
+    /// The lockfile this project shares, which is the workspace's when the
+    /// project is a workspace member
+    fn lockfile_path(&self) -> Result<PathBuf> {
+        Ok(match Workspace::find(&self.project_dir)? {
+            Some(workspace) => workspace.lockfile_path(),
+            None => Lockfile::path_for(&self.manifest_path()),
+        })
+    }
+
+    fn load_manifest(&self) -> Result<PackageManifest> {
+        let path = self.manifest_path();
+        PackageManifest::load_from_file(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
 }
 
 pub async fn handle_package_command(command: PackageCommand) -> Result<()> {
     match command {
         PackageCommand::Init { name } => init_package(&name),
-        PackageCommand::Install { package, save } => install_package(package.as_deref(), save).await,
+        PackageCommand::Add { package, offline } => {
+            add_package(&PackageContext::from_env()?, &package, offline).map(|_| ())
+        }
+        PackageCommand::Install { package, save, offline } => {
+            install_package(&PackageContext::from_env()?, package.as_deref(), save, offline).map(|_| ())
+        }
+        PackageCommand::Remove { package } => {
+            remove_package(&PackageContext::from_env()?, &package).map(|_| ())
+        }
         PackageCommand::Publish { registry } => publish_package(registry.as_deref()).await,
         PackageCommand::Search { query } => search_packages(&query),
         PackageCommand::Info { package } => show_package_info(&package),
         PackageCommand::List => list_packages(),
         PackageCommand::Clear => clear_cache(),
-        PackageCommand::Update => update_packages().await,
+        PackageCommand::Update { packages, offline } => {
+            update_packages(&PackageContext::from_env()?, &packages, offline).map(|_| ())
+        }
+    }
+}
+
+fn default_registry_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_default()
+        .join(".vibe")
+        .join("registry")
+}
+
+/// Packages locked for the project containing `path`, loaded from the
+/// default cache
+pub fn project_modules(path: &Path) -> Result<Option<PackageModules>> {
+    // Avoid creating the cache for files outside a locked project
+    let locked = PackageManifest::find(path)
+        .is_some_and(|manifest| Lockfile::path_for(&manifest).exists());
+    if !locked && Workspace::find(path)?.is_none() {
+        return Ok(None);
     }
+    let cache = PackageCache::new(PackageCache::default_cache_dir()?)?;
+    Ok(PackageModules::load_for_path(path, &cache)?)
 }
 
 fn init_package(name: &str) -> Result<()> {
@@ -120,43 +219,182 @@ let greet name = "Hello, " ++ name ++ "!"
     Ok(())
 }
 
-async fn install_package(package: Option<&str>, save: bool) -> Result<()> {
-    let _cache = PackageCache::new(PackageCache::default_cache_dir()?)?;
-    
-    if let Some(package_spec) = package {
-        // Install specific package
-        println!("{} {}", "Installing:".cyan().bold(), package_spec);
-        
-        // Parse package specification (name or name#hash)
-        let (_name, _hash) = if let Some(pos) = package_spec.find('#') {
-            let (name, hash_str) = package_spec.split_at(pos);
-            (name, Some(&hash_str[1..]))
+/// A dependency as given on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PackageSpec {
+    /// `name` or `name@range`
+    Version(String, Option<String>),
+    /// `name#hash`
+    Hash(String, String),
+}
+
+impl PackageSpec {
+    fn parse(spec: &str) -> Self {
+        if let Some((name, hash)) = spec.split_once('#') {
+            PackageSpec::Hash(name.to_string(), hash.to_string())
+        } else if let Some((name, version)) = spec.split_once('@') {
+            PackageSpec::Version(name.to_string(), Some(version.to_string()))
         } else {
-            (package_spec, None)
-        };
+            PackageSpec::Version(spec.to_string(), None)
+        }
+    }
 
-        // TODO: Implement actual installation
-        println!("{} Package installation not yet implemented", "!".yellow());
-        
-        if save {
-            // TODO: Update package.vibe
-            println!("{} Saving to dependencies not yet implemented", "!".yellow());
+    fn name(&self) -> &str {
+        match self {
+            PackageSpec::Version(name, _) | PackageSpec::Hash(name, _) => name,
+        }
+    }
+
+    /// Record the dependency; a missing range accepts any version for now
+    fn apply(&self, manifest: &mut PackageManifest) {
+        match self {
+            PackageSpec::Version(name, version) => manifest.require(
+                name.clone(),
+                version.clone().unwrap_or_else(|| "*".to_string()),
+            ),
+            PackageSpec::Hash(name, hash) => manifest.add_dependency(name.clone(), hash.clone()),
+        }
+    }
+}
+
+/// Resolve and install `manifest`'s dependencies, writing the lockfile
+/// next to the project's package.vibe. In a workspace, the dependencies of
+/// every member are resolved together into the root's lockfile.
+fn run_install(
+    ctx: &PackageContext,
+    manifest: &PackageManifest,
+    update: &Update,
+    offline: bool,
+) -> Result<InstallOutcome> {
+    let mut cache = PackageCache::new(ctx.cache_dir.clone())?;
+    let registry = if offline { None } else { Some(LocalRegistry::new(ctx.registry_dir.clone())?) };
+    let mut installer = Installer::new(&mut cache);
+    if let Some(registry) = &registry {
+        installer = installer.with_registry(registry);
+    }
+    let outcome = match Workspace::find(&ctx.project_dir)? {
+        Some(mut workspace) => {
+            // The project's manifest may have changes that are not saved yet
+            if !manifest.is_workspace_root() {
+                workspace.set_manifest(&manifest.package.name, manifest.clone());
+            }
+            installer.install_manifests(&workspace.manifests(), &workspace.lockfile_path(), update)?
         }
+        None => installer.install_manifest(manifest, &ctx.manifest_path(), update)?,
+    };
+    report_install(&outcome);
+    Ok(outcome)
+}
+
+fn report_install(outcome: &InstallOutcome) {
+    for package in &outcome.installed {
+        println!("  {} {} {}",
+            "+".green(),
+            package.name.bold(),
+            package.version.as_deref().unwrap_or("")
+        );
+    }
+    if outcome.lockfile_changed {
+        println!("{} package.lock ({} packages)", "✓ Wrote".green(), outcome.lockfile.packages.len());
     } else {
-        // Install from package.vibe
-        let manifest = PackageManifest::load_from_file(Path::new("package.vibe"))?;
-        println!("{} dependencies from package.vibe", "Installing".cyan().bold());
-        
-        for (name, dep) in &manifest.dependencies {
-            println!("  {} {}", "→".cyan(), format!("{}#{}", name, dep.hash));
+        println!("{}", "✓ package.lock is up to date".green());
+    }
+}
+
+/// `vibe package add`: add a dependency and install it. Without a range
+/// the dependency is recorded as compatible with the version installed.
+pub fn add_package(ctx: &PackageContext, spec: &str, offline: bool) -> Result<InstallOutcome> {
+    let spec = PackageSpec::parse(spec);
+    println!("{} {}", "Adding:".cyan().bold(), spec.name());
+
+    let mut manifest = ctx.load_manifest()?;
+    spec.apply(&mut manifest);
+    let update = Update::Only(vec![spec.name().to_string()]);
+    let outcome = run_install(ctx, &manifest, &update, offline)?;
+
+    if let PackageSpec::Version(name, None) = &spec {
+        if let Some(version) = outcome.lockfile.get(name).and_then(|p| p.version.clone()) {
+            manifest.require(name.clone(), format!("^{}", version));
         }
-        
-        if manifest.dependencies.is_empty() {
-            println!("{}", "No dependencies to install".yellow());
+    }
+    manifest.save_to_file(&ctx.manifest_path())?;
+    Ok(outcome)
+}
+
+/// `vibe package install`: install everything package.vibe needs, or one
+/// package into the cache (and package.vibe with `--save`)
+pub fn install_package(
+    ctx: &PackageContext,
+    package: Option<&str>,
+    save: bool,
+    offline: bool,
+) -> Result<InstallOutcome> {
+    match package {
+        Some(spec) if save => add_package(ctx, spec, offline),
+        Some(spec) => {
+            let spec = PackageSpec::parse(spec);
+            println!("{} {}", "Installing:".cyan().bold(), spec.name());
+            let mut manifest = PackageManifest::new("(command line)".to_string());
+            spec.apply(&mut manifest);
+
+            let mut cache = PackageCache::new(ctx.cache_dir.clone())?;
+            let registry = if offline { None } else { Some(LocalRegistry::new(ctx.registry_dir.clone())?) };
+            let mut installer = Installer::new(&mut cache);
+            if let Some(registry) = &registry {
+                installer = installer.with_registry(registry);
+            }
+            let lockfile = installer.resolve(&manifest, None, &Update::All)?;
+            let installed = installer.install(&lockfile)?;
+            let outcome = InstallOutcome { lockfile, installed, lockfile_changed: false };
+            for package in &outcome.installed {
+                println!("  {} {} {}", "+".green(), package.name.bold(), package.version.as_deref().unwrap_or(""));
+            }
+            Ok(outcome)
+        }
+        None => {
+            println!("{} dependencies from package.vibe", "Installing".cyan().bold());
+            let manifest = ctx.load_manifest()?;
+            run_install(ctx, &manifest, &Update::Locked, offline)
         }
     }
+}
 
-    Ok(())
+/// `vibe package update`: move the named packages, or all of them, to the
+/// highest versions package.vibe allows
+pub fn update_packages(ctx: &PackageContext, packages: &[String], offline: bool) -> Result<InstallOutcome> {
+    println!("{}", "Updating packages...".cyan().bold());
+    let manifest = ctx.load_manifest()?;
+    let update = if packages.is_empty() { Update::All } else { Update::Only(packages.to_vec()) };
+
+    let previous = Lockfile::load(&ctx.lockfile_path()?)?;
+    let outcome = run_install(ctx, &manifest, &update, offline)?;
+    for package in &outcome.lockfile.packages {
+        let before = previous.as_ref()
+            .and_then(|l| l.get(&package.name))
+            .and_then(|p| p.version.clone());
+        if before != package.version {
+            println!("  {} {} {} → {}",
+                "↑".cyan(),
+                package.name.bold(),
+                before.as_deref().unwrap_or("(new)"),
+                package.version.as_deref().unwrap_or("?")
+            );
+        }
+    }
+    Ok(outcome)
+}
+
+/// `vibe package remove`: drop a dependency and whatever only it needed
+pub fn remove_package(ctx: &PackageContext, name: &str) -> Result<InstallOutcome> {
+    let mut manifest = ctx.load_manifest()?;
+    if !manifest.remove_dependency(name) {
+        anyhow::bail!("{} is not a dependency of {}", name, manifest.package.name);
+    }
+    println!("{} {}", "Removing:".cyan().bold(), name);
+    // Nothing new is needed, so this never has to reach the registry
+    let outcome = run_install(ctx, &manifest, &Update::Locked, true)?;
+    manifest.save_to_file(&ctx.manifest_path())?;
+    Ok(outcome)
 }
 
 async fn publish_package(registry: Option<&str>) -> Result<()> {
@@ -322,12 +560,6 @@ fn clear_cache() -> Result<()> {
     Ok(())
 }
 
-async fn update_packages() -> Result<()> {
-    println!("{}", "Updating packages...".cyan().bold());
-    println!("{} Package updates not yet implemented", "!".yellow());
-    Ok(())
-}
-
 fn display_manifest_info(manifest: &PackageManifest) {
     println!("\n{}", "Package Information:".green());
     println!("  Name: {}", manifest.package.name.bold());
@@ -351,7 +583,10 @@ fn display_manifest_info(manifest: &PackageManifest) {
     if !manifest.dependencies.is_empty() {
         println!("\n{}", "Dependencies:".green());
         for (name, dep) in &manifest.dependencies {
-            println!("  {} → #{}", name, &dep.hash[..12]);
+            match &dep.version {
+                Some(version) => println!("  {} {}", name, version),
+                None => println!("  {} → #{}", name, &dep.hash[..dep.hash.len().min(12)]),
+            }
         }
     }
 
@@ -2,22 +2,30 @@
 
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use vibe_codebase::hash::DefinitionHash;
 use vibe_codebase::namespace::{
     DefinitionContent, DefinitionPath, NamespaceCommand, NamespacePath, NamespaceStore,
 };
 use vibe_codebase::unified_parser::{parse_unified_with_mode, SyntaxMode};
-use vibe_codebase::{CodebaseManager, EditSession, ExpressionId};
+use vibe_codebase::code_repository::CodeRepository;
+use vibe_codebase::{CodebaseManager, EditSession, ExpressionId, Term};
 use vibe_compiler::{TypeChecker, TypeEnv};
 use vibe_language::pretty_print::pretty_print;
 use vibe_language::type_annotator::embed_type_annotations;
 use vibe_language::Ident;
 use vibe_language::{DoStatement, Expr, Type, Value};
+use vibe_runtime::debugger::{DebugFrontend, Debugger, Tracer};
 use vibe_runtime::Interpreter;
 
 use crate::commands;
+use crate::module_export::{export_module, ExportCandidate};
+use crate::namespace_history::{
+    dependency_order, dependents_of, head_path, history_path, merge_definitions, NamespaceHistory,
+    NamespaceState, SourceDefinition,
+};
 use crate::search_patterns::{expr_contains_pattern, parse_type_pattern, AstPattern};
 
 #[derive(Debug)]
@@ -30,6 +38,23 @@ struct ExpressionHistory {
     timestamp: std::time::SystemTime,
 }
 
+/// What evaluating a definition bound, kept so restoring a history state can
+/// rebind it without evaluating it again
+#[derive(Clone)]
+struct EvaluatedDefinition {
+    hash: String,
+    ty: Type,
+    value: Value,
+    content: DefinitionContent,
+}
+
+/// Everything a history state bound, recorded when the state was reached
+#[derive(Clone)]
+struct StateSnapshot {
+    namespace_store: NamespaceStore,
+    evaluated: HashMap<String, EvaluatedDefinition>,
+}
+
 pub struct ShellState {
     codebase: CodebaseManager,
     #[allow(dead_code)]
@@ -46,6 +71,20 @@ pub struct ShellState {
     salsa_db: vibe_codebase::database::XsDatabaseImpl,
     syntax_mode: SyntaxMode,
     namespace_store: NamespaceStore,
+    storage_path: PathBuf,
+    /// Source of every named definition, in the order they were added
+    definitions: Vec<SourceDefinition>,
+    history: NamespaceHistory,
+    /// Command used by `edit` to open the scratch file
+    editor: Option<String>,
+    /// Definition names `debug` stops at
+    breakpoints: BTreeSet<String>,
+    /// Every evaluation of this session, with the definitions it produced
+    code_repository: CodeRepository,
+    /// What the current definitions evaluated to, by qualified name
+    evaluated: HashMap<String, EvaluatedDefinition>,
+    /// Every history state reached in this session, keyed by state hash
+    snapshots: HashMap<String, StateSnapshot>,
 }
 
 // Helper methods for common operations
@@ -113,7 +152,7 @@ impl ShellState {
         }
 
         checker
-            .check(expr, &mut type_env)
+            .check_spanned(expr, &mut type_env)
             .map_err(|e| anyhow::anyhow!("{}", e))
     }
 
@@ -210,12 +249,25 @@ impl ShellState {
 impl ShellState {
     pub fn new(storage_path: PathBuf) -> Result<Self> {
         let mut codebase = CodebaseManager::new(storage_path.clone())?;
-        let main_branch = codebase.create_branch("main".to_string())?;
-        let session = EditSession::new(main_branch.hash.clone());
+        // Continue on the branch the last session used
+        let current_branch = std::fs::read_to_string(head_path(&storage_path))
+            .map(|head| head.trim().to_string())
+            .ok()
+            .filter(|head| codebase.get_branch(head).is_ok())
+            .unwrap_or_else(|| "main".to_string());
+        if codebase.get_branch(&current_branch).is_err() {
+            codebase.create_branch(current_branch.clone())?;
+        }
+        let session = EditSession::new(codebase.get_branch(&current_branch)?.hash.clone());
+        let mut code_repository = CodeRepository::new(storage_path.join("code_repository.db"))
+            .map_err(anyhow::Error::msg)?;
+        code_repository
+            .start_session()
+            .map_err(anyhow::Error::msg)?;
 
         let mut shell_state = Self {
             codebase,
-            current_branch: "main".to_string(),
+            current_branch: current_branch.clone(),
             current_namespace: "scratch".to_string(),
             session,
             temp_definitions: HashMap::new(),
@@ -226,8 +278,34 @@ impl ShellState {
             salsa_db: vibe_codebase::database::XsDatabaseImpl::new(),
             syntax_mode: SyntaxMode::Auto,
             namespace_store: NamespaceStore::new(),
+            storage_path: storage_path.clone(),
+            definitions: Vec::new(),
+            history: NamespaceHistory::new(),
+            editor: std::env::var("EDITOR")
+                .ok()
+                .filter(|e| !e.trim().is_empty()),
+            breakpoints: BTreeSet::new(),
+            code_repository,
+            evaluated: HashMap::new(),
+            snapshots: HashMap::new(),
         };
 
+        // Restore the definitions from the last session
+        match NamespaceHistory::load(history_path(&storage_path, &current_branch)) {
+            Ok(history) => {
+                let definitions = history.current().definitions.clone();
+                shell_state.history = history;
+                for error in shell_state.restore_definitions(definitions, &HashSet::new()) {
+                    eprintln!("{}: {}", "Warning".yellow(), error);
+                }
+            }
+            Err(e) => eprintln!(
+                "{}: Failed to load namespace history: {}",
+                "Warning".yellow(),
+                e
+            ),
+        }
+
         // Auto-load index.vibes if it exists
         let index_path = PathBuf::from("index.vibes");
         if index_path.exists() {
@@ -244,9 +322,24 @@ impl ShellState {
 
     pub fn evaluate_line(&mut self, line: &str) -> Result<String> {
         // Parse expression using unified parser with current mode
-        let mut expr = parse_unified_with_mode(line, self.syntax_mode)
+        let expr = parse_unified_with_mode(line, self.syntax_mode)
             .map_err(|e| anyhow::anyhow!("Parse error: {}", e))?;
 
+        let defined = Self::defined_name(&expr);
+        let response = self.evaluate_expr(expr, line)?;
+        let qualified = defined
+            .as_ref()
+            .map(|name| format!("{}.{}", self.current_namespace, name));
+        self.record_evaluation(line, qualified.as_deref());
+        if let Some(name) = defined {
+            self.record_state(format!("add {name}"));
+        }
+        Ok(response)
+    }
+
+    /// Evaluate a parsed expression. Definitions remember `source` so they
+    /// can later be edited and replayed.
+    fn evaluate_expr(&mut self, mut expr: Expr, source: &str) -> Result<String> {
         // Resolve hash references before any other processing
         expr = self.resolve_hash_refs(&expr)?;
 
@@ -294,18 +387,7 @@ impl ShellState {
         // Handle use statements
         if let Value::UseStatement { path, items } = &result {
             // Update both runtime and type environments based on the use statement
-            let runtime_functions = match path
-                .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<_>>()
-                .as_slice()
-            {
-                ["lib"] => interpreter.get_lib_runtime_functions(),
-                ["lib", "String"] => interpreter.get_string_runtime_functions(),
-                ["lib", "List"] => interpreter.get_list_runtime_functions(),
-                ["lib", "Int"] => interpreter.get_int_runtime_functions(),
-                _ => HashMap::new(),
-            };
+            let runtime_functions = interpreter.get_module_runtime_functions(path);
 
             // Get type information for the imported functions
             use vibe_language::lib_modules::get_module_functions;
@@ -432,16 +514,21 @@ impl ShellState {
                 let current_ns = NamespacePath::from_str(&self.current_namespace);
                 let def_path = DefinitionPath::new(current_ns, name.0.clone());
                 let content = DefinitionContent::Value((*annotated_value).clone());
+                self.remember_definition(
+                    &name.0,
+                    source,
+                    EvaluatedDefinition {
+                        hash: String::new(),
+                        ty: ty.clone(),
+                        value: result.clone(),
+                        content: content.clone(),
+                    },
+                );
 
                 // Dependencies will be handled later if needed
                 let _ns_dependencies: HashSet<DefinitionHash> = HashSet::new();
 
-                let command = NamespaceCommand::AddDefinition {
-                    path: def_path,
-                    content,
-                    type_signature: ty.clone(),
-                    metadata: Default::default(),
-                };
+                let command = self.namespace_command(def_path, content, ty.clone());
 
                 if let Err(e) = self.namespace_store.execute_command(command) {
                     eprintln!("Warning: Failed to add to namespace store: {}", e);
@@ -537,13 +624,18 @@ impl ShellState {
                     params: params.iter().map(|(p, _)| p.0.clone()).collect(),
                     body: (**body).clone(),
                 };
+                self.remember_definition(
+                    &name.0,
+                    source,
+                    EvaluatedDefinition {
+                        hash: String::new(),
+                        ty: ty.clone(),
+                        value: result.clone(),
+                        content: content.clone(),
+                    },
+                );
 
-                let command = NamespaceCommand::AddDefinition {
-                    path: def_path,
-                    content,
-                    type_signature: ty.clone(),
-                    metadata: Default::default(),
-                };
+                let command = self.namespace_command(def_path, content, ty.clone());
 
                 if let Err(e) = self.namespace_store.execute_command(command) {
                     eprintln!("Warning: Failed to add to namespace store: {}", e);
@@ -718,9 +810,814 @@ impl ShellState {
             .join("\n")
     }
 
+    /// Scratch file that `edit` writes to and `update` reads from
+    pub fn scratch_path(&self) -> PathBuf {
+        self.storage_path.join("scratch.vibe")
+    }
+
+    /// Override the editor taken from `$EDITOR`; `None` only writes the scratch file
+    pub fn set_editor(&mut self, editor: Option<String>) {
+        self.editor = editor;
+    }
+
+    /// Put the source of a definition at the top of the scratch file and open
+    /// it in the editor
+    pub fn edit_definition(&mut self, name_or_hash: &str) -> Result<String> {
+        let name = match self.named_exprs.get(name_or_hash) {
+            Some(_) => name_or_hash.to_string(),
+            None => self
+                .find_expression(name_or_hash)
+                .and_then(|entry| self.find_name_for_hash(&entry.hash))
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Definition '{}' not found", name_or_hash))?,
+        };
+        let definition = self
+            .find_source_definition(&name)
+            .ok_or_else(|| anyhow::anyhow!("No source recorded for '{}'", name))?
+            .clone();
+
+        // The header keeps the namespace, so `update` replaces this definition
+        // even after switching to another namespace
+        let path = self.scratch_path();
+        let existing = std::fs::read_to_string(&path).unwrap_or_default();
+        let entry = format!(
+            "{}{}\n{}",
+            SCRATCH_NAMESPACE_HEADER, definition.namespace, definition.source
+        );
+        if !existing.contains(&entry) {
+            std::fs::write(&path, format!("{}\n\n{}", entry, existing))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+
+        if let Some(editor) = &self.editor {
+            let mut parts = editor.split_whitespace();
+            let program = parts.next().unwrap_or_default();
+            let status = std::process::Command::new(program)
+                .args(parts)
+                .arg(&path)
+                .status()
+                .with_context(|| format!("Failed to launch editor '{editor}'"))?;
+            if !status.success() {
+                anyhow::bail!("Editor exited with {}", status);
+            }
+        }
+
+        Ok(format!(
+            "Added {} to {}\n  Run `update` to apply your changes",
+            definition.name,
+            path.display()
+        ))
+    }
+
+    /// Type check the scratch file and replace the definitions it contains,
+    /// re-checking everything that depends on them
     pub fn update_codebase(&mut self) -> Result<String> {
-        // TODO: セッションの編集をコミット
-        Ok("Update functionality not yet implemented".to_string())
+        let path = self.scratch_path();
+        let text = std::fs::read_to_string(&path).map_err(|_| {
+            anyhow::anyhow!(
+                "No scratch file at {}; use `edit <name>` first",
+                path.display()
+            )
+        })?;
+        let edited = self.parse_scratch(&text)?;
+
+        let mut definitions = self.definitions.clone();
+        let mut changed = Vec::new();
+        for definition in edited {
+            match definitions
+                .iter_mut()
+                .find(|d| d.namespace == definition.namespace && d.name == definition.name)
+            {
+                Some(existing) if existing.source == definition.source => {}
+                Some(existing) => {
+                    changed.push(definition.qualified_name());
+                    *existing = definition;
+                }
+                None => {
+                    changed.push(definition.qualified_name());
+                    definitions.push(definition);
+                }
+            }
+        }
+        if changed.is_empty() {
+            return Ok("Nothing to update; the scratch file matches the codebase".to_string());
+        }
+
+        let old_hashes: HashMap<_, _> = self
+            .definitions
+            .iter()
+            .map(|d| (d.qualified_name(), d.hash.clone()))
+            .collect();
+        let dependents = dependents_of(&definitions, &changed.iter().cloned().collect());
+        // Dependents captured the old values, so they are evaluated again
+        for definition in definitions.iter_mut() {
+            if dependents.contains(&definition.qualified_name()) {
+                definition.hash.clear();
+            }
+        }
+        let previous = self.definitions.clone();
+        let errors = self.restore_definitions(definitions, &changed.iter().cloned().collect());
+        if !errors.is_empty() {
+            // Leave the codebase exactly as it was before the update
+            self.restore_definitions(previous, &HashSet::new());
+            anyhow::bail!(
+                "Update failed, nothing was changed:\n  {}",
+                errors.join("\n  ")
+            );
+        }
+
+        let mut lines = Vec::new();
+        for (label, names) in [("Updated", &changed), ("Propagated", &dependents)] {
+            for qualified in names {
+                let Some(definition) = self
+                    .definitions
+                    .iter()
+                    .find(|d| &d.qualified_name() == qualified)
+                else {
+                    continue;
+                };
+                let ty = self
+                    .evaluated
+                    .get(qualified)
+                    .map(|e| e.ty.to_string())
+                    .unwrap_or_default();
+                let previous_hash = old_hashes
+                    .get(qualified)
+                    .map(|h| format!(" (was [{}])", Self::hash_prefix(h)))
+                    .unwrap_or_default();
+                lines.push(format!(
+                    "{} {} : {} [{}]{}",
+                    label,
+                    self.display_name(definition),
+                    ty,
+                    Self::hash_prefix(&definition.hash),
+                    previous_hash
+                ));
+            }
+        }
+        let updated: Vec<_> = self
+            .definitions
+            .iter()
+            .filter(|d| changed.contains(&d.qualified_name()))
+            .cloned()
+            .collect();
+        for definition in &updated {
+            self.record_evaluation(&definition.source, Some(&definition.qualified_name()));
+        }
+        let names: Vec<_> = updated.iter().map(|d| self.display_name(d)).collect();
+        self.record_state(format!("update {}", names.join(", ")));
+        Ok(lines.join("\n"))
+    }
+
+    /// Go back to the namespace state before the last change
+    pub fn undo(&mut self) -> Result<String> {
+        match self.history.undo()? {
+            Some(undone) => Ok(self.apply_history_state(format!("Undid `{undone}`"))),
+            None => Ok("Nothing to undo".to_string()),
+        }
+    }
+
+    /// Re-apply the last change undone with `undo`
+    pub fn redo(&mut self) -> Result<String> {
+        match self.history.redo()? {
+            Some(redone) => Ok(self.apply_history_state(format!("Redid `{redone}`"))),
+            None => Ok("Nothing to redo".to_string()),
+        }
+    }
+
+    fn apply_history_state(&mut self, message: String) -> String {
+        self.sync_branch_head();
+        let definitions = self.history.current().definitions.clone();
+        let errors = self.restore_definitions(definitions, &HashSet::new());
+        if errors.is_empty() {
+            message
+        } else {
+            format!("{}\n  {}", message, errors.join("\n  "))
+        }
+    }
+
+    pub fn current_namespace(&self) -> &str {
+        &self.current_namespace
+    }
+
+    /// Namespace new definitions are added to
+    pub fn set_namespace(&mut self, name: &str) {
+        self.current_namespace = name.to_string();
+    }
+
+    pub fn current_branch(&self) -> &str {
+        &self.current_branch
+    }
+
+    /// Switch to `name`, forking it from the current branch if it does not exist
+    pub fn switch_branch(&mut self, name: &str) -> Result<String> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            anyhow::bail!(
+                "Invalid branch name '{}': use letters, digits, '-' and '_'",
+                name
+            );
+        }
+        if name == self.current_branch {
+            return Ok(format!("Already on branch {name}"));
+        }
+
+        let created = self.codebase.get_branch(name).is_err();
+        let history = if created {
+            let history = self.history.fork(history_path(&self.storage_path, name))?;
+            self.codebase
+                .fork_branch(&self.current_branch, name.to_string())?;
+            self.codebase.save_branches()?;
+            history
+        } else {
+            NamespaceHistory::load(history_path(&self.storage_path, name))?
+        };
+
+        let message = if created {
+            format!("Created branch {} from {}", name, self.current_branch)
+        } else {
+            format!("Switched to branch {name}")
+        };
+        let definitions = history.current().definitions.clone();
+        self.history = history;
+        self.current_branch = name.to_string();
+        self.session = EditSession::new(self.codebase.get_branch(name)?.hash.clone());
+        let head = head_path(&self.storage_path);
+        if let Some(parent) = head.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(head, name)?;
+
+        let errors = self.restore_definitions(definitions, &HashSet::new());
+        if errors.is_empty() {
+            Ok(message)
+        } else {
+            Ok(format!("{}\n  {}", message, errors.join("\n  ")))
+        }
+    }
+
+    pub fn list_branches(&self) -> String {
+        self.codebase
+            .branches()
+            .into_iter()
+            .map(|branch| {
+                let is_current = branch.name == self.current_branch;
+                let description = if is_current {
+                    Some(self.history.current().description.clone())
+                } else {
+                    NamespaceHistory::load(history_path(&self.storage_path, &branch.name))
+                        .ok()
+                        .map(|h| h.current().description.clone())
+                };
+                format!(
+                    "{} {} [{}] {}",
+                    if is_current { "*" } else { " " },
+                    branch.name,
+                    Self::hash_prefix(&branch.hash),
+                    description.unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Namespace changes on the current branch, newest first
+    pub fn show_log(&self, limit: Option<usize>) -> String {
+        self.history
+            .log()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|state| {
+                let count = state.definitions.len();
+                format!(
+                    "[{}] {} ({} definition{})",
+                    Self::hash_prefix(&state.hash()),
+                    state.description,
+                    count,
+                    if count == 1 { "" } else { "s" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Merge another branch into the current one. Names bound to different
+    /// hashes on both branches since they diverged are reported as conflicts
+    /// and nothing is changed.
+    pub fn merge_branch(&mut self, name: &str) -> Result<String> {
+        if name == self.current_branch {
+            anyhow::bail!("Cannot merge branch {} into itself", name);
+        }
+        self.codebase
+            .get_branch(name)
+            .map_err(|_| anyhow::anyhow!("Branch '{}' not found", name))?;
+
+        let theirs = NamespaceHistory::load(history_path(&self.storage_path, name))?;
+        let ours = self.history.current().definitions.clone();
+        let base = &self.history.common_ancestor(&theirs).definitions;
+        let merged =
+            merge_definitions(base, &ours, &theirs.current().definitions).map_err(|conflicts| {
+                let side = |hash: &Option<String>, branch: &str| match hash {
+                    Some(hash) => format!("[{}] on {}", Self::hash_prefix(hash), branch),
+                    None => format!("removed on {branch}"),
+                };
+                let lines: Vec<_> = conflicts
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{}: {}, {}",
+                            c.name,
+                            side(&c.ours, &self.current_branch),
+                            side(&c.theirs, name)
+                        )
+                    })
+                    .collect();
+                anyhow::anyhow!(
+                    "Merge conflicts, nothing was changed:\n  {}",
+                    lines.join("\n  ")
+                )
+            })?;
+
+        if merged == ours {
+            return Ok(format!("Already up to date with {name}"));
+        }
+        let changed: Vec<_> = merged
+            .iter()
+            .filter(|d| !ours.contains(d))
+            .map(|d| d.name.clone())
+            .collect();
+        let changed_names: HashSet<_> = merged
+            .iter()
+            .filter(|d| !ours.contains(d))
+            .map(|d| d.qualified_name())
+            .collect();
+        let dependents = dependents_of(&merged, &changed_names);
+        let mut merged = merged;
+        for definition in merged.iter_mut() {
+            if dependents.contains(&definition.qualified_name()) {
+                definition.hash.clear();
+            }
+        }
+
+        let errors = self.restore_definitions(merged, &changed_names);
+        if !errors.is_empty() {
+            self.restore_definitions(ours, &HashSet::new());
+            anyhow::bail!(
+                "Merge failed to type check, nothing was changed:\n  {}",
+                errors.join("\n  ")
+            );
+        }
+        self.record_state(format!("merge {name}"));
+        Ok(format!(
+            "Merged {} into {}: {}",
+            name,
+            self.current_branch,
+            changed.join(", ")
+        ))
+    }
+
+    /// Replace every named definition with `definitions`. A state reached
+    /// before is restored from its snapshot, so nothing runs twice. Otherwise
+    /// current definitions with an unchanged hash keep their values and the
+    /// rest are evaluated in dependency order; only evaluations of the
+    /// qualified names in `edited` stay in the edit session. Returns the
+    /// definitions that failed.
+    fn restore_definitions(
+        &mut self,
+        definitions: Vec<SourceDefinition>,
+        edited: &HashSet<String>,
+    ) -> Vec<String> {
+        for definition in std::mem::take(&mut self.definitions) {
+            for name in [definition.name.clone(), definition.qualified_name()] {
+                self.named_exprs.remove(&name);
+                self.type_env.remove(&name);
+                self.runtime_env.remove(&name);
+            }
+        }
+        let state_hash = NamespaceState {
+            description: String::new(),
+            definitions: definitions.clone(),
+        }
+        .hash();
+        let (mut reusable, rebuild_store) = match self.snapshots.get(&state_hash).cloned() {
+            Some(snapshot) => {
+                self.namespace_store = snapshot.namespace_store;
+                (snapshot.evaluated, false)
+            }
+            None => {
+                self.namespace_store = NamespaceStore::new();
+                (std::mem::take(&mut self.evaluated), true)
+            }
+        };
+        self.evaluated.clear();
+
+        let namespace = self.current_namespace.clone();
+        let mut errors = Vec::new();
+        for definition in dependency_order(&definitions) {
+            let qualified = definition.qualified_name();
+            if let Some(evaluated) = reusable
+                .remove(&qualified)
+                .filter(|e| !definition.hash.is_empty() && e.hash == definition.hash)
+            {
+                self.rebind_definition(definition, evaluated, rebuild_store);
+                continue;
+            }
+
+            self.current_namespace = definition.namespace.clone();
+            let edits = self.session.edits.len();
+            let result = Self::parse_definition(&definition.source)
+                .and_then(|expr| self.evaluate_expr(expr, &definition.source));
+            if !edited.contains(&qualified) {
+                self.session.edits.truncate(edits);
+            }
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", definition.name, e));
+            }
+        }
+        self.current_namespace = namespace;
+        if errors.is_empty() {
+            self.snapshot_state();
+        }
+        errors
+    }
+
+    /// Bind a definition to what it evaluated to before
+    fn rebind_definition(
+        &mut self,
+        definition: SourceDefinition,
+        evaluated: EvaluatedDefinition,
+        add_to_store: bool,
+    ) {
+        let mut names = vec![definition.name.clone()];
+        if !definition.namespace.is_empty() && definition.namespace != "main" {
+            names.push(definition.qualified_name());
+        }
+        for name in names {
+            self.named_exprs.insert(name.clone(), definition.hash.clone());
+            self.type_env.insert(name.clone(), evaluated.ty.clone());
+            self.runtime_env.insert(name, evaluated.value.clone());
+        }
+
+        if add_to_store {
+            let path = DefinitionPath::new(
+                NamespacePath::from_str(&definition.namespace),
+                definition.name.clone(),
+            );
+            let command =
+                self.namespace_command(path, evaluated.content.clone(), evaluated.ty.clone());
+            if let Err(e) = self.namespace_store.execute_command(command) {
+                eprintln!("Warning: Failed to add to namespace store: {}", e);
+            }
+        }
+        self.evaluated.insert(definition.qualified_name(), evaluated);
+        self.definitions.push(definition);
+    }
+
+    /// Remember what the current state bound, for `undo`, `redo` and
+    /// branch switches to restore
+    fn snapshot_state(&mut self) {
+        let hash = NamespaceState {
+            description: String::new(),
+            definitions: self.definitions.clone(),
+        }
+        .hash();
+        self.snapshots.insert(
+            hash,
+            StateSnapshot {
+                namespace_store: self.namespace_store.clone(),
+                evaluated: self.evaluated.clone(),
+            },
+        );
+    }
+
+    /// A definition's name as shown to the user, qualified unless it is in
+    /// the current namespace
+    fn display_name(&self, definition: &SourceDefinition) -> String {
+        if definition.namespace == self.current_namespace {
+            definition.name.clone()
+        } else {
+            definition.qualified_name()
+        }
+    }
+
+    /// Split the scratch file into its top-level definitions
+    fn parse_scratch(&self, text: &str) -> Result<Vec<SourceDefinition>> {
+        let expr = vibe_language::parser::parse(text)
+            .map_err(|e| anyhow::anyhow!("Parse error in scratch file: {}", e))?;
+        let exprs = match expr {
+            Expr::Block { exprs, .. } => exprs,
+            expr => vec![expr],
+        };
+
+        exprs
+            .iter()
+            .map(|expr| {
+                let name = Self::defined_name(expr).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Scratch file may only contain definitions, found `{}`",
+                        pretty_print(expr)
+                    )
+                })?;
+                let span = expr.span();
+                let source = text
+                    .get(span.start..span.end)
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_else(|| pretty_print(expr));
+                Ok(SourceDefinition {
+                    namespace: scratch_namespace(text, span.start)
+                        .unwrap_or_else(|| self.current_namespace.clone()),
+                    name,
+                    source,
+                    hash: String::new(),
+                })
+            })
+            .collect()
+    }
+
+    fn parse_definition(source: &str) -> Result<Expr> {
+        match vibe_language::parser::parse(source) {
+            Ok(expr) if Self::defined_name(&expr).is_some() => Ok(expr),
+            _ => parse_unified_with_mode(source, SyntaxMode::Auto)
+                .map_err(|e| anyhow::anyhow!("Parse error: {}", e)),
+        }
+    }
+
+    fn defined_name(expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Let { name, .. } | Expr::Rec { name, .. } => Some(name.0.clone()),
+            _ => None,
+        }
+    }
+
+    fn find_source_definition(&self, name: &str) -> Option<&SourceDefinition> {
+        self.definitions
+            .iter()
+            .rev()
+            .find(|d| d.name == name || format!("{}.{}", d.namespace, d.name) == name)
+    }
+
+    fn remember_definition(
+        &mut self,
+        name: &str,
+        source: &str,
+        mut evaluated: EvaluatedDefinition,
+    ) {
+        let namespace = self.current_namespace.clone();
+        self.definitions
+            .retain(|d| !(d.namespace == namespace && d.name == name));
+        let hash = self
+            .named_exprs
+            .get(name)
+            .or_else(|| self.named_exprs.get(&format!("{namespace}.{name}")))
+            .cloned()
+            .unwrap_or_default();
+        let definition = SourceDefinition {
+            namespace,
+            name: name.to_string(),
+            source: source.trim().to_string(),
+            hash,
+        };
+        evaluated.hash = definition.hash.clone();
+        self.evaluated
+            .insert(definition.qualified_name(), evaluated);
+        self.definitions.push(definition);
+    }
+
+    fn record_state(&mut self, description: String) {
+        let state = NamespaceState {
+            description,
+            definitions: self.definitions.clone(),
+        };
+        if let Err(e) = self.history.record(state) {
+            eprintln!(
+                "{}: Failed to save namespace history: {}",
+                "Warning".yellow(),
+                e
+            );
+        }
+        self.snapshot_state();
+        self.sync_branch_head();
+    }
+
+    /// Point the current branch at the current history state
+    fn sync_branch_head(&mut self) {
+        let head = self.history.current().hash();
+        let result = self
+            .codebase
+            .set_branch_hash(&self.current_branch, head)
+            .and_then(|_| self.codebase.save_branches());
+        if let Err(e) = result {
+            eprintln!("{}: Failed to save branches: {}", "Warning".yellow(), e);
+        }
+    }
+
+    fn namespace_command(
+        &self,
+        path: DefinitionPath,
+        content: DefinitionContent,
+        type_signature: Type,
+    ) -> NamespaceCommand {
+        if self.namespace_store.get_definition_by_path(&path).is_some() {
+            NamespaceCommand::UpdateDefinition {
+                path,
+                content,
+                type_signature,
+            }
+        } else {
+            NamespaceCommand::AddDefinition {
+                path,
+                content,
+                type_signature,
+                metadata: Default::default(),
+            }
+        }
+    }
+
+    /// Write the named definitions and everything they depend on to a
+    /// module file. Without names, exports what was defined this session.
+    pub fn export_module(&mut self, path: &std::path::Path, names: &[String]) -> Result<String> {
+        let exports = if names.is_empty() {
+            self.session_definitions()?
+        } else {
+            names.to_vec()
+        };
+        if exports.is_empty() {
+            anyhow::bail!("Nothing was defined in this session; name the definitions to export");
+        }
+
+        // A name defined in several namespaces is exported as its latest version
+        let mut candidates: Vec<ExportCandidate> = Vec::new();
+        for definition in &self.definitions {
+            candidates.retain(|c| c.definition.name != definition.name);
+            let expr = Self::parse_definition(&definition.source)
+                .with_context(|| format!("Failed to parse {}", definition.name))?;
+            let ty = self
+                .type_env
+                .get(&definition.name)
+                .cloned()
+                .unwrap_or(Type::Unit);
+            candidates.push(ExportCandidate {
+                definition: definition.clone(),
+                expr,
+                ty,
+            });
+        }
+
+        let module = export_module(candidates, &exports, &self.type_env)?;
+        std::fs::write(path, &module.source)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        let count = module.definitions.len();
+        let mut lines = vec![format!(
+            "Exported {} ({} definition{}) to {}",
+            module.exports.join(", "),
+            count,
+            if count == 1 { "" } else { "s" },
+            path.display()
+        )];
+        if !module.unused.is_empty() {
+            lines.push(format!(
+                "Unused, not exported: {}",
+                module.unused.join(", ")
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Record an evaluation in the code repository. A definition is stored
+    /// under its qualified name so that `export` can find what this session
+    /// defined.
+    fn record_evaluation(&mut self, input: &str, qualified: Option<&str>) {
+        let entry = match qualified.and_then(|q| self.evaluated.get(q)) {
+            Some(evaluated) => self
+                .expr_history
+                .iter()
+                .rev()
+                .find(|entry| entry.hash == evaluated.hash),
+            None => self.expr_history.last(),
+        };
+        let Some(entry) = entry else {
+            return;
+        };
+        let value = format_value(&entry.value);
+        let result = match (qualified, vibe_codebase::Hash::from_hex(&entry.hash)) {
+            (Some(name), Ok(hash)) => {
+                let term = Term {
+                    hash: hash.clone(),
+                    name: Some(name.to_string()),
+                    expr: entry.expr.clone(),
+                    ty: entry.ty.clone(),
+                    dependencies: HashSet::new(),
+                };
+                self.code_repository
+                    .store_term(&term, &HashSet::new())
+                    .and_then(|_| {
+                        self.code_repository
+                            .record_evaluation(input, Some(&hash), &value)
+                    })
+            }
+            _ => self.code_repository.record_evaluation(input, None, &value),
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "{}: Failed to record evaluation: {}",
+                "Warning".yellow(),
+                e
+            );
+        }
+    }
+
+    /// Names of the definitions evaluated in this session that still exist,
+    /// in the order they were first evaluated
+    fn session_definitions(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        for qualified in self
+            .code_repository
+            .session_definitions()
+            .map_err(anyhow::Error::msg)?
+        {
+            if let Some(definition) = self
+                .definitions
+                .iter()
+                .find(|d| d.qualified_name() == qualified)
+            {
+                if !names.contains(&definition.name) {
+                    names.push(definition.name.clone());
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Parse and type check an expression for `trace` and `debug`, returning
+    /// it with the environment it runs in
+    fn prepare_expr(&self, line: &str) -> Result<(Expr, Type, vibe_language::Environment)> {
+        let expr = parse_unified_with_mode(line, self.syntax_mode)
+            .map_err(|e| anyhow::anyhow!("Parse error: {}", e))?;
+        let expr = self.resolve_hash_refs(&expr)?;
+        let ty = self
+            .type_check_with_env(&expr)
+            .map_err(|e| anyhow::anyhow!("Type error: {}", e))?;
+
+        let mut env = Interpreter::create_initial_env();
+        for (name, val) in &self.runtime_env {
+            env = env.extend(Ident(name.clone()), val.clone());
+        }
+        Ok((expr, ty, env))
+    }
+
+    /// Evaluate an expression and show every application, match arm and
+    /// effect as an indented tree, followed by the result
+    pub fn trace_expr(&mut self, line: &str) -> Result<String> {
+        let (expr, ty, env) = self.prepare_expr(line)?;
+        let tracer = Arc::new(Mutex::new(Tracer::new()));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_observer(Some(tracer.clone()));
+        let result = interpreter.eval(&expr, &env);
+
+        let trace = tracer.lock().unwrap_or_else(|e| e.into_inner()).render();
+        match result {
+            Ok(value) => Ok(format!("{}\n{} : {}", trace, format_value(&value), ty)),
+            Err(e) => Err(anyhow::anyhow!("Evaluation failed: {}\n{}", e, trace)),
+        }
+    }
+
+    /// Evaluate an expression under the step debugger. Without breakpoints
+    /// it pauses before the first application.
+    pub fn debug_expr(&mut self, line: &str, frontend: Box<dyn DebugFrontend>) -> Result<String> {
+        let (expr, ty, env) = self.prepare_expr(line)?;
+        let mut debugger = Debugger::new(frontend).with_breakpoints(self.breakpoints.clone());
+        if self.breakpoints.is_empty() {
+            debugger = debugger.stop_on_entry();
+        }
+        let mut interpreter = Interpreter::new();
+        interpreter.set_observer(Some(Arc::new(Mutex::new(debugger))));
+        let value = interpreter.eval(&expr, &env).context("Evaluation failed")?;
+        Ok(format!("{} : {}", format_value(&value), ty))
+    }
+
+    /// Set a breakpoint on a definition name, or remove it if it is set
+    pub fn toggle_breakpoint(&mut self, name: &str) -> String {
+        if self.breakpoints.remove(name) {
+            format!("Removed breakpoint on {name}")
+        } else {
+            self.breakpoints.insert(name.to_string());
+            format!("Breakpoint set on {name}")
+        }
+    }
+
+    pub fn list_breakpoints(&self) -> String {
+        if self.breakpoints.is_empty() {
+            "No breakpoints".to_string()
+        } else {
+            self.breakpoints
+                .iter()
+                .map(|name| format!("  {name}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
     }
 
     pub fn type_of_expr(&mut self, expr_str: &str) -> Result<String> {
@@ -923,7 +1820,7 @@ impl ShellState {
 
     pub fn execute_pipeline(&self, commands: &[String]) -> Result<String> {
         use chrono::Utc;
-        use vibe_codebase::pipeline::parse_pipeline_operator;
+        use vibe_codebase::pipeline::{Pipeline, PipelineScope};
         use vibe_codebase::structured_data::{
             format_structured_data, DefinitionData, DefinitionKind, DefinitionMetadata,
             StructuredData,
@@ -935,7 +1832,11 @@ impl ShellState {
 
         // Execute the first command to get initial data
         let first_cmd = &commands[0];
-        let mut data = if first_cmd == "definitions" || first_cmd == "ls" {
+        // `from json file` is itself a stage that reads its input
+        let first_stage = usize::from(!first_cmd.starts_with("from "));
+        let mut data = if first_stage == 0 {
+            StructuredData::Empty
+        } else if first_cmd == "definitions" || first_cmd == "ls" {
             // Convert current definitions to structured data
             let mut defs = Vec::new();
             for (name, hash) in &self.named_exprs {
@@ -1014,17 +1915,28 @@ impl ShellState {
             }
             StructuredData::Definitions(defs)
         } else {
-            return Err(anyhow::anyhow!(
-                "First command must be 'definitions', 'ls', or 'search'"
-            ));
+            // Any expression, such as a list of records
+            let (expr, _, env) = self.prepare_expr(first_cmd)?;
+            let value = Interpreter::new()
+                .eval(&expr, &env)
+                .context("Evaluation failed")?;
+            StructuredData::from_value(&value)
         };
 
-        // Apply pipeline operators
-        for cmd in &commands[1..] {
-            let operator = parse_pipeline_operator(cmd)?;
-            data = operator.apply(data)?;
+        // Stage lambdas can use the shell's definitions, and stages may read
+        // files
+        let mut scope = PipelineScope::new();
+        scope.allow(vibe_language::Effect::FileSystem);
+        for (name, value) in &self.runtime_env {
+            if let Some(ty) = self.type_env.get(name) {
+                scope.bind(name.clone(), value.clone(), ty.clone());
+            }
         }
 
+        // Type check every stage, then run them
+        let pipeline = Pipeline::parse(&commands[first_stage..], &scope)?;
+        data = pipeline.run(data)?;
+
         // Format and return the result
         Ok(format_structured_data(&data))
     }
@@ -1153,6 +2065,20 @@ impl ShellState {
     }
 }
 
+/// Comment line in the scratch file naming the namespace of the definition
+/// below it
+const SCRATCH_NAMESPACE_HEADER: &str = "# namespace: ";
+
+/// Namespace named by the header directly above the definition at `start`
+fn scratch_namespace(text: &str, start: usize) -> Option<String> {
+    let before = text.get(..start)?;
+    let line = before.lines().rev().find(|l| !l.trim().is_empty())?;
+    line.trim()
+        .strip_prefix(SCRATCH_NAMESPACE_HEADER.trim_end())
+        .map(|namespace| namespace.trim().to_string())
+        .filter(|namespace| !namespace.is_empty())
+}
+
 fn format_value(val: &Value) -> String {
     match val {
         Value::Int(n) => n.to_string(),
@@ -1324,12 +2250,78 @@ pub fn run_repl() -> Result<()> {
                                 );
                             }
 
+                            Command::Edit(name) => match state.edit_definition(&name) {
+                                Ok(result) => println!("{result}"),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Update => match state.update_codebase() {
+                                Ok(result) => println!("{}", result.green()),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Undo => match state.undo() {
+                                Ok(result) => println!("{result}"),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Redo => match state.redo() {
+                                Ok(result) => println!("{result}"),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Branch(None) => {
+                                println!("Current branch: {}", state.current_branch().cyan());
+                            }
+
+                            Command::Branch(Some(name)) => match state.switch_branch(&name) {
+                                Ok(result) => println!("{result}"),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Branches => println!("{}", state.list_branches()),
+
+                            Command::Merge(name) => match state.merge_branch(&name) {
+                                Ok(result) => println!("{}", result.green()),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Log(limit) => println!("{}", state.show_log(limit)),
+
+                            Command::Export(path, names) => {
+                                match state.export_module(std::path::Path::new(&path), &names) {
+                                    Ok(result) => println!("{}", result.green()),
+                                    Err(e) => println!("{}: {}", "Error".red(), e),
+                                }
+                            }
+
+                            Command::Trace(expr) => match state.trace_expr(&expr) {
+                                Ok(result) => println!("{result}"),
+                                Err(e) => println!("{}: {}", "Error".red(), e),
+                            },
+
+                            Command::Debug(expr) => {
+                                println!("Debugging {} (type `help` at the prompt)", expr.cyan());
+                                let frontend =
+                                    Box::new(crate::debug_console::ConsoleFrontend::stdio());
+                                match state.debug_expr(&expr, frontend) {
+                                    Ok(result) => println!("{result}"),
+                                    Err(e) => println!("{}: {}", "Error".red(), e),
+                                }
+                            }
+
+                            Command::Break(None) => println!("{}", state.list_breakpoints()),
+
+                            Command::Break(Some(name)) => {
+                                println!("{}", state.toggle_breakpoint(&name));
+                            }
+
                             Command::Namespace(None) => {
-                                println!("Current namespace: {}", state.current_namespace.cyan());
+                                println!("Current namespace: {}", state.current_namespace().cyan());
                             }
 
                             Command::Namespace(Some(name)) => {
-                                state.current_namespace = name.clone();
+                                state.set_namespace(&name);
                                 println!("Changed to namespace: {}", name.cyan());
                             }
 
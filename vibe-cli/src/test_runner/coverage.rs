@@ -0,0 +1,517 @@
+//! Coverage of test runs
+//!
+//! With `vibe test --coverage` every test is evaluated under a
+//! `CoverageRecorder` and the hits are collected per file. A report relates
+//! them to the source of the file: lines and branches for the whole file,
+//! and expressions, branches and calls for each top-level definition.
+//!
+//! A definition's data is stored under its hash, with spans relative to the
+//! start of the definition, so it stays the same when the definition is
+//! renamed or moved.
+
+use super::report::escape_xml;
+use super::{for_each_expr, untyped_hash};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vibe_language::parser::parse;
+use vibe_language::{Expr, Span};
+use vibe_runtime::CoverageRecorder;
+
+/// Hits of every test run, by the file the tests are in
+#[derive(Debug, Default)]
+pub struct Coverage {
+    files: BTreeMap<PathBuf, CoverageRecorder>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the hits of a test in `file`
+    pub fn record(&mut self, file: &Path, hits: &CoverageRecorder) {
+        self.files.entry(file.to_path_buf()).or_default().merge(hits);
+    }
+
+    /// The hits of the tests in `file`
+    pub fn hits(&self, file: &Path) -> Option<&CoverageRecorder> {
+        self.files.get(file)
+    }
+
+    /// Relate the hits to the source of each file
+    pub fn report(&self) -> Result<CoverageReport> {
+        let mut files = Vec::new();
+        for (file, hits) in &self.files {
+            let source = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let expr = parse(&source)
+                .map_err(|e| anyhow::anyhow!("{}: parse error: {}", file.display(), e))?;
+            files.push(FileCoverage::new(file.clone(), source, &expr, hits));
+        }
+        Ok(CoverageReport { files })
+    }
+}
+
+/// Hits of an expression, by its span
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpanHits {
+    pub start: usize,
+    pub end: usize,
+    pub hits: u64,
+}
+
+/// How often a branch of an `if` or `match` was taken
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BranchHits {
+    pub start: usize,
+    pub end: usize,
+    /// For `if`, 0 is `then` and 1 is `else`; for `match`, the arm
+    pub branch: usize,
+    pub hits: u64,
+    /// Whether the `if` or `match` itself was evaluated
+    #[serde(skip)]
+    pub reached: bool,
+    #[serde(skip)]
+    pub line: usize,
+}
+
+/// Coverage of a top-level definition
+#[derive(Debug, Clone, Serialize)]
+pub struct DefinitionCoverage {
+    pub hash: String,
+    pub name: String,
+    pub file: PathBuf,
+    pub line: usize,
+    /// How often the function was called, or the value evaluated
+    pub calls: u64,
+    /// Spans relative to the start of the definition
+    pub expressions: Vec<SpanHits>,
+    /// Spans relative to the start of the definition
+    pub branches: Vec<BranchHits>,
+}
+
+impl DefinitionCoverage {
+    /// Evaluated and total expressions
+    pub fn expression_counts(&self) -> (usize, usize) {
+        let covered = self.expressions.iter().filter(|e| e.hits > 0).count();
+        (covered, self.expressions.len())
+    }
+
+    /// Taken and total branches
+    pub fn branch_counts(&self) -> (usize, usize) {
+        let covered = self.branches.iter().filter(|b| b.hits > 0).count();
+        (covered, self.branches.len())
+    }
+}
+
+/// Coverage of a file with tests
+#[derive(Debug, Clone)]
+pub struct FileCoverage {
+    pub file: PathBuf,
+    source: String,
+    /// Hits of each line that has an expression of a definition starting on it
+    pub lines: BTreeMap<usize, u64>,
+    /// Branches with absolute spans
+    pub branches: Vec<BranchHits>,
+    pub definitions: Vec<DefinitionCoverage>,
+}
+
+impl FileCoverage {
+    fn new(file: PathBuf, source: String, expr: &Expr, hits: &CoverageRecorder) -> Self {
+        let lines_at = LineIndex::new(&source);
+        let mut coverage = FileCoverage {
+            file,
+            source: String::new(),
+            lines: BTreeMap::new(),
+            branches: Vec::new(),
+            definitions: Vec::new(),
+        };
+
+        let exprs = match expr {
+            Expr::Block { exprs, .. } => exprs.as_slice(),
+            expr => std::slice::from_ref(expr),
+        };
+        for expr in exprs {
+            // Only what definitions contain is counted: test calls and the
+            // file itself are never evaluated as a whole
+            let (name, value, body) = match expr {
+                Expr::Let { name, value, .. } | Expr::LetRec { name, value, .. } => {
+                    let body = match value.as_ref() {
+                        Expr::Lambda { body, .. } => body.as_ref(),
+                        value => value,
+                    };
+                    (name, value.as_ref(), body)
+                }
+                Expr::Rec { name, body, .. } => (name, expr, body.as_ref()),
+                _ => continue,
+            };
+            let base = value.span().start;
+            let mut definition = DefinitionCoverage {
+                hash: untyped_hash(value).to_hex(),
+                name: name.0.clone(),
+                file: coverage.file.clone(),
+                line: lines_at.line(base),
+                calls: hits.hits(body.span()),
+                expressions: Vec::new(),
+                branches: Vec::new(),
+            };
+            let root = match expr {
+                Expr::Rec { body, .. } => body.as_ref(),
+                _ => value,
+            };
+            for_each_expr(root, &mut |expr| {
+                let span = expr.span();
+                if span.start >= span.end {
+                    return;
+                }
+                let count = hits.hits(span);
+                let line = lines_at.line(span.start);
+                let line_hits = coverage.lines.entry(line).or_default();
+                *line_hits = (*line_hits).max(count);
+                definition.expressions.push(SpanHits {
+                    start: span.start - base,
+                    end: span.end - base,
+                    hits: count,
+                });
+
+                let branch_count = match expr {
+                    Expr::If { .. } => 2,
+                    Expr::Match { cases, .. } => cases.len(),
+                    _ => 0,
+                };
+                for branch in 0..branch_count {
+                    let branch_hits = BranchHits {
+                        start: span.start,
+                        end: span.end,
+                        branch,
+                        hits: hits.branch_hits(span, branch),
+                        reached: count > 0,
+                        line,
+                    };
+                    definition.branches.push(BranchHits {
+                        start: span.start - base,
+                        end: span.end - base,
+                        ..branch_hits.clone()
+                    });
+                    coverage.branches.push(branch_hits);
+                }
+            });
+            coverage.definitions.push(definition);
+        }
+        coverage.source = source;
+        coverage
+    }
+
+    /// Covered and total lines
+    pub fn line_counts(&self) -> (usize, usize) {
+        let covered = self.lines.values().filter(|hits| **hits > 0).count();
+        (covered, self.lines.len())
+    }
+
+    /// Taken and total branches
+    pub fn branch_counts(&self) -> (usize, usize) {
+        let covered = self.branches.iter().filter(|b| b.hits > 0).count();
+        (covered, self.branches.len())
+    }
+}
+
+/// Coverage of every file with tests
+#[derive(Debug, Clone)]
+pub struct CoverageReport {
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageReport {
+    /// Definitions by hash. Identical definitions in several files share an
+    /// entry, with their hits added up.
+    pub fn definitions(&self) -> BTreeMap<String, DefinitionCoverage> {
+        let mut definitions: BTreeMap<String, DefinitionCoverage> = BTreeMap::new();
+        for definition in self.files.iter().flat_map(|f| &f.definitions) {
+            match definitions.get_mut(&definition.hash) {
+                Some(existing) => {
+                    existing.calls += definition.calls;
+                    for (total, hits) in existing.expressions.iter_mut().zip(&definition.expressions) {
+                        total.hits += hits.hits;
+                    }
+                    for (total, hits) in existing.branches.iter_mut().zip(&definition.branches) {
+                        total.hits += hits.hits;
+                    }
+                }
+                None => {
+                    definitions.insert(definition.hash.clone(), definition.clone());
+                }
+            }
+        }
+        definitions
+    }
+
+    /// The report in the lcov tracefile format
+    pub fn lcov(&self) -> String {
+        let mut lcov = String::new();
+        for file in &self.files {
+            let _ = writeln!(lcov, "TN:\nSF:{}", file.file.display());
+            for definition in &file.definitions {
+                let _ = writeln!(lcov, "FN:{},{}", definition.line, definition.name);
+            }
+            for definition in &file.definitions {
+                let _ = writeln!(lcov, "FNDA:{},{}", definition.calls, definition.name);
+            }
+            let called = file.definitions.iter().filter(|d| d.calls > 0).count();
+            let _ = writeln!(lcov, "FNF:{}\nFNH:{}", file.definitions.len(), called);
+
+            let mut blocks: Vec<(usize, usize)> = Vec::new();
+            for branch in &file.branches {
+                if blocks.last() != Some(&(branch.start, branch.end)) {
+                    blocks.push((branch.start, branch.end));
+                }
+                let taken = if branch.reached {
+                    branch.hits.to_string()
+                } else {
+                    "-".to_string()
+                };
+                let _ = writeln!(
+                    lcov,
+                    "BRDA:{},{},{},{}",
+                    branch.line,
+                    blocks.len() - 1,
+                    branch.branch,
+                    taken
+                );
+            }
+            let (taken, total) = file.branch_counts();
+            let _ = writeln!(lcov, "BRF:{total}\nBRH:{taken}");
+
+            for (line, hits) in &file.lines {
+                let _ = writeln!(lcov, "DA:{line},{hits}");
+            }
+            let (covered, total) = file.line_counts();
+            let _ = writeln!(lcov, "LF:{total}\nLH:{covered}\nend_of_record");
+        }
+        lcov
+    }
+
+    /// Coverage per file and per definition, for the terminal
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", "Coverage".bright_blue().bold());
+        let _ = writeln!(out, "{}", "========".bright_blue());
+        for file in &self.files {
+            let (lines, total_lines) = file.line_counts();
+            let (branches, total_branches) = file.branch_counts();
+            let _ = writeln!(
+                out,
+                "{}  lines {}  branches {}",
+                file.file.display().to_string().bold(),
+                ratio(lines, total_lines),
+                ratio(branches, total_branches)
+            );
+            for definition in &file.definitions {
+                let (exprs, total_exprs) = definition.expression_counts();
+                let (branches, total_branches) = definition.branch_counts();
+                let name = if definition.calls == 0 {
+                    definition.name.red()
+                } else {
+                    definition.name.normal()
+                };
+                let _ = writeln!(
+                    out,
+                    "  {} #{}  calls {}  expressions {}  branches {}",
+                    name,
+                    &definition.hash[..8],
+                    definition.calls,
+                    ratio(exprs, total_exprs),
+                    ratio(branches, total_branches)
+                );
+            }
+        }
+        out
+    }
+
+    /// Write lcov, JSON by definition hash and HTML into `dir`
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        fs::write(dir.join("lcov.info"), self.lcov())?;
+        let definitions = serde_json::to_string_pretty(&self.definitions())?;
+        fs::write(dir.join("coverage.json"), definitions)?;
+
+        let mut index = html_header("Coverage");
+        index.push_str("<table>\n<tr><th>File</th><th>Lines</th><th>Branches</th></tr>\n");
+        for file in &self.files {
+            let page = page_name(&file.file);
+            let (lines, total_lines) = file.line_counts();
+            let (branches, total_branches) = file.branch_counts();
+            let _ = writeln!(
+                index,
+                "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+                escape_xml(&page),
+                escape_xml(&file.file.display().to_string()),
+                ratio(lines, total_lines),
+                ratio(branches, total_branches)
+            );
+            fs::write(dir.join(&page), file_page(file))?;
+        }
+        index.push_str("</table>\n<h2>Definitions</h2>\n<table>\n");
+        index.push_str(
+            "<tr><th>Definition</th><th>Hash</th><th>Calls</th><th>Expressions</th><th>Branches</th></tr>\n",
+        );
+        for definition in self.definitions().values() {
+            let (exprs, total_exprs) = definition.expression_counts();
+            let (branches, total_branches) = definition.branch_counts();
+            let _ = writeln!(
+                index,
+                "<tr class=\"{}\"><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                if definition.calls > 0 { "hit" } else { "miss" },
+                escape_xml(&definition.name),
+                &definition.hash[..8],
+                definition.calls,
+                ratio(exprs, total_exprs),
+                ratio(branches, total_branches)
+            );
+        }
+        index.push_str("</table>\n</body>\n</html>\n");
+        fs::write(dir.join("index.html"), index)?;
+        Ok(())
+    }
+}
+
+/// Source of `file` with the hits of each line
+fn file_page(file: &FileCoverage) -> String {
+    let mut page = html_header(&file.file.display().to_string());
+    page.push_str("<p><a href=\"index.html\">All files</a></p>\n<table class=\"source\">\n");
+    for (number, text) in file.source.lines().enumerate() {
+        let line = number + 1;
+        let (class, hits) = match file.lines.get(&line) {
+            Some(0) => ("miss", "0".to_string()),
+            Some(hits) => ("hit", hits.to_string()),
+            None => ("", String::new()),
+        };
+        let _ = writeln!(
+            page,
+            "<tr class=\"{class}\"><td>{line}</td><td>{hits}</td><td><pre>{}</pre></td></tr>",
+            escape_xml(text)
+        );
+    }
+    page.push_str("</table>\n</body>\n</html>\n");
+    page
+}
+
+fn html_header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>\n\
+         body {{ font-family: sans-serif; }}\n\
+         td, th {{ padding: 0 0.5em; text-align: left; }}\n\
+         pre {{ margin: 0; }}\n\
+         .hit {{ background: #dfd; }}\n\
+         .miss {{ background: #fdd; }}\n\
+         </style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_xml(title)
+    )
+}
+
+/// Name of the HTML page of a file
+fn page_name(file: &Path) -> String {
+    let name: String = file
+        .display()
+        .to_string()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    format!("{name}.html")
+}
+
+fn ratio(covered: usize, total: usize) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{}/{} ({:.1}%)", covered, total, covered as f64 * 100.0 / total as f64)
+}
+
+/// 1-based lines of character offsets
+struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(source: &str) -> Self {
+        let mut starts = vec![0];
+        for (offset, ch) in source.chars().enumerate() {
+            if ch == '\n' {
+                starts.push(offset + 1);
+            }
+        }
+        LineIndex { starts }
+    }
+
+    fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|start| *start <= offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::{Ident, Literal};
+
+    #[test]
+    fn test_hits_are_reported_per_line_branch_and_definition() {
+        // let pick = fn b ->
+        //   if b then 1 else 2
+        let source = "let pick = fn b ->\n  if b then 1 else 2\n";
+        let span = Span::new;
+        let body = Expr::If {
+            cond: Box::new(Expr::Ident(Ident("b".to_string()), span(24, 25))),
+            then_expr: Box::new(Expr::Literal(Literal::Int(1), span(31, 32))),
+            else_expr: Box::new(Expr::Literal(Literal::Int(2), span(38, 39))),
+            span: span(21, 39),
+        };
+        let definition = |name: &str| Expr::Let {
+            name: Ident(name.to_string()),
+            type_ann: None,
+            value: Box::new(Expr::Lambda {
+                params: vec![(Ident("b".to_string()), None)],
+                body: Box::new(body.clone()),
+                span: span(11, 39),
+            }),
+            span: span(0, 39),
+        };
+
+        let mut hits = CoverageRecorder::new();
+        for (start, end) in [(11, 39), (21, 39), (24, 25), (31, 32)] {
+            hits.expressions.insert((start, end), 1);
+        }
+        hits.branches.entry((21, 39)).or_default().insert(0, 1);
+
+        let file = FileCoverage::new(
+            PathBuf::from("pick.vibe"),
+            source.to_string(),
+            &definition("pick"),
+            &hits,
+        );
+        assert_eq!(file.lines.get(&1), Some(&1));
+        assert_eq!(file.line_counts(), (2, 2));
+        assert_eq!(file.branch_counts(), (1, 2));
+        assert_eq!(file.definitions[0].calls, 1);
+        assert_eq!(file.definitions[0].expression_counts(), (4, 5));
+
+        let report = CoverageReport { files: vec![file] };
+        let lcov = report.lcov();
+        assert!(lcov.contains("FN:1,pick\nFNDA:1,pick\n"), "{lcov}");
+        assert!(lcov.contains("BRDA:2,0,0,1\nBRDA:2,0,1,0\nBRF:2\nBRH:1\n"), "{lcov}");
+        assert!(lcov.contains("DA:2,1\nLF:2\nLH:2\nend_of_record"), "{lcov}");
+
+        // A renamed definition keeps its hash and relative spans
+        let renamed = FileCoverage::new(
+            PathBuf::from("pick.vibe"),
+            source.to_string(),
+            &definition("choose"),
+            &hits,
+        );
+        assert_eq!(renamed.definitions[0].hash, report.files[0].definitions[0].hash);
+        assert_eq!(renamed.definitions[0].expressions, report.files[0].definitions[0].expressions);
+    }
+}
@@ -2,6 +2,24 @@
 //!
 //! This module provides a test framework that caches test results
 //! based on the content of the test and its dependencies.
+//!
+//! The top-level definitions of a test file are hashed like codebase
+//! definitions, and a test's result is stored under its own hash together
+//! with the hashes of the definitions it transitively uses. A test is only
+//! run again when one of those changes.
+//!
+//! `property "name" (fn x:Int -> ...)`, or `forAll`, is a test whose inputs
+//! are generated from its parameter types (see `vibe_runtime::property`).
+//! `snapshot "name" expr` compares `expr` with a stored snapshot (see
+//! `snapshot`).
+//!
+//! Besides the coloured text, results can be reported as JUnit XML, TAP or
+//! JSON, or followed while the tests run (see `report`). With coverage
+//! enabled, the code each test evaluates is recorded (see `coverage`).
+//!
+//! With more than one job, tests run concurrently, each with its own
+//! interpreter. Tests that may use IO, the file system or state run one at a
+//! time, and results are always reported in file and line order.
 
 #![allow(unused_imports)]
 
@@ -16,9 +34,31 @@ use thiserror::Error;
 use vibe_compiler::{TypeChecker, TypeEnv};
 use vibe_language::parser::parse;
 use vibe_language::{Expr, Ident, Value, XsError};
+use vibe_runtime::property::{Generators, PropertyConfig, PropertyOutcome};
 use vibe_runtime::Interpreter;
+use vibe_codebase::hash::DefinitionHash;
+use vibe_codebase::namespace::{
+    DefinitionContent, DefinitionMetadata, DefinitionPath, NamespaceCommand, NamespacePath,
+    NamespaceStore,
+};
+use vibe_codebase::test_cache::{result_key, CachedTestResult, TestResultCache};
 use vibe_codebase::{Codebase, Hash};
 
+pub mod coverage;
+pub mod report;
+pub mod snapshot;
+
+use coverage::Coverage;
+use snapshot::{OutputCapture, Snapshot, SnapshotOutcome};
+use report::{TestEvent, TestListener};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{mpsc, Arc, Mutex};
+use vibe_runtime::{CoverageRecorder, SharedObserver};
+
+/// Effects that make a test run apart from other such tests
+const SERIAL_EFFECTS: [&str; 3] = ["IO", "FileSystem", "State"];
+
 /// Test outcome placeholder for now
 #[derive(Debug, Clone)]
 pub enum TestOutcome {
@@ -42,6 +82,33 @@ pub enum TestError {
     IoError(String),
 }
 
+impl TestError {
+    /// Recover the kind of failure from a failed test's message, including
+    /// the expected and actual values of a failed assertion
+    pub fn from_failure(message: &str) -> Self {
+        let assertion = ["Expected ", "expected "].iter().find_map(|marker| {
+            let (_, rest) = message.split_once(marker)?;
+            let rest = rest.strip_prefix("type ").unwrap_or(rest);
+            let (expected, actual) = rest.split_once(", got ")?;
+            let actual = actual.lines().next().unwrap_or_default();
+            Some(TestError::AssertionFailed {
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            })
+        });
+        if let Some(assertion) = assertion {
+            return assertion;
+        }
+        if let Some(error) = message.strip_prefix("Parse error: ") {
+            TestError::ParseError(error.to_string())
+        } else if let Some(error) = message.strip_prefix("Type error: ") {
+            TestError::TypeError(error.to_string())
+        } else {
+            TestError::RuntimeError(message.to_string())
+        }
+    }
+}
+
 /// Test case definition
 #[derive(Debug, Clone)]
 pub struct TestCase {
@@ -49,6 +116,10 @@ pub struct TestCase {
     pub file: PathBuf,
     pub expr: Expr,
     pub expected: Option<ExpectedResult>,
+    /// Key of the test's result in the cache
+    pub cache_key: String,
+    /// Effects the test may perform
+    pub effects: BTreeSet<String>,
 }
 
 /// In-source test definition
@@ -57,6 +128,32 @@ pub struct InSourceTest {
     pub name: String,
     pub test_expr: Expr,
     pub location: (usize, usize), // line, column
+    pub kind: TestKind,
+    /// Key of the test's result in the cache
+    pub cache_key: String,
+    /// Effects the test may perform
+    pub effects: BTreeSet<String>,
+}
+
+/// What an in-source test checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestKind {
+    /// `test "name" (fn -> ...)` passes unless it fails
+    Test,
+    /// `property "name" (fn x:Int -> ...)` holds for generated inputs
+    Property,
+    /// `snapshot "name" expr` matches its stored snapshot
+    Snapshot,
+}
+
+impl TestKind {
+    fn of(function: &str) -> Self {
+        match function {
+            "snapshot" => TestKind::Snapshot,
+            "property" | "forAll" => TestKind::Property,
+            _ => TestKind::Test,
+        }
+    }
 }
 
 /// Expected result for a test
@@ -75,6 +172,18 @@ pub struct TestResult {
     pub outcome: TestOutcome,
     pub duration: Duration,
     pub cached: bool,
+    /// Line and column of an in-source test
+    pub location: Option<(usize, usize)>,
+}
+
+impl TestResult {
+    /// Why the test failed, if it did
+    pub fn error(&self) -> Option<TestError> {
+        match &self.outcome {
+            TestOutcome::Failed { error } => Some(TestError::from_failure(error)),
+            _ => None,
+        }
+    }
 }
 
 /// Test summary
@@ -85,6 +194,8 @@ pub struct TestSummary {
     pub failed: usize,
     pub cached: usize,
     pub duration: Duration,
+    /// Every result, in file and line order
+    pub results: Vec<TestResult>,
 }
 
 /// Test suite that manages multiple tests
@@ -93,18 +204,155 @@ pub struct TestSuite {
     in_source_tests: Vec<(PathBuf, InSourceTest)>,
     #[allow(dead_code)]
     codebase: Codebase,
+    cache: TestResultCache,
+    property_config: PropertyConfig,
+    /// Only used by the thread reporting results, but behind a lock so that
+    /// the suite can be shared with the threads running tests
+    listeners: Mutex<Vec<Box<dyn TestListener>>>,
+    text_output: bool,
+    coverage: Option<Coverage>,
+    jobs: usize,
+    update_snapshots: bool,
     verbose: bool,
 }
 
+/// A loaded test
+#[derive(Debug, Clone)]
+enum PendingTest {
+    Case(TestCase),
+    InSource(PathBuf, InSourceTest),
+}
+
+impl PendingTest {
+    fn name(&self) -> &str {
+        match self {
+            PendingTest::Case(test) => &test.name,
+            PendingTest::InSource(_, test) => &test.name,
+        }
+    }
+
+    fn file(&self) -> &Path {
+        match self {
+            PendingTest::Case(test) => &test.file,
+            PendingTest::InSource(file, _) => file,
+        }
+    }
+
+    /// Where the test is, for ordering; a whole-file test comes first
+    fn position(&self) -> (&Path, usize, usize) {
+        match self {
+            PendingTest::Case(test) => (&test.file, 0, 0),
+            PendingTest::InSource(file, test) => (file, test.location.0, test.location.1),
+        }
+    }
+
+    fn cache_key(&self) -> &str {
+        match self {
+            PendingTest::Case(test) => &test.cache_key,
+            PendingTest::InSource(_, test) => &test.cache_key,
+        }
+    }
+
+    /// Whether the test may affect other tests through its effects
+    fn serial(&self) -> bool {
+        let effects = match self {
+            PendingTest::Case(test) => &test.effects,
+            PendingTest::InSource(_, test) => &test.effects,
+        };
+        SERIAL_EFFECTS.iter().any(|effect| effects.contains(*effect))
+    }
+}
+
+/// A test that ran or was found in the cache, before it is recorded
+struct TestRun {
+    result: TestResult,
+    /// Whether the result may be cached
+    cacheable: bool,
+    coverage: Option<CoverageRecorder>,
+}
+
+impl TestRun {
+    fn uncacheable(result: TestResult) -> Self {
+        TestRun {
+            result,
+            cacheable: false,
+            coverage: None,
+        }
+    }
+}
+
 impl TestSuite {
     pub fn new(verbose: bool) -> Self {
         TestSuite {
             tests: Vec::new(),
             in_source_tests: Vec::new(),
             codebase: Codebase::new(),
+            cache: TestResultCache::in_memory(),
+            property_config: PropertyConfig::default(),
+            listeners: Mutex::new(Vec::new()),
+            text_output: true,
+            coverage: None,
+            jobs: 1,
+            update_snapshots: false,
             verbose,
         }
     }
+
+    /// Run properties with `config`; set it before loading tests
+    pub fn with_property_config(mut self, config: PropertyConfig) -> Self {
+        self.property_config = config;
+        self
+    }
+
+    /// Reuse and record results in `cache`
+    pub fn with_cache(mut self, cache: TestResultCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Tell `listener` about the run as it happens
+    pub fn with_listener(mut self, listener: Box<dyn TestListener>) -> Self {
+        self.listeners
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(listener);
+        self
+    }
+
+    /// Accept what snapshot tests produce, rewriting snapshots that changed
+    pub fn with_snapshot_updates(mut self, update: bool) -> Self {
+        self.update_snapshots = update;
+        self
+    }
+
+    /// Run up to `jobs` tests at the same time
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Whether results are printed as text; turn it off when standard
+    /// output carries a report instead
+    pub fn with_text_output(mut self, text_output: bool) -> Self {
+        self.text_output = text_output;
+        self
+    }
+
+    pub fn text_output(&self) -> bool {
+        self.text_output
+    }
+
+    /// Record the code each test evaluates. Cached results are not used, as
+    /// only tests that run are recorded.
+    pub fn with_coverage(mut self) -> Self {
+        self.coverage = Some(Coverage::new());
+        self
+    }
+
+    /// What the tests covered, when coverage is enabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
     
     /// Get total number of tests
     pub fn total_tests(&self) -> usize {
@@ -151,7 +399,28 @@ impl TestSuite {
         }
 
         // Extract in-source tests
-        let in_source_tests = self.extract_in_source_tests(&expr);
+        let definitions = file_definitions(&expr);
+        let declarations = file_declarations(&expr);
+        let mut in_source_tests = self.extract_in_source_tests(&expr);
+        for test in &mut in_source_tests {
+            // Spans are offsets into the source
+            test.location = crate::cli::line_column(&source, test.location.0);
+            // A property's result also depends on the inputs it was run on,
+            // and a snapshot's on what it is compared with
+            let extra = match test.kind {
+                TestKind::Test => None,
+                TestKind::Property => Some(literal_hash(format!("{:?}", self.property_config))),
+                TestKind::Snapshot => Some(literal_hash(
+                    snapshot::stored(&snapshot::snapshot_path(path, &test.name)).unwrap_or_default(),
+                )),
+            };
+            test.cache_key = dependency_key(
+                &definitions,
+                &test.test_expr,
+                declarations.iter().cloned().chain(extra),
+            );
+            test.effects = test_effects(&definitions, &test.test_expr);
+        }
         let num_in_source_tests = in_source_tests.len();
         
         if self.verbose && num_in_source_tests > 0 {
@@ -167,12 +436,17 @@ impl TestSuite {
         if num_in_source_tests == 0 {
             // Check for traditional test files with # expect: comments
             let expected = self.extract_expected_from_source(&source);
-            if expected.is_some() {
+            if let Some(expectation) = &expected {
+                // The expectation is part of the test
+                let expectation = literal_hash(format!("{expectation:?}"));
+                let cache_key = result_key(&untyped_hash(&expr), [&expectation]);
                 let test_case = TestCase {
                     name: path.file_stem().unwrap().to_string_lossy().to_string(),
                     file: path.to_path_buf(),
+                    effects: test_effects(&definitions, &expr),
                     expr,
                     expected,
+                    cache_key,
                 };
                 self.tests.push(test_case);
                 num_traditional_tests = 1;
@@ -216,12 +490,15 @@ impl TestSuite {
                             });
                         }
                     }
-                    if name == "test" && args.len() == 2 {
+                    if is_test_function(name) && args.len() == 2 {
                         if let Expr::Literal(vibe_language::Literal::String(test_name), _) = &args[0] {
                             tests.push(InSourceTest {
                                 name: test_name.clone(),
                                 test_expr: args[1].clone(),
                                 location: (span.start, span.end),
+                                kind: TestKind::of(name),
+                                cache_key: String::new(),
+                                effects: BTreeSet::new(),
                             });
                         }
                     }
@@ -229,12 +506,15 @@ impl TestSuite {
                 // Check if it's a curried test call: (test "name") (fn -> ...)
                 else if let Expr::Apply { func: inner_func, args: inner_args, .. } = func.as_ref() {
                     if let Expr::Ident(Ident(name), _) = inner_func.as_ref() {
-                        if name == "test" && inner_args.len() == 1 && args.len() == 1 {
+                        if is_test_function(name) && inner_args.len() == 1 && args.len() == 1 {
                             if let Expr::Literal(vibe_language::Literal::String(test_name), _) = &inner_args[0] {
                                 tests.push(InSourceTest {
                                     name: test_name.clone(),
                                     test_expr: args[0].clone(),
                                     location: (span.start, span.end),
+                                    kind: TestKind::of(name),
+                                    cache_key: String::new(),
+                                    effects: BTreeSet::new(),
                                 });
                             }
                         }
@@ -301,45 +581,218 @@ impl TestSuite {
         let start = Instant::now();
         let mut summary = TestSummary::default();
 
-        println!("{}", "Running tests...".bright_blue().bold());
-        println!();
-
-        // Run traditional tests
-        let tests_clone = self.tests.clone();
-        for test in &tests_clone {
-            let result = self.run_test(test);
-            self.print_result(&result);
-            self.update_summary(&mut summary, &result);
+        if self.text_output {
+            println!("{}", "Running tests...".bright_blue().bold());
+            println!();
         }
+        self.emit(TestEvent::RunStarted {
+            total: self.total_tests(),
+        });
 
-        // Run in-source tests
-        for (file, test) in &self.in_source_tests.clone() {
-            let result = self.run_in_source_test(file, test);
-            self.print_result(&result);
-            self.update_summary(&mut summary, &result);
+        let mut pending: Vec<PendingTest> = self
+            .tests
+            .iter()
+            .cloned()
+            .map(PendingTest::Case)
+            .chain(
+                self.in_source_tests
+                    .iter()
+                    .cloned()
+                    .map(|(file, test)| PendingTest::InSource(file, test)),
+            )
+            .collect();
+        pending.sort_by(|a, b| a.position().cmp(&b.position()));
+
+        let mut runs = Vec::with_capacity(pending.len());
+        let mut finished = |_: &PendingTest, run: TestRun| {
+            self.finish(&mut summary, &run.result);
+            runs.push(run);
+        };
+        if self.jobs > 1 {
+            self.run_concurrently(&pending, &mut finished);
+        } else {
+            for test in &pending {
+                finished(test, self.execute(test));
+            }
         }
 
+        for (test, run) in pending.iter().zip(runs) {
+            let result = self.record(test, run);
+            summary.results.push(result);
+        }
+        // Results of tests that changed or were removed are never used again
+        self.cache.prune(pending.iter().map(PendingTest::cache_key));
         summary.duration = start.elapsed();
-        self.print_summary(&summary);
+        if self.text_output {
+            self.print_summary(&summary);
+        }
+        self.emit(TestEvent::RunFinished((&summary).into()));
+        if let Err(e) = self.cache.save() {
+            eprintln!("{}: failed to save test results: {}", "Warning".yellow(), e);
+        }
         summary
     }
 
-    /// Run a single test
+    /// Run `pending` on `self.jobs` threads, passing each run to `finished`
+    /// in order as soon as the runs before it are done
+    fn run_concurrently(
+        &self,
+        pending: &[PendingTest],
+        finished: &mut impl FnMut(&PendingTest, TestRun),
+    ) {
+        let pool = match rayon::ThreadPoolBuilder::new().num_threads(self.jobs).build() {
+            Ok(pool) => pool,
+            Err(e) => {
+                eprintln!("{}: running tests one at a time: {}", "Warning".yellow(), e);
+                for test in pending {
+                    finished(test, self.execute(test));
+                }
+                return;
+            }
+        };
+        let (serial, independent): (Vec<usize>, Vec<usize>) =
+            (0..pending.len()).partition(|&index| pending[index].serial());
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            let independent_sender = sender.clone();
+            scope.spawn(move || {
+                pool.install(|| {
+                    independent
+                        .par_iter()
+                        .for_each_with(independent_sender, |sender, &index| {
+                            let _ = sender.send((index, self.execute(&pending[index])));
+                        })
+                })
+            });
+            // Tests that may see each other's effects run one after another
+            scope.spawn(move || {
+                for index in serial {
+                    let _ = sender.send((index, self.execute(&pending[index])));
+                }
+            });
+
+            let mut done = BTreeMap::new();
+            let mut next = 0;
+            for (index, run) in receiver {
+                done.insert(index, run);
+                while let Some(run) = done.remove(&next) {
+                    finished(&pending[next], run);
+                    next += 1;
+                }
+            }
+        });
+    }
+
+    /// Run a test, or find its result in the cache
+    fn execute(&self, test: &PendingTest) -> TestRun {
+        self.emit(TestEvent::TestStarted {
+            name: test.name().to_string(),
+            file: test.file().to_path_buf(),
+        });
+        match test {
+            PendingTest::Case(test) => self.execute_test(test),
+            PendingTest::InSource(file, test) => {
+                let mut run = self.execute_in_source_test(file, test);
+                run.result.location = Some(test.location);
+                run
+            }
+        }
+    }
+
+    /// Keep what a run produced: its result in the cache, and its coverage
+    fn record(&mut self, test: &PendingTest, run: TestRun) -> TestResult {
+        if run.cacheable && !run.result.cached {
+            self.cache_result(test.cache_key(), &run.result);
+        }
+        if let (Some(coverage), Some(hits)) = (&mut self.coverage, &run.coverage) {
+            coverage.record(test.file(), hits);
+        }
+        run.result
+    }
+
+    /// Report a test that finished and add it to the summary
+    fn finish(&self, summary: &mut TestSummary, result: &TestResult) {
+        if self.text_output {
+            self.print_result(result);
+        }
+        self.emit(TestEvent::TestFinished(result.into()));
+        self.update_summary(summary, result);
+    }
+
+    fn emit(&self, event: TestEvent) {
+        let mut listeners = self
+            .listeners
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for listener in listeners.iter_mut() {
+            listener.on_event(&event);
+        }
+    }
+
+    /// The stored result of the test with `key`
+    fn cached_result(&self, key: &str, name: &str, file: &Path) -> Option<TestResult> {
+        if self.coverage.is_some() {
+            return None;
+        }
+        let cached = self.cache.get(key)?;
+        let outcome = if cached.passed {
+            TestOutcome::Passed {
+                value: cached.output.clone(),
+            }
+        } else {
+            TestOutcome::Failed {
+                error: cached.output.clone(),
+            }
+        };
+        Some(TestResult {
+            name: name.to_string(),
+            file: file.to_path_buf(),
+            outcome,
+            duration: cached.duration(),
+            cached: true,
+            location: None,
+        })
+    }
+
+    /// Store the result of a test that ran
+    fn cache_result(&mut self, key: &str, result: &TestResult) {
+        let (passed, output) = match &result.outcome {
+            TestOutcome::Passed { value } => (true, value.clone()),
+            TestOutcome::Failed { error } => (false, error.clone()),
+            TestOutcome::Skipped { .. } | TestOutcome::Timeout => return,
+        };
+        self.cache.insert(
+            key.to_string(),
+            CachedTestResult::new(result.name.clone(), passed, output, result.duration),
+        );
+    }
+
+    /// Have `interpreter` record what it evaluates, when coverage is enabled
+    fn instrument(&self, interpreter: &mut Interpreter) -> Option<Arc<Mutex<CoverageRecorder>>> {
+        self.coverage.as_ref()?;
+        let recorder = Arc::new(Mutex::new(CoverageRecorder::new()));
+        interpreter.set_observer(Some(recorder.clone() as SharedObserver));
+        Some(recorder)
+    }
+
+    /// Run a single test and record its result
+    #[cfg(test)]
     fn run_test(&mut self, test: &TestCase) -> TestResult {
+        let run = self.execute_test(test);
+        self.record(&PendingTest::Case(test.clone()), run)
+    }
+
+    /// Run a single test
+    fn execute_test(&self, test: &TestCase) -> TestRun {
         let start = Instant::now();
 
         // Check cache first
-        // TODO: Implement proper caching
-        // let test_hash = self.compute_test_hash(test);
-        // if let Some(cached_result) = self.cache.get_cached_result(&test.expr, &[]) {
-        //     return TestResult {
-        //         name: test.name.clone(),
-        //         file: test.file.clone(),
-        //         outcome: cached_result.result.clone(),
-        //         duration: Duration::from_millis(0),
-        //         cached: true,
-        //     };
-        // }
+        if let Some(result) = self.cached_result(&test.cache_key, &test.name, &test.file) {
+            return TestRun::uncacheable(result);
+        }
+
+        let mut hits = None;
 
         // Type check
         let mut type_checker = TypeChecker::new();
@@ -363,8 +816,11 @@ impl TestSuite {
             (_, Ok(_)) => {
                 // Run the test
                 let mut interpreter = Interpreter::new();
+                let recorder = self.instrument(&mut interpreter);
                 let env = vibe_runtime::Interpreter::create_initial_env();
-                match interpreter.eval(&test.expr, &env) {
+                let result = interpreter.eval(&test.expr, &env);
+                hits = recorded_hits(recorder);
+                match result {
                     Ok(value) => match &test.expected {
                         Some(ExpectedResult::Value(expected)) => {
                             if expected == &format!("{}", value) {
@@ -383,30 +839,48 @@ impl TestSuite {
                     Err(e) => match &test.expected {
                         Some(ExpectedResult::Error(_)) => TestOutcome::Passed { value: "Error as expected".to_string() },
                         _ => TestOutcome::Failed {
-                            error: format!("Runtime error: {}", e),
+                            error: format!(
+                                "Runtime error: {}",
+                                located_error(
+                                    &e,
+                                    &test.file,
+                                    &fs::read_to_string(&test.file).unwrap_or_default()
+                                )
+                            ),
                         },
                     },
                 }
             }
         };
 
-        // Cache the result
-        // TODO: Implement proper caching
-        // let _ = self.cache.cache_result(&test.expr, &[], outcome.clone());
-
-        TestResult {
+        let result = TestResult {
             name: test.name.clone(),
             file: test.file.clone(),
             outcome,
             duration: start.elapsed(),
             cached: false,
+            location: None,
+        };
+        TestRun {
+            result,
+            cacheable: true,
+            coverage: hits,
         }
     }
 
     /// Run an in-source test
-    fn run_in_source_test(&mut self, file: &Path, test: &InSourceTest) -> TestResult {
+    fn execute_in_source_test(&self, file: &Path, test: &InSourceTest) -> TestRun {
         let start = Instant::now();
 
+        // A snapshot being updated has to run to be written
+        let updating = self.update_snapshots && test.kind == TestKind::Snapshot;
+        if let Some(result) = self
+            .cached_result(&test.cache_key, &test.name, file)
+            .filter(|_| !updating)
+        {
+            return TestRun::uncacheable(result);
+        }
+
         if self.verbose {
             println!("Running in-source test: {}", test.name);
         }
@@ -415,7 +889,7 @@ impl TestSuite {
         let source = match fs::read_to_string(file) {
             Ok(s) => s,
             Err(e) => {
-                return TestResult {
+                return TestRun::uncacheable(TestResult {
                     name: test.name.clone(),
                     file: file.to_path_buf(),
                     outcome: TestOutcome::Failed {
@@ -423,14 +897,15 @@ impl TestSuite {
                     },
                     duration: start.elapsed(),
                     cached: false,
-                };
+                    location: None,
+                });
             }
         };
 
         let file_expr = match parse(&source) {
             Ok(expr) => expr,
             Err(e) => {
-                return TestResult {
+                return TestRun::uncacheable(TestResult {
                     name: test.name.clone(),
                     file: file.to_path_buf(),
                     outcome: TestOutcome::Failed {
@@ -438,7 +913,8 @@ impl TestSuite {
                     },
                     duration: start.elapsed(),
                     cached: false,
-                };
+                    location: None,
+                });
             }
         };
 
@@ -448,49 +924,199 @@ impl TestSuite {
 
         // Build environment with definitions from the file
         let mut interpreter = Interpreter::new();
+        let recorder = self.instrument(&mut interpreter);
         let runtime_env = vibe_runtime::Interpreter::create_initial_env();
         
         // Extract and evaluate all definitions (let bindings) from the file
         let updated_env = match self.build_test_environment(&file_expr, &mut interpreter, runtime_env) {
             Ok(env) => env,
             Err(e) => {
-                return TestResult {
+                return TestRun::uncacheable(TestResult {
                     name: test.name.clone(),
                     file: file.to_path_buf(),
                     outcome: TestOutcome::Failed {
-                        error: format!("Failed to build test environment: {}", e),
+                        error: format!(
+                            "Failed to build test environment: {}",
+                            located_error(&e, file, &source)
+                        ),
                     },
                     duration: start.elapsed(),
                     cached: false,
-                };
+                    location: None,
+                });
             }
         };
 
         // Now run the test expression in the environment with all definitions
         let test_expr = test.test_expr.clone();
 
-        // The test expression should be a function
-        let outcome = match interpreter.eval(&test_expr, &updated_env) {
-            Err(e) => TestOutcome::Failed {
-                error: format!("Failed to evaluate test function: {}", e),
-            },
-            Ok(test_fn) => {
-                // Execute the test function
-                match self.execute_test_function(test_fn, &mut interpreter, &updated_env) {
-                    Ok(_) => TestOutcome::Passed { value: "Test passed".to_string() },
-                    Err(e) => TestOutcome::Failed {
-                        error: e.to_string(),
-                    },
+        let mut cacheable = true;
+        let outcome = if test.kind == TestKind::Snapshot {
+            let outcome;
+            (outcome, cacheable) =
+                self.run_snapshot(file, &file_expr, test, &mut interpreter, &updated_env, recorder.clone());
+            outcome
+        } else {
+            // The test expression should be a function
+            match interpreter.eval(&test_expr, &updated_env) {
+                Err(e) => TestOutcome::Failed {
+                    error: format!(
+                        "Failed to evaluate test function: {}",
+                        located_error(&e, file, &source)
+                    ),
+                },
+                Ok(property) if test.kind == TestKind::Property => self.run_property(
+                    file,
+                    &source,
+                    &file_expr,
+                    &test_expr,
+                    property,
+                    &mut interpreter,
+                ),
+                Ok(test_fn) => {
+                    // Execute the test function
+                    match self.execute_test_function(test_fn, &mut interpreter, &updated_env) {
+                        Ok(_) => TestOutcome::Passed { value: "Test passed".to_string() },
+                        Err(e) => TestOutcome::Failed {
+                            error: located_error(&e, file, &source),
+                        },
+                    }
                 }
             }
         };
 
-        TestResult {
+        // Failures to set up the file are not cached, since they may come
+        // from definitions the test does not use
+        let result = TestResult {
             name: test.name.clone(),
             file: file.to_path_buf(),
             outcome,
             duration: start.elapsed(),
             cached: false,
+            location: None,
+        };
+        TestRun {
+            result,
+            cacheable,
+            coverage: recorded_hits(recorder),
+        }
+    }
+
+    /// Compare what a snapshot test's expression is with its stored
+    /// snapshot, writing the snapshot when it is new or being updated.
+    /// Also returns whether the outcome may be cached: not once the snapshot
+    /// was written, or could not be.
+    fn run_snapshot(
+        &self,
+        file: &Path,
+        file_expr: &Expr,
+        test: &InSourceTest,
+        interpreter: &mut Interpreter,
+        env: &vibe_language::Environment,
+        recorder: Option<Arc<Mutex<CoverageRecorder>>>,
+    ) -> (TestOutcome, bool) {
+        let observer = |recorder: Arc<Mutex<CoverageRecorder>>| -> SharedObserver { recorder };
+        let capture = Arc::new(Mutex::new(OutputCapture::new(recorder.clone().map(observer))));
+        interpreter.set_observer(Some(capture.clone() as SharedObserver));
+        let value = interpreter.eval(&test.test_expr, env);
+        interpreter.set_observer(recorder.map(observer));
+        let value = match value {
+            Ok(value) => value,
+            Err(e) => {
+                let source = fs::read_to_string(file).unwrap_or_default();
+                let error = format!(
+                    "Failed to evaluate snapshot: {}",
+                    located_error(&e, file, &source)
+                );
+                return (TestOutcome::Failed { error }, true);
+            }
+        };
+        let output = std::mem::take(
+            &mut capture
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .lines,
+        );
+
+        // An expression that does not type check is snapshotted without a type
+        let ty = expression_type(file_expr, &test.test_expr).ok();
+        let actual = Snapshot::new(ty.as_ref(), &value, output);
+        let path = snapshot::snapshot_path(file, &test.name);
+        match snapshot::check(&path, &actual, self.update_snapshots) {
+            Ok(SnapshotOutcome::Matched) => (
+                TestOutcome::Passed {
+                    value: "Snapshot matched".to_string(),
+                },
+                true,
+            ),
+            Ok(SnapshotOutcome::Written(path)) => (
+                TestOutcome::Passed {
+                    value: format!("Snapshot written to {}", path.display()),
+                },
+                false,
+            ),
+            Ok(SnapshotOutcome::Changed(diff)) => {
+                let error = format!(
+                    "Snapshot {} changed:\n{}\n  Run `vibe test --update-snapshots` to accept the change",
+                    path.display(),
+                    diff.lines()
+                        .map(|line| format!("  {line}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                );
+                (TestOutcome::Failed { error }, true)
+            }
+            Err(e) => (TestOutcome::Failed { error: e.to_string() }, false),
+        }
+    }
+
+    /// Run a property on generated inputs, reporting the shrunk
+    /// counterexample when it fails
+    fn run_property(
+        &self,
+        file: &Path,
+        source: &str,
+        file_expr: &Expr,
+        property_expr: &Expr,
+        property: Value,
+        interpreter: &mut Interpreter,
+    ) -> TestOutcome {
+        let params = match &property {
+            Value::Closure { params, .. } | Value::RecClosure { params, .. } => params.clone(),
+            _ => {
+                return TestOutcome::Failed {
+                    error: "A property must be a function of its inputs".to_string(),
+                }
+            }
+        };
+        let (types, definitions) = match property_inputs(file_expr, property_expr, params.len()) {
+            Ok(inputs) => inputs,
+            Err(error) => return TestOutcome::Failed { error },
+        };
+
+        let config = &self.property_config;
+        let outcome = Generators::new(definitions).check(&types, config, |values| {
+            interpreter
+                .apply(property.clone(), values.to_vec())
+                .map_err(|e| located_error(&e, file, source))
+        });
+        match outcome {
+            Ok(PropertyOutcome::Passed { cases }) => TestOutcome::Passed {
+                value: format!("{cases} cases passed (seed {})", config.seed),
+            },
+            Ok(PropertyOutcome::Failed(counterexample)) => {
+                let mut error = format!(
+                    "Falsified after {} of {} cases (seed {}), shrunk {} times",
+                    counterexample.case, config.cases, config.seed, counterexample.shrinks
+                );
+                error.push_str("\n  Counterexample:");
+                for (param, value) in params.iter().zip(&counterexample.values) {
+                    error.push_str(&format!("\n    {} = {}", param.0, crate::cli::format_value(value)));
+                }
+                error.push_str(&format!("\n  {}", counterexample.error));
+                TestOutcome::Failed { error }
+            }
+            Err(error) => TestOutcome::Failed { error },
         }
     }
 
@@ -535,16 +1161,6 @@ impl TestSuite {
         }
     }
 
-    /// Compute hash for a test (for caching)
-    #[allow(dead_code)]
-    fn compute_test_hash(&self, test: &TestCase) -> Hash {
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(format!("{:?}", test.expr).as_bytes());
-        let result = hasher.finalize();
-        Hash::new(&result)
-    }
-
     /// Build test environment by evaluating definitions from file
     fn build_test_environment(
         &self,
@@ -658,10 +1274,11 @@ impl TestSuite {
         println!("{}", "Test Summary".bright_blue().bold());
         println!("{}", "============".bright_blue());
         println!(
-            "Total: {} | Passed: {} | Failed: {} | Cached: {}",
+            "Total: {} | Passed: {} | Failed: {} | Run: {} | Cached: {}",
             summary.total,
             summary.passed.to_string().green(),
             summary.failed.to_string().red(),
+            summary.total - summary.cached,
             summary.cached.to_string().cyan()
         );
         println!(
@@ -679,6 +1296,383 @@ impl TestSuite {
     }
 }
 
+/// The test results cached in `dir`. With `force` the cached results are
+/// discarded, so that every test runs again.
+pub fn load_test_cache(dir: &Path, force: bool) -> TestResultCache {
+    let mut cache = match TestResultCache::load(dir) {
+        Ok(cache) => cache,
+        Err(e) => {
+            eprintln!("{}: ignoring test cache: {}", "Warning".yellow(), e);
+            TestResultCache::in_memory()
+        }
+    };
+    if force {
+        cache.clear();
+    }
+    cache
+}
+
+/// The top-level definitions of a test file, hashed like codebase
+/// definitions. A later definition of a name replaces the earlier one.
+fn file_definitions(expr: &Expr) -> NamespaceStore {
+    let mut store = NamespaceStore::new();
+    for expr in top_level(expr) {
+        let (name, content) = match expr {
+            Expr::Let { name, value, .. } | Expr::LetRec { name, value, .. } => {
+                (name, DefinitionContent::Value((**value).clone()))
+            }
+            Expr::Rec { name, .. } => (name, DefinitionContent::Value(expr.clone())),
+            _ => continue,
+        };
+        let path = DefinitionPath::new(NamespacePath::root(), name.0.clone());
+        // Test files are not type checked as a whole, so definitions are
+        // stored untyped
+        let type_signature = vibe_language::Type::Unit;
+        let command = if store.get_definition_by_path(&path).is_some() {
+            NamespaceCommand::UpdateDefinition {
+                path,
+                content,
+                type_signature,
+            }
+        } else {
+            NamespaceCommand::AddDefinition {
+                path,
+                content,
+                type_signature,
+                metadata: DefinitionMetadata::default(),
+            }
+        };
+        // Dependencies are resolved against the store, so this cannot fail
+        let _ = store.execute_command(command);
+    }
+    store
+}
+
+/// Hashes of the type definitions and imports of a test file. Uses of them
+/// are not tracked, so every test of the file depends on all of them.
+fn file_declarations(expr: &Expr) -> Vec<DefinitionHash> {
+    top_level(expr)
+        .iter()
+        .filter(|expr| {
+            matches!(
+                expr,
+                Expr::TypeDef { .. } | Expr::Import { .. } | Expr::Use { .. }
+            )
+        })
+        .map(untyped_hash)
+        .collect()
+}
+
+fn top_level(expr: &Expr) -> &[Expr] {
+    match expr {
+        Expr::Block { exprs, .. } => exprs,
+        expr => std::slice::from_ref(expr),
+    }
+}
+
+/// Cache key of a test expression using the file's `definitions`, and
+/// whatever else the result depends on
+fn dependency_key(
+    definitions: &NamespaceStore,
+    test_expr: &Expr,
+    extra: impl IntoIterator<Item = DefinitionHash>,
+) -> String {
+    let mut dependencies = test_dependencies(definitions, test_expr);
+    dependencies.extend(extra);
+    result_key(&untyped_hash(test_expr), &dependencies)
+}
+
+/// The definitions a test uses, directly or through other definitions
+fn test_dependencies(
+    definitions: &NamespaceStore,
+    test_expr: &Expr,
+) -> std::collections::HashSet<DefinitionHash> {
+    let content = DefinitionContent::Value(test_expr.clone());
+    let mut dependencies = definitions
+        .extract_dependencies(&content)
+        .unwrap_or_default();
+    for dependency in dependencies.clone() {
+        dependencies.extend(definitions.dependency_closure(&dependency));
+    }
+    dependencies
+}
+
+fn literal_hash(text: String) -> DefinitionHash {
+    DefinitionHash::compute(
+        &DefinitionContent::Value(Expr::Literal(
+            vibe_language::Literal::String(text),
+            vibe_language::Span::new(0, 0),
+        )),
+        &vibe_language::Type::String,
+    )
+}
+
+/// Whether `name` registers an in-source test
+fn is_test_function(name: &str) -> bool {
+    matches!(name, "test" | "property" | "forAll" | "snapshot")
+}
+
+/// The types of the first `arity` parameters of a property, inferred with
+/// the file's definitions in scope, and the data types they may use
+fn property_inputs(
+    file_expr: &Expr,
+    property_expr: &Expr,
+    arity: usize,
+) -> std::result::Result<(Vec<vibe_language::Type>, Vec<vibe_language::TypeDefinition>), String> {
+    let (mut checker, mut env, definitions, failed) = file_type_checker(file_expr);
+    if let Some((name, error)) = failed_dependency(file_expr, property_expr, &failed) {
+        return Err(format!("Type error in definition `{name}`: {error}"));
+    }
+    let mut ty = checker
+        .check(property_expr, &mut env)
+        .map_err(|e| format!("Type error in property: {e}"))?;
+    let mut types = Vec::new();
+    for _ in 0..arity {
+        match ty {
+            vibe_language::Type::Function(from, to)
+            | vibe_language::Type::FunctionWithEffect { from, to, .. } => {
+                types.push(*from);
+                ty = *to;
+            }
+            _ => return Err("A property must be a function of its inputs".to_string()),
+        }
+    }
+    Ok((types, definitions))
+}
+
+/// The type of `expr` in the scope of the definitions of a file
+fn expression_type(file_expr: &Expr, expr: &Expr) -> std::result::Result<vibe_language::Type, String> {
+    let (mut checker, mut env, _, _) = file_type_checker(file_expr);
+    checker.check(expr, &mut env).map_err(|e| e.to_string())
+}
+
+/// A type checker that has checked the definitions of a file, with the
+/// types the file and the builtins define, and the definitions that failed
+/// to check by name with their first error
+fn file_type_checker(
+    file_expr: &Expr,
+) -> (
+    TypeChecker,
+    TypeEnv,
+    Vec<vibe_language::TypeDefinition>,
+    Vec<(String, String)>,
+) {
+    let mut checker = TypeChecker::new();
+    let mut env = TypeEnv::new();
+    let mut definitions = vibe_language::builtin_modules::builtin_type_definitions();
+    let mut failed = Vec::new();
+    for expr in top_level(file_expr) {
+        let name = match expr {
+            Expr::TypeDef { definition, .. } => {
+                definitions.push(definition.clone());
+                None
+            }
+            Expr::Let { name, .. } | Expr::LetRec { name, .. } | Expr::Rec { name, .. } => {
+                Some(name)
+            }
+            _ => continue,
+        };
+        // A definition that does not check stays in scope with an error type
+        let (_, diagnostics) = checker.check_all(expr, &mut env);
+        if let (Some(name), Some(diagnostic)) = (name, diagnostics.first()) {
+            failed.push((name.0.clone(), diagnostic.message.clone()));
+        }
+    }
+    (checker, env, definitions, failed)
+}
+
+/// The first of the `failed` definitions that `expr` uses, directly or
+/// through other definitions of the file
+fn failed_dependency<'a>(
+    file_expr: &Expr,
+    expr: &Expr,
+    failed: &'a [(String, String)],
+) -> Option<&'a (String, String)> {
+    if failed.is_empty() {
+        return None;
+    }
+    let definitions = file_definitions(file_expr);
+    let used = test_dependencies(&definitions, expr);
+    failed.iter().find(|(name, _)| {
+        let path = DefinitionPath::new(NamespacePath::root(), name.clone());
+        definitions
+            .get_definition_by_path(&path)
+            .is_some_and(|definition| used.contains(&definition.hash))
+    })
+}
+
+/// `error` with its span, an offset into `source`, given as
+/// `file:line:column`
+fn located_error(error: &XsError, file: &Path, source: &str) -> String {
+    let (kind, span, message) = match error {
+        XsError::TypeError(span, message) => ("Type error", span, message),
+        XsError::RuntimeError(span, message) => ("Runtime error", span, message),
+        error => return error.to_string(),
+    };
+    // Errors raised outside the source have an empty span at the start
+    if span.end == 0 || span.start > source.len() {
+        return format!("{kind}: {message}");
+    }
+    let (line, column) = crate::cli::line_column(source, span.start);
+    format!("{kind} at {}:{line}:{column}: {message}", file.display())
+}
+
+/// What an instrumented interpreter recorded
+fn recorded_hits(recorder: Option<Arc<Mutex<CoverageRecorder>>>) -> Option<CoverageRecorder> {
+    let recorder = recorder?;
+    let mut hits = recorder.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    Some(std::mem::take(&mut *hits))
+}
+
+/// Effects a test may perform: those it performs, and those in the types
+/// of the builtins it uses, directly or through the definitions it depends on
+fn test_effects(definitions: &NamespaceStore, test_expr: &Expr) -> BTreeSet<String> {
+    let env = TypeEnv::new();
+    let mut effects = BTreeSet::new();
+    let dependencies = test_dependencies(definitions, test_expr);
+    let exprs = std::iter::once(test_expr).chain(dependencies.iter().filter_map(|hash| {
+        match &definitions.get_definition(hash)?.content {
+            DefinitionContent::Value(expr) => Some(expr),
+            _ => None,
+        }
+    }));
+    for expr in exprs {
+        for_each_expr(expr, &mut |expr| {
+            let scheme = match expr {
+                Expr::Ident(Ident(name), _) => env.lookup(name),
+                Expr::QualifiedIdent {
+                    module_name, name, ..
+                } => env.lookup_module_function(&module_name.0, &name.0),
+                // `Module.name` parses as a field of the module
+                Expr::RecordAccess { record, field, .. } => match record.as_ref() {
+                    Expr::Ident(module, _) => env.lookup_module_function(&module.0, &field.0),
+                    _ => return,
+                },
+                Expr::Perform { effect, .. } => {
+                    effects.insert(effect.0.clone());
+                    return;
+                }
+                _ => return,
+            };
+            if let Some(scheme) = scheme {
+                type_effects(&scheme.typ, &mut effects);
+            }
+        });
+    }
+    effects
+}
+
+/// Add the effects of the concrete effect rows in `typ` to `effects`
+fn type_effects(typ: &vibe_language::Type, effects: &mut BTreeSet<String>) {
+    use vibe_language::{EffectRow, Type};
+
+    match typ {
+        Type::Function(from, to) => {
+            type_effects(from, effects);
+            type_effects(to, effects);
+        }
+        Type::FunctionWithEffect {
+            from,
+            to,
+            effects: row,
+        } => {
+            if let EffectRow::Concrete(set) = row {
+                effects.extend(set.iter().map(|effect| effect.to_string()));
+            }
+            type_effects(from, effects);
+            type_effects(to, effects);
+        }
+        _ => {}
+    }
+}
+
+/// Call `visit` on `expr` and every expression in it
+fn for_each_expr(expr: &Expr, visit: &mut impl FnMut(&Expr)) {
+    visit(expr);
+    let mut children: Vec<&Expr> = Vec::new();
+    match expr {
+        Expr::List(items, _) => children.extend(items),
+        Expr::Let { value, .. } | Expr::LetRec { value, .. } => children.push(value),
+        Expr::LetIn { value, body, .. } | Expr::LetRecIn { value, body, .. } => {
+            children.push(value);
+            children.push(body);
+        }
+        Expr::Rec { body, .. } | Expr::Lambda { body, .. } | Expr::FunctionDef { body, .. } => {
+            children.push(body)
+        }
+        Expr::If {
+            cond,
+            then_expr,
+            else_expr,
+            ..
+        } => children.extend([cond.as_ref(), then_expr, else_expr]),
+        Expr::Apply { func, args, .. } => {
+            children.push(func);
+            children.extend(args);
+        }
+        Expr::Match { expr, cases, .. } => {
+            children.push(expr);
+            children.extend(cases.iter().map(|(_, case)| case));
+        }
+        Expr::Constructor { args, .. } | Expr::Perform { args, .. } => children.extend(args),
+        Expr::Module { body, .. } => children.extend(body),
+        Expr::Handler { cases, body, .. } => {
+            children.extend(cases.iter().map(|(_, _, _, case)| case));
+            children.push(body);
+        }
+        Expr::HandleExpr {
+            expr,
+            handlers,
+            return_handler,
+            ..
+        } => {
+            children.push(expr);
+            children.extend(handlers.iter().map(|handler| &handler.body));
+            if let Some((_, body)) = return_handler {
+                children.push(body);
+            }
+        }
+        Expr::WithHandler { handler, body, .. } => {
+            children.push(handler);
+            children.push(body);
+        }
+        Expr::Pipeline { expr, func, .. } => {
+            children.push(expr);
+            children.push(func);
+        }
+        Expr::Block { exprs, .. } => children.extend(exprs),
+        Expr::Do { statements, .. } => {
+            children.extend(statements.iter().map(|statement| match statement {
+                vibe_language::DoStatement::Bind { expr, .. }
+                | vibe_language::DoStatement::Expression(expr) => expr,
+            }))
+        }
+        Expr::RecordLiteral { fields, .. } => children.extend(fields.iter().map(|(_, e)| e)),
+        Expr::RecordAccess { record, .. } => children.push(record),
+        Expr::RecordUpdate { record, updates, .. } => {
+            children.push(record);
+            children.extend(updates.iter().map(|(_, e)| e));
+        }
+        Expr::RecordExtend { record, fields, .. } => {
+            children.push(record);
+            children.extend(fields.iter().map(|(_, e)| e));
+        }
+        Expr::RecordRestrict { record, .. } => children.push(record),
+        Expr::Mut { value, .. } | Expr::Assign { value, .. } => children.push(value),
+        _ => {}
+    }
+    for child in children {
+        for_each_expr(child, visit);
+    }
+}
+
+fn untyped_hash(expr: &Expr) -> DefinitionHash {
+    DefinitionHash::compute(
+        &DefinitionContent::Value(expr.clone()),
+        &vibe_language::Type::Unit,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -727,4 +1721,301 @@ let x = 42
         assert_eq!(tests.len(), 1);
         assert_eq!(tests[0].name, "example");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_keys_follow_the_definitions_a_test_uses() {
+        let file = |base: i64, unrelated: i64| {
+            parse(&format!(
+                "let base = {base}\nlet answer = base\nlet unrelated = {unrelated}"
+            ))
+            .unwrap()
+        };
+        let test_expr = parse("answer").unwrap();
+        let key = |file: &Expr| dependency_key(&file_definitions(file), &test_expr, None);
+
+        // `answer` uses `base`, so the test depends on both
+        assert_eq!(key(&file(1, 2)), key(&file(1, 3)));
+        assert_ne!(key(&file(1, 2)), key(&file(4, 2)));
+    }
+
+    #[test]
+    fn test_keys_follow_type_definitions_and_imports() {
+        let test_expr = parse("Red").unwrap();
+        // One statement per line
+        let key = |source: &str| {
+            let file = Expr::Block {
+                exprs: source.lines().map(|line| parse(line).unwrap()).collect(),
+                span: vibe_language::Span::new(0, source.len()),
+            };
+            dependency_key(
+                &file_definitions(&file),
+                &test_expr,
+                file_declarations(&file),
+            )
+        };
+
+        let colors = key("type Color = | Red | Green\nlet answer = 1");
+        assert_ne!(colors, key("type Color = | Red | Blue\nlet answer = 1"));
+        assert_ne!(
+            colors,
+            key("import Colors\ntype Color = | Red | Green\nlet answer = 1")
+        );
+        assert_eq!(colors, key("type Color = | Red | Green\nlet answer = 2"));
+    }
+
+    #[test]
+    fn test_errors_are_located_in_their_file() {
+        let file = Path::new("math.vibe");
+        let source = "let a = 1\nlet b = oops";
+        let error = XsError::RuntimeError(
+            vibe_language::Span::new(18, 22),
+            "Undefined variable: oops".to_string(),
+        );
+        assert_eq!(
+            located_error(&error, file, source),
+            "Runtime error at math.vibe:2:9: Undefined variable: oops"
+        );
+
+        let builtin = XsError::RuntimeError(vibe_language::Span::new(0, 0), "boom".to_string());
+        assert_eq!(located_error(&builtin, file, source), "Runtime error: boom");
+    }
+
+    #[test]
+    fn test_results_are_reused_until_the_test_changes() {
+        let mut suite = TestSuite::new(false);
+        let test = |n: i64| {
+            let expr = parse(&n.to_string()).unwrap();
+            TestCase {
+                name: "five".to_string(),
+                file: PathBuf::from("five.vibe"),
+                cache_key: result_key(&untyped_hash(&expr), []),
+                effects: BTreeSet::new(),
+                expr,
+                expected: Some(ExpectedResult::Value("5".to_string())),
+            }
+        };
+
+        let first = suite.run_test(&test(5));
+        assert!(!first.cached);
+        let second = suite.run_test(&test(5));
+        assert!(second.cached);
+        assert!(matches!(second.outcome, TestOutcome::Passed { .. }));
+        assert!(!suite.run_test(&test(6)).cached);
+    }
+
+    #[test]
+    fn test_coverage_records_tests_that_would_be_cached() {
+        let expr = parse("5").unwrap();
+        let test = TestCase {
+            name: "five".to_string(),
+            file: PathBuf::from("five.vibe"),
+            cache_key: result_key(&untyped_hash(&expr), []),
+            effects: BTreeSet::new(),
+            expr,
+            expected: Some(ExpectedResult::Value("5".to_string())),
+        };
+
+        let mut suite = TestSuite::new(false).with_coverage();
+        suite.run_test(&test);
+        assert!(!suite.run_test(&test).cached);
+        let hits = suite.coverage().unwrap().hits(&test.file).unwrap();
+        assert_eq!(hits.hits(test.expr.span()), 2);
+    }
+
+    /// Records the name of each test as it starts and finishes
+    struct EventLog(Arc<Mutex<Vec<String>>>);
+
+    impl TestListener for EventLog {
+        fn on_event(&mut self, event: &TestEvent) {
+            let entry = match event {
+                TestEvent::TestStarted { name, .. } => format!("start {name}"),
+                TestEvent::TestFinished(record) => format!("finish {}", record.name),
+                _ => return,
+            };
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_runs_report_in_file_order() {
+        let test = |n: i64, source: String| {
+            let expr = parse(&source).unwrap();
+            TestCase {
+                name: format!("test{n}"),
+                file: PathBuf::from(format!("{n:02}.vibe")),
+                cache_key: result_key(&untyped_hash(&expr), []),
+                effects: test_effects(&NamespaceStore::new(), &expr),
+                expected: Some(ExpectedResult::Value(n.to_string())),
+                expr,
+            }
+        };
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut suite = TestSuite::new(false)
+            .with_text_output(false)
+            .with_jobs(4)
+            .with_listener(Box::new(EventLog(events.clone())));
+        for n in (0..12).rev() {
+            let source = if n % 3 == 0 {
+                format!("print {n}")
+            } else {
+                n.to_string()
+            };
+            suite.tests.push(test(n, source));
+        }
+        // Tests are added last to first; `test9` prints
+        assert_eq!(suite.tests[2].effects, BTreeSet::from(["IO".to_string()]));
+        assert!(suite.tests[0].effects.is_empty());
+
+        let summary = suite.run_all();
+        assert_eq!(summary.passed, 12, "{:?}", summary.results);
+        let names: Vec<_> = summary.results.iter().map(|r| r.name.clone()).collect();
+        let expected: Vec<_> = (0..12).map(|n| format!("test{n}")).collect();
+        assert_eq!(names, expected);
+
+        // Each test is reported as started before it runs, so before it finishes
+        let events = events.lock().unwrap();
+        for name in &expected {
+            let position = |entry: String| events.iter().position(|e| *e == entry).unwrap();
+            assert!(position(format!("start {name}")) < position(format!("finish {name}")));
+        }
+    }
+
+    #[test]
+    fn test_effects_come_from_builtin_types() {
+        use vibe_language::{Literal, Span};
+
+        let expr_effects = |expr: &Expr| Vec::from_iter(test_effects(&NamespaceStore::new(), expr));
+        let span = || Span::new(0, 0);
+        let effects = |source: &str| expr_effects(&parse(source).unwrap());
+        // `Module.name args`, with the function as field access on the module name
+        let qualified = |module: &str, name: &str, args: Vec<Literal>| Expr::Apply {
+            func: Box::new(Expr::RecordAccess {
+                record: Box::new(Expr::Ident(Ident(module.to_string()), span())),
+                field: Ident(name.to_string()),
+                span: span(),
+            }),
+            args: Vec::from_iter(args.into_iter().map(|arg| Expr::Literal(arg, span()))),
+            span: span(),
+        };
+        assert_eq!(effects(r#"inspect 1 "one""#), ["IO"]);
+        let path = Literal::String("a.json".to_string());
+        let read = qualified("Json", "readFile", vec![path]);
+        assert_eq!(expr_effects(&read), ["FileSystem"]);
+        assert_eq!(effects("{ mut s = 0; s = print s }"), ["IO"]);
+        assert_eq!(effects("perform State.get"), ["State"]);
+        let add = qualified("Int", "add", vec![Literal::Int(1), Literal::Int(2)]);
+        assert!(expr_effects(&add).is_empty());
+    }
+
+    #[test]
+    fn test_snapshots_record_type_value_and_output() {
+        let file = |items: [i64; 3]| parse(&format!("let xs = {items:?}")).unwrap();
+        let test = InSourceTest {
+            name: "printed xs".to_string(),
+            test_expr: parse("print xs").unwrap(),
+            location: (1, 1),
+            kind: TestKind::Snapshot,
+            cache_key: String::new(),
+            effects: BTreeSet::new(),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lists.vibe");
+        let run = |suite: &TestSuite, items| {
+            let file = file(items);
+            let mut interpreter = Interpreter::new();
+            let env = suite
+                .build_test_environment(&file, &mut interpreter, Interpreter::create_initial_env())
+                .unwrap();
+            suite.run_snapshot(&path, &file, &test, &mut interpreter, &env, None)
+        };
+
+        let suite = TestSuite::new(false);
+        let (outcome, cacheable) = run(&suite, [3, 1, 2]);
+        assert!(matches!(outcome, TestOutcome::Passed { .. }) && !cacheable);
+        let stored = snapshot::stored(&snapshot::snapshot_path(&path, "printed xs")).unwrap();
+        assert!(stored.starts_with("-- type --\n(List Int)\n"), "{stored}");
+        assert!(stored.contains("-- value --\n(list 3 1 2)\n"), "{stored}");
+        assert!(stored.contains("-- output --\n"), "{stored}");
+        assert!(matches!(run(&suite, [3, 1, 2]), (TestOutcome::Passed { .. }, true)));
+
+        let (TestOutcome::Failed { error }, _) = run(&suite, [3, 1, 4]) else {
+            panic!("a changed value should fail");
+        };
+        assert!(error.contains("- (list 3 1 2)\n  + (list 3 1 4)"), "{error}");
+        assert!(error.contains("--update-snapshots"), "{error}");
+
+        let updating = TestSuite::new(false).with_snapshot_updates(true);
+        assert!(matches!(run(&updating, [3, 1, 4]), (TestOutcome::Passed { .. }, false)));
+        assert!(matches!(run(&suite, [3, 1, 4]), (TestOutcome::Passed { .. }, true)));
+    }
+
+    #[test]
+    fn test_properties_report_a_shrunk_counterexample() {
+        use vibe_language::{Literal, Span, Type};
+
+        let var = |name: &str| Expr::Ident(Ident(name.to_string()), Span::new(0, 0));
+        let define = |name: &str, type_ann: Option<Type>, value: Expr| Expr::Let {
+            name: Ident(name.to_string()),
+            type_ann,
+            value: Box::new(value),
+            span: Span::new(0, 0),
+        };
+        let property = |param: Option<Type>, body: Expr| Expr::Lambda {
+            params: vec![(Ident("n".to_string()), param)],
+            body: Box::new(body),
+            span: Span::new(0, 0),
+        };
+        let suite = TestSuite::new(false);
+        let run = |file: &Expr, expr: &Expr| {
+            let mut interpreter = Interpreter::new();
+            let env = suite
+                .build_test_environment(file, &mut interpreter, Interpreter::create_initial_env())
+                .unwrap();
+            let value = interpreter.eval(expr, &env).unwrap();
+            suite.run_property(
+                Path::new("test.vibe"),
+                "",
+                file,
+                expr,
+                value,
+                &mut interpreter,
+            )
+        };
+
+        // let limit = 10
+        let file = define(
+            "limit",
+            None,
+            Expr::Literal(Literal::Int(10), Span::new(0, 0)),
+        );
+        let below_limit = Expr::Apply {
+            func: Box::new(var("<")),
+            args: vec![var("n"), var("limit")],
+            span: Span::new(0, 0),
+        };
+        // The parameter type is inferred when it is not annotated
+        for param in [Some(Type::Int), None] {
+            let TestOutcome::Failed { error } = run(&file, &property(param, below_limit.clone()))
+            else {
+                panic!("the property should fail");
+            };
+            assert!(error.contains("n = 10"), "{error}");
+            assert!(error.contains("seed"), "{error}");
+        }
+
+        // let limit : Int = "ten"
+        let file = define(
+            "limit",
+            Some(Type::Int),
+            Expr::Literal(Literal::String("ten".to_string()), Span::new(0, 0)),
+        );
+        let TestOutcome::Failed { error } = run(&file, &property(None, below_limit)) else {
+            panic!("the property should fail");
+        };
+        assert!(
+            error.starts_with("Type error in definition `limit`:"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! Machine-readable test reports
+//!
+//! A finished run can be written as JUnit XML, TAP or JSON for CI, and a run
+//! in progress can be followed through `TestEvent`s, which `JsonEventWriter`
+//! streams as one JSON object per line.
+
+use super::{TestError, TestOutcome, TestResult, TestSummary};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How `vibe test` reports results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Coloured text on the terminal
+    Text,
+    Junit,
+    Tap,
+    Json,
+    /// `TestEvent`s as JSON lines, written while the tests run
+    Events,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "text" => Ok(ReportFormat::Text),
+            "junit" => Ok(ReportFormat::Junit),
+            "tap" => Ok(ReportFormat::Tap),
+            "json" => Ok(ReportFormat::Json),
+            "events" => Ok(ReportFormat::Events),
+            _ => anyhow::bail!(
+                "Unsupported test report format `{format}` (supported: text, junit, tap, json, events)"
+            ),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// The report of a finished run, for formats written once all tests ran
+    pub fn render(self, summary: &TestSummary) -> Option<String> {
+        match self {
+            ReportFormat::Junit => Some(junit(summary)),
+            ReportFormat::Tap => Some(tap(summary)),
+            ReportFormat::Json => Some(json(summary)),
+            ReportFormat::Text | ReportFormat::Events => None,
+        }
+    }
+}
+
+/// One test's result as it appears in reports
+#[derive(Debug, Clone, Serialize)]
+pub struct TestRecord {
+    pub name: String,
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// `passed`, `failed`, `skipped` or `timeout`
+    pub status: &'static str,
+    pub duration_ms: f64,
+    pub cached: bool,
+    /// Why the test failed or was skipped
+    pub message: Option<String>,
+    /// Expected value of a failed assertion
+    pub expected: Option<String>,
+    /// Actual value of a failed assertion
+    pub actual: Option<String>,
+}
+
+impl From<&TestResult> for TestRecord {
+    fn from(result: &TestResult) -> Self {
+        let (status, message) = match &result.outcome {
+            TestOutcome::Passed { .. } => ("passed", None),
+            TestOutcome::Failed { error } => ("failed", Some(error.clone())),
+            TestOutcome::Skipped { reason } => ("skipped", Some(reason.clone())),
+            TestOutcome::Timeout => ("timeout", Some("Test timed out".to_string())),
+        };
+        let (expected, actual) = match result.error() {
+            Some(TestError::AssertionFailed { expected, actual }) => (Some(expected), Some(actual)),
+            _ => (None, None),
+        };
+        TestRecord {
+            name: result.name.clone(),
+            file: result.file.clone(),
+            line: result.location.map(|(line, _)| line),
+            column: result.location.map(|(_, column)| column),
+            status,
+            duration_ms: result.duration.as_secs_f64() * 1000.0,
+            cached: result.cached,
+            message,
+            expected,
+            actual,
+        }
+    }
+}
+
+/// Totals of a finished run
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryRecord {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub cached: usize,
+    pub duration_ms: f64,
+}
+
+impl From<&TestSummary> for SummaryRecord {
+    fn from(summary: &TestSummary) -> Self {
+        SummaryRecord {
+            total: summary.total,
+            passed: summary.passed,
+            failed: summary.failed,
+            cached: summary.cached,
+            duration_ms: summary.duration.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// Progress of a test run
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TestEvent {
+    RunStarted { total: usize },
+    TestStarted { name: String, file: PathBuf },
+    TestFinished(TestRecord),
+    RunFinished(SummaryRecord),
+}
+
+/// Follows a test run as it happens. A test is started by the thread that
+/// runs it, so starts may interleave; results come in file order.
+pub trait TestListener: Send {
+    fn on_event(&mut self, event: &TestEvent);
+}
+
+/// Writes each event as a line of JSON, flushing so that readers see
+/// progress immediately
+pub struct JsonEventWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonEventWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonEventWriter { writer }
+    }
+}
+
+impl<W: Write + Send> TestListener for JsonEventWriter<W> {
+    fn on_event(&mut self, event: &TestEvent) {
+        // A reader that went away must not stop the run
+        if let Ok(line) = serde_json::to_string(event) {
+            let _ = writeln!(self.writer, "{line}");
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+/// The run as a JSON document with a summary and every test
+pub fn json(summary: &TestSummary) -> String {
+    #[derive(Serialize)]
+    struct Report {
+        summary: SummaryRecord,
+        tests: Vec<TestRecord>,
+    }
+
+    let report = Report {
+        summary: summary.into(),
+        tests: summary.results.iter().map(TestRecord::from).collect(),
+    };
+    serde_json::to_string_pretty(&report).expect("test reports are serializable")
+}
+
+/// The run as JUnit XML, with a test suite per file
+pub fn junit(summary: &TestSummary) -> String {
+    let mut files: BTreeMap<&PathBuf, Vec<TestRecord>> = BTreeMap::new();
+    for result in &summary.results {
+        files.entry(&result.file).or_default().push(result.into());
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"vibe\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        summary.total,
+        summary.failed,
+        summary.duration.as_secs_f64()
+    );
+    for (file, records) in files {
+        let count = |status| records.iter().filter(|r| r.status == status).count();
+        let time: f64 = records.iter().map(|r| r.duration_ms).sum::<f64>() / 1000.0;
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape_xml(&file.display().to_string()),
+            records.len(),
+            count("failed"),
+            count("timeout"),
+            count("skipped"),
+            time
+        );
+        for record in &records {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" file=\"{}\"",
+                escape_xml(&record.name),
+                escape_xml(&file.with_extension("").display().to_string()),
+                escape_xml(&file.display().to_string())
+            );
+            if let Some(line) = record.line {
+                let _ = write!(xml, " line=\"{line}\"");
+            }
+            let _ = writeln!(xml, " time=\"{:.3}\">", record.duration_ms / 1000.0);
+            if record.cached {
+                xml.push_str(
+                    "      <properties><property name=\"cached\" value=\"true\"/></properties>\n",
+                );
+            }
+            let message = escape_xml(record.message.as_deref().unwrap_or_default());
+            match record.status {
+                "failed" => {
+                    let kind = if record.expected.is_some() {
+                        "AssertionFailed"
+                    } else {
+                        "Failure"
+                    };
+                    let _ = write!(xml, "      <failure message=\"{message}\" type=\"{kind}\">");
+                    if let (Some(expected), Some(actual)) = (&record.expected, &record.actual) {
+                        let _ = write!(
+                            xml,
+                            "expected: {}\nactual: {}\n",
+                            escape_xml(expected),
+                            escape_xml(actual)
+                        );
+                    }
+                    let _ = writeln!(xml, "{message}</failure>");
+                }
+                "timeout" => {
+                    let _ = writeln!(xml, "      <error message=\"{message}\" type=\"Timeout\"/>");
+                }
+                "skipped" => {
+                    let _ = writeln!(xml, "      <skipped message=\"{message}\"/>");
+                }
+                _ => {}
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// The run in the Test Anything Protocol, version 13, with YAML
+/// diagnostics for failures
+pub fn tap(summary: &TestSummary) -> String {
+    let mut tap = format!("TAP version 13\n1..{}\n", summary.results.len());
+    for (number, result) in summary.results.iter().enumerate() {
+        let record = TestRecord::from(result);
+        let number = number + 1;
+        let name = record.name.replace('#', "\\#");
+        match record.status {
+            "passed" => {
+                let cached = if record.cached { " # cached" } else { "" };
+                let _ = writeln!(tap, "ok {number} - {name}{cached}");
+            }
+            "skipped" => {
+                let reason = record.message.as_deref().unwrap_or_default();
+                let _ = writeln!(tap, "ok {number} - {name} # SKIP {reason}");
+            }
+            _ => {
+                let _ = writeln!(tap, "not ok {number} - {name}");
+                tap.push_str("  ---\n");
+                // JSON scalars are valid YAML
+                let mut field = |key: &str, value: serde_json::Value| {
+                    let _ = writeln!(tap, "  {key}: {value}");
+                };
+                if let Some(message) = &record.message {
+                    field("message", message.as_str().into());
+                }
+                field("file", record.file.display().to_string().into());
+                if let Some(line) = record.line {
+                    field("line", line.into());
+                }
+                if let Some(column) = record.column {
+                    field("column", column.into());
+                }
+                if let Some(expected) = &record.expected {
+                    field("expected", expected.as_str().into());
+                }
+                if let Some(actual) = &record.actual {
+                    field("actual", actual.as_str().into());
+                }
+                field("duration_ms", record.duration_ms.into());
+                field("cached", record.cached.into());
+                tap.push_str("  ...\n");
+            }
+        }
+    }
+    tap
+}
+
+pub(super) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn summary() -> TestSummary {
+        let result = |name: &str, outcome, cached| TestResult {
+            name: name.to_string(),
+            file: PathBuf::from("math.vibe"),
+            outcome,
+            duration: Duration::from_millis(2),
+            cached,
+            location: Some((3, 1)),
+        };
+        let results = vec![
+            result("adds", TestOutcome::Passed { value: "Test passed".to_string() }, true),
+            result(
+                "subtracts",
+                TestOutcome::Failed {
+                    error: "Assertion failed: expected 1, got 2".to_string(),
+                },
+                false,
+            ),
+        ];
+        TestSummary {
+            total: 2,
+            passed: 1,
+            failed: 1,
+            cached: 1,
+            duration: Duration::from_millis(4),
+            results,
+        }
+    }
+
+    #[test]
+    fn test_junit_reports_failures_with_their_location() {
+        let xml = junit(&summary());
+        assert!(xml.contains("<testsuite name=\"math.vibe\" tests=\"2\" failures=\"1\""), "{xml}");
+        assert!(xml.contains("name=\"subtracts\" classname=\"math\" file=\"math.vibe\" line=\"3\""), "{xml}");
+        assert!(xml.contains("type=\"AssertionFailed\">expected: 1\nactual: 2\n"), "{xml}");
+        assert!(xml.contains("<property name=\"cached\" value=\"true\"/>"), "{xml}");
+    }
+
+    #[test]
+    fn test_tap_reports_failures_with_yaml_diagnostics() {
+        let tap = tap(&summary());
+        assert!(tap.starts_with("TAP version 13\n1..2\nok 1 - adds # cached\nnot ok 2 - subtracts\n"), "{tap}");
+        assert!(tap.contains("  expected: \"1\"\n  actual: \"2\"\n"), "{tap}");
+        assert!(tap.contains("  line: 3\n  column: 1\n"), "{tap}");
+    }
+
+    #[test]
+    fn test_json_reports_and_events_carry_the_same_records() {
+        let report: serde_json::Value = serde_json::from_str(&json(&summary())).unwrap();
+        assert_eq!(report["summary"]["failed"], 1);
+        assert_eq!(report["tests"][1]["status"], "failed");
+        assert_eq!(report["tests"][1]["expected"], "1");
+        assert_eq!(report["tests"][1]["line"], 3);
+
+        let mut lines = Vec::new();
+        let mut writer = JsonEventWriter::new(&mut lines);
+        writer.on_event(&TestEvent::RunStarted { total: 2 });
+        writer.on_event(&TestEvent::TestFinished((&summary().results[0]).into()));
+        let lines = String::from_utf8(lines).unwrap();
+        let events: Vec<serde_json::Value> = lines
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events[0]["event"], "run_started");
+        assert_eq!(events[1]["event"], "test_finished");
+        assert_eq!(events[1]["cached"], true);
+    }
+}
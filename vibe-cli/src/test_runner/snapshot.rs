@@ -0,0 +1,424 @@
+//! Snapshot tests
+//!
+//! `snapshot "name" expr` records what `expr` is: its type, its value laid
+//! out over several lines when it is long, and anything it prints. The
+//! record is kept in `snapshots/<file>/<name>.snap` next to the test file, so
+//! a change to a value or to an API's types shows up as a diff in review.
+//!
+//! A missing snapshot is written on the first run. A snapshot that no longer
+//! matches fails with a line diff, until `vibe test --update-snapshots`
+//! accepts the change.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vibe_language::{Span, Type, Value, XsError};
+use vibe_runtime::{CallInfo, EvalObserver, SharedObserver};
+
+/// Directory snapshots are kept in, next to the test file
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Width a value is laid out in
+const WIDTH: usize = 80;
+
+/// What a snapshot test produced
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Type of the expression, when it type checks
+    pub ty: Option<String>,
+    /// The value, unless it is a function: a function is known by its type
+    pub value: Option<String>,
+    /// Lines the expression printed
+    pub output: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn new(ty: Option<&Type>, value: &Value, output: Vec<String>) -> Self {
+        let function = matches!(
+            value,
+            Value::Closure { .. } | Value::RecClosure { .. } | Value::BuiltinFunction { .. }
+        );
+        Snapshot {
+            ty: ty.map(|ty| normalize_type_vars(ty).to_string()),
+            value: (!function).then(|| pretty_value(value)),
+            output,
+        }
+    }
+
+    /// The snapshot as it is stored
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        if let Some(ty) = &self.ty {
+            text.push_str(&format!("-- type --\n{ty}\n"));
+        }
+        if let Some(value) = &self.value {
+            text.push_str(&format!("-- value --\n{value}\n"));
+        }
+        if !self.output.is_empty() {
+            text.push_str("-- output --\n");
+            for line in &self.output {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+/// How a snapshot compared with the stored one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotOutcome {
+    Matched,
+    /// The snapshot was new, or was updated
+    Written(PathBuf),
+    /// The stored snapshot differs; holds the diff from it
+    Changed(String),
+}
+
+/// Where the snapshot `name` of the tests in `file` is kept
+pub fn snapshot_path(file: &Path, name: &str) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    file.parent()
+        .unwrap_or(Path::new(""))
+        .join(SNAPSHOT_DIR)
+        .join(stem)
+        .join(format!("{name}.snap"))
+}
+
+/// The stored snapshot at `path`, if there is one
+pub fn stored(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+/// Compare `actual` with the snapshot at `path`, writing it when there is
+/// none yet or when `update` is set
+pub fn check(path: &Path, actual: &Snapshot, update: bool) -> Result<SnapshotOutcome> {
+    let actual = actual.render();
+    match stored(path) {
+        Some(expected) if expected == actual => Ok(SnapshotOutcome::Matched),
+        Some(expected) if !update => Ok(SnapshotOutcome::Changed(diff(&expected, &actual))),
+        _ => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            fs::write(path, actual)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            Ok(SnapshotOutcome::Written(path.to_path_buf()))
+        }
+    }
+}
+
+/// A line diff from `expected` to `actual`: removed lines start with `-`,
+/// added ones with `+`, and unchanged ones with a space
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // Length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+/// A value as `format_value` shows it, with lists, records and constructors
+/// broken over indented lines when they do not fit
+pub fn pretty_value(value: &Value) -> String {
+    let mut text = String::new();
+    write_value(&mut text, value, 0);
+    text
+}
+
+fn write_value(text: &mut String, value: &Value, indent: usize) {
+    let flat = crate::cli::format_value(value);
+    if indent + flat.len() <= WIDTH {
+        text.push_str(&flat);
+        return;
+    }
+    let inner = indent + 2;
+    let (open, items): (String, Vec<&Value>) = match value {
+        Value::List(items) if !items.is_empty() => ("(list".to_string(), items.iter().collect()),
+        Value::Constructor { name, values } if !values.is_empty() => {
+            (format!("({}", name.0), values.iter().collect())
+        }
+        Value::Record { fields } if !fields.is_empty() => {
+            text.push('{');
+            for (i, (name, value)) in fields.iter().enumerate() {
+                text.push('\n');
+                text.push_str(&" ".repeat(inner));
+                text.push_str(&format!("{name}: "));
+                write_value(text, value, inner + name.len() + 2);
+                if i + 1 < fields.len() {
+                    text.push(',');
+                }
+            }
+            text.push('\n');
+            text.push_str(&" ".repeat(indent));
+            text.push('}');
+            return;
+        }
+        _ => {
+            text.push_str(&flat);
+            return;
+        }
+    };
+    text.push_str(&open);
+    for item in items {
+        text.push('\n');
+        text.push_str(&" ".repeat(inner));
+        write_value(text, item, inner);
+    }
+    text.push(')');
+}
+
+/// `ty` with its type variables renamed `a`, `b`, ... in the order they
+/// appear, so that a snapshot does not depend on how inference numbers them
+pub fn normalize_type_vars(ty: &Type) -> Type {
+    fn rename(ty: &Type, names: &mut HashMap<String, String>) -> Type {
+        let mut boxed = |ty: &Type| Box::new(rename(ty, names));
+        match ty {
+            Type::Var(name) => {
+                let next = names.len();
+                let name = names.entry(name.clone()).or_insert_with(|| var_name(next));
+                Type::Var(name.clone())
+            }
+            Type::List(ty) => Type::List(boxed(ty)),
+            Type::Option(ty) => Type::Option(boxed(ty)),
+            Type::Function(from, to) => {
+                let from = boxed(from);
+                Type::Function(from, boxed(to))
+            }
+            Type::FunctionWithEffect { from, to, effects } => {
+                let from = boxed(from);
+                Type::FunctionWithEffect {
+                    from,
+                    to: boxed(to),
+                    effects: effects.clone(),
+                }
+            }
+            Type::UserDefined { name, type_params } => Type::UserDefined {
+                name: name.clone(),
+                type_params: type_params.iter().map(|ty| rename(ty, names)).collect(),
+            },
+            Type::Record { fields } => Type::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), rename(ty, names)))
+                    .collect(),
+            },
+            Type::ExtensibleRecord { fields, row } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), rename(ty, names)))
+                    .collect();
+                let next = names.len();
+                let row = names.entry(row.clone()).or_insert_with(|| var_name(next));
+                Type::ExtensibleRecord {
+                    fields,
+                    row: row.clone(),
+                }
+            }
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|ty| rename(ty, names)).collect()),
+            Type::Applied { constructor, args } => {
+                let next = names.len();
+                let constructor = names
+                    .entry(constructor.clone())
+                    .or_insert_with(|| var_name(next))
+                    .clone();
+                Type::Applied {
+                    constructor,
+                    args: args.iter().map(|ty| rename(ty, names)).collect(),
+                }
+            }
+            Type::Forall { vars, body } => {
+                let vars = vars
+                    .iter()
+                    .map(|var| {
+                        let next = names.len();
+                        names.entry(var.clone()).or_insert_with(|| var_name(next)).clone()
+                    })
+                    .collect();
+                Type::Forall {
+                    vars,
+                    body: Box::new(rename(body, names)),
+                }
+            }
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit => ty.clone(),
+        }
+    }
+    rename(ty, &mut HashMap::new())
+}
+
+/// `a` to `z`, then `a1`, `b1`, ...
+fn var_name(index: usize) -> String {
+    let letter = char::from(b'a' + (index % 26) as u8);
+    match index / 26 {
+        0 => letter.to_string(),
+        round => format!("{letter}{round}"),
+    }
+}
+
+/// Keeps what a snapshot prints, passing everything else on to the
+/// observer that was there before
+pub(super) struct OutputCapture {
+    pub(super) lines: Vec<String>,
+    inner: Option<SharedObserver>,
+}
+
+impl OutputCapture {
+    pub(super) fn new(inner: Option<SharedObserver>) -> Self {
+        OutputCapture {
+            lines: Vec::new(),
+            inner,
+        }
+    }
+
+    fn inner(&self, f: impl FnOnce(&mut dyn EvalObserver)) {
+        if let Some(inner) = &self.inner {
+            let mut inner = inner
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            f(&mut *inner);
+        }
+    }
+}
+
+impl EvalObserver for OutputCapture {
+    fn enter_apply(&mut self, call: &CallInfo<'_>) {
+        self.inner(|inner| inner.enter_apply(call));
+    }
+
+    fn exit_apply(&mut self, result: &Result<Value, XsError>) {
+        self.inner(|inner| inner.exit_apply(result));
+    }
+
+    fn expr(&mut self, span: &Span) {
+        self.inner(|inner| inner.expr(span));
+    }
+
+    fn branch(&mut self, span: &Span, branch: usize) {
+        self.inner(|inner| inner.branch(span, branch));
+    }
+
+    fn output(&mut self, text: &str) -> bool {
+        self.lines.push(text.to_string());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_snapshots_fail_with_a_diff_until_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = snapshot_path(&dir.path().join("math.vibe"), "sorted list");
+        assert!(path.ends_with("snapshots/math/sorted_list.snap"));
+
+        let snapshot = |n| {
+            Snapshot::new(
+                Some(&Type::List(Box::new(Type::Int))),
+                &Value::List(vec![Value::Int(1), Value::Int(n)]),
+                vec!["sorting".to_string()],
+            )
+        };
+        assert_eq!(
+            check(&path, &snapshot(2), false).unwrap(),
+            SnapshotOutcome::Written(path.clone())
+        );
+        assert_eq!(
+            stored(&path).unwrap(),
+            "-- type --\n(List Int)\n-- value --\n(list 1 2)\n-- output --\nsorting\n"
+        );
+        assert_eq!(
+            check(&path, &snapshot(2), false).unwrap(),
+            SnapshotOutcome::Matched
+        );
+
+        let SnapshotOutcome::Changed(diff) = check(&path, &snapshot(3), false).unwrap() else {
+            panic!("the snapshot should have changed");
+        };
+        assert!(diff.contains("- (list 1 2)\n+ (list 1 3)"), "{diff}");
+        assert!(diff.contains("  -- output --"), "{diff}");
+
+        check(&path, &snapshot(3), true).unwrap();
+        assert_eq!(
+            check(&path, &snapshot(3), false).unwrap(),
+            SnapshotOutcome::Matched
+        );
+    }
+
+    #[test]
+    fn test_long_values_are_broken_over_lines() {
+        let word = |c: &str| c.repeat(30);
+        let value = Value::Record {
+            fields: vec![
+                ("short".to_string(), Value::Int(1)),
+                (
+                    "words".to_string(),
+                    Value::List(["a", "b", "c"].map(|c| Value::String(word(c))).to_vec()),
+                ),
+            ],
+        };
+        let item = |c| format!("\n           \"{}\"", word(c));
+        assert_eq!(
+            pretty_value(&value),
+            format!(
+                "{{\n  short: 1,\n  words: (list{}{}{})\n}}",
+                item("a"),
+                item("b"),
+                item("c")
+            )
+        );
+        assert_eq!(pretty_value(&Value::Int(3)), "3");
+    }
+
+    #[test]
+    fn test_type_variables_are_named_in_order() {
+        let var = |name: &str| Type::Var(name.to_string());
+        let ty = Type::Function(
+            Box::new(var("t7")),
+            Box::new(Type::Function(Box::new(var("t3")), Box::new(var("t7")))),
+        );
+        assert_eq!(normalize_type_vars(&ty).to_string(), "a -> b -> a");
+    }
+}
@@ -0,0 +1,139 @@
+//! Tree-shaken WebAssembly builds of a single codebase definition
+//!
+//! `vibe build main --target wasm` compiles `main` and the definitions it
+//! transitively depends on, and nothing else, into a core module or a
+//! component, then reports how many bytes each definition contributes.
+
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vibe_codebase::vbin::VBinStorage;
+use vibe_codebase::Codebase;
+use vibe_compiler::wasm::program::{
+    compile_program, encode_component, encode_module, size_report, FunctionOrigin,
+    ProgramDefinition, SizeReport, SUPPORT_BUILTINS,
+};
+use vibe_compiler::wasm::CodeGenError;
+
+/// The result of building an entry point
+#[derive(Debug, Clone)]
+pub struct BuildOutput {
+    pub bytes: Vec<u8>,
+    pub component: bool,
+    pub report: SizeReport,
+}
+
+/// Build `entry` from `codebase`. Only `entry` and its dependencies are
+/// compiled, and only the builtins they call are included.
+pub fn build_entry(codebase: &Codebase, entry: &str, component: bool) -> Result<BuildOutput> {
+    let term = codebase
+        .get_term_by_name(entry)
+        .ok_or_else(|| anyhow::anyhow!("Definition not found: {entry}"))?;
+
+    let mut reachable = codebase.get_all_dependencies(&term.hash)?;
+    reachable.push(term.hash.clone());
+    let definitions = codebase
+        .topological_order(&reachable)?
+        .iter()
+        .filter_map(|hash| codebase.get_term(hash))
+        .map(|term| {
+            let name = term
+                .name
+                .clone()
+                .with_context(|| format!("Definition {} has no name", term.hash.to_hex()))?;
+            Ok(ProgramDefinition {
+                name,
+                expr: term.expr.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let program = compile_program(&definitions, entry)
+        .map_err(|e| match e {
+            CodeGenError::UndefinedVariable(name) => anyhow::anyhow!(
+                "`{name}` is neither a definition nor a builtin WebAssembly builds support; \
+                 besides arithmetic and comparison operators, the only builtins are {}",
+                SUPPORT_BUILTINS.map(|(builtin, _)| builtin).join(", ")
+            ),
+            e => e.into(),
+        })
+        .with_context(|| format!("Failed to compile {entry} to WebAssembly"))?;
+    let module = encode_module(&program)?;
+    let bytes = if component {
+        encode_component(&program, &term.ty)
+            .with_context(|| format!("Failed to build a component exporting {entry}"))?
+    } else {
+        module.clone()
+    };
+    let report = size_report(&program, &module, bytes.len())?;
+
+    Ok(BuildOutput {
+        bytes,
+        component,
+        report,
+    })
+}
+
+/// Render the size report, largest contributions first
+pub fn format_report(output: &Path, build: &BuildOutput) -> String {
+    let kind = if build.component {
+        "component"
+    } else {
+        "core module"
+    };
+    let mut rows: Vec<(String, usize)> = build
+        .report
+        .functions
+        .iter()
+        .map(|function| {
+            let label = match &function.origin {
+                FunctionOrigin::Definition(name) => name.clone(),
+                FunctionOrigin::Builtin(name) => format!("{name} (builtin)"),
+            };
+            (label, function.bytes)
+        })
+        .collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    rows.push((
+        "(headers, types, exports)".to_string(),
+        build.report.overhead(),
+    ));
+
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let mut report = format!(
+        "Built {} ({kind}, {} bytes)\n",
+        output.display(),
+        build.report.total
+    );
+    for (label, bytes) in rows {
+        report.push_str(&format!("  {label:<width$}  {bytes:>6} bytes\n"));
+    }
+    report
+}
+
+/// Handle `vibe build`
+pub fn run_build(
+    entry: &str,
+    target: &str,
+    codebase_path: &Path,
+    output: Option<PathBuf>,
+    component: bool,
+) -> Result<()> {
+    if target != "wasm" {
+        anyhow::bail!("Unsupported build target `{target}` (supported: wasm)");
+    }
+
+    let mut storage = VBinStorage::new(codebase_path.to_string_lossy().to_string());
+    let codebase = storage
+        .load_full()
+        .map_err(|e| anyhow::anyhow!("Failed to load codebase {}: {e}", codebase_path.display()))?;
+
+    let build = build_entry(&codebase, entry, component)?;
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{entry}.wasm")));
+    fs::write(&output, &build.bytes)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    print!("{} {}", "Success:".green(), format_report(&output, &build));
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! `vibe check`, `vibe test` and `vibe build` across a workspace
+//!
+//! Pointed at a workspace root, these commands visit every member in
+//! dependency order. `check` remembers the sources each member last checked
+//! successfully with and only checks the members that changed since, along
+//! with the members depending on them.
+
+use crate::multi_store::extract_definitions;
+use crate::test_runner::{TestSuite, TestSummary};
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vibe_codebase::package::workspace::{CheckState, Workspace, WorkspaceMember};
+use vibe_compiler::wasm::program::{compile_program, encode_module, ProgramDefinition};
+use vibe_language::parser::parse;
+
+/// Library entry used when a member's manifest does not name one
+const DEFAULT_LIB: &str = "src/lib.vibe";
+
+/// The workspace whose root manifest is in `path`, if `path` is a
+/// workspace root
+pub fn workspace_at(path: &Path) -> Result<Option<Workspace>> {
+    let manifest = path.join("package.vibe");
+    if !path.is_dir() || !manifest.is_file() {
+        return Ok(None);
+    }
+    let is_root = vibe_codebase::package::manifest::PackageManifest::load_from_file(&manifest)
+        .with_context(|| format!("Failed to read {}", manifest.display()))?
+        .is_workspace_root();
+    if !is_root {
+        return Ok(None);
+    }
+    Ok(Some(Workspace::load(path)?))
+}
+
+/// What `check_workspace` did with each member
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub checked: Vec<String>,
+    /// Members unchanged since their last successful check
+    pub up_to_date: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Type check the members that changed, and their dependents
+pub fn check_workspace(workspace: &Workspace, verbose: bool) -> Result<CheckReport> {
+    let mut state = CheckState::load(workspace)?;
+    let stale: Vec<String> = workspace
+        .stale_members(&state)?
+        .into_iter()
+        .map(|m| m.name.clone())
+        .collect();
+
+    let mut report = CheckReport::default();
+    for member in &workspace.members {
+        if !stale.contains(&member.name) {
+            println!("  {} {} (up to date)", "-".dimmed(), member.name);
+            report.up_to_date.push(member.name.clone());
+            continue;
+        }
+
+        let mut errors = 0;
+        let sources = member_sources(member);
+        for file in &sources {
+            if let Err(e) = crate::cli::check_file(file, verbose) {
+                eprintln!("{}: {}", file.display(), e);
+                errors += 1;
+            }
+        }
+        if errors == 0 {
+            println!(
+                "  {} {} ({} files)",
+                "✓".green(),
+                member.name,
+                sources.len()
+            );
+            state.record(member)?;
+            report.checked.push(member.name.clone());
+        } else {
+            println!(
+                "  {} {} ({} of {} files failed)",
+                "✗".red(),
+                member.name,
+                errors,
+                sources.len()
+            );
+            state.forget(&member.name);
+            report.failed.push(member.name.clone());
+        }
+    }
+    state.save(workspace)?;
+    Ok(report)
+}
+
+/// Run the tests of every member with `suite`, in dependency order. The
+/// suite's results should be cached at the workspace root.
+pub fn test_workspace(workspace: &Workspace, suite: &mut TestSuite) -> Result<TestSummary> {
+    for member in &workspace.members {
+        let mut found = 0;
+        for file in member_sources(member) {
+            match suite.load_test_file(&file) {
+                Ok(tests) => found += tests,
+                Err(e) => eprintln!("{}: {}: {}", "Error".red(), file.display(), e),
+            }
+        }
+        if suite.text_output() {
+            println!("Found {} tests in {}", found, member.name);
+        }
+    }
+    Ok(suite.run_all())
+}
+
+/// Build the `main` definition of every member that has a main entry into a
+/// WebAssembly module in `output_dir`. A member's build includes the
+/// libraries of the members it depends on.
+pub fn build_workspace(workspace: &Workspace, output_dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    let mut built = Vec::new();
+    for member in &workspace.members {
+        let Some(main) = &member.manifest.entry.main else {
+            continue;
+        };
+        let mut files: Vec<PathBuf> = workspace
+            .dependencies_of(&member.name)
+            .into_iter()
+            .chain(std::iter::once(member))
+            .map(library)
+            .filter(|lib| lib.is_file())
+            .collect();
+        files.push(member.dir.join(main));
+
+        let mut definitions = Vec::new();
+        for file in &files {
+            let source = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let expr = parse(&source)
+                .map_err(|e| anyhow::anyhow!("{}: parse error: {}", file.display(), e))?;
+            for (name, expr) in extract_definitions(&expr) {
+                definitions.retain(|d: &ProgramDefinition| d.name != name);
+                definitions.push(ProgramDefinition { name, expr });
+            }
+        }
+
+        let program = compile_program(&definitions, "main")
+            .with_context(|| format!("Failed to compile {} to WebAssembly", member.name))?;
+        let bytes = encode_module(&program)?;
+        let output = output_dir.join(format!("{}.wasm", member.name));
+        fs::write(&output, &bytes)
+            .with_context(|| format!("Failed to write {}", output.display()))?;
+        println!(
+            "  {} {} ({} bytes)",
+            "✓".green(),
+            output.display(),
+            bytes.len()
+        );
+        built.push(output);
+    }
+    Ok(built)
+}
+
+/// The library file of a member
+fn library(member: &WorkspaceMember) -> PathBuf {
+    member
+        .dir
+        .join(member.manifest.entry.lib.as_deref().unwrap_or(DEFAULT_LIB))
+}
+
+/// The `.vibe` files of a member, skipping hidden directories
+fn member_sources(member: &WorkspaceMember) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = walkdir::WalkDir::new(&member.dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| p.is_file() && p.extension().and_then(|s| s.to_str()) == Some("vibe"))
+        .filter(|p| p.file_name().and_then(|n| n.to_str()) != Some("package.vibe"))
+        .collect();
+    files.sort();
+    files
+}
@@ -0,0 +1,13 @@
+//! Fixtures shared by the shell integration tests
+
+use tempfile::TempDir;
+use vibe_cli::shell::ShellState;
+use vibe_codebase::unified_parser::SyntaxMode;
+
+/// A shell over a fresh codebase in `temp_dir` that never launches an editor
+pub fn new_shell(temp_dir: &TempDir) -> ShellState {
+    let mut shell = ShellState::new(temp_dir.path().to_path_buf()).unwrap();
+    shell.set_editor(None);
+    shell.set_syntax_mode(SyntaxMode::SExprOnly);
+    shell
+}
@@ -0,0 +1,55 @@
+//! The interpreter, typed IR and WASM backends agree on generated programs
+
+use std::fs;
+use tempfile::TempDir;
+use vibe_cli::conformance::{load_programs, Harness, Observed, Program};
+use vibe_language::parser::parse;
+
+#[test]
+fn test_backends_agree_on_generated_programs() {
+    let report = Harness::default().run(&[], 300, 42);
+    assert_eq!(report.programs, 300);
+    assert!(report.passed(), "{:#?}", report.divergences);
+
+    let runs: Vec<&str> = report.runs.iter().map(|(backend, _)| *backend).collect();
+    assert_eq!(runs, ["interpreter", "typed-ir", "wasm"]);
+    for (backend, count) in &report.runs {
+        assert!(*count > 100, "{backend} ran {count} programs");
+    }
+}
+
+#[test]
+fn test_errors_agree_across_backends() {
+    let program = Program::new("division by zero", parse("1 / 0").unwrap()).unwrap();
+    let (results, divergence) = Harness::default().check(&program);
+    assert!(divergence.is_none(), "{results:?}");
+    assert!(results
+        .iter()
+        .all(|(_, observed)| matches!(observed, Observed::Error(_))));
+}
+
+#[test]
+fn test_files_that_do_not_type_check_are_skipped() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("sum.vibe"), "1 + 2").unwrap();
+    fs::write(dir.path().join("ill_typed.vibe"), "1 + true").unwrap();
+    fs::write(dir.path().join("notes.txt"), "not a program").unwrap();
+
+    let (programs, skipped) = load_programs(&[dir.path().to_path_buf()]);
+    assert_eq!(programs.len(), 1);
+    assert!(programs[0].name.ends_with("sum.vibe"));
+    assert_eq!(skipped.len(), 1);
+    assert!(skipped[0].path.ends_with("ill_typed.vibe"));
+    assert!(
+        skipped[0].reason.starts_with("type error"),
+        "{}",
+        skipped[0].reason
+    );
+
+    let report = Harness::default().run(&programs, 0, 0);
+    assert!(report.passed());
+    assert_eq!(
+        report.runs,
+        [("interpreter", 1), ("typed-ir", 1), ("wasm", 1)]
+    );
+}
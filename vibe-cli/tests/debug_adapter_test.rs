@@ -0,0 +1,200 @@
+//! Tests for the Debug Adapter Protocol server
+
+use serde_json::{json, Value as Json};
+use std::collections::VecDeque;
+use std::io::{BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+use vibe_cli::dap::{read_message, serve, write_message};
+
+/// How long the client waits for the adapter before failing the test
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One direction of an in-memory connection
+struct PipeReader {
+    chunks: Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.chunks.recv_timeout(READ_TIMEOUT) {
+                Ok(chunk) => self.pending = chunk,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "no message from the debug adapter",
+                    ))
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let _ = self.0.send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn pipe() -> (PipeWriter, BufReader<PipeReader>) {
+    let (sender, receiver) = mpsc::channel();
+    let reader = PipeReader {
+        chunks: receiver,
+        pending: Vec::new(),
+    };
+    (PipeWriter(sender), BufReader::new(reader))
+}
+
+struct Client {
+    requests: PipeWriter,
+    messages: BufReader<PipeReader>,
+    /// Messages read while waiting for another one
+    unmatched: VecDeque<Json>,
+    seq: i64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let (requests, server_input) = pipe();
+        let (server_output, messages) = pipe();
+        thread::spawn(move || serve(server_input, server_output).unwrap());
+        Self {
+            requests,
+            messages,
+            unmatched: VecDeque::new(),
+            seq: 0,
+        }
+    }
+
+    fn send(&mut self, command: &str, arguments: Json) {
+        self.seq += 1;
+        let request = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        write_message(&mut self.requests, &request).unwrap();
+    }
+
+    /// The first message matching `predicate`, keeping the others for
+    /// later calls
+    fn wait_for(&mut self, predicate: impl Fn(&Json) -> bool) -> Json {
+        if let Some(index) = self.unmatched.iter().position(&predicate) {
+            return self.unmatched.remove(index).unwrap();
+        }
+        loop {
+            let message = read_message(&mut self.messages).unwrap().unwrap();
+            if predicate(&message) {
+                return message;
+            }
+            self.unmatched.push_back(message);
+        }
+    }
+
+    fn request(&mut self, command: &str, arguments: Json) -> Json {
+        self.send(command, arguments);
+        let seq = self.seq;
+        self.wait_for(|m| m["type"] == "response" && m["request_seq"] == seq)
+    }
+
+    fn event(&mut self, event: &str) -> Json {
+        self.wait_for(|m| m["type"] == "event" && m["event"] == event)
+    }
+}
+
+fn program(temp_dir: &TempDir) -> String {
+    let path = temp_dir.path().join("main.vibe");
+    std::fs::write(&path, "let main = fn -> 7").unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_stop_on_entry_and_inspect() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut client = Client::start();
+
+    let response = client.request("initialize", json!({ "adapterID": "vibe" }));
+    assert_eq!(response["body"]["supportsFunctionBreakpoints"], true);
+    client.event("initialized");
+
+    let response = client.request(
+        "launch",
+        json!({ "program": program(&temp_dir), "stopOnEntry": true }),
+    );
+    assert_eq!(response["success"], true);
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["body"]["reason"], "entry");
+
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    let frames = response["body"]["stackFrames"].as_array().unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0]["name"], "main");
+
+    let response = client.request("scopes", json!({ "frameId": 0 }));
+    let reference = response["body"]["scopes"][0]["variablesReference"].clone();
+    let response = client.request("variables", json!({ "variablesReference": reference }));
+    let variables = response["body"]["variables"].as_array().unwrap();
+    assert!(
+        variables.iter().any(|v| v["name"] == "main"),
+        "{variables:?}"
+    );
+
+    let response = client.request("evaluate", json!({ "expression": "missing" }));
+    assert_eq!(response["success"], false);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    // The response comes before anything the resumed program sends
+    assert!(client.unmatched.is_empty(), "{:?}", client.unmatched);
+    let output = client.event("output");
+    assert_eq!(output["body"]["output"], "=> 7\n");
+    assert_eq!(client.event("exited")["body"]["exitCode"], 0);
+    client.event("terminated");
+
+    let response = client.request("disconnect", json!({}));
+    assert_eq!(response["success"], true);
+}
+
+#[test]
+fn test_function_breakpoint() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut client = Client::start();
+    client.request("initialize", json!({}));
+
+    let response = client.request(
+        "setFunctionBreakpoints",
+        json!({ "breakpoints": [{ "name": "main" }] }),
+    );
+    assert_eq!(response["body"]["breakpoints"][0]["verified"], true);
+    let response = client.request("setBreakpoints", json!({ "breakpoints": [{ "line": 1 }] }));
+    assert_eq!(response["body"]["breakpoints"][0]["verified"], false);
+
+    client.request("launch", json!({ "program": program(&temp_dir) }));
+    client.request("configurationDone", json!({}));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["body"]["reason"], "function breakpoint");
+
+    client.request("next", json!({ "threadId": 1 }));
+    client.event("terminated");
+
+    let response = client.request("launch", json!({ "program": "missing.vibe" }));
+    assert_eq!(response["success"], false);
+}
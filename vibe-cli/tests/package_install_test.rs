@@ -0,0 +1,140 @@
+//! `vibe package add/install/update/remove` against a local registry
+
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use vibe_cli::package_commands::{
+    add_package, install_package, remove_package, update_packages, PackageContext,
+};
+use vibe_codebase::package::cache::PackageCache;
+use vibe_codebase::package::hash::calculate_package_hash;
+use vibe_codebase::package::lockfile::Lockfile;
+use vibe_codebase::package::manifest::PackageManifest;
+use vibe_codebase::package::modules::PackageModules;
+use vibe_codebase::package::registry::LocalRegistry;
+use vibe_language::{Expr, Ident, Span, Value};
+use vibe_runtime::Interpreter;
+
+fn publish(root: &Path, version: &str, answer: i64) {
+    let package_dir = root.join("sources").join(version);
+    fs::create_dir_all(package_dir.join("src")).unwrap();
+    let mut manifest = PackageManifest::new("math".to_string());
+    manifest.package.version = Some(version.to_string());
+    manifest.add_export("answer".to_string());
+    manifest
+        .save_to_file(&package_dir.join("package.vibe"))
+        .unwrap();
+    fs::write(
+        package_dir.join("src/lib.vibe"),
+        format!("let answer = {answer}"),
+    )
+    .unwrap();
+
+    let hash = calculate_package_hash(&package_dir).unwrap();
+    LocalRegistry::new(root.join("registry"))
+        .unwrap()
+        .publish(&manifest, &hash, &package_dir)
+        .unwrap();
+}
+
+fn context(root: &Path, cache: &str) -> PackageContext {
+    let project_dir = root.join("app");
+    fs::create_dir_all(&project_dir).unwrap();
+    PackageContext {
+        project_dir,
+        cache_dir: root.join(cache),
+        registry_dir: root.join("registry"),
+    }
+}
+
+fn locked_version(ctx: &PackageContext) -> Option<String> {
+    Lockfile::load(&ctx.project_dir.join("package.lock"))
+        .unwrap()
+        .unwrap()
+        .get("math")
+        .and_then(|p| p.version.clone())
+}
+
+fn answer(ctx: &PackageContext) -> Value {
+    let lockfile = Lockfile::load(&ctx.project_dir.join("package.lock"))
+        .unwrap()
+        .unwrap();
+    let cache = PackageCache::new(ctx.cache_dir.clone()).unwrap();
+    let modules = PackageModules::load(&lockfile, &cache).unwrap();
+    let expr = Expr::QualifiedIdent {
+        module_name: Ident("math".to_string()),
+        name: Ident("answer".to_string()),
+        span: Span::new(0, 0),
+    };
+    let env = modules.environment(&Interpreter::create_initial_env());
+    Interpreter::new().eval(&expr, &env).unwrap()
+}
+
+#[test]
+fn test_add_update_remove() {
+    let dir = TempDir::new().unwrap();
+    publish(dir.path(), "1.1.0", 11);
+    let ctx = context(dir.path(), "cache");
+    fs::write(
+        ctx.project_dir.join("package.vibe"),
+        "# My app\n{\n  package: { name: \"app\" }  # keep me\n}\n",
+    )
+    .unwrap();
+
+    add_package(&ctx, "math", false).unwrap();
+    let manifest = fs::read_to_string(ctx.project_dir.join("package.vibe")).unwrap();
+    assert_eq!(
+        manifest,
+        "# My app\n{\n  package: { name: \"app\" }  # keep me\n  dependencies: {\n    math: \"^1.1.0\",\n  }\n}\n"
+    );
+    assert_eq!(locked_version(&ctx).as_deref(), Some("1.1.0"));
+    assert_eq!(answer(&ctx), Value::Int(11));
+
+    // A newer compatible release is only picked up by `update`
+    publish(dir.path(), "1.2.0", 12);
+    publish(dir.path(), "2.0.0", 20);
+    let outcome = install_package(&ctx, None, false, false).unwrap();
+    assert!(!outcome.lockfile_changed);
+    assert_eq!(locked_version(&ctx).as_deref(), Some("1.1.0"));
+
+    update_packages(&ctx, &[], false).unwrap();
+    assert_eq!(locked_version(&ctx).as_deref(), Some("1.2.0"));
+    assert_eq!(answer(&ctx), Value::Int(12));
+
+    remove_package(&ctx, "math").unwrap();
+    assert_eq!(locked_version(&ctx), None);
+    let manifest = fs::read_to_string(ctx.project_dir.join("package.vibe")).unwrap();
+    assert!(!manifest.contains("math"), "{manifest}");
+    assert!(manifest.contains("# keep me"), "{manifest}");
+    assert!(remove_package(&ctx, "math").is_err());
+}
+
+#[test]
+fn test_offline_install_is_reproducible() {
+    let dir = TempDir::new().unwrap();
+    publish(dir.path(), "1.0.0", 10);
+    let ctx = context(dir.path(), "cache");
+    PackageManifest::new("app".to_string())
+        .save_to_file(&ctx.project_dir.join("package.vibe"))
+        .unwrap();
+    add_package(&ctx, "math@^1", false).unwrap();
+    let lockfile = fs::read_to_string(ctx.project_dir.join("package.lock")).unwrap();
+
+    // A newer release and a missing registry do not change what is installed
+    publish(dir.path(), "1.5.0", 15);
+    fs::remove_dir_all(dir.path().join("registry")).unwrap();
+    let outcome = install_package(&ctx, None, false, true).unwrap();
+    assert!(!outcome.lockfile_changed);
+    assert_eq!(
+        fs::read_to_string(ctx.project_dir.join("package.lock")).unwrap(),
+        lockfile
+    );
+    assert_eq!(answer(&ctx), Value::Int(10));
+
+    // Offline with an empty cache, the locked package cannot be found
+    let fresh = context(dir.path(), "fresh-cache");
+    let error = install_package(&fresh, None, false, true)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("not in the package cache"), "{error}");
+}
@@ -0,0 +1,104 @@
+//! Tests for branch, log and merge in the shell
+
+mod common;
+
+use common::new_shell;
+use tempfile::TempDir;
+use vibe_cli::shell::ShellState;
+
+fn update(shell: &mut ShellState, source: &str) -> String {
+    std::fs::write(shell.scratch_path(), source).unwrap();
+    shell.update_codebase().unwrap()
+}
+
+#[test]
+fn test_branches_are_independent() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+
+    assert_eq!(
+        shell.switch_branch("feature").unwrap(),
+        "Created branch feature from main"
+    );
+    shell.evaluate_line("let extra = 5").unwrap();
+    update(&mut shell, "let base = 20");
+
+    let log = shell.show_log(None);
+    let descriptions: Vec<_> = log
+        .lines()
+        .map(|line| line.split_once("] ").unwrap().1)
+        .collect();
+    assert_eq!(
+        descriptions,
+        vec![
+            "update base (2 definitions)",
+            "add extra (2 definitions)",
+            "add base (1 definition)",
+            "initial state (0 definitions)",
+        ]
+    );
+    assert_eq!(shell.show_log(Some(1)).lines().count(), 1);
+
+    assert_eq!(
+        shell.switch_branch("main").unwrap(),
+        "Switched to branch main"
+    );
+    assert!(shell.evaluate_line("base").unwrap().contains("10"));
+    assert!(shell.evaluate_line("extra").is_err());
+
+    let branches = shell.list_branches();
+    assert!(branches.contains("* main"), "{branches}");
+    assert!(branches.contains("  feature"), "{branches}");
+
+    // The last branch is restored in a new session
+    shell.switch_branch("feature").unwrap();
+    let mut shell = new_shell(&temp_dir);
+    assert_eq!(shell.current_branch(), "feature");
+    assert!(shell.evaluate_line("extra").unwrap().contains("5"));
+    assert!(shell.switch_branch("bad name").is_err());
+}
+
+#[test]
+fn test_merge_takes_changes_from_other_branch() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+    shell.evaluate_line("let total = base").unwrap();
+
+    shell.switch_branch("feature").unwrap();
+    update(&mut shell, "let base = 20");
+    shell.evaluate_line("let extra = 5").unwrap();
+
+    shell.switch_branch("main").unwrap();
+    let result = shell.merge_branch("feature").unwrap();
+    assert!(result.starts_with("Merged feature into main"), "{result}");
+    assert!(result.contains("extra"), "{result}");
+    assert!(shell.evaluate_line("total").unwrap().contains("20"));
+    assert!(shell.evaluate_line("extra").unwrap().contains("5"));
+    assert!(shell.show_log(Some(1)).contains("merge feature"));
+
+    let result = shell.merge_branch("feature").unwrap();
+    assert_eq!(result, "Already up to date with feature");
+    assert!(shell.merge_branch("main").is_err());
+    assert!(shell.merge_branch("missing").is_err());
+}
+
+#[test]
+fn test_merge_reports_conflicts() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 1").unwrap();
+
+    shell.switch_branch("feature").unwrap();
+    update(&mut shell, "let base = 2");
+    shell.switch_branch("main").unwrap();
+    update(&mut shell, "let base = 3");
+
+    let error = shell.merge_branch("feature").unwrap_err().to_string();
+    assert!(error.contains("Merge conflicts"), "{error}");
+    assert!(error.contains("base: ["), "{error}");
+    assert!(error.contains("on main"), "{error}");
+    assert!(error.contains("on feature"), "{error}");
+    assert!(shell.evaluate_line("base").unwrap().contains("3"));
+}
@@ -0,0 +1,80 @@
+//! Tests for trace, debug and break in the shell
+
+mod common;
+
+use common::new_shell;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use vibe_cli::shell::ShellState;
+use vibe_runtime::debugger::{DebugCommand, DebugFrontend, StopEvent, StopReason};
+
+/// Each pause with the innermost call and its bindings
+type Stops = Vec<(StopReason, String, Vec<String>)>;
+
+/// Records every pause
+struct Recorder {
+    stops: Arc<Mutex<Stops>>,
+}
+
+impl DebugFrontend for Recorder {
+    fn pause(&mut self, stop: &StopEvent) -> DebugCommand {
+        let frame = stop.frames.last().unwrap();
+        let bindings = frame
+            .bindings()
+            .iter()
+            .map(|(name, value)| format!("{name} = {value}"))
+            .collect();
+        self.stops
+            .lock()
+            .unwrap()
+            .push((stop.reason.clone(), frame.to_string(), bindings));
+        DebugCommand::Continue
+    }
+}
+
+fn debug(shell: &mut ShellState, expr: &str) -> (String, Stops) {
+    let stops = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Recorder {
+        stops: stops.clone(),
+    };
+    let result = shell.debug_expr(expr, Box::new(recorder)).unwrap();
+    let stops = stops.lock().unwrap().clone();
+    (result, stops)
+}
+
+#[test]
+fn test_trace_prints_calls_and_result() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let id = fn x -> x").unwrap();
+
+    let result = shell.trace_expr("id 3").unwrap();
+    assert_eq!(result, "id 3 => 3\n3 : Int");
+    assert!(shell.trace_expr("missing 3").is_err());
+}
+
+#[test]
+fn test_debug_stops_at_breakpoints() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+    shell.evaluate_line("let id = fn x -> x").unwrap();
+
+    // Without breakpoints the debugger stops before the first call
+    let (result, stops) = debug(&mut shell, "id 3");
+    assert_eq!(result, "3 : Int");
+    assert_eq!(stops.len(), 1);
+    assert_eq!(stops[0].0, StopReason::Entry);
+
+    assert_eq!(shell.toggle_breakpoint("id"), "Breakpoint set on id");
+    assert_eq!(shell.list_breakpoints(), "  id");
+    let (_, stops) = debug(&mut shell, "id 4");
+    let (reason, call, bindings) = &stops[0];
+    assert_eq!(reason, &StopReason::Breakpoint("id".to_string()));
+    assert_eq!(call, "id 4");
+    assert_eq!(bindings[0], "x = 4");
+    assert!(bindings.contains(&"base = 10".to_string()), "{bindings:?}");
+
+    assert_eq!(shell.toggle_breakpoint("id"), "Removed breakpoint on id");
+    assert_eq!(shell.list_breakpoints(), "No breakpoints");
+}
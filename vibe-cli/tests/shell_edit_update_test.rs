@@ -0,0 +1,99 @@
+//! Tests for the edit/update/undo workflow of the shell
+
+mod common;
+
+use common::new_shell;
+use tempfile::TempDir;
+
+#[test]
+fn test_edit_writes_definition_to_scratch_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+
+    let result = shell.edit_definition("base").unwrap();
+    assert!(result.contains("update"));
+    let scratch = std::fs::read_to_string(shell.scratch_path()).unwrap();
+    assert!(scratch.starts_with("# namespace: scratch\nlet base = 10"));
+
+    assert!(shell.edit_definition("missing").is_err());
+}
+
+#[test]
+fn test_update_propagates_to_dependents() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+    shell.evaluate_line("let total = base").unwrap();
+
+    shell.edit_definition("base").unwrap();
+    std::fs::write(shell.scratch_path(), "let base = 32\n").unwrap();
+    let result = shell.update_codebase().unwrap();
+    assert!(result.contains("Updated base : Int"), "{result}");
+    assert!(result.contains("Propagated total : Int"), "{result}");
+    assert!(shell.evaluate_line("total").unwrap().contains("32"));
+
+    // Applying the same scratch file again changes nothing
+    let result = shell.update_codebase().unwrap();
+    assert!(result.contains("Nothing to update"), "{result}");
+}
+
+#[test]
+fn test_update_keeps_namespace_of_edited_definition() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.set_namespace("Math");
+    shell.evaluate_line("let base = 10").unwrap();
+    shell.evaluate_line("let total = base").unwrap();
+
+    shell.set_namespace("Other");
+    shell.edit_definition("Math.base").unwrap();
+    let scratch = std::fs::read_to_string(shell.scratch_path()).unwrap();
+    std::fs::write(shell.scratch_path(), scratch.replace("10", "32")).unwrap();
+    let result = shell.update_codebase().unwrap();
+    assert!(result.contains("Updated Math.base : Int"), "{result}");
+    assert!(result.contains("Propagated Math.total : Int"), "{result}");
+    assert!(shell.evaluate_line("total").unwrap().contains("32"));
+
+    // Nothing was added to the namespace the update ran in
+    assert!(shell.edit_definition("Other.base").is_err());
+    assert_eq!(shell.undo().unwrap(), "Undid `update Math.base`");
+    assert!(shell.evaluate_line("total").unwrap().contains("10"));
+}
+
+#[test]
+fn test_failed_update_leaves_codebase_unchanged() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+
+    std::fs::write(shell.scratch_path(), "let base = undefined_name\n").unwrap();
+    let error = shell.update_codebase().unwrap_err().to_string();
+    assert!(error.contains("base"), "{error}");
+    assert!(shell.evaluate_line("base").unwrap().contains("10"));
+    assert_eq!(shell.undo().unwrap(), "Undid `add base`");
+}
+
+#[test]
+fn test_undo_redo_survive_restart() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+    std::fs::write(shell.scratch_path(), "let base = 32\n").unwrap();
+    shell.update_codebase().unwrap();
+
+    assert_eq!(shell.undo().unwrap(), "Undid `update base`");
+    assert!(shell.evaluate_line("base").unwrap().contains("10"));
+
+    // A new session starts from the persisted state and keeps the history
+    let mut shell = new_shell(&temp_dir);
+    assert!(shell.evaluate_line("base").unwrap().contains("10"));
+    assert_eq!(shell.redo().unwrap(), "Redid `update base`");
+    assert!(shell.evaluate_line("base").unwrap().contains("32"));
+    assert_eq!(shell.redo().unwrap(), "Nothing to redo");
+
+    shell.undo().unwrap();
+    shell.undo().unwrap();
+    assert!(shell.evaluate_line("base").is_err());
+    assert_eq!(shell.undo().unwrap(), "Nothing to undo");
+}
@@ -0,0 +1,77 @@
+//! Tests for exporting shell definitions as a module
+
+mod common;
+
+use common::new_shell;
+use tempfile::TempDir;
+use vibe_language::{Type, Value};
+use vibe_runtime::Interpreter;
+
+fn run_module(source: &str) -> (Type, Value) {
+    let expr = vibe_language::parser::parse(source).unwrap();
+    let ty = vibe_compiler::type_check(&expr).unwrap();
+    let value = Interpreter::new()
+        .eval(&expr, &Interpreter::create_initial_env())
+        .unwrap();
+    (ty, value)
+}
+
+#[test]
+fn test_export_writes_dependency_closure() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let base = 10").unwrap();
+    shell.evaluate_line("let unrelated = 1").unwrap();
+    shell.evaluate_line("let total = base").unwrap();
+
+    let path = temp_dir.path().join("totals.vibe");
+    let result = shell.export_module(&path, &["total".to_string()]).unwrap();
+    assert!(
+        result.starts_with("Exported total (2 definitions) to"),
+        "{result}"
+    );
+    assert!(
+        result.contains("Unused, not exported: unrelated"),
+        "{result}"
+    );
+
+    let source = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        source,
+        "export { total }\n\nlet base = 10\n\nlet total = base\n"
+    );
+    // The whole module parses, type checks and runs on its own
+    let (ty, value) = run_module(&source);
+    assert_eq!(ty, Type::Int);
+    assert_eq!(value, Value::Int(10));
+
+    assert!(shell
+        .export_module(&path, &["missing".to_string()])
+        .is_err());
+}
+
+#[test]
+fn test_export_defaults_to_session_definitions() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut shell = new_shell(&temp_dir);
+    let path = temp_dir.path().join("session.vibe");
+    assert!(shell.export_module(&path, &[]).is_err());
+
+    shell.evaluate_line("let base = 10").unwrap();
+
+    // Only names defined in this session are exported by default
+    let mut shell = new_shell(&temp_dir);
+    shell.evaluate_line("let total = base").unwrap();
+    let result = shell.export_module(&path, &[]).unwrap();
+    assert!(
+        result.starts_with("Exported total (2 definitions)"),
+        "{result}"
+    );
+    let source = std::fs::read_to_string(&path).unwrap();
+    assert!(source.starts_with("export { total }\n"), "{source}");
+    assert_eq!(run_module(&source).1, Value::Int(10));
+
+    // Evaluations are recorded per session, so a new shell starts empty
+    let mut shell = new_shell(&temp_dir);
+    assert!(shell.export_module(&path, &[]).is_err());
+}
@@ -1,11 +1,12 @@
 use anyhow::Result;
-use std::path::PathBuf;
+use tempfile::TempDir;
 use vibe_language::parser::Parser;
 use vibe_cli::shell::ShellState;
 
 #[test]
 fn test_hash_reference_in_shell() -> Result<()> {
-    let mut shell = ShellState::new(PathBuf::from("test"))?;
+    let temp_dir = TempDir::new()?;
+    let mut shell = ShellState::new(temp_dir.path().to_path_buf())?;
 
     // Simply evaluate an expression that binds a value
     let input1 = "42";
@@ -25,7 +26,8 @@ fn test_type_annotation_embedding() -> Result<()> {
     // like "add x = 42" through its REPL loop, not through evaluate_line.
     // For now, we'll test that evaluate_line works with expressions.
 
-    let mut shell = ShellState::new(PathBuf::from("test"))?;
+    let temp_dir = TempDir::new()?;
+    let mut shell = ShellState::new(temp_dir.path().to_path_buf())?;
 
     // Evaluate a simple expression
     let result = shell.evaluate_line("42")?;
@@ -36,7 +38,8 @@ fn test_type_annotation_embedding() -> Result<()> {
 
 #[test]
 fn test_optional_parameters_in_shell() -> Result<()> {
-    let mut shell = ShellState::new(PathBuf::from("test"))?;
+    let temp_dir = TempDir::new()?;
+    let mut shell = ShellState::new(temp_dir.path().to_path_buf())?;
 
     // Test basic expression evaluation
     // The actual optional parameter functionality is tested in the main test suite
@@ -73,7 +76,8 @@ fn test_import_with_hash_parsing() -> Result<()> {
 
 #[test]
 fn test_namespace_integration() -> Result<()> {
-    let _shell = ShellState::new(PathBuf::from("test"))?;
+    let temp_dir = TempDir::new()?;
+    let _shell = ShellState::new(temp_dir.path().to_path_buf())?;
 
     // TODO: Add public API for namespace manipulation
     // For now, we can't test namespace features without access to internal state
@@ -0,0 +1,79 @@
+//! Shell pipelines over definitions and runtime values
+
+mod common;
+
+use tempfile::TempDir;
+use vibe_cli::shell::ShellState;
+
+/// A shell with `limit` and `offset` defined
+fn shell_with_bindings(temp_dir: &TempDir) -> ShellState {
+    let mut shell = common::new_shell(temp_dir);
+    shell.evaluate_line("let limit = 2").unwrap();
+    shell.evaluate_line("let offset = 5").unwrap();
+    shell
+}
+
+fn pipeline(shell: &ShellState, line: &str) -> Result<String, String> {
+    let commands: Vec<String> = line.split(" | ").map(|s| s.to_string()).collect();
+    shell.execute_pipeline(&commands).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_lambda_stages() {
+    let temp_dir = TempDir::new().unwrap();
+    let shell = shell_with_bindings(&temp_dir);
+
+    // Lambdas see the shell's definitions
+    assert_eq!(
+        pipeline(&shell, "ls | where name == limit | each {fn d -> offset}").unwrap(),
+        "[5]"
+    );
+    assert_eq!(
+        pipeline(&shell, "ls | reduce --fold 10 {fn acc d -> acc}").unwrap(),
+        "10"
+    );
+    let found = pipeline(&shell, "ls | where name == limit | select name").unwrap();
+    assert!(
+        found.contains("limit") && !found.contains("offset"),
+        "{found}"
+    );
+}
+
+#[test]
+fn test_stages_are_type_checked_before_running() {
+    let temp_dir = TempDir::new().unwrap();
+    let shell = shell_with_bindings(&temp_dir);
+
+    let error = pipeline(&shell, "ls | where {fn d -> d}").unwrap_err();
+    assert!(error.contains("where"), "{error}");
+    let error = pipeline(&shell, "ls | where name > 1").unwrap_err();
+    assert!(error.contains("cannot compare column `name`"), "{error}");
+    let error = pipeline(&shell, "ls | sort size").unwrap_err();
+    assert!(error.contains("no column `size`"), "{error}");
+
+    // Any expression can start a pipeline
+    let error = pipeline(&shell, "limit | each {fn x -> x}").unwrap_err();
+    assert!(
+        error.contains("expected a list or a table, found Int"),
+        "{error}"
+    );
+}
+
+#[test]
+fn test_reading_and_writing_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let shell = shell_with_bindings(&temp_dir);
+    let path = temp_dir.path().join("people.csv");
+    std::fs::write(&path, "name,age\nada,36\nbob,25\n").unwrap();
+
+    let line = format!(
+        "from csv {} | where age >= 30 | select name | to json",
+        path.display()
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&pipeline(&shell, &line).unwrap()).unwrap();
+    assert_eq!(json, serde_json::json!([{"name": "ada"}]));
+
+    let error = pipeline(&shell, "from csv missing.csv | count").unwrap_err();
+    assert!(error.contains("cannot read missing.csv"), "{error}");
+}
@@ -0,0 +1,158 @@
+//! Tree-shaken `vibe build` of a codebase definition
+
+use tempfile::TempDir;
+use vibe_cli::wasm_build::{build_entry, format_report, run_build};
+use vibe_codebase::vbin::VBinStorage;
+use vibe_codebase::Codebase;
+use vibe_language::{Expr, Ident, Literal, Span, Type};
+use wasmtime::{Engine, Instance, Module, Store};
+
+fn var(name: &str) -> Expr {
+    Expr::Ident(Ident(name.to_string()), Span::new(0, 0))
+}
+
+fn int(n: i64) -> Expr {
+    Expr::Literal(Literal::Int(n), Span::new(0, 0))
+}
+
+fn call(func: &str, args: Vec<Expr>) -> Expr {
+    Expr::Apply {
+        func: Box::new(var(func)),
+        args,
+        span: Span::new(0, 0),
+    }
+}
+
+fn lambda(param: &str, body: Expr) -> Expr {
+    Expr::Lambda {
+        params: vec![(Ident(param.to_string()), None)],
+        body: Box::new(body),
+        span: Span::new(0, 0),
+    }
+}
+
+fn int_to_int() -> Type {
+    Type::Function(Box::new(Type::Int), Box::new(Type::Int))
+}
+
+/// `main n = square (abs n) + offset`, plus an unused `cube`
+fn codebase() -> Codebase {
+    let mut codebase = Codebase::new();
+    codebase
+        .add_term(Some("offset".to_string()), int(1), Type::Int)
+        .unwrap();
+    codebase
+        .add_term(
+            Some("square".to_string()),
+            lambda("x", call("*", vec![var("x"), var("x")])),
+            int_to_int(),
+        )
+        .unwrap();
+    codebase
+        .add_term(
+            Some("cube".to_string()),
+            lambda(
+                "x",
+                call("*", vec![var("x"), call("square", vec![var("x")])]),
+            ),
+            int_to_int(),
+        )
+        .unwrap();
+    codebase
+        .add_term(
+            Some("main".to_string()),
+            lambda(
+                "n",
+                call(
+                    "+",
+                    vec![
+                        call("square", vec![call("abs", vec![var("n")])]),
+                        var("offset"),
+                    ],
+                ),
+            ),
+            int_to_int(),
+        )
+        .unwrap();
+    codebase
+}
+
+#[test]
+fn test_build_core_module_runs_and_reports_sizes() {
+    let build = build_entry(&codebase(), "main", false).unwrap();
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, &build.bytes).unwrap();
+    let exports: Vec<&str> = module.exports().map(|e| e.name()).collect();
+    assert!(exports.contains(&"main"));
+    assert!(exports.contains(&"abs"));
+    assert!(!exports.contains(&"cube"));
+    assert!(!exports.contains(&"min"));
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let main = instance
+        .get_typed_func::<i64, i64>(&mut store, "main")
+        .unwrap();
+    assert_eq!(main.call(&mut store, -4).unwrap(), 17);
+
+    let report = format_report(std::path::Path::new("main.wasm"), &build);
+    assert!(
+        report.starts_with(&format!(
+            "Built main.wasm (core module, {} bytes)",
+            build.bytes.len()
+        )),
+        "{report}"
+    );
+    for label in [
+        "main",
+        "square",
+        "offset",
+        "abs (builtin)",
+        "(headers, types, exports)",
+    ] {
+        assert!(report.contains(&format!("  {label}")), "{report}");
+    }
+    assert!(!report.contains("cube"), "{report}");
+}
+
+#[test]
+fn test_build_component_from_stored_codebase() {
+    let dir = TempDir::new().unwrap();
+    let codebase_path = dir.path().join("codebase.vibes");
+    VBinStorage::new(codebase_path.to_string_lossy().to_string())
+        .save_full(&codebase())
+        .unwrap();
+
+    let output = dir.path().join("square.wasm");
+    run_build("square", "wasm", &codebase_path, Some(output.clone()), true).unwrap();
+    let bytes = std::fs::read(&output).unwrap();
+    wasmtime::component::Component::new(&Engine::default(), &bytes).unwrap();
+
+    let error = run_build("square", "js", &codebase_path, None, false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Unsupported build target `js` (supported: wasm)"
+    );
+    let error = run_build("missing", "wasm", &codebase_path, None, false).unwrap_err();
+    assert_eq!(error.to_string(), "Definition not found: missing");
+}
+
+#[test]
+fn test_unsupported_builtins_are_named() {
+    let mut codebase = codebase();
+    codebase
+        .add_term(
+            Some("negated".to_string()),
+            lambda("x", call("negate", vec![var("x")])),
+            int_to_int(),
+        )
+        .unwrap();
+    let error = build_entry(&codebase, "negated", false).unwrap_err();
+    assert_eq!(
+        format!("{error:#}"),
+        "Failed to compile negated to WebAssembly: `negate` is neither a definition nor a \
+         builtin WebAssembly builds support; besides arithmetic and comparison operators, \
+         the only builtins are abs, min, max"
+    );
+}
@@ -0,0 +1,132 @@
+//! Workspaces: shared lockfile, incremental checks and builds in
+//! dependency order
+
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use vibe_cli::package_commands::{add_package, PackageContext};
+use vibe_cli::workspace_commands::{build_workspace, check_workspace, workspace_at};
+use vibe_codebase::package::hash::calculate_package_hash;
+use vibe_codebase::package::lockfile::Lockfile;
+use vibe_codebase::package::manifest::PackageManifest;
+use vibe_codebase::package::registry::LocalRegistry;
+
+fn member(root: &Path, name: &str, deps: &[&str], lib: &str, main: Option<&str>) {
+    let dir = root.join(name);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let mut manifest = PackageManifest::new(name.to_string());
+    for dep in deps {
+        manifest.add_path_dependency(dep.to_string(), format!("../{dep}"));
+    }
+    if let Some(main) = main {
+        manifest.entry.main = Some("src/main.vibe".to_string());
+        fs::write(dir.join("src/main.vibe"), main).unwrap();
+    }
+    manifest.save_to_file(&dir.join("package.vibe")).unwrap();
+    fs::write(dir.join("src/lib.vibe"), lib).unwrap();
+}
+
+/// core <- text <- app, and tool on its own
+fn workspace(root: &Path) {
+    fs::create_dir_all(root).unwrap();
+    PackageManifest::workspace(vec![
+        "app".to_string(),
+        "core".to_string(),
+        "text".to_string(),
+        "tool".to_string(),
+    ])
+    .save_to_file(&root.join("package.vibe"))
+    .unwrap();
+    member(root, "core", &[], "let base = 40", None);
+    member(root, "text", &["core"], "let offset = 2", None);
+    member(
+        root,
+        "app",
+        &["text"],
+        "let unused = 0",
+        Some("let main = 42"),
+    );
+    member(root, "tool", &[], "let flag = true", None);
+}
+
+#[test]
+fn test_check_only_rechecks_dependents_of_changes() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("ws");
+    workspace(&root);
+    let ws = workspace_at(&root).unwrap().unwrap();
+    let order: Vec<&str> = ws.members.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(order, vec!["core", "tool", "text", "app"]);
+
+    let report = check_workspace(&ws, false).unwrap();
+    assert_eq!(report.checked, vec!["core", "tool", "text", "app"]);
+    assert!(report.failed.is_empty());
+
+    let report = check_workspace(&ws, false).unwrap();
+    assert!(report.checked.is_empty());
+    assert_eq!(report.up_to_date.len(), 4);
+
+    fs::write(root.join("text/src/lib.vibe"), "let offset = 3").unwrap();
+    let report = check_workspace(&ws, false).unwrap();
+    assert_eq!(report.checked, vec!["text", "app"]);
+    assert_eq!(report.up_to_date, vec!["core", "tool"]);
+
+    // A failing member stays stale until it is fixed
+    fs::write(root.join("tool/src/lib.vibe"), "let flag = missing").unwrap();
+    let report = check_workspace(&ws, false).unwrap();
+    assert_eq!(report.failed, vec!["tool"]);
+    let report = check_workspace(&ws, false).unwrap();
+    assert_eq!(report.failed, vec!["tool"]);
+}
+
+#[test]
+fn test_members_share_the_root_lockfile() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("ws");
+    workspace(&root);
+
+    let package_dir = dir.path().join("math");
+    fs::create_dir_all(package_dir.join("src")).unwrap();
+    let mut manifest = PackageManifest::new("math".to_string());
+    manifest.package.version = Some("1.0.0".to_string());
+    manifest
+        .save_to_file(&package_dir.join("package.vibe"))
+        .unwrap();
+    fs::write(package_dir.join("src/lib.vibe"), "let pi = 3").unwrap();
+    let hash = calculate_package_hash(&package_dir).unwrap();
+    LocalRegistry::new(dir.path().join("registry"))
+        .unwrap()
+        .publish(&manifest, &hash, &package_dir)
+        .unwrap();
+
+    let ctx = PackageContext {
+        project_dir: root.join("text"),
+        cache_dir: dir.path().join("cache"),
+        registry_dir: dir.path().join("registry"),
+    };
+    add_package(&ctx, "math", false).unwrap();
+
+    assert!(!root.join("text/package.lock").exists());
+    let lockfile = Lockfile::load(&root.join("package.lock")).unwrap().unwrap();
+    let names: Vec<&str> = lockfile.packages.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["math"]);
+
+    let text = fs::read_to_string(root.join("text/package.vibe")).unwrap();
+    assert!(text.contains("core: { path: \"../core\" },"), "{text}");
+    assert!(text.contains("math: \"^1.0.0\","), "{text}");
+}
+
+#[test]
+fn test_build_includes_member_dependencies() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("ws");
+    workspace(&root);
+    // Definitions of the members app depends on are linked in
+    fs::write(root.join("app/src/main.vibe"), "let main = offset").unwrap();
+    let ws = workspace_at(&root).unwrap().unwrap();
+
+    let built = build_workspace(&ws, &dir.path().join("out")).unwrap();
+    assert_eq!(built, vec![dir.path().join("out/app.wasm")]);
+    let bytes = fs::read(&built[0]).unwrap();
+    assert_eq!(&bytes[..4], b"\0asm");
+}
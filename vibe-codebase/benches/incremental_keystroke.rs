@@ -0,0 +1,79 @@
+//! Keystroke latency of incremental checking on a 5k-line module
+//!
+//! Simulates typing into one definition a quarter of the way into a large
+//! file, with every later definition depending on it, and measures the time to
+//! get fresh diagnostics, compared with re-parsing and re-checking the whole
+//! file.
+//!
+//! Run with `cargo bench -p vibe-codebase --bench incremental_keystroke`.
+//! The parser logs to stderr, so redirect it to keep the report readable.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vibe_codebase::database::XsDatabaseImpl;
+use vibe_codebase::{CodebaseQueries, ModuleId};
+
+const LINES: usize = 5_000;
+const KEYSTROKES: usize = 50;
+
+/// Line of the definition the benchmark types into, a quarter of the way in
+const EDITED_LINE: usize = LINES / 4;
+
+/// A module of `LINES` one-line definitions, each depending on the one before
+/// it, so an edit at `EDITED_LINE` has every later definition as a dependent
+fn module_source(edited: usize) -> String {
+    let mut lines = vec!["let v0 = 0".to_string()];
+    for i in 1..LINES {
+        let value = if i == EDITED_LINE { edited } else { i };
+        lines.push(format!("let v{i} = v{} + {value}", i - 1));
+    }
+    lines.join("\n")
+}
+
+fn report(label: &str, mut samples: Vec<Duration>) {
+    samples.sort();
+    let median = samples[samples.len() / 2];
+    let p95 = samples[samples.len() * 95 / 100];
+    let max = samples[samples.len() - 1];
+    println!("{label:<28} median {median:>10.2?}  p95 {p95:>10.2?}  max {max:>10.2?}");
+}
+
+fn main() {
+    let module = ModuleId("bench.vibe".to_string());
+    let mut db = XsDatabaseImpl::new();
+
+    let start = Instant::now();
+    db.set_module_text(module.clone(), Arc::new(module_source(0)));
+    let diagnostics = db.module_diagnostics(module.clone());
+    println!(
+        "initial check of {LINES} lines: {:.2?} ({} diagnostics)",
+        start.elapsed(),
+        diagnostics.len()
+    );
+
+    // Each keystroke changes the literal on `EDITED_LINE`
+    let mut incremental = Vec::with_capacity(KEYSTROKES);
+    for keystroke in 1..=KEYSTROKES {
+        let source = Arc::new(module_source(keystroke));
+        let start = Instant::now();
+        db.set_module_text(module.clone(), source);
+        let diagnostics = db.module_diagnostics(module.clone());
+        let _expr = db.module_expr(module.clone());
+        incremental.push(start.elapsed());
+        assert!(diagnostics.is_empty());
+    }
+    report("incremental keystroke", incremental);
+
+    // Baseline: what every keystroke used to cost
+    let mut full = Vec::new();
+    for keystroke in 1..=5 {
+        let source = module_source(keystroke);
+        let start = Instant::now();
+        let parsed = vibe_language::parser::parse_with_recovery(&source);
+        if let Some(expr) = parsed.into_expr() {
+            let _ = vibe_compiler::type_check_all(&expr);
+        }
+        full.push(start.elapsed());
+    }
+    report("full reparse and check", full);
+}
@@ -300,6 +300,34 @@ impl CodeRepository {
         Ok(())
     }
 
+    /// 現在のセッションで評価された定義の名前（最初に評価された順）
+    pub fn session_definitions(&self) -> Result<Vec<String>, String> {
+        let session_id = self
+            .current_session_id
+            .ok_or_else(|| "No active session".to_string())?;
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                r#"
+                SELECT d.name FROM evaluations e
+                JOIN definitions d ON d.hash = e.result_hash
+                WHERE e.session_id = ?1 AND d.name IS NOT NULL
+                GROUP BY d.name
+                ORDER BY MIN(e.id)
+                "#,
+            )
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+        let names = stmt
+            .query_map(params![session_id], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Failed to query evaluations: {}", e))?;
+
+        names
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect names: {}", e))
+    }
+
     /// トップレベル名前空間からの到達可能性分析
     pub fn analyze_reachability(
         &self,
@@ -764,6 +792,13 @@ mod tests {
 
         // Verify session is set
         assert_eq!(repo.current_session_id, Some(session_id));
+        assert_eq!(repo.session_definitions().unwrap(), vec!["test_expr"]);
+
+        // Definitions evaluated in an earlier session are not listed
+        repo.end_session().unwrap();
+        repo.start_session().unwrap();
+        repo.record_evaluation("1", None, "1").unwrap();
+        assert!(repo.session_definitions().unwrap().is_empty());
     }
 
     #[test]
@@ -97,6 +97,12 @@ pub enum CodebaseError {
     #[error("Circular dependency detected")]
     CircularDependency,
 
+    #[error("Branch already exists: {0}")]
+    BranchExists(String),
+
+    #[error("Branch not found: {0}")]
+    BranchNotFound(String),
+
     #[error("Parse error: {0}")]
     ParseError(String),
 
@@ -198,6 +204,45 @@ impl Codebase {
         Ok(result)
     }
 
+    /// Order terms so that each one comes after the terms it depends on.
+    /// Terms without a dependency between them keep the order of `hashes`,
+    /// and dependencies outside `hashes` are ignored.
+    pub fn topological_order(&self, hashes: &[Hash]) -> Result<Vec<Hash>, CodebaseError> {
+        let mut ordered = Vec::with_capacity(hashes.len());
+        let mut done = HashSet::new();
+        let mut visiting = HashSet::new();
+        for hash in hashes {
+            self.visit_in_order(hash, hashes, &mut visiting, &mut done, &mut ordered)?;
+        }
+        Ok(ordered)
+    }
+
+    fn visit_in_order(
+        &self,
+        hash: &Hash,
+        hashes: &[Hash],
+        visiting: &mut HashSet<Hash>,
+        done: &mut HashSet<Hash>,
+        ordered: &mut Vec<Hash>,
+    ) -> Result<(), CodebaseError> {
+        if done.contains(hash) {
+            return Ok(());
+        }
+        if !visiting.insert(hash.clone()) {
+            return Err(CodebaseError::CircularDependency);
+        }
+
+        let deps = self.get_direct_dependencies(hash);
+        for dep in hashes.iter().filter(|h| *h != hash && deps.contains(h)) {
+            self.visit_in_order(dep, hashes, visiting, done, ordered)?;
+        }
+
+        visiting.remove(hash);
+        done.insert(hash.clone());
+        ordered.push(hash.clone());
+        Ok(())
+    }
+
     /// Edit a term (UCM-style edit command)
     /// Returns the expression with all dependencies expanded inline
     pub fn edit(&self, name: &str) -> Result<String, CodebaseError> {
@@ -339,6 +384,18 @@ impl Codebase {
             Expr::Let { value, .. } | Expr::LetRec { value, .. } => {
                 self.extract_deps_recursive(value, deps);
             }
+            Expr::LetIn { value, body, .. } | Expr::LetRecIn { value, body, .. } => {
+                self.extract_deps_recursive(value, deps);
+                self.extract_deps_recursive(body, deps);
+            }
+            Expr::Rec { body, .. } => {
+                self.extract_deps_recursive(body, deps);
+            }
+            Expr::Block { exprs, .. } => {
+                for e in exprs {
+                    self.extract_deps_recursive(e, deps);
+                }
+            }
             Expr::If {
                 cond,
                 then_expr,
@@ -447,20 +504,66 @@ impl EditSession {
 pub struct CodebaseManager {
     codebase: Codebase,
     branches: HashMap<String, Branch>,
-    #[allow(dead_code)]
     storage_path: std::path::PathBuf,
 }
 
 impl CodebaseManager {
+    /// Open the codebase stored at `storage_path`, loading any branches
+    /// saved there by `save_branches`
     pub fn new(storage_path: std::path::PathBuf) -> Result<Self, CodebaseError> {
         std::fs::create_dir_all(&storage_path)?;
+        let branches_path = Self::branches_path(&storage_path);
+        let branches = if branches_path.exists() {
+            bincode::deserialize(&std::fs::read(&branches_path)?)?
+        } else {
+            HashMap::new()
+        };
         Ok(Self {
             codebase: Codebase::new(),
-            branches: HashMap::new(),
+            branches,
             storage_path,
         })
     }
 
+    fn branches_path(storage_path: &Path) -> std::path::PathBuf {
+        storage_path.join("branches.bin")
+    }
+
+    /// Persist all branches next to the codebase storage
+    pub fn save_branches(&self) -> Result<(), CodebaseError> {
+        let data = bincode::serialize(&self.branches)?;
+        std::fs::write(Self::branches_path(&self.storage_path), data)?;
+        Ok(())
+    }
+
+    /// All branches sorted by name
+    pub fn branches(&self) -> Vec<&Branch> {
+        let mut branches: Vec<_> = self.branches.values().collect();
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        branches
+    }
+
+    /// Create `name` as a copy of the branch `from`
+    pub fn fork_branch(&mut self, from: &str, name: String) -> Result<&Branch, CodebaseError> {
+        if self.branches.contains_key(&name) {
+            return Err(CodebaseError::BranchExists(name));
+        }
+        let mut branch = self.get_branch(from)?.clone();
+        branch.name = name.clone();
+        self.branches.insert(name.clone(), branch);
+        self.get_branch(&name)
+    }
+
+    /// Point a branch at a new head
+    pub fn set_branch_hash(&mut self, name: &str, hash: String) -> Result<(), CodebaseError> {
+        let branch = self
+            .branches
+            .get_mut(name)
+            .ok_or_else(|| CodebaseError::BranchNotFound(name.to_string()))?;
+        branch.hash = hash;
+        Ok(())
+    }
+
     pub fn create_branch(&mut self, name: String) -> Result<&Branch, CodebaseError> {
         let branch = Branch {
             name: name.clone(),
@@ -470,13 +573,13 @@ impl CodebaseManager {
         self.branches.insert(name.clone(), branch);
         self.branches
             .get(&name)
-            .ok_or(CodebaseError::TermNotFound(name))
+            .ok_or(CodebaseError::BranchNotFound(name))
     }
 
     pub fn get_branch(&self, name: &str) -> Result<&Branch, CodebaseError> {
         self.branches
             .get(name)
-            .ok_or_else(|| CodebaseError::TermNotFound(name.to_string()))
+            .ok_or_else(|| CodebaseError::BranchNotFound(name.to_string()))
     }
 
     pub fn hash_expr(&self, expr: &Expr) -> String {
@@ -960,4 +1063,64 @@ mod tests {
         let deps = codebase.extract_dependencies(&match_expr);
         assert_eq!(deps.len(), 1);
     }
+
+    #[test]
+    fn test_topological_order() {
+        let mut codebase = Codebase::new();
+        let ident = |name: &str| Expr::Ident(Ident(name.to_string()), vibe_language::Span::new(0, 1));
+        let base = codebase
+            .add_term(
+                Some("base".to_string()),
+                Expr::Literal(vibe_language::Literal::Int(1), vibe_language::Span::new(0, 1)),
+                Type::Int,
+            )
+            .unwrap();
+        let double = codebase
+            .add_term(Some("double".to_string()), ident("base"), Type::Int)
+            .unwrap();
+        let total = codebase
+            .add_term(
+                Some("total".to_string()),
+                Expr::Rec {
+                    name: Ident("total".to_string()),
+                    params: vec![],
+                    return_type: None,
+                    body: Box::new(ident("double")),
+                    span: vibe_language::Span::new(0, 1),
+                },
+                Type::Int,
+            )
+            .unwrap();
+
+        let deps = codebase.get_all_dependencies(&total).unwrap();
+        assert_eq!(deps.len(), 2);
+
+        let order = codebase
+            .topological_order(&[total.clone(), double.clone(), base.clone()])
+            .unwrap();
+        assert_eq!(order, vec![base, double, total]);
+    }
+
+    #[test]
+    fn test_branches_persist() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut manager = CodebaseManager::new(temp_dir.path().to_path_buf()).unwrap();
+        manager.create_branch("main".to_string()).unwrap();
+        manager.set_branch_hash("main", "abc".to_string()).unwrap();
+        manager.fork_branch("main", "feature".to_string()).unwrap();
+        assert!(matches!(
+            manager.fork_branch("main", "feature".to_string()),
+            Err(CodebaseError::BranchExists(_))
+        ));
+        assert!(matches!(
+            manager.set_branch_hash("missing", "abc".to_string()),
+            Err(CodebaseError::BranchNotFound(_))
+        ));
+        manager.save_branches().unwrap();
+
+        let reloaded = CodebaseManager::new(temp_dir.path().to_path_buf()).unwrap();
+        let names: Vec<_> = reloaded.branches().iter().map(|b| b.name.clone()).collect();
+        assert_eq!(names, vec!["feature", "main"]);
+        assert_eq!(reloaded.get_branch("feature").unwrap().hash, "abc");
+    }
 }
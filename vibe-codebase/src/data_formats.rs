@@ -0,0 +1,380 @@
+//! JSON, CSV and TOML for pipelines
+//!
+//! `from json`, `from csv` and `from toml` turn text into structured data and
+//! `to json`/`to csv`/`to toml` render it back. JSON arrays of objects and
+//! CSV files become tables; TOML documents become records.
+
+use crate::structured_data::{StructuredData, StructuredValue};
+use std::collections::HashMap;
+use vibe_language::{Span, XsError};
+
+/// A text format structured data can be read from and written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Json,
+    Csv,
+    Toml,
+}
+
+impl DataFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(DataFormat::Json),
+            "csv" => Some(DataFormat::Csv),
+            "toml" => Some(DataFormat::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DataFormat::Json => "json",
+            DataFormat::Csv => "csv",
+            DataFormat::Toml => "toml",
+        }
+    }
+
+    /// Parse `text` in this format
+    pub fn parse(self, text: &str) -> Result<StructuredData, XsError> {
+        match self {
+            DataFormat::Json => {
+                let json: serde_json::Value =
+                    serde_json::from_str(text).map_err(|e| format_error(self, e.to_string()))?;
+                Ok(StructuredData::from_structured(from_json(json)))
+            }
+            DataFormat::Csv => parse_csv(text),
+            DataFormat::Toml => {
+                let table: toml::Table =
+                    toml::from_str(text).map_err(|e| format_error(self, e.to_string()))?;
+                Ok(StructuredData::from_structured(from_toml(
+                    toml::Value::Table(table),
+                )))
+            }
+        }
+    }
+
+    /// Render `data` in this format
+    pub fn render(self, data: &StructuredData) -> Result<String, XsError> {
+        match self {
+            DataFormat::Json => serde_json::to_string_pretty(&to_json(&data.to_structured()?))
+                .map_err(|e| format_error(self, e.to_string())),
+            DataFormat::Csv => render_csv(data),
+            DataFormat::Toml => match to_toml(&data.to_structured()?) {
+                Some(toml::Value::Table(table)) => {
+                    toml::to_string(&table).map_err(|e| format_error(self, e.to_string()))
+                }
+                _ => Err(format_error(
+                    self,
+                    "a TOML document is a record of values".to_string(),
+                )),
+            },
+        }
+    }
+}
+
+fn format_error(format: DataFormat, message: String) -> XsError {
+    XsError::RuntimeError(
+        Span::new(0, 0),
+        format!("invalid {}: {message}", format.name().to_uppercase()),
+    )
+}
+
+fn from_json(json: serde_json::Value) -> StructuredValue {
+    match json {
+        serde_json::Value::Null => StructuredValue::Null,
+        serde_json::Value::Bool(b) => StructuredValue::Bool(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => StructuredValue::Int(i),
+            None => StructuredValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => StructuredValue::String(s),
+        serde_json::Value::Array(items) => {
+            StructuredValue::List(items.into_iter().map(from_json).collect())
+        }
+        serde_json::Value::Object(map) => StructuredValue::Record(
+            map.into_iter()
+                .map(|(key, value)| (key, from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_json(value: &StructuredValue) -> serde_json::Value {
+    match value {
+        StructuredValue::String(s) => serde_json::Value::String(s.clone()),
+        StructuredValue::Int(n) => serde_json::Value::from(*n),
+        StructuredValue::Float(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        StructuredValue::Bool(b) => serde_json::Value::Bool(*b),
+        StructuredValue::DateTime(dt) => serde_json::Value::String(dt.to_rfc3339()),
+        StructuredValue::List(items) => {
+            serde_json::Value::Array(items.iter().map(to_json).collect())
+        }
+        StructuredValue::Record(fields) => serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), to_json(value)))
+                .collect(),
+        ),
+        StructuredValue::Null => serde_json::Value::Null,
+    }
+}
+
+fn from_toml(value: toml::Value) -> StructuredValue {
+    match value {
+        toml::Value::String(s) => StructuredValue::String(s),
+        toml::Value::Integer(n) => StructuredValue::Int(n),
+        toml::Value::Float(f) => StructuredValue::Float(f),
+        toml::Value::Boolean(b) => StructuredValue::Bool(b),
+        toml::Value::Datetime(dt) => {
+            let text = dt.to_string();
+            match chrono::DateTime::parse_from_rfc3339(&text) {
+                Ok(dt) => StructuredValue::DateTime(dt.with_timezone(&chrono::Utc)),
+                // Local dates and times have no offset
+                Err(_) => StructuredValue::String(text),
+            }
+        }
+        toml::Value::Array(items) => {
+            StructuredValue::List(items.into_iter().map(from_toml).collect())
+        }
+        toml::Value::Table(table) => StructuredValue::Record(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// TOML has no null; null values and fields are left out
+fn to_toml(value: &StructuredValue) -> Option<toml::Value> {
+    Some(match value {
+        StructuredValue::String(s) => toml::Value::String(s.clone()),
+        StructuredValue::Int(n) => toml::Value::Integer(*n),
+        StructuredValue::Float(f) => toml::Value::Float(*f),
+        StructuredValue::Bool(b) => toml::Value::Boolean(*b),
+        StructuredValue::DateTime(dt) => match dt.to_rfc3339().parse() {
+            Ok(dt) => toml::Value::Datetime(dt),
+            Err(_) => toml::Value::String(dt.to_rfc3339()),
+        },
+        StructuredValue::List(items) => {
+            toml::Value::Array(items.iter().filter_map(to_toml).collect())
+        }
+        StructuredValue::Record(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            toml::Value::Table(
+                names
+                    .into_iter()
+                    .filter_map(|name| Some((name.clone(), to_toml(&fields[name])?)))
+                    .collect(),
+            )
+        }
+        StructuredValue::Null => return None,
+    })
+}
+
+/// Parse CSV with a header row. Unquoted cells that look like numbers or
+/// booleans are typed; empty unquoted cells are null.
+fn parse_csv(text: &str) -> Result<StructuredData, XsError> {
+    let mut records = csv_records(text)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(StructuredData::Table {
+            columns: Vec::new(),
+            rows: Vec::new(),
+        });
+    };
+    let columns: Vec<String> = header.into_iter().map(|(cell, _)| cell).collect();
+
+    let mut rows = Vec::new();
+    for (index, record) in records.enumerate() {
+        if record.len() > columns.len() {
+            return Err(format_error(
+                DataFormat::Csv,
+                format!(
+                    "row {} has {} cells but there are {} columns",
+                    index + 1,
+                    record.len(),
+                    columns.len()
+                ),
+            ));
+        }
+        let row: HashMap<String, StructuredValue> = columns
+            .iter()
+            .zip(record)
+            .map(|(column, (cell, quoted))| (column.clone(), csv_cell(cell, quoted)))
+            .collect();
+        rows.push(row);
+    }
+    Ok(StructuredData::Table { columns, rows })
+}
+
+fn csv_cell(cell: String, quoted: bool) -> StructuredValue {
+    if quoted {
+        return StructuredValue::String(cell);
+    }
+    if cell.is_empty() {
+        StructuredValue::Null
+    } else if let Ok(n) = cell.parse::<i64>() {
+        StructuredValue::Int(n)
+    } else if let Ok(f) = cell.parse::<f64>() {
+        StructuredValue::Float(f)
+    } else if let Ok(b) = cell.parse::<bool>() {
+        StructuredValue::Bool(b)
+    } else {
+        StructuredValue::String(cell)
+    }
+}
+
+/// Split CSV text into records of cells, each with whether it was quoted
+fn csv_records(text: &str) -> Result<Vec<Vec<(String, bool)>>, XsError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if cell.is_empty() && !quoted => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            cell.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => cell.push(c),
+                        None => {
+                            return Err(format_error(
+                                DataFormat::Csv,
+                                format!("unterminated quoted cell in row {}", records.len()),
+                            ))
+                        }
+                    }
+                }
+            }
+            ',' => record.push((std::mem::take(&mut cell), std::mem::take(&mut quoted))),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push((std::mem::take(&mut cell), std::mem::take(&mut quoted)));
+                records.push(std::mem::take(&mut record));
+            }
+            c => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || quoted || !record.is_empty() {
+        record.push((cell, quoted));
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn render_csv(data: &StructuredData) -> Result<String, XsError> {
+    let table = match data {
+        StructuredData::Table { .. } => data.clone(),
+        StructuredData::Definition(_) | StructuredData::Definitions(_) => data.to_table()?,
+        other => StructuredData::from_structured(other.to_structured()?),
+    };
+    let StructuredData::Table { columns, rows } = table else {
+        return Err(format_error(
+            DataFormat::Csv,
+            "only tables can be written as CSV".to_string(),
+        ));
+    };
+
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|c| csv_escape(c)).collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+    for row in &rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| match row.get(column) {
+                None | Some(StructuredValue::Null) => String::new(),
+                Some(StructuredValue::String(s)) => csv_escape(s),
+                Some(StructuredValue::Int(n)) => n.to_string(),
+                Some(StructuredValue::Float(f)) => f.to_string(),
+                Some(StructuredValue::Bool(b)) => b.to_string(),
+                Some(StructuredValue::DateTime(dt)) => dt.to_rfc3339(),
+                Some(nested) => csv_escape(&to_json(nested).to_string()),
+            })
+            .collect();
+        out.push_str(&cells.join(","));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_round_trip_with_typed_cells() {
+        let text = "name,age,note\nada,36,\"likes \"\"math\"\", logic\"\nbob,,\"42\"\n";
+        let data = DataFormat::Csv.parse(text).unwrap();
+        let StructuredData::Table { columns, rows } = &data else {
+            panic!("{data:?}");
+        };
+        assert_eq!(columns, &vec!["name", "age", "note"]);
+        assert_eq!(rows[0]["age"].as_int(), Some(36));
+        assert_eq!(rows[0]["note"].as_string(), Some("likes \"math\", logic"));
+        assert!(matches!(rows[1]["age"], StructuredValue::Null));
+        // Quoted cells stay strings
+        assert_eq!(rows[1]["note"].as_string(), Some("42"));
+
+        assert_eq!(
+            DataFormat::Csv.render(&data).unwrap(),
+            "name,age,note\nada,36,\"likes \"\"math\"\", logic\"\nbob,,42\n"
+        );
+        assert!(DataFormat::Csv.parse("a\n\"open").is_err());
+    }
+
+    #[test]
+    fn test_json_arrays_of_objects_are_tables() {
+        let data = DataFormat::Json
+            .parse(r#"[{"name": "ada", "age": 36}, {"name": "bob", "age": 25.5, "admin": true}]"#)
+            .unwrap();
+        let StructuredData::Table { columns, rows } = &data else {
+            panic!("{data:?}");
+        };
+        assert_eq!(columns, &vec!["age", "name", "admin"]);
+        assert!(matches!(rows[1]["age"], StructuredValue::Float(_)));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&DataFormat::Json.render(&data).unwrap()).unwrap();
+        assert_eq!(json[0]["name"], "ada");
+        assert_eq!(json[1]["admin"], true);
+    }
+
+    #[test]
+    fn test_toml_documents_are_records() {
+        let text = "title = \"demo\"\n\n[owner]\nname = \"ada\"\nborn = 1815-12-10T00:00:00Z\n";
+        let data = DataFormat::Toml.parse(text).unwrap();
+        let StructuredData::Record(fields) = &data else {
+            panic!("{data:?}");
+        };
+        assert_eq!(fields["title"].as_string(), Some("demo"));
+        let StructuredValue::Record(owner) = &fields["owner"] else {
+            panic!("{fields:?}");
+        };
+        assert!(matches!(owner["born"], StructuredValue::DateTime(_)));
+
+        let rendered = DataFormat::Toml.render(&data).unwrap();
+        assert!(rendered.contains("[owner]"), "{rendered}");
+        assert!(DataFormat::Toml
+            .render(&StructuredData::List(vec![]))
+            .is_err());
+    }
+}
@@ -3,10 +3,12 @@
 //! This module defines the Salsa database structure for efficiently
 //! tracking dependencies and recomputing only what's changed.
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-// use std::collections::HashMap;
-use vibe_compiler::{TypeChecker, TypeEnv};
-use vibe_language::{Expr, Type, XsError};
+use vibe_compiler::{TypeChecker, TypeDiagnostic, TypeEnv, TypeScheme};
+use vibe_language::parser::lexer::{Lexer, Token};
+use vibe_language::parser::recovery::{parse_with_recovery, shift_spans, split_top_level};
+use vibe_language::{Expr, Span, Type, TypeDefinition, XsError};
 
 /// Source program input for the database
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -38,6 +40,71 @@ pub struct Definition {
     pub ty: Type,
 }
 
+/// A top-level item of a module: a definition or a bare expression.
+///
+/// Items are named after the first name they define (bare expressions are
+/// `<expr>`), with `#n` appended to repeated names, so their identity survives
+/// edits to other parts of the file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemId {
+    pub module: ModuleId,
+    pub name: String,
+}
+
+/// Position and text of a top-level item in the module source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutItem {
+    pub id: ItemId,
+    /// Character span of the item in the module source
+    pub span: Span,
+    pub text: Arc<String>,
+}
+
+/// Syntax error in an item, relative to wherever the item's text starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxDiagnostic {
+    pub span: Span,
+    pub message: String,
+    pub code: String,
+}
+
+/// Result of parsing the text of a single item. Spans are relative to the item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedItem {
+    pub exprs: Vec<Expr>,
+    pub errors: Vec<SyntaxDiagnostic>,
+    /// Names the item binds at the top level, in definition order
+    pub defines: Vec<String>,
+}
+
+/// Result of type checking a single item against its dependencies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedItem {
+    /// Type of the item's (last) expression
+    pub ty: Option<Type>,
+    /// Generalized types of the names the item defines: (name, quantified vars, type)
+    pub bindings: Vec<(String, Vec<String>, Type)>,
+    pub type_definitions: Vec<TypeDefinition>,
+    /// Type errors, with spans relative to the item
+    pub diagnostics: Vec<TypeDiagnostic>,
+}
+
+/// Diagnostic for a whole module, with spans relative to the module source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleDiagnostic {
+    Syntax(SyntaxDiagnostic),
+    Type(TypeDiagnostic),
+}
+
+impl ModuleDiagnostic {
+    pub fn span(&self) -> &Span {
+        match self {
+            ModuleDiagnostic::Syntax(d) => &d.span,
+            ModuleDiagnostic::Type(d) => &d.span,
+        }
+    }
+}
+
 /// XS language database
 #[salsa::query_group(CompilerQueriesStorage)]
 pub trait CompilerQueries {
@@ -54,7 +121,7 @@ pub trait CompilerQueries {
 
 /// Dependency tracking queries
 #[salsa::query_group(DependencyQueriesStorage)]
-pub trait DependencyQueries {
+pub trait DependencyQueries: CodebaseQueries {
     /// Get module dependencies
     fn module_dependencies(&self, module: ModuleId) -> Arc<Dependencies>;
 
@@ -74,6 +141,37 @@ pub trait CodebaseQueries {
 
     /// Get all definitions in a module
     fn module_definitions(&self, module: ModuleId) -> Arc<Vec<Definition>>;
+
+    /// Full source text of a module
+    #[salsa::input]
+    fn module_text(&self, module: ModuleId) -> Arc<String>;
+
+    /// Split a module into top-level items
+    fn module_layout(&self, module: ModuleId) -> Arc<Vec<LayoutItem>>;
+
+    /// Items of a module in order with the names each defines, without positions
+    fn module_names(&self, module: ModuleId) -> Arc<Vec<(ItemId, Vec<String>)>>;
+
+    /// Text of every item in a module, by item name
+    fn module_item_texts(&self, module: ModuleId) -> Arc<HashMap<String, Arc<String>>>;
+
+    /// Text of a single item
+    fn item_source(&self, item: ItemId) -> Arc<String>;
+
+    /// Parse the text of an item. Keyed by text, so unchanged items are never reparsed.
+    fn parse_item(&self, text: Arc<String>) -> Arc<ParsedItem>;
+
+    /// Items whose definitions an item refers to
+    fn item_dependencies(&self, item: ItemId) -> Arc<Vec<ItemId>>;
+
+    /// Type check one item against the types of its dependencies
+    fn check_item(&self, item: ItemId) -> Arc<CheckedItem>;
+
+    /// Syntax and type errors of a module, in source order
+    fn module_diagnostics(&self, module: ModuleId) -> Arc<Vec<ModuleDiagnostic>>;
+
+    /// The module as a single block expression with spans relative to its source
+    fn module_expr(&self, module: ModuleId) -> Arc<Expr>;
 }
 
 /// Combined database trait
@@ -91,8 +189,8 @@ fn type_check(db: &dyn CompilerQueries, key: SourcePrograms) -> Result<Type, XsE
     let mut checker = TypeChecker::new();
     let mut env = TypeEnv::new();
     checker
-        .check(&expr, &mut env)
-        .map_err(|e| XsError::TypeError(vibe_language::Span::new(0, 0), e))
+        .check_spanned(&expr, &mut env)
+        .map_err(|d| d.to_xs_error())
 }
 
 /// Implement expression_type query
@@ -101,17 +199,30 @@ fn expression_type(db: &dyn CodebaseQueries, id: ExpressionId) -> Result<Type, X
     let mut checker = TypeChecker::new();
     let mut env = TypeEnv::new();
     checker
-        .check(&expr, &mut env)
-        .map_err(|e| XsError::TypeError(vibe_language::Span::new(0, 0), e))
+        .check_spanned(&expr, &mut env)
+        .map_err(|d| d.to_xs_error())
 }
 
 /// Implement module_dependencies query
-fn module_dependencies(_db: &dyn DependencyQueries, _module: ModuleId) -> Arc<Dependencies> {
-    // TODO: Implement actual dependency extraction
-    Arc::new(Dependencies {
-        imports: vec![],
-        exports: vec![],
-    })
+fn module_dependencies(db: &dyn DependencyQueries, module: ModuleId) -> Arc<Dependencies> {
+    let mut imports = Vec::new();
+    let mut exports = Vec::new();
+
+    for (item, defines) in db.module_names(module).iter() {
+        exports.extend(defines.iter().cloned());
+
+        let parsed = db.parse_item(db.item_source(item.clone()));
+        for expr in &parsed.exprs {
+            if let Expr::Import { module_name, .. } = expr {
+                let import = ModuleId(module_name.0.clone());
+                if !imports.contains(&import) {
+                    imports.push(import);
+                }
+            }
+        }
+    }
+
+    Arc::new(Dependencies { imports, exports })
 }
 
 /// Implement needs_recompilation query
@@ -127,9 +238,319 @@ fn needs_recompilation(db: &dyn DependencyQueries, module: ModuleId) -> bool {
 }
 
 /// Implement module_definitions query
-fn module_definitions(_db: &dyn CodebaseQueries, _module: ModuleId) -> Arc<Vec<Definition>> {
-    // TODO: Implement actual definition extraction
-    Arc::new(vec![])
+fn module_definitions(db: &dyn CodebaseQueries, module: ModuleId) -> Arc<Vec<Definition>> {
+    let mut definitions = Vec::new();
+
+    for (item, _) in db.module_names(module).iter() {
+        let parsed = db.parse_item(db.item_source(item.clone()));
+        let checked = db.check_item(item.clone());
+        if !parsed.errors.is_empty() || !checked.diagnostics.is_empty() {
+            continue;
+        }
+
+        let Some(expr) = parsed.exprs.last() else {
+            continue;
+        };
+        let expr = Arc::new(expr.clone());
+        for (name, _, ty) in &checked.bindings {
+            definitions.push(Definition {
+                name: name.clone(),
+                expr: expr.clone(),
+                ty: ty.clone(),
+            });
+        }
+    }
+
+    Arc::new(definitions)
+}
+
+/// Implement module_layout query
+fn module_layout(db: &dyn CodebaseQueries, module: ModuleId) -> Arc<Vec<LayoutItem>> {
+    let source = db.module_text(module.clone());
+    let chars: Vec<char> = source.chars().collect();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    let items = split_top_level(&source)
+        .into_iter()
+        .map(|span| {
+            let text: Arc<String> = Arc::new(chars[span.start..span.end].iter().collect());
+            let parsed = db.parse_item(text.clone());
+
+            let base = parsed
+                .defines
+                .first()
+                .cloned()
+                .unwrap_or_else(|| "<expr>".to_string());
+            let count = seen.entry(base.clone()).or_insert(0);
+            let name = if *count == 0 {
+                base
+            } else {
+                format!("{base}#{count}")
+            };
+            *count += 1;
+
+            LayoutItem {
+                id: ItemId {
+                    module: module.clone(),
+                    name,
+                },
+                span,
+                text,
+            }
+        })
+        .collect();
+
+    Arc::new(items)
+}
+
+/// Implement module_names query
+fn module_names(db: &dyn CodebaseQueries, module: ModuleId) -> Arc<Vec<(ItemId, Vec<String>)>> {
+    let names = db
+        .module_layout(module)
+        .iter()
+        .map(|item| {
+            let defines = db.parse_item(item.text.clone()).defines.clone();
+            (item.id.clone(), defines)
+        })
+        .collect();
+    Arc::new(names)
+}
+
+/// Implement module_item_texts query
+fn module_item_texts(
+    db: &dyn CodebaseQueries,
+    module: ModuleId,
+) -> Arc<HashMap<String, Arc<String>>> {
+    let texts = db
+        .module_layout(module)
+        .iter()
+        .map(|item| (item.id.name.clone(), item.text.clone()))
+        .collect();
+    Arc::new(texts)
+}
+
+/// Implement item_source query
+fn item_source(db: &dyn CodebaseQueries, item: ItemId) -> Arc<String> {
+    db.module_item_texts(item.module.clone())
+        .get(&item.name)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Implement parse_item query
+fn parse_item(_db: &dyn CodebaseQueries, text: Arc<String>) -> Arc<ParsedItem> {
+    let parsed = parse_with_recovery(&text);
+
+    let errors = parsed
+        .errors
+        .iter()
+        .map(|error| SyntaxDiagnostic {
+            span: Span::new(
+                error.location.offset,
+                error.location.offset + error.location.length,
+            ),
+            message: error.message.clone(),
+            code: error.code.clone(),
+        })
+        .collect();
+
+    let mut defines = Vec::new();
+    for expr in &parsed.exprs {
+        match expr {
+            Expr::Let { name, .. }
+            | Expr::LetRec { name, .. }
+            | Expr::Rec { name, .. }
+            | Expr::FunctionDef { name, .. } => defines.push(name.0.clone()),
+            Expr::TypeDef { definition, .. } => {
+                defines.push(definition.name.clone());
+                defines.extend(definition.constructors.iter().map(|c| c.name.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    Arc::new(ParsedItem {
+        exprs: parsed.exprs,
+        errors,
+        defines,
+    })
+}
+
+/// Names an item's text refers to. Over-approximates by taking every symbol,
+/// which at worst adds a dependency on a shadowed definition.
+fn referenced_names(text: &str) -> HashSet<String> {
+    let mut lexer = Lexer::new(text);
+    let mut names = HashSet::new();
+    loop {
+        match lexer.next_token() {
+            Ok(Some((Token::Symbol(name), _))) => {
+                names.insert(name);
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(_) => lexer.skip_char(),
+        }
+    }
+    names
+}
+
+/// Implement item_dependencies query
+fn item_dependencies(db: &dyn CodebaseQueries, item: ItemId) -> Arc<Vec<ItemId>> {
+    let names = db.module_names(item.module.clone());
+    let referenced = referenced_names(&db.item_source(item.clone()));
+
+    // Each name resolves to the nearest definition before the item
+    let mut visible: HashMap<&str, &ItemId> = HashMap::new();
+    for (id, defines) in names.iter() {
+        if *id == item {
+            break;
+        }
+        for name in defines {
+            visible.insert(name, id);
+        }
+    }
+
+    let used: HashSet<&ItemId> = referenced
+        .iter()
+        .filter_map(|name| visible.get(name.as_str()).copied())
+        .collect();
+
+    // Keep source order so dependencies are added to the environment as in a full check
+    let dependencies = names
+        .iter()
+        .map(|(id, _)| id)
+        .filter(|id| used.contains(id))
+        .cloned()
+        .collect();
+    Arc::new(dependencies)
+}
+
+/// Implement check_item query
+fn check_item(db: &dyn CodebaseQueries, item: ItemId) -> Arc<CheckedItem> {
+    let parsed = db.parse_item(db.item_source(item.clone()));
+
+    // An item that does not parse binds its names to an unknown type, so its
+    // dependents report their own errors rather than cascading ones
+    if !parsed.errors.is_empty() {
+        let bindings = parsed
+            .defines
+            .iter()
+            .map(|name| {
+                (
+                    name.clone(),
+                    vec!["a".to_string()],
+                    Type::Var("a".to_string()),
+                )
+            })
+            .collect();
+        return Arc::new(CheckedItem {
+            ty: None,
+            bindings,
+            type_definitions: vec![],
+            diagnostics: vec![],
+        });
+    }
+
+    let mut env = TypeEnv::new();
+    for dependency in db.item_dependencies(item).iter() {
+        let checked = db.check_item(dependency.clone());
+        for definition in &checked.type_definitions {
+            env.add_type_definition(definition.name.clone(), definition.clone());
+        }
+        for (name, vars, typ) in &checked.bindings {
+            env.add_binding(
+                name.clone(),
+                TypeScheme {
+                    vars: vars.clone(),
+                    typ: typ.clone(),
+                    effects: None,
+                    effect_vars: vec![],
+                },
+            );
+        }
+    }
+
+    let mut checker = TypeChecker::new();
+    let mut ty = None;
+    let mut diagnostics = Vec::new();
+    for expr in &parsed.exprs {
+        let (typ, errors) = checker.check_all(expr, &mut env);
+        ty = Some(typ);
+        diagnostics.extend(errors);
+    }
+
+    let bindings = parsed
+        .defines
+        .iter()
+        .filter_map(|name| {
+            env.lookup(name)
+                .map(|scheme| (name.clone(), scheme.vars.clone(), scheme.typ.clone()))
+        })
+        .collect();
+    let type_definitions = parsed
+        .defines
+        .iter()
+        .filter_map(|name| env.lookup_type_definition(name).cloned())
+        .collect();
+
+    Arc::new(CheckedItem {
+        ty,
+        bindings,
+        type_definitions,
+        diagnostics,
+    })
+}
+
+fn shift_span(span: &Span, offset: usize) -> Span {
+    Span::new(span.start + offset, span.end + offset)
+}
+
+/// Implement module_diagnostics query
+fn module_diagnostics(db: &dyn CodebaseQueries, module: ModuleId) -> Arc<Vec<ModuleDiagnostic>> {
+    let mut diagnostics = Vec::new();
+
+    for item in db.module_layout(module).iter() {
+        let offset = item.span.start;
+        let parsed = db.parse_item(item.text.clone());
+        for error in &parsed.errors {
+            diagnostics.push(ModuleDiagnostic::Syntax(SyntaxDiagnostic {
+                span: shift_span(&error.span, offset),
+                ..error.clone()
+            }));
+        }
+
+        for error in &db.check_item(item.id.clone()).diagnostics {
+            let mut error = error.clone();
+            error.span = shift_span(&error.span, offset);
+            for provenance in [&mut error.expected, &mut error.found]
+                .into_iter()
+                .flatten()
+            {
+                provenance.span = shift_span(&provenance.span, offset);
+            }
+            diagnostics.push(ModuleDiagnostic::Type(error));
+        }
+    }
+
+    Arc::new(diagnostics)
+}
+
+/// Implement module_expr query
+fn module_expr(db: &dyn CodebaseQueries, module: ModuleId) -> Arc<Expr> {
+    let mut exprs = Vec::new();
+    for item in db.module_layout(module).iter() {
+        for expr in &db.parse_item(item.text.clone()).exprs {
+            let mut expr = expr.clone();
+            shift_spans(&mut expr, item.span.start);
+            exprs.push(expr);
+        }
+    }
+
+    let span = match (exprs.first(), exprs.last()) {
+        (Some(first), Some(last)) => Span::new(first.span().start, last.span().end),
+        _ => Span::new(0, 0),
+    };
+    Arc::new(Expr::Block { exprs, span })
 }
 
 /// Database implementation
@@ -152,10 +573,135 @@ impl Default for XsDatabaseImpl {
     }
 }
 
+/// Parsed items kept in memory. Every edit produces a new text for the edited
+/// item, so without a bound old versions would accumulate.
+const PARSE_CACHE_CAPACITY: usize = 16 * 1024;
+
 impl XsDatabaseImpl {
     pub fn new() -> Self {
-        Self {
+        let mut db = Self {
             storage: Default::default(),
+        };
+        ParseItemQuery
+            .in_db_mut(&mut db)
+            .set_lru_capacity(PARSE_CACHE_CAPACITY);
+        db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Database that records which queries actually execute
+    #[salsa::database(
+        CompilerQueriesStorage,
+        DependencyQueriesStorage,
+        CodebaseQueriesStorage
+    )]
+    #[derive(Default)]
+    struct LoggingDatabase {
+        storage: salsa::Storage<Self>,
+        executed: Mutex<Vec<String>>,
+    }
+
+    impl salsa::Database for LoggingDatabase {
+        fn salsa_event(&self, event: salsa::Event) {
+            if let salsa::EventKind::WillExecute { database_key } = event.kind {
+                let key = format!("{:?}", database_key.debug(self));
+                self.executed.lock().unwrap().push(key);
+            }
+        }
+    }
+
+    impl LoggingDatabase {
+        /// Keys of the queries executed since the last call
+        fn take_executed(&self) -> Vec<String> {
+            std::mem::take(&mut *self.executed.lock().unwrap())
+        }
+    }
+
+    fn count(executed: &[String], query: &str) -> usize {
+        executed.iter().filter(|key| key.starts_with(query)).count()
+    }
+
+    fn module() -> ModuleId {
+        ModuleId("main.vibe".to_string())
+    }
+
+    fn item(name: &str) -> ItemId {
+        ItemId {
+            module: module(),
+            name: name.to_string(),
         }
     }
+
+    #[test]
+    fn test_module_is_split_into_named_items() {
+        let mut db = XsDatabaseImpl::new();
+        db.set_module_text(
+            module(),
+            Arc::new("let x = 1\nlet y = [x, 2]\nx".to_string()),
+        );
+
+        let names: Vec<String> = db
+            .module_names(module())
+            .iter()
+            .map(|(id, _)| id.name.clone())
+            .collect();
+        assert_eq!(names, vec!["x", "y", "<expr>"]);
+        assert_eq!(*db.item_dependencies(item("y")), vec![item("x")]);
+
+        let exports = db.module_dependencies(module()).exports.clone();
+        assert_eq!(exports, vec!["x", "y"]);
+        assert_eq!(db.module_definitions(module()).len(), 2);
+    }
+
+    #[test]
+    fn test_diagnostics_use_module_positions() {
+        let mut db = XsDatabaseImpl::new();
+        let source = "let x = 1\nlet y = [x, 2]\nlet w = [x, true]";
+        db.set_module_text(module(), Arc::new(source.to_string()));
+
+        let diagnostics = db.module_diagnostics(module());
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        let span = diagnostics[0].span();
+        assert_eq!(&source[span.start..span.end], "true");
+    }
+
+    #[test]
+    fn test_edit_rechecks_only_changed_item_and_dependents() {
+        let mut db = LoggingDatabase::default();
+        db.set_module_text(
+            module(),
+            Arc::new("let x = 1\nlet y = [x, 2]\nlet z = [3, 4]".to_string()),
+        );
+        assert!(db.module_diagnostics(module()).is_empty());
+        db.take_executed();
+
+        // Editing an item nothing depends on re-parses and re-checks only that item
+        db.set_module_text(
+            module(),
+            Arc::new("let x = 1\nlet y = [x, 2]\nlet z = [3, 5]".to_string()),
+        );
+        assert!(db.module_diagnostics(module()).is_empty());
+        let executed = db.take_executed();
+        assert_eq!(count(&executed, "parse_item"), 1);
+        assert_eq!(count(&executed, "check_item"), 1);
+        assert!(executed
+            .iter()
+            .any(|key| key.starts_with("check_item") && key.contains("\"z\"")));
+
+        // Changing the type of a definition re-checks its dependents
+        db.set_module_text(
+            module(),
+            Arc::new("let x = true\nlet y = [x, 2]\nlet z = [3, 5]".to_string()),
+        );
+        let diagnostics = db.module_diagnostics(module());
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert!(matches!(diagnostics[0], ModuleDiagnostic::Type(_)));
+        let executed = db.take_executed();
+        assert_eq!(count(&executed, "check_item"), 2, "{executed:?}");
+    }
 }
@@ -65,7 +65,7 @@ impl<'a> DependencyExtractor<'a> {
             Type::List(elem) => {
                 self.visit_type(elem, deps);
             }
-            Type::Record { fields } => {
+            Type::Record { fields } | Type::ExtensibleRecord { fields, .. } => {
                 for (_, field_type) in fields {
                     self.visit_type(field_type, deps);
                 }
@@ -73,11 +73,14 @@ impl<'a> DependencyExtractor<'a> {
             Type::Option(inner) => {
                 self.visit_type(inner, deps);
             }
-            Type::Tuple(types) => {
+            Type::Tuple(types) | Type::Applied { args: types, .. } => {
                 for t in types {
                     self.visit_type(t, deps);
                 }
             }
+            Type::Forall { body, .. } => {
+                self.visit_type(body, deps);
+            }
             // Primitive types and type variables have no dependencies
             Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit | Type::Var(_) => {}
         }
@@ -178,6 +181,14 @@ impl<'a> DependencyExtractor<'a> {
                 self.add_binding(name.0.clone());
             }
 
+            Expr::Mut { name, value, .. } => {
+                self.visit_expr(value, deps);
+                self.add_binding(name.0.clone());
+            }
+
+            // Only a local `mut` variable can be assigned to
+            Expr::Assign { value, .. } => self.visit_expr(value, deps),
+
             Expr::LetIn {
                 name,
                 type_ann,
@@ -380,15 +391,18 @@ impl<'a> DependencyExtractor<'a> {
                 }
             }
 
-            Expr::RecordAccess { record, .. } => {
+            Expr::RecordAccess { record, .. } | Expr::RecordRestrict { record, .. } => {
                 self.visit_expr(record, deps);
             }
 
             Expr::RecordUpdate {
-                record, updates, ..
-            } => {
+                record,
+                updates: fields,
+                ..
+            }
+            | Expr::RecordExtend { record, fields, .. } => {
                 self.visit_expr(record, deps);
-                for (_, expr) in updates {
+                for (_, expr) in fields {
                     self.visit_expr(expr, deps);
                 }
             }
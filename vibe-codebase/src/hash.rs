@@ -146,6 +146,18 @@ fn hash_expr(hasher: &mut Sha256, expr: &Expr) {
             hasher.update(b"\0");
             hash_expr(hasher, value);
         }
+        Expr::Mut { name, value, .. } => {
+            hasher.update(b"mut");
+            hasher.update(name.0.as_bytes());
+            hasher.update(b"\0");
+            hash_expr(hasher, value);
+        }
+        Expr::Assign { name, value, .. } => {
+            hasher.update(b"assign");
+            hasher.update(name.0.as_bytes());
+            hasher.update(b"\0");
+            hash_expr(hasher, value);
+        }
         Expr::LetIn {
             name, value, body, ..
         } => {
@@ -416,6 +428,26 @@ fn hash_expr(hasher: &mut Sha256, expr: &Expr) {
             }
         }
 
+        Expr::RecordExtend { record, fields, .. } => {
+            hasher.update(b"record_extend");
+            hash_expr(hasher, record);
+            // Sort fields for deterministic ordering
+            let mut sorted_fields: Vec<_> = fields.iter().collect();
+            sorted_fields.sort_by_key(|(ident, _)| &ident.0);
+            hasher.update(sorted_fields.len().to_le_bytes());
+            for (ident, expr) in sorted_fields {
+                hasher.update(ident.0.as_bytes());
+                hasher.update(b"\0");
+                hash_expr(hasher, expr);
+            }
+        }
+
+        Expr::RecordRestrict { record, field, .. } => {
+            hasher.update(b"record_restrict");
+            hash_expr(hasher, record);
+            hasher.update(field.0.as_bytes());
+        }
+
         Expr::LetRecIn {
             name,
             type_ann,
@@ -602,6 +634,16 @@ fn hash_type(hasher: &mut Sha256, ty: &Type) {
                 hash_type(hasher, ty);
             }
         }
+        Type::ExtensibleRecord { fields, row } => {
+            hasher.update(b"extensible_record");
+            hasher.update(fields.len().to_le_bytes());
+            for (name, ty) in fields {
+                hasher.update(name.as_bytes());
+                hasher.update(b"\0");
+                hash_type(hasher, ty);
+            }
+            hasher.update(row.as_bytes());
+        }
         Type::Option(inner) => {
             hasher.update(b"option");
             hash_type(hasher, inner);
@@ -613,6 +655,24 @@ fn hash_type(hasher: &mut Sha256, ty: &Type) {
                 hash_type(hasher, ty);
             }
         }
+        Type::Applied { constructor, args } => {
+            hasher.update(b"applied");
+            hasher.update(constructor.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(args.len().to_le_bytes());
+            for ty in args {
+                hash_type(hasher, ty);
+            }
+        }
+        Type::Forall { vars, body } => {
+            hasher.update(b"forall");
+            hasher.update(vars.len().to_le_bytes());
+            for var in vars {
+                hasher.update(var.as_bytes());
+                hasher.update(b"\0");
+            }
+            hash_type(hasher, body);
+        }
     }
 }
 
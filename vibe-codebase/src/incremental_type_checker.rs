@@ -262,8 +262,8 @@ impl IncrementalTypeChecker {
 
                 // Type check body
                 let body_type = type_checker
-                    .check(body, &mut type_env)
-                    .map_err(|e| XsError::TypeError(vibe_language::Span::new(0, 0), e))?;
+                    .check_spanned(body, &mut type_env)
+                    .map_err(|d| d.to_xs_error())?;
 
                 // Build function type
                 let mut result_type = body_type;
@@ -274,8 +274,8 @@ impl IncrementalTypeChecker {
                 Ok(result_type)
             }
             crate::namespace::DefinitionContent::Value(expr) => type_checker
-                .check(expr, &mut type_env)
-                .map_err(|e| XsError::TypeError(vibe_language::Span::new(0, 0), e)),
+                .check_spanned(expr, &mut type_env)
+                .map_err(|d| d.to_xs_error()),
             crate::namespace::DefinitionContent::Type { .. } => {
                 // Type definitions have kind * -> *
                 Ok(Type::Var("Type".to_string()))
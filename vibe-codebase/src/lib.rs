@@ -25,12 +25,14 @@ pub mod differential_test_runner;
 pub mod hash;
 pub mod incremental_type_checker;
 pub mod namespace;
+pub mod test_cache;
 
 // Code query modules
 pub mod code_query;
 pub mod query_engine;
 
 // Pipeline processing modules
+pub mod data_formats;
 pub mod pipeline;
 pub mod shell_syntax;
 pub mod structured_data;
@@ -45,8 +47,9 @@ pub use codebase::{
     TypeDef,
 };
 pub use database::{
-    CodebaseQueries, CompilerQueries, Definition, Dependencies, DependencyQueries, ExpressionId,
-    ModuleId, SourcePrograms, XsDatabase,
+    CheckedItem, CodebaseQueries, CompilerQueries, Definition, Dependencies, DependencyQueries,
+    ExpressionId, ItemId, LayoutItem, ModuleDiagnostic, ModuleId, ParsedItem, SourcePrograms,
+    SyntaxDiagnostic, XsDatabase,
 };
 use vbin::VBinStorage;
 
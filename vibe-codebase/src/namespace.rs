@@ -204,6 +204,7 @@ pub enum NamespaceCommand {
 }
 
 /// The namespace store manages all namespaces and definitions
+#[derive(Clone)]
 pub struct NamespaceStore {
     /// All namespaces by path
     namespaces: HashMap<NamespacePath, Namespace>,
@@ -329,6 +330,24 @@ impl NamespaceStore {
             .unwrap_or_default()
     }
 
+    /// Every definition `hash` depends on, directly or through other
+    /// definitions
+    pub fn dependency_closure(&self, hash: &DefinitionHash) -> HashSet<DefinitionHash> {
+        let mut closure = HashSet::new();
+        let mut pending: Vec<DefinitionHash> = self
+            .get_definition(hash)
+            .map(|def| def.dependencies.iter().cloned().collect())
+            .unwrap_or_default();
+        while let Some(current) = pending.pop() {
+            if closure.insert(current.clone()) {
+                if let Some(def) = self.get_definition(&current) {
+                    pending.extend(def.dependencies.iter().cloned());
+                }
+            }
+        }
+        closure
+    }
+
     /// Execute a namespace command
     pub fn execute_command(&mut self, command: NamespaceCommand) -> Result<(), XsError> {
         match command {
@@ -437,7 +456,7 @@ impl NamespaceStore {
     }
 
     /// Extract dependencies from definition content
-    fn extract_dependencies(
+    pub fn extract_dependencies(
         &self,
         content: &DefinitionContent,
     ) -> Result<HashSet<DefinitionHash>, XsError> {
@@ -0,0 +1,757 @@
+//! Resolving a manifest's semver ranges and installing the result
+//!
+//! [`Installer::resolve`] turns the requirements in `package.vibe` into a
+//! [`Lockfile`]: for each package it keeps the locked version when that still
+//! satisfies every requirement, and otherwise picks the highest version that
+//! does. [`Installer::install`] then copies each locked package into the
+//! [`PackageCache`], checking that its content hash is the one recorded.
+//!
+//! Without a registry the installer is offline: resolution only considers
+//! packages already in the cache, so installing from an existing lockfile
+//! works as long as the cache holds what it names.
+
+use super::{
+    cache::PackageCache,
+    hash::calculate_package_hash,
+    lockfile::{LockedPackage, Lockfile},
+    manifest::PackageManifest,
+    resolver::PackageRegistry,
+    PackageError, PackageHash, Result,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+
+/// How many times resolution restarts after learning new transitive
+/// requirements before giving up
+const MAX_RESOLUTION_ROUNDS: usize = 8;
+
+/// Which locked packages resolution may move to a different version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    /// Keep every locked version that still satisfies the manifest
+    Locked,
+    /// Re-resolve everything to the highest matching versions
+    All,
+    /// Re-resolve only the named packages
+    Only(Vec<String>),
+}
+
+impl Update {
+    fn allows(&self, name: &str) -> bool {
+        match self {
+            Update::Locked => false,
+            Update::All => true,
+            Update::Only(names) => names.iter().any(|n| n == name),
+        }
+    }
+}
+
+/// The outcome of installing a manifest
+#[derive(Debug, Clone)]
+pub struct InstallOutcome {
+    pub lockfile: Lockfile,
+    /// Packages that had to be copied into the cache
+    pub installed: Vec<LockedPackage>,
+    /// Whether the lockfile differs from the one on disk
+    pub lockfile_changed: bool,
+}
+
+/// A requirement on a package, and who placed it
+#[derive(Debug, Clone)]
+struct Requirement {
+    version: Option<String>,
+    hash: Option<String>,
+    /// The manifest, or the `name@version` of the package, that placed it
+    required_by: String,
+    /// The package that placed it, unless it comes from a manifest
+    package: Option<String>,
+}
+
+impl Requirement {
+    fn describe(&self) -> String {
+        let what = match (&self.version, &self.hash) {
+            (Some(version), _) => version.clone(),
+            (None, Some(hash)) => format!("#{}", short_hash(hash)),
+            (None, None) => "*".to_string(),
+        };
+        format!("{} (required by {})", what, self.required_by)
+    }
+
+    fn accepts(&self, version: Option<&semver::Version>, hash: &str) -> bool {
+        if self.hash.as_deref().is_some_and(|h| h != hash) {
+            return false;
+        }
+        match &self.version {
+            Some(req) => match (semver::VersionReq::parse(req), version) {
+                (Ok(req), Some(version)) => req.matches(version),
+                _ => false,
+            },
+            None => true,
+        }
+    }
+}
+
+/// A version of a package that could be installed
+#[derive(Debug, Clone)]
+struct Candidate {
+    version: Option<String>,
+    hash: String,
+}
+
+impl Candidate {
+    fn semver(&self) -> Option<semver::Version> {
+        self.version
+            .as_deref()
+            .and_then(|v| semver::Version::parse(v).ok())
+    }
+}
+
+/// Resolves manifests and installs packages into a cache
+pub struct Installer<'a> {
+    cache: &'a mut PackageCache,
+    registry: Option<&'a dyn PackageRegistry>,
+}
+
+impl<'a> Installer<'a> {
+    /// Create an offline installer that only uses the cache
+    pub fn new(cache: &'a mut PackageCache) -> Self {
+        Installer {
+            cache,
+            registry: None,
+        }
+    }
+
+    /// Fetch packages that are not cached from `registry`
+    pub fn with_registry(mut self, registry: &'a dyn PackageRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Whether the registry is unavailable
+    pub fn is_offline(&self) -> bool {
+        self.registry.is_none()
+    }
+
+    /// Resolve, install and lock the dependencies of `manifest`, whose file
+    /// is (or will be written to) `manifest_path`. The lockfile next to it is
+    /// reused when it still satisfies the manifest and nothing is updated.
+    pub fn install_manifest(
+        &mut self,
+        manifest: &PackageManifest,
+        manifest_path: &Path,
+        update: &Update,
+    ) -> Result<InstallOutcome> {
+        self.install_manifests(&[manifest], &Lockfile::path_for(manifest_path), update)
+    }
+
+    /// Like [`Installer::install_manifest`] for several manifests sharing
+    /// the lockfile at `lock_path`, such as the members of a workspace
+    pub fn install_manifests(
+        &mut self,
+        manifests: &[&PackageManifest],
+        lock_path: &Path,
+        update: &Update,
+    ) -> Result<InstallOutcome> {
+        let previous = Lockfile::load(lock_path)?;
+
+        let lockfile = match &previous {
+            Some(locked)
+                if *update == Update::Locked && manifests.iter().all(|m| locked.satisfies(m)) =>
+            {
+                locked.pruned_for(manifests)
+            }
+            _ => self.resolve_all(manifests, previous.as_ref(), update)?,
+        };
+        let installed = self.install(&lockfile)?;
+
+        let lockfile_changed = previous.as_ref() != Some(&lockfile);
+        if lockfile_changed {
+            lockfile.save(lock_path)?;
+        }
+        Ok(InstallOutcome {
+            lockfile,
+            installed,
+            lockfile_changed,
+        })
+    }
+
+    /// Resolve the dependency graph of `manifest`
+    pub fn resolve(
+        &self,
+        manifest: &PackageManifest,
+        previous: Option<&Lockfile>,
+        update: &Update,
+    ) -> Result<Lockfile> {
+        self.resolve_all(&[manifest], previous, update)
+    }
+
+    /// Resolve one graph satisfying the requirements of all `manifests`.
+    /// Path dependencies are local and are not resolved.
+    pub fn resolve_all(
+        &self,
+        manifests: &[&PackageManifest],
+        previous: Option<&Lockfile>,
+        update: &Update,
+    ) -> Result<Lockfile> {
+        // Requirements survive across rounds so that a later round respects
+        // constraints that were only discovered deep in the graph
+        let mut requirements: BTreeMap<String, Vec<Requirement>> = BTreeMap::new();
+        for manifest in manifests {
+            for dep in manifest.dependencies.values().filter(|dep| !dep.is_local()) {
+                requirements
+                    .entry(dep.name.clone())
+                    .or_default()
+                    .push(Requirement {
+                        version: dep.version.clone(),
+                        hash: (!dep.hash.is_empty()).then(|| dep.hash.clone()),
+                        required_by: manifest.package.name.clone(),
+                        package: None,
+                    });
+            }
+        }
+
+        for _ in 0..MAX_RESOLUTION_ROUNDS {
+            let mut chosen: BTreeMap<String, (Candidate, LockedPackage)> = BTreeMap::new();
+            let mut queue: VecDeque<String> = requirements.keys().cloned().collect();
+            let mut discovered = false;
+            let mut dropped = false;
+
+            while let Some(name) = queue.pop_front() {
+                if chosen.contains_key(&name) {
+                    continue;
+                }
+                // Nothing that is still chosen requires it
+                let Some(reqs) = requirements.get(&name) else {
+                    continue;
+                };
+                let candidate = self.choose(&name, reqs, previous, update)?;
+                let hash = PackageHash::from_hex(&candidate.hash)?;
+                let package_manifest = self.manifest(&hash)?;
+                let version = candidate
+                    .version
+                    .clone()
+                    .or(package_manifest.package.version.clone());
+                let required_by = match &version {
+                    Some(version) => format!("{name}@{version}"),
+                    None => format!("{name}#{}", short_hash(&candidate.hash)),
+                };
+                dropped |= drop_stale(&mut requirements, &name, &required_by);
+
+                // A published package's path dependencies only made sense
+                // in the workspace it came from
+                let registry_deps: Vec<_> = package_manifest
+                    .dependencies
+                    .values()
+                    .filter(|dep| !dep.is_local())
+                    .collect();
+                let mut dependencies: Vec<String> =
+                    registry_deps.iter().map(|dep| dep.name.clone()).collect();
+                dependencies.sort();
+                for dep in registry_deps {
+                    let requirement = Requirement {
+                        version: dep.version.clone(),
+                        hash: (!dep.hash.is_empty()).then(|| dep.hash.clone()),
+                        required_by: required_by.clone(),
+                        package: Some(name.clone()),
+                    };
+                    let known = requirements.entry(dep.name.clone()).or_default();
+                    if !known.iter().any(|r| {
+                        r.required_by == requirement.required_by
+                            && r.version == requirement.version
+                            && r.hash == requirement.hash
+                    }) {
+                        known.push(requirement);
+                        discovered = true;
+                    }
+                    queue.push_back(dep.name.clone());
+                }
+
+                let locked = LockedPackage {
+                    name: name.clone(),
+                    version,
+                    hash: candidate.hash.clone(),
+                    dependencies,
+                };
+                chosen.insert(name, (candidate, locked));
+            }
+
+            // Choices made before a stale requirement was dropped may be
+            // wrong, so resolve again without it
+            if dropped {
+                continue;
+            }
+            let consistent = chosen.iter().all(|(name, (candidate, locked))| {
+                let version = locked
+                    .version
+                    .as_deref()
+                    .and_then(|v| semver::Version::parse(v).ok());
+                requirements[name]
+                    .iter()
+                    .all(|r| r.accepts(version.as_ref(), &candidate.hash))
+            });
+            if consistent {
+                return Ok(Lockfile::new(
+                    chosen.into_values().map(|(_, locked)| locked).collect(),
+                ));
+            }
+            if !discovered {
+                break;
+            }
+        }
+
+        // Report the first package whose requirements cannot all be met
+        for (name, reqs) in &requirements {
+            if self.choose(name, reqs, None, &Update::All).is_err() {
+                return Err(self.no_match(name, reqs));
+            }
+        }
+        Err(PackageError::Resolution(
+            "Dependency requirements do not converge".to_string(),
+        ))
+    }
+
+    /// Install every package in `lockfile` that is not cached yet, returning
+    /// the ones that were installed
+    pub fn install(&mut self, lockfile: &Lockfile) -> Result<Vec<LockedPackage>> {
+        let mut installed = Vec::new();
+        for package in lockfile.install_order()? {
+            let hash = PackageHash::from_hex(&package.hash)?;
+            if self.cache.has_package(&hash) {
+                let actual = calculate_package_hash(&self.cache.package_dir(&hash))?;
+                if actual != hash {
+                    return Err(PackageError::Cache(format!(
+                        "Cached {} has content hash #{}, expected #{}",
+                        describe(package),
+                        short_hash(&actual.to_hex()),
+                        short_hash(&package.hash)
+                    )));
+                }
+                continue;
+            }
+
+            let registry = self.registry.ok_or_else(|| {
+                PackageError::NotFound(format!(
+                    "{} is not in the package cache and no registry is available (offline)",
+                    describe(package)
+                ))
+            })?;
+            let download = tempfile::TempDir::new()?;
+            registry.download_package(&hash, download.path())?;
+            let actual = calculate_package_hash(download.path())?;
+            if actual != hash {
+                return Err(PackageError::InvalidHash(format!(
+                    "{} downloaded with content hash #{}, expected #{}",
+                    describe(package),
+                    short_hash(&actual.to_hex()),
+                    short_hash(&package.hash)
+                )));
+            }
+            self.cache.store_package(&hash, download.path())?;
+            installed.push(package.clone());
+        }
+        Ok(installed)
+    }
+
+    /// Pick the version of `name` to install
+    fn choose(
+        &self,
+        name: &str,
+        requirements: &[Requirement],
+        previous: Option<&Lockfile>,
+        update: &Update,
+    ) -> Result<Candidate> {
+        // A hash pin names exactly one package
+        if let Some(hash) = requirements.iter().find_map(|r| r.hash.clone()) {
+            let manifest = self.manifest(&PackageHash::from_hex(&hash)?)?;
+            let candidate = Candidate {
+                version: manifest.package.version,
+                hash,
+            };
+            return self.accept(name, requirements, candidate);
+        }
+
+        if let Some(locked) = previous.and_then(|l| l.get(name)) {
+            let candidate = Candidate {
+                version: locked.version.clone(),
+                hash: locked.hash.clone(),
+            };
+            let version = candidate.semver();
+            if !update.allows(name)
+                && requirements
+                    .iter()
+                    .all(|r| r.accepts(version.as_ref(), &candidate.hash))
+            {
+                return Ok(candidate);
+            }
+        }
+
+        self.candidates(name)?
+            .into_iter()
+            .filter_map(|c| c.semver().map(|v| (v, c)))
+            .filter(|(v, c)| requirements.iter().all(|r| r.accepts(Some(v), &c.hash)))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, c)| c)
+            .ok_or_else(|| self.no_match(name, requirements))
+    }
+
+    fn accept(
+        &self,
+        name: &str,
+        requirements: &[Requirement],
+        candidate: Candidate,
+    ) -> Result<Candidate> {
+        let version = candidate.semver();
+        if requirements
+            .iter()
+            .all(|r| r.accepts(version.as_ref(), &candidate.hash))
+        {
+            Ok(candidate)
+        } else {
+            Err(self.no_match(name, requirements))
+        }
+    }
+
+    fn no_match(&self, name: &str, requirements: &[Requirement]) -> PackageError {
+        let wanted: Vec<String> = requirements.iter().map(Requirement::describe).collect();
+        let mut message = format!("No version of {} matches {}", name, wanted.join(", "));
+        if self.is_offline() {
+            message.push_str(" (offline: only cached packages were considered)");
+        }
+        PackageError::Resolution(message)
+    }
+
+    /// Versions of `name` available from the registry, or from the cache
+    /// when offline
+    fn candidates(&self, name: &str) -> Result<Vec<Candidate>> {
+        match self.registry {
+            Some(registry) => Ok(registry
+                .versions(name)?
+                .into_iter()
+                .filter(|v| !v.yanked)
+                .map(|v| Candidate {
+                    version: Some(v.version),
+                    hash: v.hash,
+                })
+                .collect()),
+            None => Ok(self
+                .cache
+                .list_packages()
+                .into_iter()
+                .filter(|(_, entry)| entry.name == name)
+                .map(|(hash, entry)| Candidate {
+                    version: entry.version.clone(),
+                    hash: hash.clone(),
+                })
+                .collect()),
+        }
+    }
+
+    /// The manifest of a package, from the cache or the registry
+    fn manifest(&self, hash: &PackageHash) -> Result<PackageManifest> {
+        if self.cache.has_package(hash) {
+            let package_dir = self.cache.get_package(hash)?;
+            return PackageManifest::load_from_file(&package_dir.join("package.vibe"));
+        }
+        match self.registry {
+            Some(registry) => registry.get_manifest(hash),
+            None => Err(PackageError::NotFound(format!(
+                "Package #{} is not in the package cache (offline)",
+                short_hash(&hash.to_hex())
+            ))),
+        }
+    }
+}
+
+/// Drop the requirements placed by versions of `name` other than the chosen
+/// `required_by`, and then those placed by packages nothing requires any
+/// more. Returns whether any were dropped.
+fn drop_stale(
+    requirements: &mut BTreeMap<String, Vec<Requirement>>,
+    name: &str,
+    required_by: &str,
+) -> bool {
+    let mut dropped = false;
+    let mut stale = vec![(name.to_string(), Some(required_by.to_string()))];
+    while let Some((package, keep)) = stale.pop() {
+        for reqs in requirements.values_mut() {
+            let before = reqs.len();
+            reqs.retain(|r| {
+                r.package.as_deref() != Some(package.as_str())
+                    || keep.as_deref() == Some(r.required_by.as_str())
+            });
+            dropped |= reqs.len() != before;
+        }
+        let unneeded: Vec<String> = requirements
+            .iter()
+            .filter(|(_, reqs)| reqs.is_empty())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in unneeded {
+            requirements.remove(&name);
+            stale.push((name, None));
+        }
+    }
+    dropped
+}
+
+fn describe(package: &LockedPackage) -> String {
+    match &package.version {
+        Some(version) => format!(
+            "{} {} (#{})",
+            package.name,
+            version,
+            short_hash(&package.hash)
+        ),
+        None => format!("{} (#{})", package.name, short_hash(&package.hash)),
+    }
+}
+
+fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::registry::LocalRegistry;
+    use std::fs;
+    use tempfile::TempDir;
+
+    struct Fixture {
+        dir: TempDir,
+        registry: LocalRegistry,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let registry = LocalRegistry::new(dir.path().join("registry")).unwrap();
+            Fixture { dir, registry }
+        }
+
+        fn publish(&mut self, name: &str, version: &str, deps: &[(&str, &str)]) -> String {
+            let package_dir = self
+                .dir
+                .path()
+                .join("src")
+                .join(format!("{name}-{version}"));
+            fs::create_dir_all(&package_dir).unwrap();
+            let mut manifest = PackageManifest::new(name.to_string());
+            manifest.package.version = Some(version.to_string());
+            for (dep, req) in deps {
+                manifest.require(dep.to_string(), req.to_string());
+            }
+            manifest
+                .save_to_file(&package_dir.join("package.vibe"))
+                .unwrap();
+            fs::write(
+                package_dir.join("lib.vibe"),
+                format!("let version = \"{version}\""),
+            )
+            .unwrap();
+            let hash = calculate_package_hash(&package_dir).unwrap();
+            self.registry
+                .publish(&manifest, &hash, &package_dir)
+                .unwrap();
+            hash.to_hex()
+        }
+
+        fn cache(&self) -> PackageCache {
+            PackageCache::new(self.dir.path().join("cache")).unwrap()
+        }
+
+        fn project(&self, deps: &[(&str, &str)]) -> (PackageManifest, std::path::PathBuf) {
+            let project_dir = self.dir.path().join("app");
+            fs::create_dir_all(&project_dir).unwrap();
+            let mut manifest = PackageManifest::new("app".to_string());
+            for (dep, req) in deps {
+                manifest.require(dep.to_string(), req.to_string());
+            }
+            (manifest, project_dir.join("package.vibe"))
+        }
+    }
+
+    fn versions(lockfile: &Lockfile) -> Vec<(String, String)> {
+        lockfile
+            .packages
+            .iter()
+            .map(|p| (p.name.clone(), p.version.clone().unwrap_or_default()))
+            .collect()
+    }
+
+    fn pair(name: &str, version: &str) -> (String, String) {
+        (name.to_string(), version.to_string())
+    }
+
+    #[test]
+    fn test_resolves_highest_matching_versions_transitively() {
+        let mut fixture = Fixture::new();
+        fixture.publish("text", "1.0.0", &[]);
+        fixture.publish("text", "1.3.0", &[]);
+        fixture.publish("text", "2.0.0", &[]);
+        fixture.publish("math", "1.1.0", &[("text", "^1")]);
+        fixture.publish("math", "2.0.0", &[]);
+
+        let (manifest, path) = fixture.project(&[("math", "^1.0")]);
+        let mut cache = fixture.cache();
+        let mut installer = Installer::new(&mut cache).with_registry(&fixture.registry);
+        let outcome = installer
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap();
+
+        assert_eq!(
+            versions(&outcome.lockfile),
+            vec![pair("math", "1.1.0"), pair("text", "1.3.0")]
+        );
+        assert_eq!(outcome.installed.len(), 2);
+        assert!(outcome.lockfile_changed);
+        assert_eq!(
+            outcome.lockfile.get("math").unwrap().dependencies,
+            vec!["text"]
+        );
+        assert!(path.with_file_name("package.lock").exists());
+    }
+
+    #[test]
+    fn test_transitive_requirements_narrow_the_choice() {
+        let mut fixture = Fixture::new();
+        fixture.publish("text", "1.2.0", &[]);
+        fixture.publish("text", "1.9.0", &[]);
+        fixture.publish("math", "1.0.0", &[("text", "~1.2")]);
+
+        let (manifest, path) = fixture.project(&[("math", "^1"), ("text", "^1")]);
+        let mut cache = fixture.cache();
+        let mut installer = Installer::new(&mut cache).with_registry(&fixture.registry);
+        let outcome = installer
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap();
+        assert_eq!(
+            outcome.lockfile.get("text").unwrap().version.as_deref(),
+            Some("1.2.0")
+        );
+    }
+
+    #[test]
+    fn test_requirements_of_replaced_versions_are_dropped() {
+        let mut fixture = Fixture::new();
+        fixture.publish("text", "1.0.0", &[]);
+        fixture.publish("text", "2.0.0", &[]);
+        fixture.publish("json", "1.0.0", &[]);
+        fixture.publish("math", "1.0.0", &[("text", "^1")]);
+        fixture.publish("math", "2.0.0", &[("text", "^2"), ("json", "^1")]);
+        fixture.publish("stats", "1.0.0", &[("math", "^1")]);
+
+        // math 2.0.0 is chosen until stats turns out to need math ^1
+        let (manifest, _) = fixture.project(&[("math", "*"), ("stats", "^1")]);
+        let mut cache = fixture.cache();
+        let installer = Installer::new(&mut cache).with_registry(&fixture.registry);
+        let lockfile = installer.resolve(&manifest, None, &Update::Locked).unwrap();
+        assert_eq!(
+            versions(&lockfile),
+            vec![
+                pair("math", "1.0.0"),
+                pair("stats", "1.0.0"),
+                pair("text", "1.0.0")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lockfile_pins_versions_until_updated() {
+        let mut fixture = Fixture::new();
+        fixture.publish("math", "1.0.0", &[]);
+        let (manifest, path) = fixture.project(&[("math", "^1")]);
+        let mut cache = fixture.cache();
+        Installer::new(&mut cache)
+            .with_registry(&fixture.registry)
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap();
+
+        let newer = fixture.publish("math", "1.5.0", &[]);
+        let mut installer = Installer::new(&mut cache).with_registry(&fixture.registry);
+        let outcome = installer
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap();
+        assert_eq!(versions(&outcome.lockfile), vec![pair("math", "1.0.0")]);
+        assert!(!outcome.lockfile_changed);
+
+        let outcome = installer
+            .install_manifest(&manifest, &path, &Update::Only(vec!["math".to_string()]))
+            .unwrap();
+        assert_eq!(versions(&outcome.lockfile), vec![pair("math", "1.5.0")]);
+        assert_eq!(outcome.lockfile.get("math").unwrap().hash, newer);
+        assert_eq!(outcome.installed.len(), 1);
+    }
+
+    #[test]
+    fn test_offline_install_from_lockfile_and_cache() {
+        let mut fixture = Fixture::new();
+        fixture.publish("math", "1.0.0", &[]);
+        let (manifest, path) = fixture.project(&[("math", "^1")]);
+        Installer::new(&mut fixture.cache())
+            .with_registry(&fixture.registry)
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap();
+        let lockfile = Lockfile::load(&path.with_file_name("package.lock"))
+            .unwrap()
+            .unwrap();
+
+        // The registry is gone, but the lockfile and cache are enough
+        let mut cache = fixture.cache();
+        let outcome = Installer::new(&mut cache)
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap();
+        assert_eq!(outcome.lockfile, lockfile);
+        assert!(outcome.installed.is_empty());
+
+        // A fresh cache cannot be filled offline
+        let mut empty = PackageCache::new(fixture.dir.path().join("empty-cache")).unwrap();
+        let error = Installer::new(&mut empty)
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("math 1.0.0"), "{error}");
+        assert!(error.contains("offline"), "{error}");
+    }
+
+    #[test]
+    fn test_unsatisfiable_requirements() {
+        let mut fixture = Fixture::new();
+        fixture.publish("math", "1.0.0", &[]);
+        let (manifest, _) = fixture.project(&[("math", "^2")]);
+        let mut cache = fixture.cache();
+        let installer = Installer::new(&mut cache).with_registry(&fixture.registry);
+        let error = installer
+            .resolve(&manifest, None, &Update::Locked)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Resolution error: No version of math matches ^2 (required by app)"
+        );
+    }
+
+    #[test]
+    fn test_content_hash_is_verified() {
+        let mut fixture = Fixture::new();
+        let hash = fixture.publish("math", "1.0.0", &[]);
+        // Tamper with the published copy
+        let published = fixture
+            .dir
+            .path()
+            .join("registry")
+            .join("packages")
+            .join(&hash);
+        fs::write(published.join("lib.vibe"), "let version = \"evil\"").unwrap();
+
+        let (manifest, path) = fixture.project(&[("math", "^1")]);
+        let mut cache = fixture.cache();
+        let error = Installer::new(&mut cache)
+            .with_registry(&fixture.registry)
+            .install_manifest(&manifest, &path, &Update::Locked)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("expected #"), "{error}");
+        assert!(!cache.has_package(&PackageHash::from_hex(&hash).unwrap()));
+    }
+}
@@ -0,0 +1,265 @@
+//! `package.lock`: the exact packages an install resolved to
+//!
+//! The lockfile sits next to `package.vibe` and records, for every package
+//! in the dependency graph, the version that was chosen and the content hash
+//! of what was installed. Installing from it needs neither the registry nor
+//! semver resolution, which is what makes installs reproducible and offline.
+
+use super::{manifest::PackageManifest, PackageError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the lockfile
+pub const LOCKFILE_NAME: &str = "package.lock";
+
+/// Current lockfile format version
+const FORMAT_VERSION: u32 = 1;
+
+/// A resolved package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: Option<String>,
+    /// Content hash (hex) of the installed package directory
+    pub hash: String,
+    /// Names of the packages this one depends on
+    pub dependencies: Vec<String>,
+}
+
+/// Resolved dependency graph, sorted by package name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Lockfile {
+            version: FORMAT_VERSION,
+            packages: Vec::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Build a lockfile from resolved packages
+    pub fn new(mut packages: Vec<LockedPackage>) -> Self {
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Lockfile {
+            version: FORMAT_VERSION,
+            packages,
+        }
+    }
+
+    /// Path of the lockfile belonging to a manifest
+    pub fn path_for(manifest_path: &Path) -> PathBuf {
+        manifest_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(LOCKFILE_NAME)
+    }
+
+    /// Load a lockfile, or `None` if there is none yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        let lockfile: Lockfile = serde_json::from_str(&content).map_err(|e| {
+            PackageError::Parse(format!("Failed to parse {}: {}", path.display(), e))
+        })?;
+        if lockfile.version != FORMAT_VERSION {
+            return Err(PackageError::Parse(format!(
+                "Unsupported lockfile version {} in {}",
+                lockfile.version,
+                path.display()
+            )));
+        }
+        Ok(Some(lockfile))
+    }
+
+    /// Write the lockfile
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut content = serde_json::to_string_pretty(self)
+            .map_err(|e| PackageError::Parse(format!("Failed to serialize lockfile: {}", e)))?;
+        content.push('\n');
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Look up a locked package by name
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    /// Whether every dependency of `manifest` is locked to a package that
+    /// still satisfies it
+    pub fn satisfies(&self, manifest: &PackageManifest) -> bool {
+        manifest.dependencies.values().filter(|dep| !dep.is_local()).all(|dep| {
+            self.get(&dep.name).is_some_and(|locked| {
+                if !dep.hash.is_empty() && dep.hash != locked.hash {
+                    return false;
+                }
+                match &dep.version {
+                    Some(req) => version_matches(req, locked.version.as_deref()),
+                    None => true,
+                }
+            })
+        })
+    }
+
+    /// Only the packages reachable from `manifest`'s dependencies
+    pub fn pruned(&self, manifest: &PackageManifest) -> Lockfile {
+        self.pruned_for(&[manifest])
+    }
+
+    /// Only the packages reachable from the dependencies of any of
+    /// `manifests`, such as the members of a workspace
+    pub fn pruned_for(&self, manifests: &[&PackageManifest]) -> Lockfile {
+        let mut reachable: Vec<&str> = Vec::new();
+        let mut pending: Vec<&str> = manifests
+            .iter()
+            .flat_map(|m| m.dependencies.values())
+            .filter(|dep| !dep.is_local())
+            .map(|dep| dep.name.as_str())
+            .collect();
+        while let Some(name) = pending.pop() {
+            if reachable.contains(&name) {
+                continue;
+            }
+            reachable.push(name);
+            if let Some(package) = self.get(name) {
+                pending.extend(package.dependencies.iter().map(String::as_str));
+            }
+        }
+        Lockfile::new(
+            self.packages
+                .iter()
+                .filter(|p| reachable.contains(&p.name.as_str()))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Packages in installation order: dependencies before dependents
+    pub fn install_order(&self) -> Result<Vec<&LockedPackage>> {
+        let mut ordered: Vec<&LockedPackage> = Vec::new();
+        let mut remaining: Vec<&LockedPackage> = self.packages.iter().collect();
+        while !remaining.is_empty() {
+            let (ready, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|p| {
+                p.dependencies
+                    .iter()
+                    .all(|d| ordered.iter().any(|o| &o.name == d) || self.get(d).is_none())
+            });
+            if ready.is_empty() {
+                let names: Vec<&str> = blocked.iter().map(|p| p.name.as_str()).collect();
+                return Err(PackageError::Resolution(format!(
+                    "Circular dependency between {}",
+                    names.join(", ")
+                )));
+            }
+            ordered.extend(ready);
+            remaining = blocked;
+        }
+        Ok(ordered)
+    }
+}
+
+/// Whether `version` satisfies the semver requirement `req`
+pub fn version_matches(req: &str, version: Option<&str>) -> bool {
+    let (Ok(req), Some(Ok(version))) = (
+        semver::VersionReq::parse(req),
+        version.map(semver::Version::parse),
+    ) else {
+        return false;
+    };
+    req.matches(&version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn locked(name: &str, version: &str, dependencies: &[&str]) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            hash: format!("{}{}", name, version),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = Lockfile::path_for(&temp_dir.path().join("package.vibe"));
+        assert_eq!(path, temp_dir.path().join("package.lock"));
+        assert_eq!(Lockfile::load(&path).unwrap(), None);
+
+        let lockfile = Lockfile::new(vec![
+            locked("b", "1.0.0", &[]),
+            locked("a", "2.1.0", &["b"]),
+        ]);
+        lockfile.save(&path).unwrap();
+        let loaded = Lockfile::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, lockfile);
+        assert_eq!(loaded.packages[0].name, "a");
+    }
+
+    #[test]
+    fn test_satisfies_manifest() {
+        let lockfile = Lockfile::new(vec![locked("math", "1.4.0", &[])]);
+        let mut manifest = PackageManifest::new("app".to_string());
+        manifest.require("math".to_string(), "^1.2".to_string());
+        assert!(lockfile.satisfies(&manifest));
+
+        manifest.require("math".to_string(), "^2".to_string());
+        assert!(!lockfile.satisfies(&manifest));
+
+        manifest.require("text".to_string(), "^1".to_string());
+        assert!(!lockfile.satisfies(&manifest));
+    }
+
+    #[test]
+    fn test_pruned_drops_unreachable_packages() {
+        let lockfile = Lockfile::new(vec![
+            locked("math", "1.0.0", &["text"]),
+            locked("text", "1.0.0", &[]),
+            locked("http", "1.0.0", &["text"]),
+        ]);
+        let mut manifest = PackageManifest::new("app".to_string());
+        manifest.require("math".to_string(), "^1".to_string());
+        let names: Vec<String> = lockfile
+            .pruned(&manifest)
+            .packages
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["math", "text"]);
+    }
+
+    #[test]
+    fn test_install_order() {
+        let lockfile = Lockfile::new(vec![
+            locked("app-utils", "1.0.0", &["math", "text"]),
+            locked("math", "1.0.0", &["text"]),
+            locked("text", "1.0.0", &[]),
+        ]);
+        let order: Vec<&str> = lockfile
+            .install_order()
+            .unwrap()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(order, vec!["text", "math", "app-utils"]);
+
+        let cyclic = Lockfile::new(vec![
+            locked("a", "1.0.0", &["b"]),
+            locked("b", "1.0.0", &["a"]),
+        ]);
+        assert!(cyclic.install_order().is_err());
+    }
+}
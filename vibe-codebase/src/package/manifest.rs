@@ -1,5 +1,9 @@
-use super::{PackageMetadata, Dependency, EntryPoints, PackageError, Result};
+use super::manifest_syntax::{
+    record_field, record_fields, render_key, ManifestDocument, ManifestEdits, ManifestError, Syntax,
+};
+use super::{PackageMetadata, Dependency, EntryPoints, PackageError, Result, WorkspaceConfig};
 use serde::{Serialize, Deserialize};
+use vibe_language::{Expr, Literal, Span};
 use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
@@ -11,6 +15,12 @@ pub struct PackageManifest {
     pub dependencies: HashMap<String, Dependency>,
     pub exports: Vec<String>,
     pub entry: EntryPoints,
+    /// Present in a workspace root, which has no `package` of its own
+    #[serde(default)]
+    pub workspace: Option<WorkspaceConfig>,
+    /// The parsed source, kept so that saving preserves its comments
+    #[serde(skip)]
+    document: Option<ManifestDocument>,
 }
 
 impl PackageManifest {
@@ -30,129 +40,129 @@ impl PackageManifest {
                 main: None,
                 lib: None,
             },
+            workspace: None,
+            document: None,
         }
     }
 
+    /// Create a workspace root manifest listing member directories
+    pub fn workspace(members: Vec<String>) -> Self {
+        let mut manifest = Self::new(String::new());
+        manifest.workspace = Some(WorkspaceConfig { members });
+        manifest
+    }
+
+    /// Whether this is a workspace root rather than a package
+    pub fn is_workspace_root(&self) -> bool {
+        self.workspace.is_some()
+    }
+
     /// Load manifest from a file
     pub fn load_from_file(path: &Path) -> Result<Self> {
         let content = fs::read_to_string(path)?;
-        Self::parse(&content)
+        Self::parse(&content).map_err(|e| match e {
+            PackageError::Manifest(error) => PackageError::Manifest(error.in_file(path)),
+            other => other,
+        })
     }
 
     /// Parse manifest from Vibe syntax
+    ///
+    /// The manifest is a record expression; see [`super::manifest_syntax`].
+    /// Errors carry the line and column of the offending field or value.
     pub fn parse(content: &str) -> Result<Self> {
-        // TODO: Implement proper Vibe syntax parser for manifest
-        // For now, use JSON as intermediate format
-        let json_content = Self::vibe_to_json(content)?;
-        serde_json::from_str(&json_content)
-            .map_err(|e| PackageError::Parse(e.to_string()))
-    }
-
-    /// Convert Vibe syntax to JSON (temporary implementation)
-    fn vibe_to_json(content: &str) -> Result<String> {
-        // Simple parser for Vibe manifest syntax
-        let mut package_name = "".to_string();
-        let mut package_version = None;
-        let mut package_author = None;
-        let mut package_license = None;
-        let mut package_description = None;
-        let mut entry_main = None;
-        let mut entry_lib = None;
+        let document = ManifestDocument::parse(content).map_err(PackageError::Manifest)?;
+        let mut manifest = Self::from_document(&document).map_err(PackageError::Manifest)?;
+        manifest.document = Some(document);
+        Ok(manifest)
+    }
+
+    /// Read the manifest fields out of a parsed document
+    fn from_document(doc: &ManifestDocument) -> std::result::Result<Self, ManifestError> {
+        let root = doc.expr();
+        check_keys(doc, root, "manifest", &["package", "dependencies", "exports", "entry", "workspace"])?;
+
+        let workspace = match record_field(root, "workspace") {
+            Some(workspace) => Some(parse_workspace(doc, workspace)?),
+            None => None,
+        };
+        if workspace.is_some() {
+            if let Some(package) = record_field(root, "package") {
+                return Err(doc.error(
+                    &doc.key_span(package),
+                    "a workspace root is not a package; move `package` into a member",
+                ));
+            }
+            let mut manifest = Self::new(String::new());
+            manifest.workspace = workspace;
+            return Ok(manifest);
+        }
+
+        let package = record_field(root, "package")
+            .ok_or_else(|| doc.error(root.span(), "missing `package` record"))?;
+        let package = expect_record(doc, package, "package")?;
+        check_keys(doc, package, "package", &["name", "version", "author", "license", "description"])?;
+        let name = string_field(doc, package, "package", "name")?
+            .ok_or_else(|| doc.error(package.span(), "`package.name` is required"))?;
+        let version = string_field(doc, package, "package", "version")?;
+        if let Some(version) = &version {
+            if let Err(e) = semver::Version::parse(version) {
+                let span = record_field(package, "version").unwrap().span();
+                return Err(doc.error(span, format!("`package.version` is not a semver version: {}", e)));
+            }
+        }
+
         let mut dependencies = HashMap::new();
+        if let Some(deps) = record_field(root, "dependencies") {
+            for (key, value) in record_fields(expect_record(doc, deps, "dependencies")?) {
+                dependencies.insert(key.0.clone(), parse_dependency(doc, &key.0, value)?);
+            }
+        }
+
         let mut exports = Vec::new();
-        
-        let lines: Vec<&str> = content.lines().collect();
-        let mut i = 0;
-        
-        while i < lines.len() {
-            let line = lines[i].trim();
-            
-            if line.starts_with("package {") {
-                // Parse package block
-                i += 1;
-                while i < lines.len() && !lines[i].trim().starts_with("}") {
-                    let inner_line = lines[i].trim();
-                    if let Some(pos) = inner_line.find(':') {
-                        let key = inner_line[..pos].trim();
-                        let value = inner_line[pos+1..].trim().trim_matches('"');
-                        
-                        match key {
-                            "name" => package_name = value.to_string(),
-                            "version" => package_version = Some(value.to_string()),
-                            "author" => package_author = Some(value.to_string()),
-                            "license" => package_license = Some(value.to_string()),
-                            "description" => package_description = Some(value.to_string()),
-                            _ => {}
-                        }
-                    }
-                    i += 1;
-                }
-            } else if line.starts_with("dependencies {") {
-                // Parse dependencies block
-                i += 1;
-                while i < lines.len() && !lines[i].trim().starts_with("}") {
-                    let inner_line = lines[i].trim();
-                    if let Some(pos) = inner_line.find(':') {
-                        let name = inner_line[..pos].trim();
-                        let hash = inner_line[pos+1..].trim().trim_start_matches('#');
-                        dependencies.insert(name.to_string(), Dependency {
-                            name: name.to_string(),
-                            hash: hash.to_string(),
-                        });
-                    }
-                    i += 1;
-                }
-            } else if line.starts_with("exports {") {
-                // Parse exports block
-                i += 1;
-                while i < lines.len() && !lines[i].trim().starts_with("}") {
-                    let export = lines[i].trim();
-                    if !export.is_empty() {
-                        exports.push(export.to_string());
-                    }
-                    i += 1;
-                }
-            } else if line.starts_with("entry {") {
-                // Parse entry block
-                i += 1;
-                while i < lines.len() && !lines[i].trim().starts_with("}") {
-                    let inner_line = lines[i].trim();
-                    if let Some(pos) = inner_line.find(':') {
-                        let key = inner_line[..pos].trim();
-                        let value = inner_line[pos+1..].trim().trim_matches('"');
-                        
-                        match key {
-                            "main" => entry_main = Some(value.to_string()),
-                            "lib" => entry_lib = Some(value.to_string()),
-                            _ => {}
-                        }
-                    }
-                    i += 1;
+        if let Some(list) = record_field(root, "exports") {
+            let Expr::List(items, _) = list else {
+                return Err(doc.error(list.span(), "`exports` must be a list of names"));
+            };
+            for item in items {
+                match item {
+                    Expr::Ident(name, _) => exports.push(name.0.clone()),
+                    Expr::Literal(Literal::String(name), _) => exports.push(name.clone()),
+                    _ => return Err(doc.error(item.span(), "`exports` must be a list of names")),
                 }
             }
-            
-            i += 1;
         }
-        
-        // Create JSON structure
-        let manifest = PackageManifest {
+
+        let mut entry = EntryPoints { main: None, lib: None };
+        if let Some(record) = record_field(root, "entry") {
+            let record = expect_record(doc, record, "entry")?;
+            check_keys(doc, record, "entry", &["main", "lib"])?;
+            entry.main = string_field(doc, record, "entry", "main")?;
+            entry.lib = string_field(doc, record, "entry", "lib")?;
+        }
+
+        Ok(PackageManifest {
             package: PackageMetadata {
-                name: package_name,
-                version: package_version,
-                author: package_author,
-                license: package_license,
-                description: package_description,
+                name,
+                version,
+                author: string_field(doc, package, "package", "author")?,
+                license: string_field(doc, package, "package", "license")?,
+                description: string_field(doc, package, "package", "description")?,
             },
             dependencies,
             exports,
-            entry: EntryPoints {
-                main: entry_main,
-                lib: entry_lib,
-            },
-        };
-        
-        serde_json::to_string(&manifest)
-            .map_err(|e| PackageError::Parse(format!("Failed to serialize manifest: {}", e)))
+            entry,
+            workspace: None,
+            document: None,
+        })
+    }
+
+    /// The nearest `package.vibe` at or above `path`
+    pub fn find(path: &Path) -> Option<std::path::PathBuf> {
+        let start = if path.is_dir() { path } else { path.parent()? };
+        start.ancestors()
+            .map(|dir| dir.join("package.vibe"))
+            .find(|candidate| candidate.exists())
     }
 
     /// Save manifest to a file
@@ -163,57 +173,152 @@ impl PackageManifest {
     }
 
     /// Convert to Vibe syntax
+    ///
+    /// A manifest that was parsed from a file is written back by editing
+    /// that file's text, so comments and formatting of unchanged fields are
+    /// kept. Otherwise a fresh manifest is rendered.
     pub fn to_vibe_syntax(&self) -> String {
-        let mut result = String::new();
-        
-        // Package metadata
-        result.push_str("package {\n");
-        result.push_str(&format!("  name: \"{}\"\n", self.package.name));
-        if let Some(author) = &self.package.author {
-            result.push_str(&format!("  author: \"{}\"\n", author));
+        match &self.document {
+            Some(doc) => self.edit_document(doc),
+            None => format!("{}\n", self.syntax().render("")),
         }
-        if let Some(license) = &self.package.license {
-            result.push_str(&format!("  license: \"{}\"\n", license));
+    }
+
+    /// The whole manifest as syntax
+    fn syntax(&self) -> Syntax {
+        if self.is_workspace_root() {
+            return Syntax::Record(vec![("workspace".to_string(), Syntax::Record(self.workspace_fields()))]);
         }
-        if let Some(description) = &self.package.description {
-            result.push_str(&format!("  description: \"{}\"\n", description));
+        let mut fields = vec![("package".to_string(), Syntax::Record(self.package_fields()))];
+        if !self.dependencies.is_empty() {
+            fields.push(("dependencies".to_string(), Syntax::Record(self.dependency_fields())));
         }
-        result.push_str("}\n\n");
+        if !self.exports.is_empty() {
+            fields.push(("exports".to_string(), self.exports_syntax()));
+        }
+        let entry = self.entry_fields();
+        if !entry.is_empty() {
+            fields.push(("entry".to_string(), Syntax::Record(entry)));
+        }
+        Syntax::Record(fields)
+    }
 
-        // Dependencies
-        if !self.dependencies.is_empty() {
-            result.push_str("dependencies {\n");
-            for (name, dep) in &self.dependencies {
-                result.push_str(&format!("  {}: #{}\n", name, dep.hash));
+    fn package_fields(&self) -> Vec<(String, Syntax)> {
+        let package = &self.package;
+        let mut fields = vec![("name".to_string(), Syntax::Str(package.name.clone()))];
+        for (key, value) in [
+            ("version", &package.version),
+            ("author", &package.author),
+            ("license", &package.license),
+            ("description", &package.description),
+        ] {
+            if let Some(value) = value {
+                fields.push((key.to_string(), Syntax::Str(value.clone())));
             }
-            result.push_str("}\n\n");
         }
+        fields
+    }
 
-        // Exports
-        if !self.exports.is_empty() {
-            result.push_str("exports {\n");
-            for export in &self.exports {
-                result.push_str(&format!("  {}\n", export));
+    fn dependency_fields(&self) -> Vec<(String, Syntax)> {
+        let mut names: Vec<&String> = self.dependencies.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| (name.clone(), dependency_syntax(&self.dependencies[name])))
+            .collect()
+    }
+
+    fn exports_syntax(&self) -> Syntax {
+        Syntax::List(
+            self.exports
+                .iter()
+                .map(|name| match render_key(name) == *name {
+                    true => Syntax::Ident(name.clone()),
+                    false => Syntax::Str(name.clone()),
+                })
+                .collect(),
+        )
+    }
+
+    fn workspace_fields(&self) -> Vec<(String, Syntax)> {
+        let members = self.workspace.iter().flat_map(|w| &w.members);
+        vec![("members".to_string(), Syntax::List(members.map(|m| Syntax::Str(m.clone())).collect()))]
+    }
+
+    fn entry_fields(&self) -> Vec<(String, Syntax)> {
+        [("main", &self.entry.main), ("lib", &self.entry.lib)]
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), Syntax::Str(value.clone()?))))
+            .collect()
+    }
+
+    /// Apply the differences from what `doc` says to its source
+    fn edit_document(&self, doc: &ManifestDocument) -> String {
+        let original = Self::from_document(doc).expect("manifest document was validated when parsed");
+        let root = doc.expr();
+        let mut edits = doc.edits();
+
+        if let Some(workspace) = record_field(root, "workspace") {
+            sync_fields(&mut edits, workspace, original.workspace_fields(), self.workspace_fields());
+            return edits.finish();
+        }
+
+        let package = record_field(root, "package").expect("validated manifest has a package record");
+        sync_fields(&mut edits, package, original.package_fields(), self.package_fields());
+
+        match record_field(root, "dependencies") {
+            Some(deps) => sync_fields(&mut edits, deps, original.dependency_fields(), self.dependency_fields()),
+            None if !self.dependencies.is_empty() => {
+                edits.insert_field(root, "dependencies", &Syntax::Record(self.dependency_fields()))
             }
-            result.push_str("}\n\n");
+            None => {}
         }
 
-        // Entry points
-        result.push_str("entry {\n");
-        if let Some(main) = &self.entry.main {
-            result.push_str(&format!("  main: \"{}\"\n", main));
+        if original.exports != self.exports {
+            let old = (!original.exports.is_empty()).then(|| original.exports_syntax());
+            let new = (!self.exports.is_empty()).then(|| self.exports_syntax());
+            sync_field(&mut edits, root, "exports", old, new);
         }
-        if let Some(lib) = &self.entry.lib {
-            result.push_str(&format!("  lib: \"{}\"\n", lib));
+
+        match record_field(root, "entry") {
+            Some(entry) => sync_fields(&mut edits, entry, original.entry_fields(), self.entry_fields()),
+            None if !self.entry_fields().is_empty() => {
+                edits.insert_field(root, "entry", &Syntax::Record(self.entry_fields()))
+            }
+            None => {}
         }
-        result.push_str("}\n");
 
-        result
+        edits.finish()
     }
 
     /// Add a dependency
     pub fn add_dependency(&mut self, name: String, hash: String) {
-        self.dependencies.insert(name.clone(), Dependency { name, hash });
+        self.dependencies.insert(name.clone(), Dependency { name, hash, version: None, path: None });
+    }
+
+    /// Add a dependency on the workspace member in directory `path`
+    pub fn add_path_dependency(&mut self, name: String, path: String) {
+        self.dependencies.insert(name.clone(), Dependency {
+            name,
+            hash: String::new(),
+            version: None,
+            path: Some(path),
+        });
+    }
+
+    /// Add a dependency on any version matching a semver requirement
+    pub fn require(&mut self, name: String, version: String) {
+        self.dependencies.insert(name.clone(), Dependency {
+            name,
+            hash: String::new(),
+            version: Some(version),
+            path: None,
+        });
+    }
+
+    /// Remove a dependency, returning whether it was present
+    pub fn remove_dependency(&mut self, name: &str) -> bool {
+        self.dependencies.remove(name).is_some()
     }
 
     /// Add an export
@@ -224,6 +329,147 @@ impl PackageManifest {
     }
 }
 
+/// Dependency value: a range, a `#hash` pin, or a record with both; path
+/// dependencies are always records
+fn dependency_syntax(dep: &Dependency) -> Syntax {
+    if let Some(path) = &dep.path {
+        let mut fields = vec![("path".to_string(), Syntax::Str(path.clone()))];
+        if let Some(version) = &dep.version {
+            fields.push(("version".to_string(), Syntax::Str(version.clone())));
+        }
+        return Syntax::InlineRecord(fields);
+    }
+    match (&dep.version, dep.hash.is_empty()) {
+        (Some(version), true) => Syntax::Str(version.clone()),
+        (None, _) => Syntax::Str(format!("#{}", dep.hash)),
+        (Some(version), false) => Syntax::InlineRecord(vec![
+            ("version".to_string(), Syntax::Str(version.clone())),
+            ("hash".to_string(), Syntax::Str(format!("#{}", dep.hash))),
+        ]),
+    }
+}
+
+/// Edit the fields of `record` that differ between `old` and `new`
+fn sync_fields(edits: &mut ManifestEdits, record: &Expr, old: Vec<(String, Syntax)>, new: Vec<(String, Syntax)>) {
+    let lookup = |fields: &[(String, Syntax)], key: &str| {
+        fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+    };
+    for (key, _) in &old {
+        if lookup(&new, key).is_none() {
+            sync_field(edits, record, key, lookup(&old, key), None);
+        }
+    }
+    for (key, value) in &new {
+        sync_field(edits, record, key, lookup(&old, key), Some(value.clone()));
+    }
+}
+
+fn sync_field(edits: &mut ManifestEdits, record: &Expr, key: &str, old: Option<Syntax>, new: Option<Syntax>) {
+    match (old, new) {
+        (old, new) if old == new => {}
+        (Some(_), Some(new)) => edits.replace(record_field(record, key).unwrap(), &new),
+        (None, Some(new)) => edits.insert_field(record, key, &new),
+        (Some(_), None) => edits.remove_field(record, key),
+        (None, None) => {}
+    }
+}
+
+fn parse_workspace(doc: &ManifestDocument, value: &Expr) -> std::result::Result<WorkspaceConfig, ManifestError> {
+    let workspace = expect_record(doc, value, "workspace")?;
+    check_keys(doc, workspace, "workspace", &["members"])?;
+    let members = record_field(workspace, "members")
+        .ok_or_else(|| doc.error(workspace.span(), "`workspace.members` is required"))?;
+    let Expr::List(items, _) = members else {
+        return Err(doc.error(members.span(), "`workspace.members` must be a list of directories"));
+    };
+    let members = items
+        .iter()
+        .map(|item| match item {
+            Expr::Literal(Literal::String(dir), _) => Ok(dir.clone()),
+            _ => Err(doc.error(item.span(), "`workspace.members` must be a list of directories")),
+        })
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(WorkspaceConfig { members })
+}
+
+fn expect_record<'e>(doc: &ManifestDocument, expr: &'e Expr, path: &str) -> std::result::Result<&'e Expr, ManifestError> {
+    match expr {
+        Expr::RecordLiteral { .. } => Ok(expr),
+        _ => Err(doc.error(expr.span(), format!("`{}` must be a record", path))),
+    }
+}
+
+/// Reject fields of `record` that are not in `allowed`
+fn check_keys(doc: &ManifestDocument, record: &Expr, path: &str, allowed: &[&str]) -> std::result::Result<(), ManifestError> {
+    for (key, value) in record_fields(record) {
+        if !allowed.contains(&key.0.as_str()) {
+            return Err(doc.error(
+                &doc.key_span(value),
+                format!("unknown field `{}` in `{}`; expected one of {}", key.0, path, allowed.join(", ")),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn string_field(doc: &ManifestDocument, record: &Expr, path: &str, key: &str) -> std::result::Result<Option<String>, ManifestError> {
+    match record_field(record, key) {
+        None => Ok(None),
+        Some(Expr::Literal(Literal::String(s), _)) => Ok(Some(s.clone())),
+        Some(other) => Err(doc.error(other.span(), format!("`{}.{}` must be a string", path, key))),
+    }
+}
+
+fn parse_dependency(doc: &ManifestDocument, name: &str, value: &Expr) -> std::result::Result<Dependency, ManifestError> {
+    let path = format!("dependencies.{}", name);
+    let mut dependency = Dependency { name: name.to_string(), hash: String::new(), version: None, path: None };
+    match value {
+        Expr::Literal(Literal::String(s), span) => match s.strip_prefix('#') {
+            Some(hash) => dependency.hash = parse_hash(doc, span, &path, hash)?,
+            None => dependency.version = Some(parse_range(doc, span, &path, s)?),
+        },
+        Expr::RecordLiteral { .. } => {
+            check_keys(doc, value, &path, &["version", "hash", "path"])?;
+            dependency.path = string_field(doc, value, &path, "path")?;
+            if let Some(version) = string_field(doc, value, &path, "version")? {
+                let span = record_field(value, "version").unwrap().span();
+                dependency.version = Some(parse_range(doc, span, &path, &version)?);
+            }
+            if let Some(hash) = string_field(doc, value, &path, "hash")? {
+                let span = record_field(value, "hash").unwrap().span();
+                dependency.hash = parse_hash(doc, span, &path, hash.trim_start_matches('#'))?;
+            }
+            if dependency.path.is_some() && !dependency.hash.is_empty() {
+                let span = record_field(value, "hash").unwrap().span();
+                return Err(doc.error(span, format!("`{}` is a path dependency and cannot pin a hash", path)));
+            }
+            if dependency.version.is_none() && dependency.hash.is_empty() && dependency.path.is_none() {
+                return Err(doc.error(value.span(), format!("`{}` needs a `version`, a `hash` or a `path`", path)));
+            }
+        }
+        _ => {
+            return Err(doc.error(
+                value.span(),
+                format!("`{}` must be a version range like \"^1.2\", a hash like \"#a3f2\", or a record", path),
+            ))
+        }
+    }
+    Ok(dependency)
+}
+
+fn parse_range(doc: &ManifestDocument, span: &Span, path: &str, range: &str) -> std::result::Result<String, ManifestError> {
+    semver::VersionReq::parse(range)
+        .map(|_| range.to_string())
+        .map_err(|e| doc.error(span, format!("`{}`: invalid version range \"{}\": {}", path, range, e)))
+}
+
+fn parse_hash(doc: &ManifestDocument, span: &Span, path: &str, hash: &str) -> std::result::Result<String, ManifestError> {
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(doc.error(span, format!("`{}`: \"#{}\" is not a hexadecimal hash", path, hash)));
+    }
+    Ok(hash.to_lowercase())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,6 +498,133 @@ mod tests {
         let syntax = manifest.to_vibe_syntax();
         assert!(syntax.contains("name: \"web-framework\""));
         assert!(syntax.contains("author: \"vibe-community\""));
-        assert!(syntax.contains("http: #a3f2b1c4d5e6f7890"));
+        assert!(syntax.contains("http: \"#a3f2b1c4d5e6f7890\","));
+        assert_eq!(PackageManifest::parse(&syntax).unwrap().dependencies, manifest.dependencies);
+    }
+
+    #[test]
+    fn test_version_requirements_round_trip() {
+        let mut manifest = PackageManifest::new("app".to_string());
+        manifest.package.version = Some("0.1.0".to_string());
+        manifest.require("math-utils".to_string(), "^1.2".to_string());
+        manifest.add_dependency("http".to_string(), "abc123".to_string());
+
+        let parsed = PackageManifest::parse(&manifest.to_vibe_syntax()).unwrap();
+        assert_eq!(parsed.package.version.as_deref(), Some("0.1.0"));
+        assert_eq!(parsed.dependencies["math-utils"].version.as_deref(), Some("^1.2"));
+        assert_eq!(parsed.dependencies["math-utils"].hash, "");
+        assert_eq!(parsed.dependencies["http"].hash, "abc123");
+        assert_eq!(parsed.dependencies["http"].version, None);
+
+        manifest.remove_dependency("http");
+        assert!(!manifest.to_vibe_syntax().contains("http"));
+    }
+
+    const COMMENTED: &str = r##"# Application manifest
+{
+  package: {
+    name: "app"
+    version: "0.1.0"  # bumped by release tooling
+  }
+
+  dependencies: {
+    # numeric helpers
+    math: "^1.2"
+    "json-lite": { version: "^0.3", hash: "#abc" }
+  }
+
+  exports: [main]
+}
+"##;
+
+    #[test]
+    fn test_edits_preserve_comments() {
+        let mut manifest = PackageManifest::parse(COMMENTED).unwrap();
+        assert_eq!(manifest.dependencies["json-lite"].hash, "abc");
+        assert_eq!(manifest.to_vibe_syntax(), COMMENTED);
+
+        manifest.require("http".to_string(), "^2".to_string());
+        manifest.remove_dependency("json-lite");
+        manifest.entry.main = Some("src/main.vibe".to_string());
+        let edited = manifest.to_vibe_syntax();
+        assert_eq!(
+            edited,
+            COMMENTED
+                .replace("    \"json-lite\": { version: \"^0.3\", hash: \"#abc\" }\n", "    http: \"^2\"\n")
+                .replace("  exports: [main]\n", "  exports: [main]\n  entry: {\n    main: \"src/main.vibe\",\n  }\n")
+        );
+
+        let reparsed = PackageManifest::parse(&edited).unwrap();
+        assert_eq!(reparsed.dependencies, manifest.dependencies);
+        assert_eq!(reparsed.entry, manifest.entry);
+    }
+
+    fn parse_error(content: &str) -> String {
+        match PackageManifest::parse(content) {
+            Err(PackageError::Manifest(error)) => error.to_string(),
+            other => panic!("expected a manifest error, got {:?}", other.map(|m| m.package)),
+        }
+    }
+
+    #[test]
+    fn test_schema_errors_point_at_the_field() {
+        assert_eq!(
+            parse_error("{\n  package: { name: \"a\" }\n  exprots: [main]\n}"),
+            "3:3: unknown field `exprots` in `manifest`; expected one of package, dependencies, exports, entry, workspace"
+        );
+        assert_eq!(
+            parse_error("{ package: { version: \"1.0.0\" } }"),
+            "1:12: `package.name` is required"
+        );
+        assert_eq!(
+            parse_error("{ package: { name: \"a\", version: \"one\" } }"),
+            "1:34: `package.version` is not a semver version: unexpected character 'o' while parsing major version number"
+        );
+        assert!(parse_error("{ package: { name: \"a\" }, dependencies: {\n  math: \"^^1\"\n} }")
+            .starts_with("2:9: `dependencies.math`: invalid version range \"^^1\""));
+        assert_eq!(
+            parse_error("{ package: { name: \"a\" }, dependencies: { math: \"#xyz\" } }"),
+            "1:49: `dependencies.math`: \"#xyz\" is not a hexadecimal hash"
+        );
+        assert_eq!(
+            parse_error("{ package: { name: 1 } }"),
+            "1:20: `package.name` must be a string"
+        );
+    }
+
+    #[test]
+    fn test_workspace_root_and_path_dependencies() {
+        let root = PackageManifest::parse("# all of it\n{ workspace: { members: [\"core\"] } }\n").unwrap();
+        assert!(root.is_workspace_root());
+        let mut edited = root.clone();
+        edited.workspace.as_mut().unwrap().members.push("apps/*".to_string());
+        assert_eq!(
+            edited.to_vibe_syntax(),
+            "# all of it\n{ workspace: { members: [\"core\", \"apps/*\"] } }\n"
+        );
+        assert_eq!(
+            parse_error("{ workspace: { members: [] }, package: { name: \"a\" } }"),
+            "1:31: a workspace root is not a package; move `package` into a member"
+        );
+
+        let mut member = PackageManifest::new("app".to_string());
+        member.add_path_dependency("core".to_string(), "../core".to_string());
+        let syntax = member.to_vibe_syntax();
+        assert!(syntax.contains("core: { path: \"../core\" },"), "{syntax}");
+        let parsed = PackageManifest::parse(&syntax).unwrap();
+        assert!(parsed.dependencies["core"].is_local());
+        assert_eq!(parsed.dependencies, member.dependencies);
+    }
+
+    #[test]
+    fn test_load_error_names_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("package.vibe");
+        fs::write(&path, "{ package: { name: \"a\" }, entry: [] }").unwrap();
+        let error = PackageManifest::load_from_file(&path).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!("Invalid manifest: {}:1:34: `entry` must be a record", path.display())
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,683 @@
+//! `package.vibe` as a Vibe record expression
+//!
+//! A manifest is a single record literal, read with the Vibe parser:
+//!
+//! ```vibe
+//! # Package metadata
+//! {
+//!   package: { name: "app", version: "0.1.0" },
+//!   dependencies: {
+//!     math: "^1.2",          # semver range
+//!     "json-lite": "#a3f2b1", # content hash
+//!   },
+//!   exports: [main],
+//!   entry: { main: "src/main.vibe" },
+//! }
+//! ```
+//!
+//! Fields may be separated by commas or newlines, and field names that are
+//! not identifiers are quoted. [`ManifestDocument`] keeps the source next to
+//! the parsed [`Expr`] so that [`ManifestEdits`] can change individual
+//! fields in place, leaving comments and layout elsewhere untouched.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use vibe_language::{Expr, Ident, Span, XsError};
+
+/// A manifest error at a position in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestError {
+    pub message: String,
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+    pub path: Option<PathBuf>,
+}
+
+impl ManifestError {
+    fn new(source: &[char], span: Span, message: String) -> Self {
+        let before = &source[..span.start.min(source.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        ManifestError {
+            message,
+            span,
+            line,
+            column,
+            path: None,
+        }
+    }
+
+    /// Attribute the error to a file
+    pub fn in_file(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(
+                f,
+                "{}:{}:{}: {}",
+                path.display(),
+                self.line,
+                self.column,
+                self.message
+            ),
+            None => write!(f, "{}:{}: {}", self.line, self.column, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// A parsed manifest together with its source
+#[derive(Debug, Clone)]
+pub struct ManifestDocument {
+    chars: Vec<char>,
+    expr: Expr,
+    /// Start of each field's name, keyed by the start of its value
+    key_starts: HashMap<usize, usize>,
+}
+
+impl ManifestDocument {
+    /// Parse a manifest; the whole file must be one record literal
+    pub fn parse(source: &str) -> Result<Self, ManifestError> {
+        let chars: Vec<char> = source.chars().collect();
+        let expr = match vibe_language::parser::parse(source) {
+            Ok(expr) => expr,
+            Err(XsError::ParseError(position, message)) => {
+                let position = source[..position.min(source.len())].chars().count();
+                return Err(syntax_error(&chars, position, &message));
+            }
+            Err(e) => return Err(ManifestError::new(&chars, Span::new(0, 0), e.to_string())),
+        };
+        let expr = match expr {
+            Expr::RecordLiteral { .. } => in_chars(source, &chars, &expr)?,
+            Expr::Block { exprs, .. } if exprs.is_empty() => {
+                return Err(ManifestError::new(
+                    &chars,
+                    Span::new(chars.len(), chars.len()),
+                    "empty manifest, expected a record".to_string(),
+                ))
+            }
+            _ => {
+                return Err(ManifestError::new(
+                    &chars,
+                    char_span(source, expr.span()),
+                    "a manifest is a record, expected `{`".to_string(),
+                ))
+            }
+        };
+
+        let mut document = ManifestDocument {
+            chars,
+            expr: expr.clone(),
+            key_starts: HashMap::new(),
+        };
+        document.find_keys(&expr)?;
+        Ok(document)
+    }
+
+    /// Record where the name of each field starts, and check that no record
+    /// repeats a field
+    fn find_keys(&mut self, expr: &Expr) -> Result<(), ManifestError> {
+        match expr {
+            Expr::List(items, _) => items.iter().try_for_each(|item| self.find_keys(item)),
+            Expr::RecordLiteral { fields, .. } => {
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    let colon = self.chars[..value.span().start]
+                        .iter()
+                        .rposition(|c| *c == ':')
+                        .unwrap_or(0);
+                    let mut start = colon;
+                    while start > 0 && self.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    if start > 0 && self.chars[start - 1] == '"' {
+                        start -= 1;
+                        while start > 0 && self.chars[start - 1] != '"' {
+                            start -= 1;
+                        }
+                        start = start.saturating_sub(1);
+                    } else {
+                        while start > 0
+                            && (self.chars[start - 1].is_alphanumeric()
+                                || self.chars[start - 1] == '_')
+                        {
+                            start -= 1;
+                        }
+                    }
+                    self.key_starts.insert(value.span().start, start);
+                    if fields[..index].iter().any(|(other, _)| other == name) {
+                        let span = self.key_span(value);
+                        return Err(self.error(&span, format!("duplicate field `{}`", name.0)));
+                    }
+                    self.find_keys(value)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The manifest record
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// The original source
+    pub fn source(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// An error located at `span`
+    pub fn error(&self, span: &Span, message: impl Into<String>) -> ManifestError {
+        ManifestError::new(&self.chars, span.clone(), message.into())
+    }
+
+    /// Span of a field's name, given its value
+    pub fn key_span(&self, value: &Expr) -> Span {
+        let start = self.key_starts[&value.span().start];
+        let mut end = start;
+        if self.chars[start] == '"' {
+            end += 1;
+            while end < self.chars.len() && self.chars[end] != '"' {
+                end += 1;
+            }
+            end += 1;
+        } else {
+            while end < self.chars.len()
+                && (self.chars[end].is_alphanumeric() || self.chars[end] == '_')
+            {
+                end += 1;
+            }
+        }
+        Span::new(start, end)
+    }
+
+    /// Start editing the source
+    pub fn edits(&self) -> ManifestEdits<'_> {
+        ManifestEdits {
+            document: self,
+            edits: Vec::new(),
+            commas_added: HashSet::new(),
+        }
+    }
+
+    fn field_start(&self, value: &Expr) -> usize {
+        self.key_starts[&value.span().start]
+    }
+
+    fn line_start(&self, position: usize) -> usize {
+        self.chars[..position]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Leading whitespace of the line containing `position`
+    fn indentation(&self, position: usize) -> String {
+        self.chars[self.line_start(position)..]
+            .iter()
+            .take_while(|c| **c == ' ' || **c == '\t')
+            .collect()
+    }
+
+    /// Whether only separators and a comment follow `position` on its line
+    fn rest_of_line_is_trivia(&self, position: usize) -> bool {
+        for c in &self.chars[position..] {
+            match c {
+                '\n' | '#' => return true,
+                ' ' | '\t' | '\r' | ',' => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// The position just after the end of the line containing `position`
+    fn next_line(&self, position: usize) -> Option<usize> {
+        self.chars[position..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map(|i| position + i + 1)
+    }
+
+    /// Whether the text between `start` and `end`, ignoring comments,
+    /// contains a comma
+    fn has_comma(&self, start: usize, end: usize) -> bool {
+        let mut in_comment = false;
+        for c in &self.chars[start..end] {
+            match c {
+                '#' => in_comment = true,
+                '\n' => in_comment = false,
+                ',' if !in_comment => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+/// Fields of a record literal
+pub fn record_fields(expr: &Expr) -> &[(Ident, Expr)] {
+    match expr {
+        Expr::RecordLiteral { fields, .. } => fields,
+        _ => &[],
+    }
+}
+
+/// The value of a field in a record literal
+pub fn record_field<'e>(record: &'e Expr, key: &str) -> Option<&'e Expr> {
+    record_fields(record)
+        .iter()
+        .find(|(name, _)| name.0 == key)
+        .map(|(_, value)| value)
+}
+
+/// A manifest error for the parse error `message` at `position`
+fn syntax_error(chars: &[char], position: usize, message: &str) -> ManifestError {
+    let message = message.lines().next().unwrap_or_default();
+    let message = message.split_once("]: ").map_or(message, |(_, rest)| rest);
+
+    // A field name with a dash reads as a subtraction, so the parser stops
+    // at the colon after it
+    if chars.get(position) == Some(&':') {
+        let start = chars[..position]
+            .iter()
+            .rposition(|c| c.is_whitespace() || matches!(c, '{' | ','))
+            .map_or(0, |i| i + 1);
+        let name: String = chars[start..position].iter().collect();
+        if !name.is_empty() && render_key(&name) != name {
+            return ManifestError::new(
+                chars,
+                Span::new(start, position),
+                format!(
+                    "field name `{}` is not an identifier; quote names that are not \
+                     identifiers, like {}",
+                    name,
+                    quote(&name)
+                ),
+            );
+        }
+    }
+    ManifestError::new(
+        chars,
+        Span::new(position, position + 1),
+        message.to_string(),
+    )
+}
+
+/// `span`, counted in bytes of `source`, counted in characters
+fn char_span(source: &str, span: &Span) -> Span {
+    let start = source[..span.start].chars().count();
+    Span::new(start, start + source[span.start..span.end].chars().count())
+}
+
+/// A manifest value with its spans counted in characters rather than bytes
+fn in_chars(source: &str, chars: &[char], expr: &Expr) -> Result<Expr, ManifestError> {
+    let span = char_span(source, expr.span());
+    Ok(match expr {
+        Expr::Literal(literal, _) => Expr::Literal(literal.clone(), span),
+        Expr::Ident(name, _) => Expr::Ident(name.clone(), span),
+        Expr::List(items, _) => Expr::List(
+            items
+                .iter()
+                .map(|item| in_chars(source, chars, item))
+                .collect::<Result<_, _>>()?,
+            span,
+        ),
+        Expr::RecordLiteral { fields, .. } => Expr::RecordLiteral {
+            fields: fields
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), in_chars(source, chars, value)?)))
+                .collect::<Result<_, ManifestError>>()?,
+            span,
+        },
+        _ => {
+            return Err(ManifestError::new(
+                chars,
+                span,
+                "expected a value".to_string(),
+            ))
+        }
+    })
+}
+
+/// Manifest syntax to write out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Syntax {
+    Str(String),
+    Ident(String),
+    /// Rendered on one line
+    List(Vec<Syntax>),
+    /// Rendered one field per line
+    Record(Vec<(String, Syntax)>),
+    /// Rendered on one line
+    InlineRecord(Vec<(String, Syntax)>),
+}
+
+impl Syntax {
+    /// Render at the given indentation; nested lines are indented by two
+    /// more spaces
+    pub fn render(&self, indent: &str) -> String {
+        match self {
+            Syntax::Str(s) => quote(s),
+            Syntax::Ident(name) => name.clone(),
+            Syntax::List(items) => {
+                let items: Vec<String> = items.iter().map(|i| i.render(indent)).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Syntax::Record(fields) if fields.is_empty() => "{}".to_string(),
+            Syntax::Record(fields) => {
+                let inner = format!("{}  ", indent);
+                let mut out = String::from("{\n");
+                for (key, value) in fields {
+                    out.push_str(&format!(
+                        "{}{}: {},\n",
+                        inner,
+                        render_key(key),
+                        value.render(&inner)
+                    ));
+                }
+                out.push_str(indent);
+                out.push('}');
+                out
+            }
+            Syntax::InlineRecord(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", render_key(key), value.render(indent)))
+                    .collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+    }
+}
+
+/// A field name, quoted unless it is an identifier
+pub fn render_key(key: &str) -> String {
+    let identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if identifier {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// In-place changes to a manifest's source
+pub struct ManifestEdits<'d> {
+    document: &'d ManifestDocument,
+    /// Replacements of character ranges, in the order they were made
+    edits: Vec<(usize, usize, String)>,
+    /// Records whose last field already gained a separating comma
+    commas_added: HashSet<usize>,
+}
+
+impl ManifestEdits<'_> {
+    /// Replace a value
+    pub fn replace(&mut self, value: &Expr, syntax: &Syntax) {
+        let span = value.span();
+        let indent = self.document.indentation(span.start);
+        self.edits
+            .push((span.start, span.end, syntax.render(&indent)));
+    }
+
+    /// Remove a field, together with its line when it has one to itself
+    pub fn remove_field(&mut self, record: &Expr, key: &str) {
+        let fields = record_fields(record);
+        let Some(index) = fields.iter().position(|(name, _)| name.0 == key) else {
+            return;
+        };
+        let doc = self.document;
+        let value = &fields[index].1;
+        let start = doc.field_start(value);
+        let end = value.span().end;
+
+        let line_start = doc.line_start(start);
+        let alone = doc.chars[line_start..start]
+            .iter()
+            .all(|c| *c == ' ' || *c == '\t')
+            && doc.rest_of_line_is_trivia(end);
+        if alone {
+            let line_end = doc.next_line(end).unwrap_or(doc.chars.len());
+            self.edits.push((line_start, line_end, String::new()));
+            return;
+        }
+
+        // Inline: take the following separator, or the preceding one for
+        // the last field
+        match fields.get(index + 1) {
+            Some((_, next)) => {
+                let next_start = doc.field_start(next);
+                self.edits.push((start, next_start, String::new()));
+            }
+            None if index > 0 => {
+                let previous_end = fields[index - 1].1.span().end;
+                self.edits.push((previous_end, end, String::new()));
+            }
+            None => self.edits.push((start, end, String::new())),
+        }
+    }
+
+    /// Add a field after the last one in `record`
+    pub fn insert_field(&mut self, record: &Expr, key: &str, syntax: &Syntax) {
+        let doc = self.document;
+        let span = record.span();
+        let close = span.end - 1;
+        let fields = record_fields(record);
+        let key = render_key(key);
+
+        let Some((_, last)) = fields.last() else {
+            // An empty record is rewritten as a one-field record
+            let indent = doc.indentation(span.start);
+            let inner = format!("{}  ", indent);
+            let text = if doc.chars[span.start..span.end].contains(&'\n') || doc.expr.span() == span
+            {
+                format!(
+                    "{{\n{}{}: {},\n{}}}",
+                    inner,
+                    key,
+                    syntax.render(&inner),
+                    indent
+                )
+            } else {
+                format!("{{ {}: {} }}", key, syntax.render(&indent))
+            };
+            self.edits.push((span.start, span.end, text));
+            return;
+        };
+
+        let last_end = last.span().end;
+        let trailing_comma = doc.has_comma(last_end, close);
+        let uses_commas = trailing_comma
+            || fields
+                .windows(2)
+                .any(|pair| doc.has_comma(pair[0].1.span().end, doc.field_start(&pair[1].1)));
+
+        let multiline = doc.next_line(last_end).is_some_and(|next| next <= close);
+        if multiline {
+            let line = doc.next_line(last_end).unwrap();
+            let indent = doc.indentation(doc.field_start(last));
+            let comma = if uses_commas { "," } else { "" };
+            if uses_commas && !trailing_comma && self.commas_added.insert(span.start) {
+                self.edits.push((last_end, last_end, ",".to_string()));
+            }
+            self.edits.push((
+                line,
+                line,
+                format!("{}{}: {}{}\n", indent, key, syntax.render(&indent), comma),
+            ));
+        } else {
+            let indent = doc.indentation(span.start);
+            self.edits.push((
+                last_end,
+                last_end,
+                format!(", {}: {}", key, syntax.render(&indent)),
+            ));
+        }
+    }
+
+    /// The edited source
+    pub fn finish(self) -> String {
+        let mut chars = self.document.chars.clone();
+        let mut edits: Vec<(usize, (usize, usize, String))> =
+            self.edits.into_iter().enumerate().collect();
+        // Apply from the end so earlier positions stay valid; of two edits
+        // at one position, the later one goes first and ends up after
+        edits.sort_by(|(ia, a), (ib, b)| b.0.cmp(&a.0).then(ib.cmp(ia)));
+        for (_, (start, end, text)) in edits {
+            chars.splice(start..end, text.chars());
+        }
+        chars.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::Literal;
+
+    const MANIFEST: &str = r#"# The app
+{
+  package: {
+    name: "app"   # shown in search
+    version: "0.1.0"
+  },
+  dependencies: {
+    math: "^1.2",
+    "json-lite": { version: "^0.3", hash: "abc" },
+  },
+  exports: [main, helper],
+}
+"#;
+
+    #[test]
+    fn test_parse_record() {
+        let doc = ManifestDocument::parse(MANIFEST).unwrap();
+        let package = record_field(doc.expr(), "package").unwrap();
+        assert!(matches!(
+            record_field(package, "version"),
+            Some(Expr::Literal(Literal::String(v), _)) if v == "0.1.0"
+        ));
+        let deps = record_field(doc.expr(), "dependencies").unwrap();
+        let json = record_field(deps, "json-lite").unwrap();
+        assert_eq!(record_fields(json).len(), 2);
+        assert!(
+            matches!(record_field(doc.expr(), "exports"), Some(Expr::List(items, _)) if items.len() == 2)
+        );
+
+        let span = doc.key_span(record_field(package, "name").unwrap());
+        let source = doc.source();
+        assert_eq!(&source[span.start..span.end], "name");
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let error =
+            ManifestDocument::parse("{\n  package: { name: \"a\" }\n  math-utils: \"^1\"\n}")
+                .unwrap_err();
+        assert_eq!((error.line, error.column), (3, 3));
+        assert!(error.message.contains("quote names"), "{}", error.message);
+
+        let error = ManifestDocument::parse("{ a: 1, a: 2 }").unwrap_err();
+        assert_eq!(error.to_string(), "1:9: duplicate field `a`");
+
+        let error = ManifestDocument::parse("package {}").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:1: a manifest is a record, expected `{`"
+        );
+
+        let error = ManifestDocument::parse("{ a: 1 ").unwrap_err();
+        assert_eq!(error.to_string(), "1:1: Unclosed `{`");
+
+        let error = ManifestDocument::parse("{ a: 1 + 2 }").unwrap_err();
+        assert_eq!(error.to_string(), "1:6: expected a value");
+    }
+
+    #[test]
+    fn test_edits_keep_comments_and_layout() {
+        let doc = ManifestDocument::parse(MANIFEST).unwrap();
+        let package = record_field(doc.expr(), "package").unwrap();
+        let deps = record_field(doc.expr(), "dependencies").unwrap();
+
+        let mut edits = doc.edits();
+        edits.replace(
+            record_field(package, "version").unwrap(),
+            &Syntax::Str("0.2.0".to_string()),
+        );
+        edits.insert_field(package, "license", &Syntax::Str("MIT".to_string()));
+        edits.remove_field(deps, "math");
+        edits.insert_field(deps, "http", &Syntax::Str("^2".to_string()));
+        edits.insert_field(
+            doc.expr(),
+            "entry",
+            &Syntax::Record(vec![(
+                "main".to_string(),
+                Syntax::Str("src/main.vibe".to_string()),
+            )]),
+        );
+
+        assert_eq!(
+            edits.finish(),
+            r#"# The app
+{
+  package: {
+    name: "app"   # shown in search
+    version: "0.2.0"
+    license: "MIT"
+  },
+  dependencies: {
+    "json-lite": { version: "^0.3", hash: "abc" },
+    http: "^2",
+  },
+  exports: [main, helper],
+  entry: {
+    main: "src/main.vibe",
+  },
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_inline_edits() {
+        let doc =
+            ManifestDocument::parse("{ package: { name: \"a\", version: \"1.0.0\" }, entry: {} }")
+                .unwrap();
+        let package = record_field(doc.expr(), "package").unwrap();
+        let entry = record_field(doc.expr(), "entry").unwrap();
+        let mut edits = doc.edits();
+        edits.remove_field(package, "version");
+        edits.insert_field(package, "license", &Syntax::Str("MIT".to_string()));
+        edits.insert_field(entry, "lib", &Syntax::Str("lib.vibe".to_string()));
+        assert_eq!(
+            edits.finish(),
+            "{ package: { name: \"a\", license: \"MIT\" }, entry: { lib: \"lib.vibe\" } }"
+        );
+    }
+}
@@ -4,10 +4,15 @@
 //! including manifest handling, caching, registry access, and dependency resolution.
 
 pub mod manifest;
+pub mod manifest_syntax;
 pub mod hash;
 pub mod cache;
 pub mod registry;
 pub mod resolver;
+pub mod lockfile;
+pub mod install;
+pub mod modules;
+pub mod workspace;
 pub mod utils;
 
 use sha2::{Sha256, Digest};
@@ -52,7 +57,7 @@ pub struct PackageId {
 }
 
 /// Package metadata
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct PackageMetadata {
     pub name: String,
     pub author: Option<String>,
@@ -62,14 +67,37 @@ pub struct PackageMetadata {
 }
 
 /// Dependency specification
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Dependency {
     pub name: String,
-    pub hash: String, // Hex string representation
+    #[serde(default)]
+    pub hash: String, // Hex string representation, empty when only a version range is given
+    /// Semver requirement such as `^1.2`
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Directory of a workspace member, relative to the depending package
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl Dependency {
+    /// Whether this dependency is a workspace member rather than a package
+    /// from the cache or registry
+    pub fn is_local(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+/// `workspace` section of a workspace root manifest
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WorkspaceConfig {
+    /// Member directories relative to the root; `dir/*` names every
+    /// subdirectory of `dir` that has a package.vibe
+    pub members: Vec<String>,
 }
 
 /// Entry points
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct EntryPoints {
     pub main: Option<String>,
     pub lib: Option<String>,
@@ -89,12 +117,18 @@ pub enum PackageError {
     
     #[error("Parse error: {0}")]
     Parse(String),
+
+    #[error("Invalid manifest: {0}")]
+    Manifest(manifest_syntax::ManifestError),
     
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
     
     #[error("Cache error: {0}")]
     Cache(String),
+    
+    #[error("Resolution error: {0}")]
+    Resolution(String),
 }
 
 pub type Result<T> = std::result::Result<T, PackageError>;
\ No newline at end of file
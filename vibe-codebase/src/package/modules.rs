@@ -0,0 +1,327 @@
+//! Installed packages as modules
+//!
+//! Each package in a lockfile is loaded from the cache as a module named
+//! after the package. Its library entry is type checked and evaluated, and
+//! its exports are registered in a [`ModuleEnv`] for the type checker and
+//! bound as `Package.name` values for the interpreter, so that
+//! `math.double 21` checks and runs like a builtin module function.
+//!
+//! Inside a workspace, the members a package depends on by path are loaded
+//! the same way from their directories, after the locked packages.
+
+use super::{
+    cache::PackageCache,
+    lockfile::{LockedPackage, Lockfile},
+    manifest::PackageManifest,
+    workspace::Workspace,
+    PackageError, PackageHash, Result,
+};
+use std::fs;
+use std::path::Path;
+use vibe_compiler::{ModuleEnv, ModuleInfo, TypeChecker, TypeEnv};
+use vibe_language::{Environment, Expr, Ident, Value};
+use vibe_runtime::Interpreter;
+
+/// Library entry used when a manifest does not name one
+const DEFAULT_LIB: &str = "src/lib.vibe";
+
+/// The modules of every installed package
+pub struct PackageModules {
+    modules: ModuleEnv,
+    /// Exported values keyed by qualified name
+    values: Vec<(String, Value)>,
+}
+
+impl PackageModules {
+    /// Load every package in `lockfile` from `cache`
+    pub fn load(lockfile: &Lockfile, cache: &PackageCache) -> Result<Self> {
+        let mut loaded = PackageModules {
+            modules: ModuleEnv::new(),
+            values: Vec::new(),
+        };
+        for package in lockfile.install_order()? {
+            loaded.load_package(package, cache)?;
+        }
+        Ok(loaded)
+    }
+
+    /// Load the packages locked for the project containing `path`, if it
+    /// belongs to one with a lockfile or to a workspace
+    pub fn load_for_path(path: &Path, cache: &PackageCache) -> Result<Option<Self>> {
+        if let Some(workspace) = Workspace::find(path)? {
+            return match workspace.member_for_path(path) {
+                Some(member) => Self::load_for_member(&workspace, &member.name, cache).map(Some),
+                None => Ok(None),
+            };
+        }
+        let Some(manifest_path) = PackageManifest::find(path) else {
+            return Ok(None);
+        };
+        match Lockfile::load(&Lockfile::path_for(&manifest_path))? {
+            Some(lockfile) => Self::load(&lockfile, cache).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Load what the workspace member `name` can use: the workspace's locked
+    /// packages, then the members it depends on
+    pub fn load_for_member(workspace: &Workspace, name: &str, cache: &PackageCache) -> Result<Self> {
+        let mut loaded = match Lockfile::load(&workspace.lockfile_path())? {
+            Some(lockfile) => Self::load(&lockfile, cache)?,
+            None => Self::load(&Lockfile::default(), cache)?,
+        };
+        for member in workspace.dependencies_of(name) {
+            loaded.load_dir(&member.name, &member.dir)?;
+        }
+        Ok(loaded)
+    }
+
+    /// Module information for the type checker
+    pub fn module_env(&self) -> &ModuleEnv {
+        &self.modules
+    }
+
+    /// A type environment with every package module imported
+    pub fn type_env(&self) -> TypeEnv {
+        let mut env = TypeEnv::default();
+        env.import_modules(&self.modules);
+        env
+    }
+
+    /// `env` extended with the qualified exports of every package
+    pub fn environment(&self, env: &Environment) -> Environment {
+        self.values.iter().fold(env.clone(), |env, (name, value)| {
+            env.extend(Ident(name.clone()), value.clone())
+        })
+    }
+
+    /// The value of an export
+    pub fn value(&self, module: &str, name: &str) -> Option<&Value> {
+        let qualified = format!("{}.{}", module, name);
+        self.values
+            .iter()
+            .find(|(n, _)| *n == qualified)
+            .map(|(_, v)| v)
+    }
+
+    fn load_package(&mut self, package: &LockedPackage, cache: &PackageCache) -> Result<()> {
+        let package_dir = cache.get_package(&PackageHash::from_hex(&package.hash)?)?;
+        self.load_dir(&package.name, &package_dir)
+    }
+
+    /// Load the package in `package_dir` as the module `name`
+    fn load_dir(&mut self, name: &str, package_dir: &Path) -> Result<()> {
+        let manifest = PackageManifest::load_from_file(&package_dir.join("package.vibe"))?;
+        let lib = manifest.entry.lib.as_deref().unwrap_or(DEFAULT_LIB);
+        let source = fs::read_to_string(package_dir.join(lib)).map_err(|e| {
+            PackageError::NotFound(format!(
+                "{}: cannot read library {}: {}",
+                name, lib, e
+            ))
+        })?;
+        let expr = vibe_language::parser::parse(&source)
+            .map_err(|e| PackageError::Parse(format!("{}/{}: {}", name, lib, e)))?;
+
+        // Packages may refer to the packages loaded before them
+        let mut checker = TypeChecker::new();
+        let mut type_env = self.type_env();
+        let mut interpreter = Interpreter::new();
+        let mut env = self.environment(&Interpreter::create_initial_env());
+
+        let mut module = ModuleInfo::new(name.to_string());
+        let mut defined = Vec::new();
+        for definition in top_level_definitions(&expr) {
+            let definition_name = match definition {
+                Expr::Let { name, .. } | Expr::LetRec { name, .. } => name.clone(),
+                _ => continue,
+            };
+            checker
+                .check(definition, &mut type_env)
+                .map_err(|e| type_error(name, &definition_name, e))?;
+            let scheme = type_env
+                .lookup(&definition_name.0)
+                .cloned()
+                .ok_or_else(|| type_error(name, &definition_name, "no type inferred".to_string()))?;
+            let value = interpreter
+                .eval(definition, &env)
+                .map_err(|e| PackageError::Parse(format!("{}.{}: {}", name, definition_name.0, e)))?;
+            env = env.extend(definition_name.clone(), value.clone());
+            module.add_export(definition_name.0.clone(), scheme);
+            defined.push((definition_name.0, value));
+        }
+
+        let exports: Vec<String> = if manifest.exports.is_empty() {
+            defined.iter().map(|(name, _)| name.clone()).collect()
+        } else {
+            manifest.exports.clone()
+        };
+        for export in &exports {
+            if !module.is_exported(export) {
+                return Err(PackageError::Parse(format!(
+                    "{} exports {}, which {} does not define",
+                    name, export, lib
+                )));
+            }
+        }
+        module.exports.retain(|name, _| exports.contains(name));
+        for (export, value) in defined {
+            if exports.contains(&export) {
+                self.values.push((format!("{}.{}", name, export), value));
+            }
+        }
+        self.modules.register_module(module);
+        Ok(())
+    }
+}
+
+fn type_error(package: &str, name: &Ident, error: String) -> PackageError {
+    PackageError::Parse(format!(
+        "{}.{}: type error: {}",
+        package, name.0, error
+    ))
+}
+
+/// The definitions at the top level of a parsed file
+fn top_level_definitions(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Block { exprs, .. } => exprs.iter().collect(),
+        other => vec![other],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::hash::calculate_package_hash;
+    use tempfile::TempDir;
+    use vibe_language::{Literal, Span, Type};
+
+    fn install(
+        cache: &mut PackageCache,
+        root: &Path,
+        name: &str,
+        exports: &[&str],
+        lib: &str,
+    ) -> LockedPackage {
+        let package_dir = root.join(name);
+        fs::create_dir_all(package_dir.join("src")).unwrap();
+        let mut manifest = PackageManifest::new(name.to_string());
+        manifest.package.version = Some("1.0.0".to_string());
+        for export in exports {
+            manifest.add_export(export.to_string());
+        }
+        manifest
+            .save_to_file(&package_dir.join("package.vibe"))
+            .unwrap();
+        fs::write(package_dir.join("src/lib.vibe"), lib).unwrap();
+        let hash = calculate_package_hash(&package_dir).unwrap();
+        cache.store_package(&hash, &package_dir).unwrap();
+        LockedPackage {
+            name: name.to_string(),
+            version: Some("1.0.0".to_string()),
+            hash: hash.to_hex(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// `module.name`, as field access on the module name is parsed
+    fn qualified(module: &str, name: &str) -> Expr {
+        Expr::RecordAccess {
+            record: Box::new(Expr::Ident(Ident(module.to_string()), Span::new(0, 0))),
+            field: Ident(name.to_string()),
+            span: Span::new(0, 0),
+        }
+    }
+
+    #[test]
+    fn test_exports_are_visible_to_checker_and_interpreter() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = PackageCache::new(temp_dir.path().join("cache")).unwrap();
+        let locked = install(
+            &mut cache,
+            temp_dir.path(),
+            "MathUtils",
+            &["answer"],
+            "let base = 40\nlet answer = 42",
+        );
+        let modules = PackageModules::load(&Lockfile::new(vec![locked]), &cache).unwrap();
+
+        let module = modules.module_env().resolve_module("MathUtils").unwrap();
+        assert!(module.is_exported("answer"));
+        assert!(!module.is_exported("base"));
+
+        let expr = Expr::Apply {
+            func: Box::new(Expr::Ident(Ident("+".to_string()), Span::new(0, 0))),
+            args: vec![
+                qualified("MathUtils", "answer"),
+                Expr::Literal(Literal::Int(1), Span::new(0, 0)),
+            ],
+            span: Span::new(0, 0),
+        };
+        let mut type_env = modules.type_env();
+        let ty = TypeChecker::new().check(&expr, &mut type_env).unwrap();
+        assert_eq!(ty, Type::Int);
+
+        let env = modules.environment(&Interpreter::create_initial_env());
+        let value = Interpreter::new().eval(&expr, &env).unwrap();
+        assert_eq!(value, Value::Int(43));
+
+        let hidden =
+            TypeChecker::new().check(&qualified("MathUtils", "base"), &mut modules.type_env());
+        assert!(hidden.is_err());
+    }
+
+    #[test]
+    fn test_missing_export_is_an_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = PackageCache::new(temp_dir.path().join("cache")).unwrap();
+        let locked = install(
+            &mut cache,
+            temp_dir.path(),
+            "text",
+            &["shout"],
+            "let quiet = 1",
+        );
+        let error = PackageModules::load(&Lockfile::new(vec![locked]), &cache)
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            error,
+            "Parse error: text exports shout, which src/lib.vibe does not define"
+        );
+    }
+
+    #[test]
+    fn test_workspace_members_load_their_path_dependencies() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("ws");
+        fs::create_dir_all(&root).unwrap();
+        PackageManifest::workspace(vec!["core".to_string(), "app".to_string()])
+            .save_to_file(&root.join("package.vibe"))
+            .unwrap();
+        for (name, deps, lib) in [("core", None, "let answer = 42"), ("app", Some("core"), "let main = 1")] {
+            let dir = root.join(name);
+            fs::create_dir_all(dir.join("src")).unwrap();
+            let mut manifest = PackageManifest::new(name.to_string());
+            if let Some(dep) = deps {
+                manifest.add_path_dependency(dep.to_string(), format!("../{}", dep));
+            }
+            manifest.save_to_file(&dir.join("package.vibe")).unwrap();
+            fs::write(dir.join("src/lib.vibe"), lib).unwrap();
+        }
+
+        let cache = PackageCache::new(temp_dir.path().join("cache")).unwrap();
+        let modules = PackageModules::load_for_path(&root.join("app/src/lib.vibe"), &cache)
+            .unwrap()
+            .unwrap();
+        assert_eq!(modules.value("core", "answer"), Some(&Value::Int(42)));
+        assert!(modules.module_env().resolve_module("app").is_none());
+
+        // core itself sees no workspace modules
+        let modules = PackageModules::load_for_path(&root.join("core"), &cache)
+            .unwrap()
+            .unwrap();
+        assert_eq!(modules.value("core", "answer"), None);
+    }
+}
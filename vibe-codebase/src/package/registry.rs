@@ -71,9 +71,10 @@ impl LocalRegistry {
             yanked: false,
         });
 
-        // Update latest if this is newer
-        // TODO: Implement proper version comparison
-        entry.latest = hash.to_hex();
+        // Latest is the highest version that has not been yanked
+        if let Some(latest) = highest_matching(&entry.versions, &semver::VersionReq::STAR) {
+            entry.latest = latest.hash.clone();
+        }
 
         // Save index
         self.save_index()?;
@@ -110,9 +111,13 @@ impl super::resolver::PackageRegistry for LocalRegistry {
             .ok_or_else(|| PackageError::NotFound(format!("Package '{}' not found", name)))?;
 
         let hash_str = if let Some(version) = version {
-            // Find specific version
-            entry.versions.iter()
-                .find(|v| v.version == version && !v.yanked)
+            // Find specific version, or the highest one matching a range
+            let exact = entry.versions.iter().find(|v| v.version == version && !v.yanked);
+            let matching = || {
+                let req = semver::VersionReq::parse(version).ok()?;
+                highest_matching(&entry.versions, &req)
+            };
+            exact.or_else(matching)
                 .map(|v| &v.hash)
                 .ok_or_else(|| PackageError::NotFound(
                     format!("Version '{}' of package '{}' not found", version, name)
@@ -126,6 +131,12 @@ impl super::resolver::PackageRegistry for LocalRegistry {
             .map_err(|_| PackageError::InvalidHash(hash_str.to_string()))
     }
 
+    fn versions(&self, name: &str) -> Result<Vec<VersionEntry>> {
+        self.index.get(name)
+            .map(|entry| entry.versions.clone())
+            .ok_or_else(|| PackageError::NotFound(format!("Package '{}' not found", name)))
+    }
+
     fn get_manifest(&self, hash: &PackageHash) -> Result<PackageManifest> {
         let package_dir = self.package_dir(hash);
         let manifest_path = package_dir.join("package.vibe");
@@ -151,6 +162,17 @@ impl super::resolver::PackageRegistry for LocalRegistry {
     }
 }
 
+/// The highest non-yanked version matching `req`. Versions that are not
+/// valid semver never match.
+pub fn highest_matching<'a>(versions: &'a [VersionEntry], req: &semver::VersionReq) -> Option<&'a VersionEntry> {
+    versions.iter()
+        .filter(|v| !v.yanked)
+        .filter_map(|v| semver::Version::parse(&v.version).ok().map(|parsed| (parsed, v)))
+        .filter(|(parsed, _)| req.matches(parsed))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, v)| v)
+}
+
 /// HTTP-based registry client
 #[allow(dead_code)]
 pub struct HttpRegistry {
@@ -174,6 +196,14 @@ impl super::resolver::PackageRegistry for HttpRegistry {
         unimplemented!("HTTP registry not yet implemented")
     }
 
+    fn versions(&self, name: &str) -> Result<Vec<VersionEntry>> {
+        // TODO: Implement HTTP API calls
+        Err(PackageError::Resolution(format!(
+            "Cannot list the versions of {name}: the HTTP registry at {} is not supported yet",
+            self.base_url
+        )))
+    }
+
     fn get_manifest(&self, _hash: &PackageHash) -> Result<PackageManifest> {
         // TODO: Implement HTTP API calls
         unimplemented!("HTTP registry not yet implemented")
@@ -223,4 +253,45 @@ mod tests {
 
         Ok(())
     }
+
+    fn publish_version(registry: &mut LocalRegistry, root: &Path, version: &str) -> Result<PackageHash> {
+        let package_dir = root.join(version);
+        std::fs::create_dir_all(&package_dir)?;
+        let mut manifest = PackageManifest::new("math".to_string());
+        manifest.package.version = Some(version.to_string());
+        manifest.save_to_file(&package_dir.join("package.vibe"))?;
+        let hash = crate::package::hash::calculate_package_hash(&package_dir)?;
+        registry.publish(&manifest, &hash, &package_dir)?;
+        Ok(hash)
+    }
+
+    #[test]
+    fn test_semver_latest_and_ranges() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let mut registry = LocalRegistry::new(temp_dir.path().join("registry"))?;
+
+        let v1_10 = publish_version(&mut registry, temp_dir.path(), "1.10.0")?;
+        let v2 = publish_version(&mut registry, temp_dir.path(), "2.0.0")?;
+        // Publishing an older version afterwards does not move `latest` back
+        let v1_9 = publish_version(&mut registry, temp_dir.path(), "1.9.3")?;
+
+        assert_eq!(registry.find_package("math", None)?, v2);
+        assert_eq!(registry.find_package("math", Some("^1.2"))?, v1_10);
+        assert_eq!(registry.find_package("math", Some("1.9.3"))?, v1_9);
+        assert!(registry.find_package("math", Some("^3")).is_err());
+        assert_eq!(registry.versions("math")?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_registry_cannot_list_versions_yet() {
+        let registry = HttpRegistry::new("https://packages.example".to_string());
+        let error = registry.versions("math").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Resolution error: Cannot list the versions of math: \
+             the HTTP registry at https://packages.example is not supported yet"
+        );
+    }
 }
\ No newline at end of file
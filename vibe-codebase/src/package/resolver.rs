@@ -1,4 +1,4 @@
-use super::{PackageHash, manifest::PackageManifest, PackageError, Result, cache::PackageCache};
+use super::{PackageHash, manifest::PackageManifest, PackageError, Result, cache::PackageCache, registry::VersionEntry};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::Path;
 
@@ -13,6 +13,9 @@ pub trait PackageRegistry: Send + Sync {
     /// Find package by name and optional version
     fn find_package(&self, name: &str, version: Option<&str>) -> Result<PackageHash>;
     
+    /// All published versions of a package
+    fn versions(&self, name: &str) -> Result<Vec<VersionEntry>>;
+    
     /// Get package manifest by hash
     fn get_manifest(&self, hash: &PackageHash) -> Result<PackageManifest>;
     
@@ -0,0 +1,426 @@
+//! Workspaces: several local packages developed together
+//!
+//! A workspace root is a `package.vibe` with a `workspace` section instead of
+//! a `package`:
+//!
+//! ```vibe
+//! { workspace: { members: ["core", "apps/*"] } }
+//! ```
+//!
+//! Members depend on each other through path dependencies such as
+//! `core: { path: "../core" }`, and share the root's `package.lock` and the
+//! package cache. [`Workspace::members`] lists members in dependency order,
+//! and [`CheckState`] remembers the source hash each member was last checked
+//! at, so that after a change only that member and its dependents are
+//! checked again.
+
+use super::{
+    hash::calculate_package_hash, lockfile::Lockfile, manifest::PackageManifest, PackageError,
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the check state is kept, relative to the workspace root
+const STATE_FILE: &str = ".vibe/workspace-state.json";
+
+/// A package in a workspace
+#[derive(Debug, Clone)]
+pub struct WorkspaceMember {
+    pub name: String,
+    /// Canonical directory of the member
+    pub dir: PathBuf,
+    pub manifest: PackageManifest,
+    /// Members this one depends on by path
+    pub local_dependencies: Vec<String>,
+}
+
+impl WorkspaceMember {
+    pub fn manifest_path(&self) -> PathBuf {
+        self.dir.join("package.vibe")
+    }
+
+    /// Content hash of the member's sources
+    pub fn source_hash(&self) -> Result<String> {
+        Ok(calculate_package_hash(&self.dir)?.to_hex())
+    }
+}
+
+/// A workspace root and its members
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Canonical directory of the root manifest
+    pub root: PathBuf,
+    pub manifest: PackageManifest,
+    /// Members, dependencies before dependents
+    pub members: Vec<WorkspaceMember>,
+}
+
+impl Workspace {
+    /// Load the workspace whose root manifest is in `root`
+    pub fn load(root: &Path) -> Result<Self> {
+        let root = root.canonicalize()?;
+        let manifest = PackageManifest::load_from_file(&root.join("package.vibe"))?;
+        let Some(config) = &manifest.workspace else {
+            return Err(PackageError::NotFound(format!(
+                "{} is not a workspace root",
+                root.display()
+            )));
+        };
+
+        let mut members: Vec<WorkspaceMember> = Vec::new();
+        for dir in member_dirs(&root, &config.members)? {
+            let manifest = PackageManifest::load_from_file(&dir.join("package.vibe"))?;
+            let name = manifest.package.name.clone();
+            if members.iter().any(|m| m.name == name) {
+                return Err(PackageError::Resolution(format!(
+                    "Two workspace members are named {}",
+                    name
+                )));
+            }
+            members.push(WorkspaceMember {
+                name,
+                dir,
+                manifest,
+                local_dependencies: Vec::new(),
+            });
+        }
+
+        for index in 0..members.len() {
+            let mut local = Vec::new();
+            let member = &members[index];
+            for dep in member.manifest.dependencies.values() {
+                let Some(path) = &dep.path else { continue };
+                let target = member
+                    .dir
+                    .join(path)
+                    .canonicalize()
+                    .ok()
+                    .and_then(|dir| members.iter().find(|m| m.dir == dir))
+                    .ok_or_else(|| {
+                        PackageError::Resolution(format!(
+                            "{} depends on {}, which is not a workspace member",
+                            member.name, path
+                        ))
+                    })?;
+                if target.name != dep.name {
+                    return Err(PackageError::Resolution(format!(
+                        "{} depends on {} as {}; path dependencies use the member's name",
+                        member.name, target.name, dep.name
+                    )));
+                }
+                local.push(target.name.clone());
+            }
+            local.sort();
+            members[index].local_dependencies = local;
+        }
+
+        Ok(Workspace {
+            root,
+            manifest,
+            members: dependency_order(members)?,
+        })
+    }
+
+    /// The workspace containing `path`, if any
+    pub fn find(path: &Path) -> Result<Option<Self>> {
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+        let start = if path.is_dir() {
+            path.as_path()
+        } else {
+            match path.parent() {
+                Some(parent) => parent,
+                None => return Ok(None),
+            }
+        };
+        for dir in start.ancestors() {
+            let candidate = dir.join("package.vibe");
+            if candidate.exists()
+                && PackageManifest::load_from_file(&candidate)?.is_workspace_root()
+            {
+                return Self::load(dir).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join("package.vibe")
+    }
+
+    /// The lockfile shared by every member
+    pub fn lockfile_path(&self) -> PathBuf {
+        Lockfile::path_for(&self.manifest_path())
+    }
+
+    pub fn member(&self, name: &str) -> Option<&WorkspaceMember> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    /// The member whose directory contains `path`
+    pub fn member_for_path(&self, path: &Path) -> Option<&WorkspaceMember> {
+        let path = path.canonicalize().ok()?;
+        self.members
+            .iter()
+            .filter(|m| path.starts_with(&m.dir))
+            .max_by_key(|m| m.dir.components().count())
+    }
+
+    /// Every member's manifest
+    pub fn manifests(&self) -> Vec<&PackageManifest> {
+        self.members.iter().map(|m| &m.manifest).collect()
+    }
+
+    /// Replace a member's manifest, e.g. with one that is about to be saved
+    pub fn set_manifest(&mut self, name: &str, manifest: PackageManifest) {
+        if let Some(member) = self.members.iter_mut().find(|m| m.name == name) {
+            member.manifest = manifest;
+        }
+    }
+
+    /// The members `name` depends on, directly or not, in dependency order
+    pub fn dependencies_of(&self, name: &str) -> Vec<&WorkspaceMember> {
+        let mut needed = vec![name.to_string()];
+        let mut pending = vec![name.to_string()];
+        while let Some(next) = pending.pop() {
+            for dep in self
+                .member(&next)
+                .iter()
+                .flat_map(|m| &m.local_dependencies)
+            {
+                if !needed.contains(dep) {
+                    needed.push(dep.clone());
+                    pending.push(dep.clone());
+                }
+            }
+        }
+        self.members
+            .iter()
+            .filter(|m| m.name != name && needed.contains(&m.name))
+            .collect()
+    }
+
+    /// The named members and every member depending on them, directly or
+    /// not, in dependency order
+    pub fn dependents_of(&self, names: &[String]) -> Vec<&WorkspaceMember> {
+        let mut affected: Vec<&str> = names.iter().map(String::as_str).collect();
+        // Members are ordered, so one pass sees every dependency first
+        for member in &self.members {
+            if member
+                .local_dependencies
+                .iter()
+                .any(|dep| affected.contains(&dep.as_str()))
+                && !affected.contains(&member.name.as_str())
+            {
+                affected.push(&member.name);
+            }
+        }
+        self.members
+            .iter()
+            .filter(|m| affected.contains(&m.name.as_str()))
+            .collect()
+    }
+
+    /// Members that changed since they were last checked, and their
+    /// dependents
+    pub fn stale_members(&self, state: &CheckState) -> Result<Vec<&WorkspaceMember>> {
+        let mut changed = Vec::new();
+        for member in &self.members {
+            if state.checked.get(&member.name) != Some(&member.source_hash()?) {
+                changed.push(member.name.clone());
+            }
+        }
+        Ok(self.dependents_of(&changed))
+    }
+}
+
+/// Source hashes of the members as they were last checked successfully
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckState {
+    checked: BTreeMap<String, String>,
+}
+
+impl CheckState {
+    /// Load the state of `workspace`; a missing file means nothing was
+    /// checked yet
+    pub fn load(workspace: &Workspace) -> Result<Self> {
+        let path = workspace.root.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(CheckState::default());
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| PackageError::Parse(format!("Failed to parse {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, workspace: &Workspace) -> Result<()> {
+        let path = workspace.root.join(STATE_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| PackageError::Parse(format!("Failed to serialize check state: {}", e)))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Remember that `member` checked successfully as it is now
+    pub fn record(&mut self, member: &WorkspaceMember) -> Result<()> {
+        self.checked
+            .insert(member.name.clone(), member.source_hash()?);
+        Ok(())
+    }
+
+    /// Forget a member, so that it is checked again next time
+    pub fn forget(&mut self, name: &str) {
+        self.checked.remove(name);
+    }
+}
+
+/// Canonical member directories; `dir/*` expands to the subdirectories of
+/// `dir` that have a package.vibe
+fn member_dirs(root: &Path, members: &[String]) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for member in members {
+        if let Some(parent) = member.strip_suffix("/*") {
+            let mut found: Vec<PathBuf> = fs::read_dir(root.join(parent))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|dir| dir.join("package.vibe").is_file())
+                .collect();
+            found.sort();
+            for dir in found {
+                dirs.push(dir.canonicalize()?);
+            }
+        } else {
+            let dir = root.join(member);
+            if !dir.join("package.vibe").is_file() {
+                return Err(PackageError::NotFound(format!(
+                    "Workspace member {} has no package.vibe",
+                    member
+                )));
+            }
+            dirs.push(dir.canonicalize()?);
+        }
+    }
+    dirs.dedup();
+    Ok(dirs)
+}
+
+/// Order members so that each comes after the members it depends on
+fn dependency_order(mut remaining: Vec<WorkspaceMember>) -> Result<Vec<WorkspaceMember>> {
+    let mut ordered: Vec<WorkspaceMember> = Vec::new();
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|m| {
+            m.local_dependencies
+                .iter()
+                .all(|dep| ordered.iter().any(|o| &o.name == dep))
+        });
+        if ready.is_empty() {
+            let names: Vec<&str> = blocked.iter().map(|m| m.name.as_str()).collect();
+            return Err(PackageError::Resolution(format!(
+                "Circular dependency between workspace members {}",
+                names.join(", ")
+            )));
+        }
+        ordered.extend(ready);
+        remaining = blocked;
+    }
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn member(root: &Path, dir: &str, deps: &[(&str, &str)]) {
+        let dir = root.join(dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let mut manifest = PackageManifest::new(dir.file_name().unwrap().to_string_lossy().into());
+        for (name, path) in deps {
+            manifest.add_path_dependency(name.to_string(), path.to_string());
+        }
+        manifest.save_to_file(&dir.join("package.vibe")).unwrap();
+        fs::write(dir.join("src/lib.vibe"), "let x = 1").unwrap();
+    }
+
+    /// core <- text <- app, and an unrelated tool
+    fn workspace() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        PackageManifest::workspace(vec!["packages/*".to_string(), "tool".to_string()])
+            .save_to_file(&dir.path().join("package.vibe"))
+            .unwrap();
+        member(dir.path(), "packages/app", &[("text", "../text")]);
+        member(dir.path(), "packages/core", &[]);
+        member(dir.path(), "packages/text", &[("core", "../core")]);
+        member(dir.path(), "tool", &[]);
+        dir
+    }
+
+    fn names(members: Vec<&WorkspaceMember>) -> Vec<&str> {
+        members.into_iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_members_in_dependency_order() {
+        let dir = workspace();
+        let ws = Workspace::load(dir.path()).unwrap();
+        assert_eq!(
+            names(ws.members.iter().collect()),
+            vec!["core", "tool", "text", "app"]
+        );
+        assert_eq!(names(ws.dependencies_of("app")), vec!["core", "text"]);
+        assert_eq!(
+            names(ws.dependents_of(&["core".to_string()])),
+            vec!["core", "text", "app"]
+        );
+
+        let found = Workspace::find(&dir.path().join("packages/text/src/lib.vibe"))
+            .unwrap()
+            .unwrap();
+        let file = dir.path().join("packages/text/src/lib.vibe");
+        assert_eq!(found.member_for_path(&file).unwrap().name, "text");
+    }
+
+    #[test]
+    fn test_only_changed_members_and_dependents_are_stale() {
+        let dir = workspace();
+        let ws = Workspace::load(dir.path()).unwrap();
+        let mut state = CheckState::load(&ws).unwrap();
+        assert_eq!(ws.stale_members(&state).unwrap().len(), 4);
+
+        for member in &ws.members {
+            state.record(member).unwrap();
+        }
+        state.save(&ws).unwrap();
+        let state = CheckState::load(&ws).unwrap();
+        assert!(ws.stale_members(&state).unwrap().is_empty());
+
+        fs::write(dir.path().join("packages/text/src/lib.vibe"), "let x = 2").unwrap();
+        assert_eq!(
+            names(ws.stale_members(&state).unwrap()),
+            vec!["text", "app"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_path_dependencies() {
+        let dir = workspace();
+        member(dir.path(), "packages/app", &[("text", "../missing")]);
+        let error = Workspace::load(dir.path()).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Resolution error: app depends on ../missing, which is not a workspace member"
+        );
+
+        member(dir.path(), "packages/app", &[("text", "../text")]);
+        member(dir.path(), "packages/core", &[("app", "../app")]);
+        let error = Workspace::load(dir.path()).unwrap_err().to_string();
+        assert!(error.contains("Circular dependency"), "{error}");
+    }
+}
@@ -1,10 +1,30 @@
 //! Pipeline Processing for XS Shell
 //!
 //! Implements nushell-style pipeline operations for processing structured data.
+//!
+//! Stages work on codebase definitions as well as on any runtime `Value`
+//! list or record, and `where`, `each` and `reduce` take Vibe lambdas. A
+//! `Pipeline` is type checked against the type of its input before any stage
+//! runs.
+//!
+//! `from json|csv|toml` and `to json|csv|toml` convert between text and
+//! structured data. Reading a file needs the `FileSystem` effect, which the
+//! `PipelineScope` has to allow.
 
+use crate::data_formats::DataFormat;
 use crate::structured_data::{StructuredData, StructuredValue};
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use vibe_language::{Span, XsError};
+use std::path::PathBuf;
+use vibe_compiler::{TypeChecker, TypeEnv, TypeScheme};
+use vibe_language::parser::parse;
+use vibe_language::{Effect, Environment, Expr, Ident, Literal, Span, Type, Value, XsError};
+use vibe_runtime::Interpreter;
+
+/// Name the current row is bound to while a stage's lambda runs
+const ITEM: &str = "$it";
+/// Name the accumulator is bound to while a `reduce` lambda runs
+const ACCUMULATOR: &str = "$acc";
 
 /// Pipeline operator that transforms structured data
 pub trait PipelineOperator {
@@ -13,6 +33,116 @@ pub trait PipelineOperator {
 
     /// Get a description of this operator
     fn description(&self) -> String;
+
+    /// The type of the data this operator produces from data of type `input`
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        Ok(input.clone())
+    }
+
+    /// The effects running this operator performs
+    fn effects(&self) -> Vec<Effect> {
+        Vec::new()
+    }
+}
+
+/// Definitions visible to the lambdas of pipeline stages
+#[derive(Debug, Clone)]
+pub struct PipelineScope {
+    values: Environment,
+    types: HashMap<String, Type>,
+    effects: Vec<Effect>,
+}
+
+impl Default for PipelineScope {
+    fn default() -> Self {
+        Self {
+            values: Interpreter::create_initial_env(),
+            types: HashMap::new(),
+            effects: Vec::new(),
+        }
+    }
+}
+
+impl PipelineScope {
+    /// Only the builtins, and no effects
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let stages perform `effect`
+    pub fn allow(&mut self, effect: Effect) {
+        if !self.effects.contains(&effect) {
+            self.effects.push(effect);
+        }
+    }
+
+    /// Make `name` available to stage lambdas
+    pub fn bind(&mut self, name: String, value: Value, ty: Type) {
+        self.values = self.values.extend(Ident(name.clone()), value);
+        self.types.insert(name, ty);
+    }
+
+    fn type_env(&self) -> TypeEnv {
+        let mut env = TypeEnv::default();
+        for (name, ty) in &self.types {
+            env.add_binding(name.clone(), TypeScheme::mono(ty.clone()));
+        }
+        env
+    }
+}
+
+/// A sequence of stages, type checked as a whole before it runs
+pub struct Pipeline {
+    stages: Vec<Box<dyn PipelineOperator>>,
+    effects: Vec<Effect>,
+}
+
+impl Pipeline {
+    /// Parse one stage per command
+    pub fn parse(commands: &[String], scope: &PipelineScope) -> Result<Self, XsError> {
+        let stages = commands
+            .iter()
+            .map(|cmd| parse_pipeline_stage(cmd, scope))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            stages,
+            effects: scope.effects.clone(),
+        })
+    }
+
+    /// The type of the output for input of type `input`, or the first stage
+    /// that cannot take what the stage before it produces or needs an effect
+    /// the scope does not allow
+    pub fn check(&self, input: &Type) -> Result<Type, XsError> {
+        let mut ty = input.clone();
+        for stage in &self.stages {
+            if let Some(effect) = stage
+                .effects()
+                .into_iter()
+                .find(|effect| !self.effects.contains(effect))
+            {
+                return Err(XsError::TypeError(
+                    Span::new(0, 0),
+                    format!(
+                        "{} needs the {effect} effect, which this pipeline does not allow",
+                        stage.description()
+                    ),
+                ));
+            }
+            ty = stage.output_type(&ty)?;
+        }
+        Ok(ty)
+    }
+
+    /// Type check, then run every stage in order
+    pub fn run(&self, input: StructuredData) -> Result<StructuredData, XsError> {
+        self.check(&input.value_type())?;
+        let mut data = input;
+        for stage in &self.stages {
+            data = stage.apply(data)?;
+        }
+        Ok(data)
+    }
 }
 
 /// Filter rows based on a predicate
@@ -114,6 +244,8 @@ impl PipelineOperator for FilterOperator {
 pub struct MapOperator {
     transformer: Box<dyn Fn(HashMap<String, StructuredValue>) -> HashMap<String, StructuredValue>>,
     description: String,
+    /// The columns kept, for a `select`
+    selected: Option<Vec<String>>,
 }
 
 impl MapOperator {
@@ -124,13 +256,15 @@ impl MapOperator {
         Self {
             transformer: Box::new(transformer),
             description,
+            selected: None,
         }
     }
 
     /// Select specific columns
     pub fn select(columns: Vec<String>) -> Self {
         let desc = format!("select {}", columns.join(", "));
-        Self::new(
+        let selected = Some(columns.clone());
+        let operator = Self::new(
             move |mut row| {
                 let mut new_row = HashMap::new();
                 for col in &columns {
@@ -141,7 +275,11 @@ impl MapOperator {
                 new_row
             },
             desc,
-        )
+        );
+        Self {
+            selected,
+            ..operator
+        }
     }
 
     /// Add a computed column
@@ -171,7 +309,9 @@ impl PipelineOperator for MapOperator {
                     .collect();
 
                 // Update columns if needed
-                if let Some(first_row) = transformed.first() {
+                if let Some(selected) = &self.selected {
+                    columns.retain(|column| selected.contains(column));
+                } else if let Some(first_row) = transformed.first() {
                     columns = first_row.keys().cloned().collect();
                 }
 
@@ -197,6 +337,24 @@ impl PipelineOperator for MapOperator {
     fn description(&self) -> String {
         self.description.clone()
     }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        let Some(selected) = &self.selected else {
+            return Ok(input.clone());
+        };
+        let Some(fields) = row_fields(input, "select")? else {
+            return Ok(input.clone());
+        };
+        for column in selected {
+            column_type(fields, column, "select")?;
+        }
+        let fields = fields
+            .iter()
+            .filter(|(name, _)| selected.contains(name))
+            .cloned()
+            .collect();
+        Ok(Type::List(Box::new(Type::Record { fields })))
+    }
 }
 
 /// Sort rows by a field
@@ -261,6 +419,21 @@ impl PipelineOperator for SortOperator {
             if self.descending { "desc" } else { "asc" }
         )
     }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        if let Some(fields) = row_fields(input, "sort")? {
+            match column_type(fields, &self.field, "sort")? {
+                Type::Int | Type::Float | Type::String | Type::Bool | Type::Var(_) => {}
+                other => {
+                    return Err(stage_type_error(
+                        "sort",
+                        format!("cannot order column `{}` of type {other}", self.field),
+                    ))
+                }
+            }
+        }
+        Ok(input.clone())
+    }
 }
 
 /// Take first N rows
@@ -369,6 +542,23 @@ impl PipelineOperator for GroupByOperator {
     fn description(&self) -> String {
         format!("group by {}", self.field)
     }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        if let Some(fields) = row_fields(input, "group by")? {
+            column_type(fields, &self.field, "group by")?;
+        }
+        let row = match input {
+            Type::List(row) => (**row).clone(),
+            _ => Type::Var("row".to_string()),
+        };
+        let mut fields = vec![
+            ("count".to_string(), Type::Int),
+            ("items".to_string(), Type::List(Box::new(row))),
+            (self.field.clone(), Type::String),
+        ];
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Type::List(Box::new(Type::Record { fields })))
+    }
 }
 
 /// Count rows
@@ -390,6 +580,573 @@ impl PipelineOperator for CountOperator {
     fn description(&self) -> String {
         "count".to_string()
     }
+
+    fn output_type(&self, _input: &Type) -> Result<Type, XsError> {
+        Ok(Type::Int)
+    }
+}
+
+/// Comparison used by `filter` and `where`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+}
+
+impl Comparison {
+    fn parse(op: &str) -> Option<Self> {
+        match op {
+            "==" | "=" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            "contains" => Some(Comparison::Contains),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Contains => "contains",
+        }
+    }
+
+    fn holds(self, left: &StructuredValue, right: &StructuredValue) -> bool {
+        if self == Comparison::Contains {
+            return match (left, right) {
+                (StructuredValue::String(s), StructuredValue::String(sub)) => s.contains(sub),
+                (StructuredValue::List(items), _) => items
+                    .iter()
+                    .any(|item| item.compare(right) == Some(Ordering::Equal)),
+                _ => false,
+            };
+        }
+        let Some(ordering) = left.compare(right) else {
+            return self == Comparison::NotEqual;
+        };
+        match self {
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+            Comparison::Contains => unreachable!(),
+        }
+    }
+}
+
+/// Keep the rows whose field compares to a value, by the value's type
+pub struct CompareOperator {
+    field: String,
+    comparison: Comparison,
+    value: StructuredValue,
+}
+
+impl CompareOperator {
+    pub fn new(field: String, comparison: Comparison, value: StructuredValue) -> Self {
+        Self {
+            field,
+            comparison,
+            value,
+        }
+    }
+}
+
+impl PipelineOperator for CompareOperator {
+    fn apply(&self, input: StructuredData) -> Result<StructuredData, XsError> {
+        match input {
+            StructuredData::Table { columns, rows } => Ok(StructuredData::Table {
+                columns,
+                rows: rows
+                    .into_iter()
+                    .filter(|row| {
+                        row.get(&self.field)
+                            .is_some_and(|v| self.comparison.holds(v, &self.value))
+                    })
+                    .collect(),
+            }),
+
+            StructuredData::Definitions(_) => self.apply(input.to_table()?),
+
+            StructuredData::List(items) if items.is_empty() => Ok(StructuredData::List(items)),
+
+            _ => Err(XsError::RuntimeError(
+                Span::new(0, 0),
+                format!("Cannot compare field `{}` of non-tabular data", self.field),
+            )),
+        }
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "where {} {} {}",
+            self.field,
+            self.comparison.symbol(),
+            format_value_simple(&self.value)
+        )
+    }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        let Some(fields) = row_fields(input, "where")? else {
+            return Ok(input.clone());
+        };
+        let field_type = column_type(fields, &self.field, "where")?;
+        let value_type = self.value.value_type();
+        let comparable = match (&field_type, &value_type) {
+            (Type::Var(_), _) => true,
+            (Type::Int | Type::Float, Type::Int | Type::Float) => true,
+            (Type::List(item), _) if self.comparison == Comparison::Contains => {
+                matches!(**item, Type::Var(_)) || **item == value_type
+            }
+            (Type::String, Type::String) => true,
+            (_, _) if self.comparison == Comparison::Contains => false,
+            (field_type, value_type) => field_type == value_type,
+        };
+        if !comparable {
+            return Err(stage_type_error(
+                "where",
+                format!(
+                    "cannot compare column `{}` of type {} with {} of type {}",
+                    self.field,
+                    field_type,
+                    format_value_simple(&self.value),
+                    value_type
+                ),
+            ));
+        }
+        Ok(input.clone())
+    }
+}
+
+/// Which lambda stage a `LambdaOperator` is
+#[derive(Debug, Clone, PartialEq)]
+pub enum LambdaStage {
+    /// Keep the rows the function returns `true` for
+    Where,
+    /// Replace every row with the function's result
+    Each,
+    /// Fold the rows with a function of the accumulator and the row,
+    /// starting from `fold` or else from the first row
+    Reduce { fold: Option<Expr> },
+}
+
+impl LambdaStage {
+    fn name(&self) -> &'static str {
+        match self {
+            LambdaStage::Where => "where",
+            LambdaStage::Each => "each",
+            LambdaStage::Reduce { .. } => "reduce",
+        }
+    }
+}
+
+/// A stage that runs a Vibe function over every row of a list or table
+pub struct LambdaOperator {
+    stage: LambdaStage,
+    function: Expr,
+    source: String,
+    scope: PipelineScope,
+}
+
+impl LambdaOperator {
+    pub fn new(stage: LambdaStage, function: Expr, source: String, scope: PipelineScope) -> Self {
+        Self {
+            stage,
+            function,
+            source,
+            scope,
+        }
+    }
+
+    /// The function applied to `args`, with the parameters of a literal
+    /// lambda annotated by `param_types` so field access on them is checked
+    fn application(&self, args: &[&str], param_types: &[Type]) -> Expr {
+        let mut function = self.function.clone();
+        if let Expr::Lambda { params, .. } = &mut function {
+            for ((_, annotation), ty) in params.iter_mut().zip(param_types) {
+                if annotation.is_none() && !matches!(ty, Type::Var(_)) {
+                    *annotation = Some(ty.clone());
+                }
+            }
+        }
+        let span = self.function.span().clone();
+        Expr::Apply {
+            func: Box::new(function),
+            args: args
+                .iter()
+                .map(|name| Expr::Ident(Ident(name.to_string()), span.clone()))
+                .collect(),
+            span,
+        }
+    }
+
+    fn call(&self, args: Vec<(&str, Value)>) -> Result<Value, XsError> {
+        let names: Vec<&str> = args.iter().map(|(name, _)| *name).collect();
+        let expr = self.application(&names, &[]);
+        let mut env = self.scope.values.clone();
+        for (name, value) in args {
+            env = env.extend(Ident(name.to_string()), value);
+        }
+        Interpreter::new().eval(&expr, &env)
+    }
+
+    fn check(&self, expr: &Expr, bindings: &[(&str, &Type)]) -> Result<Type, XsError> {
+        let mut env = self.scope.type_env();
+        for (name, ty) in bindings {
+            env.add_binding(name.to_string(), TypeScheme::mono((*ty).clone()));
+        }
+        TypeChecker::new()
+            .check(expr, &mut env)
+            .map_err(|e| stage_type_error(self.stage.name(), format!("{}: {e}", self.source)))
+    }
+}
+
+impl PipelineOperator for LambdaOperator {
+    fn apply(&self, input: StructuredData) -> Result<StructuredData, XsError> {
+        let items = input.to_values()?;
+        match &self.stage {
+            LambdaStage::Where => {
+                let mut keep = Vec::with_capacity(items.len());
+                for item in items {
+                    match self.call(vec![(ITEM, item)])? {
+                        Value::Bool(b) => keep.push(b),
+                        other => {
+                            return Err(XsError::RuntimeError(
+                                self.function.span().clone(),
+                                format!("where: expected a Bool, got {other:?}"),
+                            ))
+                        }
+                    }
+                }
+                let mut keep = keep.into_iter();
+                Ok(match input {
+                    StructuredData::Table { columns, rows } => StructuredData::Table {
+                        columns,
+                        rows: rows
+                            .into_iter()
+                            .filter(|_| keep.next().unwrap_or(false))
+                            .collect(),
+                    },
+                    StructuredData::List(items) => StructuredData::List(
+                        items
+                            .into_iter()
+                            .filter(|_| keep.next().unwrap_or(false))
+                            .collect(),
+                    ),
+                    other => self.apply(other.to_table()?)?,
+                })
+            }
+            LambdaStage::Each => {
+                let results = items
+                    .into_iter()
+                    .map(|item| self.call(vec![(ITEM, item)]))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(StructuredData::from_value(&Value::List(results)))
+            }
+            LambdaStage::Reduce { fold } => {
+                let mut items = items.into_iter();
+                let mut acc = match fold {
+                    Some(init) => Interpreter::new().eval(init, &self.scope.values)?,
+                    None => match items.next() {
+                        Some(first) => first,
+                        None => {
+                            return Err(XsError::RuntimeError(
+                                self.function.span().clone(),
+                                "reduce: nothing to reduce; give a starting value with --fold"
+                                    .to_string(),
+                            ))
+                        }
+                    },
+                };
+                for item in items {
+                    acc = self.call(vec![(ACCUMULATOR, acc), (ITEM, item)])?;
+                }
+                Ok(StructuredData::from_value(&acc))
+            }
+        }
+    }
+
+    fn description(&self) -> String {
+        match &self.stage {
+            LambdaStage::Reduce { fold: Some(init) } => {
+                format!("reduce --fold {init:?} {}", self.source)
+            }
+            stage => format!("{} {}", stage.name(), self.source),
+        }
+    }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        let item = match input {
+            Type::List(item) => (**item).clone(),
+            Type::Var(_) => Type::Var("row".to_string()),
+            other => {
+                return Err(stage_type_error(
+                    self.stage.name(),
+                    format!("expected a list or a table, found {other}"),
+                ))
+            }
+        };
+        let span = self.function.span().clone();
+        let var = |name: &str| Expr::Ident(Ident(name.to_string()), span.clone());
+
+        match &self.stage {
+            LambdaStage::Where => {
+                // The condition of an `if` must be a Bool
+                let test = Expr::If {
+                    cond: Box::new(self.application(&[ITEM], std::slice::from_ref(&item))),
+                    then_expr: Box::new(var(ITEM)),
+                    else_expr: Box::new(var(ITEM)),
+                    span: span.clone(),
+                };
+                self.check(&test, &[(ITEM, &item)])?;
+                Ok(input.clone())
+            }
+            LambdaStage::Each => {
+                let result = self.check(
+                    &self.application(&[ITEM], std::slice::from_ref(&item)),
+                    &[(ITEM, &item)],
+                )?;
+                Ok(Type::List(Box::new(result)))
+            }
+            LambdaStage::Reduce { fold } => {
+                let acc = match fold {
+                    Some(init) => self.check(init, &[])?,
+                    None => item.clone(),
+                };
+                // Both branches of an `if` have the same type
+                let step = Expr::If {
+                    cond: Box::new(Expr::Literal(Literal::Bool(true), span.clone())),
+                    then_expr: Box::new(var(ACCUMULATOR)),
+                    else_expr: Box::new(
+                        self.application(&[ACCUMULATOR, ITEM], &[acc.clone(), item.clone()]),
+                    ),
+                    span: span.clone(),
+                };
+                self.check(&step, &[(ACCUMULATOR, &acc), (ITEM, &item)])
+            }
+        }
+    }
+}
+
+/// Parse text, or the file at `path`, as structured data
+pub struct FromOperator {
+    format: DataFormat,
+    path: Option<PathBuf>,
+}
+
+impl FromOperator {
+    pub fn new(format: DataFormat, path: Option<PathBuf>) -> Self {
+        Self { format, path }
+    }
+}
+
+impl PipelineOperator for FromOperator {
+    fn apply(&self, input: StructuredData) -> Result<StructuredData, XsError> {
+        let text = match &self.path {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| {
+                XsError::RuntimeError(
+                    Span::new(0, 0),
+                    format!("cannot read {}: {e}", path.display()),
+                )
+            })?,
+            None => match input {
+                StructuredData::Value(StructuredValue::String(text)) => text,
+                other => {
+                    return Err(XsError::RuntimeError(
+                        Span::new(0, 0),
+                        format!(
+                            "from {} expects text, found {}",
+                            self.format.name(),
+                            other.value_type()
+                        ),
+                    ))
+                }
+            },
+        };
+        self.format.parse(&text)
+    }
+
+    fn description(&self) -> String {
+        match &self.path {
+            Some(path) => format!("from {} {}", self.format.name(), path.display()),
+            None => format!("from {}", self.format.name()),
+        }
+    }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        if self.path.is_none() && !matches!(input, Type::String | Type::Var(_)) {
+            return Err(stage_type_error(
+                &self.description(),
+                format!("expected String, found {input}"),
+            ));
+        }
+        // The shape of the data is only known once it is read
+        Ok(Type::Var("data".to_string()))
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        match self.path {
+            Some(_) => vec![Effect::FileSystem],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Render structured data as text
+pub struct ToOperator {
+    format: DataFormat,
+}
+
+impl ToOperator {
+    pub fn new(format: DataFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl PipelineOperator for ToOperator {
+    fn apply(&self, input: StructuredData) -> Result<StructuredData, XsError> {
+        Ok(StructuredData::Value(StructuredValue::String(
+            self.format.render(&input)?,
+        )))
+    }
+
+    fn description(&self) -> String {
+        format!("to {}", self.format.name())
+    }
+
+    fn output_type(&self, input: &Type) -> Result<Type, XsError> {
+        match self.format {
+            DataFormat::Csv => {
+                row_fields(input, &self.description())?;
+            }
+            DataFormat::Toml if !matches!(input, Type::Record { .. } | Type::Var(_)) => {
+                return Err(stage_type_error(
+                    &self.description(),
+                    format!("expected a record, found {input}"),
+                ));
+            }
+            _ => {}
+        }
+        Ok(Type::String)
+    }
+}
+
+/// The fields of the rows of a table type, `None` when the row type is not
+/// known yet
+fn row_fields<'a>(input: &'a Type, stage: &str) -> Result<Option<&'a [(String, Type)]>, XsError> {
+    match input {
+        Type::List(row) => match row.as_ref() {
+            Type::Record { fields } => Ok(Some(fields)),
+            Type::Var(_) => Ok(None),
+            other => Err(stage_type_error(
+                stage,
+                format!("expected a table, found a list of {other}"),
+            )),
+        },
+        Type::Var(_) => Ok(None),
+        other => Err(stage_type_error(
+            stage,
+            format!("expected a table, found {other}"),
+        )),
+    }
+}
+
+fn column_type(fields: &[(String, Type)], column: &str, stage: &str) -> Result<Type, XsError> {
+    fields
+        .iter()
+        .find(|(name, _)| name == column)
+        .map(|(_, ty)| ty.clone())
+        .ok_or_else(|| {
+            let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            stage_type_error(
+                stage,
+                format!("no column `{column}`; columns are {}", names.join(", ")),
+            )
+        })
+}
+
+fn stage_type_error(stage: &str, message: String) -> XsError {
+    XsError::TypeError(Span::new(0, 0), format!("{stage}: {message}"))
+}
+
+/// A literal operand of `filter`/`where`: a number, a boolean, a quoted
+/// string, or else a bare word
+fn parse_operand(text: &str) -> StructuredValue {
+    if let Some(quoted) = text
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        return StructuredValue::String(quoted.to_string());
+    }
+    if let Ok(n) = text.parse::<i64>() {
+        StructuredValue::Int(n)
+    } else if let Ok(f) = text.parse::<f64>() {
+        StructuredValue::Float(f)
+    } else if let Ok(b) = text.parse::<bool>() {
+        StructuredValue::Bool(b)
+    } else {
+        StructuredValue::String(text.to_string())
+    }
+}
+
+/// Parse the function of a lambda stage, written `{fn r -> ...}` or as any
+/// expression evaluating to a function
+fn parse_function(text: &str) -> Result<Expr, XsError> {
+    let text = text.trim();
+    let body = text
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(text);
+    if body.trim().is_empty() {
+        return Err(XsError::ParseError(0, "expected a function".to_string()));
+    }
+    parse(body)
+}
+
+/// Split `--fold <init> {fn acc r -> ...}` at the trailing block
+fn split_fold(text: &str) -> Result<(&str, &str), XsError> {
+    let missing = || {
+        XsError::ParseError(
+            0,
+            "reduce --fold needs a starting value and a `{fn acc r -> ...}` block".to_string(),
+        )
+    };
+    if !text.ends_with('}') {
+        return Err(missing());
+    }
+    let mut depth = 0;
+    for (i, c) in text.char_indices().rev() {
+        match c {
+            '}' => depth += 1,
+            '{' => {
+                depth -= 1;
+                if depth == 0 {
+                    let init = text[..i].trim();
+                    return if init.is_empty() {
+                        Err(missing())
+                    } else {
+                        Ok((init, &text[i..]))
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(missing())
 }
 
 // Helper function
@@ -405,6 +1162,14 @@ fn format_value_simple(val: &StructuredValue) -> String {
 
 /// Parse a pipeline command
 pub fn parse_pipeline_operator(cmd: &str) -> Result<Box<dyn PipelineOperator>, XsError> {
+    parse_pipeline_stage(cmd, &PipelineScope::new())
+}
+
+/// Parse a pipeline command whose lambdas see the definitions in `scope`
+pub fn parse_pipeline_stage(
+    cmd: &str,
+    scope: &PipelineScope,
+) -> Result<Box<dyn PipelineOperator>, XsError> {
     let parts: Vec<&str> = cmd.split_whitespace().collect();
     if parts.is_empty() {
         return Err(XsError::RuntimeError(
@@ -432,22 +1197,55 @@ pub fn parse_pipeline_operator(cmd: &str) -> Result<Box<dyn PipelineOperator>, X
             else if parts.len() >= 4 && parts[2] == "contains" {
                 let value = parts[3..].join(" ");
                 Ok(Box::new(FilterOperator::contains(field, value)))
-            } else if parts.len() >= 4 && (parts[2] == "==" || parts[2] == "=") {
-                let value = parts[3..].join(" ");
-                Ok(Box::new(FilterOperator::equals(
-                    field,
-                    StructuredValue::String(value),
-                )))
             } else {
-                // Try to parse as "filter field value" (without operator means ==)
-                let value = parts[2..].join(" ");
-                Ok(Box::new(FilterOperator::equals(
-                    field,
-                    StructuredValue::String(value),
+                Ok(Box::new(parse_comparison(&parts[1..])?))
+            }
+        }
+
+        "where" => {
+            let rest = cmd.trim()["where".len()..].trim();
+            if rest.starts_with('{') {
+                let function = parse_function(rest)?;
+                Ok(Box::new(LambdaOperator::new(
+                    LambdaStage::Where,
+                    function,
+                    rest.to_string(),
+                    scope.clone(),
                 )))
+            } else {
+                Ok(Box::new(parse_comparison(&parts[1..])?))
             }
         }
 
+        "each" => {
+            let rest = cmd.trim()["each".len()..].trim();
+            let function = parse_function(rest)?;
+            Ok(Box::new(LambdaOperator::new(
+                LambdaStage::Each,
+                function,
+                rest.to_string(),
+                scope.clone(),
+            )))
+        }
+
+        "reduce" => {
+            let rest = cmd.trim()["reduce".len()..].trim();
+            let (fold, rest) = match rest.strip_prefix("--fold") {
+                Some(fold) => {
+                    let (init, block) = split_fold(fold.trim())?;
+                    (Some(parse(init)?), block)
+                }
+                None => (None, rest),
+            };
+            let function = parse_function(rest)?;
+            Ok(Box::new(LambdaOperator::new(
+                LambdaStage::Reduce { fold },
+                function,
+                rest.to_string(),
+                scope.clone(),
+            )))
+        }
+
         "select" => {
             if parts.len() < 2 {
                 return Err(XsError::RuntimeError(
@@ -501,9 +1299,276 @@ pub fn parse_pipeline_operator(cmd: &str) -> Result<Box<dyn PipelineOperator>, X
 
         "count" => Ok(Box::new(CountOperator)),
 
+        "from" | "to" => {
+            let format = parts.get(1).and_then(|name| DataFormat::from_name(name));
+            match (parts[0], format, &parts[2..]) {
+                ("from", Some(format), []) => Ok(Box::new(FromOperator::new(format, None))),
+                ("from", Some(format), [path]) => Ok(Box::new(FromOperator::new(
+                    format,
+                    Some(PathBuf::from(path)),
+                ))),
+                ("to", Some(format), []) => Ok(Box::new(ToOperator::new(format))),
+                _ => Err(XsError::RuntimeError(
+                    Span::new(0, 0),
+                    format!(
+                        "expected `{} json|csv|toml{}`",
+                        parts[0],
+                        if parts[0] == "from" { " [path]" } else { "" }
+                    ),
+                )),
+            }
+        }
+
         _ => Err(XsError::RuntimeError(
             Span::new(0, 0),
             format!("Unknown pipeline command: {}", parts[0]),
         )),
     }
 }
+
+/// `field op value`, or `field value` meaning `==`
+fn parse_comparison(parts: &[&str]) -> Result<CompareOperator, XsError> {
+    let (field, comparison, value) = match parts {
+        [field, op, value @ ..] if !value.is_empty() && Comparison::parse(op).is_some() => {
+            (field, Comparison::parse(op).unwrap(), value)
+        }
+        [field, value @ ..] if !value.is_empty() => (field, Comparison::Equal, value),
+        _ => {
+            return Err(XsError::RuntimeError(
+                Span::new(0, 0),
+                "expected `field op value`, or a `{fn r -> ...}` block".to_string(),
+            ))
+        }
+    };
+    Ok(CompareOperator::new(
+        field.to_string(),
+        comparison,
+        parse_operand(&value.join(" ")),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> StructuredData {
+        let person = |name: &str, age: i64| Value::Record {
+            fields: vec![
+                ("age".to_string(), Value::Int(age)),
+                ("name".to_string(), Value::String(name.to_string())),
+            ],
+        };
+        StructuredData::from_value(&Value::List(vec![
+            person("ada", 36),
+            person("bob", 25),
+            person("cy", 41),
+        ]))
+    }
+
+    fn span() -> Span {
+        Span::new(0, 0)
+    }
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(Ident(name.to_string()), span())
+    }
+
+    fn call(func: &str, args: Vec<Expr>) -> Expr {
+        Expr::Apply {
+            func: Box::new(ident(func)),
+            args,
+            span: span(),
+        }
+    }
+
+    fn field(record: &str, name: &str) -> Expr {
+        Expr::RecordAccess {
+            record: Box::new(ident(record)),
+            field: Ident(name.to_string()),
+            span: span(),
+        }
+    }
+
+    fn lambda(params: &[&str], body: Expr) -> Expr {
+        Expr::Lambda {
+            params: params
+                .iter()
+                .map(|p| (Ident(p.to_string()), None))
+                .collect(),
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn stage(stage: LambdaStage, function: Expr) -> Box<dyn PipelineOperator> {
+        Box::new(LambdaOperator::new(
+            stage,
+            function,
+            "{fn ...}".to_string(),
+            PipelineScope::new(),
+        ))
+    }
+
+    fn names(data: &StructuredData) -> Vec<String> {
+        data.to_values()
+            .unwrap()
+            .into_iter()
+            .map(|row| match row {
+                Value::Record { fields } => format!("{:?}", fields[1].1),
+                other => format!("{other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_typed_comparisons() {
+        let older = parse_pipeline_operator("where age > 30").unwrap();
+        let result = older.apply(people()).unwrap();
+        assert_eq!(names(&result), vec!["String(\"ada\")", "String(\"cy\")"]);
+
+        // Numbers compare as numbers, not as strings
+        let younger = parse_pipeline_operator("filter age < 100").unwrap();
+        assert_eq!(names(&younger.apply(people()).unwrap()).len(), 3);
+
+        let pipeline =
+            Pipeline::parse(&["where age > \"30\"".to_string()], &PipelineScope::new()).unwrap();
+        let error = pipeline.check(&people().value_type()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("cannot compare column `age` of type Int with 30 of type String"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_lambda_stages_over_values() {
+        // where {fn r -> r.age > 30} | each {fn r -> r.age} | reduce {fn acc x -> acc + x}
+        let older = lambda(
+            &["r"],
+            call(
+                ">",
+                vec![field("r", "age"), Expr::Literal(Literal::Int(30), span())],
+            ),
+        );
+        let age = lambda(&["r"], field("r", "age"));
+        let sum = lambda(&["acc", "x"], call("+", vec![ident("acc"), ident("x")]));
+        let pipeline = Pipeline {
+            stages: vec![
+                stage(LambdaStage::Where, older),
+                stage(LambdaStage::Each, age),
+                stage(LambdaStage::Reduce { fold: None }, sum.clone()),
+            ],
+            effects: Vec::new(),
+        };
+
+        assert_eq!(pipeline.check(&people().value_type()).unwrap(), Type::Int);
+        let result = pipeline.run(people()).unwrap();
+        assert!(matches!(
+            result,
+            StructuredData::Value(StructuredValue::Int(77))
+        ));
+
+        let folded = stage(
+            LambdaStage::Reduce {
+                fold: Some(Expr::Literal(Literal::Int(100), span())),
+            },
+            sum,
+        );
+        let ages = StructuredData::from_value(&Value::List(vec![Value::Int(1), Value::Int(2)]));
+        assert!(matches!(
+            folded.apply(ages).unwrap(),
+            StructuredData::Value(StructuredValue::Int(103))
+        ));
+    }
+
+    #[test]
+    fn test_pipeline_is_checked_before_running() {
+        let missing = lambda(&["r"], field("r", "height"));
+        let pipeline = Pipeline {
+            stages: vec![
+                Box::new(TakeOperator::new(1)),
+                stage(LambdaStage::Each, missing),
+            ],
+            effects: Vec::new(),
+        };
+        let error = pipeline.run(people()).unwrap_err();
+        assert!(
+            error.to_string().contains("Field 'height' not found"),
+            "{error}"
+        );
+
+        // A where function must return a Bool
+        let pipeline =
+            Pipeline::parse(&["where {fn r -> r}".to_string()], &PipelineScope::new()).unwrap();
+        assert!(pipeline.check(&people().value_type()).is_err());
+
+        let pipeline = Pipeline::parse(
+            &["select name".to_string(), "sort age".to_string()],
+            &PipelineScope::new(),
+        )
+        .unwrap();
+        let error = pipeline.check(&people().value_type()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("no column `age`; columns are name"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_from_and_to_formats() {
+        let scope = PipelineScope::new();
+        let stages = |commands: &[&str]| {
+            let commands: Vec<String> = commands.iter().map(|c| c.to_string()).collect();
+            Pipeline::parse(&commands, &scope).unwrap()
+        };
+        let csv = StructuredData::Value(StructuredValue::String(
+            "name,age\nada,36\nbob,25\n".to_string(),
+        ));
+        let output = stages(&["from csv", "where age > 30", "to json"])
+            .run(csv)
+            .unwrap();
+        let StructuredData::Value(StructuredValue::String(json)) = output else {
+            panic!("{output:?}");
+        };
+        let rows: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(rows, serde_json::json!([{"name": "ada", "age": 36}]));
+
+        assert!(stages(&["to toml"])
+            .check(&people().value_type())
+            .unwrap_err()
+            .to_string()
+            .contains("expected a record"));
+        assert!(parse_pipeline_operator("from yaml").is_err());
+    }
+
+    #[test]
+    fn test_reading_files_needs_the_file_system_effect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("people.json");
+        std::fs::write(&path, r#"[{"name": "ada"}, {"name": "bob"}]"#).unwrap();
+        let commands = vec![format!("from json {}", path.display()), "count".to_string()];
+
+        let error = Pipeline::parse(&commands, &PipelineScope::new())
+            .unwrap()
+            .run(StructuredData::Empty)
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("needs the FileSystem effect"),
+            "{error}"
+        );
+
+        let mut scope = PipelineScope::new();
+        scope.allow(Effect::FileSystem);
+        let count = Pipeline::parse(&commands, &scope)
+            .unwrap()
+            .run(StructuredData::Empty)
+            .unwrap();
+        assert!(matches!(
+            count,
+            StructuredData::Value(StructuredValue::Int(2))
+        ));
+    }
+}
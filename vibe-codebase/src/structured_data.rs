@@ -8,7 +8,7 @@ use crate::namespace::DefinitionPath;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use vibe_language::{Value, XsError};
+use vibe_language::{Type, Value, XsError};
 
 /// Structured data that can flow through pipelines
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -189,6 +189,110 @@ impl StructuredData {
         }
     }
 
+    /// Convert a runtime value. A list of records becomes a table whose
+    /// columns are the record fields in first-seen order.
+    pub fn from_value(value: &Value) -> Self {
+        Self::from_structured(StructuredValue::from_value(value))
+    }
+
+    /// Wrap a value, turning a list of records into a table
+    pub fn from_structured(value: StructuredValue) -> Self {
+        match value {
+            StructuredValue::List(items)
+                if !items.is_empty()
+                    && items
+                        .iter()
+                        .all(|v| matches!(v, StructuredValue::Record(_))) =>
+            {
+                let mut columns: Vec<String> = Vec::new();
+                let mut rows = Vec::new();
+                for item in items {
+                    if let StructuredValue::Record(row) = item {
+                        let mut names: Vec<&String> = row.keys().collect();
+                        names.sort();
+                        for name in names {
+                            if !columns.contains(name) {
+                                columns.push(name.clone());
+                            }
+                        }
+                        rows.push(row);
+                    }
+                }
+                StructuredData::Table { columns, rows }
+            }
+            StructuredValue::List(items) => StructuredData::List(items),
+            StructuredValue::Record(fields) => StructuredData::Record(fields),
+            value => StructuredData::Value(value),
+        }
+    }
+
+    /// The data as a single value; a table is a list of records
+    pub fn to_structured(&self) -> Result<StructuredValue, XsError> {
+        Ok(match self {
+            StructuredData::Table { rows, .. } => StructuredValue::List(
+                rows.iter()
+                    .map(|row| StructuredValue::Record(row.clone()))
+                    .collect(),
+            ),
+            StructuredData::List(items) => StructuredValue::List(items.clone()),
+            StructuredData::Record(fields) => StructuredValue::Record(fields.clone()),
+            StructuredData::Value(value) => value.clone(),
+            StructuredData::Definition(_) | StructuredData::Definitions(_) => {
+                self.to_table()?.to_structured()?
+            }
+            StructuredData::Empty => StructuredValue::Null,
+        })
+    }
+
+    /// The runtime values of the items of a list or table, one per row
+    pub fn to_values(&self) -> Result<Vec<Value>, XsError> {
+        match self {
+            StructuredData::Table { rows, .. } => rows
+                .iter()
+                .map(|row| StructuredValue::Record(row.clone()).to_value())
+                .collect(),
+            StructuredData::List(items) => items.iter().map(|item| item.to_value()).collect(),
+            StructuredData::Definitions(_) | StructuredData::Definition(_) => {
+                self.to_table()?.to_values()
+            }
+            StructuredData::Empty => Ok(Vec::new()),
+            _ => Err(XsError::RuntimeError(
+                vibe_language::Span::new(0, 0),
+                "Expected a list or a table".to_string(),
+            )),
+        }
+    }
+
+    /// The Vibe type of this data: a table is a list of records
+    pub fn value_type(&self) -> Type {
+        match self {
+            StructuredData::Definition(_) | StructuredData::Definitions(_) => self
+                .to_table()
+                .map(|table| table.value_type())
+                .unwrap_or_else(|_| Type::Var("a".to_string())),
+            StructuredData::Table { columns, rows } => {
+                let mut fields: Vec<(String, Type)> = columns
+                    .iter()
+                    .map(|column| {
+                        let ty = rows
+                            .iter()
+                            .filter_map(|row| row.get(column))
+                            .find(|value| !matches!(value, StructuredValue::Null))
+                            .map(StructuredValue::value_type)
+                            .unwrap_or_else(|| Type::Var(column.clone()));
+                        (column.clone(), ty)
+                    })
+                    .collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Type::List(Box::new(Type::Record { fields }))
+            }
+            StructuredData::Value(value) => value.value_type(),
+            StructuredData::List(items) => StructuredValue::List(items.clone()).value_type(),
+            StructuredData::Record(fields) => StructuredValue::Record(fields.clone()).value_type(),
+            StructuredData::Empty => Type::List(Box::new(Type::Var("a".to_string()))),
+        }
+    }
+
     /// Check if data is empty
     pub fn is_empty(&self) -> bool {
         match self {
@@ -212,6 +316,45 @@ impl StructuredValue {
             Value::List(items) => {
                 StructuredValue::List(items.iter().map(Self::from_value).collect())
             }
+            Value::Record { fields } => StructuredValue::Record(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), Self::from_value(value)))
+                    .collect(),
+            ),
+            // A parsed `JsonValue` is the value it wraps
+            Value::Constructor { name, values } if name.0 == "JsonObject" => {
+                match values.as_slice() {
+                    [Value::List(fields)] => StructuredValue::Record(
+                        fields
+                            .iter()
+                            .filter_map(|field| match field {
+                                Value::Constructor { name, values } if name.0 == "JsonField" => {
+                                    match values.as_slice() {
+                                        [Value::String(key), value] => {
+                                            Some((key.clone(), Self::from_value(value)))
+                                        }
+                                        _ => None,
+                                    }
+                                }
+                                _ => None,
+                            })
+                            .collect(),
+                    ),
+                    _ => StructuredValue::Null,
+                }
+            }
+            Value::Constructor { name, values }
+                if matches!(
+                    name.0.as_str(),
+                    "JsonNull" | "JsonBool" | "JsonInt" | "JsonFloat" | "JsonString" | "JsonArray"
+                ) =>
+            {
+                match values.as_slice() {
+                    [inner] => Self::from_value(inner),
+                    _ => StructuredValue::Null,
+                }
+            }
             _ => StructuredValue::String(format!("{value:?}")),
         }
     }
@@ -227,13 +370,16 @@ impl StructuredValue {
                 let values: Result<Vec<_>, _> = items.iter().map(|item| item.to_value()).collect();
                 Ok(Value::List(values?))
             }
-            StructuredValue::Record(_fields) => {
-                // Records are not supported in Value enum
-                Err(XsError::RuntimeError(
-                    vibe_language::Span::new(0, 0),
-                    "Record values are not supported".to_string(),
-                ))
+            StructuredValue::Record(fields) => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(name, value)| Ok((name.clone(), value.to_value()?)))
+                    .collect::<Result<Vec<_>, XsError>>()?;
+                // Same field order as evaluated record literals
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Ok(Value::Record { fields })
             }
+            StructuredValue::DateTime(dt) => Ok(Value::String(dt.to_rfc3339())),
             _ => Err(XsError::RuntimeError(
                 vibe_language::Span::new(0, 0),
                 format!("Cannot convert {self:?} to Value"),
@@ -241,6 +387,47 @@ impl StructuredValue {
         }
     }
 
+    /// The Vibe type of this value. Dates are strings, and the element type
+    /// of a list is that of its first element.
+    pub fn value_type(&self) -> Type {
+        match self {
+            StructuredValue::String(_) | StructuredValue::DateTime(_) => Type::String,
+            StructuredValue::Int(_) => Type::Int,
+            StructuredValue::Float(_) => Type::Float,
+            StructuredValue::Bool(_) => Type::Bool,
+            StructuredValue::List(items) => Type::List(Box::new(
+                items
+                    .first()
+                    .map(Self::value_type)
+                    .unwrap_or_else(|| Type::Var("a".to_string())),
+            )),
+            StructuredValue::Record(fields) => {
+                let mut fields: Vec<(String, Type)> = fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.value_type()))
+                    .collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Type::Record { fields }
+            }
+            StructuredValue::Null => Type::Var("a".to_string()),
+        }
+    }
+
+    /// Compare two values of the same type. Ints and floats compare as
+    /// numbers; values of different types are unordered.
+    pub fn compare(&self, other: &StructuredValue) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (StructuredValue::String(a), StructuredValue::String(b)) => Some(a.cmp(b)),
+            (StructuredValue::Int(a), StructuredValue::Int(b)) => Some(a.cmp(b)),
+            (StructuredValue::Float(a), StructuredValue::Float(b)) => a.partial_cmp(b),
+            (StructuredValue::Int(a), StructuredValue::Float(b)) => (*a as f64).partial_cmp(b),
+            (StructuredValue::Float(a), StructuredValue::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (StructuredValue::Bool(a), StructuredValue::Bool(b)) => Some(a.cmp(b)),
+            (StructuredValue::DateTime(a), StructuredValue::DateTime(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
     /// Get as string if possible
     pub fn as_string(&self) -> Option<&str> {
         match self {
@@ -0,0 +1,189 @@
+//! Persistent, hash-keyed test results
+//!
+//! As in Unison, a test's result is stored under the hash of the test
+//! together with the hashes of every definition it transitively depends on.
+//! The result stays valid until one of those changes, so renaming a test
+//! keeps it. Results of tests that no longer exist are pruned after a run.
+
+use crate::hash::DefinitionHash;
+use crate::WorkspaceError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where the cache is kept, relative to the directory tests are run in
+pub const CACHE_FILE: &str = ".vibe/test-cache.json";
+
+/// The key a test's result is stored under. Results recorded by another
+/// version of vibe are not reused.
+pub fn result_key<'a>(
+    test: &DefinitionHash,
+    dependencies: impl IntoIterator<Item = &'a DefinitionHash>,
+) -> String {
+    let mut dependencies: Vec<&DefinitionHash> = dependencies.into_iter().collect();
+    dependencies.sort_by_key(|hash| hash.0);
+    dependencies.dedup();
+
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(test.0);
+    for dependency in dependencies {
+        hasher.update(dependency.0);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// A stored test result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedTestResult {
+    /// Name of the test when the result was recorded
+    pub name: String,
+    pub passed: bool,
+    /// What the test produced, or why it failed
+    pub output: String,
+    /// How long the run took, in microseconds
+    pub duration_us: u64,
+}
+
+impl CachedTestResult {
+    pub fn new(name: String, passed: bool, output: String, duration: Duration) -> Self {
+        Self {
+            name,
+            passed,
+            output,
+            duration_us: duration.as_micros() as u64,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(self.duration_us)
+    }
+}
+
+/// Test results by key, optionally backed by a file
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TestResultCache {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    results: BTreeMap<String, CachedTestResult>,
+}
+
+impl TestResultCache {
+    /// A cache that is never written to disk
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Load the cache kept in `dir`; a missing file is an empty cache
+    pub fn load(dir: &Path) -> Result<Self, WorkspaceError> {
+        let path = dir.join(CACHE_FILE);
+        let mut cache = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?).map_err(|e| {
+                WorkspaceError::ParseError(format!("Failed to parse {}: {}", path.display(), e))
+            })?
+        } else {
+            Self::default()
+        };
+        cache.path = Some(path);
+        Ok(cache)
+    }
+
+    /// Write the cache back to the file it was loaded from
+    pub fn save(&self) -> Result<(), WorkspaceError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            WorkspaceError::ParseError(format!("Failed to serialize test cache: {}", e))
+        })?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&CachedTestResult> {
+        self.results.get(key)
+    }
+
+    pub fn insert(&mut self, key: String, result: CachedTestResult) {
+        self.results.insert(key, result);
+    }
+
+    /// Drop the results of every key not in `seen`
+    pub fn prune<'a>(&mut self, seen: impl IntoIterator<Item = &'a str>) {
+        let seen: HashSet<&str> = seen.into_iter().collect();
+        self.results.retain(|key, _| seen.contains(key.as_str()));
+    }
+
+    pub fn clear(&mut self) {
+        self.results.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_depends_on_the_dependency_closure_not_its_order() {
+        let test = DefinitionHash([1; 32]);
+        let a = DefinitionHash([2; 32]);
+        let b = DefinitionHash([3; 32]);
+
+        assert_eq!(result_key(&test, [&a, &b]), result_key(&test, [&b, &a, &b]));
+        assert_ne!(result_key(&test, [&a]), result_key(&test, [&a, &b]));
+        assert_ne!(result_key(&test, [&a]), result_key(&b, [&a]));
+    }
+
+    #[test]
+    fn test_results_survive_a_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = result_key(&DefinitionHash([1; 32]), []);
+
+        let mut cache = TestResultCache::load(dir.path()).unwrap();
+        assert!(cache.is_empty());
+        cache.insert(
+            key.clone(),
+            CachedTestResult::new(
+                "adds".to_string(),
+                true,
+                "Test passed".to_string(),
+                Duration::from_millis(3),
+            ),
+        );
+        cache.save().unwrap();
+
+        let cache = TestResultCache::load(dir.path()).unwrap();
+        let result = cache.get(&key).unwrap();
+        assert_eq!(result.name, "adds");
+        assert_eq!(result.duration(), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn test_prune_keeps_only_seen_keys() {
+        let result = |name: &str| {
+            CachedTestResult::new(name.to_string(), true, String::new(), Duration::ZERO)
+        };
+        let mut cache = TestResultCache::in_memory();
+        cache.insert("a".to_string(), result("a"));
+        cache.insert("b".to_string(), result("b"));
+
+        cache.prune(["b", "c"]);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+    }
+}
@@ -12,8 +12,8 @@ pub fn generate_wasm_direct(expr: &Expr) -> Result<WasmModule, XsError> {
     // Type check first
     let mut type_checker = TypeChecker::new();
     let mut type_env = TypeEnv::new();
-    let _ty = type_checker.check(expr, &mut type_env)
-        .map_err(|e| XsError::TypeError(vibe_language::Span::new(0, 0), e))?;
+    let _ty = type_checker.check_spanned(expr, &mut type_env)
+        .map_err(|d| d.to_xs_error())?;
     
     // Convert to IR (simplified - just use untyped IR for now)
     let ir = expr_to_ir(expr)?;
@@ -0,0 +1,660 @@
+//! Type annotations as written by the programmer
+//!
+//! An annotation may quantify its type variables explicitly at the top,
+//! `forall a b. (a -> b) -> List a -> List b`, refer to type aliases
+//! (`type Name = String`) and apply type variables to arguments (`f a`).
+//! Before the type checker uses one, its aliases are expanded and it is
+//! kind checked: `List` has kind `* -> *`, so `List Int` is a type but
+//! `List` alone is not, and `f` in `f a` must be a type constructor.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::TypeEnv;
+use vibe_language::{Type, TypeDefinition};
+
+/// The kind of a type: `*` for types with values, `k1 -> k2` for type
+/// constructors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Type,
+    Arrow(Box<Kind>, Box<Kind>),
+    Var(usize),
+}
+
+impl Kind {
+    /// How many arguments a constructor of this kind takes
+    fn arity(&self) -> usize {
+        match self {
+            Kind::Arrow(_, result) => 1 + result.arity(),
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Type => write!(f, "*"),
+            Kind::Arrow(from, to) if matches!(**from, Kind::Arrow(..)) => {
+                write!(f, "({from}) -> {to}")
+            }
+            Kind::Arrow(from, to) => write!(f, "{from} -> {to}"),
+            Kind::Var(n) => write!(f, "k{n}"),
+        }
+    }
+}
+
+/// `ty` with every type alias replaced by the type it names
+pub(crate) fn expand_aliases(ty: &Type, env: &TypeEnv) -> Result<Type, String> {
+    expand(ty, env, &mut Vec::new())
+}
+
+fn expand(ty: &Type, env: &TypeEnv, expanding: &mut Vec<String>) -> Result<Type, String> {
+    let all = |types: &[Type], expanding: &mut Vec<String>| {
+        types
+            .iter()
+            .map(|ty| expand(ty, env, expanding))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(match ty {
+        Type::UserDefined { name, type_params } => {
+            let args = all(type_params, expanding)?;
+            let Some(definition) = env.lookup_type_definition(name) else {
+                return Ok(Type::UserDefined {
+                    name: name.clone(),
+                    type_params: args,
+                });
+            };
+            let Some(target) = &definition.alias else {
+                return Ok(Type::UserDefined {
+                    name: name.clone(),
+                    type_params: args,
+                });
+            };
+            if expanding.contains(name) {
+                return Err(format!("Type alias `{name}` refers to itself"));
+            }
+            if definition.type_params.len() != args.len() {
+                return Err(format!(
+                    "Type alias `{name}` expects {} argument(s) but was given {}",
+                    definition.type_params.len(),
+                    args.len()
+                ));
+            }
+            let substitution: HashMap<String, Type> =
+                definition.type_params.iter().cloned().zip(args).collect();
+            expanding.push(name.clone());
+            let expanded = expand(&target.apply_subst(&substitution), env, expanding);
+            expanding.pop();
+            expanded?
+        }
+        Type::List(inner) => Type::List(Box::new(expand(inner, env, expanding)?)),
+        Type::Option(inner) => Type::Option(Box::new(expand(inner, env, expanding)?)),
+        Type::Function(from, to) => Type::Function(
+            Box::new(expand(from, env, expanding)?),
+            Box::new(expand(to, env, expanding)?),
+        ),
+        Type::FunctionWithEffect { from, to, effects } => Type::FunctionWithEffect {
+            from: Box::new(expand(from, env, expanding)?),
+            to: Box::new(expand(to, env, expanding)?),
+            effects: effects.clone(),
+        },
+        Type::Tuple(types) => Type::Tuple(all(types, expanding)?),
+        Type::Record { fields } => Type::Record {
+            fields: expand_fields(fields, env, expanding)?,
+        },
+        Type::ExtensibleRecord { fields, row } => Type::ExtensibleRecord {
+            fields: expand_fields(fields, env, expanding)?,
+            row: row.clone(),
+        },
+        Type::Applied { constructor, args } => Type::Applied {
+            constructor: constructor.clone(),
+            args: all(args, expanding)?,
+        },
+        Type::Forall { vars, body } => Type::Forall {
+            vars: vars.clone(),
+            body: Box::new(expand(body, env, expanding)?),
+        },
+        Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit | Type::Var(_) => {
+            ty.clone()
+        }
+    })
+}
+
+fn expand_fields(
+    fields: &[(String, Type)],
+    env: &TypeEnv,
+    expanding: &mut Vec<String>,
+) -> Result<Vec<(String, Type)>, String> {
+    fields
+        .iter()
+        .map(|(name, ty)| Ok((name.clone(), expand(ty, env, expanding)?)))
+        .collect()
+}
+
+/// Checks that annotation `ann` is a type of kind `*`. Only the top of an
+/// annotation may be quantified.
+pub(crate) fn check_annotation(ann: &Type, env: &TypeEnv) -> Result<(), String> {
+    let body = match ann {
+        Type::Forall { body, .. } => body,
+        _ => ann,
+    };
+    Kinds::new(env).expect_type(body)
+}
+
+/// Checks the type a type alias names, which must be a type of kind `*`
+/// given its parameters
+pub(crate) fn check_alias(definition: &TypeDefinition, env: &TypeEnv) -> Result<(), String> {
+    let Some(target) = &definition.alias else {
+        return Ok(());
+    };
+    if mentions(target, &definition.name) {
+        return Err(format!("Type alias `{}` refers to itself", definition.name));
+    }
+    let target = expand_aliases(target, env)?;
+    Kinds::new(env).expect_type(&target)
+}
+
+fn mentions(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::UserDefined {
+            name: other,
+            type_params,
+        } => other == name || type_params.iter().any(|ty| mentions(ty, name)),
+        Type::List(inner) | Type::Option(inner) => mentions(inner, name),
+        Type::Function(from, to) | Type::FunctionWithEffect { from, to, .. } => {
+            mentions(from, name) || mentions(to, name)
+        }
+        Type::Tuple(types) | Type::Applied { args: types, .. } => {
+            types.iter().any(|ty| mentions(ty, name))
+        }
+        Type::Record { fields } | Type::ExtensibleRecord { fields, .. } => {
+            fields.iter().any(|(_, ty)| mentions(ty, name))
+        }
+        Type::Forall { body, .. } => mentions(body, name),
+        _ => false,
+    }
+}
+
+/// Kind inference over one annotation
+struct Kinds<'a> {
+    env: &'a TypeEnv,
+    /// The kind of each type variable seen so far
+    vars: HashMap<String, Kind>,
+    solved: HashMap<usize, Kind>,
+    next: usize,
+    /// Definitions whose kind is being inferred; a recursive reference
+    /// assumes every parameter has kind `*`
+    defining: Vec<String>,
+}
+
+impl<'a> Kinds<'a> {
+    fn new(env: &'a TypeEnv) -> Self {
+        Kinds {
+            env,
+            vars: HashMap::new(),
+            solved: HashMap::new(),
+            next: 0,
+            defining: Vec::new(),
+        }
+    }
+
+    fn fresh(&mut self) -> Kind {
+        self.next += 1;
+        Kind::Var(self.next - 1)
+    }
+
+    fn resolve(&self, kind: &Kind) -> Kind {
+        match kind {
+            Kind::Var(n) => match self.solved.get(n) {
+                Some(kind) => self.resolve(kind),
+                None => kind.clone(),
+            },
+            Kind::Arrow(from, to) => {
+                Kind::Arrow(Box::new(self.resolve(from)), Box::new(self.resolve(to)))
+            }
+            Kind::Type => Kind::Type,
+        }
+    }
+
+    /// `kind` as far as it is known, for error messages; kinds that could
+    /// be anything are shown as `*`
+    fn known(&self, kind: &Kind) -> Kind {
+        match self.resolve(kind) {
+            Kind::Var(_) => Kind::Type,
+            Kind::Arrow(from, to) => {
+                Kind::Arrow(Box::new(self.known(&from)), Box::new(self.known(&to)))
+            }
+            Kind::Type => Kind::Type,
+        }
+    }
+
+    fn unify(&mut self, k1: &Kind, k2: &Kind) -> bool {
+        match (self.resolve(k1), self.resolve(k2)) {
+            (Kind::Type, Kind::Type) => true,
+            (Kind::Var(a), Kind::Var(b)) if a == b => true,
+            (Kind::Var(n), kind) | (kind, Kind::Var(n)) => {
+                if occurs(n, &kind) {
+                    return false;
+                }
+                self.solved.insert(n, kind);
+                true
+            }
+            (Kind::Arrow(from1, to1), Kind::Arrow(from2, to2)) => {
+                self.unify(&from1, &from2) && self.unify(&to1, &to2)
+            }
+            _ => false,
+        }
+    }
+
+    fn var_kind(&mut self, name: &str) -> Kind {
+        if let Some(kind) = self.vars.get(name) {
+            return kind.clone();
+        }
+        let kind = self.fresh();
+        self.vars.insert(name.to_string(), kind.clone());
+        kind
+    }
+
+    /// Checks that `ty` is a type of kind `*`
+    fn expect_type(&mut self, ty: &Type) -> Result<(), String> {
+        let kind = self.infer(ty)?;
+        if self.unify(&kind, &Kind::Type) {
+            return Ok(());
+        }
+        let kind = self.known(&kind);
+        Err(match ty {
+            Type::UserDefined { name, type_params } => format!(
+                "Type constructor `{name}` expects {} argument(s) but was given {}",
+                type_params.len() + kind.arity(),
+                type_params.len()
+            ),
+            Type::Var(name) => {
+                format!("Kind mismatch: `{name}` is used as `*` and as `{kind}`")
+            }
+            _ => format!("Kind mismatch: `{ty}` has kind `{kind}` but a type is expected here"),
+        })
+    }
+
+    fn infer(&mut self, ty: &Type) -> Result<Kind, String> {
+        match ty {
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit => {}
+            Type::Var(name) => return Ok(self.var_kind(name)),
+            Type::List(inner) | Type::Option(inner) => self.expect_type(inner)?,
+            Type::Function(from, to) | Type::FunctionWithEffect { from, to, .. } => {
+                self.expect_type(from)?;
+                self.expect_type(to)?;
+            }
+            Type::Tuple(types) => {
+                for ty in types {
+                    self.expect_type(ty)?;
+                }
+            }
+            Type::Record { fields } => {
+                for (_, ty) in fields {
+                    self.expect_type(ty)?;
+                }
+            }
+            Type::ExtensibleRecord { fields, row } => {
+                for (_, ty) in fields {
+                    self.expect_type(ty)?;
+                }
+                self.expect_type(&Type::Var(row.clone()))?;
+            }
+            Type::UserDefined { name, type_params } => {
+                let kind = self.constructor_kind(name);
+                return self.apply(ty, name, kind, type_params);
+            }
+            Type::Applied { constructor, args } => {
+                let kind = self.var_kind(constructor);
+                return self.apply(ty, constructor, kind, args);
+            }
+            Type::Forall { .. } => {
+                return Err(format!(
+                    "`forall` is only allowed at the top of a type annotation, not in `{ty}`"
+                ))
+            }
+        }
+        Ok(Kind::Type)
+    }
+
+    /// The kind of `head` applied to `args`, as `ty`
+    fn apply(&mut self, ty: &Type, head: &str, kind: Kind, args: &[Type]) -> Result<Kind, String> {
+        let mut wanted = self.fresh();
+        let result = wanted.clone();
+        for arg in args.iter().rev() {
+            let arg_kind = self.infer(arg)?;
+            wanted = Kind::Arrow(Box::new(arg_kind), Box::new(wanted));
+        }
+        if self.unify(&kind, &wanted) {
+            return Ok(result);
+        }
+        let kind = self.known(&kind);
+        Err(match ty {
+            Type::UserDefined { .. } if kind.arity() < args.len() => format!(
+                "Type constructor `{head}` expects {} argument(s) but was given {}",
+                kind.arity(),
+                args.len()
+            ),
+            Type::Applied { .. } => format!(
+                "Kind mismatch: `{head}` is used as `{}` and as `{kind}`",
+                self.known(&wanted)
+            ),
+            _ => format!("Kind mismatch in `{ty}`: `{head}` has kind `{kind}`"),
+        })
+    }
+
+    /// The kind of the type constructor `name`. Names that are not defined
+    /// may have any kind.
+    fn constructor_kind(&mut self, name: &str) -> Kind {
+        let arrows = |params: usize| {
+            (0..params).fold(Kind::Type, |kind, _| {
+                Kind::Arrow(Box::new(Kind::Type), Box::new(kind))
+            })
+        };
+        match name {
+            "List" | "Option" => return arrows(1),
+            _ => {}
+        }
+        let Some(definition) = self.env.lookup_type_definition(name) else {
+            return self.fresh();
+        };
+        if self.defining.iter().any(|defining| defining == name) {
+            return arrows(definition.type_params.len());
+        }
+        self.definition_kind(definition)
+    }
+
+    /// The kind of a type definition, inferred from how its parameters are
+    /// used; parameters used only as types have kind `*`
+    fn definition_kind(&mut self, definition: &TypeDefinition) -> Kind {
+        let outer = std::mem::take(&mut self.vars);
+        self.defining.push(definition.name.clone());
+        let params: Vec<Kind> = definition
+            .type_params
+            .iter()
+            .map(|param| self.var_kind(param))
+            .collect();
+        let fields = definition
+            .alias
+            .iter()
+            .chain(definition.constructors.iter().flat_map(|c| &c.fields));
+        for field in fields {
+            // A definition that does not kind check was reported where it
+            // was declared
+            let _ = self.expect_type(field);
+        }
+        self.defining.pop();
+        self.vars = outer;
+        params.iter().rev().fold(Kind::Type, |kind, param| {
+            let param = match self.resolve(param) {
+                Kind::Var(_) => Kind::Type,
+                param => param,
+            };
+            Kind::Arrow(Box::new(param), Box::new(kind))
+        })
+    }
+}
+
+fn occurs(n: usize, kind: &Kind) -> bool {
+    match kind {
+        Kind::Var(m) => n == *m,
+        Kind::Arrow(from, to) => occurs(n, from) || occurs(n, to),
+        Kind::Type => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::type_check;
+    use vibe_language::{Expr, Ident, Literal, Span};
+
+    fn span() -> Span {
+        Span::new(0, 0)
+    }
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(Ident(name.to_string()), span())
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Int(n), span())
+    }
+
+    fn lambda(params: &[&str], body: Expr) -> Expr {
+        Expr::Lambda {
+            params: params
+                .iter()
+                .map(|param| (Ident(param.to_string()), None))
+                .collect(),
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn apply(func: Expr, args: Vec<Expr>) -> Expr {
+        Expr::Apply {
+            func: Box::new(func),
+            args,
+            span: span(),
+        }
+    }
+
+    fn let_in(name: &str, type_ann: Type, value: Expr, body: Expr) -> Expr {
+        Expr::LetIn {
+            name: Ident(name.to_string()),
+            type_ann: Some(type_ann),
+            value: Box::new(value),
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn forall(vars: &[&str], body: Type) -> Type {
+        Type::Forall {
+            vars: vars.iter().map(|var| var.to_string()).collect(),
+            body: Box::new(body),
+        }
+    }
+
+    fn var(name: &str) -> Type {
+        Type::Var(name.to_string())
+    }
+
+    fn user(name: &str, type_params: Vec<Type>) -> Type {
+        Type::UserDefined {
+            name: name.to_string(),
+            type_params,
+        }
+    }
+
+    fn applied(constructor: &str, args: Vec<Type>) -> Type {
+        Type::Applied {
+            constructor: constructor.to_string(),
+            args,
+        }
+    }
+
+    fn function(from: Type, to: Type) -> Type {
+        Type::Function(Box::new(from), Box::new(to))
+    }
+
+    fn alias(name: &str, params: &[&str], target: Type) -> TypeDefinition {
+        TypeDefinition {
+            name: name.to_string(),
+            type_params: params.iter().map(|p| p.to_string()).collect(),
+            constructors: vec![],
+            alias: Some(target),
+        }
+    }
+
+    #[test]
+    fn test_aliases_expand_with_their_arguments() {
+        let mut env = TypeEnv::default();
+        env.add_type_definition("Name".to_string(), alias("Name", &[], Type::String));
+        env.add_type_definition(
+            "Pair".to_string(),
+            alias("Pair", &["a"], Type::Tuple(vec![var("a"), var("a")])),
+        );
+
+        let ann = function(
+            user("Name", vec![]),
+            user("Pair", vec![user("Name", vec![])]),
+        );
+        assert_eq!(
+            expand_aliases(&ann, &env).unwrap(),
+            function(Type::String, Type::Tuple(vec![Type::String, Type::String]))
+        );
+
+        let error = expand_aliases(&user("Pair", vec![]), &env).unwrap_err();
+        assert_eq!(
+            error,
+            "Type alias `Pair` expects 1 argument(s) but was given 0"
+        );
+
+        let looping = alias("Loop", &[], Type::List(Box::new(user("Loop", vec![]))));
+        let error = check_alias(&looping, &env).unwrap_err();
+        assert_eq!(error, "Type alias `Loop` refers to itself");
+    }
+
+    #[test]
+    fn test_higher_kinded_variables_are_kind_checked() {
+        let env = TypeEnv::default();
+
+        // forall f a b. (a -> b) -> f a -> f b
+        let map = Type::Forall {
+            vars: vec!["f".to_string(), "a".to_string(), "b".to_string()],
+            body: Box::new(function(
+                function(var("a"), var("b")),
+                function(applied("f", vec![var("a")]), applied("f", vec![var("b")])),
+            )),
+        };
+        assert_eq!(check_annotation(&map, &env), Ok(()));
+
+        // f -> f Int
+        let mixed = function(var("f"), applied("f", vec![Type::Int]));
+        let error = check_annotation(&mixed, &env).unwrap_err();
+        assert_eq!(error, "Kind mismatch: `f` is used as `* -> *` and as `*`");
+
+        // List -> Int, List Int Int
+        let bare = function(user("List", vec![]), Type::Int);
+        let error = check_annotation(&bare, &env).unwrap_err();
+        assert_eq!(
+            error,
+            "Type constructor `List` expects 1 argument(s) but was given 0"
+        );
+        let extra = user("List", vec![Type::Int, Type::Int]);
+        let error = check_annotation(&extra, &env).unwrap_err();
+        assert_eq!(
+            error,
+            "Type constructor `List` expects 1 argument(s) but was given 2"
+        );
+
+        // f List, with f applied to a type elsewhere
+        let wrapped = function(
+            applied("f", vec![user("List", vec![])]),
+            applied("g", vec![Type::Int]),
+        );
+        assert_eq!(check_annotation(&wrapped, &env), Ok(()));
+
+        // (forall a. a) -> Int
+        let nested = function(
+            Type::Forall {
+                vars: vec!["a".to_string()],
+                body: Box::new(var("a")),
+            },
+            Type::Int,
+        );
+        assert!(check_annotation(&nested, &env)
+            .unwrap_err()
+            .contains("only allowed at the top"));
+    }
+
+    #[test]
+    fn test_quantified_annotations_stay_polymorphic() {
+        // let twice : forall f a. (f a -> f a) -> f a -> f a = fn g x -> g (g x)
+        // in twice (fn xs -> xs) [1]
+        let f_a = applied("f", vec![var("a")]);
+        let twice = let_in(
+            "twice",
+            forall(
+                &["f", "a"],
+                function(
+                    function(f_a.clone(), f_a.clone()),
+                    function(f_a.clone(), f_a),
+                ),
+            ),
+            lambda(
+                &["g", "x"],
+                apply(ident("g"), vec![apply(ident("g"), vec![ident("x")])]),
+            ),
+            apply(
+                ident("twice"),
+                vec![
+                    lambda(&["xs"], ident("xs")),
+                    Expr::List(vec![int(1)], span()),
+                ],
+            ),
+        );
+        assert_eq!(type_check(&twice).unwrap().to_string(), "(List Int)");
+
+        // let inc : forall a. a -> a = fn x -> x + 1 in inc
+        let inc = let_in(
+            "inc",
+            forall(&["a"], function(var("a"), var("a"))),
+            lambda(&["x"], apply(ident("+"), vec![ident("x"), int(1)])),
+            ident("inc"),
+        );
+        let error = type_check(&inc).unwrap_err().to_string();
+        assert!(error.contains("`a` is bound to `Int`"), "{error}");
+
+        // let first : forall a b. a -> b -> b = fn x y -> x in first
+        let first = let_in(
+            "first",
+            forall(
+                &["a", "b"],
+                function(var("a"), function(var("b"), var("b"))),
+            ),
+            lambda(&["x", "y"], ident("x")),
+            ident("first"),
+        );
+        let error = type_check(&first).unwrap_err().to_string();
+        assert!(error.contains("must be the same type"), "{error}");
+    }
+
+    #[test]
+    fn test_type_aliases_in_annotations() {
+        // type Names = List String
+        // let none : Names = [] in none
+        let names = Expr::TypeDef {
+            definition: alias("Names", &[], Type::List(Box::new(Type::String))),
+            span: span(),
+        };
+        let none = let_in(
+            "none",
+            user("Names", vec![]),
+            Expr::List(vec![], span()),
+            ident("none"),
+        );
+        let program = Expr::Block {
+            exprs: vec![names.clone(), none],
+            span: span(),
+        };
+        assert_eq!(type_check(&program).unwrap().to_string(), "(List String)");
+
+        // let ones : Names = [1] in ones
+        let ones = let_in(
+            "ones",
+            user("Names", vec![]),
+            Expr::List(vec![int(1)], span()),
+            ident("ones"),
+        );
+        let program = Expr::Block {
+            exprs: vec![names, ones],
+            span: span(),
+        };
+        assert!(type_check(&program).is_err());
+    }
+}
@@ -28,9 +28,18 @@ impl TypeErrorHelper {
     /// Generate improved type mismatch error
     #[allow(dead_code)]
     pub fn type_mismatch(&self, expected: &Type, actual: &Type, span: Span) -> XsError {
-        let mut builder = ErrorBuilder::type_mismatch(expected.clone(), actual.clone())
+        let builder = self
+            .type_mismatch_builder(expected, actual)
             .with_snippet("", span.clone()); // Source will be added later
 
+        let context = builder.build();
+        XsError::TypeError(span, context.to_ai_format())
+    }
+
+    /// Error builder for a type mismatch with conversion suggestions attached
+    pub fn type_mismatch_builder(&self, expected: &Type, actual: &Type) -> ErrorBuilder {
+        let mut builder = ErrorBuilder::type_mismatch(expected.clone(), actual.clone());
+
         // Add specific suggestions for Int/Float mismatches
         match (expected, actual) {
             (Type::Float, Type::Int) => {
@@ -57,8 +66,7 @@ impl TypeErrorHelper {
             }
         }
 
-        let context = builder.build();
-        XsError::TypeError(span, context.to_ai_format())
+        builder
     }
 
     /// Generate improved undefined variable error
@@ -160,11 +168,25 @@ impl TypeErrorHelper {
             Type::Record { fields } => {
                 format!("record with {} field(s)", fields.len())
             }
+            Type::ExtensibleRecord { fields, .. } => {
+                format!("record with at least {} field(s)", fields.len())
+            }
             Type::Option(t) => format!("optional {}", self.type_to_readable(t)),
             Type::Tuple(types) => {
                 let type_strs: Vec<String> = types.iter().map(|t| self.type_to_readable(t)).collect();
                 format!("tuple of ({})", type_strs.join(", "))
             }
+            Type::Applied { constructor, args } => {
+                let type_strs: Vec<String> = args.iter().map(|t| self.type_to_readable(t)).collect();
+                format!(
+                    "type constructor variable {} applied to ({})",
+                    constructor,
+                    type_strs.join(", ")
+                )
+            }
+            Type::Forall { vars, body } => {
+                format!("for all {}, {}", vars.join(", "), self.type_to_readable(body))
+            }
         }
     }
 
@@ -298,11 +320,23 @@ fn type_string(ty: &Type) -> String {
                 .collect();
             format!("{{ {} }}", field_strs.join(", "))
         }
+        Type::ExtensibleRecord { fields, row } => {
+            let field_strs: Vec<String> = fields
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, type_string(ty)))
+                .collect();
+            format!("{{ {} | {} }}", field_strs.join(", "), row)
+        }
         Type::Option(t) => format!("{}?", type_string(t)),
         Type::Tuple(types) => {
             let type_strs: Vec<String> = types.iter().map(type_string).collect();
             format!("({})", type_strs.join(", "))
         }
+        Type::Applied { constructor, args } => {
+            let type_strs: Vec<String> = args.iter().map(type_string).collect();
+            format!("({} {})", constructor, type_strs.join(" "))
+        }
+        Type::Forall { vars, body } => format!("forall {}. {}", vars.join(" "), type_string(body)),
     }
 }
 
@@ -4,12 +4,16 @@
 //! for the XS language compiler.
 
 // Re-export type checker functionality
+mod annotations;
 mod effect_checker;
 mod effect_inference;
 mod improved_errors;
 mod module_env;
+mod mutable_vars;
 mod perceus;
+mod records;
 pub mod semantic_analysis;
+pub mod type_diagnostics;
 pub mod wasm;
 
 use effect_checker::EffectScheme;
@@ -20,12 +24,13 @@ use effect_checker::EffectScheme;
 
 pub use module_env::{ExportedItem, ModuleEnv, ModuleInfo};
 pub use perceus::PerceusTransform;
+pub use type_diagnostics::{Provenance, TypeDiagnostic};
 
 // Type checker exports
 use std::collections::{HashMap, HashSet};
 use vibe_language::{
-    extensible_effects::ExtensibleEffectRow, DoStatement, Expr, Ident, Literal, Pattern, Span,
-    Type, TypeDefinition, XsError,
+    extensible_effects::ExtensibleEffectRow, DoStatement, Effect, EffectRow, EffectSet, Expr, Ident,
+    Literal, Pattern, Span, Type, TypeDefinition, XsError,
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -58,6 +63,8 @@ impl TypeScheme {
 
 pub struct TypeEnv {
     bindings: Vec<HashMap<String, TypeScheme>>,
+    /// Names declared with `mut` in each scope of `bindings`
+    mutable: Vec<HashSet<String>>,
     type_definitions: HashMap<String, TypeDefinition>,
     modules: HashMap<String, HashMap<String, TypeScheme>>,
 }
@@ -66,6 +73,7 @@ impl Default for TypeEnv {
     fn default() -> Self {
         let mut env = TypeEnv {
             bindings: vec![HashMap::new()],
+            mutable: vec![HashSet::new()],
             type_definitions: HashMap::new(),
             modules: HashMap::new(),
         };
@@ -266,10 +274,11 @@ impl Default for TypeEnv {
 
         env.add_builtin(
             "print",
-            Type::Function(
-                Box::new(Type::Var("a".to_string())),
-                Box::new(Type::Var("a".to_string())),
-            ),
+            Type::FunctionWithEffect {
+                from: Box::new(Type::Var("a".to_string())),
+                to: Box::new(Type::Var("a".to_string())),
+                effects: EffectRow::Concrete(EffectSet::single(Effect::IO)),
+            },
         );
 
         // Test framework builtins
@@ -287,6 +296,22 @@ impl Default for TypeEnv {
             ),
         );
 
+        for name in ["property", "forAll"] {
+            env.add_builtin(
+                name,
+                Type::Function(
+                    Box::new(Type::String),
+                    Box::new(Type::Function(
+                        Box::new(Type::Function(
+                            Box::new(Type::Var("a".to_string())),
+                            Box::new(Type::Var("b".to_string())),
+                        )),
+                        Box::new(Type::Unit),
+                    )),
+                ),
+            );
+        }
+
         env.add_builtin(
             "assert",
             Type::Function(
@@ -302,10 +327,11 @@ impl Default for TypeEnv {
             "inspect",
             Type::Function(
                 Box::new(Type::Var("a".to_string())),
-                Box::new(Type::Function(
-                    Box::new(Type::String),
-                    Box::new(Type::Var("a".to_string())),
-                )),
+                Box::new(Type::FunctionWithEffect {
+                    from: Box::new(Type::String),
+                    to: Box::new(Type::Var("a".to_string())),
+                    effects: EffectRow::Concrete(EffectSet::single(Effect::IO)),
+                }),
             ),
         );
 
@@ -332,18 +358,41 @@ impl TypeEnv {
 
     pub fn push_scope(&mut self) {
         self.bindings.push(HashMap::new());
+        self.mutable.push(HashSet::new());
     }
 
     pub fn pop_scope(&mut self) {
         self.bindings.pop();
+        self.mutable.pop();
     }
 
     pub fn add_binding(&mut self, name: String, scheme: TypeScheme) {
+        if let Some(last) = self.mutable.last_mut() {
+            last.remove(&name);
+        }
         if let Some(last) = self.bindings.last_mut() {
             last.insert(name, scheme);
         }
     }
 
+    /// Bind a variable declared with `mut`, which can be assigned to
+    pub fn add_mutable_binding(&mut self, name: String, typ: Type) {
+        self.add_binding(name.clone(), TypeScheme::mono(typ));
+        if let Some(last) = self.mutable.last_mut() {
+            last.insert(name);
+        }
+    }
+
+    /// Whether `name` refers to a variable declared with `mut`
+    pub fn is_mutable(&self, name: &str) -> bool {
+        self.bindings
+            .iter()
+            .zip(&self.mutable)
+            .rev()
+            .find(|(scope, _)| scope.contains_key(name))
+            .is_some_and(|(_, mutable)| mutable.contains(name))
+    }
+
     pub fn lookup(&self, name: &str) -> Option<&TypeScheme> {
         for scope in self.bindings.iter().rev() {
             if let Some(scheme) = scope.get(name) {
@@ -357,6 +406,38 @@ impl TypeEnv {
         self.type_definitions.insert(name, def);
     }
 
+    /// Bind each constructor of `definition` as a function to its type
+    pub fn add_constructors(&mut self, definition: &TypeDefinition) {
+        for constructor in &definition.constructors {
+            let mut cons_type = Type::UserDefined {
+                name: definition.name.clone(),
+                type_params: definition
+                    .type_params
+                    .iter()
+                    .map(|p| Type::Var(p.clone()))
+                    .collect(),
+            };
+
+            // Build constructor function type
+            for field_type in constructor.fields.iter().rev() {
+                cons_type = Type::Function(Box::new(field_type.clone()), Box::new(cons_type));
+            }
+
+            let scheme = if definition.type_params.is_empty() {
+                TypeScheme::mono(cons_type)
+            } else {
+                TypeScheme {
+                    vars: definition.type_params.clone(),
+                    typ: cons_type,
+                    effects: None,
+                    effect_vars: vec![],
+                }
+            };
+
+            self.add_binding(constructor.name.clone(), scheme);
+        }
+    }
+
     pub fn lookup_type_definition(&self, name: &str) -> Option<&TypeDefinition> {
         self.type_definitions.get(name)
     }
@@ -373,12 +454,27 @@ impl TypeEnv {
             }
             self.modules.insert(module_name.clone(), module_bindings);
         }
+
+        for definition in vibe_language::builtin_modules::builtin_type_definitions() {
+            self.add_constructors(&definition);
+            self.add_type_definition(definition.name.clone(), definition);
+        }
     }
 
     pub fn lookup_module_function(&self, module: &str, function: &str) -> Option<&TypeScheme> {
         self.modules.get(module)?.get(function)
     }
 
+    /// Make the exports of every module in `modules` available as `Module.name`
+    pub fn import_modules(&mut self, modules: &ModuleEnv) {
+        for module in modules.get_all_modules() {
+            let bindings = self.modules.entry(module.name.clone()).or_default();
+            for (name, item) in &module.exports {
+                bindings.insert(name.clone(), item.type_scheme.clone());
+            }
+        }
+    }
+
     /// Get all bindings from all scopes (for effect variable collection)
     pub fn all_bindings(&self) -> Vec<(&String, &TypeScheme)> {
         let mut bindings = Vec::new();
@@ -401,10 +497,26 @@ impl TypeEnv {
     }
 }
 
+/// `mut` variables declared so far in a block, each mapped to itself, and
+/// the variables holding closures that use them, mapped to the `mut`
+/// variable they use
+#[derive(Default)]
+struct MutableScope {
+    mutable: HashMap<String, String>,
+    closures: HashMap<String, String>,
+}
+
 pub struct TypeChecker {
     fresh_var_counter: usize,
     substitutions: HashMap<String, Type>,
     effect_checker: Option<effect_checker::EffectChecker>,
+    /// Span of the innermost expression responsible for the error being propagated
+    error_span: Option<Span>,
+    /// Expected/found provenance of the type mismatch being propagated
+    pending_mismatch: Option<(Provenance, Provenance)>,
+    /// Keep checking after an error, collecting diagnostics instead of failing
+    recover: bool,
+    diagnostics: Vec<TypeDiagnostic>,
 }
 
 impl Default for TypeChecker {
@@ -419,6 +531,10 @@ impl TypeChecker {
             fresh_var_counter: 0,
             substitutions: HashMap::new(),
             effect_checker: Some(effect_checker::EffectChecker::new()),
+            error_span: None,
+            pending_mismatch: None,
+            recover: false,
+            diagnostics: Vec::new(),
         }
     }
 
@@ -427,13 +543,105 @@ impl TypeChecker {
             fresh_var_counter: 0,
             substitutions: HashMap::new(),
             effect_checker: None,
+            error_span: None,
+            pending_mismatch: None,
+            recover: false,
+            diagnostics: Vec::new(),
         }
     }
 
     fn fresh_var(&mut self) -> Type {
-        let var = Type::Var(format!("a{}", self.fresh_var_counter));
+        Type::Var(self.fresh_var_name())
+    }
+
+    fn fresh_var_name(&mut self) -> String {
+        let name = format!("a{}", self.fresh_var_counter);
         self.fresh_var_counter += 1;
-        var
+        name
+    }
+
+    /// The type a binding's annotation stands for, with its aliases
+    /// expanded and its kinds checked. Variables it quantifies with `forall`
+    /// become fresh variables, returned with their names so that
+    /// `check_quantified` can make sure they stay polymorphic.
+    fn annotation_type(
+        &mut self,
+        ann: &Type,
+        env: &TypeEnv,
+    ) -> Result<(Type, Vec<(String, Type)>), String> {
+        let ann = annotations::expand_aliases(ann, env)?;
+        annotations::check_annotation(&ann, env)?;
+        match ann {
+            Type::Forall { vars, body } => {
+                let quantified: Vec<(String, Type)> = vars
+                    .into_iter()
+                    .map(|var| (var, self.fresh_var()))
+                    .collect();
+                let subst = quantified.iter().cloned().collect();
+                Ok((self.substitute_with_map(&body, &subst), quantified))
+            }
+            ann => Ok((ann, Vec::new())),
+        }
+    }
+
+    /// The type a parameter or return type annotation stands for; only a
+    /// binding's annotation may be quantified
+    fn monotype_annotation(&mut self, ann: &Type, env: &TypeEnv) -> Result<Type, String> {
+        if let Type::Forall { .. } = ann {
+            return Err(format!(
+                "`forall` is only allowed at the top of a binding's type annotation, not in `{ann}`"
+            ));
+        }
+        Ok(self.annotation_type(ann, env)?.0)
+    }
+
+    /// Checks that the variables an annotation quantified over were not
+    /// bound to a more specific type, to each other or to a type from the
+    /// enclosing scope. A recursive binding's own name is not part of the
+    /// enclosing scope.
+    fn check_quantified(
+        &self,
+        quantified: &[(String, Type)],
+        env: &TypeEnv,
+        binding: Option<&str>,
+    ) -> Result<(), String> {
+        let mut env_vars = HashSet::new();
+        for scope in &env.bindings {
+            for (name, scheme) in scope {
+                if Some(name.as_str()) == binding {
+                    continue;
+                }
+                let typ = self.substitute(&scheme.typ);
+                env_vars.extend(
+                    Self::free_type_vars(&typ)
+                        .into_iter()
+                        .filter(|var| !scheme.vars.contains(var)),
+                );
+            }
+        }
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        for (name, var) in quantified {
+            match self.substitute(var) {
+                Type::Var(v) if !env_vars.contains(&v) => {
+                    if let Some(other) = seen.insert(v, name) {
+                        return Err(format!(
+                            "Type annotation is too general: `{other}` and `{name}` must be the same type"
+                        ));
+                    }
+                }
+                Type::Var(_) => {
+                    return Err(format!(
+                        "Type annotation is too general: `{name}` is fixed by the enclosing scope"
+                    ))
+                }
+                ty => {
+                    return Err(format!(
+                        "Type annotation is too general: `{name}` is bound to `{ty}`"
+                    ))
+                }
+            }
+        }
+        Ok(())
     }
 
     fn handle_use(
@@ -544,7 +752,98 @@ impl TypeChecker {
                 self.unify(r1, r2)
             }
             (Type::List(e1), Type::List(e2)) => self.unify(e1, e2),
+            (Type::Record { fields: f1 }, Type::Record { fields: f2 }) => {
+                let names1: Vec<&String> = f1.iter().map(|(name, _)| name).collect();
+                let names2: Vec<&String> = f2.iter().map(|(name, _)| name).collect();
+                if names1 != names2 {
+                    return Err(format!("Cannot unify {t1} with {t2}"));
+                }
+                for ((_, ty1), (_, ty2)) in f1.iter().zip(f2.iter()) {
+                    self.unify(ty1, ty2)?;
+                }
+                Ok(())
+            }
+            (Type::ExtensibleRecord { fields: f1, row }, Type::Record { fields: f2 })
+            | (Type::Record { fields: f2 }, Type::ExtensibleRecord { fields: f1, row }) => {
+                // The closed record must have every field of the open one;
+                // the row is whatever it has left over
+                let mut rest = f2.clone();
+                for (name, ty1) in f1 {
+                    let Some(index) = rest.iter().position(|(field, _)| field == name) else {
+                        return Err(format!(
+                            "Cannot unify {t1} with {t2}: field '{name}' is missing"
+                        ));
+                    };
+                    let (_, ty2) = rest.remove(index);
+                    self.unify(ty1, &ty2)?;
+                }
+                self.unify(&Type::Var(row.clone()), &Type::Record { fields: rest })
+            }
+            (
+                Type::ExtensibleRecord {
+                    fields: f1,
+                    row: r1,
+                },
+                Type::ExtensibleRecord {
+                    fields: f2,
+                    row: r2,
+                },
+            ) => {
+                let only1 = records::missing_fields(f1, f2);
+                let only2 = records::missing_fields(f2, f1);
+                for (name, ty1) in f1 {
+                    if let Some((_, ty2)) = f2.iter().find(|(field, _)| field == name) {
+                        self.unify(ty1, ty2)?;
+                    }
+                }
+                if r1 == r2 {
+                    return if only1.is_empty() && only2.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(format!("Cannot unify {t1} with {t2}"))
+                    };
+                }
+                // Each row takes the fields only the other record has, and
+                // both share whatever is left
+                let rest = self.fresh_var_name();
+                let rest1 = Type::open_record(only2, rest.clone());
+                let rest2 = Type::open_record(only1, rest);
+                self.unify(&Type::Var(r1.clone()), &rest1)?;
+                self.unify(&Type::Var(r2.clone()), &rest2)
+            }
             (Type::Option(inner1), Type::Option(inner2)) => self.unify(inner1, inner2),
+            (
+                Type::Applied {
+                    constructor: c1,
+                    args: a1,
+                },
+                Type::Applied {
+                    constructor: c2,
+                    args: a2,
+                },
+            ) if a1.len() == a2.len() => {
+                self.unify(&Type::Var(c1.clone()), &Type::Var(c2.clone()))?;
+                for (arg1, arg2) in a1.iter().zip(a2.iter()) {
+                    self.unify(arg1, arg2)?;
+                }
+                Ok(())
+            }
+            // A higher-kinded variable applied to n arguments matches any
+            // type whose last n arguments it can take, binding the
+            // constructor to the rest (`f a` with `List Int` binds `f` to `List`)
+            (Type::Applied { constructor, args }, other)
+            | (other, Type::Applied { constructor, args })
+                if !matches!(other, Type::Applied { args: fewer, .. } if fewer.len() < args.len()) =>
+            {
+                let Some((head, rest)) = other.split_constructor(args.len()) else {
+                    return Err(format!("Cannot unify {t1} with {t2}"));
+                };
+                self.unify(&Type::Var(constructor.clone()), &head)?;
+                for (arg1, arg2) in args.iter().zip(rest.iter()) {
+                    self.unify(arg1, arg2)?;
+                }
+                Ok(())
+            }
             (Type::Tuple(types1), Type::Tuple(types2)) => {
                 if types1.len() != types2.len() {
                     Err(format!(
@@ -617,6 +916,16 @@ impl TypeChecker {
             Type::UserDefined { type_params, .. } => {
                 type_params.iter().any(|t| Self::occurs_check(var, t))
             }
+            Type::Record { fields } => fields.iter().any(|(_, t)| Self::occurs_check(var, t)),
+            Type::ExtensibleRecord { fields, row } => {
+                row == var || fields.iter().any(|(_, t)| Self::occurs_check(var, t))
+            }
+            Type::Applied { constructor, args } => {
+                constructor == var || args.iter().any(|t| Self::occurs_check(var, t))
+            }
+            Type::Forall { vars, body } => {
+                !vars.iter().any(|v| v == var) && Self::occurs_check(var, body)
+            }
             _ => false,
         }
     }
@@ -626,8 +935,19 @@ impl TypeChecker {
         for var in &scheme.vars {
             subst.insert(var.clone(), self.fresh_var());
         }
+        // Explicitly quantified variables are instantiated like the
+        // scheme's own
+        let typ = match &scheme.typ {
+            Type::Forall { vars, body } => {
+                for var in vars {
+                    subst.insert(var.clone(), self.fresh_var());
+                }
+                body.as_ref()
+            }
+            typ => typ,
+        };
 
-        let instantiated_typ = self.substitute_with_map(&scheme.typ, &subst);
+        let instantiated_typ = self.substitute_with_map(typ, &subst);
 
         // If the scheme has effects, instantiate them too
         if let (Some(effects), Some(effect_checker)) = (&scheme.effects, &mut self.effect_checker) {
@@ -691,15 +1011,46 @@ impl TypeChecker {
                     .map(|t| self.substitute_with_map(t, subst))
                     .collect(),
             ),
+            Type::Record { fields } => Type::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, t)| (name.clone(), self.substitute_with_map(t, subst)))
+                    .collect(),
+            },
+            Type::ExtensibleRecord { fields, row } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, t)| (name.clone(), self.substitute_with_map(t, subst)))
+                    .collect();
+                // Splice in whatever the row has been bound to
+                let rest = self.substitute_with_map(&Type::Var(row.clone()), subst);
+                Type::extend_record(fields, rest).unwrap_or_else(|| typ.clone())
+            }
+            Type::Applied { constructor, args } => {
+                let args = args
+                    .iter()
+                    .map(|t| self.substitute_with_map(t, subst))
+                    .collect();
+                // Apply whatever the constructor variable has been bound to
+                let head = self.substitute_with_map(&Type::Var(constructor.clone()), subst);
+                Type::apply_constructor(head, args).unwrap_or_else(|| typ.clone())
+            }
+            Type::Forall { vars, body } => {
+                let mut inner = subst.clone();
+                for var in vars {
+                    inner.remove(var);
+                }
+                Type::Forall {
+                    vars: vars.clone(),
+                    body: Box::new(self.substitute_with_map(body, &inner)),
+                }
+            }
             _ => typ.clone(),
         }
     }
 
     fn generalize(&self, typ: &Type, env: &TypeEnv) -> TypeScheme {
-        let typ = self.substitute(typ);
-        let free_vars = Self::free_type_vars(&typ);
-        let env_vars = self.env_type_vars(env);
-        let vars: Vec<String> = free_vars.difference(&env_vars).cloned().collect();
+        let (typ, vars) = self.quantify(typ, env);
         TypeScheme {
             vars,
             typ,
@@ -709,10 +1060,7 @@ impl TypeChecker {
     }
 
     fn generalize_with_effects(&mut self, typ: &Type, env: &TypeEnv) -> TypeScheme {
-        let typ = self.substitute(typ);
-        let free_vars = Self::free_type_vars(&typ);
-        let env_vars = self.env_type_vars(env);
-        let vars: Vec<String> = free_vars.difference(&env_vars).cloned().collect();
+        let (typ, vars) = self.quantify(typ, env);
 
         // Extract effects from function types
         let (effects, effect_vars) = match &typ {
@@ -736,6 +1084,26 @@ impl TypeChecker {
         }
     }
 
+    /// `typ` without a top-level `forall`, and the variables to generalize
+    /// it over: those it quantified explicitly and those free in it but not
+    /// in `env`
+    fn quantify(&self, typ: &Type, env: &TypeEnv) -> (Type, Vec<String>) {
+        let (typ, mut vars) = match self.substitute(typ) {
+            Type::Forall { vars, body } => (*body, vars),
+            typ => (typ, Vec::new()),
+        };
+        let free_vars = Self::free_type_vars(&typ);
+        let env_vars = self.env_type_vars(env);
+        vars.extend(
+            free_vars
+                .difference(&env_vars)
+                .filter(|var| !vars.contains(var))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        (typ, vars)
+    }
+
     fn free_type_vars(typ: &Type) -> HashSet<String> {
         match typ {
             Type::Var(name) => {
@@ -769,6 +1137,36 @@ impl TypeChecker {
                 }
                 vars
             }
+            Type::Record { fields } => {
+                let mut vars = HashSet::new();
+                for (_, t) in fields {
+                    vars.extend(Self::free_type_vars(t));
+                }
+                vars
+            }
+            Type::ExtensibleRecord { fields, row } => {
+                let mut vars = HashSet::new();
+                for (_, t) in fields {
+                    vars.extend(Self::free_type_vars(t));
+                }
+                vars.insert(row.clone());
+                vars
+            }
+            Type::Applied { constructor, args } => {
+                let mut vars = HashSet::new();
+                for t in args {
+                    vars.extend(Self::free_type_vars(t));
+                }
+                vars.insert(constructor.clone());
+                vars
+            }
+            Type::Forall { vars: bound, body } => {
+                let mut vars = Self::free_type_vars(body);
+                for var in bound {
+                    vars.remove(var);
+                }
+                vars
+            }
             _ => HashSet::new(),
         }
     }
@@ -896,7 +1294,225 @@ impl TypeChecker {
         Ok(typ)
     }
 
+    /// Type check `expr`, reporting the first error with the span of the
+    /// sub-expression that caused it
+    pub fn check_spanned(
+        &mut self,
+        expr: &Expr,
+        env: &mut TypeEnv,
+    ) -> Result<Type, TypeDiagnostic> {
+        self.error_span = None;
+        self.pending_mismatch = None;
+        let typ = self.check(expr, env).map_err(|message| {
+            let span = self.error_span.take().unwrap_or_else(|| expr.span().clone());
+            self.make_diagnostic(span, message)
+        })?;
+        Ok(self.substitute(&typ))
+    }
+
+    /// Type check `expr`, continuing past errors.
+    ///
+    /// Every ill-typed sub-expression is reported once and then given a fresh
+    /// type variable as its error type, so checking carries on around it. The
+    /// returned type is meaningful only when there are no diagnostics.
+    pub fn check_all(&mut self, expr: &Expr, env: &mut TypeEnv) -> (Type, Vec<TypeDiagnostic>) {
+        let was_recovering = std::mem::replace(&mut self.recover, true);
+        self.error_span = None;
+        self.pending_mismatch = None;
+
+        let typ = match self.check(expr, env) {
+            Ok(typ) => self.substitute(&typ),
+            // Only reachable if the error escapes recovery, e.g. from effect inference
+            Err(message) => {
+                let span = self.error_span.take().unwrap_or_else(|| expr.span().clone());
+                let diagnostic = self.make_diagnostic(span, message);
+                self.diagnostics.push(diagnostic);
+                self.fresh_var()
+            }
+        };
+
+        self.recover = was_recovering;
+        (typ, std::mem::take(&mut self.diagnostics))
+    }
+
+    fn make_diagnostic(&mut self, span: Span, message: String) -> TypeDiagnostic {
+        let (expected, found) = match self.pending_mismatch.take() {
+            Some((expected, found)) => (Some(Box::new(expected)), Some(Box::new(found))),
+            None => (None, None),
+        };
+        TypeDiagnostic {
+            span,
+            message,
+            expected,
+            found,
+        }
+    }
+
+    /// Require `found`, the type of `expr`, to match `expected`.
+    ///
+    /// `expected_span` and `reason` describe where the expectation comes from;
+    /// on failure both sides are recorded for the diagnostic and the error is
+    /// attributed to `expr`.
+    fn expect_type(
+        &mut self,
+        expected: &Type,
+        expected_span: &Span,
+        reason: &str,
+        found: &Type,
+        expr: &Expr,
+    ) -> Result<(), String> {
+        self.unify(expected, found).map_err(|message| {
+            if self.error_span.is_none() {
+                self.error_span = Some(expr.span().clone());
+                self.pending_mismatch = Some((
+                    Provenance {
+                        typ: self.substitute(expected),
+                        span: expected_span.clone(),
+                        reason: reason.to_string(),
+                    },
+                    Provenance {
+                        typ: self.substitute(found),
+                        span: expr.span().clone(),
+                        reason: "this expression".to_string(),
+                    },
+                ));
+            }
+            message
+        })
+    }
+
+    /// Apply a function of type `func_type` to an argument, blaming the
+    /// argument if it does not fit the parameter type
+    fn apply_argument(
+        &mut self,
+        func_type: &Type,
+        func: &Expr,
+        arg_type: &Type,
+        arg: &Expr,
+    ) -> Result<Type, String> {
+        match self.substitute(func_type) {
+            Type::Function(param, result) | Type::FunctionWithEffect {
+                from: param,
+                to: result,
+                ..
+            } => {
+                let reason = match func {
+                    Expr::Ident(Ident(name), _) => format!("parameter of `{name}`"),
+                    _ => "function parameter".to_string(),
+                };
+                self.expect_type(&param, func.span(), &reason, arg_type, arg)?;
+                Ok(self.substitute(&result))
+            }
+            Type::Var(_) => {
+                let result_type = self.fresh_var();
+                let expected_func_type =
+                    Type::Function(Box::new(arg_type.clone()), Box::new(result_type.clone()));
+                self.unify(func_type, &expected_func_type)?;
+                Ok(self.substitute(&result_type))
+            }
+            other => {
+                self.error_span.get_or_insert_with(|| func.span().clone());
+                Err(format!("Cannot apply a value of type {other} as a function"))
+            }
+        }
+    }
+
+    /// Reject a closure that uses a `mut` variable of the current block
+    /// after the block has returned: the block's result, or a value assigned
+    /// to a variable declared outside the block. `expr` is the statement just
+    /// checked, with type `typ`.
+    fn check_mutable_escape(
+        &mut self,
+        expr: &Expr,
+        typ: &Type,
+        is_last: bool,
+        scope: &mut MutableScope,
+        env: &TypeEnv,
+    ) -> Result<(), String> {
+        // A binding whose check failed under recovery is missing
+        let mut bound_type = |name: &Ident| match env.lookup(&name.0).cloned() {
+            Some(scheme) => {
+                let typ = self.instantiate(&scheme);
+                self.substitute(&typ)
+            }
+            None => Type::Unit,
+        };
+        let escaping = |value: &Expr, scope: &MutableScope| {
+            mutable_vars::captured(value, &scope.mutable)
+                .or_else(|| mutable_vars::mentioned(value, &scope.closures))
+                .map(str::to_string)
+        };
+        let (name, value) = match expr {
+            Expr::Mut { name, value, .. }
+            | Expr::Let { name, value, .. }
+            | Expr::LetRec { name, value, .. } => {
+                let value_type = bound_type(name);
+                match escaping(value, scope) {
+                    Some(used) if mutable_vars::contains_function(&value_type) => {
+                        scope.closures.insert(name.0.clone(), used)
+                    }
+                    _ => scope.closures.remove(&name.0),
+                };
+                if matches!(expr, Expr::Mut { .. }) {
+                    scope.mutable.insert(name.0.clone(), name.0.clone());
+                } else {
+                    scope.mutable.remove(&name.0);
+                }
+                return Ok(());
+            }
+            Expr::Assign { name, value, .. } if !scope.mutable.contains_key(&name.0) => {
+                if !mutable_vars::contains_function(&bound_type(name)) {
+                    return Ok(());
+                }
+                (escaping(value, scope), value.as_ref())
+            }
+            _ if is_last && mutable_vars::contains_function(&self.substitute(typ)) => {
+                (escaping(expr, scope), expr)
+            }
+            _ => return Ok(()),
+        };
+        match name {
+            Some(name) => {
+                self.error_span.get_or_insert_with(|| value.span().clone());
+                Err(format!(
+                    "Mutable variable `{name}` escapes its scope in a closure"
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
     pub fn check_with_effects(&mut self, expr: &Expr, env: &mut TypeEnv) -> Result<Type, String> {
+        match self.infer_expr(expr, env) {
+            Ok(typ) => Ok(typ),
+            Err(message) => {
+                // The innermost failing expression owns the error
+                let span = self
+                    .error_span
+                    .get_or_insert_with(|| expr.span().clone())
+                    .clone();
+                if self.recover {
+                    let diagnostic = self.make_diagnostic(span, message);
+                    self.diagnostics.push(diagnostic);
+                    self.error_span = None;
+                    let error_type = self.fresh_var();
+                    // Bind a failed definition so its uses are not reported as undefined
+                    if let Expr::Let { name, .. }
+                    | Expr::LetRec { name, .. }
+                    | Expr::Rec { name, .. }
+                    | Expr::Mut { name, .. } = expr
+                    {
+                        env.add_binding(name.0.clone(), TypeScheme::mono(error_type.clone()));
+                    }
+                    Ok(error_type)
+                } else {
+                    Err(message)
+                }
+            }
+        }
+    }
+
+    fn infer_expr(&mut self, expr: &Expr, env: &mut TypeEnv) -> Result<Type, String> {
         // Check effects if effect checker is enabled
         if let Some(effect_checker) = &mut self.effect_checker {
             let _effects = effect_checker.infer_effects(expr, env)?;
@@ -923,7 +1539,7 @@ impl TypeChecker {
                     let elem_type = self.check(&exprs[0], env)?;
                     for expr in &exprs[1..] {
                         let t = self.check(expr, env)?;
-                        self.unify(&elem_type, &t)?;
+                        self.expect_type(&elem_type, exprs[0].span(), "first list element", &t, expr)?;
                     }
                     Ok(Type::List(Box::new(self.substitute(&elem_type))))
                 }
@@ -933,7 +1549,7 @@ impl TypeChecker {
                 name,
                 type_ann,
                 value,
-                ..
+                span,
             } => {
                 // Check if this is a function that references itself (recursive)
                 let is_recursive = match value.as_ref() {
@@ -946,9 +1562,16 @@ impl TypeChecker {
                     _ => false,
                 };
 
+                let annotation = match type_ann {
+                    Some(ann) => Some(self.annotation_type(ann, env)?),
+                    None => None,
+                };
                 let value_type = if is_recursive {
                     // Handle as recursive function
-                    let var_type = type_ann.clone().unwrap_or_else(|| self.fresh_var());
+                    let var_type = match &annotation {
+                        Some((ann, _)) => ann.clone(),
+                        None => self.fresh_var(),
+                    };
                     env.push_scope();
                     env.add_binding(name.0.clone(), TypeScheme::mono(var_type.clone()));
 
@@ -962,8 +1585,9 @@ impl TypeChecker {
                     self.check(value, env)?
                 };
 
-                if let Some(ann) = type_ann {
-                    self.unify(&value_type, ann)?;
+                if let Some((ann, quantified)) = &annotation {
+                    self.expect_type(ann, span, "type annotation", &value_type, value)?;
+                    self.check_quantified(quantified, env, None)?;
                 }
 
                 let scheme = self.generalize_with_effects(&value_type, env);
@@ -977,11 +1601,15 @@ impl TypeChecker {
                 value,
                 ..
             } => {
-                let var_type = type_ann.clone().unwrap_or_else(|| self.fresh_var());
+                let (var_type, quantified) = match type_ann {
+                    Some(ann) => self.annotation_type(ann, env)?,
+                    None => (self.fresh_var(), Vec::new()),
+                };
                 env.add_binding(name.0.clone(), TypeScheme::mono(var_type.clone()));
 
                 let value_type = self.check(value, env)?;
                 self.unify(&var_type, &value_type)?;
+                self.check_quantified(&quantified, env, Some(&name.0))?;
 
                 let final_type = self.substitute(&var_type);
                 let scheme = self.generalize_with_effects(&final_type, env);
@@ -995,14 +1623,16 @@ impl TypeChecker {
                 type_ann,
                 value,
                 body,
-                ..
+                span,
             } => {
                 env.push_scope();
 
                 let value_type = self.check(value, env)?;
 
                 if let Some(ann) = type_ann {
-                    self.unify(&value_type, ann)?;
+                    let (ann, quantified) = self.annotation_type(ann, env)?;
+                    self.expect_type(&ann, span, "type annotation", &value_type, value)?;
+                    self.check_quantified(&quantified, env, None)?;
                 }
 
                 let scheme = self.generalize(&value_type, env);
@@ -1019,6 +1649,7 @@ impl TypeChecker {
                 return_type,
                 effects,
                 body,
+                span,
                 ..
             } => {
                 // Handle FunctionDef similar to Lambda but with more information
@@ -1027,7 +1658,7 @@ impl TypeChecker {
 
                 for param in params {
                     let param_type = if let Some(typ) = &param.typ {
-                        typ.clone()
+                        self.monotype_annotation(typ, env)?
                     } else {
                         self.fresh_var()
                     };
@@ -1040,7 +1671,8 @@ impl TypeChecker {
 
                 // Check return type if specified
                 if let Some(ret_type) = return_type {
-                    self.unify(&body_type, ret_type)?;
+                    let ret_type = self.monotype_annotation(ret_type, env)?;
+                    self.expect_type(&ret_type, span, "declared return type", &body_type, body)?;
                 }
 
                 // Build function type
@@ -1064,7 +1696,10 @@ impl TypeChecker {
                 let mut param_types = Vec::new();
 
                 for (Ident(param_name), param_type_ann) in params {
-                    let param_type = param_type_ann.clone().unwrap_or_else(|| self.fresh_var());
+                    let param_type = match param_type_ann {
+                        Some(ann) => self.monotype_annotation(ann, env)?,
+                        None => self.fresh_var(),
+                    };
                     param_types.push(param_type.clone());
                     env.add_binding(param_name.clone(), TypeScheme::mono(param_type));
                 }
@@ -1106,7 +1741,7 @@ impl TypeChecker {
                 params,
                 return_type,
                 body,
-                ..
+                span,
             } => {
                 // First, create a fresh type variable for the recursive function
                 let rec_type = self.fresh_var();
@@ -1119,7 +1754,10 @@ impl TypeChecker {
                 // Create function type
                 let mut param_types = Vec::new();
                 for (Ident(param_name), param_type_ann) in params {
-                    let param_type = param_type_ann.clone().unwrap_or_else(|| self.fresh_var());
+                    let param_type = match param_type_ann {
+                        Some(ann) => self.monotype_annotation(ann, env)?,
+                        None => self.fresh_var(),
+                    };
                     param_types.push(param_type.clone());
                     env.add_binding(param_name.clone(), TypeScheme::mono(param_type));
                 }
@@ -1127,7 +1765,8 @@ impl TypeChecker {
                 let body_type = self.check(body, env)?;
 
                 if let Some(ret_type) = return_type {
-                    self.unify(&body_type, ret_type)?;
+                    let ret_type = self.monotype_annotation(ret_type, env)?;
+                    self.expect_type(&ret_type, span, "declared return type", &body_type, body)?;
                 }
 
                 let mut func_type = self.substitute(&body_type);
@@ -1148,14 +1787,20 @@ impl TypeChecker {
                 cond,
                 then_expr,
                 else_expr,
-                ..
+                span,
             } => {
                 let cond_type = self.check(cond, env)?;
-                self.unify(&cond_type, &Type::Bool)?;
+                self.expect_type(&Type::Bool, span, "if condition", &cond_type, cond)?;
 
                 let then_type = self.check(then_expr, env)?;
                 let else_type = self.check(else_expr, env)?;
-                self.unify(&then_type, &else_type)?;
+                self.expect_type(
+                    &then_type,
+                    then_expr.span(),
+                    "then branch",
+                    &else_type,
+                    else_expr,
+                )?;
 
                 Ok(self.substitute(&then_type))
             }
@@ -1166,13 +1811,7 @@ impl TypeChecker {
                 let mut current_type = func_type;
                 for arg in args {
                     let arg_type = self.check(arg, env)?;
-                    let result_type = self.fresh_var();
-
-                    let expected_func_type =
-                        Type::Function(Box::new(arg_type.clone()), Box::new(result_type.clone()));
-
-                    self.unify(&current_type, &expected_func_type)?;
-                    current_type = self.substitute(&result_type);
+                    current_type = self.apply_argument(&current_type, func, &arg_type, arg)?;
                 }
 
                 Ok(current_type)
@@ -1187,16 +1826,24 @@ impl TypeChecker {
                     self.check_pattern(pattern, &expr_type, env)?;
                     let case_type = self.check(case_expr, env)?;
 
-                    if let Some(ref expected_type) = result_type {
-                        self.unify(expected_type, &case_type)?;
+                    if let Some((ref expected_type, first_span)) = result_type {
+                        self.expect_type(
+                            expected_type,
+                            first_span,
+                            "first match arm",
+                            &case_type,
+                            case_expr,
+                        )?;
                     } else {
-                        result_type = Some(case_type);
+                        result_type = Some((case_type, case_expr.span()));
                     }
 
                     env.pop_scope();
                 }
 
-                result_type.ok_or_else(|| "Empty match expression".to_string())
+                result_type
+                    .map(|(typ, _)| typ)
+                    .ok_or_else(|| "Empty match expression".to_string())
             }
 
             Expr::Constructor { name, args, .. } => {
@@ -1228,38 +1875,10 @@ impl TypeChecker {
             }
 
             Expr::TypeDef { definition, .. } => {
+                annotations::check_alias(definition, env)?;
                 env.add_type_definition(definition.name.clone(), definition.clone());
 
-                // Add constructors to environment
-                for constructor in &definition.constructors {
-                    let mut cons_type = Type::UserDefined {
-                        name: definition.name.clone(),
-                        type_params: definition
-                            .type_params
-                            .iter()
-                            .map(|p| Type::Var(p.clone()))
-                            .collect(),
-                    };
-
-                    // Build constructor function type
-                    for field_type in constructor.fields.iter().rev() {
-                        cons_type =
-                            Type::Function(Box::new(field_type.clone()), Box::new(cons_type));
-                    }
-
-                    let scheme = if definition.type_params.is_empty() {
-                        TypeScheme::mono(cons_type)
-                    } else {
-                        TypeScheme {
-                            vars: definition.type_params.clone(),
-                            typ: cons_type,
-                            effects: None,
-                            effect_vars: vec![],
-                        }
-                    };
-
-                    env.add_binding(constructor.name.clone(), scheme);
-                }
+                env.add_constructors(definition);
 
                 Ok(Type::Int) // Type definitions don't have a runtime value
             }
@@ -1339,12 +1958,7 @@ impl TypeChecker {
                 let expr_type = self.check(expr, env)?;
                 let func_type = self.check(func, env)?;
 
-                let result_type = self.fresh_var();
-                let expected_func_type =
-                    Type::Function(Box::new(expr_type), Box::new(result_type.clone()));
-
-                self.unify(&func_type, &expected_func_type)?;
-                Ok(self.substitute(&result_type))
+                self.apply_argument(&func_type, func, &expr_type, expr)
             }
 
             Expr::Block { exprs, .. } => {
@@ -1353,9 +1967,12 @@ impl TypeChecker {
                 } else {
                     env.push_scope();
                     let mut last_type = Type::Unit;
+                    let mut scope = MutableScope::default();
 
-                    for expr in exprs {
+                    for (i, expr) in exprs.iter().enumerate() {
                         last_type = self.check(expr, env)?;
+                        let is_last = i + 1 == exprs.len();
+                        self.check_mutable_escape(expr, &last_type, is_last, &mut scope, env)?;
                     }
 
                     env.pop_scope();
@@ -1363,6 +1980,27 @@ impl TypeChecker {
                 }
             }
 
+            Expr::Mut { name, value, .. } => {
+                let value_type = self.check(value, env)?;
+                env.add_mutable_binding(name.0.clone(), value_type);
+                Ok(Type::Unit)
+            }
+
+            Expr::Assign { name, value, span } => {
+                if !env.is_mutable(&name.0) {
+                    self.error_span.get_or_insert_with(|| span.clone());
+                    return Err(format!(
+                        "Cannot assign to `{}`: it is not declared with `mut`",
+                        name.0
+                    ));
+                }
+                let var_type = self.instantiate(env.lookup(&name.0).unwrap());
+                let value_type = self.check(value, env)?;
+                let reason = format!("type of `{}`", name.0);
+                self.expect_type(&var_type, span, &reason, &value_type, value)?;
+                Ok(Type::Unit)
+            }
+
             Expr::Hole {
                 name,
                 type_hint,
@@ -1448,6 +2086,7 @@ impl TypeChecker {
 
                 // Normal record field access
                 let record_type = self.check(record, env)?;
+                let record_type = self.substitute(&record_type);
 
                 match &record_type {
                     Type::Record { fields } => {
@@ -1459,10 +2098,14 @@ impl TypeChecker {
                         }
                         Err(format!("Field '{}' not found in record", field.0))
                     }
-                    Type::Var(_) => {
-                        // If it's a type variable, we need to constrain it to be a record
-                        // For now, return a fresh type variable
-                        Ok(self.fresh_var())
+                    Type::Var(_) | Type::ExtensibleRecord { .. } => {
+                        // Any record with the field will do: { field: a | r }
+                        let field_type = self.fresh_var();
+                        let row = self.fresh_var_name();
+                        let expected =
+                            Type::open_record(vec![(field.0.clone(), field_type.clone())], row);
+                        self.unify(&record_type, &expected)?;
+                        Ok(field_type)
                     }
                     _ => Err(format!(
                         "Cannot access field '{}' on non-record type",
@@ -1475,6 +2118,7 @@ impl TypeChecker {
                 record, updates, ..
             } => {
                 let record_type = self.check(record, env)?;
+                let record_type = self.substitute(&record_type);
 
                 match record_type {
                     Type::Record { mut fields } => {
@@ -1501,14 +2145,72 @@ impl TypeChecker {
 
                         Ok(Type::Record { fields })
                     }
-                    Type::Var(_) => {
-                        // If it's a type variable, return it as is for now
-                        Ok(record_type)
+                    Type::Var(_) | Type::ExtensibleRecord { .. } => {
+                        // { a: t1, b: t2 | r } becomes { a: u1, b: u2 | r }
+                        let row = self.fresh_var_name();
+                        let mut before = Vec::new();
+                        let mut after = Vec::new();
+                        for (update_name, update_expr) in updates {
+                            before.push((update_name.0.clone(), self.fresh_var()));
+                            after.push((update_name.0.clone(), self.check(update_expr, env)?));
+                        }
+                        self.unify(&record_type, &Type::open_record(before, row.clone()))?;
+                        Ok(self.substitute(&Type::open_record(after, row)))
                     }
                     _ => Err("Cannot update fields on non-record type".to_string()),
                 }
             }
 
+            Expr::RecordExtend { record, fields, .. } => {
+                let record_type = self.check(record, env)?;
+                let mut added = Vec::new();
+                for (name, expr) in fields {
+                    added.push((name.0.clone(), self.check(expr, env)?));
+                }
+
+                // Records have at most one field of each name
+                let existing = match self.substitute(&record_type) {
+                    Type::Record { fields } | Type::ExtensibleRecord { fields, .. } => fields,
+                    Type::Var(_) => Vec::new(),
+                    _ => return Err("Cannot extend non-record type".to_string()),
+                };
+                for (index, (name, _)) in added.iter().enumerate() {
+                    if existing.iter().chain(&added[..index]).any(|(field, _)| field == name) {
+                        return Err(format!("Field '{name}' already exists in record"));
+                    }
+                }
+
+                Ok(Type::extend_record(added, self.substitute(&record_type))
+                    .expect("record type was checked above"))
+            }
+
+            Expr::RecordRestrict { record, field, .. } => {
+                let record_type = self.check(record, env)?;
+                match self.substitute(&record_type) {
+                    Type::Record { mut fields } => {
+                        let Some(index) = fields.iter().position(|(name, _)| name == &field.0)
+                        else {
+                            return Err(format!("Field '{}' not found in record", field.0));
+                        };
+                        fields.remove(index);
+                        Ok(Type::Record { fields })
+                    }
+                    record_type @ (Type::Var(_) | Type::ExtensibleRecord { .. }) => {
+                        // { field: a | r } becomes r
+                        let row = self.fresh_var_name();
+                        let field_type = self.fresh_var();
+                        let expected =
+                            Type::open_record(vec![(field.0.clone(), field_type)], row.clone());
+                        self.unify(&record_type, &expected)?;
+                        Ok(self.substitute(&Type::Var(row)))
+                    }
+                    _ => Err(format!(
+                        "Cannot remove field '{}' from non-record type",
+                        field.0
+                    )),
+                }
+            }
+
             Expr::LetRecIn {
                 name,
                 type_ann,
@@ -1517,10 +2219,9 @@ impl TypeChecker {
                 ..
             } => {
                 // Similar to LetRec but with a body expression
-                let value_type = if let Some(ann) = type_ann {
-                    ann.clone()
-                } else {
-                    self.fresh_var()
+                let (value_type, quantified) = match type_ann {
+                    Some(ann) => self.annotation_type(ann, env)?,
+                    None => (self.fresh_var(), Vec::new()),
                 };
 
                 // Add name to environment for recursive calls
@@ -1530,6 +2231,7 @@ impl TypeChecker {
                 // Type check the value
                 let inferred_type = self.check(value, env)?;
                 self.unify(&value_type, &inferred_type)?;
+                self.check_quantified(&quantified, env, Some(&name.0))?;
 
                 // Update binding with generalized type
                 let gen_scheme = self.generalize(&inferred_type, env);
@@ -1569,6 +2271,26 @@ impl TypeChecker {
         pattern: &Pattern,
         expected_type: &Type,
         env: &mut TypeEnv,
+    ) -> Result<(), String> {
+        self.check_pattern_inner(pattern, expected_type, env)
+            .map_err(|message| {
+                let span = match pattern {
+                    Pattern::Wildcard(span)
+                    | Pattern::Literal(_, span)
+                    | Pattern::Variable(_, span)
+                    | Pattern::Constructor { span, .. }
+                    | Pattern::List { span, .. } => span,
+                };
+                self.error_span.get_or_insert_with(|| span.clone());
+                message
+            })
+    }
+
+    fn check_pattern_inner(
+        &mut self,
+        pattern: &Pattern,
+        expected_type: &Type,
+        env: &mut TypeEnv,
     ) -> Result<(), String> {
         match pattern {
             Pattern::Wildcard(_) => Ok(()),
@@ -1681,6 +2403,18 @@ pub fn type_check(expr: &Expr) -> Result<Type, XsError> {
     let mut type_checker = TypeChecker::new();
     let mut type_env = TypeEnv::new();
     type_checker
-        .check(expr, &mut type_env)
-        .map_err(|e| XsError::TypeError(expr.span().clone(), e))
+        .check_spanned(expr, &mut type_env)
+        .map_err(|d| d.to_xs_error())
+}
+
+// Type check and collect every error instead of stopping at the first one
+pub fn type_check_all(expr: &Expr) -> Result<Type, Vec<TypeDiagnostic>> {
+    let mut type_checker = TypeChecker::new();
+    let mut type_env = TypeEnv::new();
+    let (typ, diagnostics) = type_checker.check_all(expr, &mut type_env);
+    if diagnostics.is_empty() {
+        Ok(typ)
+    } else {
+        Err(diagnostics)
+    }
 }
@@ -0,0 +1,377 @@
+//! Scope checks for `mut` variables
+//!
+//! A `mut` variable is desugared to a `State` effect handled by the block
+//! that declares it, so it must not be used once that block has returned.
+//! The only way to use it later is from a closure that outlives the block:
+//! the block's result, or a value assigned to a variable declared outside
+//! it. These helpers find such closures.
+
+use std::collections::HashMap;
+use vibe_language::{DoStatement, Expr, Pattern, Type};
+
+/// Whether a value of this type can hold a closure
+pub(crate) fn contains_function(typ: &Type) -> bool {
+    match typ {
+        Type::Function(..) | Type::FunctionWithEffect { .. } => true,
+        Type::List(inner) | Type::Option(inner) => contains_function(inner),
+        Type::Tuple(types)
+        | Type::UserDefined {
+            type_params: types, ..
+        }
+        | Type::Applied { args: types, .. } => types.iter().any(contains_function),
+        Type::Forall { body, .. } => contains_function(body),
+        Type::Record { fields } | Type::ExtensibleRecord { fields, .. } => {
+            fields.iter().any(|(_, typ)| contains_function(typ))
+        }
+        Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit | Type::Var(_) => false,
+    }
+}
+
+/// What the first of `names` used free inside a closure in `expr` maps to
+pub(crate) fn captured<'a>(expr: &Expr, names: &'a HashMap<String, String>) -> Option<&'a str> {
+    let mut finder = Finder {
+        names,
+        bound: Vec::new(),
+        closures: 0,
+        only_captured: true,
+    };
+    finder.find(expr)
+}
+
+/// What the first of `names` used free anywhere in `expr` maps to
+pub(crate) fn mentioned<'a>(expr: &Expr, names: &'a HashMap<String, String>) -> Option<&'a str> {
+    let mut finder = Finder {
+        names,
+        bound: Vec::new(),
+        closures: 0,
+        only_captured: false,
+    };
+    finder.find(expr)
+}
+
+struct Finder<'a> {
+    names: &'a HashMap<String, String>,
+    /// Names shadowed at the current point
+    bound: Vec<String>,
+    /// How many closures the current point is inside
+    closures: usize,
+    only_captured: bool,
+}
+
+impl<'a> Finder<'a> {
+    fn name(&self, name: &str) -> Option<&'a str> {
+        if self.only_captured && self.closures == 0 {
+            return None;
+        }
+        if self.bound.iter().any(|bound| bound == name) {
+            return None;
+        }
+        self.names.get(name).map(String::as_str)
+    }
+
+    /// Look into `expr` with `names` shadowed
+    fn scoped<'e>(
+        &mut self,
+        names: impl IntoIterator<Item = &'e str>,
+        closure: bool,
+        expr: &Expr,
+    ) -> Option<&'a str> {
+        let depth = self.bound.len();
+        self.bound.extend(names.into_iter().map(str::to_string));
+        self.closures += closure as usize;
+        let found = self.find(expr);
+        self.closures -= closure as usize;
+        self.bound.truncate(depth);
+        found
+    }
+
+    fn all<'e>(&mut self, exprs: impl IntoIterator<Item = &'e Expr>) -> Option<&'a str> {
+        exprs.into_iter().find_map(|expr| self.find(expr))
+    }
+
+    fn find(&mut self, expr: &Expr) -> Option<&'a str> {
+        match expr {
+            Expr::Ident(ident, _) => self.name(&ident.0),
+            Expr::List(items, _) => self.all(items),
+            Expr::Let { value, .. } | Expr::LetRec { value, .. } => self.find(value),
+            Expr::LetIn {
+                name, value, body, ..
+            } => self
+                .find(value)
+                .or_else(|| self.scoped([name.0.as_str()], false, body)),
+            Expr::LetRecIn {
+                name, value, body, ..
+            } => self
+                .scoped([name.0.as_str()], false, value)
+                .or_else(|| self.scoped([name.0.as_str()], false, body)),
+            Expr::Rec {
+                name, params, body, ..
+            } => {
+                let names = params.iter().map(|(param, _)| param.0.as_str());
+                self.scoped(names.chain([name.0.as_str()]), true, body)
+            }
+            Expr::Lambda { params, body, .. } => {
+                self.scoped(params.iter().map(|(param, _)| param.0.as_str()), true, body)
+            }
+            Expr::FunctionDef {
+                name, params, body, ..
+            } => {
+                let names = params.iter().map(|param| param.name.0.as_str());
+                self.scoped(names.chain([name.0.as_str()]), true, body)
+            }
+            Expr::If {
+                cond,
+                then_expr,
+                else_expr,
+                ..
+            } => self.all([cond.as_ref(), then_expr, else_expr]),
+            Expr::Apply { func, args, .. } => self.find(func).or_else(|| self.all(args)),
+            Expr::Match { expr, cases, .. } => self.find(expr).or_else(|| {
+                cases.iter().find_map(|(pattern, case)| {
+                    let mut names = Vec::new();
+                    pattern_names(pattern, &mut names);
+                    self.scoped(names, false, case)
+                })
+            }),
+            Expr::Constructor { args, .. } | Expr::Perform { args, .. } => self.all(args),
+            Expr::Module { body, .. } => self.all(body),
+            Expr::Handler { cases, body, .. } => self.find(body).or_else(|| {
+                cases.iter().find_map(|(_, _, continuation, case)| {
+                    self.scoped([continuation.0.as_str()], false, case)
+                })
+            }),
+            Expr::HandleExpr {
+                expr,
+                handlers,
+                return_handler,
+                ..
+            } => self
+                .find(expr)
+                .or_else(|| {
+                    handlers.iter().find_map(|handler| {
+                        let mut names = vec![handler.continuation.0.as_str()];
+                        for arg in &handler.args {
+                            pattern_names(arg, &mut names);
+                        }
+                        self.scoped(names, false, &handler.body)
+                    })
+                })
+                .or_else(|| {
+                    return_handler
+                        .as_ref()
+                        .and_then(|(name, body)| self.scoped([name.0.as_str()], false, body))
+                }),
+            Expr::WithHandler { handler, body, .. } => {
+                self.find(handler).or_else(|| self.find(body))
+            }
+            Expr::Pipeline { expr, func, .. } => self.find(expr).or_else(|| self.find(func)),
+            Expr::Block { exprs, .. } => {
+                let depth = self.bound.len();
+                let mut found = None;
+                for expr in exprs {
+                    found = self.find(expr);
+                    if found.is_some() {
+                        break;
+                    }
+                    if let Expr::Let { name, .. }
+                    | Expr::LetRec { name, .. }
+                    | Expr::Mut { name, .. } = expr
+                    {
+                        self.bound.push(name.0.clone());
+                    }
+                }
+                self.bound.truncate(depth);
+                found
+            }
+            Expr::Mut { value, .. } => self.find(value),
+            Expr::Assign { name, value, .. } => self.name(&name.0).or_else(|| self.find(value)),
+            Expr::Do { statements, .. } => {
+                let depth = self.bound.len();
+                let mut found = None;
+                for statement in statements {
+                    found = match statement {
+                        DoStatement::Bind { name, expr, .. } => {
+                            let found = self.find(expr);
+                            self.bound.push(name.0.clone());
+                            found
+                        }
+                        DoStatement::Expression(expr) => self.find(expr),
+                    };
+                    if found.is_some() {
+                        break;
+                    }
+                }
+                self.bound.truncate(depth);
+                found
+            }
+            Expr::RecordLiteral { fields, .. } => self.all(fields.iter().map(|(_, value)| value)),
+            Expr::RecordAccess { record, .. } | Expr::RecordRestrict { record, .. } => {
+                self.find(record)
+            }
+            Expr::RecordUpdate {
+                record,
+                updates: fields,
+                ..
+            }
+            | Expr::RecordExtend { record, fields, .. } => self
+                .find(record)
+                .or_else(|| self.all(fields.iter().map(|(_, value)| value))),
+            Expr::Literal(..)
+            | Expr::TypeDef { .. }
+            | Expr::Import { .. }
+            | Expr::Use { .. }
+            | Expr::QualifiedIdent { .. }
+            | Expr::Hole { .. }
+            | Expr::HashRef { .. } => None,
+        }
+    }
+}
+
+fn pattern_names<'e>(pattern: &'e Pattern, names: &mut Vec<&'e str>) {
+    match pattern {
+        Pattern::Variable(name, _) => names.push(&name.0),
+        Pattern::Constructor { patterns, .. } | Pattern::List { patterns, .. } => {
+            for pattern in patterns {
+                pattern_names(pattern, names);
+            }
+        }
+        Pattern::Wildcard(_) | Pattern::Literal(..) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::type_check;
+    use vibe_language::{Expr, Ident, Literal, Span, Type};
+
+    fn span() -> Span {
+        Span::new(0, 0)
+    }
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(Ident(name.to_string()), span())
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Int(n), span())
+    }
+
+    fn add(left: Expr, right: Expr) -> Expr {
+        Expr::Apply {
+            func: Box::new(ident("+")),
+            args: vec![left, right],
+            span: span(),
+        }
+    }
+
+    fn lambda(param: &str, body: Expr) -> Expr {
+        Expr::Lambda {
+            params: vec![(Ident(param.to_string()), None)],
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn mutable(name: &str, value: Expr) -> Expr {
+        Expr::Mut {
+            name: Ident(name.to_string()),
+            value: Box::new(value),
+            span: span(),
+        }
+    }
+
+    fn assign(name: &str, value: Expr) -> Expr {
+        Expr::Assign {
+            name: Ident(name.to_string()),
+            value: Box::new(value),
+            span: span(),
+        }
+    }
+
+    fn let_(name: &str, value: Expr) -> Expr {
+        Expr::Let {
+            name: Ident(name.to_string()),
+            type_ann: None,
+            value: Box::new(value),
+            span: span(),
+        }
+    }
+
+    fn block(exprs: Vec<Expr>) -> Expr {
+        Expr::Block {
+            exprs,
+            span: span(),
+        }
+    }
+
+    #[test]
+    fn test_mut_variable_is_assigned_its_own_type() {
+        // { mut s = 0; s = s + 1; s }
+        let counter = block(vec![
+            mutable("s", int(0)),
+            assign("s", add(ident("s"), int(1))),
+            ident("s"),
+        ]);
+        assert_eq!(type_check(&counter).unwrap(), Type::Int);
+
+        // { mut s = 0; s = true }
+        let retyped = block(vec![
+            mutable("s", int(0)),
+            assign("s", Expr::Literal(Literal::Bool(true), span())),
+        ]);
+        assert!(type_check(&retyped).is_err());
+
+        // { let s = 0; s = 1 }
+        let immutable = block(vec![let_("s", int(0)), assign("s", int(1))]);
+        let error = type_check(&immutable).unwrap_err().to_string();
+        assert!(error.contains("not declared with `mut`"), "{error}");
+    }
+
+    #[test]
+    fn test_closure_over_mut_variable_cannot_escape() {
+        let escapes = |expr: &Expr| {
+            let error = type_check(expr).unwrap_err().to_string();
+            assert!(error.contains("`s` escapes its scope"), "{error}");
+        };
+
+        // { mut s = 0; fn x -> s + x }
+        escapes(&block(vec![
+            mutable("s", int(0)),
+            lambda("x", add(ident("s"), ident("x"))),
+        ]));
+
+        // { mut s = 0; let f = fn x -> s + x; f }
+        escapes(&block(vec![
+            mutable("s", int(0)),
+            let_("f", lambda("x", add(ident("s"), ident("x")))),
+            ident("f"),
+        ]));
+
+        // { mut f = fn x -> x; { mut s = 0; f = fn x -> s }; f 1 }
+        escapes(&block(vec![
+            mutable("f", lambda("x", ident("x"))),
+            block(vec![
+                mutable("s", int(0)),
+                assign("f", lambda("x", ident("s"))),
+            ]),
+            Expr::Apply {
+                func: Box::new(ident("f")),
+                args: vec![int(1)],
+                span: span(),
+            },
+        ]));
+
+        // Calling the closure inside the block is fine:
+        // { mut s = 1; let f = fn x -> s + x; f 2 }
+        let called = block(vec![
+            mutable("s", int(1)),
+            let_("f", lambda("x", add(ident("s"), ident("x")))),
+            Expr::Apply {
+                func: Box::new(ident("f")),
+                args: vec![int(2)],
+                span: span(),
+            },
+        ]);
+        assert_eq!(type_check(&called).unwrap(), Type::Int);
+    }
+}
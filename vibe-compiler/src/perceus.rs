@@ -176,7 +176,11 @@ impl PerceusTransform {
             }
 
             // Effect handlers not yet implemented
-            Expr::Handler { .. } | Expr::WithHandler { .. } | Expr::Perform { .. } => {
+            Expr::Handler { .. }
+            | Expr::WithHandler { .. }
+            | Expr::Perform { .. }
+            | Expr::Mut { .. }
+            | Expr::Assign { .. } => {
                 // TODO: Implement effect handler transformation
                 IrExpr::Literal(Literal::Int(0))
             }
@@ -244,7 +248,7 @@ impl PerceusTransform {
                 IrExpr::Literal(Literal::Int(0))
             }
 
-            Expr::RecordUpdate { .. } => {
+            Expr::RecordUpdate { .. } | Expr::RecordExtend { .. } | Expr::RecordRestrict { .. } => {
                 // TODO: Implement record update transformation
                 IrExpr::Literal(Literal::Int(0))
             }
@@ -0,0 +1,250 @@
+//! Row-polymorphic records
+//!
+//! A record type is either closed, `{name: String, age: Int}`, or open,
+//! `{name: String | r}`, where the row variable `r` stands for any other
+//! fields. Accessing, updating or removing a field only requires the record
+//! to have that field, so functions over records are generic in the rest.
+
+use vibe_language::Type;
+
+/// The fields of `fields` that `other` does not have
+pub(crate) fn missing_fields(
+    fields: &[(String, Type)],
+    other: &[(String, Type)],
+) -> Vec<(String, Type)> {
+    fields
+        .iter()
+        .filter(|(name, _)| !other.iter().any(|(field, _)| field == name))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::type_check;
+    use vibe_language::{Expr, Ident, Literal, Span, Type};
+
+    fn span() -> Span {
+        Span::new(0, 0)
+    }
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(Ident(name.to_string()), span())
+    }
+
+    fn string(s: &str) -> Expr {
+        Expr::Literal(Literal::String(s.to_string()), span())
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Int(n), span())
+    }
+
+    fn fields(fields: Vec<(&str, Expr)>) -> Vec<(Ident, Expr)> {
+        fields
+            .into_iter()
+            .map(|(name, value)| (Ident(name.to_string()), value))
+            .collect()
+    }
+
+    fn record(values: Vec<(&str, Expr)>) -> Expr {
+        Expr::RecordLiteral {
+            fields: fields(values),
+            span: span(),
+        }
+    }
+
+    fn access(record: Expr, field: &str) -> Expr {
+        Expr::RecordAccess {
+            record: Box::new(record),
+            field: Ident(field.to_string()),
+            span: span(),
+        }
+    }
+
+    fn lambda(param: &str, body: Expr) -> Expr {
+        Expr::Lambda {
+            params: vec![(Ident(param.to_string()), None)],
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn apply(func: Expr, arg: Expr) -> Expr {
+        Expr::Apply {
+            func: Box::new(func),
+            args: vec![arg],
+            span: span(),
+        }
+    }
+
+    fn let_in(name: &str, value: Expr, body: Expr) -> Expr {
+        Expr::LetIn {
+            name: Ident(name.to_string()),
+            type_ann: None,
+            value: Box::new(value),
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn type_string(expr: &Expr) -> String {
+        type_check(expr).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_field_access_is_row_polymorphic() {
+        // fn r -> r.name
+        let name = lambda("r", access(ident("r"), "name"));
+        let Type::Function(param, result) = type_check(&name).unwrap() else {
+            panic!("expected a function type");
+        };
+        let Type::ExtensibleRecord { fields, .. } = *param else {
+            panic!("expected an open record, got {param}");
+        };
+        assert_eq!(fields, vec![("name".to_string(), *result)]);
+
+        // let name = fn r -> r.name in (name { name: "Ada", age: 36 }, name { name: 1 })
+        let both = let_in(
+            "name",
+            name,
+            Expr::List(
+                vec![
+                    apply(
+                        ident("name"),
+                        record(vec![("name", string("Ada")), ("age", int(36))]),
+                    ),
+                    apply(ident("name"), record(vec![("name", string("Grace"))])),
+                ],
+                span(),
+            ),
+        );
+        assert_eq!(type_string(&both), "(List String)");
+
+        // name { age: 36 }
+        let missing = let_in(
+            "name",
+            lambda("r", access(ident("r"), "name")),
+            apply(ident("name"), record(vec![("age", int(36))])),
+        );
+        let error = type_check(&missing).unwrap_err().to_string();
+        assert!(error.contains("field 'name' is missing"), "{error}");
+    }
+
+    #[test]
+    fn test_functions_over_the_same_row_combine() {
+        // fn r -> r.first ++ r.last
+        let full_name = lambda(
+            "r",
+            Expr::Apply {
+                func: Box::new(ident("++")),
+                args: vec![access(ident("r"), "first"), access(ident("r"), "last")],
+                span: span(),
+            },
+        );
+        let typ = type_string(&full_name);
+        assert!(
+            typ.starts_with("{first: String, last: String | ") && typ.ends_with("} -> String"),
+            "{typ}"
+        );
+    }
+
+    #[test]
+    fn test_record_extension_and_restriction() {
+        let person = record(vec![("name", string("Ada"))]);
+
+        // { age: 36 | { name: "Ada" } }
+        let extended = Expr::RecordExtend {
+            record: Box::new(person.clone()),
+            fields: fields(vec![("age", int(36))]),
+            span: span(),
+        };
+        assert_eq!(type_string(&extended), "{age: Int, name: String}");
+
+        // { { age: 36 | { name: "Ada" } } - name }
+        let restricted = Expr::RecordRestrict {
+            record: Box::new(extended),
+            field: Ident("name".to_string()),
+            span: span(),
+        };
+        assert_eq!(type_string(&restricted), "{age: Int}");
+
+        // { name: "Grace" | { name: "Ada" } }
+        let duplicate = Expr::RecordExtend {
+            record: Box::new(person.clone()),
+            fields: fields(vec![("name", string("Grace"))]),
+            span: span(),
+        };
+        let error = type_check(&duplicate).unwrap_err().to_string();
+        assert!(error.contains("'name' already exists"), "{error}");
+
+        // fn r -> { id: 1 | { r - name } }
+        let rekey = lambda(
+            "r",
+            Expr::RecordExtend {
+                record: Box::new(Expr::RecordRestrict {
+                    record: Box::new(ident("r")),
+                    field: Ident("name".to_string()),
+                    span: span(),
+                }),
+                fields: fields(vec![("id", int(1))]),
+                span: span(),
+            },
+        );
+        let Type::Function(param, result) = type_check(&rekey).unwrap() else {
+            panic!("expected a function type");
+        };
+        match (*param, *result) {
+            (
+                Type::ExtensibleRecord { fields, row },
+                Type::ExtensibleRecord {
+                    fields: result_fields,
+                    row: result_row,
+                },
+            ) => {
+                assert_eq!(fields[0].0, "name");
+                assert_eq!(result_fields, vec![("id".to_string(), Type::Int)]);
+                assert_eq!(row, result_row);
+            }
+            (param, result) => panic!("expected open records, got {param} -> {result}"),
+        }
+
+        // (fn r -> { id: 1 | { r - name } }) { name: "Ada", age: 36 }
+        assert_eq!(
+            type_string(&apply(
+                rekey,
+                record(vec![("name", string("Ada")), ("age", int(36))])
+            )),
+            "{age: Int, id: Int}"
+        );
+    }
+
+    #[test]
+    fn test_open_record_annotation() {
+        let open = Type::ExtensibleRecord {
+            fields: vec![("name".to_string(), Type::String)],
+            row: "rest".to_string(),
+        };
+        let annotated = |typ: Type, field: &str| Expr::Lambda {
+            params: vec![(Ident("r".to_string()), Some(typ))],
+            body: Box::new(access(ident("r"), field)),
+            span: span(),
+        };
+
+        // fn (r: { name: String | rest }) -> r.name
+        let typ = type_string(&annotated(open, "name"));
+        assert!(
+            typ.starts_with("{name: String | ") && typ.ends_with("} -> String"),
+            "{typ}"
+        );
+
+        // fn (r: { name: String }) -> r.age
+        let closed = Type::Record {
+            fields: vec![("name".to_string(), Type::String)],
+        };
+        let error = type_check(&annotated(closed, "age"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("age"), "{error}");
+    }
+}
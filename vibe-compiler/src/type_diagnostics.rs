@@ -0,0 +1,191 @@
+//! Span-carrying type errors
+//!
+//! The type checker reports each error against the innermost expression that
+//! caused it, and for mismatches records where both the expected and the found
+//! type came from.
+
+use crate::improved_errors::TypeErrorHelper;
+use std::fmt;
+use vibe_language::error_context::{
+    ErrorBuilder, ErrorCategory, ErrorContext, RelatedInfo, Severity,
+};
+use vibe_language::{Span, Type, XsError};
+
+/// Where a type in a mismatch came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub typ: Type,
+    pub span: Span,
+    /// Why this type was required, e.g. "if condition" or "parameter of `f`"
+    pub reason: String,
+}
+
+/// A type error located in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDiagnostic {
+    pub span: Span,
+    pub message: String,
+    pub expected: Option<Box<Provenance>>,
+    pub found: Option<Box<Provenance>>,
+}
+
+impl TypeDiagnostic {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            expected: None,
+            found: None,
+        }
+    }
+
+    /// Human readable notes explaining the expected and found types
+    pub fn notes(&self) -> Vec<(Span, String)> {
+        let mut notes = Vec::new();
+        if let Some(expected) = &self.expected {
+            notes.push((
+                expected.span.clone(),
+                format!("expected {} because of {}", expected.typ, expected.reason),
+            ));
+        }
+        if let Some(found) = &self.found {
+            notes.push((
+                found.span.clone(),
+                format!("found {} from {}", found.typ, found.reason),
+            ));
+        }
+        notes
+    }
+
+    pub fn to_xs_error(&self) -> XsError {
+        XsError::TypeError(self.span.clone(), self.to_string())
+    }
+
+    /// Rich error context with a source snippet, conversion suggestions and
+    /// the provenance of both sides as related information
+    pub fn to_error_context(&self, source: &str) -> ErrorContext {
+        let builder = match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => {
+                TypeErrorHelper::new().type_mismatch_builder(&expected.typ, &found.typ)
+            }
+            _ => ErrorBuilder::new(ErrorCategory::Type, self.message.clone()),
+        };
+
+        let mut context = builder.with_snippet(source, self.span.clone()).build();
+        context
+            .related
+            .extend(self.notes().into_iter().map(|(span, message)| RelatedInfo {
+                message,
+                location: Some(span),
+                severity: Severity::Info,
+            }));
+        context
+    }
+}
+
+impl fmt::Display for TypeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for (_, note) in self.notes() {
+            write!(f, "\n  note: {note}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{type_check, type_check_all, TypeChecker, TypeEnv, TypeScheme};
+    use vibe_language::parser::parse;
+    use vibe_language::parser::recovery::{shift_spans, split_top_level};
+    use vibe_language::{Expr, Span, Type, XsError};
+
+    /// The span of the `nth` occurrence of `text` in `source`
+    fn span_of(source: &str, text: &str, nth: usize) -> Span {
+        let (start, _) = source.match_indices(text).nth(nth).unwrap();
+        Span::new(start, start + text.len())
+    }
+
+    /// Parse each top-level statement of `source` into one block
+    fn parse_statements(source: &str) -> Expr {
+        let exprs = split_top_level(source)
+            .into_iter()
+            .map(|span| {
+                let mut expr = parse(&source[span.start..span.end]).unwrap();
+                shift_spans(&mut expr, span.start);
+                expr
+            })
+            .collect();
+        Expr::Block {
+            exprs,
+            span: Span::new(0, source.len()),
+        }
+    }
+
+    #[test]
+    fn test_error_points_at_argument() {
+        // f : Int -> Int
+        let source = r#"f "hello""#;
+        let mut env = TypeEnv::new();
+        env.add_binding(
+            "f".to_string(),
+            TypeScheme::mono(Type::Function(Box::new(Type::Int), Box::new(Type::Int))),
+        );
+
+        let error = TypeChecker::new()
+            .check_spanned(&parse(source).unwrap(), &mut env)
+            .unwrap_err();
+        assert_eq!(error.span, span_of(source, r#""hello""#, 0));
+        assert!(error.message.contains("Cannot unify"));
+        assert_eq!(error.expected.as_ref().unwrap().reason, "parameter of `f`");
+        assert_eq!(error.found.as_ref().unwrap().typ, Type::String);
+        assert!(error.to_string().contains("parameter of `f`"));
+    }
+
+    #[test]
+    fn test_type_check_reports_inner_span() {
+        let source = "if 1 { 2 } else { 3 }";
+        match type_check(&parse(source).unwrap()) {
+            Err(XsError::TypeError(span, msg)) => {
+                assert_eq!(span, span_of(source, "1", 0));
+                assert!(msg.contains("if condition"));
+            }
+            other => panic!("Expected type error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_collects_multiple_errors() {
+        let source = "if 1 { 2 } else { 3 }\nlet xs = [1, true]\nif false { 1 } else { true }";
+        let errors = type_check_all(&parse_statements(source)).unwrap_err();
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert_eq!(errors[0].span, span_of(source, "1", 0));
+        assert_eq!(errors[0].expected.as_ref().unwrap().reason, "if condition");
+        assert_eq!(errors[1].span, span_of(source, "true", 0));
+        assert_eq!(
+            errors[1].expected.as_ref().unwrap().span,
+            span_of(source, "1", 1)
+        );
+        assert_eq!(errors[2].span, span_of(source, "true", 1));
+    }
+
+    #[test]
+    fn test_failed_definition_is_still_bound() {
+        let source = "let x : Int = \"no\"\nlet y : Int = x";
+        let errors = type_check_all(&parse_statements(source)).unwrap_err();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].span, span_of(source, "\"no\"", 0));
+    }
+
+    #[test]
+    fn test_error_context_has_provenance() {
+        let source = "if true { 1 } else { \"no\" }";
+        let errors = type_check_all(&parse(source).unwrap()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+
+        let context = errors[0].to_error_context(source);
+        assert_eq!(context.related.len(), 2);
+        assert!(context.related[0].message.contains("then branch"));
+        assert!(context.snippet.is_some());
+    }
+}
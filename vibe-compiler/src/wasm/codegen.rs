@@ -16,10 +16,14 @@ pub struct CodeGenerator {
     next_local: u32,
     /// Generated functions
     functions: Vec<WasmFunction>,
+    /// Top-level functions callable by name: index and arity
+    function_indices: HashMap<String, (u32, usize)>,
+    /// Whether constructs without a real lowering (closures, lists,
+    /// strings) may be replaced by placeholder values
+    allow_placeholders: bool,
     // TODO: The following fields will be used when implementing WebAssembly GC features:
     // - type_allocator: TypeIndexAllocator - for managing GC type indices
     // - std_types: StandardTypes - standard GC types (structs, arrays)
-    // - function_indices: HashMap<String, u32> - for function table management
 }
 
 impl Default for CodeGenerator {
@@ -35,9 +39,51 @@ impl CodeGenerator {
             locals: HashMap::new(),
             next_local: 0,
             functions: Vec::new(),
+            function_indices: HashMap::new(),
+            allow_placeholders: true,
         }
     }
 
+    /// Declare a top-level function so that references to `name` become
+    /// direct calls to function `index`
+    pub fn declare_function(&mut self, name: &str, index: u32, arity: usize) {
+        self.function_indices
+            .insert(name.to_string(), (index, arity));
+        self.allow_placeholders = false;
+    }
+
+    /// Generate a top-level function from the IR of its definition.
+    ///
+    /// A lambda becomes a function taking its parameters as `i64`s; any
+    /// other value becomes a function without parameters.
+    pub fn generate_function(
+        &mut self,
+        name: &str,
+        ir: &IrExpr,
+    ) -> Result<WasmFunction, CodeGenError> {
+        let (params, body) = match ir {
+            IrExpr::Lambda { params, body } => (params.as_slice(), body.as_ref()),
+            _ => (&[][..], ir),
+        };
+        self.start_function(name, vec![WasmType::I64; params.len()], vec![WasmType::I64]);
+        for param in params {
+            self.locals.insert(param.clone(), self.next_local);
+            self.next_local += 1;
+        }
+        self.generate_expr(body)?;
+        self.finish_function()
+    }
+
+    /// Push a placeholder for a construct that has no lowering yet, or fail
+    /// when generating real functions
+    fn placeholder(&mut self, instr: WasmInstr, what: &str) -> Result<(), CodeGenError> {
+        if !self.allow_placeholders {
+            return Err(CodeGenError::UnsupportedExpr(what.to_string()));
+        }
+        self.emit(instr);
+        Ok(())
+    }
+
     /// Generate WebAssembly module from IR
     pub fn generate(&mut self, ir: &IrExpr) -> Result<WasmModule, CodeGenError> {
         // Start with main function
@@ -94,14 +140,14 @@ impl CodeGenerator {
                 Ok(())
             }
             Literal::Bool(b) => {
-                self.emit(WasmInstr::I32Const(if *b { 1 } else { 0 }));
+                // Booleans share the i64 representation of comparison results
+                self.emit(WasmInstr::I64Const(if *b { 1 } else { 0 }));
                 Ok(())
             }
             Literal::String(_s) => {
                 // TODO: Implement string literal generation
                 // For now, just push null
-                self.emit(WasmInstr::RefNull(WasmType::ArrayRef(0)));
-                Ok(())
+                self.placeholder(WasmInstr::RefNull(WasmType::ArrayRef(0)), "string literals")
             }
             Literal::Float(f) => {
                 self.emit(WasmInstr::F64Const(f.0));
@@ -115,6 +161,14 @@ impl CodeGenerator {
         if let Some(&idx) = self.locals.get(name) {
             self.emit(WasmInstr::LocalGet(idx));
             Ok(())
+        } else if let Some(&(idx, arity)) = self.function_indices.get(name) {
+            if arity > 0 {
+                return Err(CodeGenError::UnsupportedExpr(format!(
+                    "`{name}` used as a function value"
+                )));
+            }
+            self.emit(WasmInstr::Call(idx));
+            Ok(())
         } else {
             Err(CodeGenError::UndefinedVariable(name.to_string()))
         }
@@ -147,14 +201,41 @@ impl CodeGenerator {
     fn generate_lambda(&mut self, _params: &[String], _body: &IrExpr) -> Result<(), CodeGenError> {
         // TODO: Implement closure creation
         // For now, just push null
-        self.emit(WasmInstr::RefNull(WasmType::StructRef(0)));
-        Ok(())
+        self.placeholder(WasmInstr::RefNull(WasmType::StructRef(0)), "closures")
     }
 
     /// Generate function application
     fn generate_apply(&mut self, func: &IrExpr, args: &[IrExpr]) -> Result<(), CodeGenError> {
-        // Check if this is a builtin function
+        // `f a b` parses as `(f a) b`; apply `f` to all its arguments at once
+        if let IrExpr::Apply {
+            func: inner,
+            args: first,
+        } = func
+        {
+            let all: Vec<IrExpr> = first.iter().chain(args).cloned().collect();
+            return self.generate_apply(inner, &all);
+        }
+
         if let IrExpr::Var(name) = func {
+            // Direct call to a top-level function
+            if let (false, Some(&(idx, arity))) = (
+                self.locals.contains_key(name),
+                self.function_indices.get(name),
+            ) {
+                if arity != args.len() {
+                    return Err(CodeGenError::InvalidCall(format!(
+                        "`{name}` takes {arity} arguments but is applied to {}",
+                        args.len()
+                    )));
+                }
+                for arg in args {
+                    self.generate_expr(arg)?;
+                }
+                self.emit(WasmInstr::Call(idx));
+                return Ok(());
+            }
+
+            // Check if this is a builtin function
             if let Some(()) = self.try_generate_builtin(name, args)? {
                 return Ok(());
             }
@@ -170,6 +251,11 @@ impl CodeGenerator {
 
         // TODO: Implement proper function call
         // For now, just drop all values and push 0
+        if !self.allow_placeholders {
+            return Err(CodeGenError::UnsupportedExpr(
+                "calls to function values".to_string(),
+            ));
+        }
         for _ in 0..=args.len() {
             self.emit(WasmInstr::Drop);
         }
@@ -185,8 +271,10 @@ impl CodeGenerator {
         then_expr: &IrExpr,
         else_expr: &IrExpr,
     ) -> Result<(), CodeGenError> {
-        // Generate condition
+        // Generate condition, turning the i64 truth value into an i32
         self.generate_expr(cond)?;
+        self.emit(WasmInstr::I64Const(0));
+        self.emit(WasmInstr::I64Ne);
 
         // Generate if instruction
         let mut then_instrs = vec![];
@@ -217,7 +305,7 @@ impl CodeGenerator {
         if let Some(ref mut func) = self.current_function {
             func.body = saved_instrs.unwrap_or_default();
             func.body.push(WasmInstr::If {
-                result_type: None, // TODO: Determine result type
+                result_type: Some(WasmType::I64),
                 then_instrs,
                 else_instrs,
             });
@@ -230,8 +318,7 @@ impl CodeGenerator {
     fn generate_list(&mut self, _exprs: &[IrExpr]) -> Result<(), CodeGenError> {
         // TODO: Implement list creation
         // For now, just push null
-        self.emit(WasmInstr::RefNull(WasmType::ArrayRef(0)));
-        Ok(())
+        self.placeholder(WasmInstr::RefNull(WasmType::ArrayRef(0)), "lists")
     }
 
     /// Generate drop instruction
@@ -305,6 +392,8 @@ impl CodeGenerator {
                 | "<"
                 | ">"
                 | "="
+                | "=="
+                | "!="
                 | "<="
                 | ">="
                 | "cons"
@@ -338,11 +427,16 @@ impl CodeGenerator {
                 // Convert i32 to i64 for consistency
                 self.emit(WasmInstr::I64ExtendI32S);
             }
-            "=" => {
+            "=" | "==" => {
                 self.emit(WasmInstr::I64Eq);
                 // Convert i32 to i64 for consistency
                 self.emit(WasmInstr::I64ExtendI32S);
             }
+            "!=" => {
+                self.emit(WasmInstr::I64Ne);
+                // Convert i32 to i64 for consistency
+                self.emit(WasmInstr::I64ExtendI32S);
+            }
             "<=" => {
                 self.emit(WasmInstr::I64LeS);
                 // Convert i32 to i64 for consistency
@@ -356,6 +450,9 @@ impl CodeGenerator {
             "cons" | "concat" | "print" => {
                 // TODO: Implement list/string/IO operations
                 // For now, just drop arguments and push dummy value
+                if !self.allow_placeholders {
+                    return Err(CodeGenError::UnsupportedExpr(format!("builtin `{name}`")));
+                }
                 for _ in 0..args.len() {
                     self.emit(WasmInstr::Drop);
                 }
@@ -405,4 +502,61 @@ mod tests {
         // Should have: i64.const 10, local.set 0, local.get 0
         assert!(func.body.len() >= 3);
     }
+
+    /// Lower `expr` with `generate` and validate the resulting module
+    fn validate(expr: &vibe_language::Expr) -> Result<(), String> {
+        let ir = crate::PerceusTransform::new().transform(expr);
+        let module = CodeGenerator::new()
+            .generate(&ir)
+            .map_err(|e| e.to_string())?;
+        let wat_text = super::super::emit::emit_wat(&module).map_err(|e| e.to_string())?;
+        let bytes = wat::parse_str(&wat_text).map_err(|e| e.to_string())?;
+        wasmparser::Validator::new()
+            .validate_all(&bytes)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_conditionals_validate() {
+        use vibe_language::{Expr, Ident, Span};
+
+        let lit = |lit: Literal| Expr::Literal(lit, Span::new(0, 0));
+        let var = |name: &str| Expr::Ident(Ident(name.to_string()), Span::new(0, 0));
+        let call = |op: &str, args: Vec<Expr>| Expr::Apply {
+            func: Box::new(var(op)),
+            args,
+            span: Span::new(0, 0),
+        };
+        let if_ = |cond: Expr, then_expr: Expr, else_expr: Expr| Expr::If {
+            cond: Box::new(cond),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+            span: Span::new(0, 0),
+        };
+        let int = |n: i64| lit(Literal::Int(n));
+
+        // Booleans and comparison results are both i64 truth values
+        let cases = [
+            // if true { 1 } else { 0 }
+            if_(lit(Literal::Bool(true)), int(1), int(0)),
+            // if 1 < 2 { 10 } else { 20 }
+            if_(call("<", vec![int(1), int(2)]), int(10), int(20)),
+            // let x = 3 in if x != 3 { x } else { if false { 1 } else { 2 } }
+            Expr::LetIn {
+                name: Ident("x".to_string()),
+                type_ann: None,
+                value: Box::new(int(3)),
+                body: Box::new(if_(
+                    call("!=", vec![var("x"), int(3)]),
+                    var("x"),
+                    if_(lit(Literal::Bool(false)), int(1), int(2)),
+                )),
+                span: Span::new(0, 0),
+            },
+        ];
+        for expr in &cases {
+            assert_eq!(validate(expr), Ok(()), "{expr:?}");
+        }
+    }
 }
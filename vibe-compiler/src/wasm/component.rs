@@ -4,6 +4,8 @@
 //! from XS modules, enabling type-safe composition and distribution.
 
 use super::{CodeGenError, WasmModule};
+use std::collections::HashMap;
+use vibe_language::{Type, TypeDefinition as XsTypeDefinition};
 
 /// Component metadata
 #[derive(Debug)]
@@ -56,6 +58,10 @@ pub enum TypeDefinition {
         name: String,
         cases: Vec<(String, Option<WitType>)>,
     },
+    Enum {
+        name: String,
+        cases: Vec<String>,
+    },
     Alias {
         name: String,
         target: WitType,
@@ -79,6 +85,7 @@ pub enum WitType {
     String,
     List(Box<WitType>),
     Option(Box<WitType>),
+    Tuple(Vec<WitType>),
     Result {
         ok: Option<Box<WitType>>,
         err: Option<Box<WitType>>,
@@ -132,102 +139,462 @@ impl ComponentBuilder {
     }
 }
 
-/// Convert XS type to WIT type
-pub fn xs_type_to_wit(xs_type: &vibe_language::Type) -> WitType {
-    use vibe_language::Type;
-
-    match xs_type {
-        Type::Int => WitType::S64,
-        Type::Float => WitType::Float64,
-        Type::Bool => WitType::Bool,
-        Type::String => WitType::String,
-        Type::Unit => WitType::String, // Unit represented as empty string in WIT
-        Type::List(inner) => WitType::List(Box::new(xs_type_to_wit(inner))),
-        Type::Function(_, _) | Type::FunctionWithEffect { .. } => {
-            // Functions are handled separately in interfaces
-            WitType::String // Placeholder
+/// Convert a structural XS type to WIT
+///
+/// No type definitions are available here, so ADTs other than `Option` and
+/// `Result` are rejected; use [`WitTypeMapper`] when the module defines types.
+pub fn xs_type_to_wit(xs_type: &Type) -> Result<WitType, CodeGenError> {
+    let definitions = HashMap::new();
+    WitTypeMapper::new(&definitions).map(xs_type)
+}
+
+/// Maps XS types to WIT, collecting the named WIT types they refer to
+///
+/// Records become `record`s named after their fields, ADTs become `variant`s
+/// or `enum`s (or `option`/`result` when they have that shape), and generic
+/// ADTs get one WIT type per distinct instantiation.
+pub struct WitTypeMapper<'a> {
+    definitions: &'a HashMap<String, XsTypeDefinition>,
+    types: Vec<TypeDefinition>,
+    named: Vec<(Type, String)>,
+    in_progress: Vec<Type>,
+}
+
+impl<'a> WitTypeMapper<'a> {
+    pub fn new(definitions: &'a HashMap<String, XsTypeDefinition>) -> Self {
+        Self {
+            definitions,
+            types: Vec::new(),
+            named: Vec::new(),
+            in_progress: Vec::new(),
         }
-        Type::Var(_) => WitType::String, // Type variables need special handling
-        Type::UserDefined { .. } => WitType::String, // ADTs need special handling
-        Type::Record { .. } => WitType::String, // Records need special handling
-        Type::Option(inner) => {
-            // Option type maps to WIT option
-            WitType::Option(Box::new(xs_type_to_wit(inner)))
+    }
+
+    /// Map a parameter, field or payload type
+    pub fn map(&mut self, ty: &Type) -> Result<WitType, CodeGenError> {
+        match ty {
+            Type::Int => Ok(WitType::S64),
+            Type::Float => Ok(WitType::Float64),
+            Type::Bool => Ok(WitType::Bool),
+            Type::String => Ok(WitType::String),
+            Type::Unit => Ok(WitType::Tuple(Vec::new())),
+            Type::List(inner) => Ok(WitType::List(Box::new(self.map(inner)?))),
+            Type::Option(inner) => Ok(WitType::Option(Box::new(self.map(inner)?))),
+            Type::Tuple(items) => Ok(WitType::Tuple(
+                items
+                    .iter()
+                    .map(|item| self.map(item))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Type::Record { fields } => self.map_record(ty, fields),
+            Type::UserDefined { name, type_params } => {
+                self.map_user_defined(ty, name, type_params)
+            }
+            Type::Var(name)
+            | Type::Applied {
+                constructor: name, ..
+            } => Err(CodeGenError::TypeError(format!(
+                "type variable `{name}` must be instantiated before it can cross the component boundary"
+            ))),
+            Type::ExtensibleRecord { row, .. } => Err(CodeGenError::TypeError(format!(
+                "record type `{ty}` must have its row `{row}` closed before it can cross the component boundary"
+            ))),
+            Type::Function(..) | Type::FunctionWithEffect { .. } => {
+                Err(CodeGenError::TypeError(format!(
+                    "function type `{ty}` cannot cross the component boundary"
+                )))
+            }
+            Type::Forall { .. } => Err(CodeGenError::TypeError(format!(
+                "polymorphic type `{ty}` must be monomorphised before it can cross the component boundary"
+            ))),
         }
-        Type::Tuple(_types) => {
-            // Tuples need special handling in WIT
-            // For now, represent as string
-            WitType::String
+    }
+
+    /// Map a function result; `Unit` means the function has no result
+    pub fn map_result(&mut self, ty: &Type) -> Result<Option<WitType>, CodeGenError> {
+        if is_unit(ty) {
+            Ok(None)
+        } else {
+            self.map(ty).map(Some)
         }
     }
-}
 
-/// Generate WIT interface from XS module exports
-pub fn generate_wit_interface(
-    _module_name: &str,
-    exports: &[(String, vibe_language::Type)],
-) -> InterfaceDefinition {
-    let mut functions = Vec::new();
+    /// The named types referenced so far, dependencies first
+    pub fn into_types(self) -> Vec<TypeDefinition> {
+        self.types
+    }
 
-    for (name, typ) in exports {
-        if let Some(sig) = extract_function_signature(typ) {
-            functions.push(FunctionSignature {
-                name: name.clone(),
-                params: sig.0,
-                results: sig.1,
-            });
+    fn map_record(
+        &mut self,
+        ty: &Type,
+        fields: &[(String, Type)],
+    ) -> Result<WitType, CodeGenError> {
+        if fields.is_empty() {
+            return Ok(WitType::Tuple(Vec::new()));
         }
+        if let Some(name) = self.lookup(ty) {
+            return Ok(WitType::Named(name));
+        }
+
+        let mut mapped = Vec::new();
+        for (field, field_type) in fields {
+            mapped.push((to_wit_identifier(field), self.map(field_type)?));
+        }
+        let field_names: Vec<&str> = mapped.iter().map(|(name, _)| name.as_str()).collect();
+        let name = self.fresh_name(format!("record-{}", field_names.join("-")));
+
+        self.named.push((ty.clone(), name.clone()));
+        self.types.push(TypeDefinition::Record {
+            name: name.clone(),
+            fields: mapped,
+        });
+        Ok(WitType::Named(name))
     }
 
-    InterfaceDefinition {
-        functions,
-        types: Vec::new(), // TODO: Extract type definitions
+    fn map_user_defined(
+        &mut self,
+        ty: &Type,
+        name: &str,
+        args: &[Type],
+    ) -> Result<WitType, CodeGenError> {
+        if is_unit(ty) {
+            return Ok(WitType::Tuple(Vec::new()));
+        }
+        if let Some(existing) = self.lookup(ty) {
+            return Ok(WitType::Named(existing));
+        }
+
+        let Some(definition) = self.definitions.get(name) else {
+            return match (name, args) {
+                ("Option", [inner]) => Ok(WitType::Option(Box::new(self.map(inner)?))),
+                ("Result", [ok, err]) => Ok(WitType::Result {
+                    ok: self.map_result(ok)?.map(Box::new),
+                    err: self.map_result(err)?.map(Box::new),
+                }),
+                _ => Err(CodeGenError::TypeError(format!("unknown type `{name}`"))),
+            };
+        };
+
+        if definition.type_params.len() != args.len() {
+            return Err(CodeGenError::TypeError(format!(
+                "type `{name}` expects {} type argument(s) but got {}",
+                definition.type_params.len(),
+                args.len()
+            )));
+        }
+        if self.in_progress.contains(ty) {
+            return Err(CodeGenError::TypeError(format!(
+                "recursive type `{ty}` cannot be represented in WIT"
+            )));
+        }
+
+        let subst: HashMap<String, Type> = definition
+            .type_params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        if let Some(target) = &definition.alias {
+            return self.map_alias(ty, name, &target.apply_subst(&subst));
+        }
+        let cases: Vec<(String, Vec<Type>)> = definition
+            .constructors
+            .iter()
+            .map(|constructor| {
+                let fields = constructor
+                    .fields
+                    .iter()
+                    .map(|field| field.apply_subst(&subst))
+                    .collect();
+                (constructor.name.clone(), fields)
+            })
+            .collect();
+
+        self.in_progress.push(ty.clone());
+        let result = self.map_cases(ty, name, args, &cases);
+        self.in_progress.pop();
+        result
+    }
+
+    /// A type alias keeps its name in the interface when it has no
+    /// parameters; a generic one is expanded where it is used
+    fn map_alias(&mut self, ty: &Type, name: &str, target: &Type) -> Result<WitType, CodeGenError> {
+        self.in_progress.push(ty.clone());
+        let target = self.map(target);
+        self.in_progress.pop();
+        let target = target?;
+        let Type::UserDefined { type_params, .. } = ty else {
+            return Ok(target);
+        };
+        if !type_params.is_empty() {
+            return Ok(target);
+        }
+
+        let type_name = self.fresh_name(to_wit_identifier(name));
+        self.named.push((ty.clone(), type_name.clone()));
+        self.types.push(TypeDefinition::Alias {
+            name: type_name.clone(),
+            target,
+        });
+        Ok(WitType::Named(type_name))
+    }
+
+    fn map_cases(
+        &mut self,
+        ty: &Type,
+        name: &str,
+        args: &[Type],
+        cases: &[(String, Vec<Type>)],
+    ) -> Result<WitType, CodeGenError> {
+        match cases {
+            [(none, none_fields), (some, some_fields)]
+            | [(some, some_fields), (none, none_fields)]
+                if none == "None"
+                    && some == "Some"
+                    && none_fields.is_empty()
+                    && some_fields.len() == 1 =>
+            {
+                return Ok(WitType::Option(Box::new(self.map(&some_fields[0])?)));
+            }
+            [(ok, ok_fields), (err, err_fields)] | [(err, err_fields), (ok, ok_fields)]
+                if ok == "Ok" && err == "Err" =>
+            {
+                return Ok(WitType::Result {
+                    ok: self.map_payload(ok_fields)?.map(Box::new),
+                    err: self.map_payload(err_fields)?.map(Box::new),
+                });
+            }
+            _ => {}
+        }
+
+        let mut type_name = to_wit_identifier(name);
+        for arg in args {
+            let arg = self.map(arg)?;
+            type_name.push('-');
+            type_name.push_str(&type_suffix(&arg));
+        }
+        let type_name = self.fresh_name(type_name);
+
+        let definition = if cases.iter().all(|(_, fields)| fields.is_empty()) {
+            TypeDefinition::Enum {
+                name: type_name.clone(),
+                cases: cases
+                    .iter()
+                    .map(|(case, _)| to_wit_identifier(case))
+                    .collect(),
+            }
+        } else {
+            let mut mapped = Vec::new();
+            for (case, fields) in cases {
+                mapped.push((to_wit_identifier(case), self.map_payload(fields)?));
+            }
+            TypeDefinition::Variant {
+                name: type_name.clone(),
+                cases: mapped,
+            }
+        };
+
+        self.named.push((ty.clone(), type_name.clone()));
+        self.types.push(definition);
+        Ok(WitType::Named(type_name))
+    }
+
+    /// Constructor fields become no payload, a single payload or a tuple
+    fn map_payload(&mut self, fields: &[Type]) -> Result<Option<WitType>, CodeGenError> {
+        match fields {
+            [] => Ok(None),
+            [field] => self.map_result(field),
+            _ => Ok(Some(WitType::Tuple(
+                fields
+                    .iter()
+                    .map(|field| self.map(field))
+                    .collect::<Result<_, _>>()?,
+            ))),
+        }
+    }
+
+    fn lookup(&self, ty: &Type) -> Option<String> {
+        self.named
+            .iter()
+            .find(|(named, _)| named == ty)
+            .map(|(_, name)| name.clone())
+    }
+
+    fn fresh_name(&self, base: String) -> String {
+        let taken = |name: &str| self.named.iter().any(|(_, existing)| existing == name);
+        if !taken(&base) {
+            return base;
+        }
+        (2..)
+            .map(|n| format!("{base}-{n}"))
+            .find(|name| !taken(name))
+            .expect("unbounded range")
     }
 }
 
-/// Function parameter and return type information
-type FunctionSignatureTuple = (Vec<(String, WitType)>, Vec<WitType>);
+fn is_unit(ty: &Type) -> bool {
+    match ty {
+        Type::Unit => true,
+        Type::UserDefined { name, type_params } => name == "Unit" && type_params.is_empty(),
+        _ => false,
+    }
+}
 
-/// Extract function signature from XS type, handling curried functions
-fn extract_function_signature(typ: &vibe_language::Type) -> Option<FunctionSignatureTuple> {
-    use vibe_language::Type;
+/// A kebab-case rendering of a WIT type for use in generated names
+pub(crate) fn type_suffix(ty: &WitType) -> String {
+    let rendered = wit_type_to_string(ty);
+    rendered
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
 
-    let mut params = Vec::new();
-    let mut current_type = typ;
-    let mut param_count = 0;
+const WIT_KEYWORDS: &[&str] = &[
+    "as",
+    "bool",
+    "borrow",
+    "char",
+    "constructor",
+    "enum",
+    "export",
+    "f32",
+    "f64",
+    "flags",
+    "from",
+    "func",
+    "future",
+    "import",
+    "include",
+    "interface",
+    "list",
+    "option",
+    "own",
+    "package",
+    "record",
+    "resource",
+    "result",
+    "s16",
+    "s32",
+    "s64",
+    "s8",
+    "static",
+    "stream",
+    "string",
+    "tuple",
+    "type",
+    "u16",
+    "u32",
+    "u64",
+    "u8",
+    "use",
+    "variant",
+    "with",
+    "world",
+];
+
+/// Convert an XS identifier to a kebab-case WIT identifier
+///
+/// `snake_case` and `camelCase` words are split on `_` and case changes, and
+/// names that collide with WIT keywords are escaped with `%`.
+pub fn to_wit_identifier(name: &str) -> String {
+    let mut ident = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c == '_' || c == '-' {
+            if !ident.is_empty() && !ident.ends_with('-') {
+                ident.push('-');
+            }
+            previous_lower = false;
+        } else if c.is_ascii_uppercase() {
+            if previous_lower && !ident.ends_with('-') {
+                ident.push('-');
+            }
+            ident.push(c.to_ascii_lowercase());
+            previous_lower = false;
+        } else {
+            ident.push(c);
+            previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        }
+    }
+    let ident = ident.trim_end_matches('-').to_string();
+    if WIT_KEYWORDS.contains(&ident.as_str()) {
+        format!("%{ident}")
+    } else {
+        ident
+    }
+}
 
-    // Unwrap curried functions
+/// Split a curried function type into its parameters and final result
+pub(crate) fn split_function_type(typ: &Type) -> Option<(Vec<Type>, Type)> {
+    let mut params = Vec::new();
+    let mut current = typ;
     loop {
-        match current_type {
-            Type::Function(param_type, result_type)
+        match current {
+            Type::Function(param, result)
             | Type::FunctionWithEffect {
-                from: param_type,
-                to: result_type,
+                from: param,
+                to: result,
                 ..
             } => {
-                // Check if the result is another function (curried)
-                match result_type.as_ref() {
-                    Type::Function(..) | Type::FunctionWithEffect { .. } => {
-                        // This is a curried function, add parameter and continue
-                        param_count += 1;
-                        params.push((format!("arg{param_count}"), xs_type_to_wit(param_type)));
-                        current_type = result_type;
-                    }
-                    _ => {
-                        // This is the final result
-                        param_count += 1;
-                        params.push((format!("arg{param_count}"), xs_type_to_wit(param_type)));
-                        let results = vec![xs_type_to_wit(result_type)];
-                        return Some((params, results));
-                    }
+                params.push((**param).clone());
+                match result.as_ref() {
+                    Type::Function(..) | Type::FunctionWithEffect { .. } => current = result,
+                    _ => return Some((params, (**result).clone())),
                 }
             }
-            _ => {
-                // Not a function
-                return None;
-            }
+            _ => return (!params.is_empty()).then(|| (params, current.clone())),
+        }
+    }
+}
+
+/// Build the WIT signature of an exported function
+///
+/// Returns `None` when `typ` is not a function. `Unit` parameters are dropped
+/// and a `Unit` result becomes a function without results.
+pub fn function_signature(
+    mapper: &mut WitTypeMapper<'_>,
+    name: &str,
+    typ: &Type,
+) -> Result<Option<FunctionSignature>, CodeGenError> {
+    let Some((param_types, result_type)) = split_function_type(typ) else {
+        return Ok(None);
+    };
+
+    let mut params = Vec::new();
+    for (i, param_type) in param_types.iter().enumerate() {
+        if !is_unit(param_type) {
+            params.push((format!("arg{}", i + 1), mapper.map(param_type)?));
+        }
+    }
+    let results = mapper.map_result(&result_type)?.into_iter().collect();
+
+    Ok(Some(FunctionSignature {
+        name: to_wit_identifier(name),
+        params,
+        results,
+    }))
+}
+
+/// Generate WIT interface from XS module exports
+pub fn generate_wit_interface(
+    _module_name: &str,
+    exports: &[(String, Type)],
+) -> Result<InterfaceDefinition, CodeGenError> {
+    let definitions = HashMap::new();
+    let mut mapper = WitTypeMapper::new(&definitions);
+    let mut functions = Vec::new();
+
+    for (name, typ) in exports {
+        if let Some(sig) = function_signature(&mut mapper, name, typ)? {
+            functions.push(sig);
         }
     }
+
+    Ok(InterfaceDefinition {
+        functions,
+        types: mapper.into_types(),
+    })
 }
 
 /// Generate WIT file content from interface definition
@@ -243,7 +610,19 @@ pub fn generate_wit_file(
 
     // Interface declaration
     wit.push_str("interface exports {\n");
+    write_interface_items(&mut wit, interface);
+    wit.push_str("}\n\n");
+
+    // World declaration
+    wit.push_str(&format!("world {package_name} {{\n"));
+    wit.push_str("  export exports;\n");
+    wit.push_str("}\n");
+
+    wit
+}
 
+/// Write the type definitions and function signatures of an interface body
+pub(crate) fn write_interface_items(wit: &mut String, interface: &InterfaceDefinition) {
     // Type definitions
     for type_def in &interface.types {
         match type_def {
@@ -273,6 +652,13 @@ pub fn generate_wit_file(
                 }
                 wit.push_str("  }\n\n");
             }
+            TypeDefinition::Enum { name, cases } => {
+                wit.push_str(&format!("  enum {name} {{\n"));
+                for case_name in cases {
+                    wit.push_str(&format!("    {case_name},\n"));
+                }
+                wit.push_str("  }\n\n");
+            }
             TypeDefinition::Alias { name, target } => {
                 wit.push_str(&format!(
                     "  type {} = {};\n\n",
@@ -310,19 +696,10 @@ pub fn generate_wit_file(
 
         wit.push_str(";\n");
     }
-
-    wit.push_str("}\n\n");
-
-    // World declaration
-    wit.push_str(&format!("world {package_name} {{\n"));
-    wit.push_str("  export exports;\n");
-    wit.push_str("}\n");
-
-    wit
 }
 
 /// Convert WIT type to string representation
-fn wit_type_to_string(typ: &WitType) -> String {
+pub(crate) fn wit_type_to_string(typ: &WitType) -> String {
     match typ {
         WitType::Bool => "bool".to_string(),
         WitType::S8 => "s8".to_string(),
@@ -333,22 +710,26 @@ fn wit_type_to_string(typ: &WitType) -> String {
         WitType::U32 => "u32".to_string(),
         WitType::S64 => "s64".to_string(),
         WitType::U64 => "u64".to_string(),
-        WitType::Float32 => "float32".to_string(),
-        WitType::Float64 => "float64".to_string(),
+        WitType::Float32 => "f32".to_string(),
+        WitType::Float64 => "f64".to_string(),
         WitType::String => "string".to_string(),
         WitType::List(inner) => format!("list<{}>", wit_type_to_string(inner)),
         WitType::Option(inner) => format!("option<{}>", wit_type_to_string(inner)),
-        WitType::Result { ok, err } => {
-            let ok_str = ok
-                .as_ref()
-                .map(|t| wit_type_to_string(t))
-                .unwrap_or_else(|| "_".to_string());
-            let err_str = err
-                .as_ref()
-                .map(|t| wit_type_to_string(t))
-                .unwrap_or_else(|| "_".to_string());
-            format!("result<{ok_str}, {err_str}>")
+        WitType::Tuple(items) => {
+            let items: Vec<String> = items.iter().map(wit_type_to_string).collect();
+            format!("tuple<{}>", items.join(", "))
         }
+        WitType::Result { ok, err } => match (ok, err) {
+            (None, None) => "result".to_string(),
+            (Some(ok), None) => format!("result<{}>", wit_type_to_string(ok)),
+            (ok, Some(err)) => {
+                let ok_str = ok
+                    .as_ref()
+                    .map(|t| wit_type_to_string(t))
+                    .unwrap_or_else(|| "_".to_string());
+                format!("result<{ok_str}, {}>", wit_type_to_string(err))
+            }
+        },
         WitType::Named(name) => name.clone(),
     }
 }
@@ -372,6 +753,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_open_record_cannot_cross_boundary() {
+        let closed = Type::Record {
+            fields: vec![("name".to_string(), Type::String)],
+        };
+        assert!(xs_type_to_wit(&closed).is_ok());
+
+        let open = Type::ExtensibleRecord {
+            fields: vec![("name".to_string(), Type::String)],
+            row: "r".to_string(),
+        };
+        let error = xs_type_to_wit(&open).unwrap_err().to_string();
+        assert!(error.contains("row `r`"), "{error}");
+    }
+
     #[test]
     fn test_wit_generation() {
         let interface = InterfaceDefinition {
@@ -2,15 +2,28 @@
 //!
 //! This module provides the actual implementation for building
 //! WebAssembly components from XS modules.
+//!
+//! When a WIT world is supplied, every exported function gets a core wasm
+//! adapter that follows the canonical ABI: arguments that do not fit in 16
+//! flat values are lifted from linear memory, and results that do not fit in
+//! a single flat value are lowered into a return area. The adapter calls the
+//! module function of the same name, which takes and returns the flattened
+//! values directly.
 
-use super::component::ComponentMetadata;
+use super::component::{to_wit_identifier, ComponentMetadata};
 use super::emit::emit_wat;
-use super::{codegen::CodeGenerator, CodeGenError, WasmModule};
+use super::{codegen::CodeGenerator, CodeGenError, WasmModule, WasmType};
 use crate::PerceusTransform;
 use std::collections::HashMap;
+use std::fmt::Write;
 use vibe_language::Expr;
 use wasm_encoder::{Component, ComponentTypeSection};
-use wit_component::ComponentEncoder;
+use wit_component::{ComponentEncoder, StringEncoding};
+use wit_parser::abi::{AbiVariant, WasmType as AbiType};
+use wit_parser::{
+    Function, Int, Mangling, Resolve, SizeAlign, Type as WitTy, TypeDefKind, WasmExport, WorldId,
+    WorldItem, WorldKey,
+};
 
 /// Full component builder implementation
 pub struct ComponentBuilderImpl {
@@ -74,37 +87,57 @@ impl ComponentBuilderImpl {
         // Emit the module to WAT then encode to WASM
         let wat_text = emit_wat(main_module)
             .map_err(|e| CodeGenError::TypeError(format!("WAT emission failed: {e}")))?;
-        let wasm_bytes = wat::parse_str(&wat_text)
-            .map_err(|e| CodeGenError::TypeError(format!("WAT parsing failed: {e}")))?;
 
         // Build component using wit-component
         if let Some(wit_source) = &self.wit_source {
-            self.build_with_wit(&wasm_bytes, wit_source)
+            self.build_with_wit(main_module, &wat_text, wit_source)
         } else {
             // Build basic component without WIT
+            let wasm_bytes = parse_wat(&wat_text)?;
             self.build_basic_component(&wasm_bytes)
         }
     }
 
     /// Build component with WIT interface
+    ///
+    /// The WIT world is embedded into the core module together with the
+    /// canonical ABI glue for its exports, so the encoded component exports
+    /// every function with its WIT type.
     fn build_with_wit(
         &self,
-        module_bytes: &[u8],
-        _wit_source: &str,
+        module: &WasmModule,
+        wat_text: &str,
+        wit_source: &str,
     ) -> Result<Vec<u8>, CodeGenError> {
-        // Note: wit-component expects WIT definitions to be embedded in the wasm module
-        // as custom sections. For now, we'll create a basic component.
-        // In a full implementation, we would need to:
-        // 1. Parse the WIT source
-        // 2. Embed it as custom sections in the module
-        // 3. Or use a different approach with wit-bindgen
+        let mut resolve = Resolve::default();
+        let package = resolve
+            .push_str(format!("{}.wit", self.metadata.name), wit_source)
+            .map_err(|e| CodeGenError::TypeError(format!("Invalid WIT: {e:#}")))?;
+        let world = resolve
+            .select_world(package, None)
+            .map_err(|e| CodeGenError::TypeError(format!("Invalid WIT: {e:#}")))?;
+
+        let glue = generate_export_glue(&resolve, world, module)?;
+        let body = wat_text
+            .trim_end()
+            .strip_suffix(')')
+            .ok_or_else(|| CodeGenError::TypeError("Malformed module text".to_string()))?;
+        let mut module_bytes = parse_wat(&format!("{body}{glue})\n"))?;
+
+        wit_component::embed_component_metadata(
+            &mut module_bytes,
+            &resolve,
+            world,
+            StringEncoding::UTF8,
+        )
+        .map_err(|e| CodeGenError::TypeError(format!("Embedding WIT failed: {e:#}")))?;
 
         let component_bytes = ComponentEncoder::default()
             .validate(true)
-            .module(module_bytes)
-            .map_err(|e| CodeGenError::TypeError(format!("Component encoder error: {e}")))?
+            .module(&module_bytes)
+            .map_err(|e| CodeGenError::TypeError(format!("Component encoder error: {e:#}")))?
             .encode()
-            .map_err(|e| CodeGenError::TypeError(format!("Component encoding failed: {e}")))?;
+            .map_err(|e| CodeGenError::TypeError(format!("Component encoding failed: {e:#}")))?;
 
         Ok(component_bytes)
     }
@@ -158,9 +191,636 @@ pub fn compile_xs_to_component(
     builder.build()
 }
 
+fn parse_wat(wat_text: &str) -> Result<Vec<u8>, CodeGenError> {
+    wat::parse_str(wat_text)
+        .map_err(|e| CodeGenError::TypeError(format!("WAT parsing failed: {e}")))
+}
+
+/// Generate the canonical ABI exports, memory and allocator for `world`
+///
+/// The returned text is a sequence of module fields to append to the core
+/// module. Each exported WIT function must have a module function with the
+/// same (kebab-cased) name whose signature is the flattened WIT signature.
+fn generate_export_glue(
+    resolve: &Resolve,
+    world: WorldId,
+    module: &WasmModule,
+) -> Result<String, CodeGenError> {
+    let mut sizes = SizeAlign::default();
+    sizes.fill(resolve);
+
+    let implementations: HashMap<String, (usize, &super::WasmFunction)> = module
+        .functions
+        .iter()
+        .enumerate()
+        .map(|(idx, func)| (to_wit_identifier(&func.name), (idx, func)))
+        .collect();
+
+    let mut glue = String::new();
+    for (key, item) in &resolve.worlds[world].exports {
+        let functions: Vec<(Option<&WorldKey>, &Function)> = match item {
+            WorldItem::Function(func) => vec![(None, func)],
+            WorldItem::Interface { id, .. } => resolve.interfaces[*id]
+                .functions
+                .values()
+                .map(|func| (Some(key), func))
+                .collect(),
+            WorldItem::Type(_) => continue,
+        };
+
+        for (interface, func) in functions {
+            let (idx, implementation) = implementations.get(&func.name).ok_or_else(|| {
+                CodeGenError::UndefinedVariable(format!(
+                    "exported function `{}` has no implementation in the module",
+                    func.name
+                ))
+            })?;
+            let export_name = resolve.wasm_export_name(
+                Mangling::Standard32,
+                WasmExport::Func {
+                    interface,
+                    func,
+                    post_return: false,
+                },
+            );
+            let mut writer = GlueWriter::new(resolve, &sizes);
+            glue.push_str(&writer.export(&export_name, func, implementation, *idx)?);
+        }
+    }
+
+    if module.memory.is_some() {
+        glue.push_str("  (export \"cm32p2_memory\" (memory 0))\n");
+    } else {
+        glue.push_str("  (memory (export \"cm32p2_memory\") 1)\n");
+    }
+    glue.push_str(CABI_REALLOC);
+    Ok(glue)
+}
+
+/// Bump allocator used by hosts to pass strings and lists, and by the glue
+/// to allocate return areas
+const CABI_REALLOC: &str = r#"  (global $cabi_heap (mut i32) (i32.const 16))
+  (func $cabi_realloc (export "cm32p2_realloc")
+    (param $old i32) (param $old_size i32) (param $align i32) (param $new_size i32)
+    (result i32)
+    (local $ptr i32)
+    global.get $cabi_heap
+    local.get $align
+    i32.add
+    i32.const 1
+    i32.sub
+    i32.const 0
+    local.get $align
+    i32.sub
+    i32.and
+    local.tee $ptr
+    local.get $new_size
+    i32.add
+    global.set $cabi_heap
+    block
+      global.get $cabi_heap
+      memory.size
+      i32.const 65536
+      i32.mul
+      i32.le_u
+      br_if 0
+      global.get $cabi_heap
+      memory.size
+      i32.const 65536
+      i32.mul
+      i32.sub
+      i32.const 65535
+      i32.add
+      i32.const 16
+      i32.shr_u
+      memory.grow
+      i32.const -1
+      i32.eq
+      if
+        unreachable
+      end
+    end
+    local.get $old_size
+    if
+      local.get $ptr
+      local.get $old
+      local.get $old_size
+      memory.copy
+    end
+    local.get $ptr)
+"#;
+
+/// Core wasm value types of flattened WIT values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CoreType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl CoreType {
+    fn from_abi(ty: AbiType) -> Self {
+        match ty {
+            AbiType::I32 | AbiType::Pointer | AbiType::Length => CoreType::I32,
+            AbiType::I64 | AbiType::PointerOrI64 => CoreType::I64,
+            AbiType::F32 => CoreType::F32,
+            AbiType::F64 => CoreType::F64,
+        }
+    }
+
+    fn from_wasm(ty: &WasmType) -> Option<Self> {
+        match ty {
+            WasmType::I32 => Some(CoreType::I32),
+            WasmType::I64 => Some(CoreType::I64),
+            WasmType::F32 => Some(CoreType::F32),
+            WasmType::F64 => Some(CoreType::F64),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CoreType::I32 => "i32",
+            CoreType::I64 => "i64",
+            CoreType::F32 => "f32",
+            CoreType::F64 => "f64",
+        }
+    }
+}
+
+/// Writes the adapter for one exported function
+struct GlueWriter<'a> {
+    resolve: &'a Resolve,
+    sizes: &'a SizeAlign,
+    locals: Vec<CoreType>,
+    body: String,
+    depth: usize,
+}
+
+impl<'a> GlueWriter<'a> {
+    fn new(resolve: &'a Resolve, sizes: &'a SizeAlign) -> Self {
+        Self {
+            resolve,
+            sizes,
+            locals: Vec::new(),
+            body: String::new(),
+            depth: 2,
+        }
+    }
+
+    fn export(
+        &mut self,
+        export_name: &str,
+        func: &Function,
+        implementation: &super::WasmFunction,
+        implementation_idx: usize,
+    ) -> Result<String, CodeGenError> {
+        let signature = self.resolve.wasm_signature(AbiVariant::GuestExport, func);
+
+        let param_types: Vec<&WitTy> = func.params.iter().map(|(_, ty)| ty).collect();
+        let result_types: Vec<&WitTy> = func.results.iter_types().collect();
+        let flat_params: Vec<CoreType> = param_types.iter().flat_map(|ty| self.flat(ty)).collect();
+        let flat_results: Vec<CoreType> =
+            result_types.iter().flat_map(|ty| self.flat(ty)).collect();
+        check_implementation(func, implementation, &flat_params, &flat_results)?;
+
+        // Lift the arguments
+        if signature.indirect_params {
+            let offsets = self.sizes.field_offsets(param_types.iter().copied());
+            let mut values = Vec::new();
+            for (offset, ty) in offsets {
+                values.extend(self.load(ty, "$p0", offset.size_wasm32())?);
+            }
+            for value in values {
+                self.line(format!("local.get $t{value}"));
+            }
+        } else {
+            for i in 0..flat_params.len() {
+                self.line(format!("local.get $p{i}"));
+            }
+        }
+
+        self.line(format!("call $func{implementation_idx}"));
+
+        // Lower the results into a return area
+        if signature.retptr {
+            let values: Vec<usize> = flat_results.iter().map(|ty| self.local(*ty)).collect();
+            for value in values.iter().rev() {
+                self.line(format!("local.set $t{value}"));
+            }
+
+            let area = self.sizes.record(result_types.iter().copied());
+            let retptr = self.local(CoreType::I32);
+            self.line("i32.const 0");
+            self.line("i32.const 0");
+            self.line(format!("i32.const {}", area.align.align_wasm32()));
+            self.line(format!("i32.const {}", area.size.size_wasm32()));
+            self.line("call $cabi_realloc");
+            self.line(format!("local.set $t{retptr}"));
+
+            let base = format!("$t{retptr}");
+            let mut remaining = values.as_slice();
+            for (offset, ty) in self.sizes.field_offsets(result_types.iter().copied()) {
+                let (field, rest) = remaining.split_at(self.flat(ty).len());
+                self.store(ty, field, &base, offset.size_wasm32())?;
+                remaining = rest;
+            }
+            self.line(format!("local.get $t{retptr}"));
+        }
+
+        let mut func_text = format!("  (func (export {export_name:?})");
+        if signature.indirect_params {
+            func_text.push_str(" (param $p0 i32)");
+        } else {
+            for (i, ty) in flat_params.iter().enumerate() {
+                write!(func_text, " (param $p{i} {})", ty.name()).unwrap();
+            }
+        }
+        for ty in &signature.results {
+            write!(func_text, " (result {})", CoreType::from_abi(*ty).name()).unwrap();
+        }
+        func_text.push('\n');
+        for (i, ty) in self.locals.iter().enumerate() {
+            writeln!(func_text, "    (local $t{i} {})", ty.name()).unwrap();
+        }
+        func_text.push_str(&self.body);
+        func_text.push_str("  )\n");
+        Ok(func_text)
+    }
+
+    fn line(&mut self, instr: impl AsRef<str>) {
+        self.body.push_str(&"  ".repeat(self.depth));
+        self.body.push_str(instr.as_ref());
+        self.body.push('\n');
+    }
+
+    fn local(&mut self, ty: CoreType) -> usize {
+        self.locals.push(ty);
+        self.locals.len() - 1
+    }
+
+    fn flat(&self, ty: &WitTy) -> Vec<CoreType> {
+        let mut flat = Vec::new();
+        self.resolve.push_flat(ty, &mut flat);
+        flat.into_iter().map(CoreType::from_abi).collect()
+    }
+
+    /// Lift a value stored at `base + offset` into flat locals
+    fn load(&mut self, ty: &WitTy, base: &str, offset: usize) -> Result<Vec<usize>, CodeGenError> {
+        let scalar = match ty {
+            WitTy::Bool | WitTy::U8 => Some(("i32.load8_u", CoreType::I32)),
+            WitTy::S8 => Some(("i32.load8_s", CoreType::I32)),
+            WitTy::U16 => Some(("i32.load16_u", CoreType::I32)),
+            WitTy::S16 => Some(("i32.load16_s", CoreType::I32)),
+            WitTy::U32 | WitTy::S32 | WitTy::Char => Some(("i32.load", CoreType::I32)),
+            WitTy::U64 | WitTy::S64 => Some(("i64.load", CoreType::I64)),
+            WitTy::F32 => Some(("f32.load", CoreType::F32)),
+            WitTy::F64 => Some(("f64.load", CoreType::F64)),
+            WitTy::String | WitTy::Id(_) => None,
+        };
+        if let Some((instr, core)) = scalar {
+            return Ok(vec![self.load_scalar(instr, core, base, offset)]);
+        }
+
+        let kind = match ty {
+            WitTy::Id(id) => &self.resolve.types[*id].kind,
+            _ => return Ok(self.load_pointer_pair(base, offset)),
+        };
+        match kind {
+            TypeDefKind::Type(inner) => self.load(inner, base, offset),
+            TypeDefKind::List(_) => Ok(self.load_pointer_pair(base, offset)),
+            TypeDefKind::Record(record) => {
+                self.load_fields(record.fields.iter().map(|f| &f.ty), base, offset)
+            }
+            TypeDefKind::Tuple(tuple) => self.load_fields(tuple.types.iter(), base, offset),
+            TypeDefKind::Flags(flags) => Ok(match flags.flags.len() {
+                0 => Vec::new(),
+                1..=8 => vec![self.load_scalar("i32.load8_u", CoreType::I32, base, offset)],
+                9..=16 => vec![self.load_scalar("i32.load16_u", CoreType::I32, base, offset)],
+                n => (0..(n + 31) / 32)
+                    .map(|i| self.load_scalar("i32.load", CoreType::I32, base, offset + 4 * i))
+                    .collect(),
+            }),
+            TypeDefKind::Enum(enum_) => {
+                let instr = load_discriminant(enum_.tag());
+                Ok(vec![self.load_scalar(instr, CoreType::I32, base, offset)])
+            }
+            TypeDefKind::Variant(variant) => {
+                let cases: Vec<Option<&WitTy>> =
+                    variant.cases.iter().map(|case| case.ty.as_ref()).collect();
+                self.load_variant(ty, variant.tag(), &cases, base, offset)
+            }
+            TypeDefKind::Option(inner) => {
+                self.load_variant(ty, Int::U8, &[None, Some(inner)], base, offset)
+            }
+            TypeDefKind::Result(result) => self.load_variant(
+                ty,
+                Int::U8,
+                &[result.ok.as_ref(), result.err.as_ref()],
+                base,
+                offset,
+            ),
+            other => Err(unsupported(other)),
+        }
+    }
+
+    fn load_scalar(&mut self, instr: &str, core: CoreType, base: &str, offset: usize) -> usize {
+        let local = self.local(core);
+        self.line(format!("local.get {base}"));
+        self.line(format!("{instr} offset={offset}"));
+        self.line(format!("local.set $t{local}"));
+        local
+    }
+
+    fn load_pointer_pair(&mut self, base: &str, offset: usize) -> Vec<usize> {
+        vec![
+            self.load_scalar("i32.load", CoreType::I32, base, offset),
+            self.load_scalar("i32.load", CoreType::I32, base, offset + 4),
+        ]
+    }
+
+    fn load_fields<'t>(
+        &mut self,
+        types: impl Iterator<Item = &'t WitTy>,
+        base: &str,
+        offset: usize,
+    ) -> Result<Vec<usize>, CodeGenError> {
+        let mut values = Vec::new();
+        for (field_offset, ty) in self.sizes.field_offsets(types) {
+            values.extend(self.load(ty, base, offset + field_offset.size_wasm32())?);
+        }
+        Ok(values)
+    }
+
+    /// Load the discriminant and, for the active case, its payload widened
+    /// to the joined flat representation of all cases
+    fn load_variant(
+        &mut self,
+        ty: &WitTy,
+        tag: Int,
+        cases: &[Option<&WitTy>],
+        base: &str,
+        offset: usize,
+    ) -> Result<Vec<usize>, CodeGenError> {
+        let joined = self.flat(ty);
+        let discriminant = self.load_scalar(load_discriminant(tag), CoreType::I32, base, offset);
+        let payload: Vec<usize> = joined[1..].iter().map(|ty| self.local(*ty)).collect();
+        let payload_offset = offset
+            + self
+                .sizes
+                .payload_offset(tag, cases.iter().copied())
+                .size_wasm32();
+
+        for (index, case) in cases.iter().enumerate() {
+            let Some(case) = case else { continue };
+            self.line(format!("local.get $t{discriminant}"));
+            self.line(format!("i32.const {index}"));
+            self.line("i32.eq");
+            self.line("if");
+            self.depth += 1;
+            let values = self.load(case, base, payload_offset)?;
+            for (value, target) in values.iter().zip(&payload) {
+                self.line(format!("local.get $t{value}"));
+                self.coerce(self.locals[*value], self.locals[*target])?;
+                self.line(format!("local.set $t{target}"));
+            }
+            self.depth -= 1;
+            self.line("end");
+        }
+
+        let mut values = vec![discriminant];
+        values.extend(payload);
+        Ok(values)
+    }
+
+    /// Lower flat locals into memory at `base + offset`
+    fn store(
+        &mut self,
+        ty: &WitTy,
+        values: &[usize],
+        base: &str,
+        offset: usize,
+    ) -> Result<(), CodeGenError> {
+        let scalar = match ty {
+            WitTy::Bool | WitTy::U8 | WitTy::S8 => Some("i32.store8"),
+            WitTy::U16 | WitTy::S16 => Some("i32.store16"),
+            WitTy::U32 | WitTy::S32 | WitTy::Char => Some("i32.store"),
+            WitTy::U64 | WitTy::S64 => Some("i64.store"),
+            WitTy::F32 => Some("f32.store"),
+            WitTy::F64 => Some("f64.store"),
+            WitTy::String | WitTy::Id(_) => None,
+        };
+        if let Some(instr) = scalar {
+            self.store_scalar(instr, values[0], base, offset);
+            return Ok(());
+        }
+
+        let kind = match ty {
+            WitTy::Id(id) => &self.resolve.types[*id].kind,
+            _ => {
+                self.store_pointer_pair(values, base, offset);
+                return Ok(());
+            }
+        };
+        match kind {
+            TypeDefKind::Type(inner) => self.store(inner, values, base, offset),
+            TypeDefKind::List(_) => {
+                self.store_pointer_pair(values, base, offset);
+                Ok(())
+            }
+            TypeDefKind::Record(record) => {
+                self.store_fields(record.fields.iter().map(|f| &f.ty), values, base, offset)
+            }
+            TypeDefKind::Tuple(tuple) => {
+                self.store_fields(tuple.types.iter(), values, base, offset)
+            }
+            TypeDefKind::Flags(flags) => {
+                match flags.flags.len() {
+                    0 => {}
+                    1..=8 => self.store_scalar("i32.store8", values[0], base, offset),
+                    9..=16 => self.store_scalar("i32.store16", values[0], base, offset),
+                    _ => {
+                        for (i, value) in values.iter().enumerate() {
+                            self.store_scalar("i32.store", *value, base, offset + 4 * i);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            TypeDefKind::Enum(enum_) => {
+                self.store_scalar(store_discriminant(enum_.tag()), values[0], base, offset);
+                Ok(())
+            }
+            TypeDefKind::Variant(variant) => {
+                let cases: Vec<Option<&WitTy>> =
+                    variant.cases.iter().map(|case| case.ty.as_ref()).collect();
+                self.store_variant(variant.tag(), &cases, values, base, offset)
+            }
+            TypeDefKind::Option(inner) => {
+                self.store_variant(Int::U8, &[None, Some(inner)], values, base, offset)
+            }
+            TypeDefKind::Result(result) => self.store_variant(
+                Int::U8,
+                &[result.ok.as_ref(), result.err.as_ref()],
+                values,
+                base,
+                offset,
+            ),
+            other => Err(unsupported(other)),
+        }
+    }
+
+    fn store_scalar(&mut self, instr: &str, value: usize, base: &str, offset: usize) {
+        self.line(format!("local.get {base}"));
+        self.line(format!("local.get $t{value}"));
+        self.line(format!("{instr} offset={offset}"));
+    }
+
+    fn store_pointer_pair(&mut self, values: &[usize], base: &str, offset: usize) {
+        self.store_scalar("i32.store", values[0], base, offset);
+        self.store_scalar("i32.store", values[1], base, offset + 4);
+    }
+
+    fn store_fields<'t>(
+        &mut self,
+        types: impl Iterator<Item = &'t WitTy>,
+        values: &[usize],
+        base: &str,
+        offset: usize,
+    ) -> Result<(), CodeGenError> {
+        let mut remaining = values;
+        for (field_offset, ty) in self.sizes.field_offsets(types) {
+            let (field, rest) = remaining.split_at(self.flat(ty).len());
+            self.store(ty, field, base, offset + field_offset.size_wasm32())?;
+            remaining = rest;
+        }
+        Ok(())
+    }
+
+    /// Store the discriminant and narrow the joined payload back to the
+    /// flat representation of the active case
+    fn store_variant(
+        &mut self,
+        tag: Int,
+        cases: &[Option<&WitTy>],
+        values: &[usize],
+        base: &str,
+        offset: usize,
+    ) -> Result<(), CodeGenError> {
+        let discriminant = values[0];
+        self.store_scalar(store_discriminant(tag), discriminant, base, offset);
+        let payload_offset = offset
+            + self
+                .sizes
+                .payload_offset(tag, cases.iter().copied())
+                .size_wasm32();
+
+        for (index, case) in cases.iter().enumerate() {
+            let Some(case) = case else { continue };
+            self.line(format!("local.get $t{discriminant}"));
+            self.line(format!("i32.const {index}"));
+            self.line("i32.eq");
+            self.line("if");
+            self.depth += 1;
+            let mut narrowed = Vec::new();
+            for (source, target) in values[1..].iter().zip(self.flat(case)) {
+                let local = self.local(target);
+                self.line(format!("local.get $t{source}"));
+                self.coerce(self.locals[*source], target)?;
+                self.line(format!("local.set $t{local}"));
+                narrowed.push(local);
+            }
+            self.store(case, &narrowed, base, payload_offset)?;
+            self.depth -= 1;
+            self.line("end");
+        }
+        Ok(())
+    }
+
+    /// Convert between a case's flat type and the joined variant type
+    fn coerce(&mut self, from: CoreType, to: CoreType) -> Result<(), CodeGenError> {
+        use CoreType::*;
+        let instrs: &[&str] = match (from, to) {
+            _ if from == to => &[],
+            (F32, I32) => &["i32.reinterpret_f32"],
+            (I32, F32) => &["f32.reinterpret_i32"],
+            (I32, I64) => &["i64.extend_i32_u"],
+            (I64, I32) => &["i32.wrap_i64"],
+            (F32, I64) => &["i32.reinterpret_f32", "i64.extend_i32_u"],
+            (I64, F32) => &["i32.wrap_i64", "f32.reinterpret_i32"],
+            (F64, I64) => &["i64.reinterpret_f64"],
+            (I64, F64) => &["f64.reinterpret_i64"],
+            _ => {
+                return Err(CodeGenError::TypeError(format!(
+                    "cannot convert {} to {} in variant payload",
+                    from.name(),
+                    to.name()
+                )))
+            }
+        };
+        for instr in instrs {
+            self.line(*instr);
+        }
+        Ok(())
+    }
+}
+
+/// The module function behind an export must take and return the flattened
+/// WIT values
+fn check_implementation(
+    func: &Function,
+    implementation: &super::WasmFunction,
+    params: &[CoreType],
+    results: &[CoreType],
+) -> Result<(), CodeGenError> {
+    let core = |types: &[WasmType]| -> Option<Vec<CoreType>> {
+        types.iter().map(CoreType::from_wasm).collect()
+    };
+    let names = |types: &[CoreType]| -> String {
+        types.iter().map(|t| t.name()).collect::<Vec<_>>().join(" ")
+    };
+
+    if core(&implementation.params).as_deref() == Some(params)
+        && core(&implementation.results).as_deref() == Some(results)
+    {
+        return Ok(());
+    }
+    Err(CodeGenError::TypeError(format!(
+        "implementation of `{}` must have signature (param {}) (result {}) to match its WIT type",
+        func.name,
+        names(params),
+        names(results)
+    )))
+}
+
+fn load_discriminant(tag: Int) -> &'static str {
+    match tag {
+        Int::U8 => "i32.load8_u",
+        Int::U16 => "i32.load16_u",
+        Int::U32 | Int::U64 => "i32.load",
+    }
+}
+
+fn store_discriminant(tag: Int) -> &'static str {
+    match tag {
+        Int::U8 => "i32.store8",
+        Int::U16 => "i32.store16",
+        Int::U32 | Int::U64 => "i32.store",
+    }
+}
+
+fn unsupported(kind: &TypeDefKind) -> CodeGenError {
+    CodeGenError::TypeError(format!(
+        "{} values are not supported at the component boundary",
+        kind.as_str()
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::wasm::{WasmFunction, WasmInstr};
     use vibe_language::{Literal, Span};
 
     #[test]
@@ -183,4 +843,153 @@ mod tests {
         // For now, just check that it doesn't panic
         let _ = result;
     }
+
+    const SHAPES_WIT: &str = r#"package xs:shapes@0.1.0;
+
+interface exports {
+  record point {
+    x: s64,
+    y: s64,
+  }
+
+  variant shape {
+    circle(f64),
+    square(s64),
+  }
+
+  norm: func(arg1: point) -> s64;
+  origin: func() -> point;
+  unit-circle: func() -> shape;
+}
+
+world shapes {
+  export exports;
+}
+"#;
+
+    fn function(
+        name: &str,
+        params: Vec<WasmType>,
+        results: Vec<WasmType>,
+        body: Vec<WasmInstr>,
+    ) -> WasmFunction {
+        WasmFunction {
+            name: name.to_string(),
+            params,
+            results,
+            locals: vec![],
+            body,
+        }
+    }
+
+    /// Module functions taking and returning the flattened WIT values
+    fn shapes_module() -> WasmModule {
+        WasmModule {
+            functions: vec![
+                function(
+                    "norm",
+                    vec![WasmType::I64, WasmType::I64],
+                    vec![WasmType::I64],
+                    vec![
+                        WasmInstr::LocalGet(0),
+                        WasmInstr::LocalGet(1),
+                        WasmInstr::I64Add,
+                    ],
+                ),
+                function(
+                    "origin",
+                    vec![],
+                    vec![WasmType::I64, WasmType::I64],
+                    vec![WasmInstr::I64Const(3), WasmInstr::I64Const(4)],
+                ),
+                function(
+                    "unit_circle",
+                    vec![],
+                    vec![WasmType::I32, WasmType::I64],
+                    vec![
+                        WasmInstr::I32Const(0),
+                        WasmInstr::I64Const(1.0f64.to_bits() as i64),
+                    ],
+                ),
+            ],
+            ..WasmModule::default()
+        }
+    }
+
+    fn build(module: WasmModule, wit: &str) -> Result<Vec<u8>, CodeGenError> {
+        let metadata = ComponentMetadata {
+            name: "shapes".to_string(),
+            version: "0.1.0".to_string(),
+            exports: vec![],
+            imports: vec![],
+        };
+        let mut builder = ComponentBuilderImpl::new(metadata).with_wit_source(wit.to_string());
+        builder.add_module("main".to_string(), module);
+        builder.build()
+    }
+
+    fn glue(module: &WasmModule, wit: &str) -> String {
+        let mut resolve = Resolve::default();
+        let package = resolve.push_str("test.wit", wit).unwrap();
+        let world = resolve.select_world(package, None).unwrap();
+        generate_export_glue(&resolve, world, module).unwrap()
+    }
+
+    #[test]
+    fn test_component_with_typed_exports() {
+        let bytes = build(shapes_module(), SHAPES_WIT).unwrap();
+        // Component binaries use layer 1 in the version field
+        assert_eq!(&bytes[..8], b"\0asm\x0d\0\x01\0");
+    }
+
+    #[test]
+    fn test_results_are_lowered_into_return_area() {
+        let glue = glue(&shapes_module(), SHAPES_WIT);
+        assert!(glue.contains("cm32p2|xs:shapes/exports@0.1|origin"));
+        assert!(glue.contains("call $cabi_realloc"));
+        assert!(glue.contains("i64.store offset=8"));
+        // The circle payload arrives as the joined i64 and is stored as f64
+        assert!(glue.contains("f64.reinterpret_i64"));
+        assert!(glue.contains("f64.store offset=8"));
+    }
+
+    #[test]
+    fn test_many_params_are_lifted_from_memory() {
+        let fields: Vec<String> = (0..17).map(|i| format!("f{i}: s64")).collect();
+        let wit = format!(
+            "package xs:wide@0.1.0;\n\ninterface exports {{\n  record wide {{ {} }}\n  first: func(arg1: wide) -> s64;\n}}\n\nworld wide {{\n  export exports;\n}}\n",
+            fields.join(", ")
+        );
+        let mut body = vec![WasmInstr::LocalGet(0)];
+        body.extend((1..17).flat_map(|i| [WasmInstr::LocalGet(i), WasmInstr::Drop]));
+        let module = WasmModule {
+            functions: vec![function(
+                "first",
+                vec![WasmType::I64; 17],
+                vec![WasmType::I64],
+                body,
+            )],
+            ..WasmModule::default()
+        };
+
+        let glue = glue(&module, &wit);
+        assert!(glue.contains("(param $p0 i32) (result i64)"));
+        assert!(glue.contains("i64.load offset=128"));
+        build(module, &wit).unwrap();
+    }
+
+    #[test]
+    fn test_implementation_must_match_flat_signature() {
+        let mut module = shapes_module();
+        module.functions[0].params = vec![WasmType::I64];
+        let error = build(module, SHAPES_WIT).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`norm` must have signature (param i64 i64) (result i64)"));
+
+        let mut module = shapes_module();
+        module.functions.remove(1);
+        let error = build(module, SHAPES_WIT).unwrap_err();
+        assert!(error.to_string().contains("`origin` has no implementation"));
+    }
 }
@@ -87,9 +87,9 @@ fn emit_function(
 ) -> Result<(), std::fmt::Error> {
     write!(output, "  (func $func{} (export \"{}\")", idx, func.name)?;
 
-    // Emit parameters
+    // Emit parameters; they share the local index space with the locals
     for (i, param) in func.params.iter().enumerate() {
-        write!(output, " (param $p{i} ")?;
+        write!(output, " (param $l{i} ")?;
         emit_type(output, param)?;
         write!(output, ")")?;
     }
@@ -105,7 +105,7 @@ fn emit_function(
 
     // Emit locals
     for (i, local) in func.locals.iter().enumerate() {
-        write!(output, "    (local $l{i} ")?;
+        write!(output, "    (local $l{} ", func.params.len() + i)?;
         emit_type(output, local)?;
         writeln!(output, ")")?;
     }
@@ -334,10 +334,11 @@ mod tests {
         };
 
         let wat = emit_wat(&module).unwrap();
-        assert!(wat.contains("(param $p0 i64)"));
-        assert!(wat.contains("(param $p1 i64)"));
+        assert!(wat.contains("(param $l0 i64)"));
+        assert!(wat.contains("(param $l1 i64)"));
         assert!(wat.contains("(result i64)"));
-        assert!(wat.contains("(local $l0 i64)"));
+        assert!(wat.contains("(local $l2 i64)"));
         assert!(wat.contains("i64.add"));
+        wat::parse_str(&wat).unwrap();
     }
 }
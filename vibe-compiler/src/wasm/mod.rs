@@ -11,6 +11,7 @@ pub mod codegen;
 pub mod component;
 pub mod component_builder;
 pub mod emit;
+pub mod program;
 pub mod types;
 pub mod wit_generator;
 
@@ -0,0 +1,503 @@
+//! Whole-program compilation of top-level definitions
+//!
+//! Starting from an entry definition, only the definitions it transitively
+//! refers to are compiled, each into its own WebAssembly function. Builtins
+//! that map to single instructions are inlined; builtins that need a support
+//! function are added to the module only when a compiled definition uses
+//! them.
+//!
+//! Reachability is found by walking the lowered IR rather than the
+//! dependency tables of a code repository: the same walk finds the builtins
+//! that need support functions, and the definitions need not come from a
+//! repository at all.
+
+use super::codegen::CodeGenerator;
+use super::component::ComponentMetadata;
+use super::component_builder::ComponentBuilderImpl;
+use super::emit::emit_wat;
+use super::wit_generator::WitGenerator;
+use super::{CodeGenError, WasmFunction, WasmInstr, WasmModule, WasmType};
+use crate::PerceusTransform;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use vibe_language::ir::IrExpr;
+use vibe_language::{Expr, Literal, Type};
+
+/// A named top-level definition
+#[derive(Debug, Clone)]
+pub struct ProgramDefinition {
+    pub name: String,
+    pub expr: Expr,
+}
+
+/// Where a function of a compiled program comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionOrigin {
+    Definition(String),
+    Builtin(String),
+}
+
+impl FunctionOrigin {
+    pub fn name(&self) -> &str {
+        match self {
+            FunctionOrigin::Definition(name) | FunctionOrigin::Builtin(name) => name,
+        }
+    }
+}
+
+/// A compiled program: one function per reachable definition followed by the
+/// support functions of the builtins they use
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub entry: String,
+    pub module: WasmModule,
+    /// Origin of each function in `module.functions`, by index
+    pub functions: Vec<FunctionOrigin>,
+}
+
+/// Size of one function in an encoded module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSize {
+    pub origin: FunctionOrigin,
+    pub bytes: usize,
+}
+
+/// Per-function breakdown of an encoded module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Size of the whole binary
+    pub total: usize,
+    pub functions: Vec<FunctionSize>,
+}
+
+impl SizeReport {
+    /// Bytes not attributed to a function: headers, types, exports and glue
+    pub fn overhead(&self) -> usize {
+        let attributed: usize = self.functions.iter().map(|f| f.bytes).sum();
+        self.total.saturating_sub(attributed)
+    }
+}
+
+/// Builtins implemented by a support function, with their arity. Together
+/// with the arithmetic and comparison operators these are the only builtins
+/// a program can call.
+pub const SUPPORT_BUILTINS: [(&str, usize); 3] = [("abs", 1), ("min", 2), ("max", 2)];
+
+/// Compile `entry` and the definitions it transitively uses.
+///
+/// Definitions that `entry` does not reach are left out, as are the support
+/// functions of builtins no compiled definition calls.
+pub fn compile_program(
+    definitions: &[ProgramDefinition],
+    entry: &str,
+) -> Result<Program, CodeGenError> {
+    let by_name: HashMap<&str, &ProgramDefinition> = definitions
+        .iter()
+        .map(|definition| (definition.name.as_str(), definition))
+        .collect();
+    if !by_name.contains_key(entry) {
+        return Err(CodeGenError::UndefinedVariable(entry.to_string()));
+    }
+
+    let mut lowered: HashMap<&str, IrExpr> = HashMap::new();
+    let mut reachable: HashSet<&str> = HashSet::new();
+    let mut builtins = BTreeSet::new();
+    let mut queue = vec![entry];
+    while let Some(name) = queue.pop() {
+        if !reachable.insert(name) {
+            continue;
+        }
+        let definition = by_name[name];
+        if let Some(construct) = unsupported_construct(&definition.expr) {
+            return Err(CodeGenError::UnsupportedExpr(format!(
+                "{construct} in `{name}`"
+            )));
+        }
+        let ir = PerceusTransform::new().transform(&definition.expr);
+
+        let mut referenced = BTreeSet::new();
+        let bound = match &ir {
+            IrExpr::Lambda { params, .. } => params.iter().cloned().collect(),
+            _ => HashSet::new(),
+        };
+        free_names(&ir, &bound, &mut referenced);
+        for referenced in referenced {
+            if let Some((other, _)) = by_name.get_key_value(referenced.as_str()) {
+                queue.push(other);
+            } else if let Some((builtin, _)) = SUPPORT_BUILTINS
+                .iter()
+                .find(|(builtin, _)| *builtin == referenced)
+            {
+                builtins.insert(*builtin);
+            }
+        }
+        lowered.insert(name, ir);
+    }
+
+    // Functions keep the order the definitions were given in
+    let mut functions: Vec<FunctionOrigin> = definitions
+        .iter()
+        .filter(|definition| reachable.contains(definition.name.as_str()))
+        .map(|definition| FunctionOrigin::Definition(definition.name.clone()))
+        .collect();
+    functions.extend(
+        builtins
+            .iter()
+            .map(|builtin| FunctionOrigin::Builtin(builtin.to_string())),
+    );
+
+    let mut generator = CodeGenerator::new();
+    for (index, origin) in functions.iter().enumerate() {
+        let arity = match origin {
+            FunctionOrigin::Definition(name) => match &lowered[name.as_str()] {
+                IrExpr::Lambda { params, .. } => params.len(),
+                _ => 0,
+            },
+            FunctionOrigin::Builtin(name) => support_arity(name),
+        };
+        generator.declare_function(origin.name(), index as u32, arity);
+    }
+
+    let mut module = WasmModule::default();
+    for origin in &functions {
+        let function = match origin {
+            FunctionOrigin::Definition(name) => generator
+                .generate_function(name, &lowered[name.as_str()])
+                .map_err(|e| in_definition(name, e))?,
+            FunctionOrigin::Builtin(name) => support_function(name),
+        };
+        module.functions.push(function);
+    }
+
+    Ok(Program {
+        entry: entry.to_string(),
+        module,
+        functions,
+    })
+}
+
+/// Encode a program as a core WebAssembly module
+pub fn encode_module(program: &Program) -> Result<Vec<u8>, CodeGenError> {
+    let wat_text = emit_wat(&program.module)
+        .map_err(|e| CodeGenError::TypeError(format!("WAT emission failed: {e}")))?;
+    wat::parse_str(&wat_text)
+        .map_err(|e| CodeGenError::TypeError(format!("WAT parsing failed: {e}")))
+}
+
+/// Encode a program as a component exporting its entry point with type
+/// `entry_type`
+pub fn encode_component(program: &Program, entry_type: &Type) -> Result<Vec<u8>, CodeGenError> {
+    let name = super::component::to_wit_identifier(&program.entry);
+    let mut generator = WitGenerator::new(format!("vibe:{name}"), "0.1.0".to_string());
+    generator.add_export(program.entry.clone(), entry_type.clone());
+    let wit = generator.generate()?;
+
+    let metadata = ComponentMetadata {
+        name,
+        version: "0.1.0".to_string(),
+        exports: vec![],
+        imports: vec![],
+    };
+    let mut builder = ComponentBuilderImpl::new(metadata).with_wit_source(wit);
+    builder.add_module("main".to_string(), program.module.clone());
+    builder.build()
+}
+
+/// Attribute the code section of `module_bytes`, the core module encoded from
+/// `program`, to the program's functions. `total` is the size of the binary
+/// actually produced, which is larger when the module is wrapped in a
+/// component.
+pub fn size_report(
+    program: &Program,
+    module_bytes: &[u8],
+    total: usize,
+) -> Result<SizeReport, CodeGenError> {
+    let mut bodies = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module_bytes) {
+        let payload =
+            payload.map_err(|e| CodeGenError::TypeError(format!("Invalid module: {e}")))?;
+        if let wasmparser::Payload::CodeSectionEntry(body) = payload {
+            bodies.push(body.range().len());
+        }
+    }
+
+    let functions = program
+        .functions
+        .iter()
+        .zip(bodies)
+        .map(|(origin, bytes)| FunctionSize {
+            origin: origin.clone(),
+            bytes,
+        })
+        .collect();
+    Ok(SizeReport { total, functions })
+}
+
+fn in_definition(name: &str, error: CodeGenError) -> CodeGenError {
+    match error {
+        CodeGenError::UnsupportedExpr(what) => {
+            CodeGenError::UnsupportedExpr(format!("{what} in `{name}`"))
+        }
+        CodeGenError::InvalidCall(what) => CodeGenError::InvalidCall(format!("{what} in `{name}`")),
+        other => other,
+    }
+}
+
+/// The first construct in `expr` the WebAssembly backend cannot compile.
+/// The IR lowering replaces these with placeholder values, so they are
+/// rejected before lowering.
+fn unsupported_construct(expr: &Expr) -> Option<&'static str> {
+    match expr {
+        Expr::Literal(Literal::Int(_) | Literal::Bool(_), _) | Expr::Ident(..) => None,
+        Expr::Literal(Literal::Float(_), _) => Some("float literal"),
+        Expr::Literal(Literal::String(_), _) => Some("string literal"),
+        Expr::Lambda { body, .. } | Expr::Rec { body, .. } => unsupported_construct(body),
+        Expr::Apply { func, args, .. } => {
+            unsupported_construct(func).or_else(|| args.iter().find_map(unsupported_construct))
+        }
+        Expr::Pipeline { expr, func, .. } => {
+            unsupported_construct(expr).or_else(|| unsupported_construct(func))
+        }
+        Expr::If {
+            cond,
+            then_expr,
+            else_expr,
+            ..
+        } => unsupported_construct(cond)
+            .or_else(|| unsupported_construct(then_expr))
+            .or_else(|| unsupported_construct(else_expr)),
+        Expr::LetIn { value, body, .. } => {
+            unsupported_construct(value).or_else(|| unsupported_construct(body))
+        }
+        Expr::Match { .. } => Some("pattern matching"),
+        Expr::List(..) => Some("list"),
+        Expr::RecordLiteral { .. } | Expr::RecordAccess { .. } | Expr::RecordUpdate { .. } => {
+            Some("record")
+        }
+        Expr::Constructor { .. } => Some("constructor"),
+        Expr::Perform { .. }
+        | Expr::Handler { .. }
+        | Expr::WithHandler { .. }
+        | Expr::HandleExpr { .. }
+        | Expr::Do { .. } => Some("effect"),
+        _ => Some("expression"),
+    }
+}
+
+/// Collect the names `ir` refers to that are not bound inside it
+fn free_names(ir: &IrExpr, bound: &HashSet<String>, out: &mut BTreeSet<String>) {
+    match ir {
+        IrExpr::Var(name) if !bound.contains(name) => {
+            out.insert(name.clone());
+        }
+        IrExpr::Let { name, value, body } => {
+            free_names(value, bound, out);
+            let mut bound = bound.clone();
+            bound.insert(name.clone());
+            free_names(body, &bound, out);
+        }
+        IrExpr::LetRec { name, value, body } => {
+            let mut bound = bound.clone();
+            bound.insert(name.clone());
+            free_names(value, &bound, out);
+            free_names(body, &bound, out);
+        }
+        IrExpr::Lambda { params, body } => {
+            let mut bound = bound.clone();
+            bound.extend(params.iter().cloned());
+            free_names(body, &bound, out);
+        }
+        IrExpr::Apply { func, args } => {
+            free_names(func, bound, out);
+            for arg in args {
+                free_names(arg, bound, out);
+            }
+        }
+        IrExpr::If {
+            cond,
+            then_expr,
+            else_expr,
+        } => {
+            free_names(cond, bound, out);
+            free_names(then_expr, bound, out);
+            free_names(else_expr, bound, out);
+        }
+        IrExpr::List(items) | IrExpr::Sequence(items) => {
+            for item in items {
+                free_names(item, bound, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn support_arity(name: &str) -> usize {
+    SUPPORT_BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map_or(0, |(_, arity)| *arity)
+}
+
+/// Support function implementing the builtin `name`
+fn support_function(name: &str) -> WasmFunction {
+    let select = |comparison: WasmInstr| {
+        vec![
+            WasmInstr::LocalGet(0),
+            WasmInstr::LocalGet(1),
+            comparison,
+            WasmInstr::If {
+                result_type: Some(WasmType::I64),
+                then_instrs: vec![WasmInstr::LocalGet(0)],
+                else_instrs: vec![WasmInstr::LocalGet(1)],
+            },
+        ]
+    };
+    let body = match name {
+        "abs" => vec![
+            WasmInstr::LocalGet(0),
+            WasmInstr::I64Const(0),
+            WasmInstr::I64LtS,
+            WasmInstr::If {
+                result_type: Some(WasmType::I64),
+                then_instrs: vec![
+                    WasmInstr::I64Const(0),
+                    WasmInstr::LocalGet(0),
+                    WasmInstr::I64Sub,
+                ],
+                else_instrs: vec![WasmInstr::LocalGet(0)],
+            },
+        ],
+        "min" => select(WasmInstr::I64LtS),
+        "max" => select(WasmInstr::I64GtS),
+        _ => unreachable!("`{name}` is not a support builtin"),
+    };
+    WasmFunction {
+        name: name.to_string(),
+        params: vec![WasmType::I64; support_arity(name)],
+        results: vec![WasmType::I64],
+        locals: vec![],
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::{Ident, Span};
+
+    fn span() -> Span {
+        Span::new(0, 0)
+    }
+
+    fn var(name: &str) -> Expr {
+        Expr::Ident(Ident(name.to_string()), span())
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Int(n), span())
+    }
+
+    fn call(func: &str, args: Vec<Expr>) -> Expr {
+        Expr::Apply {
+            func: Box::new(var(func)),
+            args,
+            span: span(),
+        }
+    }
+
+    fn lambda(params: &[&str], body: Expr) -> Expr {
+        Expr::Lambda {
+            params: params
+                .iter()
+                .map(|p| (Ident(p.to_string()), None))
+                .collect(),
+            body: Box::new(body),
+            span: span(),
+        }
+    }
+
+    fn definition(name: &str, expr: Expr) -> ProgramDefinition {
+        ProgramDefinition {
+            name: name.to_string(),
+            expr,
+        }
+    }
+
+    fn definitions() -> Vec<ProgramDefinition> {
+        vec![
+            definition("offset", int(1)),
+            definition(
+                "square",
+                lambda(&["x"], call("*", vec![var("x"), var("x")])),
+            ),
+            definition("cube", lambda(&["x"], call("*", vec![var("x"), var("x")]))),
+            definition(
+                "main",
+                lambda(
+                    &["n"],
+                    call(
+                        "+",
+                        vec![
+                            call("square", vec![call("min", vec![var("n"), int(3)])]),
+                            var("offset"),
+                        ],
+                    ),
+                ),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_only_reachable_definitions_and_builtins() {
+        let program = compile_program(&definitions(), "main").unwrap();
+        assert_eq!(
+            program.functions,
+            vec![
+                FunctionOrigin::Definition("offset".to_string()),
+                FunctionOrigin::Definition("square".to_string()),
+                FunctionOrigin::Definition("main".to_string()),
+                FunctionOrigin::Builtin("min".to_string()),
+            ]
+        );
+
+        let bytes = encode_module(&program).unwrap();
+        let report = size_report(&program, &bytes, bytes.len()).unwrap();
+        assert_eq!(report.functions.len(), 4);
+        assert!(report.functions.iter().all(|f| f.bytes > 0));
+        assert!(report.overhead() > 0);
+
+        let square_only = compile_program(&definitions(), "square").unwrap();
+        assert_eq!(square_only.module.functions.len(), 1);
+        assert!(encode_module(&square_only).unwrap().len() < bytes.len());
+    }
+
+    #[test]
+    fn test_rejects_what_the_backend_cannot_compile() {
+        let mut program = definitions();
+        program.push(definition(
+            "greet",
+            Expr::Literal(Literal::String("hi".to_string()), span()),
+        ));
+        program.push(definition("twice", lambda(&["f"], call("f", vec![int(1)]))));
+
+        let error = compile_program(&program, "greet").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported IR expression: string literal in `greet`"
+        );
+        let error = compile_program(&program, "twice").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported IR expression: calls to function values in `twice`"
+        );
+        let error = compile_program(&program, "missing").unwrap_err();
+        assert_eq!(error.to_string(), "Undefined variable: missing");
+    }
+
+    #[test]
+    fn test_component_exports_entry() {
+        let program = compile_program(&definitions(), "main").unwrap();
+        let entry_type = Type::Function(Box::new(Type::Int), Box::new(Type::Int));
+        let bytes = encode_component(&program, &entry_type).unwrap();
+        assert!(wasmparser::Parser::is_component(&bytes));
+    }
+}
@@ -20,7 +20,7 @@ pub fn xs_type_to_wasm(ty: &Type) -> Result<WasmType, CodeGenError> {
             // Function as closure struct with function reference
             Ok(WasmType::StructRef(2)) // Placeholder index
         }
-        Type::Var(name) => {
+        Type::Var(name) | Type::Applied { constructor: name, .. } => {
             // Type variables should be resolved before code generation
             Err(CodeGenError::TypeError(format!(
                 "Unresolved type variable: {name}"
@@ -40,7 +40,7 @@ pub fn xs_type_to_wasm(ty: &Type) -> Result<WasmType, CodeGenError> {
             // Effects are tracked at compile-time, not runtime
             Ok(WasmType::StructRef(2)) // Same as regular function
         }
-        Type::Record { .. } => {
+        Type::Record { .. } | Type::ExtensibleRecord { .. } => {
             // Records as struct references
             Ok(WasmType::StructRef(4)) // Placeholder index
         }
@@ -54,6 +54,10 @@ pub fn xs_type_to_wasm(ty: &Type) -> Result<WasmType, CodeGenError> {
             // Number of fields determined by tuple size
             Ok(WasmType::StructRef((6 + types.len()) as u32)) // Placeholder index based on size
         }
+        Type::Forall { body, .. } => {
+            // Quantifiers are erased; the body is represented uniformly
+            xs_type_to_wasm(body)
+        }
     }
 }
 
@@ -3,7 +3,11 @@
 //! This module converts XS module definitions to WIT format,
 //! enabling WebAssembly Component Model integration.
 
-use super::component::{xs_type_to_wit, WitType};
+use super::component::{
+    function_signature, split_function_type, to_wit_identifier, type_suffix,
+    write_interface_items, InterfaceDefinition, WitTypeMapper,
+};
+use super::CodeGenError;
 use std::collections::HashMap;
 use std::fmt::Write;
 use vibe_language::{Type, TypeDefinition};
@@ -14,6 +18,7 @@ pub struct WitGenerator {
     version: String,
     types: HashMap<String, TypeDefinition>,
     exports: Vec<(String, Type)>,
+    instantiations: HashMap<String, Vec<Vec<Type>>>,
 }
 
 impl WitGenerator {
@@ -24,6 +29,7 @@ impl WitGenerator {
             version,
             types: HashMap::new(),
             exports: Vec::new(),
+            instantiations: HashMap::new(),
         }
     }
 
@@ -37,32 +43,63 @@ impl WitGenerator {
         self.exports.push((name, typ));
     }
 
+    /// Record the argument types a generic export is applied to
+    ///
+    /// Each distinct instantiation becomes its own WIT function, named after
+    /// the export and the types it was instantiated with (e.g. `identity-s64`).
+    pub fn add_instantiation(&mut self, name: String, arg_types: Vec<Type>) {
+        self.instantiations.entry(name).or_default().push(arg_types);
+    }
+
+    /// Build the exports interface, monomorphising generic exports
+    pub fn interface(&self) -> Result<InterfaceDefinition, CodeGenError> {
+        let mut mapper = WitTypeMapper::new(&self.types);
+
+        // Monomorphic type definitions are part of the interface even when no
+        // export mentions them
+        let mut names: Vec<&String> = self
+            .types
+            .iter()
+            .filter(|(_, def)| def.type_params.is_empty())
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        for name in names {
+            mapper.map(&Type::UserDefined {
+                name: name.clone(),
+                type_params: Vec::new(),
+            })?;
+        }
+
+        let mut functions = Vec::new();
+        for (name, typ) in &self.exports {
+            for (export_name, concrete) in self.monomorphise(name, typ)? {
+                let sig =
+                    function_signature(&mut mapper, &export_name, &concrete).map_err(|e| {
+                        CodeGenError::TypeError(format!("in export `{export_name}`: {e}"))
+                    })?;
+                functions.extend(sig);
+            }
+        }
+
+        Ok(InterfaceDefinition {
+            functions,
+            types: mapper.into_types(),
+        })
+    }
+
     /// Generate WIT file content
-    pub fn generate(&self) -> String {
+    pub fn generate(&self) -> Result<String, CodeGenError> {
+        let interface = self.interface()?;
         let mut wit = String::new();
 
         // Package declaration
         writeln!(&mut wit, "package {}@{};", self.package_name, self.version).unwrap();
         writeln!(&mut wit).unwrap();
 
-        // Generate type definitions
-        if !self.types.is_empty() {
-            writeln!(&mut wit, "interface types {{").unwrap();
-            for (name, typ_def) in &self.types {
-                let wit_type = self.generate_type_definition(name, typ_def);
-                writeln!(&mut wit, "  {wit_type}").unwrap();
-            }
-            writeln!(&mut wit, "}}").unwrap();
-            writeln!(&mut wit).unwrap();
-        }
-
-        // Generate exports interface
+        // Generate exports interface, including the types it refers to
         writeln!(&mut wit, "interface exports {{").unwrap();
-        for (name, typ) in &self.exports {
-            if let Some(func_sig) = self.generate_function_signature(name, typ) {
-                writeln!(&mut wit, "  {func_sig};").unwrap();
-            }
-        }
+        write_interface_items(&mut wit, &interface);
         writeln!(&mut wit, "}}").unwrap();
         writeln!(&mut wit).unwrap();
 
@@ -74,159 +111,255 @@ impl WitGenerator {
             .unwrap_or(&self.package_name);
         writeln!(&mut wit, "world {world_name} {{").unwrap();
         writeln!(&mut wit, "  export exports;").unwrap();
-        if !self.types.is_empty() {
-            writeln!(&mut wit, "  export types;").unwrap();
-        }
         writeln!(&mut wit, "}}").unwrap();
 
-        wit
-    }
-
-    /// Generate type definition for WIT
-    fn generate_type_definition(&self, name: &str, typ_def: &TypeDefinition) -> String {
-        self.generate_variant_type(name, typ_def)
+        Ok(wit)
     }
 
-    /// Generate variant type for ADTs
-    fn generate_variant_type(&self, name: &str, typ_def: &TypeDefinition) -> String {
-        let mut wit = format!("variant {} {{", to_wit_identifier(name));
-
-        for constructor in &typ_def.constructors {
-            write!(&mut wit, "\n    {}", to_wit_identifier(&constructor.name)).unwrap();
-
-            if !constructor.fields.is_empty() {
-                write!(&mut wit, "(").unwrap();
-                let field_types: Vec<String> = constructor
-                    .fields
-                    .iter()
-                    .map(|field| wit_type_string(&xs_type_to_wit(field)))
-                    .collect();
-                write!(&mut wit, "{}", field_types.join(", ")).unwrap();
-                write!(&mut wit, ")").unwrap();
-            }
-
-            write!(&mut wit, ",").unwrap();
+    /// Instantiate a generic export at every recorded set of argument types
+    fn monomorphise(&self, name: &str, typ: &Type) -> Result<Vec<(String, Type)>, CodeGenError> {
+        // Explicitly quantified variables are instantiated like any other
+        let typ = match typ {
+            Type::Forall { body, .. } => body.as_ref(),
+            typ => typ,
+        };
+        let vars = type_vars_in_order(typ);
+        let Some((params, _)) = split_function_type(typ) else {
+            return Ok(vec![(name.to_string(), typ.clone())]);
+        };
+        if vars.is_empty() {
+            return Ok(vec![(name.to_string(), typ.clone())]);
         }
 
-        write!(&mut wit, "\n  }}").unwrap();
-        wit
-    }
-
-    /// Generate function signature for WIT
-    fn generate_function_signature(&self, name: &str, typ: &Type) -> Option<String> {
-        let (params, result) = self.extract_function_parts(typ)?;
-
-        let mut sig = format!("{}: func(", to_wit_identifier(name));
-
-        // Add parameters
-        let param_strs: Vec<String> = params
-            .iter()
-            .enumerate()
-            .map(|(i, param_type)| {
-                format!(
-                    "arg{}: {}",
-                    i + 1,
-                    wit_type_string(&xs_type_to_wit(param_type))
-                )
-            })
-            .collect();
-        sig.push_str(&param_strs.join(", "));
-        sig.push(')');
+        let mut instances: Vec<(String, Type)> = Vec::new();
+        for arg_types in self.instantiations.get(name).into_iter().flatten() {
+            let mut subst = HashMap::new();
+            let matches = params
+                .iter()
+                .zip(arg_types)
+                .all(|(param, arg)| match_type(param, arg, &mut subst));
+            if !matches {
+                let args: Vec<String> = arg_types.iter().map(|t| t.to_string()).collect();
+                return Err(CodeGenError::TypeError(format!(
+                    "`{name} : {typ}` cannot be applied to arguments of type {}",
+                    args.join(", ")
+                )));
+            }
 
-        // Add result
-        match &result {
-            Type::UserDefined { name, .. } if name == "Unit" => {
-                // No return value for Unit type
+            let concrete = typ.apply_subst(&subst);
+            let remaining = type_vars_in_order(&concrete);
+            if !remaining.is_empty() {
+                return Err(CodeGenError::TypeError(format!(
+                    "instantiation of `{name}` leaves type variable(s) {} undetermined",
+                    remaining.join(", ")
+                )));
             }
-            _ => {
-                sig.push_str(" -> ");
-                sig.push_str(&wit_type_string(&xs_type_to_wit(&result)));
+            if instances.iter().any(|(_, existing)| *existing == concrete) {
+                continue;
             }
+
+            let mut mapper = WitTypeMapper::new(&self.types);
+            let mut export_name = name.to_string();
+            for var in &vars {
+                let suffix = match self.constructor_suffix(&mut mapper, &subst[var])? {
+                    Some(suffix) => suffix,
+                    None => type_suffix(&mapper.map(&subst[var])?),
+                };
+                export_name.push('_');
+                export_name.push_str(&suffix);
+            }
+            instances.push((export_name, concrete));
         }
 
-        Some(sig)
+        if instances.is_empty() {
+            return Err(CodeGenError::TypeError(format!(
+                "exported function `{name} : {typ}` is generic over {}; apply it to concrete arguments in the module so it can be monomorphised",
+                vars.join(", ")
+            )));
+        }
+        Ok(instances)
     }
 
-    /// Extract parameters and result from potentially curried function
-    fn extract_function_parts(&self, typ: &Type) -> Option<(Vec<Type>, Type)> {
-        let mut params = Vec::new();
-        let mut current_type = typ;
-
-        loop {
-            match current_type {
-                Type::Function(param, result)
-                | Type::FunctionWithEffect {
-                    from: param,
-                    to: result,
-                    ..
-                } => {
-                    params.push((**param).clone());
-
-                    // Check if result is another function
-                    match result.as_ref() {
-                        Type::Function(..) | Type::FunctionWithEffect { .. } => {
-                            current_type = result;
-                        }
-                        _ => {
-                            return Some((params, (**result).clone()));
-                        }
-                    }
-                }
-                _ => return None,
-            }
+    /// The name suffix for a higher-kinded variable bound to a type
+    /// constructor that still takes arguments (`list` for `List`), or `None`
+    /// if `bound` is a type
+    fn constructor_suffix(
+        &self,
+        mapper: &mut WitTypeMapper,
+        bound: &Type,
+    ) -> Result<Option<String>, CodeGenError> {
+        let Type::UserDefined { name, type_params } = bound else {
+            return Ok(None);
+        };
+        let arity = match (self.types.get(name), name.as_str()) {
+            (Some(definition), _) => definition.type_params.len(),
+            (None, "List" | "Option") => 1,
+            (None, "Result") => 2,
+            (None, _) => return Ok(None),
+        };
+        if type_params.len() >= arity {
+            return Ok(None);
+        }
+        // Part of a longer name, so a keyword needs no escaping
+        let mut suffix = to_wit_identifier(name).trim_start_matches('%').to_string();
+        for param in type_params {
+            suffix.push('-');
+            suffix.push_str(&type_suffix(&mapper.map(param)?));
         }
+        Ok(Some(suffix))
     }
 }
 
-/// Convert WIT type to string representation
-fn wit_type_string(wit_type: &WitType) -> String {
-    match wit_type {
-        WitType::Bool => "bool".to_string(),
-        WitType::S8 => "s8".to_string(),
-        WitType::U8 => "u8".to_string(),
-        WitType::S16 => "s16".to_string(),
-        WitType::U16 => "u16".to_string(),
-        WitType::S32 => "s32".to_string(),
-        WitType::U32 => "u32".to_string(),
-        WitType::S64 => "s64".to_string(),
-        WitType::U64 => "u64".to_string(),
-        WitType::Float32 => "float32".to_string(),
-        WitType::Float64 => "float64".to_string(),
-        WitType::String => "string".to_string(),
-        WitType::List(inner) => format!("list<{}>", wit_type_string(inner)),
-        WitType::Option(inner) => format!("option<{}>", wit_type_string(inner)),
-        WitType::Result { ok, err } => {
-            let ok_str = ok
-                .as_ref()
-                .map(|t| wit_type_string(t))
-                .unwrap_or_else(|| "_".to_string());
-            let err_str = err
-                .as_ref()
-                .map(|t| wit_type_string(t))
-                .unwrap_or_else(|| "_".to_string());
-            format!("result<{ok_str}, {err_str}>")
+/// Type variables of `typ` in order of first occurrence
+fn type_vars_in_order(typ: &Type) -> Vec<String> {
+    fn collect(typ: &Type, vars: &mut Vec<String>) {
+        match typ {
+            Type::Var(name) => {
+                if !vars.contains(name) {
+                    vars.push(name.clone());
+                }
+            }
+            Type::List(inner) | Type::Option(inner) => collect(inner, vars),
+            Type::Function(from, to) | Type::FunctionWithEffect { from, to, .. } => {
+                collect(from, vars);
+                collect(to, vars);
+            }
+            Type::UserDefined { type_params, .. } => {
+                type_params.iter().for_each(|t| collect(t, vars))
+            }
+            Type::Applied { constructor, args } => {
+                collect(&Type::Var(constructor.clone()), vars);
+                args.iter().for_each(|t| collect(t, vars));
+            }
+            Type::Forall { vars: bound, body } => {
+                bound.iter().for_each(|var| collect(&Type::Var(var.clone()), vars));
+                collect(body, vars);
+            }
+            Type::Tuple(items) => items.iter().for_each(|t| collect(t, vars)),
+            Type::Record { fields } => fields.iter().for_each(|(_, t)| collect(t, vars)),
+            Type::ExtensibleRecord { fields, row } => {
+                fields.iter().for_each(|(_, t)| collect(t, vars));
+                collect(&Type::Var(row.clone()), vars);
+            }
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Unit => {}
         }
-        WitType::Named(name) => to_wit_identifier(name),
     }
+
+    let mut vars = Vec::new();
+    collect(typ, &mut vars);
+    vars
 }
 
-/// Convert XS identifier to WIT-compatible identifier
-fn to_wit_identifier(name: &str) -> String {
-    // WIT identifiers must be kebab-case
-    name.chars()
-        .map(|c| {
-            if c == '_' {
-                '-'
-            } else {
-                c.to_ascii_lowercase()
+/// One-way matching of a generic type against a concrete one
+fn match_type(pattern: &Type, concrete: &Type, subst: &mut HashMap<String, Type>) -> bool {
+    match (pattern, concrete) {
+        (Type::Var(name), _) => match subst.get(name) {
+            Some(bound) => bound == concrete,
+            None => {
+                subst.insert(name.clone(), concrete.clone());
+                true
             }
-        })
-        .collect()
+        },
+        (Type::List(a), Type::List(b)) | (Type::Option(a), Type::Option(b)) => {
+            match_type(a, b, subst)
+        }
+        (
+            Type::Function(from_a, to_a)
+            | Type::FunctionWithEffect {
+                from: from_a,
+                to: to_a,
+                ..
+            },
+            Type::Function(from_b, to_b)
+            | Type::FunctionWithEffect {
+                from: from_b,
+                to: to_b,
+                ..
+            },
+        ) => match_type(from_a, from_b, subst) && match_type(to_a, to_b, subst),
+        (Type::Tuple(a), Type::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| match_type(a, b, subst))
+        }
+        (Type::Record { fields: a }, Type::Record { fields: b }) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((na, a), (nb, b))| na == nb && match_type(a, b, subst))
+        }
+        (Type::ExtensibleRecord { fields: a, row }, Type::Record { fields: b }) => {
+            let matched = a.iter().all(|(name, a)| {
+                b.iter()
+                    .any(|(field, b)| field == name && match_type(a, b, subst))
+            });
+            let rest = b
+                .iter()
+                .filter(|(field, _)| !a.iter().any(|(name, _)| name == field))
+                .cloned()
+                .collect();
+            matched && match_type(&Type::Var(row.clone()), &Type::Record { fields: rest }, subst)
+        }
+        (
+            Type::UserDefined {
+                name: name_a,
+                type_params: a,
+            },
+            Type::UserDefined {
+                name: name_b,
+                type_params: b,
+            },
+        ) => {
+            name_a == name_b
+                && a.len() == b.len()
+                && a.iter().zip(b).all(|(a, b)| match_type(a, b, subst))
+        }
+        // `f a` binds `f` to the constructor the concrete type applies
+        (Type::Applied { constructor, args }, _) => {
+            match concrete.split_constructor(args.len()) {
+                Some((head, rest)) => {
+                    match_type(&Type::Var(constructor.clone()), &head, subst)
+                        && args.iter().zip(&rest).all(|(a, b)| match_type(a, b, subst))
+                }
+                None => false,
+            }
+        }
+        _ => pattern == concrete,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use vibe_language::Constructor;
+
+    fn func(from: Type, to: Type) -> Type {
+        Type::Function(Box::new(from), Box::new(to))
+    }
+
+    fn named(name: &str, type_params: Vec<Type>) -> Type {
+        Type::UserDefined {
+            name: name.to_string(),
+            type_params,
+        }
+    }
+
+    fn definition(
+        name: &str,
+        params: &[&str],
+        constructors: Vec<(&str, Vec<Type>)>,
+    ) -> TypeDefinition {
+        TypeDefinition {
+            name: name.to_string(),
+            type_params: params.iter().map(|p| p.to_string()).collect(),
+            constructors: constructors
+                .into_iter()
+                .map(|(name, fields)| Constructor {
+                    name: name.to_string(),
+                    fields,
+                })
+                .collect(),
+            alias: None,
+        }
+    }
 
     #[test]
     fn test_generate_simple_module() {
@@ -239,7 +372,7 @@ mod tests {
         );
         gen.add_export("add".to_string(), add_type);
 
-        let wit = gen.generate();
+        let wit = gen.generate().unwrap();
         assert!(wit.contains("package xs:math@0.1.0;"));
         assert!(wit.contains("add: func(arg1: s64, arg2: s64) -> s64;"));
         assert!(wit.contains("world math {"));
@@ -248,30 +381,252 @@ mod tests {
 
     #[test]
     fn test_generate_with_types() {
-        use vibe_language::Constructor;
+        let mut gen = WitGenerator::new("xs:data".to_string(), "0.1.0".to_string());
+        gen.add_type_definition(
+            "Shape".to_string(),
+            definition(
+                "Shape",
+                &[],
+                vec![
+                    ("Circle", vec![Type::Float]),
+                    ("Rect", vec![Type::Float, Type::Float]),
+                    ("Empty", vec![]),
+                ],
+            ),
+        );
+        gen.add_type_definition(
+            "Color".to_string(),
+            definition("Color", &[], vec![("Red", vec![]), ("DarkBlue", vec![])]),
+        );
+        gen.add_export(
+            "area".to_string(),
+            func(named("Shape", vec![]), Type::Float),
+        );
+
+        let wit = gen.generate().unwrap();
+        assert!(wit.contains("  enum color {\n    red,\n    dark-blue,\n  }"));
+        assert!(wit.contains("  variant shape {"));
+        assert!(wit.contains("    circle(f64),"));
+        assert!(wit.contains("    rect(tuple<f64, f64>),"));
+        assert!(wit.contains("    empty,"));
+        assert!(wit.contains("area: func(arg1: shape) -> f64;"));
+    }
+
+    #[test]
+    fn test_records_tuples_and_unit() {
+        let point = Type::Record {
+            fields: vec![("x".to_string(), Type::Int), ("y".to_string(), Type::Int)],
+        };
+        let mut gen = WitGenerator::new("xs:geo".to_string(), "0.1.0".to_string());
+        gen.add_export(
+            "swap".to_string(),
+            func(
+                Type::Tuple(vec![Type::Int, Type::String]),
+                Type::Tuple(vec![Type::String, Type::Int]),
+            ),
+        );
+        gen.add_export("show".to_string(), func(point.clone(), Type::Unit));
+        gen.add_export("origin".to_string(), func(Type::Unit, point));
+        gen.add_export(
+            "find".to_string(),
+            func(Type::String, Type::Option(Box::new(Type::Int))),
+        );
+
+        let wit = gen.generate().unwrap();
+        assert!(wit.contains("  record record-x-y {\n    x: s64,\n    y: s64,\n  }"));
+        assert!(wit.contains("swap: func(arg1: tuple<s64, string>) -> tuple<string, s64>;"));
+        assert!(wit.contains("show: func(arg1: record-x-y);"));
+        assert!(wit.contains("origin: func() -> record-x-y;"));
+        assert!(wit.contains("find: func(arg1: string) -> option<s64>;"));
+        assert_eq!(wit.matches("record record-x-y").count(), 1);
+    }
+
+    #[test]
+    fn test_option_and_result_shaped_adts() {
+        let mut gen = WitGenerator::new("xs:data".to_string(), "0.1.0".to_string());
+        gen.add_type_definition(
+            "Maybe".to_string(),
+            definition(
+                "Maybe",
+                &["a"],
+                vec![("None", vec![]), ("Some", vec![Type::Var("a".to_string())])],
+            ),
+        );
+        gen.add_type_definition(
+            "Outcome".to_string(),
+            definition(
+                "Outcome",
+                &["e", "a"],
+                vec![
+                    ("Ok", vec![Type::Var("a".to_string())]),
+                    ("Err", vec![Type::Var("e".to_string())]),
+                ],
+            ),
+        );
+        gen.add_export(
+            "parse".to_string(),
+            func(
+                Type::String,
+                named(
+                    "Outcome",
+                    vec![Type::String, named("Maybe", vec![Type::Int])],
+                ),
+            ),
+        );
+        gen.add_export(
+            "check".to_string(),
+            func(Type::Int, named("Outcome", vec![Type::String, Type::Unit])),
+        );
 
+        let wit = gen.generate().unwrap();
+        assert!(wit.contains("parse: func(arg1: string) -> result<option<s64>, string>;"));
+        assert!(wit.contains("check: func(arg1: s64) -> result<_, string>;"));
+        assert!(!wit.contains("variant"));
+    }
+
+    #[test]
+    fn test_generic_adts_are_instantiated() {
         let mut gen = WitGenerator::new("xs:data".to_string(), "0.1.0".to_string());
+        gen.add_type_definition(
+            "Pair".to_string(),
+            definition(
+                "Pair",
+                &["a"],
+                vec![
+                    (
+                        "Both",
+                        vec![Type::Var("a".to_string()), Type::Var("a".to_string())],
+                    ),
+                    ("Neither", vec![]),
+                ],
+            ),
+        );
+        gen.add_export(
+            "ints".to_string(),
+            func(Type::Unit, named("Pair", vec![Type::Int])),
+        );
+        gen.add_export(
+            "names".to_string(),
+            func(
+                Type::Unit,
+                named("Pair", vec![Type::List(Box::new(Type::String))]),
+            ),
+        );
+
+        let wit = gen.generate().unwrap();
+        assert!(wit.contains("  variant pair-s64 {\n    both(tuple<s64, s64>),\n    neither,\n  }"));
+        assert!(wit.contains("  variant pair-list-string {"));
+        assert!(wit.contains("ints: func() -> pair-s64;"));
+        assert!(wit.contains("names: func() -> pair-list-string;"));
+    }
 
-        // Add Option type definition
-        let option_type_def = TypeDefinition {
-            name: "Option".to_string(),
-            type_params: vec!["a".to_string()],
-            constructors: vec![
-                Constructor {
-                    name: "None".to_string(),
-                    fields: vec![],
-                },
-                Constructor {
-                    name: "Some".to_string(),
-                    fields: vec![Type::Var("a".to_string())],
-                },
-            ],
+    #[test]
+    fn test_generic_exports_are_monomorphised() {
+        let a = || Type::Var("a".to_string());
+        let b = || Type::Var("b".to_string());
+        let mut gen = WitGenerator::new("xs:poly".to_string(), "0.1.0".to_string());
+        gen.add_export("identity".to_string(), func(a(), a()));
+        gen.add_export(
+            "pair".to_string(),
+            func(a(), func(b(), Type::Tuple(vec![a(), b()]))),
+        );
+        gen.add_instantiation("identity".to_string(), vec![Type::Int]);
+        gen.add_instantiation("identity".to_string(), vec![Type::String]);
+        gen.add_instantiation("identity".to_string(), vec![Type::Int]);
+        gen.add_instantiation("pair".to_string(), vec![Type::Bool, Type::Float]);
+
+        let wit = gen.generate().unwrap();
+        assert!(wit.contains("identity-s64: func(arg1: s64) -> s64;"));
+        assert!(wit.contains("identity-string: func(arg1: string) -> string;"));
+        assert_eq!(wit.matches("identity-s64").count(), 1);
+        assert!(wit.contains("pair-bool-f64: func(arg1: bool, arg2: f64) -> tuple<bool, f64>;"));
+    }
+
+    #[test]
+    fn test_higher_kinded_exports_and_aliases() {
+        let f_a = || Type::Applied {
+            constructor: "f".to_string(),
+            args: vec![Type::Var("a".to_string())],
         };
-        gen.add_type_definition("Option".to_string(), option_type_def);
+        let mut gen = WitGenerator::new("xs:hkt".to_string(), "0.1.0".to_string());
+        gen.add_type_definition(
+            "UserId".to_string(),
+            TypeDefinition {
+                alias: Some(Type::String),
+                ..definition("UserId", &[], vec![])
+            },
+        );
+        gen.add_export(
+            "same".to_string(),
+            Type::Forall {
+                vars: vec!["f".to_string(), "a".to_string()],
+                body: Box::new(func(f_a(), f_a())),
+            },
+        );
+        gen.add_export("login".to_string(), func(named("UserId", vec![]), Type::Bool));
+        gen.add_instantiation(
+            "same".to_string(),
+            vec![Type::List(Box::new(Type::Int))],
+        );
+        gen.add_instantiation(
+            "same".to_string(),
+            vec![Type::Option(Box::new(Type::String))],
+        );
+
+        let wit = gen.generate().unwrap();
+        assert!(wit.contains("type user-id = string;"), "{wit}");
+        assert!(wit.contains("login: func(arg1: user-id) -> bool;"), "{wit}");
+        assert!(
+            wit.contains("same-list-s64: func(arg1: list<s64>) -> list<s64>;"),
+            "{wit}"
+        );
+        assert!(
+            wit.contains("same-option-string: func(arg1: option<string>) -> option<string>;"),
+            "{wit}"
+        );
+    }
+
+    #[test]
+    fn test_unrepresentable_exports_are_errors() {
+        let a = Type::Var("a".to_string());
+        let mut gen = WitGenerator::new("xs:poly".to_string(), "0.1.0".to_string());
+        gen.add_export("identity".to_string(), func(a.clone(), a));
+        let error = gen.generate().unwrap_err().to_string();
+        assert!(error.contains("generic over a"), "{error}");
+
+        let mut gen = WitGenerator::new("xs:hof".to_string(), "0.1.0".to_string());
+        gen.add_export(
+            "apply".to_string(),
+            func(func(Type::Int, Type::Int), Type::Int),
+        );
+        let error = gen.generate().unwrap_err().to_string();
+        assert!(
+            error.contains("cannot cross the component boundary"),
+            "{error}"
+        );
 
-        let wit = gen.generate();
-        assert!(wit.contains("variant option {"));
-        assert!(wit.contains("none,"));
-        assert!(wit.contains("some(string),"));
+        let mut gen = WitGenerator::new("xs:list".to_string(), "0.1.0".to_string());
+        gen.add_type_definition(
+            "IntList".to_string(),
+            definition(
+                "IntList",
+                &[],
+                vec![
+                    ("Nil", vec![]),
+                    ("Cons", vec![Type::Int, named("IntList", vec![])]),
+                ],
+            ),
+        );
+        let error = gen.generate().unwrap_err().to_string();
+        assert!(error.contains("recursive type"), "{error}");
+    }
+
+    #[test]
+    fn test_wit_identifiers() {
+        use crate::wasm::component::to_wit_identifier;
+        assert_eq!(to_wit_identifier("parse_json"), "parse-json");
+        assert_eq!(to_wit_identifier("userName"), "user-name");
+        assert_eq!(to_wit_identifier("DarkBlue"), "dark-blue");
+        assert_eq!(to_wit_identifier("record"), "%record");
     }
 }
@@ -5,26 +5,84 @@ use crate::normalized_ast::{
     NormalizedExpr, NormalizedPattern, NormalizedDef, NormalizedHandler,
     DesugarContext, DoStatement
 };
+use crate::effect_normalizer::EffectNormalizer;
+use crate::typed_ir::SyntaxSugar;
 use std::collections::BTreeMap;
 
 /// Convert surface AST to normalized AST
 pub struct AstNormalizer {
     ctx: DesugarContext,
+    effects: EffectNormalizer,
+    /// Variables in scope, innermost last, with the State effect holding
+    /// each `mut` variable
+    scope: Vec<(String, Option<String>)>,
+    /// Sugar removed so far, for printing the normalized form back
+    sugar: Vec<SyntaxSugar>,
 }
 
 impl AstNormalizer {
     pub fn new() -> Self {
         Self {
             ctx: DesugarContext::new(),
+            effects: EffectNormalizer::new(),
+            scope: Vec::new(),
+            sugar: Vec::new(),
         }
     }
     
+    /// Syntax sugar removed by normalization, for [`crate::resugar`]
+    pub fn sugar(&self) -> &[SyntaxSugar] {
+        &self.sugar
+    }
+    
+    /// The State effect holding `name`, if it is a `mut` variable
+    fn mutable_effect(&self, name: &str) -> Option<String> {
+        self.scope.iter().rev()
+            .find(|(bound, _)| bound == name)
+            .and_then(|(_, effect)| effect.clone())
+    }
+    
+    /// Normalize `expr` with `names` bound as ordinary variables
+    fn normalize_scoped(&mut self, names: &[String], expr: &Expr) -> NormalizedExpr {
+        let depth = self.scope.len();
+        self.scope.extend(names.iter().map(|name| (name.clone(), None)));
+        let result = self.normalize_expr(expr);
+        self.scope.truncate(depth);
+        result
+    }
+    
     /// Normalize a top-level expression
     pub fn normalize_expr(&mut self, expr: &Expr) -> NormalizedExpr {
         match expr {
             Expr::Literal(lit, _) => NormalizedExpr::Literal(lit.clone()),
             
-            Expr::Ident(ident, _) => NormalizedExpr::Var(ident.0.clone()),
+            Expr::Ident(ident, _) => match self.mutable_effect(&ident.0) {
+                Some(effect) => NormalizedExpr::Perform {
+                    effect,
+                    operation: "get".to_string(),
+                    args: vec![],
+                },
+                None => NormalizedExpr::Var(ident.0.clone()),
+            },
+            
+            Expr::Assign { name, value, .. } => {
+                let effect = self.mutable_effect(&name.0).unwrap_or_else(|| {
+                    panic!("Assignment to `{}`, which is not a mut variable", name.0)
+                });
+                NormalizedExpr::Perform {
+                    effect,
+                    operation: "put".to_string(),
+                    args: vec![self.normalize_expr(value)],
+                }
+            }
+            
+            Expr::Mut { .. } => {
+                // A mut variable is scoped to the rest of its block, here empty
+                let depth = self.scope.len();
+                let result = self.normalize_block(std::slice::from_ref(expr));
+                self.scope.truncate(depth);
+                result
+            }
             
             Expr::List(elements, _) => {
                 NormalizedExpr::List(
@@ -42,7 +100,7 @@ impl AstNormalizer {
                 NormalizedExpr::Let {
                     name: name.0.clone(),
                     value: Box::new(self.normalize_expr(value)),
-                    body: Box::new(self.normalize_expr(body)),
+                    body: Box::new(self.normalize_scoped(std::slice::from_ref(&name.0), body)),
                 }
             }
             
@@ -52,10 +110,11 @@ impl AstNormalizer {
             }
             
             Expr::LetRecIn { name, value, body, .. } => {
+                let names = [name.0.clone()];
                 NormalizedExpr::LetRec {
                     name: name.0.clone(),
-                    value: Box::new(self.normalize_expr(value)),
-                    body: Box::new(self.normalize_expr(body)),
+                    value: Box::new(self.normalize_scoped(&names, value)),
+                    body: Box::new(self.normalize_scoped(&names, body)),
                 }
             }
             
@@ -65,7 +124,7 @@ impl AstNormalizer {
                     .map(|(ident, _)| ident.0.clone())
                     .collect();
                     
-                let normalized_body = self.normalize_expr(body);
+                let normalized_body = self.normalize_scoped(&param_names, body);
                 NormalizedExpr::desugar_multi_lambda(param_names, normalized_body)
             }
             
@@ -75,7 +134,7 @@ impl AstNormalizer {
                     .map(|p| p.name.0.clone())
                     .collect();
                     
-                let normalized_body = self.normalize_expr(body);
+                let normalized_body = self.normalize_scoped(&param_names, body);
                 NormalizedExpr::desugar_multi_lambda(param_names, normalized_body)
             }
             
@@ -143,11 +202,10 @@ impl AstNormalizer {
             }
             
             Expr::Block { exprs, .. } => {
-                let normalized_exprs: Vec<NormalizedExpr> = exprs.iter()
-                    .map(|e| self.normalize_expr(e))
-                    .collect();
-                    
-                NormalizedExpr::desugar_sequence(normalized_exprs, &mut self.ctx)
+                let depth = self.scope.len();
+                let result = self.normalize_block(exprs);
+                self.scope.truncate(depth);
+                result
             }
             
             Expr::Do { statements, .. } => {
@@ -231,6 +289,51 @@ impl AstNormalizer {
         }
     }
     
+    /// Normalize the statements of a block. `let` and `mut` bind the rest of
+    /// the block; a `mut` variable becomes a State effect handled there.
+    fn normalize_block(&mut self, exprs: &[Expr]) -> NormalizedExpr {
+        match exprs {
+            [] => NormalizedExpr::Constructor {
+                name: "Unit".to_string(),
+                args: vec![],
+            },
+            [Expr::Let { name, value, .. }, rest @ ..] => {
+                let value = self.normalize_expr(value);
+                self.scope.push((name.0.clone(), None));
+                let body = if rest.is_empty() {
+                    NormalizedExpr::Var(name.0.clone())
+                } else {
+                    self.normalize_block(rest)
+                };
+                NormalizedExpr::Let {
+                    name: name.0.clone(),
+                    value: Box::new(value),
+                    body: Box::new(body),
+                }
+            }
+            [Expr::Mut { name, value, .. }, rest @ ..] => {
+                let initial = self.normalize_expr(value);
+                let effect = self.ctx.fresh_var("State");
+                self.scope.push((name.0.clone(), Some(effect.clone())));
+                let body = self.normalize_block(rest);
+                self.sugar.push(SyntaxSugar::MutableVariable {
+                    name: name.0.clone(),
+                    effect: effect.clone(),
+                });
+                self.effects.local_state(&effect, initial, body)
+            }
+            [expr] => self.normalize_expr(expr),
+            [expr, rest @ ..] => {
+                let value = self.normalize_expr(expr);
+                NormalizedExpr::Let {
+                    name: self.ctx.fresh_var("seq"),
+                    value: Box::new(value),
+                    body: Box::new(self.normalize_block(rest)),
+                }
+            }
+        }
+    }
+    
     /// Normalize a pattern
     pub fn normalize_pattern(&mut self, pattern: &Pattern) -> NormalizedPattern {
         match pattern {
@@ -400,4 +503,80 @@ mod tests {
             _ => panic!("Expected lambda"),
         }
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_normalize_mut_to_local_state() {
+        let mut normalizer = AstNormalizer::new();
+        let span = Span::new(0, 0);
+        let var = |name: &str| Expr::Ident(Ident(name.to_string()), span.clone());
+        
+        // { mut s = 0; s = 1; (fn s -> s) s }
+        let expr = Expr::Block {
+            exprs: vec![
+                Expr::Mut {
+                    name: Ident("s".to_string()),
+                    value: Box::new(Expr::Literal(Literal::Int(0), span.clone())),
+                    span: span.clone(),
+                },
+                Expr::Assign {
+                    name: Ident("s".to_string()),
+                    value: Box::new(Expr::Literal(Literal::Int(1), span.clone())),
+                    span: span.clone(),
+                },
+                Expr::Apply {
+                    func: Box::new(Expr::Lambda {
+                        params: vec![(Ident("s".to_string()), None)],
+                        body: Box::new(var("s")),
+                        span: span.clone(),
+                    }),
+                    args: vec![var("s")],
+                    span: span.clone(),
+                },
+            ],
+            span: span.clone(),
+        };
+        
+        let result = normalizer.normalize_expr(&expr);
+        assert_eq!(
+            normalizer.sugar(),
+            [SyntaxSugar::MutableVariable {
+                name: "s".to_string(),
+                effect: "State$0".to_string(),
+            }]
+        );
+        
+        // (handle { put 1; (fn s -> s) get } { get, put, return }) 0
+        let NormalizedExpr::Apply { func, arg } = result else {
+            panic!("Expected the handler applied to the initial state, got {result:?}");
+        };
+        assert_eq!(*arg, NormalizedExpr::Literal(Literal::Int(0)));
+        let NormalizedExpr::Handle { expr: body, handlers } = *func else {
+            panic!("Expected a handler");
+        };
+        let operations: Vec<(&str, &str)> = handlers.iter()
+            .map(|h| (h.effect.as_str(), h.operation.as_str()))
+            .collect();
+        assert_eq!(operations, [("State$0", "get"), ("State$0", "put"), ("return", "return")]);
+        
+        let NormalizedExpr::Let { value, body, .. } = *body else {
+            panic!("Expected a sequence");
+        };
+        assert_eq!(*value, NormalizedExpr::Perform {
+            effect: "State$0".to_string(),
+            operation: "put".to_string(),
+            args: vec![NormalizedExpr::Literal(Literal::Int(1))],
+        });
+        // The lambda parameter shadows the mut variable
+        assert_eq!(*body, NormalizedExpr::Apply {
+            func: Box::new(NormalizedExpr::Lambda {
+                param: "s".to_string(),
+                body: Box::new(NormalizedExpr::Var("s".to_string())),
+            }),
+            arg: Box::new(NormalizedExpr::Perform {
+                effect: "State$0".to_string(),
+                operation: "get".to_string(),
+                args: vec![],
+            }),
+        });
+    }
+}
@@ -3,7 +3,7 @@
 //! This module provides namespace organization for builtin functions,
 //! allowing them to be accessed as Int.toString, String.concat, etc.
 
-use crate::Type;
+use crate::{Constructor, Effect, EffectRow, EffectSet, Type, TypeDefinition};
 use std::collections::HashMap;
 
 /// Represents a builtin module with its functions
@@ -26,6 +26,56 @@ impl BuiltinModule {
     }
 }
 
+/// The type `Json.parse` returns
+pub fn json_value_type() -> Type {
+    Type::UserDefined {
+        name: "JsonValue".to_string(),
+        type_params: vec![],
+    }
+}
+
+/// A key and its value in a `JsonObject`
+pub fn json_field_type() -> Type {
+    Type::UserDefined {
+        name: "JsonField".to_string(),
+        type_params: vec![],
+    }
+}
+
+/// Data types the builtin modules use
+pub fn builtin_type_definitions() -> Vec<TypeDefinition> {
+    let constructor = |name: &str, fields: Vec<Type>| Constructor {
+        name: name.to_string(),
+        fields,
+    };
+    vec![
+        TypeDefinition {
+            name: "JsonValue".to_string(),
+            type_params: vec![],
+            constructors: vec![
+                constructor("JsonNull", vec![]),
+                constructor("JsonBool", vec![Type::Bool]),
+                constructor("JsonInt", vec![Type::Int]),
+                constructor("JsonFloat", vec![Type::Float]),
+                constructor("JsonString", vec![Type::String]),
+                constructor("JsonArray", vec![Type::List(Box::new(json_value_type()))]),
+                // The object's fields in key order
+                constructor("JsonObject", vec![Type::List(Box::new(json_field_type()))]),
+            ],
+            alias: None,
+        },
+        TypeDefinition {
+            name: "JsonField".to_string(),
+            type_params: vec![],
+            constructors: vec![constructor(
+                "JsonField",
+                vec![Type::String, json_value_type()],
+            )],
+            alias: None,
+        },
+    ]
+}
+
 /// Registry of all builtin modules
 pub struct BuiltinModuleRegistry {
     modules: HashMap<String, BuiltinModule>,
@@ -175,10 +225,11 @@ impl BuiltinModuleRegistry {
         let mut io_module = BuiltinModule::new("IO");
         io_module.add_function(
             "print",
-            Type::Function(
-                Box::new(Type::Var("a".to_string())),
-                Box::new(Type::Var("a".to_string())),
-            ),
+            Type::FunctionWithEffect {
+                from: Box::new(Type::Var("a".to_string())),
+                to: Box::new(Type::Var("a".to_string())),
+                effects: EffectRow::Concrete(EffectSet::single(Effect::IO)),
+            },
         );
         self.modules.insert("IO".to_string(), io_module);
 
@@ -192,6 +243,26 @@ impl BuiltinModuleRegistry {
             ),
         );
         self.modules.insert("Float".to_string(), float_module);
+
+        // Json module
+        let mut json_module = BuiltinModule::new("Json");
+        json_module.add_function(
+            "parse",
+            Type::Function(Box::new(Type::String), Box::new(json_value_type())),
+        );
+        json_module.add_function(
+            "stringify",
+            Type::Function(Box::new(Type::Var("a".to_string())), Box::new(Type::String)),
+        );
+        json_module.add_function(
+            "readFile",
+            Type::FunctionWithEffect {
+                from: Box::new(Type::String),
+                to: Box::new(json_value_type()),
+                effects: EffectRow::Concrete(EffectSet::single(Effect::FileSystem)),
+            },
+        );
+        self.modules.insert("Json".to_string(), json_module);
     }
 
     pub fn get_module(&self, name: &str) -> Option<&BuiltinModule> {
@@ -233,6 +304,20 @@ mod tests {
         assert!(registry.get_function_type("String", "concat").is_some());
         assert!(registry.get_function_type("String", "length").is_some());
 
+        // Test Json module; reading a file is a FileSystem effect
+        assert_eq!(
+            registry.get_function_type("Json", "parse"),
+            Some(&Type::Function(
+                Box::new(Type::String),
+                Box::new(json_value_type())
+            ))
+        );
+        assert!(matches!(
+            registry.get_function_type("Json", "readFile"),
+            Some(Type::FunctionWithEffect { effects, .. })
+                if effects == &EffectRow::Concrete(EffectSet::single(Effect::FileSystem))
+        ));
+
         // Test non-existent module
         assert!(registry.get_module("NonExistent").is_none());
         assert!(registry.get_function_type("Int", "nonExistent").is_none());
@@ -64,6 +64,70 @@ impl EffectNormalizer {
         handler
     }
     
+    /// Run `body` with a local state effect named `effect`, starting from
+    /// `initial`. This is the state monad as a handler: each operation
+    /// returns a function of the current state.
+    /// Transform: body, with `effect.get ()` and `effect.put v`
+    /// Into: (handle body { get () k -> fn s -> k s s
+    ///                      put v k -> fn _ -> k () v
+    ///                      return x -> fn _ -> x }) initial
+    pub fn local_state(
+        &mut self,
+        effect: &str,
+        initial: NormalizedExpr,
+        body: NormalizedExpr,
+    ) -> NormalizedExpr {
+        let var = |name: &String| Box::new(NormalizedExpr::Var(name.clone()));
+        let apply = |func: Box<NormalizedExpr>, arg: Box<NormalizedExpr>| {
+            Box::new(NormalizedExpr::Apply { func, arg })
+        };
+        
+        let (k, state) = (self.ctx.fresh_var("k"), self.ctx.fresh_var("s"));
+        let get = NormalizedHandler {
+            effect: effect.to_string(),
+            operation: "get".to_string(),
+            params: vec![],
+            resume: k.clone(),
+            body: NormalizedExpr::Lambda {
+                param: state.clone(),
+                body: apply(apply(var(&k), var(&state)), var(&state)),
+            },
+        };
+        
+        let (k, value) = (self.ctx.fresh_var("k"), self.ctx.fresh_var("v"));
+        let unit = Box::new(NormalizedExpr::Constructor {
+            name: "Unit".to_string(),
+            args: vec![],
+        });
+        let put = NormalizedHandler {
+            effect: effect.to_string(),
+            operation: "put".to_string(),
+            params: vec![value.clone()],
+            resume: k.clone(),
+            body: NormalizedExpr::Lambda {
+                param: self.ctx.fresh_var("_"),
+                body: apply(apply(var(&k), unit), var(&value)),
+            },
+        };
+        
+        let result = self.ctx.fresh_var("x");
+        let finish = NormalizedHandler {
+            effect: "return".to_string(),
+            operation: "return".to_string(),
+            params: vec![result.clone()],
+            resume: "_".to_string(),
+            body: NormalizedExpr::Lambda {
+                param: self.ctx.fresh_var("_"),
+                body: var(&result),
+            },
+        };
+        
+        NormalizedExpr::Apply {
+            func: Box::new(self.normalize_handle(body, vec![get, put, finish])),
+            arg: Box::new(initial),
+        }
+    }
+    
     /// Transform do-notation with effects into explicit perform/handle
     pub fn desugar_do_with_effects(
         &mut self,
@@ -350,4 +414,22 @@ mod tests {
         let effects = normalizer.infer_effects(&expr);
         assert_eq!(effects.len(), 0);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_local_state_handles_its_effect() {
+        let mut normalizer = EffectNormalizer::new();
+        
+        let body = NormalizedExpr::Perform {
+            effect: "State$0".to_string(),
+            operation: "get".to_string(),
+            args: vec![],
+        };
+        let expr = normalizer.local_state(
+            "State$0",
+            NormalizedExpr::Literal(Literal::Int(0)),
+            body,
+        );
+        
+        assert!(normalizer.infer_effects(&expr).is_empty());
+    }
+}
@@ -327,11 +327,26 @@ fn type_to_string(ty: &Type) -> String {
                 .collect();
             format!("{{ {} }}", field_types.join(", "))
         }
+        Type::ExtensibleRecord { fields, row } => {
+            let field_types: Vec<String> = fields
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, type_to_string(ty)))
+                .collect();
+            format!("{{ {} | '{} }}", field_types.join(", "), row)
+        }
         Type::Option(t) => format!("{}?", type_to_string(t)),
         Type::Tuple(types) => {
             let type_strs: Vec<String> = types.iter().map(type_to_string).collect();
             format!("({})", type_strs.join(", "))
         }
+        Type::Applied { constructor, args } => {
+            let arg_strs: Vec<String> = args.iter().map(type_to_string).collect();
+            format!("'{}[{}]", constructor, arg_strs.join(", "))
+        }
+        Type::Forall { vars, body } => {
+            let var_strs: Vec<String> = vars.iter().map(|v| format!("'{v}")).collect();
+            format!("forall {}. {}", var_strs.join(" "), type_to_string(body))
+        }
     }
 }
 
@@ -28,6 +28,8 @@ pub mod optimized_ir;
 pub mod parser;
 pub mod pretty_print;
 pub mod recursion_detector;
+pub mod resugar;
+pub mod source_print;
 pub mod type_annotator;
 pub mod typed_ir;
 
@@ -231,6 +233,19 @@ pub enum Expr {
         exprs: Vec<Expr>,
         span: Span,
     },
+    /// `mut name = value`: a mutable variable for the rest of the enclosing
+    /// block, desugared to a local `State` effect
+    Mut {
+        name: Ident,
+        value: Box<Expr>,
+        span: Span,
+    },
+    /// `name = value`: assignment to a mutable variable
+    Assign {
+        name: Ident,
+        value: Box<Expr>,
+        span: Span,
+    },
     Hole {
         name: Option<String>,
         type_hint: Option<Type>,
@@ -254,6 +269,18 @@ pub enum Expr {
         updates: Vec<(Ident, Expr)>,
         span: Span,
     },
+    /// Add fields the record does not have: `{ name: "x" | record }`
+    RecordExtend {
+        record: Box<Expr>,
+        fields: Vec<(Ident, Expr)>,
+        span: Span,
+    },
+    /// Remove a field from a record: `{ record - name }`
+    RecordRestrict {
+        record: Box<Expr>,
+        field: Ident,
+        span: Span,
+    },
     // Hash reference for content-addressed code
     HashRef {
         hash: String, // Hex string of the hash
@@ -305,11 +332,15 @@ impl Expr {
             Expr::Perform { span, .. } => span,
             Expr::Pipeline { span, .. } => span,
             Expr::Block { span, .. } => span,
+            Expr::Mut { span, .. } => span,
+            Expr::Assign { span, .. } => span,
             Expr::Hole { span, .. } => span,
             Expr::Do { span, .. } => span,
             Expr::RecordLiteral { span, .. } => span,
             Expr::RecordAccess { span, .. } => span,
             Expr::RecordUpdate { span, .. } => span,
+            Expr::RecordExtend { span, .. } => span,
+            Expr::RecordRestrict { span, .. } => span,
             Expr::HashRef { span, .. } => span,
         }
     }
@@ -343,10 +374,27 @@ pub enum Type {
     Record {
         fields: Vec<(String, Type)>,
     },
+    /// Record with at least `fields`; the type variable `row` stands for
+    /// the rest (e.g., `{name: String | r}`)
+    ExtensibleRecord {
+        fields: Vec<(String, Type)>,
+        row: String,
+    },
     /// Option type (e.g., String? is sugar for Option String)
     Option(Box<Type>),
     /// Tuple type
     Tuple(Vec<Type>),
+    /// Higher-kinded type variable applied to arguments (e.g., `f a`)
+    Applied {
+        constructor: String,
+        args: Vec<Type>,
+    },
+    /// Explicitly quantified type (e.g., `forall a b. (a -> b) -> List a -> List b`).
+    /// Only allowed at the top of a type annotation.
+    Forall {
+        vars: Vec<String>,
+        body: Box<Type>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -361,6 +409,10 @@ pub struct TypeDefinition {
     pub name: String,
     pub type_params: Vec<String>,
     pub constructors: Vec<Constructor>,
+    /// The type this is another name for (`type Name = String`); aliases
+    /// have no constructors
+    #[serde(default)]
+    pub alias: Option<Type>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -439,6 +491,24 @@ impl fmt::Display for Type {
                 }
                 write!(f, "}}")
             }
+            Type::ExtensibleRecord { fields, row } => {
+                write!(f, "{{")?;
+                for (i, (name, ty)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, ty)?;
+                }
+                write!(f, " | {row}}}")
+            }
+            Type::Applied { constructor, args } => {
+                write!(f, "({constructor}")?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                write!(f, ")")
+            }
+            Type::Forall { vars, body } => write!(f, "forall {}. {body}", vars.join(" ")),
         }
     }
 }
@@ -515,6 +585,11 @@ impl Environment {
         self.bindings.is_empty()
     }
 
+    /// All bindings in the order they were added, shadowed ones included
+    pub fn bindings(&self) -> impl DoubleEndedIterator<Item = (&Ident, &Value)> {
+        self.bindings.iter().map(|(name, value)| (name, value))
+    }
+
     pub fn debug_bindings(&self) -> Vec<String> {
         self.bindings
             .iter()
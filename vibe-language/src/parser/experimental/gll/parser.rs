@@ -118,6 +118,8 @@ pub struct GLLParser {
     pub(crate) input: Vec<String>,
     /// Maximum iterations to prevent infinite loops
     max_iterations: usize,
+    /// Furthest input position reached by a successful terminal match
+    furthest_pos: usize,
 }
 
 impl GLLParser {
@@ -131,6 +133,7 @@ impl GLLParser {
             state: ParserState::default(),
             input: Vec::new(),
             max_iterations: 10000, // Default max iterations
+            furthest_pos: 0,
         }
     }
 
@@ -141,6 +144,7 @@ impl GLLParser {
         self.sppf.clear();
         self.worklist.clear();
         self.processed.clear();
+        self.furthest_pos = 0;
         
         // Track parsing start
         self.track_effect(ParseEffect::SemanticAction("gll_parse_start".to_string()));
@@ -247,6 +251,7 @@ impl GLLParser {
         
         if input_pos < self.input.len() && self.input[input_pos] == terminal {
             // Match successful
+            self.furthest_pos = self.furthest_pos.max(input_pos + 1);
             let new_sppf = self.sppf.create_terminal(terminal.to_string(), input_pos, input_pos + 1);
             
             let combined_sppf = if let Some(left) = sppf_node {
@@ -474,6 +479,11 @@ impl GLLParser {
     pub fn set_max_iterations(&mut self, max: usize) {
         self.max_iterations = max;
     }
+
+    /// Index of the first input token that no parse path could consume
+    pub fn furthest_position(&self) -> usize {
+        self.furthest_pos
+    }
     
     /// Parse with AI-friendly error reporting
     pub fn parse_with_errors(&mut self, input: Vec<String>) -> Result<Vec<usize>, crate::parser::experimental::error::ParseError> {
@@ -483,8 +493,8 @@ impl GLLParser {
                 // Convert simple error to structured error
                 use crate::parser::experimental::error_helpers::ErrorReporting;
                 
-                // Try to determine what went wrong
-                let position = if self.input.is_empty() { 0 } else { self.input.len() - 1 }; // Use actual input position
+                // The first token no parse path could consume is where things went wrong
+                let position = self.furthest_pos.min(self.input.len());
                 
                 if msg.contains("No valid parse found") {
                     // Determine expected tokens at failure point
@@ -1,7 +1,7 @@
 //! SPPF to AST Converter - Converts Shared Packed Parse Forest to Vibe AST
 
 use super::gll::sppf::{SharedPackedParseForest, SPPFNode, SPPFNodeType};
-use crate::{Expr, Ident, Literal, Pattern, Span, Type, HandlerCase};
+use crate::{Constructor, Expr, Ident, Literal, Pattern, Span, Type, TypeDefinition, HandlerCase};
 use crate::parser::lexer::Token;
 use ordered_float::OrderedFloat;
 
@@ -826,6 +826,11 @@ impl SPPFToASTConverter {
             }
         }
         
+        // `mut x = e` and `x = e` start like an application, so check them first
+        if let Some(statement) = self.convert_mutable_statement(node_id)? {
+            return Ok(statement);
+        }
+        
         // Check if this is a simple lambda at top level (fn x -> expr)
         if node.start < self.tokens.len() {
             if let Some(Token::Fn) = self.get_token_at_position(node.start) {
@@ -845,6 +850,26 @@ impl SPPFToASTConverter {
                 return self.parse_handle_from_tokens(node.start, node.end);
             }
             
+            // Check if this is a record or a block of statements
+            if let Some(Token::LeftBrace) = self.get_token_at_position(node.start) {
+                if self.matching_close(node.start, node.end) == Some(node.end - 1) {
+                    return self.parse_braces_from_tokens(node.start, node.end);
+                }
+            }
+            
+            // Check if this is a type definition or type alias
+            if let Some(Token::Type) = self.get_token_at_position(node.start) {
+                return self.parse_type_def_from_tokens(node.start, node.end);
+            }
+            
+            // Check if this is an export list or a library import
+            if let Some(Token::Export) = self.get_token_at_position(node.start) {
+                return self.parse_export_from_tokens(node.start, node.end);
+            }
+            if self.is_library_import(node.start) {
+                return self.parse_library_import_from_tokens(node.start, node.end);
+            }
+            
             // Check if this is a simple function application (f arg1 arg2 ...)
             if let Some(token) = self.get_token_at_position(node.start) {
                 println!("DEBUG: First token at pos {}: {:?}", node.start, token);
@@ -937,6 +962,40 @@ impl SPPFToASTConverter {
         Ok(Expr::Literal(Literal::Int(0), Span::new(node.start, node.end)))
     }
     
+    /// Convert a MutBinding or Assignment child of `node_id`, if it has one
+    fn convert_mutable_statement(&self, node_id: usize) -> Result<Option<Expr>, ConversionError> {
+        let node = self.get_node(node_id).ok_or(ConversionError::InvalidNode)?;
+        for &child_id in node.children.iter().flatten() {
+            let Some(child) = self.get_node(child_id) else { continue };
+            let SPPFNodeType::NonTerminal(kind) = &child.node_type else { continue };
+            // The statement starts where the TopLevelDef does
+            let name_pos = match kind.as_str() {
+                "MutBinding" => node.start + 1,
+                "Assignment" => node.start,
+                _ => continue,
+            };
+            let Some(Token::Symbol(name)) = self.get_token_at_position(name_pos) else { continue };
+            let value_id = child.children.iter().flatten().copied().find(|&id| {
+                self.get_node(id).is_some_and(|value| {
+                    matches!(&value.node_type, SPPFNodeType::NonTerminal(nt) if nt == "Expr")
+                })
+            });
+            let value = match value_id {
+                Some(value_id) => self.convert_node_to_expr(value_id)?,
+                None => self.parse_expression_from_tokens(name_pos + 2, node.end)?,
+            };
+            let name = Ident(name.clone());
+            let value = Box::new(value);
+            let span = Span::new(node.start, node.end);
+            return Ok(Some(if kind == "MutBinding" {
+                Expr::Mut { name, value, span }
+            } else {
+                Expr::Assign { name, value, span }
+            }));
+        }
+        Ok(None)
+    }
+    
     /// Convert a Program node
     fn convert_program(&self, node_id: usize) -> Result<Expr, ConversionError> {
         println!("DEBUG: convert_program called for node {}", node_id);
@@ -1059,7 +1118,7 @@ impl SPPFToASTConverter {
                     while i < self.tokens.len() {
                         match &self.tokens[i] {
                             // If we see another Let or a Symbol that could start a statement, break
-                            Token::Let => break,
+                            Token::Let | Token::Type | Token::Export | Token::Import => break,
                             Token::Symbol(s) if i > 0 => {
                                 // Check if this could be the start of a new statement
                                 // "print" after "let x = 42" would be a new statement
@@ -1078,6 +1137,14 @@ impl SPPFToASTConverter {
                         i += 1;
                     }
                 }
+                Token::Type => {
+                    count += 1;
+                    i += 1;
+                }
+                Token::Export | Token::Import if self.is_export_or_library_import(i) => {
+                    count += 1;
+                    i = self.closing_brace_after(i).map_or(self.tokens.len(), |close| close + 1);
+                }
                 Token::Symbol(s) => {
                     // This could be the start of a statement like "print x"
                     // Only count if it's at the beginning or after a complete expression
@@ -1132,7 +1199,7 @@ impl SPPFToASTConverter {
                             // Check if we've reached another statement
                             if let Some(tok) = self.tokens.get(end) {
                                 match tok {
-                                    Token::Let => break,
+                                    Token::Let | Token::Type | Token::Export | Token::Import => break,
                                     Token::Symbol(s) if self.looks_like_statement_start(end) => break,
                                     _ => end += 1,
                                 }
@@ -1151,6 +1218,31 @@ impl SPPFToASTConverter {
                             pos += 1;
                         }
                     }
+                    Token::Type => {
+                        // A type definition runs up to the next definition
+                        let end = (pos + 1..self.tokens.len())
+                            .find(|&i| matches!(self.tokens[i], Token::Let | Token::Type))
+                            .unwrap_or(self.tokens.len());
+                        if let Ok(def) = self.parse_type_def_from_tokens(pos, end) {
+                            definitions.push(def);
+                            pos = end;
+                        } else {
+                            pos += 1;
+                        }
+                    }
+                    Token::Export | Token::Import if self.is_export_or_library_import(pos) => {
+                        // The list of names ends the statement
+                        let end = self
+                            .closing_brace_after(pos)
+                            .map_or(self.tokens.len(), |close| close + 1);
+                        let def = if let Token::Export = token {
+                            self.parse_export_from_tokens(pos, end)?
+                        } else {
+                            self.parse_library_import_from_tokens(pos, end)?
+                        };
+                        definitions.push(def);
+                        pos = end;
+                    }
                     Token::Symbol(s) if self.looks_like_statement_start(pos) => {
                         // This starts a new statement like "print x"
                         let mut end = pos + 1;
@@ -1394,10 +1486,7 @@ impl SPPFToASTConverter {
     
     /// Get token at a specific position
     fn get_token_at_position(&self, pos: usize) -> Option<&Token> {
-        // // eprintln!("Getting token at position {}, tokens.len()={}", pos, self.tokens.len());
-        let token = self.tokens.get(pos);
-        // // eprintln!("Token at {}: {:?}", pos, token);
-        token
+        self.tokens.get(pos)
     }
     
     /// Parse let binding from tokens in the range
@@ -1482,6 +1571,10 @@ impl SPPFToASTConverter {
                                     // List expression
                                     self.parse_list_from_tokens(start + body_start, end)?
                                 }
+                                Token::LeftBrace => {
+                                    // Record or block
+                                    self.parse_braces_from_tokens(start + body_start, end)?
+                                }
                                 Token::Symbol(s) => {
                                     // Check for None/Some constructors
                                     if s == "None" {
@@ -1811,6 +1904,14 @@ impl SPPFToASTConverter {
                         args.push(list_expr);
                         pos = bracket_end + 1;
                     }
+                    Token::LeftBrace => {
+                        // Record or block
+                        let brace_end = self.matching_close(pos, end).ok_or_else(|| {
+                            ConversionError::UnexpectedToken("Unclosed `{` in application".to_string())
+                        })?;
+                        args.push(self.parse_braces_from_tokens(pos, brace_end + 1)?);
+                        pos = brace_end + 1;
+                    }
                     _ => {
                         println!("DEBUG: Unexpected token in application at {}: {:?}", pos, token);
                         return Err(ConversionError::UnexpectedToken(format!("Unexpected token in application: {:?}", token)));
@@ -2367,6 +2468,120 @@ impl SPPFToASTConverter {
         Err(ConversionError::UnexpectedToken("Failed to parse parenthesized expression".to_string()))
     }
     
+    /// Parse `{ ... }` as a record literal when it starts with a field, and
+    /// as a block otherwise
+    fn parse_braces_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        if self.is_record_start(start) {
+            self.parse_record_from_tokens(start, end)
+        } else if let Some(restrict) = self.parse_record_restrict_from_tokens(start, end) {
+            Ok(restrict)
+        } else {
+            self.parse_block_from_tokens(start, end)
+        }
+    }
+    
+    /// Parse `{ record - name }`, if the braces hold exactly that. A block
+    /// subtracting anything but a field name (`{ n - 1 }`) stays a block.
+    fn parse_record_restrict_from_tokens(&self, start: usize, end: usize) -> Option<Expr> {
+        let close = self.matching_close(start, end)?;
+        if close < start + 4 {
+            return None;
+        }
+        let field = match (&self.tokens[close - 2], &self.tokens[close - 1]) {
+            (Token::Symbol(minus), Token::Symbol(field))
+                if minus == "-" && field.starts_with(|c: char| c.is_lowercase() || c == '_') =>
+            {
+                Ident(field.clone())
+            }
+            _ => return None,
+        };
+        let record = match self.get_token_at_position(start + 1) {
+            Some(Token::Symbol(name)) if close == start + 4 => Expr::Ident(Ident(name.clone()), Span::new(start + 1, start + 2)),
+            Some(Token::LeftBrace) if self.matching_close(start + 1, close) == Some(close - 3) => {
+                self.parse_braces_from_tokens(start + 1, close - 2).ok()?
+            }
+            _ => return None,
+        };
+        Some(Expr::RecordRestrict {
+            record: Box::new(record),
+            field,
+            span: Span::new(start, close + 1),
+        })
+    }
+    
+    /// Whether the `{` at `pos` opens a record: `{}`, `{ name: ...` or `{ "name": ...`
+    fn is_record_start(&self, pos: usize) -> bool {
+        match (self.get_token_at_position(pos + 1), self.get_token_at_position(pos + 2)) {
+            (Some(Token::RightBrace), _) => true,
+            (Some(Token::Symbol(_) | Token::String(_)), Some(Token::Colon)) => true,
+            _ => false,
+        }
+    }
+    
+    /// Whether a record field (`name:` or `"name":`) starts at `pos`
+    fn is_field_start(&self, pos: usize) -> bool {
+        matches!(self.get_token_at_position(pos), Some(Token::Symbol(_) | Token::String(_)))
+            && matches!(self.get_token_at_position(pos + 1), Some(Token::Colon))
+    }
+    
+    /// Parse record literal from tokens { name: expr, "quoted-name": expr },
+    /// or an extension { name: expr | record }.
+    /// Fields are separated by commas, or need none when they are on
+    /// separate lines, and a trailing comma is allowed.
+    fn parse_record_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        let close = self
+            .matching_close(start, end)
+            .ok_or_else(|| ConversionError::UnexpectedToken("Unclosed record".to_string()))?;
+        
+        let mut fields = Vec::new();
+        let mut pos = start + 1;
+        while pos < close {
+            let name = match self.get_token_at_position(pos) {
+                Some(Token::Symbol(name) | Token::String(name)) => Ident(name.clone()),
+                token => return Err(ConversionError::UnexpectedToken(format!("Expected a field name, found {:?}", token))),
+            };
+            if !matches!(self.get_token_at_position(pos + 1), Some(Token::Colon)) {
+                return Err(ConversionError::UnexpectedToken(format!("Expected `:` after field `{}`", name.0)));
+            }
+            
+            // The value runs to the next comma or field outside nested brackets
+            let value_start = pos + 2;
+            let mut value_end = value_start;
+            let mut depth = 0;
+            while value_end < close {
+                match self.get_token_at_position(value_end) {
+                    Some(Token::LeftParen | Token::LeftBracket | Token::LeftBrace) => depth += 1,
+                    Some(Token::RightParen | Token::RightBracket | Token::RightBrace) => depth -= 1,
+                    Some(Token::Comma | Token::Pipe) if depth == 0 => break,
+                    _ if depth == 0 && value_end > value_start && self.is_field_start(value_end) => break,
+                    _ => {}
+                }
+                value_end += 1;
+            }
+            fields.push((name, self.parse_statement_value_from_tokens(value_start, value_end)?));
+            
+            pos = value_end;
+            match self.get_token_at_position(pos) {
+                Some(Token::Comma) => pos += 1,
+                // { name: "x" | record } extends `record`
+                Some(Token::Pipe) => {
+                    let record = self.parse_statement_value_from_tokens(pos + 1, close)?;
+                    return Ok(Expr::RecordExtend {
+                        record: Box::new(record),
+                        fields,
+                        span: Span::new(start, close + 1),
+                    });
+                }
+                _ => {}
+            }
+        }
+        
+        Ok(Expr::RecordLiteral {
+            fields,
+            span: Span::new(start, close + 1),
+        })
+    }
+    
     /// Parse block from tokens { expr; expr; ... }
     fn parse_block_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
         // eprintln!("parse_block_from_tokens: range {}-{}", start, end);
@@ -2411,22 +2626,24 @@ impl SPPFToASTConverter {
                     });
                 }
                 
-                // For now, parse single expression in block
-                // TODO: Parse multiple expressions separated by semicolons
+                // Statements are separated by semicolons outside nested brackets
                 let mut exprs = Vec::new();
-                
-                // Simple case: single literal or identifier
-                if inner_end == inner_start + 1 {
-                    if let Some(token) = self.get_token_at_position(inner_start) {
-                        let expr = match token {
-                            Token::Int(n) => Expr::Literal(Literal::Int(*n), Span::new(inner_start, inner_start + 1)),
-                            Token::Float(f) => Expr::Literal(Literal::Float(OrderedFloat(*f)), Span::new(inner_start, inner_start + 1)),
-                            Token::String(s) => Expr::Literal(Literal::String(s.clone()), Span::new(inner_start, inner_start + 1)),
-                            Token::Bool(b) => Expr::Literal(Literal::Bool(*b), Span::new(inner_start, inner_start + 1)),
-                            Token::Symbol(s) => Expr::Ident(Ident(s.clone()), Span::new(inner_start, inner_start + 1)),
-                            _ => return Err(ConversionError::UnexpectedToken(format!("Unexpected token in block: {:?}", token))),
-                        };
-                        exprs.push(expr);
+                let mut depth = 0;
+                let mut statement_start = inner_start;
+                for pos in inner_start..=inner_end {
+                    match self.get_token_at_position(pos) {
+                        Some(Token::LeftParen | Token::LeftBracket | Token::LeftBrace) => depth += 1,
+                        Some(Token::RightParen | Token::RightBracket) => depth -= 1,
+                        Some(Token::RightBrace) if pos < inner_end => depth -= 1,
+                        _ => {}
+                    }
+                    let ends_statement = pos == inner_end
+                        || (depth == 0 && matches!(self.get_token_at_position(pos), Some(Token::Semicolon)));
+                    if ends_statement {
+                        if pos > statement_start {
+                            exprs.push(self.parse_block_statement_from_tokens(statement_start, pos)?);
+                        }
+                        statement_start = pos + 1;
                     }
                 }
                 
@@ -2440,6 +2657,49 @@ impl SPPFToASTConverter {
         Err(ConversionError::UnexpectedToken("Failed to parse block expression".to_string()))
     }
     
+    /// Parse one statement of a block: `mut x = e`, `x = e`, a let or an expression
+    fn parse_block_statement_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        let span = Span::new(start, end);
+        match (self.get_token_at_position(start), self.get_token_at_position(start + 1)) {
+            (Some(Token::Symbol(keyword)), Some(Token::Symbol(name)))
+                if keyword == "mut" && matches!(self.get_token_at_position(start + 2), Some(Token::Equals)) =>
+            {
+                Ok(Expr::Mut {
+                    name: Ident(name.clone()),
+                    value: Box::new(self.parse_statement_value_from_tokens(start + 3, end)?),
+                    span,
+                })
+            }
+            (Some(Token::Symbol(name)), Some(Token::Equals)) => Ok(Expr::Assign {
+                name: Ident(name.clone()),
+                value: Box::new(self.parse_statement_value_from_tokens(start + 2, end)?),
+                span,
+            }),
+            (Some(Token::Let), _) => self.parse_let_binding_from_tokens(start, end),
+            _ => self.parse_statement_value_from_tokens(start, end),
+        }
+    }
+    
+    /// Parse the expression of a block statement or record field
+    fn parse_statement_value_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        if start >= end {
+            return Err(ConversionError::UnexpectedToken("Expected a value".to_string()));
+        }
+        match self.get_token_at_position(start) {
+            Some(Token::LeftBrace) if self.matching_close(start, end) == Some(end - 1) => {
+                return self.parse_braces_from_tokens(start, end);
+            }
+            Some(Token::LeftBracket) if self.matching_close(start, end) == Some(end - 1) => {
+                return self.parse_list_from_tokens(start, end);
+            }
+            _ => {}
+        }
+        if let Ok(expr) = self.parse_simple_expr_from_token_range(start, end) {
+            return Ok(expr);
+        }
+        self.parse_expression_from_tokens(start, end)
+    }
+    
     /// Parse if expression from tokens: if cond { expr } else { expr }
     fn parse_if_expr_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
         // eprintln!("parse_if_expr_from_tokens: range {}-{}", start, end);
@@ -3329,80 +3589,299 @@ impl SPPFToASTConverter {
     
     /// Parse type from tokens in the range
     fn parse_type_from_tokens(&self, start: usize, end: usize) -> Option<Type> {
-        // Collect tokens in the range
-        let mut tokens = Vec::new();
-        for i in start..end.min(self.tokens.len()) {
-            tokens.push(&self.tokens[i]);
-        }
-        
-        if tokens.is_empty() {
+        let end = end.min(self.tokens.len());
+        if start >= end {
             return None;
         }
+        let tokens = &self.tokens[start..end];
         
-        // Handle list types first: [Type]
-        if let Token::LeftBracket = tokens[0] {
-            if tokens.len() >= 3 {
-                if let Token::RightBracket = tokens[tokens.len() - 1] {
-                    // Parse inner type
-                    let inner_type = self.parse_type_from_tokens(start + 1, start + tokens.len() - 1);
-                    return inner_type.map(|t| Type::List(Box::new(t)));
-                }
+        // Explicit quantification: forall a b. Type
+        if let Token::Forall = tokens[0] {
+            let dot = tokens.iter().position(|t| matches!(t, Token::Dot))?;
+            let vars = tokens[1..dot]
+                .iter()
+                .map(|t| match t {
+                    Token::Symbol(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            if vars.is_empty() {
+                return None;
             }
-            return None;
+            let body = self.parse_type_from_tokens(start + dot + 1, end)?;
+            return Some(Type::Forall { vars, body: Box::new(body) });
         }
         
-        // Look for function arrow to handle function types
-        let mut arrow_pos = None;
+        // Function type: split at the first arrow outside brackets
+        let mut depth: i32 = 0;
         for (i, token) in tokens.iter().enumerate() {
-            if let Token::Arrow = token {
-                arrow_pos = Some(i);
-                break;
-            }
-        }
-        
-        if let Some(arrow_idx) = arrow_pos {
-            // Function type: Type -> Type
-            if arrow_idx > 0 && arrow_idx + 1 < tokens.len() {
-                let left_type = self.parse_type_from_tokens(start, start + arrow_idx);
-                let right_type = self.parse_type_from_tokens(start + arrow_idx + 1, end);
-                
-                if let (Some(from), Some(to)) = (left_type, right_type) {
+            match token {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+                Token::Arrow if depth == 0 => {
+                    let from = self.parse_type_from_tokens(start, start + i)?;
+                    let to = self.parse_type_from_tokens(start + i + 1, end)?;
                     return Some(Type::Function(Box::new(from), Box::new(to)));
                 }
+                _ => {}
             }
-            return None;
         }
         
-        // Handle simple types
-        match tokens[0] {
+        // Type application by juxtaposition: List a, f a, Result e a
+        let mut atoms = Vec::new();
+        let mut pos = start;
+        while pos < end {
+            let (atom, next) = self.parse_type_atom(pos, end)?;
+            atoms.push(atom);
+            pos = next;
+        }
+        let head = atoms.remove(0);
+        if atoms.is_empty() {
+            return Some(head);
+        }
+        Type::apply_constructor(head, atoms)
+    }
+    
+    /// Parse a type that needs no parentheses to be an argument, returning
+    /// it with the position after it
+    fn parse_type_atom(&self, start: usize, end: usize) -> Option<(Type, usize)> {
+        let (atom, next) = match &self.tokens[start] {
+            open @ (Token::LeftBracket | Token::LeftParen) => {
+                let close = self.matching_close(start, end)?;
+                let inner = self.parse_type_from_tokens(start + 1, close)?;
+                let atom = if let Token::LeftBracket = open {
+                    Type::List(Box::new(inner))
+                } else {
+                    inner
+                };
+                (atom, close + 1)
+            }
+            Token::LeftBrace => {
+                let close = self.matching_close(start, end)?;
+                (self.parse_record_type(start + 1, close)?, close + 1)
+            }
             Token::Symbol(name) => {
-                let base_type = match name.as_str() {
-                    "Int" => Some(Type::Int),
-                    "Float" => Some(Type::Float),
-                    "Bool" => Some(Type::Bool),
-                    "String" => Some(Type::String),
-                    _ => {
-                        // Simple user-defined type
-                        Some(Type::UserDefined {
-                            name: name.clone(),
-                            type_params: vec![],
-                        })
-                    }
+                let atom = match name.as_str() {
+                    "Int" => Type::Int,
+                    "Float" => Type::Float,
+                    "Bool" => Type::Bool,
+                    "String" => Type::String,
+                    // Lowercase names are type variables
+                    _ if name.starts_with(|c: char| c.is_lowercase()) => Type::Var(name.clone()),
+                    _ => Type::UserDefined {
+                        name: name.clone(),
+                        type_params: vec![],
+                    },
                 };
-                
-                // Check for optional type suffix (?)
-                if tokens.len() > 1 {
-                    if let Token::QuestionMark = tokens[1] {
-                        // T? is sugar for Option T
-                        return base_type.map(|t| Type::Option(Box::new(t)));
-                    }
+                (atom, start + 1)
+            }
+            _ => return None,
+        };
+        
+        // T? is sugar for Option T
+        if next < end {
+            if let Token::QuestionMark = self.tokens[next] {
+                return Some((Type::Option(Box::new(atom)), next + 1));
+            }
+        }
+        Some((atom, next))
+    }
+    
+    /// Parse the fields of a record type between `start` and `end`: a
+    /// closed `{name: String}` or an open `{name: String | r}`
+    fn parse_record_type(&self, start: usize, end: usize) -> Option<Type> {
+        let mut fields = Vec::new();
+        let mut pos = start;
+        while pos < end {
+            let Token::Symbol(name) = &self.tokens[pos] else {
+                return None;
+            };
+            if !matches!(self.tokens.get(pos + 1), Some(Token::Colon)) {
+                return None;
+            }
+            // The field type runs to the next `,` or `|` outside brackets
+            let mut depth = 0;
+            let mut field_end = pos + 2;
+            while field_end < end {
+                match self.tokens[field_end] {
+                    Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                    Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+                    Token::Comma | Token::Pipe if depth == 0 => break,
+                    _ => {}
                 }
-                
-                base_type
+                field_end += 1;
+            }
+            fields.push((name.clone(), self.parse_type_from_tokens(pos + 2, field_end)?));
+            pos = field_end;
+            match self.tokens.get(pos) {
+                Some(Token::Comma) if pos < end => pos += 1,
+                _ => break,
+            }
+        }
+        if pos == end {
+            return Some(Type::Record { fields });
+        }
+        match (&self.tokens[pos], self.tokens.get(pos + 1)) {
+            (Token::Pipe, Some(Token::Symbol(row))) if pos + 2 == end && row.starts_with(char::is_lowercase) => {
+                Some(Type::ExtensibleRecord { fields, row: row.clone() })
             }
             _ => None,
         }
     }
+    
+    /// Position of the bracket closing the one at `open`
+    fn matching_close(&self, open: usize, end: usize) -> Option<usize> {
+        let mut depth = 0;
+        for i in open..end {
+            match self.tokens[i] {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+                Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+    
+    /// Whether `import` at `pos` names a slash-separated library path
+    /// (`import lib/String { ... }`) rather than a module (`import Math`)
+    fn is_library_import(&self, pos: usize) -> bool {
+        matches!(self.get_token_at_position(pos), Some(Token::Import))
+            && matches!(
+                self.get_token_at_position(pos + 1),
+                Some(Token::Symbol(name)) if name.starts_with(|c: char| c.is_lowercase())
+            )
+    }
+    
+    fn is_export_or_library_import(&self, pos: usize) -> bool {
+        matches!(self.get_token_at_position(pos), Some(Token::Export)) || self.is_library_import(pos)
+    }
+    
+    /// Position of the first `}` after `start`
+    fn closing_brace_after(&self, start: usize) -> Option<usize> {
+        (start..self.tokens.len()).find(|&i| matches!(self.tokens[i], Token::RightBrace))
+    }
+    
+    /// Parse `{ a, b }` at `start`, returning the names (`None` for `{ .. }`)
+    /// and the position after the closing brace
+    fn parse_name_list_from_tokens(&self, start: usize, end: usize) -> Result<(Option<Vec<Ident>>, usize), ConversionError> {
+        if !matches!(self.get_token_at_position(start), Some(Token::LeftBrace)) {
+            return Err(ConversionError::UnexpectedToken("expected `{` before the list of names".to_string()));
+        }
+        let mut names = Vec::new();
+        let mut all = false;
+        let mut pos = start + 1;
+        while pos < end {
+            match &self.tokens[pos] {
+                Token::RightBrace => return Ok(((!all).then_some(names), pos + 1)),
+                Token::Symbol(name) => names.push(Ident(name.clone())),
+                Token::Ellipsis => all = true,
+                Token::Comma => {}
+                token => return Err(ConversionError::UnexpectedToken(format!("{token:?} in a list of names"))),
+            }
+            pos += 1;
+        }
+        Err(ConversionError::UnexpectedToken("unclosed list of names".to_string()))
+    }
+    
+    /// Parse `export { a, b }`. The export list of a file becomes a `Module`
+    /// without a name or body: the rest of the file is its body.
+    fn parse_export_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        let (exports, next) = self.parse_name_list_from_tokens(start + 1, end.min(self.tokens.len()))?;
+        Ok(Expr::Module {
+            name: Ident(String::new()),
+            exports: exports.unwrap_or_default(),
+            body: vec![],
+            span: Span::new(start, next),
+        })
+    }
+    
+    /// Parse `import lib/String { a, b }`, which brings library functions
+    /// into scope like `use`
+    fn parse_library_import_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        let end = end.min(self.tokens.len());
+        let mut path = Vec::new();
+        let mut pos = start + 1;
+        while let Some(Token::Symbol(segment)) = self.get_token_at_position(pos) {
+            path.push(segment.clone());
+            pos += 1;
+            match self.get_token_at_position(pos) {
+                Some(Token::Symbol(slash)) if slash == "/" => pos += 1,
+                _ => break,
+            }
+        }
+        let (items, next) = self.parse_name_list_from_tokens(pos, end)?;
+        Ok(Expr::Use {
+            path,
+            items,
+            span: Span::new(start, next),
+        })
+    }
+    
+    /// Parse `type Name params = | Ctor T ... | ...` or a type alias,
+    /// `type Name params = Type`
+    fn parse_type_def_from_tokens(&self, start: usize, end: usize) -> Result<Expr, ConversionError> {
+        let end = end.min(self.tokens.len());
+        let Some(Token::Symbol(name)) = self.get_token_at_position(start + 1) else {
+            return Err(ConversionError::UnexpectedToken("type name".to_string()));
+        };
+        let eq = (start + 2..end)
+            .find(|&i| matches!(self.tokens[i], Token::Equals))
+            .ok_or(ConversionError::MissingChildren)?;
+        let type_params = self.tokens[start + 2..eq]
+            .iter()
+            .map(|t| match t {
+                Token::Symbol(param) => Ok(param.clone()),
+                other => Err(ConversionError::UnexpectedToken(format!("{:?}", other))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let mut definition = TypeDefinition {
+            name: name.clone(),
+            type_params,
+            constructors: vec![],
+            alias: None,
+        };
+        if let Some(Token::Pipe) = self.get_token_at_position(eq + 1) {
+            // Each constructor runs up to the next `|`
+            let mut bars: Vec<usize> = (eq + 1..end)
+                .filter(|&i| matches!(self.tokens[i], Token::Pipe))
+                .collect();
+            bars.push(end);
+            for pair in bars.windows(2) {
+                let Some(Token::Symbol(constructor)) = self.get_token_at_position(pair[0] + 1) else {
+                    return Err(ConversionError::UnexpectedToken("constructor name".to_string()));
+                };
+                let mut fields = Vec::new();
+                let mut pos = pair[0] + 2;
+                while pos < pair[1] {
+                    let (field, next) = self
+                        .parse_type_atom(pos, pair[1])
+                        .ok_or(ConversionError::TypeMismatch)?;
+                    fields.push(field);
+                    pos = next;
+                }
+                definition.constructors.push(Constructor {
+                    name: constructor.clone(),
+                    fields,
+                });
+            }
+        } else {
+            let target = self
+                .parse_type_from_tokens(eq + 1, end)
+                .ok_or(ConversionError::TypeMismatch)?;
+            definition.alias = Some(target);
+        }
+        
+        Ok(Expr::TypeDef {
+            definition,
+            span: Span::new(start, end),
+        })
+    }
 }
 
 /// Conversion error types
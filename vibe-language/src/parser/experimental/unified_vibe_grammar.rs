@@ -19,7 +19,7 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         rhs: vec![GLLSymbol::NonTerminal("TopLevelDef".to_string())],
     });
     
-    // TopLevelDef -> LetBinding | TypeDef | ModuleDef | ImportDef | TypeClassDef | InstanceDef | EffectDef | Expr
+    // TopLevelDef -> LetBinding | TypeDef | ModuleDef | ExportDef | ImportDef | TypeClassDef | InstanceDef | EffectDef | MutBinding | Assignment | Expr
     rules.push(GLLRule {
         lhs: "TopLevelDef".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("LetBinding".to_string())],
@@ -32,6 +32,10 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         lhs: "TopLevelDef".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("ModuleDef".to_string())],
     });
+    rules.push(GLLRule {
+        lhs: "TopLevelDef".to_string(),
+        rhs: vec![GLLSymbol::NonTerminal("ExportDef".to_string())],
+    });
     rules.push(GLLRule {
         lhs: "TopLevelDef".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("ImportDef".to_string())],
@@ -48,6 +52,14 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         lhs: "TopLevelDef".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("EffectDef".to_string())],
     });
+    rules.push(GLLRule {
+        lhs: "TopLevelDef".to_string(),
+        rhs: vec![GLLSymbol::NonTerminal("MutBinding".to_string())],
+    });
+    rules.push(GLLRule {
+        lhs: "TopLevelDef".to_string(),
+        rhs: vec![GLLSymbol::NonTerminal("Assignment".to_string())],
+    });
     rules.push(GLLRule {
         lhs: "TopLevelDef".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("Expr".to_string())],
@@ -128,7 +140,7 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         ],
     });
     
-    // TypeAnnotation -> : Type | ε
+    // TypeAnnotation -> : Type | : forall identifier TypeParams . Type | ε
     rules.push(GLLRule {
         lhs: "TypeAnnotation".to_string(),
         rhs: vec![
@@ -136,6 +148,18 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
             GLLSymbol::NonTerminal("Type".to_string()),
         ],
     });
+    // Quantifiers only appear at the top of an annotation (rank-1)
+    rules.push(GLLRule {
+        lhs: "TypeAnnotation".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal(":".to_string()),
+            GLLSymbol::Terminal("forall".to_string()),
+            GLLSymbol::Terminal("identifier".to_string()),
+            GLLSymbol::NonTerminal("TypeParams".to_string()),
+            GLLSymbol::Terminal(".".to_string()),
+            GLLSymbol::NonTerminal("Type".to_string()),
+        ],
+    });
     rules.push(GLLRule {
         lhs: "TypeAnnotation".to_string(),
         rhs: vec![GLLSymbol::Epsilon],
@@ -506,7 +530,7 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         rhs: vec![GLLSymbol::NonTerminal("PrimaryExpr".to_string())],
     });
     
-    // PrimaryExpr -> Literal | identifier | Constructor | ( Expr ) | ( Operator ) | [ ListElements ] | { RecordFields } | Block | perform PostfixExpr | handle Block Handlers
+    // PrimaryExpr -> Literal | identifier | Constructor | ( Expr ) | ( Operator ) | [ ListElements ] | { RecordFields } | { RecordFields | Expr } | Block | perform PostfixExpr | handle Block Handlers
     rules.push(GLLRule {
         lhs: "PrimaryExpr".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("Literal".to_string())],
@@ -569,6 +593,17 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
             GLLSymbol::Terminal("}".to_string()),
         ],
     });
+    // Record extension: { name: "x" | record }
+    rules.push(GLLRule {
+        lhs: "PrimaryExpr".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("{".to_string()),
+            GLLSymbol::NonTerminal("RecordFields".to_string()),
+            GLLSymbol::Terminal("|".to_string()),
+            GLLSymbol::NonTerminal("Expr".to_string()),
+            GLLSymbol::Terminal("}".to_string()),
+        ],
+    });
     rules.push(GLLRule {
         lhs: "PrimaryExpr".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("Block".to_string())],
@@ -584,11 +619,28 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         ],
     });
     
-    // BlockStatements -> Statement BlockStatements | Expr | Statement
+    // BlockStatements -> Statement BlockStatements | Statement ; BlockStatements
+    //                  | Expr ; BlockStatements | Expr | Statement
+    rules.push(GLLRule {
+        lhs: "BlockStatements".to_string(),
+        rhs: vec![
+            GLLSymbol::NonTerminal("Statement".to_string()),
+            GLLSymbol::NonTerminal("BlockStatements".to_string()),
+        ],
+    });
     rules.push(GLLRule {
         lhs: "BlockStatements".to_string(),
         rhs: vec![
             GLLSymbol::NonTerminal("Statement".to_string()),
+            GLLSymbol::Terminal(";".to_string()),
+            GLLSymbol::NonTerminal("BlockStatements".to_string()),
+        ],
+    });
+    rules.push(GLLRule {
+        lhs: "BlockStatements".to_string(),
+        rhs: vec![
+            GLLSymbol::NonTerminal("Expr".to_string()),
+            GLLSymbol::Terminal(";".to_string()),
             GLLSymbol::NonTerminal("BlockStatements".to_string()),
         ],
     });
@@ -602,11 +654,19 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         rhs: vec![GLLSymbol::NonTerminal("Statement".to_string())],
     });
     
-    // Statement -> LetBinding | Pattern <- Expr
+    // Statement -> LetBinding | MutBinding | Assignment | Pattern <- Expr
     rules.push(GLLRule {
         lhs: "Statement".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("LetBinding".to_string())],
     });
+    rules.push(GLLRule {
+        lhs: "Statement".to_string(),
+        rhs: vec![GLLSymbol::NonTerminal("MutBinding".to_string())],
+    });
+    rules.push(GLLRule {
+        lhs: "Statement".to_string(),
+        rhs: vec![GLLSymbol::NonTerminal("Assignment".to_string())],
+    });
     rules.push(GLLRule {
         lhs: "Statement".to_string(),
         rhs: vec![
@@ -616,6 +676,27 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         ],
     });
     
+    // MutBinding -> mut identifier = Expr
+    rules.push(GLLRule {
+        lhs: "MutBinding".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("mut".to_string()),
+            GLLSymbol::Terminal("identifier".to_string()),
+            GLLSymbol::Terminal("=".to_string()),
+            GLLSymbol::NonTerminal("Expr".to_string()),
+        ],
+    });
+    
+    // Assignment -> identifier = Expr
+    rules.push(GLLRule {
+        lhs: "Assignment".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("identifier".to_string()),
+            GLLSymbol::Terminal("=".to_string()),
+            GLLSymbol::NonTerminal("Expr".to_string()),
+        ],
+    });
+    
     // ========== Patterns ==========
     
     // Pattern -> identifier | Constructor Patterns | Literal | _ | [ ListPattern ] | ( Pattern ) | Pattern :: Pattern
@@ -698,11 +779,24 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         ],
     });
     
-    // TypeAtom -> type_identifier | type_identifier TypeArgs | ( Type ) | { RecordType }
+    // TypeAtom -> type_identifier | type_identifier TypeArgs | identifier | identifier TypeArgs
+    //           | ( Type ) | { RecordType } | { RecordType | identifier }
     rules.push(GLLRule {
         lhs: "TypeAtom".to_string(),
         rhs: vec![GLLSymbol::Terminal("type_identifier".to_string())],
     });
+    // Type variables, applied to arguments when they are higher-kinded (f a)
+    rules.push(GLLRule {
+        lhs: "TypeAtom".to_string(),
+        rhs: vec![GLLSymbol::Terminal("identifier".to_string())],
+    });
+    rules.push(GLLRule {
+        lhs: "TypeAtom".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("identifier".to_string()),
+            GLLSymbol::NonTerminal("TypeArgs".to_string()),
+        ],
+    });
     rules.push(GLLRule {
         lhs: "TypeAtom".to_string(),
         rhs: vec![
@@ -726,6 +820,17 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
             GLLSymbol::Terminal("}".to_string()),
         ],
     });
+    // Open record: { name: String | r }
+    rules.push(GLLRule {
+        lhs: "TypeAtom".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("{".to_string()),
+            GLLSymbol::NonTerminal("RecordType".to_string()),
+            GLLSymbol::Terminal("|".to_string()),
+            GLLSymbol::Terminal("identifier".to_string()),
+            GLLSymbol::Terminal("}".to_string()),
+        ],
+    });
     
     // TypeDef -> type type_identifier TypeParams = TypeBody
     rules.push(GLLRule {
@@ -795,6 +900,68 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         ],
     });
     
+    // ExportDef -> export { ImportList }
+    // (the lexer's `export` token is the `exposing` terminal)
+    rules.push(GLLRule {
+        lhs: "ExportDef".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("exposing".to_string()),
+            GLLSymbol::Terminal("{".to_string()),
+            GLLSymbol::NonTerminal("ImportList".to_string()),
+            GLLSymbol::Terminal("}".to_string()),
+        ],
+    });
+    
+    // ImportDef -> import LibraryPath { ImportList }
+    rules.push(GLLRule {
+        lhs: "ImportDef".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("import".to_string()),
+            GLLSymbol::NonTerminal("LibraryPath".to_string()),
+            GLLSymbol::Terminal("{".to_string()),
+            GLLSymbol::NonTerminal("ImportList".to_string()),
+            GLLSymbol::Terminal("}".to_string()),
+        ],
+    });
+    
+    // LibraryPath -> identifier / LibraryPathTail | identifier
+    rules.push(GLLRule {
+        lhs: "LibraryPath".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("identifier".to_string()),
+            GLLSymbol::Terminal("/".to_string()),
+            GLLSymbol::NonTerminal("LibraryPathTail".to_string()),
+        ],
+    });
+    rules.push(GLLRule {
+        lhs: "LibraryPath".to_string(),
+        rhs: vec![GLLSymbol::Terminal("identifier".to_string())],
+    });
+    
+    // LibraryPathTail -> PathSegment / LibraryPathTail | PathSegment
+    rules.push(GLLRule {
+        lhs: "LibraryPathTail".to_string(),
+        rhs: vec![
+            GLLSymbol::NonTerminal("PathSegment".to_string()),
+            GLLSymbol::Terminal("/".to_string()),
+            GLLSymbol::NonTerminal("LibraryPathTail".to_string()),
+        ],
+    });
+    rules.push(GLLRule {
+        lhs: "LibraryPathTail".to_string(),
+        rhs: vec![GLLSymbol::NonTerminal("PathSegment".to_string())],
+    });
+    
+    // PathSegment -> identifier | type_identifier
+    rules.push(GLLRule {
+        lhs: "PathSegment".to_string(),
+        rhs: vec![GLLSymbol::Terminal("identifier".to_string())],
+    });
+    rules.push(GLLRule {
+        lhs: "PathSegment".to_string(),
+        rhs: vec![GLLSymbol::Terminal("type_identifier".to_string())],
+    });
+    
     // ImportTail -> as identifier | ( ImportList ) | exposing ( ImportList ) | ε
     rules.push(GLLRule {
         lhs: "ImportTail".to_string(),
@@ -973,7 +1140,7 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         rhs: vec![GLLSymbol::Epsilon],
     });
     
-    // RecordFields -> RecordField , RecordFields | RecordField | ε
+    // RecordFields -> RecordField , RecordFields | RecordField RecordFields | RecordField | ε
     rules.push(GLLRule {
         lhs: "RecordFields".to_string(),
         rhs: vec![
@@ -982,6 +1149,14 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
             GLLSymbol::NonTerminal("RecordFields".to_string()),
         ],
     });
+    // Fields on separate lines need no comma
+    rules.push(GLLRule {
+        lhs: "RecordFields".to_string(),
+        rhs: vec![
+            GLLSymbol::NonTerminal("RecordField".to_string()),
+            GLLSymbol::NonTerminal("RecordFields".to_string()),
+        ],
+    });
     rules.push(GLLRule {
         lhs: "RecordFields".to_string(),
         rhs: vec![GLLSymbol::NonTerminal("RecordField".to_string())],
@@ -1038,7 +1213,7 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
         rhs: vec![GLLSymbol::Epsilon],
     });
     
-    // RecordField -> identifier : Expr
+    // RecordField -> identifier : Expr | string : Expr
     rules.push(GLLRule {
         lhs: "RecordField".to_string(),
         rhs: vec![
@@ -1047,6 +1222,15 @@ pub fn create_unified_vibe_grammar() -> GLLGrammar {
             GLLSymbol::NonTerminal("Expr".to_string()),
         ],
     });
+    // Names that are not identifiers are quoted: { "json-lite": "^0.3" }
+    rules.push(GLLRule {
+        lhs: "RecordField".to_string(),
+        rhs: vec![
+            GLLSymbol::Terminal("string".to_string()),
+            GLLSymbol::Terminal(":".to_string()),
+            GLLSymbol::NonTerminal("Expr".to_string()),
+        ],
+    });
     
     // RecordType -> RecordTypeField , RecordType | RecordTypeField | ε
     rules.push(GLLRule {
@@ -27,7 +27,14 @@ impl UnifiedVibeParser {
     pub fn parse(&mut self, source: &str) -> Result<Vec<Expr>, ParseError> {
         // Tokenize the input
         let tokens = self.tokenize(source)?;
-        
+        self.parse_tokens(tokens)
+    }
+    
+    /// Parse an already tokenized input (newlines and comments removed).
+    ///
+    /// Spans in the resulting AST are token indices into `tokens`, and the
+    /// offset of a returned error is the index of the offending token.
+    pub fn parse_tokens(&mut self, tokens: Vec<Token>) -> Result<Vec<Expr>, ParseError> {
         // Store tokens for later use
         self.last_tokens = tokens.clone();
         
@@ -37,7 +44,7 @@ impl UnifiedVibeParser {
             .collect();
         
         #[cfg(test)]
-        if source.contains('[') {
+        if token_strings.iter().any(|t| t == "[") {
             println!("DEBUG: GLL parser input strings: {:?}", token_strings);
         }
         
@@ -126,6 +133,7 @@ impl UnifiedVibeParser {
             Token::Symbol(ref s) if s == "class" => "class".to_string(),
             Token::Symbol(ref s) if s == "instance" => "instance".to_string(),
             Token::Symbol(ref s) if s == "mod" => "mod".to_string(),
+            Token::Symbol(ref s) if s == "mut" => "mut".to_string(),
             
             // Operators
             Token::Equals => "=".to_string(),
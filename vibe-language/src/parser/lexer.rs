@@ -105,6 +105,11 @@ impl<'a> Lexer<'a> {
         lexer
     }
 
+    /// Skip the character at the current position so lexing can resume after an error
+    pub fn skip_char(&mut self) {
+        self.advance();
+    }
+
     pub fn next_token(&mut self) -> Result<Option<(Token, Span)>, XsError> {
         self.skip_whitespace_except_newline();
 
@@ -14,34 +14,30 @@
 pub mod ast_bridge;
 pub mod lexer;
 pub mod experimental;
+pub mod recovery;
 
 pub use lexer::Lexer;
 pub use experimental::unified_vibe_parser::UnifiedVibeParser;
+pub use recovery::{parse_with_recovery, RecoveredParse};
 
 // Re-export for backward compatibility
 pub use UnifiedVibeParser as Parser;
 
 // Convenience function for parsing
+//
+// Fails with the first syntax error; use `parse_with_recovery` to get a
+// partial AST together with every diagnostic in the file.
 pub fn parse(input: &str) -> Result<crate::Expr, crate::XsError> {
-    let mut parser = UnifiedVibeParser::new();
-    let exprs = parser.parse(input)
-        .map_err(|e| crate::XsError::ParseError(0, e.to_string()))?;
-    
-    // Return the first expression or create a sequence if multiple
-    match exprs.len() {
-        0 => Err(crate::XsError::ParseError(
-            0,
-            "No expressions found".to_string()
-        )),
-        1 => Ok(exprs.into_iter().next().unwrap()),
-        _ => {
-            // Create a Block expression for multiple expressions
-            Ok(crate::Expr::Block {
-                exprs,
-                span: crate::Span::new(0, 0)
-            })
-        }
+    let parsed = parse_with_recovery(input);
+
+    if let Some(error) = parsed.errors.first() {
+        return Err(recovery::to_xs_error(error));
     }
+
+    // Return the single expression, or a Block spanning all of them
+    parsed.into_expr().ok_or_else(|| {
+        crate::XsError::ParseError(0, "No expressions found".to_string())
+    })
 }
 
 #[cfg(test)]
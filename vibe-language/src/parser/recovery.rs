@@ -0,0 +1,742 @@
+//! Error-recovering parse mode
+//!
+//! The GLL parser either accepts a whole token stream or rejects it. To report
+//! every syntax error in a file, the recovering mode first tries the whole file
+//! and, if that fails, re-parses it one top-level statement at a time. Each
+//! broken statement becomes an error hole in the resulting AST and contributes
+//! its own diagnostic, while the statements around it are still parsed.
+//!
+//! Statement boundaries follow a simple layout rule: a token in the first
+//! column starts a new statement unless the previous token expects a
+//! continuation (`=`, `in`, `->`, operators, ...). Definition keywords (`let`,
+//! `type`, `module`, ...) in the first column start a new statement even
+//! inside brackets or after a dangling operator, which is how unclosed
+//! brackets and truncated expressions are detected and reported.
+//!
+//! All spans produced here are character offsets into the source, the same
+//! unit the lexer uses.
+
+use super::experimental::error::{ErrorContext, ErrorLocation, ParseError};
+use super::experimental::unified_vibe_parser::UnifiedVibeParser;
+use super::lexer::{Lexer, Token};
+use crate::{DoStatement, Expr, HandlerCase, Pattern, Span, XsError};
+
+/// Hole name used for AST nodes that replace statements which failed to parse
+pub const ERROR_HOLE_NAME: &str = "<error>";
+
+/// Create an error node covering `span`
+pub fn error_node(span: Span) -> Expr {
+    Expr::Hole {
+        name: Some(ERROR_HOLE_NAME.to_string()),
+        type_hint: None,
+        span,
+    }
+}
+
+/// Check whether an expression is an error node produced by recovery
+pub fn is_error_node(expr: &Expr) -> bool {
+    matches!(expr, Expr::Hole { name: Some(name), .. } if name == ERROR_HOLE_NAME)
+}
+
+/// Result of a recovering parse: a (possibly partial) AST plus all diagnostics
+#[derive(Debug, Clone)]
+pub struct RecoveredParse {
+    /// Top-level expressions; statements that failed to parse are error nodes
+    pub exprs: Vec<Expr>,
+    /// Syntax errors in source order, with real offsets, lines and columns
+    pub errors: Vec<ParseError>,
+}
+
+impl RecoveredParse {
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Span covering all top-level expressions
+    pub fn span(&self) -> Span {
+        match (self.exprs.first(), self.exprs.last()) {
+            (Some(first), Some(last)) => Span::new(first.span().start, last.span().end),
+            _ => Span::new(0, 0),
+        }
+    }
+
+    /// Collapse the top-level expressions into a single expression.
+    ///
+    /// A single statement is returned as-is, several statements become a
+    /// `Block` whose span covers all of them.
+    pub fn into_expr(self) -> Option<Expr> {
+        let span = self.span();
+        let mut exprs = self.exprs;
+        match exprs.len() {
+            0 => None,
+            1 => exprs.pop(),
+            _ => Some(Expr::Block { exprs, span }),
+        }
+    }
+}
+
+/// Convert a structured parse error into the `XsError` used by the rest of the pipeline
+pub fn to_xs_error(error: &ParseError) -> XsError {
+    XsError::ParseError(error.location.offset, error.to_string())
+}
+
+/// Parse `source`, recovering from syntax errors at statement and bracket boundaries
+pub fn parse_with_recovery(source: &str) -> RecoveredParse {
+    let lines = LineIndex::new(source);
+    let mut errors = Vec::new();
+    let tokens = lex(&lines, &mut errors);
+
+    let mut parser = UnifiedVibeParser::new();
+
+    // Assignments to `mut` variables are only separated by layout, which the
+    // whole-file parse ignores, so such files are parsed a statement at a time
+    let declares_mut = tokens
+        .iter()
+        .any(|t| matches!(&t.token, Token::Symbol(s) if s == "mut"));
+
+    // Fast path: a clean file parses exactly as before, just with real spans
+    if errors.is_empty() && !tokens.is_empty() && !declares_mut {
+        let raw: Vec<Token> = tokens.iter().map(|t| t.token.clone()).collect();
+        if let Ok(mut exprs) = parser.parse_tokens(raw) {
+            let spans: Vec<Span> = tokens.iter().map(|t| t.span.clone()).collect();
+            for expr in &mut exprs {
+                remap_expr(expr, &spans);
+            }
+            return RecoveredParse { exprs, errors };
+        }
+    }
+
+    let mut exprs = Vec::new();
+    for statement in split_statements(tokens, &lines, &mut errors) {
+        let spans: Vec<Span> = statement.iter().map(|t| t.span.clone()).collect();
+        let raw: Vec<Token> = statement.iter().map(|t| t.token.clone()).collect();
+        let statement_span = Span::new(spans[0].start, spans[spans.len() - 1].end);
+
+        match parser.parse_tokens(raw) {
+            Ok(mut parsed) => {
+                for expr in &mut parsed {
+                    remap_expr(expr, &spans);
+                }
+                exprs.extend(parsed);
+            }
+            Err(e) => {
+                errors.push(relocate_error(e, &statement, &lines));
+                exprs.push(error_node(statement_span));
+            }
+        }
+    }
+
+    errors.sort_by_key(|e| e.location.offset);
+    RecoveredParse { exprs, errors }
+}
+
+/// Split `source` into its top-level statements using the same layout rule as
+/// the recovering parser. Each span runs from the first to the last token of a
+/// statement, in character offsets.
+pub fn split_top_level(source: &str) -> Vec<Span> {
+    let lines = LineIndex::new(source);
+    let mut errors = Vec::new();
+    let tokens = lex(&lines, &mut errors);
+
+    split_statements(tokens, &lines, &mut errors)
+        .iter()
+        .map(|statement| {
+            Span::new(
+                statement[0].span.start,
+                statement[statement.len() - 1].span.end,
+            )
+        })
+        .collect()
+}
+
+/// A significant token with its source span and layout information
+#[derive(Debug, Clone)]
+struct LexedToken {
+    token: Token,
+    span: Span,
+    /// The token is the first thing on its line, in the first column
+    at_line_start: bool,
+}
+
+/// Lex the whole source, skipping over characters the lexer cannot handle
+fn lex(lines: &LineIndex, errors: &mut Vec<ParseError>) -> Vec<LexedToken> {
+    let mut lexer = Lexer::new(lines.source);
+    let mut tokens = Vec::new();
+    let mut line_start = 0;
+
+    loop {
+        match lexer.next_token() {
+            Ok(Some((Token::Newline, span))) => line_start = span.end,
+            Ok(Some((Token::Comment(_), _))) => {}
+            Ok(Some((token, span))) => {
+                let at_line_start = span.start == line_start;
+                tokens.push(LexedToken {
+                    token,
+                    span,
+                    at_line_start,
+                });
+            }
+            Ok(None) => break,
+            Err(XsError::ParseError(pos, msg)) => {
+                errors.push(build_error(msg, Span::new(pos, pos + 1), None, lines));
+                lexer.skip_char();
+            }
+            Err(e) => {
+                errors.push(build_error(e.to_string(), Span::new(0, 1), None, lines));
+                break;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Split the token stream into top-level statements, repairing bracket mismatches
+fn split_statements(
+    tokens: Vec<LexedToken>,
+    lines: &LineIndex,
+    errors: &mut Vec<ParseError>,
+) -> Vec<Vec<LexedToken>> {
+    let mut statements = Vec::new();
+    let mut current: Vec<LexedToken> = Vec::new();
+    let mut open: Vec<LexedToken> = Vec::new();
+
+    for token in tokens {
+        let starts_new = token.at_line_start
+            && current.last().is_some_and(|prev| {
+                if is_definition_keyword(&token.token) {
+                    !expects_nested_definition(&prev.token)
+                } else {
+                    open.is_empty()
+                        && can_start_statement(&token.token)
+                        && !expects_continuation(&prev.token)
+                }
+            });
+
+        if starts_new {
+            report_unclosed(&mut open, lines, errors);
+            statements.push(std::mem::take(&mut current));
+        }
+
+        if opening_bracket(&token.token) {
+            open.push(token.clone());
+        } else if let Some(opener) = closing_bracket(&token.token) {
+            match open.iter().rposition(|t| t.token == opener) {
+                Some(index) => {
+                    let mut unclosed = open.split_off(index + 1);
+                    report_unclosed(&mut unclosed, lines, errors);
+                    open.pop();
+                }
+                None => {
+                    let message = format!("Unmatched closing `{}`", bracket_text(&token.token));
+                    errors.push(build_error(message, token.span.clone(), None, lines));
+                    continue;
+                }
+            }
+        }
+
+        current.push(token);
+    }
+
+    report_unclosed(&mut open, lines, errors);
+    if !current.is_empty() {
+        statements.push(current);
+    }
+    statements
+}
+
+fn report_unclosed(open: &mut Vec<LexedToken>, lines: &LineIndex, errors: &mut Vec<ParseError>) {
+    for token in open.drain(..) {
+        let message = format!("Unclosed `{}`", bracket_text(&token.token));
+        errors.push(build_error(message, token.span, None, lines));
+    }
+}
+
+fn opening_bracket(token: &Token) -> bool {
+    matches!(token, Token::LeftParen | Token::LeftBracket | Token::LeftBrace)
+}
+
+/// The opening bracket matching a closing one
+fn closing_bracket(token: &Token) -> Option<Token> {
+    match token {
+        Token::RightParen => Some(Token::LeftParen),
+        Token::RightBracket => Some(Token::LeftBracket),
+        Token::RightBrace => Some(Token::LeftBrace),
+        _ => None,
+    }
+}
+
+fn bracket_text(token: &Token) -> &'static str {
+    match token {
+        Token::LeftParen => "(",
+        Token::RightParen => ")",
+        Token::LeftBracket => "[",
+        Token::RightBracket => "]",
+        Token::LeftBrace => "{",
+        Token::RightBrace => "}",
+        _ => "?",
+    }
+}
+
+/// Keywords that always begin a new top-level statement when in the first column
+fn is_definition_keyword(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Let
+            | Token::LetRec
+            | Token::Type
+            | Token::Data
+            | Token::Effect
+            | Token::Module
+            | Token::Export
+            | Token::Import
+    )
+}
+
+/// Tokens that may begin a top-level statement at bracket depth zero
+fn can_start_statement(token: &Token) -> bool {
+    match token {
+        Token::Symbol(s) => {
+            s.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                && !matches!(s.as_str(), "then" | "and" | "of" | "when")
+        }
+        Token::Int(_)
+        | Token::Float(_)
+        | Token::String(_)
+        | Token::Bool(_)
+        | Token::LeftParen
+        | Token::LeftBracket
+        | Token::LeftBrace
+        | Token::Fn
+        | Token::If
+        | Token::Match
+        | Token::Do
+        | Token::Perform
+        | Token::Handle
+        | Token::At => true,
+        other => is_definition_keyword(other),
+    }
+}
+
+/// Tokens after which the next line continues the same statement
+fn expects_continuation(token: &Token) -> bool {
+    match token {
+        Token::Symbol(s) => {
+            matches!(s.as_str(), "then" | "and" | "of" | "when")
+                || s.chars().next().is_some_and(|c| !c.is_alphanumeric() && c != '_')
+        }
+        Token::Equals
+        | Token::EqualsEquals
+        | Token::In
+        | Token::Else
+        | Token::Arrow
+        | Token::FatArrow
+        | Token::LeftArrow
+        | Token::Pipe
+        | Token::PipeForward
+        | Token::DoubleColon
+        | Token::Dot
+        | Token::Comma
+        | Token::Colon
+        | Token::LessThan
+        | Token::GreaterThan
+        | Token::LeftAngle
+        | Token::RightAngle
+        | Token::With
+        | Token::Where
+        | Token::Backslash => true,
+        other => opening_bracket(other),
+    }
+}
+
+/// Tokens after which a `let` in the first column is still part of the same expression
+fn expects_nested_definition(token: &Token) -> bool {
+    match token {
+        Token::Symbol(s) => s == "then",
+        Token::In | Token::Equals | Token::Arrow | Token::FatArrow | Token::Else => true,
+        other => opening_bracket(other),
+    }
+}
+
+/// Turn an error from the GLL parser (token-indexed) into one with source positions
+fn relocate_error(error: ParseError, statement: &[LexedToken], lines: &LineIndex) -> ParseError {
+    let index = error.location.offset;
+    let last = &statement[statement.len() - 1];
+
+    let (message, span, found) = if !error.message.starts_with("Unexpected token") {
+        // Not a positional failure (e.g. iteration limit): blame the statement
+        let span = Span::new(statement[0].span.start, last.span.end);
+        (error.message, span, None)
+    } else if let Some(token) = statement.get(index) {
+        let text = lines.slice(&token.span).to_string();
+        (
+            format!("Unexpected token `{}`", text),
+            token.span.clone(),
+            Some(text),
+        )
+    } else {
+        let text = lines.slice(&last.span).to_string();
+        (
+            format!("Unexpected end of statement after `{}`", text),
+            Span::new(last.span.end, last.span.end),
+            Some("<EOF>".to_string()),
+        )
+    };
+
+    build_error(message, span, found, lines)
+}
+
+fn build_error(
+    message: String,
+    span: Span,
+    found: Option<String>,
+    lines: &LineIndex,
+) -> ParseError {
+    let (line, column) = lines.line_column(span.start);
+    let location = ErrorLocation {
+        file: None,
+        line,
+        column,
+        offset: span.start,
+        length: span.end.saturating_sub(span.start),
+    };
+
+    let source_lines: Vec<&str> = lines.source.lines().collect();
+    let context = ErrorContext {
+        before: line
+            .checked_sub(2)
+            .and_then(|i| source_lines.get(i))
+            .map(|l| vec![l.to_string()])
+            .unwrap_or_default(),
+        error_line: source_lines.get(line - 1).copied().unwrap_or("").to_string(),
+        after: source_lines
+            .get(line)
+            .map(|l| vec![l.to_string()])
+            .unwrap_or_default(),
+        expected: Vec::new(),
+        found,
+    };
+
+    ParseError::syntax(message, location).with_context(context)
+}
+
+/// Character offset to line/column conversion
+struct LineIndex<'a> {
+    source: &'a str,
+    /// Character offset of the start of each line
+    line_starts: Vec<usize>,
+    /// Byte offset of each character, plus the source length
+    byte_offsets: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        let mut byte_offsets = Vec::with_capacity(source.len() + 1);
+        for (i, (byte, ch)) in source.char_indices().enumerate() {
+            byte_offsets.push(byte);
+            if ch == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        byte_offsets.push(source.len());
+        Self {
+            source,
+            line_starts,
+            byte_offsets,
+        }
+    }
+
+    /// 1-indexed line and column of a character offset
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    fn slice(&self, span: &Span) -> &'a str {
+        let last = self.byte_offsets.len() - 1;
+        let start = self.byte_offsets[span.start.min(last)];
+        let end = self.byte_offsets[span.end.min(last)];
+        &self.source[start..end]
+    }
+}
+
+/// Map a token-index span from the GLL converter to character offsets
+fn remap_span(span: &mut Span, tokens: &[Span]) {
+    let Some(last) = tokens.last() else {
+        return;
+    };
+    let start = tokens.get(span.start).map_or(last.end, |t| t.start);
+    let end = if span.end > span.start {
+        tokens.get(span.end - 1).map_or(last.end, |t| t.end)
+    } else {
+        start
+    };
+    *span = Span::new(start, end.max(start));
+}
+
+fn remap_expr(expr: &mut Expr, tokens: &[Span]) {
+    map_expr_spans(expr, &mut |span| remap_span(span, tokens));
+}
+
+/// Shift every span in `expr` by `offset` characters.
+///
+/// Used to place an expression parsed from a slice of a file back at its
+/// position in the whole file.
+pub fn shift_spans(expr: &mut Expr, offset: usize) {
+    map_expr_spans(expr, &mut |span| {
+        *span = Span::new(span.start + offset, span.end + offset);
+    });
+}
+
+fn map_pattern_spans(pattern: &mut Pattern, f: &mut impl FnMut(&mut Span)) {
+    match pattern {
+        Pattern::Wildcard(span) | Pattern::Literal(_, span) | Pattern::Variable(_, span) => f(span),
+        Pattern::Constructor { patterns, span, .. } | Pattern::List { patterns, span } => {
+            f(span);
+            for p in patterns {
+                map_pattern_spans(p, f);
+            }
+        }
+    }
+}
+
+/// Apply `f` to every span in `expr`, including nested patterns
+fn map_expr_spans(expr: &mut Expr, f: &mut impl FnMut(&mut Span)) {
+    match expr {
+        Expr::Literal(_, span)
+        | Expr::Ident(_, span)
+        | Expr::TypeDef { span, .. }
+        | Expr::Import { span, .. }
+        | Expr::Use { span, .. }
+        | Expr::QualifiedIdent { span, .. }
+        | Expr::Hole { span, .. }
+        | Expr::HashRef { span, .. } => f(span),
+        Expr::List(items, span) | Expr::Block { exprs: items, span } => {
+            f(span);
+            for item in items {
+                map_expr_spans(item, f);
+            }
+        }
+        Expr::Module { body, span, .. } => {
+            f(span);
+            for item in body {
+                map_expr_spans(item, f);
+            }
+        }
+        Expr::Let { value, span, .. }
+        | Expr::LetRec { value, span, .. }
+        | Expr::Mut { value, span, .. }
+        | Expr::Assign { value, span, .. } => {
+            f(span);
+            map_expr_spans(value, f);
+        }
+        Expr::LetIn {
+            value, body, span, ..
+        }
+        | Expr::LetRecIn {
+            value, body, span, ..
+        } => {
+            f(span);
+            map_expr_spans(value, f);
+            map_expr_spans(body, f);
+        }
+        Expr::Rec { body, span, .. }
+        | Expr::Lambda { body, span, .. }
+        | Expr::FunctionDef { body, span, .. } => {
+            f(span);
+            map_expr_spans(body, f);
+        }
+        Expr::If {
+            cond,
+            then_expr,
+            else_expr,
+            span,
+        } => {
+            f(span);
+            map_expr_spans(cond, f);
+            map_expr_spans(then_expr, f);
+            map_expr_spans(else_expr, f);
+        }
+        Expr::Apply { func, args, span } => {
+            f(span);
+            map_expr_spans(func, f);
+            for arg in args {
+                map_expr_spans(arg, f);
+            }
+        }
+        Expr::Match { expr, cases, span } => {
+            f(span);
+            map_expr_spans(expr, f);
+            for (pattern, body) in cases {
+                map_pattern_spans(pattern, f);
+                map_expr_spans(body, f);
+            }
+        }
+        Expr::Constructor { args, span, .. } | Expr::Perform { args, span, .. } => {
+            f(span);
+            for arg in args {
+                map_expr_spans(arg, f);
+            }
+        }
+        Expr::Handler { cases, body, span } => {
+            f(span);
+            for (_, patterns, _, case_body) in cases {
+                for p in patterns {
+                    map_pattern_spans(p, f);
+                }
+                map_expr_spans(case_body, f);
+            }
+            map_expr_spans(body, f);
+        }
+        Expr::HandleExpr {
+            expr,
+            handlers,
+            return_handler,
+            span,
+        } => {
+            f(span);
+            map_expr_spans(expr, f);
+            for HandlerCase {
+                args, body, span, ..
+            } in handlers
+            {
+                f(span);
+                for p in args {
+                    map_pattern_spans(p, f);
+                }
+                map_expr_spans(body, f);
+            }
+            if let Some((_, body)) = return_handler {
+                map_expr_spans(body, f);
+            }
+        }
+        Expr::WithHandler {
+            handler,
+            body,
+            span,
+        } => {
+            f(span);
+            map_expr_spans(handler, f);
+            map_expr_spans(body, f);
+        }
+        Expr::Pipeline { expr, func, span } => {
+            f(span);
+            map_expr_spans(expr, f);
+            map_expr_spans(func, f);
+        }
+        Expr::Do { statements, span } => {
+            f(span);
+            for statement in statements {
+                match statement {
+                    DoStatement::Bind { expr, span, .. } => {
+                        f(span);
+                        map_expr_spans(expr, f);
+                    }
+                    DoStatement::Expression(expr) => map_expr_spans(expr, f),
+                }
+            }
+        }
+        Expr::RecordLiteral { fields, span } => {
+            f(span);
+            for (_, value) in fields {
+                map_expr_spans(value, f);
+            }
+        }
+        Expr::RecordAccess { record, span, .. } | Expr::RecordRestrict { record, span, .. } => {
+            f(span);
+            map_expr_spans(record, f);
+        }
+        Expr::RecordUpdate {
+            record,
+            updates: fields,
+            span,
+        }
+        | Expr::RecordExtend {
+            record,
+            fields,
+            span,
+        } => {
+            f(span);
+            map_expr_spans(record, f);
+            for (_, value) in fields {
+                map_expr_spans(value, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_source_has_no_errors() {
+        let result = parse_with_recovery("let x = 42");
+        assert!(!result.has_errors(), "{:?}", result.errors);
+        assert_eq!(result.exprs.len(), 1);
+        assert_eq!(result.exprs[0].span(), &Span::new(0, 10));
+    }
+
+    #[test]
+    fn test_reports_every_broken_statement() {
+        let source = "let a = 1 +\nlet b = 2\nlet c = * 3\n";
+        let result = parse_with_recovery(source);
+
+        assert_eq!(result.errors.len(), 2, "{:?}", result.errors);
+        assert_eq!(result.errors[0].location.line, 1);
+        assert_eq!(result.errors[1].location.line, 3);
+        assert_eq!(result.errors[1].location.column, 9);
+        assert_eq!(result.errors[1].location.offset, 30);
+
+        assert_eq!(result.exprs.len(), 3);
+        assert!(is_error_node(&result.exprs[0]));
+        assert!(matches!(result.exprs[1], Expr::Let { .. }));
+        assert_eq!(result.exprs[1].span(), &Span::new(12, 21));
+        assert!(is_error_node(&result.exprs[2]));
+    }
+
+    #[test]
+    fn test_unclosed_bracket_is_reported_at_opener() {
+        let source = "let a = (1\nlet b = 2";
+        let result = parse_with_recovery(source);
+
+        let unclosed = result
+            .errors
+            .iter()
+            .find(|e| e.message.contains("Unclosed"))
+            .expect("unclosed bracket diagnostic");
+        assert_eq!(unclosed.location.offset, 8);
+        assert!(result.exprs.iter().any(|e| matches!(e, Expr::Let { .. })));
+    }
+
+    #[test]
+    fn test_unmatched_closing_bracket() {
+        let result = parse_with_recovery("let a = 1)");
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].location.offset, 9);
+        assert!(matches!(result.exprs[0], Expr::Let { .. }));
+    }
+
+    #[test]
+    fn test_block_span_covers_statements() {
+        let result = parse_with_recovery("let a = 1\nlet b = 2");
+        let expr = result.into_expr().unwrap();
+        assert!(matches!(expr, Expr::Block { .. }));
+        assert_eq!(expr.span(), &Span::new(0, 19));
+    }
+
+    #[test]
+    fn test_split_top_level_and_shift_spans() {
+        let source = "let a = 1\nlet b =\n  [a]\n";
+        let items = split_top_level(source);
+        assert_eq!(items, vec![Span::new(0, 9), Span::new(10, 23)]);
+
+        let mut expr = parse_with_recovery("let c = 1").into_expr().unwrap();
+        shift_spans(&mut expr, 20);
+        assert_eq!(expr.span(), &Span::new(20, 29));
+    }
+}
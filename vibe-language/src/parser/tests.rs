@@ -1,5 +1,5 @@
 use super::*;
-use crate::{Expr, Ident, Literal, Span};
+use crate::{Expr, Ident, Literal, Span, Type};
 
 #[test]
 fn test_parse_simple_expression() {
@@ -271,3 +271,127 @@ fn test_parse_comments() {
         _ => panic!("Expected Let expression after comment, got {:?}", expr),
     }
 }
+
+#[test]
+fn test_parse_mut_and_assignment() {
+    let expr = parse("mut count = 0\ncount = count + 1\ncount").unwrap();
+    let Expr::Block { exprs, .. } = expr else {
+        panic!("Expected a block, got {:?}", expr);
+    };
+    match exprs.as_slice() {
+        [Expr::Mut { name, value, .. }, Expr::Assign { name: assigned, .. }, Expr::Ident(read, _)] => {
+            assert_eq!(name.0, "count");
+            assert!(matches!(value.as_ref(), Expr::Literal(Literal::Int(0), _)));
+            assert_eq!(assigned.0, "count");
+            assert_eq!(read.0, "count");
+        }
+        _ => panic!("Expected mut, assignment and read, got {:?}", exprs),
+    }
+}
+
+#[test]
+fn test_parse_quantified_annotation_and_type_alias() {
+    let expr = parse("let map : forall f a b. (a -> b) -> f a -> f b = fn g x -> x").unwrap();
+    let Expr::Let { type_ann: Some(ann), .. } = expr else {
+        panic!("Expected an annotated let, got {:?}", expr);
+    };
+    assert_eq!(ann.to_string(), "forall f a b. (a -> b) -> (f a) -> (f b)");
+
+    let expr = parse("type Pair a = List a\nlet p : Pair Int = [1, 2]").unwrap();
+    let Expr::Block { exprs, .. } = expr else {
+        panic!("Expected a block, got {:?}", expr);
+    };
+    match exprs.as_slice() {
+        [Expr::TypeDef { definition, .. }, Expr::Let { type_ann: Some(ann), .. }] => {
+            assert_eq!(definition.name, "Pair");
+            assert_eq!(definition.type_params, vec!["a".to_string()]);
+            assert_eq!(definition.alias, Some(Type::List(Box::new(Type::Var("a".to_string())))));
+            assert_eq!(ann.to_string(), "(Pair Int)");
+        }
+        _ => panic!("Expected a type alias and a let, got {:?}", exprs),
+    }
+}
+
+#[test]
+fn test_parse_record_extension_and_restriction() {
+    let expr = parse(r#"{ age: 36, id: 1 | person }"#).unwrap();
+    let Expr::RecordExtend { record, fields, .. } = expr else {
+        panic!("Expected a record extension, got {:?}", expr);
+    };
+    assert!(matches!(record.as_ref(), Expr::Ident(name, _) if name.0 == "person"));
+    let names: Vec<_> = fields.iter().map(|(name, _)| name.0.as_str()).collect();
+    assert_eq!(names, vec!["age", "id"]);
+
+    let expr = parse(r#"{ { age: 36 | person } - name }"#).unwrap();
+    let Expr::RecordRestrict { record, field, .. } = expr else {
+        panic!("Expected a record restriction, got {:?}", expr);
+    };
+    assert_eq!(field.0, "name");
+    assert!(matches!(record.as_ref(), Expr::RecordExtend { .. }));
+
+    // A block subtracting a number is still a block
+    let expr = parse("{ n - 1 }").unwrap();
+    assert!(matches!(expr, Expr::Block { .. }), "{:?}", expr);
+}
+
+#[test]
+fn test_parse_record_type_annotations() {
+    let expr = parse("let name : { name: String | rest } -> String = fn r -> r").unwrap();
+    let Expr::Let { type_ann: Some(ann), .. } = expr else {
+        panic!("Expected an annotated let, got {:?}", expr);
+    };
+    assert_eq!(
+        ann,
+        Type::Function(
+            Box::new(Type::ExtensibleRecord {
+                fields: vec![("name".to_string(), Type::String)],
+                row: "rest".to_string(),
+            }),
+            Box::new(Type::String),
+        )
+    );
+
+    let expr = parse("let origin : { x: Int, y: Int } = { x: 0, y: 0 }").unwrap();
+    let Expr::Let { type_ann: Some(ann), .. } = expr else {
+        panic!("Expected an annotated let, got {:?}", expr);
+    };
+    assert_eq!(
+        ann,
+        Type::Record {
+            fields: vec![("x".to_string(), Type::Int), ("y".to_string(), Type::Int)],
+        }
+    );
+}
+
+#[test]
+fn test_parse_record_fields_on_lines_and_quoted_names() {
+    let expr = parse("{\n  name: \"app\"  # comment\n  \"json-lite\": \"^0.3\",\n}").unwrap();
+    let Expr::RecordLiteral { fields, .. } = expr else {
+        panic!("Expected a record, got {:?}", expr);
+    };
+    let names: Vec<_> = fields.iter().map(|(name, _)| name.0.as_str()).collect();
+    assert_eq!(names, vec!["name", "json-lite"]);
+}
+
+#[test]
+fn test_parse_mut_in_function_body() {
+    let expr = parse("let f = fn x { mut s = x; s = s + 1; s }").unwrap();
+    let Expr::Let { value, .. } = expr else {
+        panic!("Expected a let, got {:?}", expr);
+    };
+    let Expr::Lambda { body, .. } = value.as_ref() else {
+        panic!("Expected a lambda, got {:?}", value);
+    };
+    let Expr::Block { exprs, .. } = body.as_ref() else {
+        panic!("Expected a block body, got {:?}", body);
+    };
+    match exprs.as_slice() {
+        [Expr::Mut { name, .. }, Expr::Assign { name: assigned, value, .. }, Expr::Ident(read, _)] => {
+            assert_eq!(name.0, "s");
+            assert_eq!(assigned.0, "s");
+            assert!(matches!(value.as_ref(), Expr::Apply { args, .. } if args.len() == 2));
+            assert_eq!(read.0, "s");
+        }
+        _ => panic!("Expected mut, assignment and read, got {:?}", exprs),
+    }
+}
@@ -73,6 +73,12 @@ impl<'a> PrettyPrinter<'a> {
                 ..
             } => self.format_let(name, type_ann, value),
             Expr::LetRec { name, value, .. } => self.format_let(name, &None, value),
+            Expr::Mut { name, value, .. } => {
+                format!("mut {} = {}", name.0, self.format_expr(value, None))
+            }
+            Expr::Assign { name, value, .. } => {
+                format!("{} = {}", name.0, self.format_expr(value, None))
+            }
             Expr::LetIn {
                 name,
                 type_ann,
@@ -289,6 +295,17 @@ impl<'a> PrettyPrinter<'a> {
                     .join(", ");
                 format!("{{ {} | {} }}", self.format_expr(record, None), updates_str)
             }
+            Expr::RecordExtend { record, fields, .. } => {
+                let fields_str = fields
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.0, self.format_expr(v, None)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{{ {} | {} }}", fields_str, self.format_expr(record, None))
+            }
+            Expr::RecordRestrict { record, field, .. } => {
+                format!("{{ {} - {} }}", self.format_expr(record, None), field.0)
+            }
             Expr::HashRef { hash, .. } => {
                 format!("#{}", hash)
             }
@@ -373,11 +390,25 @@ impl<'a> PrettyPrinter<'a> {
                     .collect();
                 format!("{{ {} }}", field_strs.join(", "))
             }
+            Type::ExtensibleRecord { fields, row } => {
+                let field_strs: Vec<String> = fields
+                    .iter()
+                    .map(|(name, typ)| format!("{}: {}", name, self.format_type(typ)))
+                    .collect();
+                format!("{{ {} | {} }}", field_strs.join(", "), row)
+            }
             Type::Option(t) => format!("{}?", self.format_type(t)),
             Type::Tuple(types) => {
                 let type_strs: Vec<String> = types.iter().map(|t| self.format_type(t)).collect();
                 format!("({})", type_strs.join(", "))
             }
+            Type::Applied { constructor, args } => {
+                let arg_strs: Vec<String> = args.iter().map(|t| self.format_type(t)).collect();
+                format!("{} {}", constructor, arg_strs.join(" "))
+            }
+            Type::Forall { vars, body } => {
+                format!("forall {}. {}", vars.join(" "), self.format_type(body))
+            }
         }
     }
 
@@ -624,6 +655,15 @@ impl<'a> PrettyPrinter<'a> {
             format!(" {}", typedef.type_params.join(" "))
         };
 
+        if let Some(alias) = &typedef.alias {
+            return format!(
+                "(type {}{} = {})",
+                typedef.name,
+                type_params,
+                self.format_type(alias)
+            );
+        }
+
         let constructors = typedef
             .constructors
             .iter()
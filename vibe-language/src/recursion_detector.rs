@@ -51,7 +51,7 @@ impl<'a> RecursionVisitor<'a> {
                 self.bound_vars = old_bound;
             }
 
-            Expr::Let { value, .. } => {
+            Expr::Let { value, .. } | Expr::Mut { value, .. } | Expr::Assign { value, .. } => {
                 // Check value first (before name is bound)
                 self.visit_expr(value);
                 // Note: for top-level let, we don't shadow the name
@@ -161,14 +161,17 @@ impl<'a> RecursionVisitor<'a> {
                     self.visit_expr(value);
                 }
             }
-            Expr::RecordAccess { record, .. } => {
+            Expr::RecordAccess { record, .. } | Expr::RecordRestrict { record, .. } => {
                 self.visit_expr(record);
             }
             Expr::RecordUpdate {
-                record, updates, ..
-            } => {
+                record,
+                updates: fields,
+                ..
+            }
+            | Expr::RecordExtend { record, fields, .. } => {
                 self.visit_expr(record);
-                for (_, value) in updates {
+                for (_, value) in fields {
                     self.visit_expr(value);
                 }
             }
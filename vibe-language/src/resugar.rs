@@ -0,0 +1,291 @@
+//! Resugaring - converts normalized AST back to surface syntax
+//!
+//! Normalization forgets how an expression was written. The [`SyntaxSugar`]
+//! recorded by [`crate::ast_normalizer::AstNormalizer`] says which desugared
+//! forms to restore; everything else is printed in its plain surface form.
+
+use crate::normalized_ast::{NormalizedExpr, NormalizedHandler, NormalizedPattern};
+use crate::typed_ir::SyntaxSugar;
+use crate::{Expr, HandlerCase, Ident, Literal, Pattern, Span};
+use std::collections::HashMap;
+
+/// Convert `expr` back to surface syntax, restoring `sugar`
+pub fn resugar(expr: &NormalizedExpr, sugar: &[SyntaxSugar]) -> Expr {
+    let mutable = sugar
+        .iter()
+        .filter_map(|sugar| match sugar {
+            SyntaxSugar::MutableVariable { name, effect } => Some((effect.as_str(), name.as_str())),
+            _ => None,
+        })
+        .collect();
+    Resugarer { mutable }.expr(expr)
+}
+
+struct Resugarer<'a> {
+    /// `mut` variables by the State effect holding them
+    mutable: HashMap<&'a str, &'a str>,
+}
+
+impl Resugarer<'_> {
+    fn expr(&self, expr: &NormalizedExpr) -> Expr {
+        match expr {
+            NormalizedExpr::Literal(lit) => Expr::Literal(lit.clone(), span()),
+            NormalizedExpr::Var(name) => ident(name),
+            NormalizedExpr::Apply { func, arg } => {
+                if let Some(block) = self.local_state(func, arg) {
+                    return block;
+                }
+                let (func, mut args) = match self.expr(func) {
+                    Expr::Apply { func, args, .. } => (func, args),
+                    func => (Box::new(func), Vec::new()),
+                };
+                args.push(self.expr(arg));
+                Expr::Apply {
+                    func,
+                    args,
+                    span: span(),
+                }
+            }
+            NormalizedExpr::Lambda { param, body } => {
+                let (mut params, body) = match self.expr(body) {
+                    Expr::Lambda { params, body, .. } => (params, body),
+                    body => (Vec::new(), Box::new(body)),
+                };
+                params.insert(0, (Ident(param.clone()), None));
+                Expr::Lambda {
+                    params,
+                    body,
+                    span: span(),
+                }
+            }
+            NormalizedExpr::Let { name, value, body } => {
+                // Blocks are normalized to lets, binding `seq$N` to statements
+                let first = if name.starts_with("seq$") {
+                    self.expr(value)
+                } else {
+                    Expr::Let {
+                        name: Ident(name.clone()),
+                        type_ann: None,
+                        value: Box::new(self.expr(value)),
+                        span: span(),
+                    }
+                };
+                let rest = match body.as_ref() {
+                    NormalizedExpr::Var(var) if var == name => Vec::new(),
+                    body => self.statements(body),
+                };
+                block(std::iter::once(first).chain(rest).collect())
+            }
+            NormalizedExpr::LetRec { name, value, body } => Expr::LetRecIn {
+                name: Ident(name.clone()),
+                type_ann: None,
+                value: Box::new(self.expr(value)),
+                body: Box::new(self.expr(body)),
+                span: span(),
+            },
+            NormalizedExpr::Match { expr, cases } => {
+                let bool_case = |(pattern, _): &(NormalizedPattern, NormalizedExpr), value| {
+                    *pattern == NormalizedPattern::Literal(Literal::Bool(value))
+                };
+                match cases.as_slice() {
+                    [then_case, else_case]
+                        if bool_case(then_case, true) && bool_case(else_case, false) =>
+                    {
+                        Expr::If {
+                            cond: Box::new(self.expr(expr)),
+                            then_expr: Box::new(self.expr(&then_case.1)),
+                            else_expr: Box::new(self.expr(&else_case.1)),
+                            span: span(),
+                        }
+                    }
+                    _ => Expr::Match {
+                        expr: Box::new(self.expr(expr)),
+                        cases: cases
+                            .iter()
+                            .map(|(pattern, case)| (resugar_pattern(pattern), self.expr(case)))
+                            .collect(),
+                        span: span(),
+                    },
+                }
+            }
+            NormalizedExpr::List(items) => {
+                Expr::List(items.iter().map(|item| self.expr(item)).collect(), span())
+            }
+            NormalizedExpr::Record(fields) => Expr::RecordLiteral {
+                fields: fields
+                    .iter()
+                    .map(|(name, value)| (Ident(name.clone()), self.expr(value)))
+                    .collect(),
+                span: span(),
+            },
+            NormalizedExpr::Field { expr, field } => Expr::RecordAccess {
+                record: Box::new(self.expr(expr)),
+                field: Ident(field.clone()),
+                span: span(),
+            },
+            NormalizedExpr::Constructor { name, args } => Expr::Constructor {
+                name: Ident(name.clone()),
+                args: args.iter().map(|arg| self.expr(arg)).collect(),
+                span: span(),
+            },
+            NormalizedExpr::Perform {
+                effect,
+                operation,
+                args,
+            } => match (
+                self.mutable.get(effect.as_str()),
+                operation.as_str(),
+                args.as_slice(),
+            ) {
+                (Some(name), "get", []) => ident(name),
+                (Some(name), "put", [value]) => Expr::Assign {
+                    name: Ident(name.to_string()),
+                    value: Box::new(self.expr(value)),
+                    span: span(),
+                },
+                _ => Expr::Perform {
+                    effect: Ident(operation_name(effect, operation)),
+                    args: args.iter().map(|arg| self.expr(arg)).collect(),
+                    span: span(),
+                },
+            },
+            NormalizedExpr::Handle { expr, handlers } => {
+                let (returns, handlers): (Vec<_>, Vec<_>) = handlers
+                    .iter()
+                    .partition(|handler| handler.effect == "return");
+                Expr::HandleExpr {
+                    expr: Box::new(self.expr(expr)),
+                    handlers: handlers
+                        .into_iter()
+                        .map(|handler| self.handler(handler))
+                        .collect(),
+                    return_handler: returns.first().map(|handler| {
+                        let var = handler.params.first().cloned().unwrap_or_default();
+                        (Ident(var), Box::new(self.expr(&handler.body)))
+                    }),
+                    span: span(),
+                }
+            }
+        }
+    }
+
+    /// `mut name = initial` followed by the statements of `body`, if `func`
+    /// handles the State effect of a `mut` variable
+    fn local_state(&self, func: &NormalizedExpr, initial: &NormalizedExpr) -> Option<Expr> {
+        let NormalizedExpr::Handle {
+            expr: body,
+            handlers,
+        } = func
+        else {
+            return None;
+        };
+        let name = handlers
+            .iter()
+            .find_map(|handler| self.mutable.get(handler.effect.as_str()))?;
+        let declaration = Expr::Mut {
+            name: Ident(name.to_string()),
+            value: Box::new(self.expr(initial)),
+            span: span(),
+        };
+        let rest = match body.as_ref() {
+            NormalizedExpr::Constructor { name, args } if name == "Unit" && args.is_empty() => {
+                Vec::new()
+            }
+            body => self.statements(body),
+        };
+        Some(block(std::iter::once(declaration).chain(rest).collect()))
+    }
+
+    /// The statements `expr` resugars to, spliced into an enclosing block
+    fn statements(&self, expr: &NormalizedExpr) -> Vec<Expr> {
+        match self.expr(expr) {
+            Expr::Block { exprs, .. } => exprs,
+            expr => vec![expr],
+        }
+    }
+
+    fn handler(&self, handler: &NormalizedHandler) -> HandlerCase {
+        HandlerCase {
+            effect: Ident(handler.effect.clone()),
+            operation: (handler.operation != "perform").then(|| Ident(handler.operation.clone())),
+            args: handler
+                .params
+                .iter()
+                .map(|param| Pattern::Variable(Ident(param.clone()), span()))
+                .collect(),
+            continuation: Ident(handler.resume.clone()),
+            body: self.expr(&handler.body),
+            span: span(),
+        }
+    }
+}
+
+fn resugar_pattern(pattern: &NormalizedPattern) -> Pattern {
+    match pattern {
+        NormalizedPattern::Wildcard => Pattern::Wildcard(span()),
+        NormalizedPattern::Variable(name) => Pattern::Variable(Ident(name.clone()), span()),
+        NormalizedPattern::Literal(lit) => Pattern::Literal(lit.clone(), span()),
+        NormalizedPattern::Constructor { name, patterns } => Pattern::Constructor {
+            name: Ident(name.clone()),
+            patterns: patterns.iter().map(resugar_pattern).collect(),
+            span: span(),
+        },
+        NormalizedPattern::List(patterns) => Pattern::List {
+            patterns: patterns.iter().map(resugar_pattern).collect(),
+            span: span(),
+        },
+        NormalizedPattern::Cons { head, tail } => Pattern::Constructor {
+            name: Ident("::".to_string()),
+            patterns: vec![resugar_pattern(head), resugar_pattern(tail)],
+            span: span(),
+        },
+    }
+}
+
+/// `Effect.operation`, or just `Effect` for a plain `perform`
+fn operation_name(effect: &str, operation: &str) -> String {
+    if operation == "perform" {
+        effect.to_string()
+    } else {
+        format!("{effect}.{operation}")
+    }
+}
+
+fn block(exprs: Vec<Expr>) -> Expr {
+    Expr::Block {
+        exprs,
+        span: span(),
+    }
+}
+
+fn ident(name: &str) -> Expr {
+    Expr::Ident(Ident(name.to_string()), span())
+}
+
+fn span() -> Span {
+    Span::new(0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_normalizer::AstNormalizer;
+    use crate::pretty_print::pretty_print;
+
+    #[test]
+    fn test_mut_prints_back_as_mut() {
+        let source = crate::parser::parse("{ mut s = 0; s = s + 1; s }").unwrap();
+
+        let mut normalizer = AstNormalizer::new();
+        let normalized = normalizer.normalize_expr(&source);
+        assert_eq!(
+            pretty_print(&resugar(&normalized, normalizer.sugar())),
+            pretty_print(&source)
+        );
+
+        // Without the metadata the State effect shows through
+        let plain = pretty_print(&resugar(&normalized, &[]));
+        assert!(plain.contains("State$0.put"), "{plain}");
+        assert!(!plain.contains("mut"), "{plain}");
+    }
+}
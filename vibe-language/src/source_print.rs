@@ -0,0 +1,423 @@
+//! Print expressions back as source in the syntax the parser reads
+//!
+//! [`crate::pretty_print`] shows expressions as S-expressions for display.
+//! This printer is for writing code out to `.vibe` files: operands and
+//! arguments are parenthesized unless they are atoms, blocks are laid out one
+//! statement per line, and forms without a surface syntax fall back to the
+//! S-expression form.
+
+use crate::pretty_print::pretty_print;
+use crate::{DoStatement, Expr, FunctionParam, Ident, Literal, Pattern, Type, TypeDefinition};
+
+const INDENT: &str = "  ";
+
+/// Operators printed infix when applied to two arguments
+const INFIX_OPERATORS: [&str; 17] = [
+    "|>", "||", "&&", "==", "!=", "<", ">", "<=", ">=", "::", "++", "+", "-", "*", "/", "mod", "^",
+];
+
+/// Print `expr` as source
+pub fn print_source(expr: &Expr) -> String {
+    SourcePrinter { depth: 0 }.expr(expr)
+}
+
+struct SourcePrinter {
+    depth: usize,
+}
+
+impl SourcePrinter {
+    fn nested(&self) -> Self {
+        SourcePrinter {
+            depth: self.depth + 1,
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Literal(lit, _) => literal(lit),
+            Expr::Ident(name, _) if is_operator(&name.0) => format!("({})", name.0),
+            Expr::Ident(name, _) => name.0.clone(),
+            Expr::QualifiedIdent {
+                module_name, name, ..
+            } => format!("{}.{}", module_name.0, name.0),
+            Expr::List(items, _) => format!("[{}]", self.comma_separated(items)),
+            Expr::Let {
+                name,
+                type_ann,
+                value,
+                ..
+            } => self.binding(name, type_ann, value),
+            Expr::LetIn {
+                name,
+                type_ann,
+                value,
+                body,
+                ..
+            } => format!(
+                "{} in {}",
+                self.binding(name, type_ann, value),
+                self.expr(body)
+            ),
+            Expr::Lambda { params, body, .. } => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|(name, ty)| parameter(name, ty.as_ref()))
+                    .collect();
+                if params.is_empty() {
+                    format!("fn -> {}", self.expr(body))
+                } else {
+                    format!("fn {} -> {}", params.join(" "), self.expr(body))
+                }
+            }
+            Expr::FunctionDef {
+                name, params, body, ..
+            } => format!("let {} = {}", name.0, self.function(params, body)),
+            Expr::If {
+                cond,
+                then_expr,
+                else_expr,
+                ..
+            } => format!(
+                "if {} {} else {}",
+                self.expr(cond),
+                self.braced(then_expr),
+                self.braced(else_expr)
+            ),
+            Expr::Apply { func, args, .. } => match (func.as_ref(), args.as_slice()) {
+                (Expr::Ident(op, _), [left, right]) if is_operator(&op.0) => {
+                    format!("{} {} {}", self.atom(left), op.0, self.atom(right))
+                }
+                _ => {
+                    // Curried application reads as one `f x y`
+                    let head = match func.as_ref() {
+                        Expr::Apply { .. } if !is_infix(func) => self.expr(func),
+                        _ => self.atom(func),
+                    };
+                    let mut parts = vec![head];
+                    parts.extend(args.iter().map(|arg| self.atom(arg)));
+                    parts.join(" ")
+                }
+            },
+            Expr::Pipeline { expr, func, .. } => {
+                format!("{} |> {}", self.atom(expr), self.atom(func))
+            }
+            Expr::Constructor { name, args, .. } => {
+                let mut parts = vec![name.0.clone()];
+                parts.extend(args.iter().map(|arg| self.atom(arg)));
+                parts.join(" ")
+            }
+            Expr::Match { expr, cases, .. } => {
+                let inner = self.nested();
+                let cases: Vec<String> = cases
+                    .iter()
+                    .map(|(pattern, body)| {
+                        format!(
+                            "{}{} -> {}",
+                            inner.indent(),
+                            self::pattern(pattern),
+                            inner.expr(body)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "match {} {{\n{}\n{}}}",
+                    self.expr(expr),
+                    cases.join("\n"),
+                    self.indent()
+                )
+            }
+            Expr::Block { exprs, .. } => self.block(exprs.iter().map(|e| self.nested().expr(e))),
+            Expr::Do { statements, .. } => {
+                let inner = self.nested();
+                let statements = statements.iter().map(|statement| match statement {
+                    DoStatement::Bind { name, expr, .. } => {
+                        format!("{} <- {}", name.0, inner.expr(expr))
+                    }
+                    DoStatement::Expression(expr) => inner.expr(expr),
+                });
+                format!("do {}", self.block(statements))
+            }
+            Expr::Mut { name, value, .. } => format!("mut {} = {}", name.0, self.expr(value)),
+            Expr::Assign { name, value, .. } => format!("{} = {}", name.0, self.expr(value)),
+            Expr::Perform { effect, args, .. } => {
+                let mut parts = vec!["perform".to_string(), effect.0.clone()];
+                parts.extend(args.iter().map(|arg| self.atom(arg)));
+                parts.join(" ")
+            }
+            Expr::RecordLiteral { fields, .. } if fields.is_empty() => "{}".to_string(),
+            Expr::RecordLiteral { fields, .. } => format!("{{ {} }}", self.fields(fields)),
+            Expr::RecordAccess { record, field, .. } => {
+                format!("{}.{}", self.atom(record), field.0)
+            }
+            Expr::RecordExtend { record, fields, .. } => {
+                format!("{{ {} | {} }}", self.fields(fields), self.expr(record))
+            }
+            Expr::RecordRestrict { record, field, .. } => {
+                format!("{{ {} - {} }}", self.expr(record), field.0)
+            }
+            Expr::TypeDef { definition, .. } => type_definition(definition),
+            Expr::Module {
+                name,
+                exports,
+                body,
+                ..
+            } if name.0.is_empty() && body.is_empty() => {
+                format!("export {{ {} }}", names(exports))
+            }
+            Expr::Use { path, items, .. } => format!(
+                "import {} {{ {} }}",
+                path.join("/"),
+                items.as_deref().map_or("..".to_string(), names)
+            ),
+            Expr::Import {
+                module_name,
+                items,
+                as_name,
+                hash,
+                ..
+            } => {
+                let mut result = format!("import {}", module_name.0);
+                if let Some(hash) = hash {
+                    result.push_str(&format!("@{hash}"));
+                }
+                if let Some(items) = items {
+                    result.push_str(&format!(" ({})", names(items)));
+                }
+                if let Some(alias) = as_name {
+                    result.push_str(&format!(" as {}", alias.0));
+                }
+                result
+            }
+            _ => pretty_print(expr),
+        }
+    }
+
+    /// `expr`, parenthesized unless it is an atom
+    fn atom(&self, expr: &Expr) -> String {
+        let atomic = match expr {
+            Expr::Literal(Literal::Int(n), _) => *n >= 0,
+            Expr::Literal(Literal::Float(f), _) => f.0 >= 0.0,
+            Expr::Constructor { args, .. } => args.is_empty(),
+            Expr::Literal(..)
+            | Expr::Ident(..)
+            | Expr::QualifiedIdent { .. }
+            | Expr::List(..)
+            | Expr::Block { .. }
+            | Expr::RecordLiteral { .. }
+            | Expr::RecordAccess { .. }
+            | Expr::RecordExtend { .. }
+            | Expr::RecordRestrict { .. } => true,
+            _ => false,
+        };
+        if atomic {
+            self.expr(expr)
+        } else {
+            format!("({})", self.expr(expr))
+        }
+    }
+
+    fn binding(&self, name: &Ident, type_ann: &Option<Type>, value: &Expr) -> String {
+        match (type_ann, value) {
+            (None, Expr::FunctionDef { params, body, .. }) => {
+                format!("let {} = {}", name.0, self.function(params, body))
+            }
+            (Some(ty), _) => format!("let {} : {} = {}", name.0, ty, self.expr(value)),
+            (None, _) => format!("let {} = {}", name.0, self.expr(value)),
+        }
+    }
+
+    fn function(&self, params: &[FunctionParam], body: &Expr) -> String {
+        let params: Vec<String> = params
+            .iter()
+            .map(|param| parameter(&param.name, param.typ.as_ref()))
+            .collect();
+        format!("fn {} -> {}", params.join(" "), self.expr(body))
+    }
+
+    /// `expr` as the body of an `if` branch
+    fn braced(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Block { .. } => self.expr(expr),
+            _ => format!("{{ {} }}", self.expr(expr)),
+        }
+    }
+
+    /// Statements laid out one per line between braces
+    fn block(&self, statements: impl Iterator<Item = String>) -> String {
+        let inner = self.nested().indent();
+        let lines: Vec<String> = statements.map(|s| format!("{inner}{s}")).collect();
+        if lines.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{\n{}\n{}}}", lines.join("\n"), self.indent())
+        }
+    }
+
+    fn comma_separated(&self, items: &[Expr]) -> String {
+        items
+            .iter()
+            .map(|item| self.expr(item))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn fields(&self, fields: &[(Ident, Expr)]) -> String {
+        fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", field_name(name), self.expr(value)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn indent(&self) -> String {
+        INDENT.repeat(self.depth)
+    }
+}
+
+fn is_infix(expr: &Expr) -> bool {
+    matches!(expr, Expr::Apply { func, args, .. }
+        if args.len() == 2 && matches!(func.as_ref(), Expr::Ident(op, _) if is_operator(&op.0)))
+}
+
+fn is_operator(name: &str) -> bool {
+    INFIX_OPERATORS.contains(&name)
+}
+
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Int(n) => n.to_string(),
+        Literal::Float(f) => format!("{:?}", f.0),
+        Literal::Bool(b) => b.to_string(),
+        Literal::String(s) => format!("{s:?}"),
+    }
+}
+
+/// A record field name, quoted unless it is an identifier
+fn field_name(name: &Ident) -> String {
+    let mut chars = name.0.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if identifier {
+        name.0.clone()
+    } else {
+        format!("{:?}", name.0)
+    }
+}
+
+fn parameter(name: &Ident, ty: Option<&Type>) -> String {
+    match ty {
+        Some(ty) => format!("({} : {})", name.0, ty),
+        None => name.0.clone(),
+    }
+}
+
+fn names(names: &[Ident]) -> String {
+    names
+        .iter()
+        .map(|name| name.0.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard(_) => "_".to_string(),
+        Pattern::Literal(lit, _) => literal(lit),
+        Pattern::Variable(name, _) => name.0.clone(),
+        Pattern::Constructor { name, patterns, .. } => {
+            let mut parts = vec![name.0.clone()];
+            parts.extend(patterns.iter().map(|p| match p {
+                Pattern::Constructor { patterns, .. } if !patterns.is_empty() => {
+                    format!("({})", self::pattern(p))
+                }
+                _ => self::pattern(p),
+            }));
+            parts.join(" ")
+        }
+        Pattern::List { patterns, .. } => format!(
+            "[{}]",
+            patterns
+                .iter()
+                .map(self::pattern)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn type_definition(definition: &TypeDefinition) -> String {
+    let mut head = vec!["type".to_string(), definition.name.clone()];
+    head.extend(definition.type_params.iter().cloned());
+    let head = head.join(" ");
+    if let Some(alias) = &definition.alias {
+        return format!("{head} = {alias}");
+    }
+    let constructors: Vec<String> = definition
+        .constructors
+        .iter()
+        .map(|constructor| {
+            let mut parts = vec![constructor.name.clone()];
+            parts.extend(constructor.fields.iter().map(|field| match field {
+                Type::Function(..) | Type::FunctionWithEffect { .. } => format!("({field})"),
+                _ => field.to_string(),
+            }));
+            format!("| {}", parts.join(" "))
+        })
+        .collect();
+    format!("{} = {}", head, constructors.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn reprint(source: &str) -> String {
+        print_source(&parse(source).unwrap())
+    }
+
+    #[test]
+    fn test_definitions_round_trip() {
+        for source in [
+            "let base = 10",
+            "let greeting = \"say \\\"hi\\\"\"",
+            "let total = base",
+            "export { total, base }",
+            "import lib/String { concat }",
+        ] {
+            assert_eq!(reprint(source), source);
+            assert_eq!(reprint(&reprint(source)), source);
+        }
+    }
+
+    #[test]
+    fn test_operators_and_application() {
+        for source in [
+            "let sum = x + y",
+            "f (g 1)",
+            "let f = fn x y -> x",
+            "let xs = [1, 2]",
+            "perform IO 1",
+            "let deps = { math: \"^1.2\", \"json-lite\": \"^0.3\" }",
+        ] {
+            assert_eq!(reprint(source), source);
+        }
+    }
+
+    #[test]
+    fn test_blocks_are_indented() {
+        let expr = parse("mut count = 0\ncount = count + 1\ncount").unwrap();
+        let Expr::Block { exprs, span } = expr else {
+            panic!("Expected a block");
+        };
+        let body = Expr::Lambda {
+            params: vec![],
+            body: Box::new(Expr::Block { exprs, span }),
+            span: crate::Span::new(0, 0),
+        };
+        assert_eq!(
+            print_source(&body),
+            "fn -> {\n  mut count = 0\n  count = count + 1\n  count\n}"
+        );
+    }
+}
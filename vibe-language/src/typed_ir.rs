@@ -120,6 +120,8 @@ pub enum SyntaxSugar {
     OptionalType,
     /// Tuple syntax
     TupleSyntax,
+    /// `mut name = e`, desugared to the local State effect `effect`
+    MutableVariable { name: String, effect: String },
 }
 
 /// Formatting preferences from the original source
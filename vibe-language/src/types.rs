@@ -35,6 +35,14 @@ impl Type {
                 }
                 vars
             }
+            Type::ExtensibleRecord { fields, row } => {
+                let mut vars = HashSet::new();
+                for (_, ty) in fields {
+                    vars.extend(ty.free_vars());
+                }
+                vars.insert(row.clone());
+                vars
+            }
             Type::Option(t) => t.free_vars(),
             Type::Tuple(types) => {
                 let mut vars = HashSet::new();
@@ -43,6 +51,21 @@ impl Type {
                 }
                 vars
             }
+            Type::Applied { constructor, args } => {
+                let mut vars = HashSet::new();
+                vars.insert(constructor.clone());
+                for t in args {
+                    vars.extend(t.free_vars());
+                }
+                vars
+            }
+            Type::Forall { vars: bound, body } => {
+                let mut vars = body.free_vars();
+                for var in bound {
+                    vars.remove(var);
+                }
+                vars
+            }
         }
     }
 
@@ -70,10 +93,134 @@ impl Type {
                     .map(|(name, ty)| (name.clone(), ty.apply_subst(subst)))
                     .collect(),
             },
+            Type::ExtensibleRecord { fields, row } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), ty.apply_subst(subst)))
+                    .collect();
+                let rest = Type::Var(row.clone()).apply_subst(subst);
+                Type::extend_record(fields, rest).unwrap_or_else(|| self.clone())
+            }
             Type::Option(t) => Type::Option(Box::new(t.apply_subst(subst))),
             Type::Tuple(types) => Type::Tuple(
                 types.iter().map(|t| t.apply_subst(subst)).collect()
             ),
+            Type::Applied { constructor, args } => {
+                let args = args.iter().map(|t| t.apply_subst(subst)).collect();
+                let constructor = Type::Var(constructor.clone()).apply_subst(subst);
+                Type::apply_constructor(constructor, args).unwrap_or_else(|| self.clone())
+            }
+            Type::Forall { vars, body } => {
+                let inner: HashMap<String, Type> = subst
+                    .iter()
+                    .filter(|(var, _)| !vars.contains(var))
+                    .map(|(var, t)| (var.clone(), t.clone()))
+                    .collect();
+                Type::Forall {
+                    vars: vars.clone(),
+                    body: Box::new(body.apply_subst(&inner)),
+                }
+            }
+        }
+    }
+
+    /// `constructor` applied to `args`
+    ///
+    /// `constructor` is what a higher-kinded type variable stands for: a type
+    /// constructor given fewer arguments than it takes (`List`, `Result e`)
+    /// or another type variable. Returns `None` for anything else.
+    pub fn apply_constructor(constructor: Type, mut args: Vec<Type>) -> Option<Type> {
+        let typ = match constructor {
+            Type::Var(name) if args.is_empty() => Type::Var(name),
+            Type::Var(name) => Type::Applied {
+                constructor: name,
+                args,
+            },
+            Type::Applied {
+                constructor,
+                args: mut first,
+            } => {
+                first.append(&mut args);
+                Type::Applied {
+                    constructor,
+                    args: first,
+                }
+            }
+            Type::UserDefined {
+                name,
+                mut type_params,
+            } => {
+                type_params.append(&mut args);
+                match (name.as_str(), type_params.len()) {
+                    ("List", 1) => Type::List(Box::new(type_params.remove(0))),
+                    ("Option", 1) => Type::Option(Box::new(type_params.remove(0))),
+                    _ => Type::UserDefined { name, type_params },
+                }
+            }
+            _ => return None,
+        };
+        Some(typ)
+    }
+
+    /// Split off the last `arity` type arguments, giving the type constructor
+    /// a higher-kinded type variable applied to that many arguments stands for
+    pub fn split_constructor(&self, arity: usize) -> Option<(Type, Vec<Type>)> {
+        let constructor = |name: &str| Type::UserDefined {
+            name: name.to_string(),
+            type_params: Vec::new(),
+        };
+        match (self, arity) {
+            (_, 0) => Some((self.clone(), Vec::new())),
+            (Type::List(inner), 1) => Some((constructor("List"), vec![(**inner).clone()])),
+            (Type::Option(inner), 1) => Some((constructor("Option"), vec![(**inner).clone()])),
+            (Type::UserDefined { name, type_params }, _) if type_params.len() >= arity => {
+                let (first, rest) = type_params.split_at(type_params.len() - arity);
+                let constructor = Type::UserDefined {
+                    name: name.clone(),
+                    type_params: first.to_vec(),
+                };
+                Some((constructor, rest.to_vec()))
+            }
+            (Type::Applied { constructor, args }, _) if args.len() >= arity => {
+                let (first, rest) = args.split_at(args.len() - arity);
+                let constructor = Type::apply_constructor(
+                    Type::Var(constructor.clone()),
+                    first.to_vec(),
+                )?;
+                Some((constructor, rest.to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The record with `fields` followed by the fields of `rest`
+    ///
+    /// `rest` is what a row variable stands for: a closed record, an open
+    /// record or another row variable. Returns `None` for anything else.
+    pub fn extend_record(mut fields: Vec<(String, Type)>, rest: Type) -> Option<Type> {
+        let typ = match rest {
+            Type::Record { fields: more } => {
+                fields.extend(more);
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Type::Record { fields }
+            }
+            Type::ExtensibleRecord { fields: more, row } => {
+                fields.extend(more);
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Type::ExtensibleRecord { fields, row }
+            }
+            Type::Var(row) => Type::open_record(fields, row),
+            _ => return None,
+        };
+        Some(typ)
+    }
+
+    /// The record with at least `fields`, the rest standing for `row`
+    pub fn open_record(mut fields: Vec<(String, Type)>, row: String) -> Type {
+        if fields.is_empty() {
+            return Type::Var(row);
         }
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+        Type::ExtensibleRecord { fields, row }
     }
 }
@@ -0,0 +1,136 @@
+//! Coverage of evaluated code
+//!
+//! [`CoverageRecorder`] is an observer that counts how often each expression
+//! was evaluated and how often each branch of an `if` or `match` was taken,
+//! keyed by source span. Relating spans to files and definitions is left to
+//! whoever knows the source that was run.
+
+use crate::debugger::{CallInfo, EvalObserver};
+use std::collections::BTreeMap;
+use vibe_language::{Span, Value, XsError};
+
+/// Start and end offset of a span
+pub type SpanKey = (usize, usize);
+
+/// Hit counts gathered while the interpreter evaluates
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageRecorder {
+    /// Evaluations of the expression at each span
+    pub expressions: BTreeMap<SpanKey, u64>,
+    /// For the `if` or `match` at each span, how often each branch was taken
+    pub branches: BTreeMap<SpanKey, BTreeMap<usize, u64>>,
+}
+
+impl CoverageRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How often the expression at `span` was evaluated
+    pub fn hits(&self, span: &Span) -> u64 {
+        self.expressions
+            .get(&(span.start, span.end))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// How often `branch` of the `if` or `match` at `span` was taken
+    pub fn branch_hits(&self, span: &Span, branch: usize) -> u64 {
+        self.branches
+            .get(&(span.start, span.end))
+            .and_then(|branches| branches.get(&branch))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Add the counts of `other`, e.g. of another test run on the same source
+    pub fn merge(&mut self, other: &CoverageRecorder) {
+        for (span, hits) in &other.expressions {
+            *self.expressions.entry(*span).or_default() += hits;
+        }
+        for (span, branches) in &other.branches {
+            let counts = self.branches.entry(*span).or_default();
+            for (branch, hits) in branches {
+                *counts.entry(*branch).or_default() += hits;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expressions.is_empty()
+    }
+}
+
+impl EvalObserver for CoverageRecorder {
+    fn enter_apply(&mut self, _call: &CallInfo<'_>) {}
+
+    fn exit_apply(&mut self, _result: &Result<Value, XsError>) {}
+
+    fn expr(&mut self, span: &Span) {
+        *self.expressions.entry((span.start, span.end)).or_default() += 1;
+    }
+
+    fn branch(&mut self, span: &Span, branch: usize) {
+        *self
+            .branches
+            .entry((span.start, span.end))
+            .or_default()
+            .entry(branch)
+            .or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Interpreter, SharedObserver};
+    use std::sync::{Arc, Mutex};
+    use vibe_language::{Expr, Ident, Literal, Pattern};
+
+    #[test]
+    fn test_records_expressions_and_branches_taken() {
+        let span = |start, end| Span::new(start, end);
+        // if true then (match 2 { 1 -> "one"; _ -> "other" }) else "no"
+        let arms = vec![
+            (
+                Pattern::Literal(Literal::Int(1), span(20, 21)),
+                Expr::Literal(Literal::String("one".to_string()), span(25, 30)),
+            ),
+            (
+                Pattern::Wildcard(span(32, 33)),
+                Expr::Literal(Literal::String("other".to_string()), span(37, 44)),
+            ),
+        ];
+        let expr = Expr::If {
+            cond: Box::new(Expr::Literal(Literal::Bool(true), span(3, 7))),
+            then_expr: Box::new(Expr::Match {
+                expr: Box::new(Expr::Literal(Literal::Int(2), span(16, 17))),
+                cases: arms,
+                span: span(10, 46),
+            }),
+            else_expr: Box::new(Expr::Ident(Ident("no".to_string()), span(52, 56))),
+            span: span(0, 56),
+        };
+
+        let recorder = Arc::new(Mutex::new(CoverageRecorder::new()));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_observer(Some(recorder.clone() as SharedObserver));
+        let value = interpreter
+            .eval(&expr, &Interpreter::create_initial_env())
+            .unwrap();
+        assert_eq!(value, Value::String("other".to_string()));
+
+        let recorder = recorder.lock().unwrap();
+        assert_eq!(recorder.hits(&span(0, 56)), 1);
+        assert_eq!(recorder.hits(&span(37, 44)), 1);
+        assert_eq!(recorder.hits(&span(25, 30)), 0);
+        assert_eq!(recorder.hits(&span(52, 56)), 0);
+        assert_eq!(recorder.branch_hits(&span(0, 56), 0), 1);
+        assert_eq!(recorder.branch_hits(&span(0, 56), 1), 0);
+        assert_eq!(recorder.branch_hits(&span(10, 46), 1), 1);
+
+        let mut total = recorder.clone();
+        total.merge(&recorder);
+        assert_eq!(total.branch_hits(&span(10, 46), 1), 2);
+    }
+}
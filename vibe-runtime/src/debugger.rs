@@ -0,0 +1,627 @@
+//! Evaluation tracing and step debugging
+//!
+//! When an observer is set, the interpreter reports every function application
+//! (with its evaluated arguments and result), every match arm taken and every
+//! effect performed. [`Tracer`] records these as a tree, and [`Debugger`]
+//! pauses at breakpoints or after a step and hands the call stack to a
+//! [`DebugFrontend`], which decides how to continue.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use vibe_language::pretty_print::pretty_print;
+use vibe_language::{Environment, Expr, Literal, Pattern, Span, Value, XsError};
+
+/// Observer shared between the interpreter and whoever reads its results
+pub type SharedObserver = Arc<Mutex<dyn EvalObserver + Send>>;
+
+/// A function application about to happen
+pub struct CallInfo<'a> {
+    /// The applied expression as written, e.g. `double` or `List.map`
+    pub function: String,
+    /// Parameter names bound by this application, when the callee is a closure
+    pub params: Vec<String>,
+    pub args: &'a [Value],
+    pub span: &'a Span,
+    /// Environment at the call site
+    pub env: &'a Environment,
+}
+
+/// Hooks called by the interpreter while it evaluates
+pub trait EvalObserver {
+    fn enter_apply(&mut self, call: &CallInfo<'_>);
+
+    fn exit_apply(&mut self, result: &Result<Value, XsError>);
+
+    fn match_arm(&mut self, _arm: usize, _pattern: &Pattern, _value: &Value) {}
+
+    fn effect(&mut self, _effect: &str, _args: &[Value], _result: &Result<Value, XsError>) {}
+
+    /// An expression is about to be evaluated
+    fn expr(&mut self, _span: &Span) {}
+
+    /// Branch `branch` of the `if` or `match` at `span` was taken. For `if`
+    /// 0 is the `then` branch and 1 the `else` branch; for `match` it is the arm.
+    fn branch(&mut self, _span: &Span, _branch: usize) {}
+
+    /// Program output such as `print`. Return true to stop it from being
+    /// written to stdout.
+    fn output(&mut self, _text: &str) -> bool {
+        false
+    }
+}
+
+pub(crate) fn callee_name(func: &Expr) -> String {
+    match func {
+        Expr::Ident(name, _) => name.0.clone(),
+        Expr::QualifiedIdent {
+            module_name, name, ..
+        } => format!("{}.{}", module_name.0, name.0),
+        other => pretty_print(other),
+    }
+}
+
+pub(crate) fn param_names(func: &Value) -> Vec<String> {
+    match func {
+        Value::Closure { params, .. } | Value::RecClosure { params, .. } => {
+            params.iter().map(|p| p.0.clone()).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn format_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard(_) => "_".to_string(),
+        Pattern::Variable(name, _) => name.0.clone(),
+        Pattern::Literal(Literal::String(s), _) => format!("{s:?}"),
+        Pattern::Literal(Literal::Int(n), _) => n.to_string(),
+        Pattern::Literal(Literal::Float(f), _) => f.0.to_string(),
+        Pattern::Literal(Literal::Bool(b), _) => b.to_string(),
+        Pattern::Constructor { name, patterns, .. } if patterns.is_empty() => name.0.clone(),
+        Pattern::Constructor { name, patterns, .. } => {
+            let args: Vec<_> = patterns.iter().map(format_pattern).collect();
+            format!("({} {})", name.0, args.join(" "))
+        }
+        Pattern::List { patterns, .. } => {
+            let items: Vec<_> = patterns.iter().map(format_pattern).collect();
+            format!("[{}]", items.join(", "))
+        }
+    }
+}
+
+fn format_result(result: &Result<Value, String>) -> String {
+    match result {
+        Ok(value) => value.to_string(),
+        Err(e) => format!("error: {e}"),
+    }
+}
+
+/// One step of a recorded evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    Apply {
+        function: String,
+        args: Vec<Value>,
+        result: Result<Value, String>,
+        /// Steps taken while evaluating the function body
+        children: Vec<TraceEvent>,
+    },
+    MatchArm {
+        arm: usize,
+        pattern: String,
+        value: Value,
+    },
+    Effect {
+        effect: String,
+        args: Vec<Value>,
+        result: Result<Value, String>,
+    },
+}
+
+/// Records an evaluation as a tree of [`TraceEvent`]s
+#[derive(Debug, Default)]
+pub struct Tracer {
+    /// Applications that have not returned yet, with the events inside them
+    open: Vec<(String, Vec<Value>, Vec<TraceEvent>)>,
+    events: Vec<TraceEvent>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Top-level events, in evaluation order
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    fn push(&mut self, event: TraceEvent) {
+        match self.open.last_mut() {
+            Some((_, _, children)) => children.push(event),
+            None => self.events.push(event),
+        }
+    }
+
+    /// The trace as an indented tree, one event per line
+    pub fn render(&self) -> String {
+        fn render_event(event: &TraceEvent, depth: usize, out: &mut Vec<String>) {
+            let indent = "  ".repeat(depth);
+            match event {
+                TraceEvent::Apply {
+                    function,
+                    args,
+                    result,
+                    children,
+                } => {
+                    let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+                    let call = if args.is_empty() {
+                        function.clone()
+                    } else {
+                        format!("{} {}", function, args.join(" "))
+                    };
+                    out.push(format!("{indent}{call} => {}", format_result(result)));
+                    for child in children {
+                        render_event(child, depth + 1, out);
+                    }
+                }
+                TraceEvent::MatchArm {
+                    arm,
+                    pattern,
+                    value,
+                } => out.push(format!("{indent}match {value} -> arm {arm}: {pattern}")),
+                TraceEvent::Effect {
+                    effect,
+                    args,
+                    result,
+                } => {
+                    let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+                    out.push(format!(
+                        "{indent}perform {} {} => {}",
+                        effect,
+                        args.join(" "),
+                        format_result(result)
+                    ));
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        for event in &self.events {
+            render_event(event, 0, &mut lines);
+        }
+        lines.join("\n")
+    }
+}
+
+impl EvalObserver for Tracer {
+    fn enter_apply(&mut self, call: &CallInfo<'_>) {
+        self.open
+            .push((call.function.clone(), call.args.to_vec(), Vec::new()));
+    }
+
+    fn exit_apply(&mut self, result: &Result<Value, XsError>) {
+        if let Some((function, args, children)) = self.open.pop() {
+            self.push(TraceEvent::Apply {
+                function,
+                args,
+                result: result.clone().map_err(|e| e.to_string()),
+                children,
+            });
+        }
+    }
+
+    fn match_arm(&mut self, arm: usize, pattern: &Pattern, value: &Value) {
+        self.push(TraceEvent::MatchArm {
+            arm,
+            pattern: format_pattern(pattern),
+            value: value.clone(),
+        });
+    }
+
+    fn effect(&mut self, effect: &str, args: &[Value], result: &Result<Value, XsError>) {
+        self.push(TraceEvent::Effect {
+            effect: effect.to_string(),
+            args: args.to_vec(),
+            result: result.clone().map_err(|e| e.to_string()),
+        });
+    }
+}
+
+/// A function application on the debugger's call stack
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub function: String,
+    pub params: Vec<String>,
+    pub args: Vec<Value>,
+    pub span: Span,
+    pub env: Environment,
+}
+
+impl Frame {
+    /// Bindings visible inside the frame: parameters first, then the
+    /// call-site environment from the most recent binding outwards. Shadowed
+    /// names and builtins are left out.
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut seen = HashSet::new();
+        let params = self.params.iter().cloned().zip(self.args.iter().cloned());
+        let env = self
+            .env
+            .bindings()
+            .rev()
+            .filter(|(_, value)| !matches!(value, Value::BuiltinFunction { .. }))
+            .map(|(name, value)| (name.0.clone(), value.clone()));
+        params
+            .chain(env)
+            .filter(|(name, _)| seen.insert(name.clone()))
+            .collect()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.bindings()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Shows the call as written, e.g. `double 3`
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the debugger paused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Entered a function with a breakpoint on its name
+    Breakpoint(String),
+    /// Finished a step
+    Step,
+    /// Paused before the first application
+    Entry,
+}
+
+/// The state handed to a frontend when the debugger pauses
+#[derive(Debug, Clone)]
+pub struct StopEvent {
+    pub reason: StopReason,
+    /// Call stack, outermost first
+    pub frames: Vec<Frame>,
+}
+
+/// How to continue after a pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+}
+
+/// User interface of the debugger, e.g. a console or a DAP connection
+pub trait DebugFrontend: Send {
+    /// Called with the current stack whenever evaluation pauses. Blocks until
+    /// the user decides how to continue.
+    fn pause(&mut self, stop: &StopEvent) -> DebugCommand;
+
+    /// Program output. Return true if the frontend displayed it itself.
+    fn output(&mut self, _text: &str) -> bool {
+        false
+    }
+}
+
+/// Breakpoints on definition names, shared so they can change while running
+pub type Breakpoints = Arc<Mutex<BTreeSet<String>>>;
+
+#[derive(Debug, Clone, Copy)]
+enum StepMode {
+    Run,
+    /// Pause at the next application
+    StepIn(StopReasonKind),
+    /// Pause at the next application at most this deep
+    StepOver(usize),
+    /// Pause at the next application shallower than this
+    StepOut(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StopReasonKind {
+    Entry,
+    Step,
+}
+
+/// Step debugger with breakpoints on function names
+pub struct Debugger {
+    breakpoints: Breakpoints,
+    stack: Vec<Frame>,
+    mode: StepMode,
+    frontend: Box<dyn DebugFrontend>,
+}
+
+impl Debugger {
+    pub fn new(frontend: Box<dyn DebugFrontend>) -> Self {
+        Self {
+            breakpoints: Breakpoints::default(),
+            stack: Vec::new(),
+            mode: StepMode::Run,
+            frontend,
+        }
+    }
+
+    /// Pause before the first application instead of running to a breakpoint
+    pub fn stop_on_entry(mut self) -> Self {
+        self.mode = StepMode::StepIn(StopReasonKind::Entry);
+        self
+    }
+
+    pub fn with_breakpoints(self, names: impl IntoIterator<Item = String>) -> Self {
+        self.breakpoints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(names);
+        self
+    }
+
+    /// Use breakpoints owned by someone else, e.g. set before the program starts
+    pub fn with_breakpoint_handle(mut self, breakpoints: Breakpoints) -> Self {
+        self.breakpoints = breakpoints;
+        self
+    }
+
+    /// Handle for changing breakpoints while the program runs
+    pub fn breakpoints(&self) -> Breakpoints {
+        self.breakpoints.clone()
+    }
+
+    fn stop_reason(&self, function: &str) -> Option<StopReason> {
+        let depth = self.stack.len();
+        if self
+            .breakpoints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(function)
+        {
+            return Some(StopReason::Breakpoint(function.to_string()));
+        }
+        match self.mode {
+            StepMode::StepIn(StopReasonKind::Entry) => Some(StopReason::Entry),
+            StepMode::StepIn(StopReasonKind::Step) => Some(StopReason::Step),
+            StepMode::StepOver(max) if depth <= max => Some(StopReason::Step),
+            StepMode::StepOut(below) if depth < below => Some(StopReason::Step),
+            _ => None,
+        }
+    }
+}
+
+impl EvalObserver for Debugger {
+    fn enter_apply(&mut self, call: &CallInfo<'_>) {
+        self.stack.push(Frame {
+            function: call.function.clone(),
+            params: call.params.clone(),
+            args: call.args.to_vec(),
+            span: call.span.clone(),
+            env: call.env.clone(),
+        });
+
+        if let Some(reason) = self.stop_reason(&call.function) {
+            let stop = StopEvent {
+                reason,
+                frames: self.stack.clone(),
+            };
+            let depth = self.stack.len();
+            self.mode = match self.frontend.pause(&stop) {
+                DebugCommand::Continue => StepMode::Run,
+                DebugCommand::StepIn => StepMode::StepIn(StopReasonKind::Step),
+                DebugCommand::StepOver => StepMode::StepOver(depth),
+                DebugCommand::StepOut => StepMode::StepOut(depth),
+            };
+        }
+    }
+
+    fn exit_apply(&mut self, _result: &Result<Value, XsError>) {
+        self.stack.pop();
+    }
+
+    fn output(&mut self, text: &str) -> bool {
+        self.frontend.output(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+    use vibe_language::{Ident, Span};
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(Ident(name.to_string()), Span::new(0, 0))
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Int(n), Span::new(0, 0))
+    }
+
+    fn apply(func: Expr, args: Vec<Expr>) -> Expr {
+        Expr::Apply {
+            func: Box::new(func),
+            args,
+            span: Span::new(0, 0),
+        }
+    }
+
+    /// Environment with `double = fn x -> x * 2` and `quad = fn x -> double (double x)`
+    fn env() -> Environment {
+        let mut interpreter = Interpreter::new();
+        let base = Interpreter::create_initial_env();
+        let lambda = |body: Expr| Expr::Lambda {
+            params: vec![(Ident("x".to_string()), None)],
+            body: Box::new(body),
+            span: Span::new(0, 0),
+        };
+        let double = interpreter
+            .eval(&lambda(apply(ident("*"), vec![ident("x"), int(2)])), &base)
+            .unwrap();
+        let env = base.extend(Ident("double".to_string()), double);
+        let quad = interpreter
+            .eval(
+                &lambda(apply(
+                    ident("double"),
+                    vec![apply(ident("double"), vec![ident("x")])],
+                )),
+                &env,
+            )
+            .unwrap();
+        env.extend(Ident("quad".to_string()), quad)
+    }
+
+    fn run(expr: &Expr, observer: SharedObserver) -> Value {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_observer(Some(observer));
+        interpreter.eval(expr, &env()).unwrap()
+    }
+
+    #[test]
+    fn test_trace_tree() {
+        let tracer = Arc::new(Mutex::new(Tracer::new()));
+        let value = run(&apply(ident("quad"), vec![int(3)]), tracer.clone());
+        assert_eq!(value, Value::Int(12));
+
+        let trace = tracer.lock().unwrap().render();
+        assert_eq!(
+            trace,
+            "quad 3 => 12\n  double 3 => 6\n    * 3 2 => 6\n  double 6 => 12\n    * 6 2 => 12"
+        );
+    }
+
+    #[test]
+    fn test_trace_match_and_effect() {
+        let expr = Expr::Match {
+            expr: Box::new(int(2)),
+            cases: vec![
+                (Pattern::Literal(Literal::Int(1), Span::new(0, 0)), int(10)),
+                (
+                    Pattern::Variable(Ident("n".to_string()), Span::new(0, 0)),
+                    Expr::Perform {
+                        effect: Ident("print".to_string()),
+                        args: vec![Expr::Literal(
+                            Literal::String("hi".to_string()),
+                            Span::new(0, 0),
+                        )],
+                        span: Span::new(0, 0),
+                    },
+                ),
+            ],
+            span: Span::new(0, 0),
+        };
+        let tracer = Arc::new(Mutex::new(Tracer::new()));
+        run(&expr, tracer.clone());
+
+        let tracer = tracer.lock().unwrap();
+        assert_eq!(
+            tracer.events()[0],
+            TraceEvent::MatchArm {
+                arm: 1,
+                pattern: "n".to_string(),
+                value: Value::Int(2),
+            }
+        );
+        assert!(tracer.render().contains("perform print \"hi\" => (Unit)"));
+    }
+
+    /// Each pause with the function names on the stack
+    type Stops = Vec<(StopReason, Vec<String>)>;
+
+    /// Replays a fixed list of commands and records where it stopped
+    struct Scripted {
+        commands: Vec<DebugCommand>,
+        stops: Arc<Mutex<Stops>>,
+    }
+
+    impl DebugFrontend for Scripted {
+        fn pause(&mut self, stop: &StopEvent) -> DebugCommand {
+            let stack = stop.frames.iter().map(|f| f.function.clone()).collect();
+            self.stops
+                .lock()
+                .unwrap()
+                .push((stop.reason.clone(), stack));
+            if self.commands.is_empty() {
+                DebugCommand::Continue
+            } else {
+                self.commands.remove(0)
+            }
+        }
+    }
+
+    fn debug(commands: Vec<DebugCommand>, breakpoints: &[&str], entry: bool) -> Stops {
+        let stops = Arc::new(Mutex::new(Vec::new()));
+        let frontend = Scripted {
+            commands,
+            stops: stops.clone(),
+        };
+        let mut debugger = Debugger::new(Box::new(frontend))
+            .with_breakpoints(breakpoints.iter().map(|b| b.to_string()));
+        if entry {
+            debugger = debugger.stop_on_entry();
+        }
+        run(
+            &apply(ident("quad"), vec![int(3)]),
+            Arc::new(Mutex::new(debugger)),
+        );
+        let stops = stops.lock().unwrap().clone();
+        stops
+    }
+
+    #[test]
+    fn test_breakpoint_and_continue() {
+        let stops = debug(vec![], &["double"], false);
+        let stacks: Vec<_> = stops.iter().map(|(_, stack)| stack.join(" > ")).collect();
+        assert_eq!(stacks, vec!["quad > double", "quad > double"]);
+        assert_eq!(stops[0].0, StopReason::Breakpoint("double".to_string()));
+    }
+
+    #[test]
+    fn test_step_in_over_out() {
+        use DebugCommand::*;
+
+        let stops = debug(vec![StepIn, StepIn, StepOut], &[], true);
+        let stacks: Vec<_> = stops.iter().map(|(_, stack)| stack.join(" > ")).collect();
+        assert_eq!(
+            stacks,
+            vec![
+                "quad",
+                "quad > double",
+                "quad > double > *",
+                "quad > double"
+            ]
+        );
+        assert_eq!(stops[0].0, StopReason::Entry);
+
+        let stops = debug(vec![StepIn, StepOver], &[], true);
+        let stacks: Vec<_> = stops.iter().map(|(_, stack)| stack.join(" > ")).collect();
+        assert_eq!(stacks, vec!["quad", "quad > double", "quad > double"]);
+    }
+
+    #[test]
+    fn test_frame_bindings() {
+        let frame = Frame {
+            function: "double".to_string(),
+            params: vec!["x".to_string()],
+            args: vec![Value::Int(3)],
+            span: Span::new(0, 0),
+            env: env().extend(Ident("x".to_string()), Value::Int(99)),
+        };
+        let bindings = frame.bindings();
+        assert_eq!(bindings[0], ("x".to_string(), Value::Int(3)));
+        assert_eq!(bindings.iter().filter(|(n, _)| n == "x").count(), 1);
+        assert!(bindings.iter().any(|(n, _)| n == "quad"));
+        assert!(!bindings.iter().any(|(n, _)| n == "+"));
+        assert_eq!(frame.lookup("x"), Some(Value::Int(3)));
+    }
+}
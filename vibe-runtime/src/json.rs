@@ -0,0 +1,204 @@
+//! `Json.parse`, `Json.stringify` and `Json.readFile`
+//!
+//! Parsed JSON is a `JsonValue`:
+//!
+//! ```text
+//! type JsonValue = JsonNull | JsonBool Bool | JsonInt Int | JsonFloat Float
+//!                | JsonString String | JsonArray (List JsonValue)
+//!                | JsonObject (List JsonField)
+//! type JsonField = JsonField String JsonValue
+//! ```
+//!
+//! `JsonObject` holds one `JsonField` per key, in key order.
+//! `stringify` takes a `JsonValue` or plain Vibe records, lists and scalars.
+
+use vibe_language::{Ident, Value};
+
+/// Parse JSON text into a `JsonValue`
+pub fn parse(text: &str) -> Result<Value, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| format!("invalid JSON: {e}"))?;
+    Ok(from_json(&json))
+}
+
+/// Render a `JsonValue`, or a plain value, as compact JSON text
+pub fn stringify(value: &Value) -> Result<String, String> {
+    Ok(to_json(value)?.to_string())
+}
+
+/// Read and parse a JSON file
+pub fn read_file(path: &str) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    parse(&text).map_err(|e| format!("{path}: {e}"))
+}
+
+fn constructor(name: &str, values: Vec<Value>) -> Value {
+    Value::Constructor {
+        name: Ident(name.to_string()),
+        values,
+    }
+}
+
+fn from_json(json: &serde_json::Value) -> Value {
+    match json {
+        serde_json::Value::Null => constructor("JsonNull", vec![]),
+        serde_json::Value::Bool(b) => constructor("JsonBool", vec![Value::Bool(*b)]),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => constructor("JsonInt", vec![Value::Int(i)]),
+            None => constructor(
+                "JsonFloat",
+                vec![Value::Float(n.as_f64().unwrap_or(f64::NAN))],
+            ),
+        },
+        serde_json::Value::String(s) => constructor("JsonString", vec![Value::String(s.clone())]),
+        serde_json::Value::Array(items) => constructor(
+            "JsonArray",
+            vec![Value::List(items.iter().map(from_json).collect())],
+        ),
+        serde_json::Value::Object(map) => {
+            // serde_json keeps keys sorted
+            let fields = map
+                .iter()
+                .map(|(key, value)| {
+                    constructor(
+                        "JsonField",
+                        vec![Value::String(key.clone()), from_json(value)],
+                    )
+                })
+                .collect();
+            constructor("JsonObject", vec![Value::List(fields)])
+        }
+    }
+}
+
+fn to_json(value: &Value) -> Result<serde_json::Value, String> {
+    Ok(match value {
+        Value::Constructor { name, values } => match (name.0.as_str(), values.as_slice()) {
+            ("JsonNull", []) => serde_json::Value::Null,
+            ("JsonBool" | "JsonInt" | "JsonFloat" | "JsonString" | "JsonArray", [inner]) => {
+                to_json(inner)?
+            }
+            ("JsonObject", [Value::List(fields)]) => serde_json::Value::Object(
+                fields
+                    .iter()
+                    .map(|field| match field {
+                        Value::Constructor { name, values } if name.0 == "JsonField" => {
+                            match values.as_slice() {
+                                [Value::String(key), value] => Ok((key.clone(), to_json(value)?)),
+                                _ => Err(format!("cannot convert {field} to a JSON field")),
+                            }
+                        }
+                        _ => Err(format!("cannot convert {field} to a JSON field")),
+                    })
+                    .collect::<Result<_, String>>()?,
+            ),
+            ("None", []) => serde_json::Value::Null,
+            ("Some", [inner]) => to_json(inner)?,
+            _ => return Err(format!("cannot convert {value} to JSON")),
+        },
+        Value::Int(n) => serde_json::Value::from(*n),
+        Value::Float(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| format!("cannot convert {f} to JSON"))?,
+        Value::Bool(b) => serde_json::Value::Bool(*b),
+        Value::String(s) => serde_json::Value::String(s.clone()),
+        Value::List(items) => {
+            serde_json::Value::Array(items.iter().map(to_json).collect::<Result<_, _>>()?)
+        }
+        Value::Record { fields } => serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), to_json(value)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        _ => return Err(format!("cannot convert {value} to JSON")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_stringify_round_trip() {
+        let text = r#"{"name":"ada","scores":[1,2.5],"admin":false,"team":null}"#;
+        let value = parse(text).unwrap();
+        let Value::Constructor { name, values } = &value else {
+            panic!("{value:?}");
+        };
+        assert_eq!(name.0, "JsonObject");
+        let Value::List(fields) = &values[0] else {
+            panic!("{values:?}");
+        };
+        assert_eq!(fields.len(), 4);
+        assert_eq!(
+            fields[2].to_string(),
+            "(JsonField \"scores\" (JsonArray (list (JsonInt 1) (JsonFloat 2.5))))"
+        );
+
+        assert_eq!(
+            stringify(&value).unwrap(),
+            r#"{"admin":false,"name":"ada","scores":[1,2.5],"team":null}"#
+        );
+    }
+
+    #[test]
+    fn test_objects_have_a_concrete_type() {
+        use vibe_language::{Expr, Ident, Literal, Span};
+
+        let span = || Span::new(0, 0);
+        let apply = |name: &str, args: Vec<Expr>| Expr::Apply {
+            func: Box::new(Expr::Ident(Ident(name.to_string()), span())),
+            args,
+            span: span(),
+        };
+        let int = |n: i64| Expr::Literal(Literal::Int(n), span());
+        let field = |value: Expr| {
+            apply(
+                "JsonField",
+                vec![
+                    Expr::Literal(Literal::String("a".to_string()), span()),
+                    value,
+                ],
+            )
+        };
+        let check = |expr: Expr| {
+            vibe_compiler::TypeChecker::new()
+                .check_spanned(&expr, &mut vibe_compiler::TypeEnv::new())
+                .map(|typ| typ.to_string())
+        };
+
+        // JsonObject [JsonField "a" (JsonInt 1)]
+        let object = apply(
+            "JsonObject",
+            vec![Expr::List(
+                vec![field(apply("JsonInt", vec![int(1)]))],
+                span(),
+            )],
+        );
+        assert_eq!(check(object).unwrap(), "JsonValue");
+
+        // JsonObject { a: JsonInt 1 }
+        let record = Expr::RecordLiteral {
+            fields: vec![(Ident("a".to_string()), apply("JsonInt", vec![int(1)]))],
+            span: span(),
+        };
+        assert!(check(apply("JsonObject", vec![record])).is_err());
+
+        // JsonObject [JsonField "a" 1]
+        let untagged = apply("JsonObject", vec![Expr::List(vec![field(int(1))], span())]);
+        assert!(check(untagged).is_err());
+    }
+
+    #[test]
+    fn test_stringify_plain_values() {
+        let value = Value::List(vec![Value::Record {
+            fields: vec![
+                ("age".to_string(), Value::Int(36)),
+                ("name".to_string(), Value::String("ada".to_string())),
+            ],
+        }]);
+        assert_eq!(stringify(&value).unwrap(), r#"[{"age":36,"name":"ada"}]"#);
+        assert!(parse("{oops").unwrap_err().starts_with("invalid JSON"));
+    }
+}
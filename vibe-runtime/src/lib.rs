@@ -4,6 +4,7 @@
 //! for the XS language.
 
 use std::collections::HashMap;
+use std::sync::MutexGuard;
 use thiserror::Error;
 use vibe_language::{
     DoStatement, Environment, Expr, Ident, Literal, Pattern, Span, TypeDefinition, Value, XsError,
@@ -11,10 +12,16 @@ use vibe_language::{
 
 // Backend module for different execution strategies
 pub mod backend;
+pub mod coverage;
+pub mod debugger;
 pub mod effect_runtime;
+pub mod json;
+pub mod property;
 
 // Re-export important types
 pub use backend::{Backend, InterpreterBackend};
+pub use coverage::CoverageRecorder;
+pub use debugger::{CallInfo, EvalObserver, SharedObserver};
 // use backend::literal_to_value;
 
 /// Runtime errors
@@ -76,6 +83,38 @@ impl<B: Backend> Runtime<B> {
 #[derive(Default)]
 pub struct Interpreter {
     type_definitions: HashMap<String, TypeDefinition>,
+    /// Notified of evaluated expressions, applications, branches and effects
+    /// when set
+    observer: Option<SharedObserver>,
+    /// Values of the `mut` variables of the blocks being evaluated, by id
+    mutable_cells: Vec<(u64, Value)>,
+    next_cell: u64,
+}
+
+/// Name of the constructor a `mut` variable is bound to in the environment;
+/// it holds the id of the variable's cell
+const MUT_CELL: &str = "<mut>";
+
+fn lock_observer(observer: &SharedObserver) -> MutexGuard<'_, dyn EvalObserver + Send + 'static> {
+    observer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The cell id of a `mut` variable's binding
+fn mutable_cell(value: &Value) -> Option<u64> {
+    match value {
+        Value::Constructor { name, values } if name.0 == MUT_CELL => match values.as_slice() {
+            [Value::Int(id)] => Some(*id as u64),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn unit() -> Value {
+    Value::Constructor {
+        name: Ident("Unit".to_string()),
+        values: vec![],
+    }
 }
 
 impl Interpreter {
@@ -83,6 +122,11 @@ impl Interpreter {
         Self::default()
     }
 
+    /// Report evaluation steps to `observer`, or stop reporting with `None`
+    pub fn set_observer(&mut self, observer: Option<SharedObserver>) {
+        self.observer = observer;
+    }
+
     pub fn get_lib_runtime_functions(&self) -> HashMap<String, Value> {
         let mut functions = HashMap::new();
 
@@ -183,6 +227,17 @@ impl Interpreter {
         functions
     }
 
+    /// The runtime functions of the library module at `path`
+    pub fn get_module_runtime_functions(&self, path: &[String]) -> HashMap<String, Value> {
+        match path.iter().map(|s| s.as_str()).collect::<Vec<_>>().as_slice() {
+            ["lib"] => self.get_lib_runtime_functions(),
+            ["lib", "String"] => self.get_string_runtime_functions(),
+            ["lib", "List"] => self.get_list_runtime_functions(),
+            ["lib", "Int"] => self.get_int_runtime_functions(),
+            _ => HashMap::new(),
+        }
+    }
+
     pub fn create_initial_env() -> Environment {
         let mut env = Environment::new();
 
@@ -481,6 +536,18 @@ impl Interpreter {
                 applied_args: vec![],
             },
         );
+
+        // Json module
+        for name in ["jsonParse", "jsonStringify", "jsonReadFile"] {
+            env = env.extend(
+                Ident(name.to_string()),
+                Value::BuiltinFunction {
+                    name: name.to_string(),
+                    arity: 1,
+                    applied_args: vec![],
+                },
+            );
+        }
         
         // Test-related builtin functions
         env = env.extend(
@@ -491,6 +558,17 @@ impl Interpreter {
                 applied_args: vec![],
             },
         );
+        // `forAll` is another name for `property`
+        for name in ["property", "forAll"] {
+            env = env.extend(
+                Ident(name.to_string()),
+                Value::BuiltinFunction {
+                    name: "property".to_string(),
+                    arity: 2,
+                    applied_args: vec![],
+                },
+            );
+        }
         env = env.extend(
             Ident("assert".to_string()),
             Value::BuiltinFunction {
@@ -512,6 +590,9 @@ impl Interpreter {
     }
 
     pub fn eval(&mut self, expr: &Expr, env: &Environment) -> Result<Value, XsError> {
+        if let Some(observer) = &self.observer {
+            lock_observer(observer).expr(expr.span());
+        }
         match expr {
             Expr::Literal(lit, _) => Ok(match lit {
                 Literal::Int(n) => Value::Int(*n),
@@ -532,12 +613,16 @@ impl Interpreter {
                     }
                     _ => {
                         // Look up in environment
-                        env.lookup(name).cloned().ok_or_else(|| {
+                        let value = env.lookup(name).ok_or_else(|| {
                             XsError::RuntimeError(
                                 span.clone(),
                                 format!("Undefined variable: {name}"),
                             )
-                        })
+                        })?;
+                        match mutable_cell(value) {
+                            Some(id) => self.cell(id, name, span).map(|cell| cell.clone()),
+                            None => Ok(value.clone()),
+                        }
                     }
                 }
             }
@@ -644,6 +729,9 @@ impl Interpreter {
                 span,
             } => {
                 let cond_val = self.eval(cond, env)?;
+                if let (Some(observer), Value::Bool(taken)) = (&self.observer, &cond_val) {
+                    lock_observer(observer).branch(span, usize::from(!taken));
+                }
                 match cond_val {
                     Value::Bool(true) => self.eval(then_expr, env),
                     Value::Bool(false) => self.eval(else_expr, env),
@@ -656,146 +744,37 @@ impl Interpreter {
 
             Expr::Apply { func, args, span } => {
                 let func_val = self.eval(func, env)?;
-
-                match &func_val {
-                    Value::Closure {
-                        params,
-                        body,
-                        env: closure_env,
-                    } => {
-                        if args.len() > params.len() {
-                            return Err(XsError::RuntimeError(
-                                span.clone(),
-                                format!(
-                                    "Function expects {} arguments, got {}",
-                                    params.len(),
-                                    args.len()
-                                ),
-                            ));
-                        }
-
-                        if args.len() < params.len() {
-                            // Partial application
-                            let mut partial_env = closure_env.clone();
-                            let mut remaining_params = params.clone();
-
-                            for (i, arg) in args.iter().enumerate() {
-                                let arg_val = self.eval(arg, env)?;
-                                partial_env = partial_env.extend(params[i].clone(), arg_val);
-                                remaining_params.remove(0);
-                            }
-
-                            Ok(Value::Closure {
-                                params: remaining_params,
-                                body: body.clone(),
-                                env: partial_env,
-                            })
-                        } else {
-                            // Full application
-                            let mut new_env = closure_env.clone();
-                            for (param, arg) in params.iter().zip(args.iter()) {
-                                let arg_val = self.eval(arg, env)?;
-                                new_env = new_env.extend(param.clone(), arg_val);
-                            }
-
-                            self.eval(body, &new_env)
-                        }
-                    }
-                    Value::RecClosure {
-                        name,
-                        params,
-                        body,
-                        env: closure_env,
-                    } => {
-                        if args.len() > params.len() {
-                            return Err(XsError::RuntimeError(
-                                span.clone(),
-                                format!(
-                                    "Function expects {} arguments, got {}",
-                                    params.len(),
-                                    args.len()
-                                ),
-                            ));
-                        }
-
-                        if args.len() < params.len() {
-                            // Partial application of recursive function
-                            let mut partial_env = closure_env.clone();
-                            partial_env = partial_env.extend(name.clone(), func_val.clone());
-
-                            // Apply the given arguments
-                            let mut remaining_params = params.clone();
-                            for (i, arg) in args.iter().enumerate() {
-                                let arg_val = self.eval(arg, env)?;
-                                partial_env = partial_env.extend(params[i].clone(), arg_val);
-                                remaining_params.remove(0);
-                            }
-
-                            // Create a new closure with remaining parameters
-                            Ok(Value::Closure {
-                                params: remaining_params,
-                                body: body.clone(),
-                                env: partial_env,
-                            })
-                        } else {
-                            // Full application
-                            let mut new_env = closure_env.clone();
-                            new_env = new_env.extend(name.clone(), func_val.clone());
-
-                            for (param, arg) in params.iter().zip(args.iter()) {
-                                let arg_val = self.eval(arg, env)?;
-                                new_env = new_env.extend(param.clone(), arg_val);
-                            }
-
-                            self.eval(body, &new_env)
-                        }
-                    }
-                    Value::BuiltinFunction {
-                        name,
-                        arity,
-                        applied_args,
-                    } => {
-                        let mut all_args = applied_args.clone();
-
-                        // Evaluate and add new arguments
-                        for arg in args {
-                            all_args.push(self.eval(arg, env)?);
-                        }
-
-                        if all_args.len() < *arity {
-                            // Partial application - return a new builtin with more args
-                            Ok(Value::BuiltinFunction {
-                                name: name.clone(),
-                                arity: *arity,
-                                applied_args: all_args,
-                            })
-                        } else if all_args.len() == *arity {
-                            // Full application - execute the builtin
-                            self.execute_builtin(name, &all_args, span)
-                        } else {
-                            Err(XsError::RuntimeError(
-                                span.clone(),
-                                format!(
-                                    "{} expects {} arguments, got {}",
-                                    name,
-                                    arity,
-                                    all_args.len()
-                                ),
-                            ))
-                        }
-                    }
-                    _ => Err(XsError::RuntimeError(
-                        span.clone(),
-                        "Cannot apply non-function value".to_string(),
-                    )),
+                let mut arg_vals = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_vals.push(self.eval(arg, env)?);
                 }
+
+                let Some(observer) = self.observer.clone() else {
+                    return self.apply_values(func_val, arg_vals, span);
+                };
+                let call = CallInfo {
+                    function: debugger::callee_name(func),
+                    params: debugger::param_names(&func_val),
+                    args: &arg_vals,
+                    span,
+                    env,
+                };
+                lock_observer(&observer).enter_apply(&call);
+                let result = self.apply_values(func_val, arg_vals.clone(), span);
+                lock_observer(&observer).exit_apply(&result);
+                result
             }
 
             Expr::Match { expr, cases, span } => {
                 let value = self.eval(expr, env)?;
 
-                for (pattern, case_expr) in cases {
+                for (arm, (pattern, case_expr)) in cases.iter().enumerate() {
                     if let Some(bindings) = self.match_pattern(pattern, &value)? {
+                        if let Some(observer) = &self.observer {
+                            let mut observer = lock_observer(observer);
+                            observer.match_arm(arm, pattern, &value);
+                            observer.branch(span, arm);
+                        }
                         // Create new environment with pattern bindings
                         let mut new_env = env.clone();
                         for (name, val) in bindings {
@@ -872,6 +851,11 @@ impl Interpreter {
                 // Look up builtin module functions
                 let builtin_key = format!("{}.{}", module_name.0, name.0);
 
+                // Package modules bind their exports under qualified names
+                if let Some(value) = env.lookup(&Ident(builtin_key.clone())) {
+                    return Ok(value.clone());
+                }
+
                 // Map new namespace names to existing builtin functions
                 let mapped_name = match builtin_key.as_str() {
                     // Int module
@@ -904,6 +888,11 @@ impl Interpreter {
                     // Float module
                     "Float.add" => "+.",
 
+                    // Json module
+                    "Json.parse" => "jsonParse",
+                    "Json.stringify" => "jsonStringify",
+                    "Json.readFile" => "jsonReadFile",
+
                     _ => {
                         return Err(XsError::RuntimeError(
                             span.clone(),
@@ -937,38 +926,15 @@ impl Interpreter {
             }
 
             Expr::Perform { effect, args, .. } => {
-                // For simple built-in effects, handle them directly
-                match effect.0.as_str() {
-                    "print" => {
-                        if let Some(arg) = args.get(0) {
-                            let val = self.eval(arg, env)?;
-                            if let Value::String(s) = val {
-                                println!("{}", s);
-                                Ok(Value::Constructor {
-                                    name: Ident("Unit".to_string()),
-                                    values: vec![],
-                                })
-                            } else {
-                                Err(XsError::RuntimeError(
-                                    Span::new(0, 0),
-                                    "print expects a string argument".to_string(),
-                                ))
-                            }
-                        } else {
-                            Err(XsError::RuntimeError(
-                                Span::new(0, 0),
-                                "print expects an argument".to_string(),
-                            ))
-                        }
-                    }
-                    _ => {
-                        // TODO: Implement general effect handling
-                        Err(XsError::RuntimeError(
-                            Span::new(0, 0),
-                            format!("Effect '{}' not yet implemented", effect.0),
-                        ))
-                    }
+                let mut arg_vals = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_vals.push(self.eval(arg, env)?);
+                }
+                let result = self.perform_effect(effect, &arg_vals);
+                if let Some(observer) = &self.observer {
+                    lock_observer(observer).effect(&effect.0, &arg_vals, &result);
                 }
+                result
             }
 
             Expr::Pipeline { expr, func, .. } => {
@@ -1026,24 +992,74 @@ impl Interpreter {
                 } else {
                     let mut result = Value::Int(0);
                     let mut local_env = env.clone();
+                    // `mut` variables live until the block returns
+                    let cells = self.mutable_cells.len();
 
                     for expr in exprs {
                         // Handle let expressions specially to update the environment
-                        match expr {
+                        let evaluated = match expr {
                             Expr::Let { name, value, .. } => {
-                                let val = self.eval(value, &local_env)?;
-                                local_env = local_env.extend(name.clone(), val.clone());
-                                result = val;
+                                self.eval(value, &local_env).map(|val| {
+                                    local_env = local_env.extend(name.clone(), val.clone());
+                                    val
+                                })
                             }
-                            _ => {
-                                result = self.eval(expr, &local_env)?;
+                            Expr::Mut { name, value, .. } => {
+                                self.eval(value, &local_env).map(|val| {
+                                    self.next_cell += 1;
+                                    self.mutable_cells.push((self.next_cell, val));
+                                    let cell = Value::Constructor {
+                                        name: Ident(MUT_CELL.to_string()),
+                                        values: vec![Value::Int(self.next_cell as i64)],
+                                    };
+                                    local_env = local_env.extend(name.clone(), cell);
+                                    unit()
+                                })
+                            }
+                            // Imports in a module body bind the library functions
+                            Expr::Use { path, items, .. } => {
+                                for (name, value) in self.get_module_runtime_functions(path) {
+                                    let imported = items.as_ref().map_or(true, |items| {
+                                        items.iter().any(|item| item.0 == name)
+                                    });
+                                    if imported {
+                                        local_env = local_env.extend(Ident(name), value);
+                                    }
+                                }
+                                Ok(unit())
+                            }
+                            _ => self.eval(expr, &local_env),
+                        };
+                        match evaluated {
+                            Ok(value) => result = value,
+                            Err(e) => {
+                                self.mutable_cells.truncate(cells);
+                                return Err(e);
                             }
                         }
                     }
+                    self.mutable_cells.truncate(cells);
                     Ok(result)
                 }
             }
 
+            Expr::Mut { span, .. } => Err(XsError::RuntimeError(
+                span.clone(),
+                "`mut` declares a variable only inside a block".to_string(),
+            )),
+
+            Expr::Assign { name, value, span } => {
+                let value = self.eval(value, env)?;
+                let id = env.lookup(name).and_then(mutable_cell).ok_or_else(|| {
+                    XsError::RuntimeError(
+                        span.clone(),
+                        format!("Cannot assign to `{name}`: it is not declared with `mut`"),
+                    )
+                })?;
+                *self.cell(id, name, span)? = value;
+                Ok(unit())
+            }
+
             Expr::Hole { name, span, .. } => Err(XsError::RuntimeError(
                 span.clone(),
                 format!(
@@ -1109,6 +1125,11 @@ impl Interpreter {
                         // Look up builtin module functions
                         let builtin_key = format!("{}.{}", module_name.0, field.0);
 
+                        // Package modules bind their exports under qualified names
+                        if let Some(value) = env.lookup(&Ident(builtin_key.clone())) {
+                            return Ok(value.clone());
+                        }
+
                         // Map new namespace names to existing builtin functions
                         let mapped_name = match builtin_key.as_str() {
                             // Int module
@@ -1141,6 +1162,11 @@ impl Interpreter {
                             // Float module
                             "Float.add" => "+.",
 
+                            // Json module
+                            "Json.parse" => "jsonParse",
+                            "Json.stringify" => "jsonStringify",
+                            "Json.readFile" => "jsonReadFile",
+
                             _ => {
                                 // Continue with record access
                                 let record_value = self.eval(record, env)?;
@@ -1243,6 +1269,68 @@ impl Interpreter {
                 }
             }
 
+            Expr::RecordExtend {
+                record,
+                fields,
+                span,
+            } => {
+                let record_value = self.eval(record, env)?;
+
+                match record_value {
+                    Value::Record {
+                        fields: mut record_fields,
+                    } => {
+                        for (name, expr) in fields {
+                            if record_fields.iter().any(|(fname, _)| fname == &name.0) {
+                                return Err(XsError::RuntimeError(
+                                    span.clone(),
+                                    format!("Field '{}' already exists in record", name.0),
+                                ));
+                            }
+                            let value = self.eval(expr, env)?;
+                            record_fields.push((name.0.clone(), value));
+                        }
+
+                        // Sort fields by name for consistent representation
+                        record_fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+                        Ok(Value::Record {
+                            fields: record_fields,
+                        })
+                    }
+                    _ => Err(XsError::RuntimeError(
+                        span.clone(),
+                        "Cannot extend non-record value".to_string(),
+                    )),
+                }
+            }
+
+            Expr::RecordRestrict {
+                record,
+                field,
+                span,
+            } => {
+                let record_value = self.eval(record, env)?;
+
+                match record_value {
+                    Value::Record { mut fields } => {
+                        let Some(index) = fields.iter().position(|(fname, _)| fname == &field.0)
+                        else {
+                            return Err(XsError::RuntimeError(
+                                span.clone(),
+                                format!("Field '{}' not found in record", field.0),
+                            ));
+                        };
+                        fields.remove(index);
+                        Ok(Value::Record { fields })
+                    }
+                    _ => Err(XsError::RuntimeError(
+                        span.clone(),
+                        format!("Cannot remove field '{}' from non-record value", field.0),
+                    )),
+                }
+            }
+
             Expr::LetRecIn {
                 name, value, body, ..
             } => {
@@ -1312,6 +1400,182 @@ impl Interpreter {
         }
     }
 
+    /// Apply a function value to arguments, e.g. a property to its inputs
+    pub fn apply(&mut self, func: Value, args: Vec<Value>) -> Result<Value, XsError> {
+        self.apply_values(func, args, &Span::new(0, 0))
+    }
+
+    /// Apply an evaluated function to evaluated arguments
+    fn apply_values(
+        &mut self,
+        func_val: Value,
+        arg_vals: Vec<Value>,
+        span: &Span,
+    ) -> Result<Value, XsError> {
+        match &func_val {
+            Value::Closure {
+                params,
+                body,
+                env: closure_env,
+            } => {
+                if arg_vals.len() > params.len() {
+                    return Err(XsError::RuntimeError(
+                        span.clone(),
+                        format!(
+                            "Function expects {} arguments, got {}",
+                            params.len(),
+                            arg_vals.len()
+                        ),
+                    ));
+                }
+
+                let mut new_env = closure_env.clone();
+                let applied = arg_vals.len();
+                for (param, arg_val) in params.iter().zip(arg_vals) {
+                    new_env = new_env.extend(param.clone(), arg_val);
+                }
+
+                if applied < params.len() {
+                    // Partial application
+                    Ok(Value::Closure {
+                        params: params[applied..].to_vec(),
+                        body: body.clone(),
+                        env: new_env,
+                    })
+                } else {
+                    self.eval(body, &new_env)
+                }
+            }
+            Value::RecClosure {
+                name,
+                params,
+                body,
+                env: closure_env,
+            } => {
+                if arg_vals.len() > params.len() {
+                    return Err(XsError::RuntimeError(
+                        span.clone(),
+                        format!(
+                            "Function expects {} arguments, got {}",
+                            params.len(),
+                            arg_vals.len()
+                        ),
+                    ));
+                }
+
+                let mut new_env = closure_env.clone();
+                new_env = new_env.extend(name.clone(), func_val.clone());
+                let applied = arg_vals.len();
+                for (param, arg_val) in params.iter().zip(arg_vals) {
+                    new_env = new_env.extend(param.clone(), arg_val);
+                }
+
+                if applied < params.len() {
+                    // Partial application of recursive function
+                    Ok(Value::Closure {
+                        params: params[applied..].to_vec(),
+                        body: body.clone(),
+                        env: new_env,
+                    })
+                } else {
+                    self.eval(body, &new_env)
+                }
+            }
+            Value::BuiltinFunction {
+                name,
+                arity,
+                applied_args,
+            } => {
+                let mut all_args = applied_args.clone();
+                all_args.extend(arg_vals);
+
+                if all_args.len() < *arity {
+                    // Partial application - return a new builtin with more args
+                    Ok(Value::BuiltinFunction {
+                        name: name.clone(),
+                        arity: *arity,
+                        applied_args: all_args,
+                    })
+                } else if all_args.len() == *arity {
+                    // Full application - execute the builtin
+                    self.execute_builtin(name, &all_args, span)
+                } else {
+                    Err(XsError::RuntimeError(
+                        span.clone(),
+                        format!(
+                            "{} expects {} arguments, got {}",
+                            name,
+                            arity,
+                            all_args.len()
+                        ),
+                    ))
+                }
+            }
+            _ => Err(XsError::RuntimeError(
+                span.clone(),
+                "Cannot apply non-function value".to_string(),
+            )),
+        }
+    }
+
+    /// Print a line of program output, unless the observer takes it
+    fn write_output(&self, text: String) {
+        let taken = match &self.observer {
+            Some(observer) => lock_observer(observer).output(&text),
+            None => false,
+        };
+        if !taken {
+            println!("{text}");
+        }
+    }
+
+    /// The cell of the `mut` variable `name`, which must belong to a block
+    /// still being evaluated
+    fn cell(&mut self, id: u64, name: &Ident, span: &Span) -> Result<&mut Value, XsError> {
+        self.mutable_cells
+            .iter_mut()
+            .rev()
+            .find(|(cell, _)| *cell == id)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                XsError::RuntimeError(
+                    span.clone(),
+                    format!("Mutable variable `{name}` used outside its scope"),
+                )
+            })
+    }
+
+    /// Perform a built-in effect on evaluated arguments
+    fn perform_effect(&mut self, effect: &Ident, args: &[Value]) -> Result<Value, XsError> {
+        // For simple built-in effects, handle them directly
+        match effect.0.as_str() {
+            "print" => match args.first() {
+                Some(Value::String(s)) => {
+                    self.write_output(s.clone());
+                    Ok(Value::Constructor {
+                        name: Ident("Unit".to_string()),
+                        values: vec![],
+                    })
+                }
+                Some(_) => Err(XsError::RuntimeError(
+                    Span::new(0, 0),
+                    "print expects a string argument".to_string(),
+                )),
+                None => Err(XsError::RuntimeError(
+                    Span::new(0, 0),
+                    "print expects an argument".to_string(),
+                )),
+            },
+            _ => {
+                // TODO: Implement general effect handling
+                Err(XsError::RuntimeError(
+                    Span::new(0, 0),
+                    format!("Effect '{}' not yet implemented", effect.0),
+                ))
+            }
+        }
+    }
+
     fn execute_builtin(
         &mut self,
         name: &str,
@@ -1530,6 +1794,27 @@ impl Interpreter {
                     "string-length requires a string argument".to_string(),
                 )),
             },
+            "jsonParse" => match &args[0] {
+                Value::String(s) => {
+                    json::parse(s).map_err(|e| XsError::RuntimeError(span.clone(), e))
+                }
+                _ => Err(XsError::RuntimeError(
+                    span.clone(),
+                    "Json.parse requires a string argument".to_string(),
+                )),
+            },
+            "jsonStringify" => json::stringify(&args[0])
+                .map(Value::String)
+                .map_err(|e| XsError::RuntimeError(span.clone(), e)),
+            "jsonReadFile" => match &args[0] {
+                Value::String(path) => {
+                    json::read_file(path).map_err(|e| XsError::RuntimeError(span.clone(), e))
+                }
+                _ => Err(XsError::RuntimeError(
+                    span.clone(),
+                    "Json.readFile requires a path".to_string(),
+                )),
+            },
             "str-eq" | "stringEq" => match (&args[0], &args[1]) {
                 (Value::String(s1), Value::String(s2)) => Ok(Value::Bool(s1 == s2)),
                 _ => Err(XsError::RuntimeError(
@@ -1539,7 +1824,7 @@ impl Interpreter {
             },
             "print" => {
                 let value = &args[0];
-                println!("{value}");
+                self.write_output(value.to_string());
                 Ok(value.clone())
             }
             "stringAt" => match (&args[0], &args[1]) {
@@ -1652,6 +1937,14 @@ impl Interpreter {
                     values: vec![],
                 })
             }
+            "property" => {
+                // property : String -> (a -> b) -> Unit
+                // Like `test`, the test runner generates inputs and runs it
+                Ok(Value::Constructor {
+                    name: Ident("Unit".to_string()),
+                    values: vec![],
+                })
+            }
             "assert" => {
                 // assert : Bool -> String -> Unit
                 if args.len() != 2 {
@@ -1691,7 +1984,7 @@ impl Interpreter {
                 }
                 match &args[1] {
                     Value::String(label) => {
-                        println!("[{}] {}", label, args[0]);
+                        self.write_output(format!("[{}] {}", label, args[0]));
                         Ok(args[0].clone())
                     }
                     _ => Err(XsError::RuntimeError(
@@ -1937,4 +2230,56 @@ mod tests {
         let result = interp.eval(&expr, &env).unwrap();
         assert_eq!(result, Value::Int(12));
     }
+
+    #[test]
+    fn test_eval_mut_variable() {
+        let (mut interp, env) = setup();
+        let span = || Span::new(0, 0);
+        let var = |name: &str| Expr::Ident(Ident(name.to_string()), span());
+        let int = |n: i64| Expr::Literal(Literal::Int(n), span());
+        let mutable = |value: Expr| Expr::Mut {
+            name: Ident("s".to_string()),
+            value: Box::new(value),
+            span: span(),
+        };
+
+        // { mut s = 1; s = s + 2; s }
+        let expr = Expr::Block {
+            exprs: vec![
+                mutable(int(1)),
+                Expr::Assign {
+                    name: Ident("s".to_string()),
+                    value: Box::new(Expr::Apply {
+                        func: Box::new(var("+")),
+                        args: vec![var("s"), int(2)],
+                        span: span(),
+                    }),
+                    span: span(),
+                },
+                var("s"),
+            ],
+            span: span(),
+        };
+        assert_eq!(interp.eval(&expr, &env).unwrap(), Value::Int(3));
+
+        // A closure called after its block returned has no variable to read:
+        // ({ mut s = 1; fn x -> s }) 0
+        let escaped = Expr::Apply {
+            func: Box::new(Expr::Block {
+                exprs: vec![
+                    mutable(int(1)),
+                    Expr::Lambda {
+                        params: vec![(Ident("x".to_string()), None)],
+                        body: Box::new(var("s")),
+                        span: span(),
+                    },
+                ],
+                span: span(),
+            }),
+            args: vec![int(0)],
+            span: span(),
+        };
+        let error = interp.eval(&escaped, &env).unwrap_err().to_string();
+        assert!(error.contains("used outside its scope"), "{error}");
+    }
 }
@@ -0,0 +1,635 @@
+//! Property-based testing
+//!
+//! `property "name" (fn xs:List Int -> ...)` runs its function on inputs
+//! generated from the parameter types. Generators exist for Int, Float,
+//! Bool, String, Unit, lists, options, records and user-defined types; a
+//! type variable is generated as Int. Inputs grow from small to large over
+//! the cases, and a failing input is shrunk to a minimal counterexample.
+//!
+//! Runs are deterministic: the same seed and case count give the same
+//! inputs.
+
+use std::collections::HashMap;
+use vibe_language::{Ident, Type, TypeDefinition, Value};
+
+/// How many cases to run, and from which seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyConfig {
+    pub cases: usize,
+    pub seed: u64,
+    /// Size of the inputs of the last case, e.g. the longest list
+    pub max_size: usize,
+    /// Give up shrinking after this many steps
+    pub max_shrinks: usize,
+}
+
+impl Default for PropertyConfig {
+    fn default() -> Self {
+        Self {
+            cases: 100,
+            seed: 0x5eed,
+            max_size: 30,
+            max_shrinks: 1000,
+        }
+    }
+}
+
+/// A failing input, after shrinking
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    pub values: Vec<Value>,
+    /// Why the property failed for `values`
+    pub error: String,
+    /// The case that first failed, counting from 1
+    pub case: usize,
+    pub shrinks: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyOutcome {
+    Passed { cases: usize },
+    Failed(Counterexample),
+}
+
+/// Small, seedable random number generator (SplitMix64)
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..=max`
+    pub fn below(&mut self, max: usize) -> usize {
+        (self.next_u64() % (max as u64 + 1)) as usize
+    }
+
+    /// A number in `-max..=max`
+    pub fn between(&mut self, max: i64) -> i64 {
+        (self.next_u64() % (2 * max as u64 + 1)) as i64 - max
+    }
+}
+
+/// Generates and shrinks values of a type
+pub struct Generators {
+    definitions: HashMap<String, TypeDefinition>,
+}
+
+/// Generating deeper than this means a type has no finite values
+const MAX_DEPTH: usize = 64;
+
+impl Generators {
+    /// Generators for the builtin types and the user-defined `definitions`
+    pub fn new(definitions: impl IntoIterator<Item = TypeDefinition>) -> Self {
+        Self {
+            definitions: definitions
+                .into_iter()
+                .map(|definition| (definition.name.clone(), definition))
+                .collect(),
+        }
+    }
+
+    /// A value of type `ty` of at most `size`
+    pub fn generate(&self, ty: &Type, size: usize, rng: &mut Rng) -> Result<Value, String> {
+        self.generate_at(ty, size, rng, 0)
+    }
+
+    fn generate_at(
+        &self,
+        ty: &Type,
+        size: usize,
+        rng: &mut Rng,
+        depth: usize,
+    ) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("cannot generate finite values of type {ty}"));
+        }
+        Ok(match ty {
+            Type::Int | Type::Var(_) => Value::Int(rng.between(size as i64)),
+            Type::Float => {
+                let whole = rng.between(size as i64) as f64;
+                Value::Float(whole + rng.below(99) as f64 / 100.0)
+            }
+            Type::Bool => Value::Bool(rng.below(1) == 1),
+            Type::String => {
+                let length = rng.below(size);
+                // Printable ASCII
+                let text = (0..length)
+                    .map(|_| char::from(b' ' + rng.below(94) as u8))
+                    .collect();
+                Value::String(text)
+            }
+            Type::Unit => constructor("Unit", vec![]),
+            Type::List(element) => {
+                let length = rng.below(size);
+                let items = (0..length)
+                    .map(|_| self.generate_at(element, size, rng, depth + 1))
+                    .collect::<Result<_, _>>()?;
+                Value::List(items)
+            }
+            Type::Option(inner) => {
+                if rng.below(3) == 0 {
+                    constructor("None", vec![])
+                } else {
+                    let value = self.generate_at(inner, size, rng, depth + 1)?;
+                    constructor("Some", vec![value])
+                }
+            }
+            // An open record is generated with none of the fields its row may add
+            Type::Record { fields } | Type::ExtensibleRecord { fields, .. } => Value::Record {
+                fields: fields
+                    .iter()
+                    .map(|(name, ty)| {
+                        Ok((name.clone(), self.generate_at(ty, size, rng, depth + 1)?))
+                    })
+                    .collect::<Result<_, String>>()?,
+            },
+            Type::Forall { body, .. } => self.generate_at(body, size, rng, depth)?,
+            Type::UserDefined { name, type_params } => {
+                if let Some(target) = self.alias(name, type_params) {
+                    return self.generate_at(&target, size, rng, depth + 1);
+                }
+                let constructors = self.constructors(name, type_params)?;
+                // Small values prefer constructors that do not recurse
+                let base: Vec<&(String, Vec<Type>)> = constructors
+                    .iter()
+                    .filter(|(_, fields)| !fields.iter().any(|field| mentions(field, name)))
+                    .collect();
+                let choices: Vec<&(String, Vec<Type>)> = if size == 0 && !base.is_empty() {
+                    base
+                } else {
+                    constructors.iter().collect()
+                };
+                let (constructor_name, fields) = choices[rng.below(choices.len() - 1)];
+                let values = fields
+                    .iter()
+                    .map(|field| self.generate_at(field, size / 2, rng, depth + 1))
+                    .collect::<Result<_, _>>()?;
+                constructor(constructor_name, values)
+            }
+            Type::Function(..)
+            | Type::FunctionWithEffect { .. }
+            | Type::Tuple(_)
+            | Type::Applied { .. } => {
+                return Err(format!("no generator for values of type {ty}"))
+            }
+        })
+    }
+
+    /// Simpler values of type `ty` than `value`, simplest first
+    pub fn shrink(&self, value: &Value, ty: &Type) -> Vec<Value> {
+        match (value, ty) {
+            (Value::Int(n), _) => shrink_int(*n).into_iter().map(Value::Int).collect(),
+            (Value::Float(f), _) => {
+                let mut candidates = Vec::new();
+                if *f != 0.0 {
+                    candidates.push(Value::Float(0.0));
+                }
+                if f.trunc() != *f {
+                    candidates.push(Value::Float(f.trunc()));
+                }
+                candidates
+            }
+            (Value::Bool(true), _) => vec![Value::Bool(false)],
+            (Value::String(s), _) => {
+                let chars: Vec<char> = s.chars().collect();
+                shrink_list(&chars, |c| if *c == 'a' { Vec::new() } else { vec!['a'] })
+                    .into_iter()
+                    .map(|chars| Value::String(chars.into_iter().collect()))
+                    .collect()
+            }
+            (Value::List(items), Type::List(element)) => {
+                shrink_list(items, |item| self.shrink(item, element))
+                    .into_iter()
+                    .map(Value::List)
+                    .collect()
+            }
+            (Value::Constructor { name, values }, Type::Option(inner)) => {
+                match (name.0.as_str(), values.as_slice()) {
+                    ("Some", [value]) => std::iter::once(constructor("None", vec![]))
+                        .chain(
+                            self.shrink(value, inner)
+                                .into_iter()
+                                .map(|value| constructor("Some", vec![value])),
+                        )
+                        .collect(),
+                    _ => Vec::new(),
+                }
+            }
+            (Value::Record { fields }, Type::Record { fields: types }) => {
+                let mut candidates = Vec::new();
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    let Some((_, ty)) = types.iter().find(|(field, _)| field == name) else {
+                        continue;
+                    };
+                    for smaller in self.shrink(value, ty) {
+                        let mut fields = fields.clone();
+                        fields[index].1 = smaller;
+                        candidates.push(Value::Record { fields });
+                    }
+                }
+                candidates
+            }
+            (
+                Value::Constructor { name, values },
+                Type::UserDefined {
+                    name: type_name,
+                    type_params,
+                },
+            ) => {
+                let Ok(constructors) = self.constructors(type_name, type_params) else {
+                    return Vec::new();
+                };
+                let Some((_, fields)) = constructors.iter().find(|(c, _)| *c == name.0) else {
+                    return Vec::new();
+                };
+                let mut candidates = Vec::new();
+                // Constructors without fields come first
+                for (other, other_fields) in &constructors {
+                    if other_fields.is_empty() && *other != name.0 {
+                        candidates.push(constructor(other, vec![]));
+                    }
+                }
+                // Then the parts of the value that have its type
+                for (value, field) in values.iter().zip(fields) {
+                    if field == ty {
+                        candidates.push(value.clone());
+                    }
+                }
+                for (index, (value, field)) in values.iter().zip(fields).enumerate() {
+                    for smaller in self.shrink(value, field) {
+                        let mut values = values.clone();
+                        values[index] = smaller;
+                        candidates.push(Value::Constructor {
+                            name: name.clone(),
+                            values,
+                        });
+                    }
+                }
+                candidates
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Constructors of `name` with the type parameters replaced by `args`
+    /// The expansion of `name` applied to `args` when it is a type alias
+    fn alias(&self, name: &str, args: &[Type]) -> Option<Type> {
+        let definition = self.definitions.get(name)?;
+        let target = definition.alias.as_ref()?;
+        let substitution: HashMap<&str, &Type> = definition
+            .type_params
+            .iter()
+            .map(String::as_str)
+            .zip(args)
+            .collect();
+        Some(substitute(target, &substitution))
+    }
+
+    fn constructors(&self, name: &str, args: &[Type]) -> Result<Vec<(String, Vec<Type>)>, String> {
+        let definition = self
+            .definitions
+            .get(name)
+            .ok_or_else(|| format!("no generator for the unknown type {name}"))?;
+        let substitution: HashMap<&str, &Type> = definition
+            .type_params
+            .iter()
+            .map(String::as_str)
+            .zip(args)
+            .collect();
+        Ok(definition
+            .constructors
+            .iter()
+            .map(|c| {
+                let fields = c
+                    .fields
+                    .iter()
+                    .map(|field| substitute(field, &substitution))
+                    .collect();
+                (c.name.clone(), fields)
+            })
+            .collect())
+    }
+
+    /// Run `property` on `config.cases` generated inputs for `types`. The
+    /// property fails when it returns an error or `false`.
+    pub fn check<F>(
+        &self,
+        types: &[Type],
+        config: &PropertyConfig,
+        mut property: F,
+    ) -> Result<PropertyOutcome, String>
+    where
+        F: FnMut(&[Value]) -> Result<Value, String>,
+    {
+        let mut failure = |values: &[Value]| match property(values) {
+            Ok(Value::Bool(false)) => Some("property returned false".to_string()),
+            Ok(_) => None,
+            Err(error) => Some(error),
+        };
+
+        let mut rng = Rng::new(config.seed);
+        for case in 0..config.cases {
+            let size = case * config.max_size / config.cases.max(1);
+            let values = types
+                .iter()
+                .map(|ty| self.generate(ty, size, &mut rng))
+                .collect::<Result<Vec<_>, _>>()?;
+            let Some(error) = failure(&values) else {
+                continue;
+            };
+
+            let mut counterexample = Counterexample {
+                values,
+                error,
+                case: case + 1,
+                shrinks: 0,
+            };
+            'shrinking: while counterexample.shrinks < config.max_shrinks {
+                for (index, ty) in types.iter().enumerate() {
+                    for smaller in self.shrink(&counterexample.values[index], ty) {
+                        let mut values = counterexample.values.clone();
+                        values[index] = smaller;
+                        if let Some(error) = failure(&values) {
+                            counterexample.values = values;
+                            counterexample.error = error;
+                            counterexample.shrinks += 1;
+                            continue 'shrinking;
+                        }
+                    }
+                }
+                break;
+            }
+            return Ok(PropertyOutcome::Failed(counterexample));
+        }
+        Ok(PropertyOutcome::Passed {
+            cases: config.cases,
+        })
+    }
+}
+
+fn constructor(name: &str, values: Vec<Value>) -> Value {
+    Value::Constructor {
+        name: Ident(name.to_string()),
+        values,
+    }
+}
+
+/// 0 first, then values approaching `n`
+fn shrink_int(n: i64) -> Vec<i64> {
+    let mut candidates = Vec::new();
+    if n == 0 {
+        return candidates;
+    }
+    if n < 0 && n != i64::MIN {
+        candidates.push(-n);
+    }
+    candidates.push(0);
+    let mut step = n / 2;
+    while step != 0 {
+        candidates.push(n - step);
+        step /= 2;
+    }
+    candidates.dedup();
+    candidates.retain(|&candidate| candidate != n);
+    candidates
+}
+
+/// The empty list, halves, the list without one item, and the list with one
+/// item shrunk
+fn shrink_list<T: Clone>(items: &[T], shrink: impl Fn(&T) -> Vec<T>) -> Vec<Vec<T>> {
+    let mut candidates = Vec::new();
+    if items.is_empty() {
+        return candidates;
+    }
+    candidates.push(Vec::new());
+    if items.len() > 2 {
+        let half = items.len() / 2;
+        candidates.push(items[..half].to_vec());
+        candidates.push(items[half..].to_vec());
+    }
+    if items.len() > 1 {
+        for index in 0..items.len() {
+            let mut fewer = items.to_vec();
+            fewer.remove(index);
+            candidates.push(fewer);
+        }
+    }
+    for (index, item) in items.iter().enumerate() {
+        for smaller in shrink(item) {
+            let mut items = items.to_vec();
+            items[index] = smaller;
+            candidates.push(items);
+        }
+    }
+    candidates
+}
+
+/// Whether `ty` refers to the type `name`
+fn mentions(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::UserDefined {
+            name: other,
+            type_params,
+        } => other == name || type_params.iter().any(|param| mentions(param, name)),
+        Type::List(inner) | Type::Option(inner) => mentions(inner, name),
+        Type::Record { fields } | Type::ExtensibleRecord { fields, .. } => {
+            fields.iter().any(|(_, ty)| mentions(ty, name))
+        }
+        Type::Tuple(types) | Type::Applied { args: types, .. } => {
+            types.iter().any(|ty| mentions(ty, name))
+        }
+        Type::Forall { body, .. } => mentions(body, name),
+        Type::Function(from, to) | Type::FunctionWithEffect { from, to, .. } => {
+            mentions(from, name) || mentions(to, name)
+        }
+        _ => false,
+    }
+}
+
+fn substitute(ty: &Type, substitution: &HashMap<&str, &Type>) -> Type {
+    match ty {
+        Type::Var(name) => substitution
+            .get(name.as_str())
+            .map(|ty| (*ty).clone())
+            .unwrap_or_else(|| ty.clone()),
+        Type::List(inner) => Type::List(Box::new(substitute(inner, substitution))),
+        Type::Option(inner) => Type::Option(Box::new(substitute(inner, substitution))),
+        Type::Record { fields } => Type::Record {
+            fields: fields
+                .iter()
+                .map(|(name, ty)| (name.clone(), substitute(ty, substitution)))
+                .collect(),
+        },
+        Type::Tuple(types) => Type::Tuple(
+            types
+                .iter()
+                .map(|ty| substitute(ty, substitution))
+                .collect(),
+        ),
+        Type::UserDefined { name, type_params } => Type::UserDefined {
+            name: name.clone(),
+            type_params: type_params
+                .iter()
+                .map(|ty| substitute(ty, substitution))
+                .collect(),
+        },
+        Type::Function(from, to) => Type::Function(
+            Box::new(substitute(from, substitution)),
+            Box::new(substitute(to, substitution)),
+        ),
+        Type::ExtensibleRecord { fields, row } => {
+            let fields = fields
+                .iter()
+                .map(|(name, ty)| (name.clone(), substitute(ty, substitution)))
+                .collect();
+            let rest = substitute(&Type::Var(row.clone()), substitution);
+            Type::extend_record(fields, rest).unwrap_or_else(|| ty.clone())
+        }
+        Type::Applied { constructor, args } => {
+            let args = args
+                .iter()
+                .map(|ty| substitute(ty, substitution))
+                .collect();
+            let constructor = substitute(&Type::Var(constructor.clone()), substitution);
+            Type::apply_constructor(constructor, args).unwrap_or_else(|| ty.clone())
+        }
+        _ => ty.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vibe_language::Constructor;
+
+    fn tree() -> TypeDefinition {
+        let tree = Type::UserDefined {
+            name: "Tree".to_string(),
+            type_params: vec![Type::Var("a".to_string())],
+        };
+        TypeDefinition {
+            name: "Tree".to_string(),
+            type_params: vec!["a".to_string()],
+            constructors: vec![
+                Constructor {
+                    name: "Leaf".to_string(),
+                    fields: vec![],
+                },
+                Constructor {
+                    name: "Node".to_string(),
+                    fields: vec![tree.clone(), Type::Var("a".to_string()), tree],
+                },
+            ],
+            alias: None,
+        }
+    }
+
+    #[test]
+    fn test_generated_values_have_the_requested_type() {
+        let generators = Generators::new([tree()]);
+        let mut rng = Rng::new(7);
+        let record = Type::Record {
+            fields: vec![
+                ("name".to_string(), Type::String),
+                (
+                    "tags".to_string(),
+                    Type::List(Box::new(Type::Option(Box::new(Type::Bool)))),
+                ),
+            ],
+        };
+        for size in 0..20 {
+            let Value::Record { fields } = generators.generate(&record, size, &mut rng).unwrap()
+            else {
+                panic!("expected a record");
+            };
+            assert!(matches!(&fields[0].1, Value::String(s) if s.len() <= size));
+            assert!(matches!(&fields[1].1, Value::List(items) if items.len() <= size));
+        }
+
+        let int_tree = Type::UserDefined {
+            name: "Tree".to_string(),
+            type_params: vec![Type::Int],
+        };
+        let leaf = generators.generate(&int_tree, 0, &mut rng).unwrap();
+        assert_eq!(leaf, constructor("Leaf", vec![]));
+        assert!(generators
+            .generate(
+                &Type::Function(Box::new(Type::Int), Box::new(Type::Int)),
+                3,
+                &mut rng
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_failures_shrink_to_a_minimal_counterexample() {
+        let generators = Generators::new([]);
+        let config = PropertyConfig::default();
+
+        // No list has three or more items
+        let ints = Type::List(Box::new(Type::Int));
+        let outcome = generators
+            .check(&[ints], &config, |values| match &values[0] {
+                Value::List(items) => Ok(Value::Bool(items.len() < 3)),
+                _ => Err("not a list".to_string()),
+            })
+            .unwrap();
+        let PropertyOutcome::Failed(counterexample) = outcome else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(
+            counterexample.values,
+            vec![Value::List(vec![Value::Int(0); 3])]
+        );
+        assert_eq!(counterexample.error, "property returned false");
+
+        // Every number is below 10
+        let outcome = generators
+            .check(&[Type::Int], &config, |values| match values[0] {
+                Value::Int(n) if n >= 10 => Err(format!("{n} is too big")),
+                _ => Ok(Value::Bool(true)),
+            })
+            .unwrap();
+        let PropertyOutcome::Failed(counterexample) = outcome else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(counterexample.values, vec![Value::Int(10)]);
+        assert_eq!(counterexample.error, "10 is too big");
+    }
+
+    #[test]
+    fn test_runs_are_reproducible_from_the_seed() {
+        let generators = Generators::new([]);
+        let config = PropertyConfig {
+            cases: 20,
+            ..PropertyConfig::default()
+        };
+        let inputs = |seed| {
+            let mut seen = Vec::new();
+            generators
+                .check(
+                    &[Type::String],
+                    &PropertyConfig { seed, ..config },
+                    |values| {
+                        seen.push(values[0].clone());
+                        Ok(Value::Bool(true))
+                    },
+                )
+                .unwrap();
+            seen
+        };
+        assert_eq!(inputs(1), inputs(1));
+        assert_ne!(inputs(1), inputs(2));
+        assert_eq!(inputs(1).len(), 20);
+    }
+}